@@ -63,12 +63,23 @@ public class ElectricalProfileSetManager extends APIClient {
      * Return the electrical profile set corresponding to the given id, in a ready-to-use format.
      */
     public ElectricalProfileMapping getProfileMap(String profileSetId) {
+        return getProfileMap(profileSetId, null);
+    }
+
+    /**
+     * Return the given version of an electrical profile set, in a ready-to-use format. The set is
+     * downloaded again if another version was cached. A null version accepts any cached version.
+     */
+    public ElectricalProfileMapping getProfileMap(String profileSetId, Long version) {
         if (profileSetId == null) {
             return null;
         }
 
-        cache.putIfAbsent(profileSetId, new CacheEntry(null));
-        var cacheEntry = cache.get(profileSetId);
+        var cacheEntry = cache.compute(profileSetId, (id, entry) -> {
+            if (entry == null || (version != null && !version.equals(entry.version)))
+                return new CacheEntry(null, version);
+            return entry;
+        });
 
         synchronized (cacheEntry) {
             if (cacheEntry.status == CacheStatus.CACHED) return cacheEntry.mapping;
@@ -89,9 +100,11 @@ public class ElectricalProfileSetManager extends APIClient {
     protected static class CacheEntry {
         protected CacheStatus status;
         private ElectricalProfileMapping mapping;
+        private final Long version;
 
-        CacheEntry(ElectricalProfileMapping mapping) {
+        CacheEntry(ElectricalProfileMapping mapping, Long version) {
             this.mapping = mapping;
+            this.version = version;
             this.status = CacheStatus.INITIALIZING;
         }
 
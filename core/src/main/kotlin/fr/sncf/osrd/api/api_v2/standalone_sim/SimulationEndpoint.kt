@@ -42,7 +42,10 @@ class SimulationEndpoint(
 
             // load electrical profile set
             val electricalProfileMap =
-                electricalProfileSetManager.getProfileMap(request.electricalProfileSetId)
+                electricalProfileSetManager.getProfileMap(
+                    request.electricalProfileSetId,
+                    request.electricalProfileSetVersion
+                )
 
             // Parse rolling stocks
             val rollingStock = parseRawRollingStock(request.rollingStock)
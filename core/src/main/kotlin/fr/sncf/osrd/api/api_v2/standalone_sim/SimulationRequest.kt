@@ -30,7 +30,8 @@ class SimulationRequest(
     @Json(name = "power_restrictions") val powerRestrictions: List<SimulationPowerRestrictionItem>,
     val options: TrainScheduleOptions,
     @Json(name = "rolling_stock") val rollingStock: PhysicsRollingStockModel,
-    @Json(name = "electrical_profile_set_id") val electricalProfileSetId: String?
+    @Json(name = "electrical_profile_set_id") val electricalProfileSetId: String?,
+    @Json(name = "electrical_profile_set_version") val electricalProfileSetVersion: Long?
 ) {
     companion object {
         val adapter: JsonAdapter<SimulationRequest> =
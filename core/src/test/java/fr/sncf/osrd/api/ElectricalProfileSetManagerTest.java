@@ -21,6 +21,18 @@ public class ElectricalProfileSetManagerTest extends ApiTest {
         verifyProfileMap(profileMap);
     }
 
+    @Test
+    public void testReloadEditedSet() {
+        var testID = "small_infra/external_generated_inputs.json";
+        var profileMap = electricalProfileSetManager.getProfileMap(testID, 1L);
+        assert electricalProfileSetManager.getProfileMap(testID, 1L) == profileMap;
+        assert electricalProfileSetManager.getProfileMap(testID) == profileMap;
+
+        var editedProfileMap = electricalProfileSetManager.getProfileMap(testID, 2L);
+        assert editedProfileMap != profileMap;
+        verifyProfileMap(editedProfileMap);
+    }
+
     @Test
     public void testSoftError() {
         assertThatThrownBy(() -> electricalProfileSetManager.getProfileMap("small_infra/invalid.json"))
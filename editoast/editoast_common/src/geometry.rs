@@ -76,7 +76,7 @@ pub enum GeoJsonMultiPolygon {
 
 #[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
 #[allow(unused)]
-pub struct GeoJsonPointValue(#[schema(min_items = 2, max_items = 2)] Vec<f64>);
+pub struct GeoJsonPointValue(#[schema(min_items = 2, max_items = 2)] pub Vec<f64>);
 
 #[derive(Serialize, ToSchema)]
 #[allow(unused)]
@@ -84,7 +84,7 @@ pub struct GeoJsonMultiPointValue(#[schema(min_items = 1)] Vec<GeoJsonPointValue
 
 #[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
 #[allow(unused)]
-pub struct GeoJsonLineStringValue(#[schema(min_items = 2)] Vec<GeoJsonPointValue>);
+pub struct GeoJsonLineStringValue(#[schema(min_items = 2)] pub Vec<GeoJsonPointValue>);
 
 #[derive(Serialize, ToSchema)]
 #[allow(unused)]
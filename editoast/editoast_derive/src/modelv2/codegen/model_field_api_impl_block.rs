@@ -35,6 +35,12 @@ impl ToTokens for ModelFieldApiImplBlock {
                     crate::modelsv2::prelude::FilterSetting::new(#column.eq_any(values))
                 }
 
+                pub fn ne_all(&self, values: Vec<#ty>) -> crate::modelsv2::prelude::FilterSetting<#model> {
+                    use diesel::ExpressionMethods;
+                    #map_transform;
+                    crate::modelsv2::prelude::FilterSetting::new(#column.ne_all(values))
+                }
+
                 pub fn asc(&self) -> crate::modelsv2::prelude::SortSetting<#model> {
                     use diesel::ExpressionMethods;
                     crate::modelsv2::prelude::SortSetting(Box::new(#column.asc()))
@@ -80,6 +80,7 @@ enum ColumnType {
     TextualSearchString,
     Boolean,
     Null,
+    Geometry,
     Sequence(Box<ColumnType>),
 }
 
@@ -115,6 +116,7 @@ impl ColumnType {
             }
             "boolean" | "bool" => Some(ColumnType::Boolean),
             "null" => Some(ColumnType::Null),
+            "geometry" => Some(ColumnType::Geometry),
             // handles VARCHAR(240), NUMERIC(4, 2), etc.
             prefix if prefix.contains('(') => {
                 let (prefix, _) = prefix.split_once('(').unwrap();
@@ -147,6 +149,9 @@ impl ColumnType {
             ColumnType::Null => {
                 quote! { crate::views::search::TypeSpec::Type(crate::views::search::AstType::Null) }
             }
+            ColumnType::Geometry => {
+                quote! { crate::views::search::TypeSpec::Type(crate::views::search::AstType::Geometry) }
+            }
             ColumnType::Sequence(ct) => {
                 let ts = ct.to_type_spec();
                 quote! { crate::views::search::TypeSpec::Sequence(Box::new(#ts)) }
@@ -157,6 +162,7 @@ impl ColumnType {
     fn index(&self) -> TokenStream {
         match self {
             ColumnType::TextualSearchString => quote! { crate::views::search::Index::GinTrgm },
+            ColumnType::Geometry => quote! { crate::views::search::Index::Gist },
             _ => quote! { crate::views::search::Index::Default },
         }
     }
@@ -12,6 +12,7 @@ mod loading_gauge_limit;
 mod neutral_section;
 mod operational_point;
 mod railjson;
+mod railjson_migration;
 mod route;
 mod side;
 mod sign;
@@ -51,6 +52,12 @@ pub use operational_point::OperationalPointIdentifierExtension;
 pub use operational_point::OperationalPointPart;
 pub use railjson::RailJson;
 pub use railjson::RAILJSON_VERSION;
+pub use railjson_migration::migrate_railjson;
+pub use railjson_migration::LossyChange;
+pub use railjson_migration::RailJsonMigrationError;
+pub use railjson_migration::RailJsonMigrationReport;
+pub use railjson_migration::RailJsonMigrator;
+pub use railjson_migration::RAILJSON_COLLECTIONS;
 pub use route::Route;
 pub use route::RoutePath;
 pub use side::Side;
@@ -106,4 +113,5 @@ editoast_common::schemas! {
     track_offset::schemas(),
     track_section::schemas(),
     railjson::schemas(),
+    railjson_migration::schemas(),
 }
@@ -21,7 +21,7 @@ pub struct ElectricalProfile {
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone, ToSchema)]
-pub struct LevelValues(Vec<String>);
+pub struct LevelValues(pub Vec<String>);
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone, ToSchema)]
 pub struct ElectricalProfileSetData {
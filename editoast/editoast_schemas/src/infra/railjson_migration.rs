@@ -0,0 +1,589 @@
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::json;
+use serde_json::Map;
+use serde_json::Value as JsonValue;
+use thiserror::Error;
+use utoipa::ToSchema;
+
+use super::builtin_node_types_list;
+use super::Link;
+use super::SwitchType;
+use super::RAILJSON_VERSION;
+
+editoast_common::schemas! {
+    RailJsonMigrationReport,
+    LossyChange,
+}
+
+/// The collections of objects of a RailJSON document in the current version
+pub const RAILJSON_COLLECTIONS: [&str; 11] = [
+    "track_sections",
+    "buffer_stops",
+    "electrifications",
+    "detectors",
+    "operational_points",
+    "routes",
+    "signals",
+    "switches",
+    "speed_sections",
+    "extended_switch_types",
+    "neutral_sections",
+];
+
+#[derive(Debug, Error, PartialEq)]
+pub enum RailJsonMigrationError {
+    #[error("No migration leads from railjson version '{version}' to '{RAILJSON_VERSION}'")]
+    UnknownVersion { version: String },
+    #[error("Invalid railjson document: {reason}")]
+    InvalidDocument { reason: String },
+}
+
+/// What a migration did to the objects of a document
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct RailJsonMigrationReport {
+    /// The version of the original document
+    pub from_version: String,
+    /// The version the document was migrated to
+    pub to_version: String,
+    /// The changes which lost information of the original document
+    pub lossy_changes: Vec<LossyChange>,
+}
+
+/// A piece of information lost while migrating an object
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct LossyChange {
+    /// The version introduced by the migration which made the change
+    pub version: String,
+    /// The collection of the object in the original document
+    pub collection: String,
+    /// The id of the object
+    pub obj_id: String,
+    pub description: String,
+}
+
+/// What becomes of an object after a migration
+enum Outcome {
+    /// The object stays in its collection
+    Keep,
+    /// The object now belongs to another collection
+    Move(&'static str),
+    /// The object has no equivalent anymore
+    Drop,
+}
+
+/// A migration of RailJSON objects from a format version to the next one
+///
+/// `migrate` upgrades an object of a collection (named as in the `from` version)
+/// and describes the information it loses in `losses`.
+struct Migration {
+    from: &'static str,
+    to: &'static str,
+    migrate: fn(collection: &str, object: &mut JsonValue, losses: &mut Vec<String>) -> Outcome,
+}
+
+/// The chain of migrations up to [RAILJSON_VERSION], oldest first
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: "3.4.1",
+        to: "3.4.2",
+        migrate: lpv_to_psl,
+    },
+    Migration {
+        from: "3.4.2",
+        to: "3.4.3",
+        migrate: add_kilometric_points,
+    },
+    Migration {
+        from: "3.4.3",
+        to: "3.4.4",
+        migrate: track_links_to_switches,
+    },
+    Migration {
+        from: "3.4.4",
+        to: "3.4.5",
+        migrate: remove_legacy_signal_fields,
+    },
+    Migration {
+        from: "3.4.5",
+        to: "3.4.6",
+        migrate: extend_switch_types,
+    },
+    Migration {
+        from: "3.4.6",
+        to: "3.4.7",
+        migrate: remove_detector_directions,
+    },
+    Migration {
+        from: "3.4.7",
+        to: "3.4.8",
+        migrate: split_tvm,
+    },
+    Migration {
+        from: "3.4.8",
+        to: "3.4.9",
+        migrate: orient_psl_signs,
+    },
+    Migration {
+        from: "3.4.9",
+        to: "3.4.10",
+        migrate: |_, _, _| Outcome::Keep,
+    },
+    Migration {
+        from: "3.4.10",
+        to: "3.4.11",
+        migrate: add_signal_parameters,
+    },
+    Migration {
+        from: "3.4.11",
+        to: "3.4.12",
+        migrate: remove_schematic,
+    },
+];
+
+/// Upgrades the objects of a RailJSON document from an older format version to [RAILJSON_VERSION]
+///
+/// Objects are migrated one at a time, so that a document does not have to be loaded
+/// entirely in memory to be migrated. See [migrate_railjson] to migrate a whole document.
+pub struct RailJsonMigrator {
+    migrations: &'static [Migration],
+    report: RailJsonMigrationReport,
+}
+
+impl RailJsonMigrator {
+    /// Prepares the migration of a document in the given version
+    pub fn new(version: &str) -> Result<Self, RailJsonMigrationError> {
+        let start = if version == RAILJSON_VERSION {
+            MIGRATIONS.len()
+        } else {
+            MIGRATIONS
+                .iter()
+                .position(|migration| migration.from == version)
+                .ok_or_else(|| RailJsonMigrationError::UnknownVersion {
+                    version: version.to_owned(),
+                })?
+        };
+        Ok(Self {
+            migrations: &MIGRATIONS[start..],
+            report: RailJsonMigrationReport {
+                from_version: version.to_owned(),
+                to_version: RAILJSON_VERSION.to_owned(),
+                lossy_changes: vec![],
+            },
+        })
+    }
+
+    /// Whether the document is already in the current version
+    pub fn is_noop(&self) -> bool {
+        self.migrations.is_empty()
+    }
+
+    /// Upgrades an object of the given collection of the original document
+    ///
+    /// Returns the collection the object belongs to in the current version,
+    /// or `None` if it has no equivalent anymore.
+    pub fn migrate_object(&mut self, collection: &str, object: &mut JsonValue) -> Option<String> {
+        let obj_id = object
+            .get("id")
+            .and_then(JsonValue::as_str)
+            .unwrap_or_default()
+            .to_owned();
+        let mut current_collection = collection;
+        for migration in self.migrations {
+            let mut losses = vec![];
+            let outcome = (migration.migrate)(current_collection, object, &mut losses);
+            self.report
+                .lossy_changes
+                .extend(losses.into_iter().map(|description| LossyChange {
+                    version: migration.to.to_owned(),
+                    collection: collection.to_owned(),
+                    obj_id: obj_id.clone(),
+                    description,
+                }));
+            match outcome {
+                Outcome::Keep => (),
+                Outcome::Move(new_collection) => current_collection = new_collection,
+                Outcome::Drop => return None,
+            }
+        }
+        Some(current_collection.to_owned())
+    }
+
+    pub fn report(&self) -> &RailJsonMigrationReport {
+        &self.report
+    }
+
+    pub fn into_report(self) -> RailJsonMigrationReport {
+        self.report
+    }
+}
+
+/// Upgrades a whole RailJSON document to [RAILJSON_VERSION]
+pub fn migrate_railjson(
+    document: &mut JsonValue,
+) -> Result<RailJsonMigrationReport, RailJsonMigrationError> {
+    let invalid = |reason: &str| RailJsonMigrationError::InvalidDocument {
+        reason: reason.to_owned(),
+    };
+    let fields = document
+        .as_object_mut()
+        .ok_or_else(|| invalid("the document is not an object"))?;
+    let version = fields
+        .remove("version")
+        .ok_or_else(|| invalid("missing field `version`"))?;
+    let version = version
+        .as_str()
+        .ok_or_else(|| invalid("the version is not a string"))?;
+    let mut migrator = RailJsonMigrator::new(version)?;
+
+    let mut migrated: Map<String, JsonValue> = RAILJSON_COLLECTIONS
+        .iter()
+        .map(|collection| (collection.to_string(), json!([])))
+        .collect();
+    for (collection, objects) in std::mem::take(fields) {
+        let JsonValue::Array(objects) = objects else {
+            return Err(invalid(&format!("`{collection}` is not an array")));
+        };
+        for mut object in objects {
+            let Some(new_collection) = migrator.migrate_object(&collection, &mut object) else {
+                continue;
+            };
+            let Some(JsonValue::Array(objects)) = migrated.get_mut(&new_collection) else {
+                return Err(invalid(&format!("unknown field `{collection}`")));
+            };
+            objects.push(object);
+        }
+    }
+    migrated.insert("version".to_owned(), json!(RAILJSON_VERSION));
+    *fields = migrated;
+    Ok(migrator.into_report())
+}
+
+fn remove_field(object: &mut JsonValue, path: &str, losses: &mut Vec<String>) {
+    let (parent, field) = match path.rsplit_once('/') {
+        Some((parent, field)) => (object.pointer_mut(parent), field),
+        None => (Some(object), path),
+    };
+    let removed = parent
+        .and_then(JsonValue::as_object_mut)
+        .and_then(|parent| parent.remove(field));
+    if removed.is_some() {
+        losses.push(format!("field `{}` was removed", path.replace('/', ".")));
+    }
+}
+
+fn set_default(object: Option<&mut JsonValue>, field: &str, value: JsonValue) {
+    if let Some(object) = object.and_then(JsonValue::as_object_mut) {
+        object.entry(field).or_insert(value);
+    }
+}
+
+/// Applies a function to the PSL signs of a speed section
+fn for_each_psl_sign(speed_section: &mut JsonValue, mut f: impl FnMut(&mut JsonValue)) {
+    let Some(psl) = speed_section.pointer_mut("/extensions/psl_sncf") else {
+        return;
+    };
+    if let Some(z) = psl.get_mut("z") {
+        f(z);
+    }
+    for signs in ["announcement", "r"] {
+        if let Some(JsonValue::Array(signs)) = psl.get_mut(signs) {
+            signs.iter_mut().for_each(&mut f);
+        }
+    }
+}
+
+fn lpv_to_psl(collection: &str, object: &mut JsonValue, _: &mut Vec<String>) -> Outcome {
+    if collection == "speed_sections" {
+        if let Some(extensions) = object
+            .get_mut("extensions")
+            .and_then(JsonValue::as_object_mut)
+        {
+            if let Some(lpv) = extensions.remove("lpv_sncf") {
+                extensions.insert("psl_sncf".to_owned(), lpv);
+            }
+        }
+    }
+    Outcome::Keep
+}
+
+fn add_kilometric_points(collection: &str, object: &mut JsonValue, _: &mut Vec<String>) -> Outcome {
+    match collection {
+        "signals" | "buffer_stops" | "detectors" | "operational_points" => {
+            set_default(object.pointer_mut("/extensions/sncf"), "kp", json!(""));
+        }
+        "speed_sections" => {
+            for_each_psl_sign(object, |sign| set_default(Some(sign), "kp", json!("")))
+        }
+        _ => (),
+    }
+    Outcome::Keep
+}
+
+fn track_links_to_switches(
+    collection: &str,
+    object: &mut JsonValue,
+    losses: &mut Vec<String>,
+) -> Outcome {
+    if collection != "track_section_links" {
+        return Outcome::Keep;
+    }
+    let Some(link) = object.as_object_mut() else {
+        return Outcome::Keep;
+    };
+    let id = link.remove("id").unwrap_or_default();
+    let src = link.remove("src").unwrap_or_default();
+    let dst = link.remove("dst").unwrap_or_default();
+    losses.extend(
+        link.keys()
+            .map(|field| format!("field `{field}` of the track section link was removed")),
+    );
+    let link_type: SwitchType = Link.into();
+    *object = json!({
+        "id": id,
+        "switch_type": link_type.id,
+        "group_change_delay": 0.,
+        "ports": {
+            Link::A: src,
+            Link::B: dst,
+        },
+    });
+    Outcome::Move("switches")
+}
+
+fn remove_legacy_signal_fields(
+    collection: &str,
+    object: &mut JsonValue,
+    losses: &mut Vec<String>,
+) -> Outcome {
+    if collection == "signals" {
+        remove_field(object, "linked_detector", losses);
+        for field in [
+            "aspects",
+            "comment",
+            "default_aspect",
+            "installation_type",
+            "is_in_service",
+            "is_lightable",
+            "is_operational",
+            "support_type",
+            "type_code",
+            "value",
+        ] {
+            remove_field(object, &format!("/extensions/sncf/{field}"), losses);
+        }
+    }
+    Outcome::Keep
+}
+
+fn extend_switch_types(collection: &str, object: &mut JsonValue, _: &mut Vec<String>) -> Outcome {
+    if collection != "switch_types" {
+        return Outcome::Keep;
+    }
+    let id = object.get("id").and_then(JsonValue::as_str);
+    // Builtin switch types are not part of the documents anymore
+    if builtin_node_types_list()
+        .iter()
+        .any(|switch_type| Some(switch_type.id.as_str()) == id)
+    {
+        Outcome::Drop
+    } else {
+        Outcome::Move("extended_switch_types")
+    }
+}
+
+fn remove_detector_directions(
+    collection: &str,
+    object: &mut JsonValue,
+    losses: &mut Vec<String>,
+) -> Outcome {
+    if collection == "detectors" {
+        remove_field(object, "applicable_directions", losses);
+    }
+    Outcome::Keep
+}
+
+fn split_tvm(collection: &str, object: &mut JsonValue, losses: &mut Vec<String>) -> Outcome {
+    if collection != "signals" {
+        return Outcome::Keep;
+    }
+    let Some(JsonValue::Array(logical_signals)) = object.get_mut("logical_signals") else {
+        return Outcome::Keep;
+    };
+    for logical_signal in logical_signals {
+        if logical_signal["signaling_system"] != "TVM" {
+            continue;
+        }
+        let settings = logical_signal
+            .get("settings")
+            .and_then(JsonValue::as_object);
+        let is_430 = settings.and_then(|settings| settings.get("is_430")) == Some(&json!("true"));
+        let has_other_settings = settings
+            .map(|settings| settings.keys().any(|key| key != "is_430"))
+            .unwrap_or_default();
+        let has_next_systems = logical_signal
+            .get("next_signaling_systems")
+            .and_then(JsonValue::as_array)
+            .map(|systems| !systems.is_empty())
+            .unwrap_or_default();
+        let signaling_system = if is_430 { "TVM430" } else { "TVM300" };
+        if has_other_settings || has_next_systems {
+            losses.push(format!(
+                "the settings and next signaling systems of the TVM logical signal were reset when converting it to {signaling_system}"
+            ));
+        }
+        *logical_signal = json!({
+            "signaling_system": signaling_system,
+            "settings": { "Nf": "true" },
+            "next_signaling_systems": [],
+        });
+    }
+    Outcome::Keep
+}
+
+fn orient_psl_signs(collection: &str, object: &mut JsonValue, losses: &mut Vec<String>) -> Outcome {
+    if collection == "speed_sections" {
+        for_each_psl_sign(object, |sign| {
+            for angle in ["angle_geo", "angle_sch"] {
+                if let Some(sign) = sign.as_object_mut() {
+                    if sign.remove(angle).is_some() {
+                        losses.push(format!("field `{angle}` of a PSL sign was removed"));
+                    }
+                }
+            }
+            set_default(Some(sign), "direction", json!("START_TO_STOP"));
+        });
+    }
+    Outcome::Keep
+}
+
+fn add_signal_parameters(collection: &str, object: &mut JsonValue, _: &mut Vec<String>) -> Outcome {
+    if collection != "signals" {
+        return Outcome::Keep;
+    }
+    let Some(JsonValue::Array(logical_signals)) = object.get_mut("logical_signals") else {
+        return Outcome::Keep;
+    };
+    for logical_signal in logical_signals {
+        let default_parameters = if logical_signal["signaling_system"] == "BAL" {
+            json!({ "jaune_cli": "false" })
+        } else {
+            json!({})
+        };
+        set_default(
+            Some(logical_signal),
+            "default_parameters",
+            default_parameters,
+        );
+        set_default(Some(logical_signal), "conditional_parameters", json!([]));
+    }
+    Outcome::Keep
+}
+
+fn remove_schematic(collection: &str, object: &mut JsonValue, losses: &mut Vec<String>) -> Outcome {
+    if collection == "track_sections" {
+        remove_field(object, "sch", losses);
+    }
+    Outcome::Keep
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infra::RailJson;
+    use crate::infra::Switch;
+
+    #[test]
+    fn migrations_chain_up_to_the_current_version() {
+        for migrations in MIGRATIONS.windows(2) {
+            assert_eq!(migrations[0].to, migrations[1].from);
+        }
+        assert_eq!(MIGRATIONS.last().unwrap().to, RAILJSON_VERSION);
+    }
+
+    #[test]
+    fn current_version_is_not_migrated() {
+        let mut document = serde_json::to_value(RailJson::default()).unwrap();
+        let original = document.clone();
+
+        let report = migrate_railjson(&mut document).unwrap();
+
+        assert_eq!(document, original);
+        assert!(report.lossy_changes.is_empty());
+    }
+
+    #[test]
+    fn unknown_version_is_rejected() {
+        let mut document = json!({ "version": "2.0.0" });
+        assert_eq!(
+            migrate_railjson(&mut document),
+            Err(RailJsonMigrationError::UnknownVersion {
+                version: "2.0.0".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn old_document_is_migrated() {
+        let mut document = json!({
+            "version": "3.4.3",
+            "track_sections": [{
+                "id": "T1",
+                "sch": { "type": "LineString", "coordinates": [[0., 0.], [1., 1.]] },
+            }],
+            "track_section_links": [{
+                "id": "L1",
+                "src": { "track": "T1", "endpoint": "END" },
+                "dst": { "track": "T2", "endpoint": "BEGIN" },
+            }],
+            "switch_types": [
+                { "id": "point_switch" },
+                { "id": "custom" },
+            ],
+            "signals": [{
+                "id": "S1",
+                "linked_detector": "D1",
+                "logical_signals": [
+                    { "signaling_system": "BAL", "next_signaling_systems": [], "settings": {} },
+                    { "signaling_system": "TVM", "next_signaling_systems": [], "settings": { "is_430": "true" } },
+                ],
+            }],
+        });
+
+        let report = migrate_railjson(&mut document).unwrap();
+
+        assert_eq!(document["version"], RAILJSON_VERSION);
+        assert_eq!(document["track_sections"], json!([{ "id": "T1" }]));
+        assert_eq!(
+            document["extended_switch_types"],
+            json!([{ "id": "custom" }])
+        );
+        let switches: Vec<Switch> = serde_json::from_value(document["switches"].clone()).unwrap();
+        assert_eq!(switches[0].switch_type.as_str(), "link");
+        assert_eq!(document["switches"][0]["ports"]["B"]["track"], "T2");
+        let signal = &document["signals"][0];
+        assert!(signal.get("linked_detector").is_none());
+        assert_eq!(
+            signal["logical_signals"][0]["default_parameters"]["jaune_cli"],
+            "false"
+        );
+        assert_eq!(signal["logical_signals"][1]["signaling_system"], "TVM430");
+        assert_eq!(document["neutral_sections"], json!([]));
+
+        assert_eq!(report.from_version, "3.4.3");
+        let lossy_changes: Vec<_> = report
+            .lossy_changes
+            .iter()
+            .map(|change| (change.version.as_str(), change.obj_id.as_str()))
+            .collect();
+        assert_eq!(lossy_changes, vec![("3.4.5", "S1"), ("3.4.12", "T1")]);
+    }
+
+    #[test]
+    fn migrator_drops_builtin_switch_types() {
+        let mut migrator = RailJsonMigrator::new("3.4.5").unwrap();
+        let mut builtin = json!({ "id": "crossing" });
+        assert_eq!(migrator.migrate_object("switch_types", &mut builtin), None);
+        assert!(migrator.report().lossy_changes.is_empty());
+    }
+}
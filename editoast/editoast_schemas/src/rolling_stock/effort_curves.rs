@@ -20,7 +20,7 @@ editoast_common::schemas! {
 #[serde(deny_unknown_fields)]
 pub struct EffortCurves {
     pub modes: BTreeMap<String, ModeEffortCurves>,
-    default_mode: String,
+    pub default_mode: String,
 }
 
 impl EffortCurves {
@@ -36,27 +36,27 @@ impl EffortCurves {
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize, ToSchema, Hash)]
 #[serde(deny_unknown_fields)]
 pub struct ModeEffortCurves {
-    curves: Vec<ConditionalEffortCurve>,
-    default_curve: EffortCurve,
+    pub curves: Vec<ConditionalEffortCurve>,
+    pub default_curve: EffortCurve,
     pub is_electric: bool,
 }
 
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize, ToSchema, Hash)]
 #[serde(deny_unknown_fields)]
 pub struct ConditionalEffortCurve {
-    cond: EffortCurveConditions,
-    curve: EffortCurve,
+    pub cond: EffortCurveConditions,
+    pub curve: EffortCurve,
 }
 
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize, ToSchema, Hash)]
 #[serde(deny_unknown_fields)]
 pub struct EffortCurveConditions {
     #[schema(required)]
-    comfort: Option<RollingStockComfortType>,
+    pub comfort: Option<RollingStockComfortType>,
     #[schema(required)]
-    electrical_profile_level: Option<String>,
+    pub electrical_profile_level: Option<String>,
     #[schema(required)]
-    power_restriction_code: Option<String>,
+    pub power_restriction_code: Option<String>,
 }
 
 /// Train comfort that will be used for the simulation
@@ -96,43 +96,48 @@ impl<'de> Deserialize<'de> for EffortCurve {
         }
 
         let inner = InnerParams::deserialize(deserializer)?;
+        EffortCurve::new(inner.speeds, inner.max_efforts).map_err(serde::de::Error::custom)
+    }
+}
 
-        // Validate the curve
-        if inner.max_efforts.len() != inner.speeds.len() {
-            return Err(serde::de::Error::custom(
+impl EffortCurve {
+    /// Builds a curve, checking its points are valid
+    pub fn new(speeds: Vec<f64>, max_efforts: Vec<f64>) -> Result<Self, &'static str> {
+        if max_efforts.len() != speeds.len() {
+            return Err(
                 "effort curve invalid, max_efforts and speeds arrays should have the same length",
-            ));
+            );
         }
 
-        if inner.max_efforts.len() < 2 {
-            return Err(serde::de::Error::custom(
-                "effort curve should have at least 2 points.",
-            ));
+        if max_efforts.len() < 2 {
+            return Err("effort curve should have at least 2 points.");
         }
 
-        if inner.max_efforts.iter().any(|&x| x < 0.0) {
-            return Err(serde::de::Error::custom(
-                "max_efforts values must be equal or greater than 0.",
-            ));
+        if max_efforts.iter().any(|&x| x < 0.0) {
+            return Err("max_efforts values must be equal or greater than 0.");
         };
 
-        if inner.speeds.iter().any(|&x| x < 0.0) {
-            return Err(serde::de::Error::custom(
-                "speeds values must be equal or greater than 0.",
-            ));
+        if speeds.iter().any(|&x| x < 0.0) {
+            return Err("speeds values must be equal or greater than 0.");
         };
 
-        if inner.speeds.windows(2).any(|window| window[0] >= window[1]) {
-            return Err(serde::de::Error::custom(
-                "speeds values must be strictly increasing.",
-            ));
+        if speeds.windows(2).any(|window| window[0] >= window[1]) {
+            return Err("speeds values must be strictly increasing.");
         }
 
         Ok(EffortCurve {
-            speeds: inner.speeds,
-            max_efforts: inner.max_efforts,
+            speeds,
+            max_efforts,
         })
     }
+
+    pub fn speeds(&self) -> &[f64] {
+        &self.speeds
+    }
+
+    pub fn max_efforts(&self) -> &[f64] {
+        &self.max_efforts
+    }
 }
 
 #[cfg(test)]
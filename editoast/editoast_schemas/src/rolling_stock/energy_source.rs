@@ -43,23 +43,23 @@ pub enum EnergySource {
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize, ToSchema)]
 #[serde(deny_unknown_fields)]
 pub struct SpeedDependantPower {
-    speeds: Vec<f64>,
-    powers: Vec<f64>,
+    pub speeds: Vec<f64>,
+    pub powers: Vec<f64>,
 }
 
 /// energy storage of an energy source (of a rolling stock, can be a electrical battery or a hydrogen/fuel powerPack)
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize, ToSchema)]
 #[serde(deny_unknown_fields)]
 pub struct EnergyStorage {
-    capacity: f64,
+    pub capacity: f64,
     #[schema(minimum = 0, maximum = 1)]
-    soc: f64,
+    pub soc: f64,
     #[schema(minimum = 0, maximum = 1)]
-    soc_min: f64,
+    pub soc_min: f64,
     #[schema(minimum = 0, maximum = 1)]
-    soc_max: f64,
+    pub soc_max: f64,
     #[schema(required)]
-    refill_law: Option<RefillLaw>,
+    pub refill_law: Option<RefillLaw>,
 }
 
 /// physical law defining how the storage can be refilled
@@ -67,7 +67,7 @@ pub struct EnergyStorage {
 #[serde(deny_unknown_fields)]
 pub struct RefillLaw {
     #[schema(minimum = 0)]
-    tau: f64,
+    pub tau: f64,
     #[schema(minimum = 0, maximum = 1)]
-    soc_ref: f64,
+    pub soc_ref: f64,
 }
@@ -0,0 +1,13 @@
+CREATE TABLE infra_edition_history (
+    id int8 PRIMARY KEY GENERATED BY DEFAULT AS IDENTITY,
+    infra_id int8 NOT NULL REFERENCES infra(id) ON DELETE CASCADE,
+    previous_version varchar(40) NOT NULL,
+    version varchar(40) NOT NULL,
+    author varchar(255),
+    created_at timestamptz NOT NULL DEFAULT NOW(),
+    kind smallint NOT NULL,
+    target_id int8 REFERENCES infra_edition_history(id) ON DELETE SET NULL,
+    operations jsonb NOT NULL,
+    inverse_operations jsonb NOT NULL,
+    UNIQUE (infra_id, version)
+);
@@ -0,0 +1,2 @@
+DROP TABLE resource_grant;
+DROP TABLE osrd_user;
@@ -0,0 +1,17 @@
+CREATE TABLE osrd_user (
+    id int8 PRIMARY KEY GENERATED BY DEFAULT AS IDENTITY,
+    identity varchar(255) NOT NULL UNIQUE,
+    uuid uuid NOT NULL UNIQUE DEFAULT gen_random_uuid(),
+    created_at timestamptz NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE resource_grant (
+    id int8 PRIMARY KEY GENERATED BY DEFAULT AS IDENTITY,
+    user_id int8 NOT NULL REFERENCES osrd_user(id) ON DELETE CASCADE,
+    resource_type smallint NOT NULL,
+    resource_id int8 NOT NULL,
+    role smallint NOT NULL,
+    UNIQUE (user_id, resource_type, resource_id)
+);
+
+CREATE INDEX resource_grant_resource ON resource_grant (resource_type, resource_id);
@@ -0,0 +1,2 @@
+ALTER TABLE train_schedule_v2 DROP COLUMN cadence_id;
+DROP TABLE train_schedule_cadence;
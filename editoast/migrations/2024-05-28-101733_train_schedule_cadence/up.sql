@@ -0,0 +1,14 @@
+CREATE TABLE train_schedule_cadence (
+    id int8 PRIMARY KEY GENERATED BY DEFAULT AS IDENTITY,
+    timetable_id int8 NOT NULL REFERENCES timetable_v2(id) ON DELETE CASCADE,
+    template jsonb NOT NULL,
+    period int8 NOT NULL CHECK (period > 0),
+    window_start timestamptz NOT NULL,
+    window_end timestamptz NOT NULL,
+    name_pattern varchar(128) NOT NULL
+);
+
+ALTER TABLE train_schedule_v2
+ADD COLUMN cadence_id int8 NULL REFERENCES train_schedule_cadence(id) ON DELETE CASCADE;
+
+CREATE INDEX train_schedule_v2_cadence ON train_schedule_v2 (cadence_id);
@@ -0,0 +1,21 @@
+CREATE TABLE job (
+    id int8 PRIMARY KEY GENERATED BY DEFAULT AS IDENTITY,
+    kind smallint NOT NULL,
+    parameters jsonb NOT NULL,
+    status smallint NOT NULL DEFAULT 0,
+    progress float8 NOT NULL DEFAULT 0,
+    logs jsonb NOT NULL DEFAULT '[]',
+    result jsonb NOT NULL DEFAULT 'null',
+    error jsonb NOT NULL DEFAULT 'null',
+    attempts int4 NOT NULL DEFAULT 0,
+    max_attempts int4 NOT NULL DEFAULT 1,
+    cancel_requested boolean NOT NULL DEFAULT FALSE,
+    user_id int8 NULL REFERENCES osrd_user(id) ON DELETE SET NULL,
+    created_at timestamptz NOT NULL DEFAULT NOW(),
+    scheduled_at timestamptz NOT NULL DEFAULT NOW(),
+    started_at timestamptz NULL,
+    finished_at timestamptz NULL,
+    heartbeat_at timestamptz NULL
+);
+
+CREATE INDEX job_queue ON job (status, scheduled_at);
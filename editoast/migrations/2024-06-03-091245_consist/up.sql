@@ -0,0 +1,5 @@
+CREATE TABLE consist (
+    id int8 PRIMARY KEY GENERATED BY DEFAULT AS IDENTITY,
+    name varchar(255) NOT NULL UNIQUE,
+    units jsonb NOT NULL
+);
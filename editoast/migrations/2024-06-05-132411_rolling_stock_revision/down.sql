@@ -0,0 +1,2 @@
+ALTER TABLE train_schedule_v2 DROP COLUMN rolling_stock_revision_id;
+DROP TABLE rolling_stock_revision;
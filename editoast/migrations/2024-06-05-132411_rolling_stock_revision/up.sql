@@ -0,0 +1,11 @@
+CREATE TABLE rolling_stock_revision (
+    id int8 PRIMARY KEY GENERATED BY DEFAULT AS IDENTITY,
+    rolling_stock_id int8 NOT NULL REFERENCES rolling_stock(id) ON DELETE CASCADE,
+    version int8 NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT NOW(),
+    rolling_stock jsonb NOT NULL,
+    UNIQUE (rolling_stock_id, version)
+);
+
+ALTER TABLE train_schedule_v2
+ADD COLUMN rolling_stock_revision_id int8 NULL REFERENCES rolling_stock_revision(id) ON DELETE SET NULL;
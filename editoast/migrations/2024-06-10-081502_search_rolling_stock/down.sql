@@ -0,0 +1,7 @@
+-- DO NOT EDIT THIS FILE MANUALLY!
+
+DROP TABLE IF EXISTS "search_rolling_stock";
+DROP TRIGGER IF EXISTS search_rolling_stock__ins_trig ON "rolling_stock";
+DROP TRIGGER IF EXISTS search_rolling_stock__upd_trig ON "rolling_stock";
+DROP FUNCTION IF EXISTS search_rolling_stock__ins_trig_fun;
+DROP FUNCTION IF EXISTS search_rolling_stock__upd_trig_fun;
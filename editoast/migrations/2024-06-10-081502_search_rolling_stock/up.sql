@@ -0,0 +1,75 @@
+-- DO NOT EDIT THIS FILE MANUALLY!
+-- To change the migration's content, use `editoast search make-migration`.
+-- To add custom SQL code, check out `#[derive(Search)]` attributes `prepend_sql` and `append_sql`.
+
+DROP TABLE IF EXISTS "search_rolling_stock";
+
+CREATE TABLE "search_rolling_stock" (
+    id BIGINT PRIMARY KEY REFERENCES "rolling_stock"("id") ON UPDATE CASCADE ON DELETE CASCADE,
+    "name" text,
+    "traction_modes" TEXT[],
+    "max_speed" double precision,
+    "loading_gauge" VARCHAR(16),
+    "locked" boolean
+);
+
+CREATE INDEX "search_rolling_stock_name" ON "search_rolling_stock" USING gin ("name" gin_trgm_ops);
+CREATE INDEX "search_rolling_stock_traction_modes" ON "search_rolling_stock" ("traction_modes");
+CREATE INDEX "search_rolling_stock_max_speed" ON "search_rolling_stock" ("max_speed");
+CREATE INDEX "search_rolling_stock_loading_gauge" ON "search_rolling_stock" ("loading_gauge");
+CREATE INDEX "search_rolling_stock_locked" ON "search_rolling_stock" ("locked");
+
+CREATE OR REPLACE FUNCTION search_rolling_stock__ins_trig_fun()
+    RETURNS TRIGGER
+    LANGUAGE plpgsql
+AS $$
+BEGIN
+    INSERT INTO "search_rolling_stock" (id, name, traction_modes, max_speed, loading_gauge, locked)
+        SELECT "rolling_stock".id AS id, osrd_prepare_for_search(rolling_stock.name) AS name,
+    (ARRAY(SELECT jsonb_object_keys(rolling_stock.effort_curves->'modes'))) AS traction_modes,
+    (rolling_stock.max_speed) AS max_speed,
+    ((ARRAY['G1', 'G2', 'GA', 'GB', 'GB1', 'GC', 'FR3.3', 'FR3.3/GB/G2', 'GLOTT'])[rolling_stock.loading_gauge + 1]) AS loading_gauge,
+    (rolling_stock.locked) AS locked
+        FROM (SELECT NEW.*) AS "rolling_stock"
+        ;
+    RETURN NEW;
+END;
+$$;
+CREATE OR REPLACE TRIGGER search_rolling_stock__ins_trig
+AFTER INSERT ON "rolling_stock"
+FOR EACH ROW EXECUTE FUNCTION search_rolling_stock__ins_trig_fun();
+
+
+CREATE OR REPLACE FUNCTION search_rolling_stock__upd_trig_fun()
+    RETURNS TRIGGER
+    LANGUAGE plpgsql
+AS $$
+BEGIN
+    UPDATE "search_rolling_stock"
+        SET "name" = osrd_prepare_for_search(rolling_stock.name),
+        "traction_modes" = (ARRAY(SELECT jsonb_object_keys(rolling_stock.effort_curves->'modes'))),
+        "max_speed" = (rolling_stock.max_speed),
+        "loading_gauge" = ((ARRAY['G1', 'G2', 'GA', 'GB', 'GB1', 'GC', 'FR3.3', 'FR3.3/GB/G2', 'GLOTT'])[rolling_stock.loading_gauge + 1]),
+        "locked" = (rolling_stock.locked)
+        FROM (SELECT NEW.*) AS "rolling_stock"
+        
+        WHERE "rolling_stock".id = "search_rolling_stock".id;
+    RETURN NEW;
+END;
+$$;
+CREATE OR REPLACE TRIGGER search_rolling_stock__upd_trig
+AFTER UPDATE ON "rolling_stock"
+FOR EACH ROW EXECUTE FUNCTION search_rolling_stock__upd_trig_fun();
+
+
+
+INSERT INTO "search_rolling_stock" (id, "name", "traction_modes", "max_speed", "loading_gauge", "locked")
+SELECT
+    "rolling_stock"."id" AS id,
+    osrd_prepare_for_search(rolling_stock.name) AS name
+,    (ARRAY(SELECT jsonb_object_keys(rolling_stock.effort_curves->'modes'))) AS traction_modes
+,    (rolling_stock.max_speed) AS max_speed
+,    ((ARRAY['G1', 'G2', 'GA', 'GB', 'GB1', 'GC', 'FR3.3', 'FR3.3/GB/G2', 'GLOTT'])[rolling_stock.loading_gauge + 1]) AS loading_gauge
+,    (rolling_stock.locked) AS locked
+FROM "rolling_stock"
+    ;
@@ -0,0 +1,7 @@
+-- DO NOT EDIT THIS FILE MANUALLY!
+
+DROP TABLE IF EXISTS "search_train_schedule_v2";
+DROP TRIGGER IF EXISTS search_train_schedule_v2__ins_trig ON "train_schedule_v2";
+DROP TRIGGER IF EXISTS search_train_schedule_v2__upd_trig ON "train_schedule_v2";
+DROP FUNCTION IF EXISTS search_train_schedule_v2__ins_trig_fun;
+DROP FUNCTION IF EXISTS search_train_schedule_v2__upd_trig_fun;
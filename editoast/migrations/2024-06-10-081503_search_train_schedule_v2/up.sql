@@ -0,0 +1,70 @@
+-- DO NOT EDIT THIS FILE MANUALLY!
+-- To change the migration's content, use `editoast search make-migration`.
+-- To add custom SQL code, check out `#[derive(Search)]` attributes `prepend_sql` and `append_sql`.
+
+DROP TABLE IF EXISTS "search_train_schedule_v2";
+
+CREATE TABLE "search_train_schedule_v2" (
+    id BIGINT PRIMARY KEY REFERENCES "train_schedule_v2"("id") ON UPDATE CASCADE ON DELETE CASCADE,
+    "timetable_id" bigint,
+    "train_name" text,
+    "labels" TEXT,
+    "rolling_stock_name" text
+);
+
+CREATE INDEX "search_train_schedule_v2_timetable_id" ON "search_train_schedule_v2" ("timetable_id");
+CREATE INDEX "search_train_schedule_v2_train_name" ON "search_train_schedule_v2" USING gin ("train_name" gin_trgm_ops);
+CREATE INDEX "search_train_schedule_v2_labels" ON "search_train_schedule_v2" ("labels");
+CREATE INDEX "search_train_schedule_v2_rolling_stock_name" ON "search_train_schedule_v2" USING gin ("rolling_stock_name" gin_trgm_ops);
+
+CREATE OR REPLACE FUNCTION search_train_schedule_v2__ins_trig_fun()
+    RETURNS TRIGGER
+    LANGUAGE plpgsql
+AS $$
+BEGIN
+    INSERT INTO "search_train_schedule_v2" (id, timetable_id, train_name, labels, rolling_stock_name)
+        SELECT "train_schedule_v2".id AS id, (train_schedule_v2.timetable_id) AS timetable_id,
+    osrd_prepare_for_search(train_schedule_v2.train_name) AS train_name,
+    (osrd_prepare_for_search_tags(train_schedule_v2.labels)) AS labels,
+    osrd_prepare_for_search(train_schedule_v2.rolling_stock_name) AS rolling_stock_name
+        FROM (SELECT NEW.*) AS "train_schedule_v2"
+        ;
+    RETURN NEW;
+END;
+$$;
+CREATE OR REPLACE TRIGGER search_train_schedule_v2__ins_trig
+AFTER INSERT ON "train_schedule_v2"
+FOR EACH ROW EXECUTE FUNCTION search_train_schedule_v2__ins_trig_fun();
+
+
+CREATE OR REPLACE FUNCTION search_train_schedule_v2__upd_trig_fun()
+    RETURNS TRIGGER
+    LANGUAGE plpgsql
+AS $$
+BEGIN
+    UPDATE "search_train_schedule_v2"
+        SET "timetable_id" = (train_schedule_v2.timetable_id),
+        "train_name" = osrd_prepare_for_search(train_schedule_v2.train_name),
+        "labels" = (osrd_prepare_for_search_tags(train_schedule_v2.labels)),
+        "rolling_stock_name" = osrd_prepare_for_search(train_schedule_v2.rolling_stock_name)
+        FROM (SELECT NEW.*) AS "train_schedule_v2"
+        
+        WHERE "train_schedule_v2".id = "search_train_schedule_v2".id;
+    RETURN NEW;
+END;
+$$;
+CREATE OR REPLACE TRIGGER search_train_schedule_v2__upd_trig
+AFTER UPDATE ON "train_schedule_v2"
+FOR EACH ROW EXECUTE FUNCTION search_train_schedule_v2__upd_trig_fun();
+
+
+
+INSERT INTO "search_train_schedule_v2" (id, "timetable_id", "train_name", "labels", "rolling_stock_name")
+SELECT
+    "train_schedule_v2"."id" AS id,
+    (train_schedule_v2.timetable_id) AS timetable_id
+,    osrd_prepare_for_search(train_schedule_v2.train_name) AS train_name
+,    (osrd_prepare_for_search_tags(train_schedule_v2.labels)) AS labels
+,    osrd_prepare_for_search(train_schedule_v2.rolling_stock_name) AS rolling_stock_name
+FROM "train_schedule_v2"
+    ;
@@ -0,0 +1,7 @@
+-- DO NOT EDIT THIS FILE MANUALLY!
+
+DROP TABLE IF EXISTS "search_switch";
+DROP TRIGGER IF EXISTS search_switch__ins_trig ON "infra_object_switch";
+DROP TRIGGER IF EXISTS search_switch__upd_trig ON "infra_object_switch";
+DROP FUNCTION IF EXISTS search_switch__ins_trig_fun;
+DROP FUNCTION IF EXISTS search_switch__upd_trig_fun;
@@ -0,0 +1,99 @@
+-- DO NOT EDIT THIS FILE MANUALLY!
+-- To change the migration's content, use `editoast search make-migration`.
+-- To add custom SQL code, check out `#[derive(Search)]` attributes `prepend_sql` and `append_sql`.
+
+DROP TABLE IF EXISTS "search_switch";
+
+CREATE TABLE "search_switch" (
+    id BIGINT PRIMARY KEY REFERENCES "infra_object_switch"("id") ON UPDATE CASCADE ON DELETE CASCADE,
+    "obj_id" VARCHAR(255),
+    "infra_id" integer,
+    "switch_type" VARCHAR(255),
+    "line_name" text,
+    "line_code" integer
+);
+
+CREATE INDEX "search_switch_obj_id" ON "search_switch" ("obj_id");
+CREATE INDEX "search_switch_infra_id" ON "search_switch" ("infra_id");
+CREATE INDEX "search_switch_switch_type" ON "search_switch" ("switch_type");
+CREATE INDEX "search_switch_line_name" ON "search_switch" USING gin ("line_name" gin_trgm_ops);
+CREATE INDEX "search_switch_line_code" ON "search_switch" ("line_code");
+
+CREATE OR REPLACE FUNCTION search_switch__ins_trig_fun()
+    RETURNS TRIGGER
+    LANGUAGE plpgsql
+AS $$
+BEGIN
+    INSERT INTO "search_switch" (id, obj_id, infra_id, switch_type, line_name, line_code)
+        SELECT "infra_object_switch".id AS id, (infra_object_switch.obj_id) AS obj_id,
+    (infra_object_switch.infra_id) AS infra_id,
+    (infra_object_switch.data->>'switch_type') AS switch_type,
+    osrd_prepare_for_search(track_section.data->'extensions'->'sncf'->>'line_name') AS line_name,
+    ((track_section.data->'extensions'->'sncf'->>'line_code')::integer) AS line_code
+        FROM (SELECT NEW.*) AS "infra_object_switch"
+        
+            LEFT JOIN infra_object_track_section AS track_section
+            ON track_section.infra_id = infra_object_switch.infra_id
+                AND track_section.obj_id = (
+                    SELECT port.value->>'track'
+                    FROM jsonb_each(infra_object_switch.data->'ports') AS port
+                    ORDER BY port.key
+                    LIMIT 1
+                );
+    RETURN NEW;
+END;
+$$;
+CREATE OR REPLACE TRIGGER search_switch__ins_trig
+AFTER INSERT ON "infra_object_switch"
+FOR EACH ROW EXECUTE FUNCTION search_switch__ins_trig_fun();
+
+
+CREATE OR REPLACE FUNCTION search_switch__upd_trig_fun()
+    RETURNS TRIGGER
+    LANGUAGE plpgsql
+AS $$
+BEGIN
+    UPDATE "search_switch"
+        SET "obj_id" = (infra_object_switch.obj_id),
+        "infra_id" = (infra_object_switch.infra_id),
+        "switch_type" = (infra_object_switch.data->>'switch_type'),
+        "line_name" = osrd_prepare_for_search(track_section.data->'extensions'->'sncf'->>'line_name'),
+        "line_code" = ((track_section.data->'extensions'->'sncf'->>'line_code')::integer)
+        FROM (SELECT NEW.*) AS "infra_object_switch"
+        
+            LEFT JOIN infra_object_track_section AS track_section
+            ON track_section.infra_id = infra_object_switch.infra_id
+                AND track_section.obj_id = (
+                    SELECT port.value->>'track'
+                    FROM jsonb_each(infra_object_switch.data->'ports') AS port
+                    ORDER BY port.key
+                    LIMIT 1
+                )
+        WHERE "infra_object_switch".id = "search_switch".id;
+    RETURN NEW;
+END;
+$$;
+CREATE OR REPLACE TRIGGER search_switch__upd_trig
+AFTER UPDATE ON "infra_object_switch"
+FOR EACH ROW EXECUTE FUNCTION search_switch__upd_trig_fun();
+
+
+
+INSERT INTO "search_switch" (id, "obj_id", "infra_id", "switch_type", "line_name", "line_code")
+SELECT
+    "infra_object_switch"."id" AS id,
+    (infra_object_switch.obj_id) AS obj_id
+,    (infra_object_switch.infra_id) AS infra_id
+,    (infra_object_switch.data->>'switch_type') AS switch_type
+,    osrd_prepare_for_search(track_section.data->'extensions'->'sncf'->>'line_name') AS line_name
+,    ((track_section.data->'extensions'->'sncf'->>'line_code')::integer) AS line_code
+FROM "infra_object_switch"
+    
+            LEFT JOIN infra_object_track_section AS track_section
+            ON track_section.infra_id = infra_object_switch.infra_id
+                AND track_section.obj_id = (
+                    SELECT port.value->>'track'
+                    FROM jsonb_each(infra_object_switch.data->'ports') AS port
+                    ORDER BY port.key
+                    LIMIT 1
+                );
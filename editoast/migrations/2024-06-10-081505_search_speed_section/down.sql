@@ -0,0 +1,7 @@
+-- DO NOT EDIT THIS FILE MANUALLY!
+
+DROP TABLE IF EXISTS "search_speed_section";
+DROP TRIGGER IF EXISTS search_speed_section__ins_trig ON "infra_object_speed_section";
+DROP TRIGGER IF EXISTS search_speed_section__upd_trig ON "infra_object_speed_section";
+DROP FUNCTION IF EXISTS search_speed_section__ins_trig_fun;
+DROP FUNCTION IF EXISTS search_speed_section__upd_trig_fun;
@@ -0,0 +1,84 @@
+-- DO NOT EDIT THIS FILE MANUALLY!
+-- To change the migration's content, use `editoast search make-migration`.
+-- To add custom SQL code, check out `#[derive(Search)]` attributes `prepend_sql` and `append_sql`.
+
+DROP TABLE IF EXISTS "search_speed_section";
+
+CREATE TABLE "search_speed_section" (
+    id BIGINT PRIMARY KEY REFERENCES "infra_object_speed_section"("id") ON UPDATE CASCADE ON DELETE CASCADE,
+    "obj_id" VARCHAR(255),
+    "infra_id" integer,
+    "speed_limit" double precision,
+    "line_name" text,
+    "line_code" integer
+);
+
+CREATE INDEX "search_speed_section_obj_id" ON "search_speed_section" ("obj_id");
+CREATE INDEX "search_speed_section_infra_id" ON "search_speed_section" ("infra_id");
+CREATE INDEX "search_speed_section_speed_limit" ON "search_speed_section" ("speed_limit");
+CREATE INDEX "search_speed_section_line_name" ON "search_speed_section" USING gin ("line_name" gin_trgm_ops);
+CREATE INDEX "search_speed_section_line_code" ON "search_speed_section" ("line_code");
+
+CREATE OR REPLACE FUNCTION search_speed_section__ins_trig_fun()
+    RETURNS TRIGGER
+    LANGUAGE plpgsql
+AS $$
+BEGIN
+    INSERT INTO "search_speed_section" (id, obj_id, infra_id, speed_limit, line_name, line_code)
+        SELECT "infra_object_speed_section".id AS id, (infra_object_speed_section.obj_id) AS obj_id,
+    (infra_object_speed_section.infra_id) AS infra_id,
+    ((infra_object_speed_section.data->>'speed_limit')::double precision) AS speed_limit,
+    osrd_prepare_for_search(track_section.data->'extensions'->'sncf'->>'line_name') AS line_name,
+    ((track_section.data->'extensions'->'sncf'->>'line_code')::integer) AS line_code
+        FROM (SELECT NEW.*) AS "infra_object_speed_section"
+        
+            LEFT JOIN infra_object_track_section AS track_section
+            ON track_section.infra_id = infra_object_speed_section.infra_id
+                AND track_section.obj_id = infra_object_speed_section.data->'track_ranges'->0->>'track';
+    RETURN NEW;
+END;
+$$;
+CREATE OR REPLACE TRIGGER search_speed_section__ins_trig
+AFTER INSERT ON "infra_object_speed_section"
+FOR EACH ROW EXECUTE FUNCTION search_speed_section__ins_trig_fun();
+
+
+CREATE OR REPLACE FUNCTION search_speed_section__upd_trig_fun()
+    RETURNS TRIGGER
+    LANGUAGE plpgsql
+AS $$
+BEGIN
+    UPDATE "search_speed_section"
+        SET "obj_id" = (infra_object_speed_section.obj_id),
+        "infra_id" = (infra_object_speed_section.infra_id),
+        "speed_limit" = ((infra_object_speed_section.data->>'speed_limit')::double precision),
+        "line_name" = osrd_prepare_for_search(track_section.data->'extensions'->'sncf'->>'line_name'),
+        "line_code" = ((track_section.data->'extensions'->'sncf'->>'line_code')::integer)
+        FROM (SELECT NEW.*) AS "infra_object_speed_section"
+        
+            LEFT JOIN infra_object_track_section AS track_section
+            ON track_section.infra_id = infra_object_speed_section.infra_id
+                AND track_section.obj_id = infra_object_speed_section.data->'track_ranges'->0->>'track'
+        WHERE "infra_object_speed_section".id = "search_speed_section".id;
+    RETURN NEW;
+END;
+$$;
+CREATE OR REPLACE TRIGGER search_speed_section__upd_trig
+AFTER UPDATE ON "infra_object_speed_section"
+FOR EACH ROW EXECUTE FUNCTION search_speed_section__upd_trig_fun();
+
+
+
+INSERT INTO "search_speed_section" (id, "obj_id", "infra_id", "speed_limit", "line_name", "line_code")
+SELECT
+    "infra_object_speed_section"."id" AS id,
+    (infra_object_speed_section.obj_id) AS obj_id
+,    (infra_object_speed_section.infra_id) AS infra_id
+,    ((infra_object_speed_section.data->>'speed_limit')::double precision) AS speed_limit
+,    osrd_prepare_for_search(track_section.data->'extensions'->'sncf'->>'line_name') AS line_name
+,    ((track_section.data->'extensions'->'sncf'->>'line_code')::integer) AS line_code
+FROM "infra_object_speed_section"
+    
+            LEFT JOIN infra_object_track_section AS track_section
+            ON track_section.infra_id = infra_object_speed_section.infra_id
+                AND track_section.obj_id = infra_object_speed_section.data->'track_ranges'->0->>'track';
@@ -0,0 +1,8 @@
+DROP TRIGGER project_delete_grants ON project;
+DROP TRIGGER study_delete_grants ON study;
+DROP TRIGGER scenario_delete_grants ON scenario;
+DROP TRIGGER infra_delete_grants ON infra;
+DROP TRIGGER rolling_stock_delete_grants ON rolling_stock;
+DROP FUNCTION delete_resource_grants;
+
+ALTER TABLE osrd_user DROP COLUMN is_admin;
@@ -0,0 +1,29 @@
+ALTER TABLE osrd_user ADD COLUMN is_admin boolean NOT NULL DEFAULT FALSE;
+
+-- Grants are not bound to their resource by a foreign key, since they target several tables
+CREATE FUNCTION delete_resource_grants() RETURNS TRIGGER AS $$
+BEGIN
+    DELETE FROM resource_grant
+    WHERE resource_type = TG_ARGV[0]::smallint AND resource_id = OLD.id;
+    RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER project_delete_grants AFTER DELETE ON project
+    FOR EACH ROW EXECUTE FUNCTION delete_resource_grants(0);
+CREATE TRIGGER study_delete_grants AFTER DELETE ON study
+    FOR EACH ROW EXECUTE FUNCTION delete_resource_grants(1);
+CREATE TRIGGER scenario_delete_grants AFTER DELETE ON scenario
+    FOR EACH ROW EXECUTE FUNCTION delete_resource_grants(2);
+CREATE TRIGGER infra_delete_grants AFTER DELETE ON infra
+    FOR EACH ROW EXECUTE FUNCTION delete_resource_grants(3);
+CREATE TRIGGER rolling_stock_delete_grants AFTER DELETE ON rolling_stock
+    FOR EACH ROW EXECUTE FUNCTION delete_resource_grants(4);
+
+-- Grants left behind by resources deleted before
+DELETE FROM resource_grant
+WHERE (resource_type = 0 AND resource_id NOT IN (SELECT id FROM project))
+    OR (resource_type = 1 AND resource_id NOT IN (SELECT id FROM study))
+    OR (resource_type = 2 AND resource_id NOT IN (SELECT id FROM scenario))
+    OR (resource_type = 3 AND resource_id NOT IN (SELECT id FROM infra))
+    OR (resource_type = 4 AND resource_id NOT IN (SELECT id FROM rolling_stock));
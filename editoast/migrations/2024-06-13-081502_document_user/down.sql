@@ -0,0 +1 @@
+ALTER TABLE document DROP COLUMN user_id;
@@ -0,0 +1 @@
+ALTER TABLE document ADD COLUMN user_id int8 NULL REFERENCES osrd_user(id) ON DELETE SET NULL;
@@ -0,0 +1 @@
+ALTER TABLE electrical_profile_set DROP COLUMN version;
@@ -0,0 +1 @@
+ALTER TABLE electrical_profile_set ADD COLUMN version int8 NOT NULL DEFAULT 0;
@@ -0,0 +1 @@
+ALTER TABLE train_schedule_v2 DROP COLUMN cadence_departure;
@@ -0,0 +1,3 @@
+ALTER TABLE train_schedule_v2 ADD COLUMN cadence_departure timestamptz NULL;
+
+UPDATE train_schedule_v2 SET cadence_departure = start_time WHERE cadence_id IS NOT NULL;
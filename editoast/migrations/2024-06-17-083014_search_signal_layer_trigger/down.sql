@@ -0,0 +1,13 @@
+-- DO NOT EDIT THIS FILE MANUALLY!
+
+DROP TABLE IF EXISTS "search_signal";
+DROP TRIGGER IF EXISTS search_signal__ins_trig ON "infra_object_signal";
+DROP TRIGGER IF EXISTS search_signal__upd_trig ON "infra_object_signal";
+DROP FUNCTION IF EXISTS search_signal__ins_trig_fun;
+DROP FUNCTION IF EXISTS search_signal__upd_trig_fun;
+
+
+
+DROP TRIGGER IF EXISTS search_signal__layer_ins_trig ON infra_layer_signal;
+DROP TRIGGER IF EXISTS search_signal__layer_upd_trig ON infra_layer_signal;
+DROP FUNCTION IF EXISTS search_signal__layer_trig_fun;
\ No newline at end of file
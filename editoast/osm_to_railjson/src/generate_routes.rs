@@ -397,8 +397,11 @@ mod tests {
         The test case has one switch and one detector
     */
     fn generate_routes() {
-        let railjson =
-            crate::osm_to_railjson::parse_osm("src/tests/routes.osm.pbf".into()).unwrap();
+        let railjson = crate::osm_to_railjson::parse_osm(
+            "src/tests/routes.osm.pbf".into(),
+            &crate::TagMapping::default(),
+        )
+        .unwrap();
         let routes = super::routes(&railjson);
         assert_eq!(6, routes.len());
         let routes_with_switches_count = routes
@@ -1,5 +1,8 @@
 mod generate_routes;
 mod osm_to_railjson;
+mod platforms;
+mod tag_mapping;
 mod utils;
 
 pub use osm_to_railjson::osm_to_railjson;
+pub use tag_mapping::TagMapping;
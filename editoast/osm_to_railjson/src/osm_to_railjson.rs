@@ -8,6 +8,7 @@ use tracing::info;
 
 use super::utils::*;
 use crate::generate_routes;
+use crate::tag_mapping::TagMapping;
 use editoast_schemas::infra::RailJson;
 use editoast_schemas::infra::TrackSection;
 /// Run the osm-to-railjson subcommand
@@ -15,24 +16,34 @@ use editoast_schemas::infra::TrackSection;
 pub fn osm_to_railjson(
     osm_pbf_in: PathBuf,
     railjson_out: PathBuf,
+    mapping: &TagMapping,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
     info!(
         "🗺️ Converting {} to {}",
         osm_pbf_in.display(),
         railjson_out.display()
     );
-    let railjson = parse_osm(osm_pbf_in)?;
+    let railjson = parse_osm(osm_pbf_in, mapping)?;
     let file = std::fs::File::create(railjson_out)?;
     serde_json::to_writer(file, &railjson)?;
     Ok(())
 }
 
-pub fn parse_osm(osm_pbf_in: PathBuf) -> Result<RailJson, Box<dyn Error + Send + Sync>> {
-    let (nodes, edges) = osm4routing::Reader::new()
-        .require("railway", "rail")
-        .reject("service", "yard")
-        .reject("service", "siding")
-        .reject("service", "spur")
+pub fn parse_osm(
+    osm_pbf_in: PathBuf,
+    mapping: &TagMapping,
+) -> Result<RailJson, Box<dyn Error + Send + Sync>> {
+    let mut reader = osm4routing::Reader::new().require("railway", "rail");
+    if !mapping.keep_service_tracks {
+        reader = reader
+            .reject("service", "yard")
+            .reject("service", "siding")
+            .reject("service", "spur");
+    }
+    for tag in mapping.way_tags() {
+        reader = reader.read_tag(tag);
+    }
+    let (nodes, edges) = reader
         .reject("building", "*")
         .reject("area", "yes")
         .reject("gauge", "600")
@@ -57,14 +68,22 @@ pub fn parse_osm(osm_pbf_in: PathBuf) -> Result<RailJson, Box<dyn Error + Send +
     }
 
     let nodes_tracks = NodeToTrack::from_edges(&edges);
-    let signals = signals(&osm_pbf_in, &nodes_tracks, &adjacencies);
+    let signals = signals(&osm_pbf_in, &nodes_tracks, &adjacencies, mapping);
     let mut railjson = RailJson {
         extended_switch_types: vec![],
         detectors: signals.iter().map(detector).collect(),
         signals,
         speed_sections: rail_edges.clone().flat_map(speed_sections).collect(),
-        electrifications: rail_edges.clone().flat_map(electrifications).collect(),
-        operational_points: operational_points(&osm_pbf_in, &nodes_tracks),
+        electrifications: rail_edges
+            .clone()
+            .flat_map(|e| electrifications(e, mapping))
+            .collect(),
+        operational_points: operational_points(
+            &osm_pbf_in,
+            &nodes_tracks,
+            &rail_edges.clone().collect::<Vec<_>>(),
+            mapping,
+        ),
         ..Default::default()
     };
 
@@ -76,6 +95,7 @@ pub fn parse_osm(osm_pbf_in: PathBuf) -> Result<RailJson, Box<dyn Error + Send +
             TrackSection {
                 id: e.id.as_str().into(),
                 length: e.length(),
+                loading_gauge_limits: loading_gauge_limits(e, mapping),
                 geo: geo.clone(),
                 ..Default::default()
             }
@@ -140,7 +160,8 @@ mod tests {
         let output = tempfile::NamedTempFile::new().unwrap();
         assert!(osm_to_railjson(
             "src/tests/minimal_rail.osm.pbf".into(),
-            output.path().into()
+            output.path().into(),
+            &TagMapping::default(),
         )
         .is_ok());
 
@@ -154,7 +175,8 @@ mod tests {
         fn port_eq(ports: &HashMap<Identifier, TrackEndpoint>, name: &str, expected: &str) -> bool {
             ports.get(&name.into()).unwrap().track.0 == expected
         }
-        let mut railjson = parse_osm("src/tests/switches.osm.pbf".into()).unwrap();
+        let mut railjson =
+            parse_osm("src/tests/switches.osm.pbf".into(), &TagMapping::default()).unwrap();
         assert_eq!(4, railjson.switches.len());
         assert_eq!(18, railjson.buffer_stops.len());
 
@@ -208,21 +230,30 @@ mod tests {
 
     #[test]
     fn parse_signals() {
-        let railjson = parse_osm("src/tests/signals.osm.pbf".into()).unwrap();
+        let railjson =
+            parse_osm("src/tests/signals.osm.pbf".into(), &TagMapping::default()).unwrap();
         assert_eq!(1, railjson.signals.len());
         assert_eq!(1, railjson.detectors.len());
     }
 
     #[test]
     fn ignore_signals_at_end_of_line() {
-        let railjson = parse_osm("src/tests/signal_at_end_of_line.osm.pbf".into()).unwrap();
+        let railjson = parse_osm(
+            "src/tests/signal_at_end_of_line.osm.pbf".into(),
+            &TagMapping::default(),
+        )
+        .unwrap();
         assert!(railjson.signals.is_empty());
         assert_eq!(2, railjson.buffer_stops.len());
     }
 
     #[test]
     fn parse_speed() {
-        let rj = parse_osm("src/tests/minimal_rail.osm.pbf".into()).unwrap();
+        let rj = parse_osm(
+            "src/tests/minimal_rail.osm.pbf".into(),
+            &TagMapping::default(),
+        )
+        .unwrap();
         assert_eq!(2, rj.speed_sections.len());
         let forward = rj
             .speed_sections
@@ -240,14 +271,18 @@ mod tests {
 
     #[test]
     fn parse_electrifications() {
-        let rj = parse_osm("src/tests/minimal_rail.osm.pbf".into()).unwrap();
+        let rj = parse_osm(
+            "src/tests/minimal_rail.osm.pbf".into(),
+            &TagMapping::default(),
+        )
+        .unwrap();
         assert_eq!(1, rj.electrifications.len());
         assert_eq!("15000V", rj.electrifications[0].voltage);
     }
 
     #[test]
     fn parse_stations() {
-        let rj = parse_osm("src/tests/station.osm.pbf".into()).unwrap();
+        let rj = parse_osm("src/tests/station.osm.pbf".into(), &TagMapping::default()).unwrap();
         assert_eq!(1, rj.operational_points.len());
         let op = &rj.operational_points[0];
         assert_eq!(2, op.parts.len());
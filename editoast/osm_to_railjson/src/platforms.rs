@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+
+use editoast_schemas::infra::OperationalPointPart;
+use osm4routing::Coord;
+use osm4routing::Edge;
+use osmpbfreader::OsmId;
+use osmpbfreader::OsmObj;
+
+/// Meters per degree of latitude, consistent with `Coord::distance_to`
+const METERS_PER_DEGREE: f64 = 6_378_100. * std::f64::consts::PI / 180.;
+
+/// The coordinates of a stop area member: a node, or the nodes of a way
+///
+/// Platforms mapped as multipolygon relations are ignored.
+pub fn member_coordinates(objs: &BTreeMap<OsmId, OsmObj>, member: OsmId) -> Vec<Coord> {
+    let coord = |node: &osmpbfreader::Node| Coord {
+        lon: node.lon(),
+        lat: node.lat(),
+    };
+    match objs.get(&member) {
+        Some(OsmObj::Node(node)) => vec![coord(node)],
+        Some(OsmObj::Way(way)) => way
+            .nodes
+            .iter()
+            .filter_map(|node| objs.get(&(*node).into())?.node())
+            .map(coord)
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Builds an operational point part on every track passing along a platform
+///
+/// The center of the platform is projected on each track, tracks further than
+/// `max_distance` meters are ignored.
+pub fn platform_parts(
+    platform: &[Coord],
+    edges: &[&Edge],
+    max_distance: f64,
+) -> Vec<OperationalPointPart> {
+    if platform.is_empty() {
+        return vec![];
+    }
+    let center = Coord {
+        lon: platform.iter().map(|c| c.lon).sum::<f64>() / platform.len() as f64,
+        lat: platform.iter().map(|c| c.lat).sum::<f64>() / platform.len() as f64,
+    };
+    // Projects the coordinates in meters, around the center of the platform
+    let lon_scale = METERS_PER_DEGREE * center.lat.to_radians().cos();
+    let local = |c: &Coord| {
+        (
+            (c.lon - center.lon) * lon_scale,
+            (c.lat - center.lat) * METERS_PER_DEGREE,
+        )
+    };
+
+    edges
+        .iter()
+        .filter(|edge| {
+            edge.geometry.iter().any(|c| {
+                let (x, y) = local(c);
+                x.abs() <= max_distance + edge.length() && y.abs() <= max_distance + edge.length()
+            })
+        })
+        .filter_map(|edge| {
+            let mut offset = 0.;
+            let mut closest: Option<(f64, f64)> = None;
+            for segment in edge.geometry.windows(2) {
+                let ((ax, ay), (bx, by)) = (local(&segment[0]), local(&segment[1]));
+                let (dx, dy) = (bx - ax, by - ay);
+                let squared_length = dx * dx + dy * dy;
+                let t = if squared_length > 0. {
+                    (-(ax * dx + ay * dy) / squared_length).clamp(0., 1.)
+                } else {
+                    0.
+                };
+                let distance = (ax + t * dx).hypot(ay + t * dy);
+                let segment_length = segment[0].distance_to(segment[1]);
+                if closest.map_or(true, |(closest_distance, _)| distance < closest_distance) {
+                    closest = Some((distance, offset + t * segment_length));
+                }
+                offset += segment_length;
+            }
+            let (distance, position) = closest?;
+            (distance <= max_distance).then(|| OperationalPointPart {
+                track: edge.id.as_str().into(),
+                position,
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use osm4routing::NodeId;
+
+    use super::*;
+
+    fn edge(id: &str, geometry: Vec<Coord>) -> Edge {
+        Edge {
+            id: id.into(),
+            nodes: (0..geometry.len() as i64).map(NodeId).collect(),
+            geometry,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parts_on_tracks_along_the_platform() {
+        // Two parallel tracks along the equator, 10m and 50m away from the platform
+        let meters = |m: f64| m / METERS_PER_DEGREE;
+        let near = edge(
+            "near",
+            vec![
+                Coord {
+                    lon: 0.,
+                    lat: meters(10.),
+                },
+                Coord {
+                    lon: meters(1000.),
+                    lat: meters(10.),
+                },
+            ],
+        );
+        let far = edge(
+            "far",
+            vec![
+                Coord {
+                    lon: 0.,
+                    lat: meters(-50.),
+                },
+                Coord {
+                    lon: meters(1000.),
+                    lat: meters(-50.),
+                },
+            ],
+        );
+        let platform = vec![
+            Coord {
+                lon: meters(300.),
+                lat: 0.,
+            },
+            Coord {
+                lon: meters(500.),
+                lat: 0.,
+            },
+        ];
+
+        let parts = platform_parts(&platform, &[&near, &far], 20.);
+
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0].track.as_str(), "near");
+        assert!((parts[0].position - 400.).abs() < 1.);
+    }
+}
@@ -0,0 +1,207 @@
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+use editoast_schemas::infra::LogicalSignal;
+use editoast_schemas::primitives::NonBlankString;
+use editoast_schemas::rolling_stock::LoadingGaugeType;
+use serde::Deserialize;
+
+/// Configures how OpenStreetMap tags are converted to railjson
+///
+/// The default mapping keeps the historical conversion: service tracks are rejected,
+/// electrifications only come from the `voltage` tag and every main signal is a BAL signal.
+/// Each section of the mapping file opts in a richer conversion.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TagMapping {
+    /// Keep the `service=yard|siding|spur` tracks
+    #[serde(default)]
+    pub keep_service_tracks: bool,
+    /// Each matching rule adds a loading gauge limit on the whole track section
+    #[serde(default)]
+    pub loading_gauges: Vec<LoadingGaugeRule>,
+    /// The first matching rule gives the voltage of the track section,
+    /// the `voltage` tag is used when no rule matches
+    #[serde(default)]
+    pub electrifications: Vec<ElectrificationRule>,
+    /// The first matching rule gives the logical signal of a signal node,
+    /// nodes without any matching rule are not signals
+    #[serde(default)]
+    pub signals: Vec<SignalRule>,
+    #[serde(default)]
+    pub operational_points: Option<OperationalPointMapping>,
+}
+
+/// Tags an OpenStreetMap object must have, `*` matching any value
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(transparent)]
+pub struct TagFilter(pub HashMap<String, String>);
+
+impl TagFilter {
+    pub fn matches<'a>(&self, tag: impl Fn(&str) -> Option<&'a str>) -> bool {
+        self.0.iter().all(|(key, expected)| match tag(key) {
+            Some(value) => expected == "*" || expected == value,
+            None => false,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LoadingGaugeRule {
+    pub tags: TagFilter,
+    pub category: LoadingGaugeType,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ElectrificationRule {
+    pub tags: TagFilter,
+    pub voltage: NonBlankString,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SignalRule {
+    pub tags: TagFilter,
+    pub signaling_system: String,
+    #[serde(default)]
+    pub settings: HashMap<NonBlankString, NonBlankString>,
+}
+
+impl SignalRule {
+    pub fn logical_signal(&self) -> LogicalSignal {
+        LogicalSignal {
+            signaling_system: self.signaling_system.clone(),
+            settings: self.settings.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OperationalPointMapping {
+    /// Add a part on every track along the platforms of a stop area
+    #[serde(default)]
+    pub platforms: bool,
+    /// Maximum distance in meters between a platform and its tracks
+    #[serde(default = "default_platform_distance")]
+    pub platform_distance: f64,
+    /// Convert the `railway=stop|halt` nodes which are not part of a stop area
+    #[serde(default)]
+    pub railway_stops: bool,
+}
+
+fn default_platform_distance() -> f64 {
+    20.
+}
+
+impl TagMapping {
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let file = std::fs::File::open(path)?;
+        Ok(serde_yaml::from_reader(file)?)
+    }
+
+    /// The way tags needed by the mapping
+    pub fn way_tags(&self) -> BTreeSet<&str> {
+        self.loading_gauges
+            .iter()
+            .map(|rule| &rule.tags)
+            .chain(self.electrifications.iter().map(|rule| &rule.tags))
+            .flat_map(|filter| filter.0.keys())
+            .map(String::as_str)
+            .collect()
+    }
+
+    pub fn loading_gauges<'a>(
+        &self,
+        tag: impl Fn(&str) -> Option<&'a str> + Copy,
+    ) -> Vec<LoadingGaugeType> {
+        let mut categories = vec![];
+        for rule in &self.loading_gauges {
+            if rule.tags.matches(tag) && !categories.contains(&rule.category) {
+                categories.push(rule.category);
+            }
+        }
+        categories
+    }
+
+    pub fn voltage<'a>(
+        &self,
+        tag: impl Fn(&str) -> Option<&'a str> + Copy,
+    ) -> Option<&NonBlankString> {
+        self.electrifications
+            .iter()
+            .find(|rule| rule.tags.matches(tag))
+            .map(|rule| &rule.voltage)
+    }
+
+    pub fn signal_rule<'a>(
+        &self,
+        tag: impl Fn(&str) -> Option<&'a str> + Copy,
+    ) -> Option<&SignalRule> {
+        self.signals.iter().find(|rule| rule.tags.matches(tag))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_example_mapping() {
+        let mapping: TagMapping =
+            serde_yaml::from_str(include_str!("../tag_mapping.example.yml")).unwrap();
+        assert!(mapping.keep_service_tracks);
+        assert!(mapping.way_tags().contains("loading_gauge"));
+        assert!(mapping.operational_points.unwrap().platforms);
+    }
+
+    #[test]
+    fn default_mapping_is_empty() {
+        let mapping: TagMapping = serde_yaml::from_str("{}").unwrap();
+        assert!(!mapping.keep_service_tracks);
+        assert!(mapping.way_tags().is_empty());
+        assert!(mapping.operational_points.is_none());
+    }
+
+    #[test]
+    fn filters_match_tags() {
+        let filter = TagFilter(HashMap::from([
+            ("electrified".into(), "contact_line".into()),
+            ("voltage".into(), "*".into()),
+        ]));
+        let tags = HashMap::from([("electrified", "contact_line"), ("voltage", "1500")]);
+        assert!(filter.matches(|key| tags.get(key).copied()));
+        let tags = HashMap::from([("electrified", "rail"), ("voltage", "750")]);
+        assert!(!filter.matches(|key| tags.get(key).copied()));
+        let tags = HashMap::from([("electrified", "contact_line")]);
+        assert!(!filter.matches(|key| tags.get(key).copied()));
+    }
+
+    #[test]
+    fn first_signal_rule_wins() {
+        let mapping: TagMapping = serde_yaml::from_str(
+            r#"
+            signals:
+              - tags: { "railway:signal:main": "DE-ESO:hp" }
+                signaling_system: BAL
+                settings: { Nf: "false" }
+              - tags: { "railway:signal:main": "*" }
+                signaling_system: BAL
+                settings: { Nf: "true" }
+            "#,
+        )
+        .unwrap();
+        let tags = HashMap::from([("railway:signal:main", "DE-ESO:hp")]);
+        let signal = mapping
+            .signal_rule(|key| tags.get(key).copied())
+            .unwrap()
+            .logical_signal();
+        assert_eq!(signal.settings[&"Nf".into()], "false".into());
+        assert!(mapping.signal_rule(|_| None).is_none());
+    }
+}
@@ -5,6 +5,7 @@ use editoast_schemas::infra::Detector;
 use editoast_schemas::infra::Direction;
 use editoast_schemas::infra::Electrification;
 use editoast_schemas::infra::Endpoint;
+use editoast_schemas::infra::LoadingGaugeLimit;
 use editoast_schemas::infra::LogicalSignal;
 use editoast_schemas::infra::OperationalPoint;
 use editoast_schemas::infra::OperationalPointExtensions;
@@ -24,10 +25,15 @@ use osm4routing::Edge;
 use osm4routing::NodeId;
 use osmpbfreader::Node;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::str::FromStr;
 use tracing::error;
 use tracing::warn;
 
+use crate::platforms::member_coordinates;
+use crate::platforms::platform_parts;
+use crate::tag_mapping::TagMapping;
+
 // Given an edge and a coordinate, returns the coordinates used to compute the angle
 // It uses the nearest OpenStreetMap node, and the other as the the rails might do a loop
 // that would result in a bad angle
@@ -237,16 +243,26 @@ impl<'a> NodeToTrack<'a> {
     }
 }
 
+/// Signals are the main signals, unless the tag mapping has signal rules
 pub fn signals(
     osm_pbf_in: &std::path::PathBuf,
     nodes_to_tracks: &NodeToTrack,
     adjacencies: &HashMap<osm4routing::NodeId, NodeAdjacencies>,
+    mapping: &TagMapping,
 ) -> Vec<Signal> {
     let file = std::fs::File::open(osm_pbf_in).unwrap();
     let mut pbf = osmpbfreader::OsmPbfReader::new(file);
     pbf.iter()
         .flatten()
-        .filter(main_signal)
+        .filter(|obj| {
+            if mapping.signals.is_empty() {
+                main_signal(obj)
+            } else {
+                mapping
+                    .signal_rule(|key| obj.tags().get(key).map(|v| v.as_str()))
+                    .is_some()
+            }
+        })
         .flat_map(|obj| match obj {
             osmpbfreader::OsmObj::Node(node) => Some(node),
             _ => None,
@@ -254,8 +270,19 @@ pub fn signals(
         .filter(|node| adjacencies.get(&node.id).map_or(0, |adj| adj.edges.len()) != 1) // Ignore all the nodes that are at the end of a track, as it will be buffer stops
         .flat_map(|node| {
             if let Some((track, position)) = nodes_to_tracks.track_and_position(node.id) {
-                let mut settings = HashMap::new();
-                settings.insert("Nf".into(), "true".into());
+                let logical_signal =
+                    match mapping.signal_rule(|key| node.tags.get(key).map(|v| v.as_str())) {
+                        Some(rule) => rule.logical_signal(),
+                        None => {
+                            let mut settings = HashMap::new();
+                            settings.insert("Nf".into(), "true".into());
+                            LogicalSignal {
+                                signaling_system: "BAL".to_string(),
+                                settings,
+                                ..Default::default()
+                            }
+                        }
+                    };
 
                 Some(Signal {
                     id: node.id.0.to_string().into(),
@@ -263,11 +290,7 @@ pub fn signals(
                     track,
                     position,
                     sight_distance: 400.,
-                    logical_signals: vec![LogicalSignal {
-                        signaling_system: "BAL".to_string(),
-                        settings,
-                        ..Default::default()
-                    }],
+                    logical_signals: vec![logical_signal],
                     extensions: SignalExtensions {
                         sncf: Some(sncf_extensions(&node)),
                     },
@@ -403,12 +426,16 @@ pub fn edge_to_buffer(node: &NodeId, edge: &Edge, count: i64) -> BufferStop {
     }
 }
 
-pub fn electrifications(edge: &Edge) -> Option<Electrification> {
-    // TODO: handle multiple overlapping electrifications
-    // Specific infrastructures can support multiple electrifications (e.g. "voltage"="600;1500;3000;15000;25000").
-    // Short term solution : pick the first one, i.g. "600;1500;3000;15000;25000" -> "600V"
-    edge.tags.get("voltage").and_then(|voltage| {
-        voltage
+/// The voltage comes from the first matching rule of the tag mapping, or from the `voltage` tag
+pub fn electrifications(edge: &Edge, mapping: &TagMapping) -> Option<Electrification> {
+    let voltage = match mapping.voltage(|key| edge.tags.get(key).map(String::as_str)) {
+        Some(voltage) => voltage.clone(),
+        // TODO: handle multiple overlapping electrifications
+        // Specific infrastructures can support multiple electrifications (e.g. "voltage"="600;1500;3000;15000;25000").
+        // Short term solution : pick the first one, i.g. "600;1500;3000;15000;25000" -> "600V"
+        None => edge
+            .tags
+            .get("voltage")?
             .split(';')
             .next()
             .map(|v| {
@@ -417,66 +444,136 @@ pub fn electrifications(edge: &Edge) -> Option<Electrification> {
                 } else {
                     v.to_string()
                 }
-            })
-            .map(|parsed_voltage| Electrification {
-                id: edge.id.clone().into(),
-                voltage: parsed_voltage.into(),
-                track_ranges: vec![ApplicableDirectionsTrackRange::new(
-                    edge.id.clone(),
-                    0.,
-                    edge.length(),
-                    ApplicableDirections::Both,
-                )],
-            })
+            })?
+            .into(),
+    };
+    Some(Electrification {
+        id: edge.id.clone().into(),
+        voltage,
+        track_ranges: vec![ApplicableDirectionsTrackRange::new(
+            edge.id.clone(),
+            0.,
+            edge.length(),
+            ApplicableDirections::Both,
+        )],
     })
 }
 
+pub fn loading_gauge_limits(edge: &Edge, mapping: &TagMapping) -> Vec<LoadingGaugeLimit> {
+    mapping
+        .loading_gauges(|key| edge.tags.get(key).map(String::as_str))
+        .into_iter()
+        .map(|category| LoadingGaugeLimit {
+            category,
+            begin: 0.,
+            end: edge.length(),
+        })
+        .collect()
+}
+
+fn railway_stop(tags: &osmpbfreader::Tags) -> bool {
+    tags.contains("railway", "stop") || tags.contains("railway", "halt")
+}
+
+/// Operational points are built from the stop areas
+///
+/// The tag mapping can add parts along the platforms of the stop areas,
+/// and operational points for the railway stops outside of any stop area.
 pub fn operational_points(
     osm_pbf_in: &std::path::PathBuf,
     nodes_to_tracks: &NodeToTrack,
+    rail_edges: &[&Edge],
+    mapping: &TagMapping,
 ) -> Vec<OperationalPoint> {
+    let railway_stops = mapping
+        .operational_points
+        .as_ref()
+        .is_some_and(|mapping| mapping.railway_stops);
     let file = std::fs::File::open(osm_pbf_in).unwrap();
     let mut pbf = osmpbfreader::OsmPbfReader::new(file);
-    pbf.iter()
-        .flatten()
-        .filter(|obj| obj.tags().contains("public_transport", "stop_area")) // https://wiki.openstreetmap.org/wiki/Tag:public_transport%3Dstop_area
-        .flat_map(|obj| match obj {
-            osmpbfreader::OsmObj::Relation(rel) => Some(rel), // Only consider OSM relations
-            _ => None,                                        // Discard Nodes and Ways
-        })
-        .flat_map(|rel| {
-            let parts: Vec<_> = rel
-                .refs
-                .iter()
-                .filter(|r| r.role == "stop") // We ignore other members of the relation
-                .flat_map(|r| match r.member {
-                    osmpbfreader::OsmId::Node(id) => Some(id),
-                    _ => {
-                        warn!("OpenStreetMap relation ({}) has a member ({:?}) with role `stop` that isn’t a node", rel.id.0, r.member);
-                        None
-                    },
-                })
-                .flat_map(|node| {
-                    nodes_to_tracks
-                        .track_and_position(node)
-                        .map(|(track, position)| OperationalPointPart { track, position, extensions: Default::default() })
-                })
-                .collect();
-            // Parts can be empty when the stop_area references stops that are not railway (e.g. bus station)
-            if parts.is_empty() {
-                None
-            } else {
-                Some(OperationalPoint {
-                    id: rel.id.0.to_string().into(),
-                    parts,
+    let objs = match pbf.get_objs_and_deps(|obj| {
+        // https://wiki.openstreetmap.org/wiki/Tag:public_transport%3Dstop_area
+        (obj.is_relation() && obj.tags().contains("public_transport", "stop_area"))
+            || (railway_stops && obj.is_node() && railway_stop(obj.tags()))
+    }) {
+        Ok(objs) => objs,
+        Err(e) => {
+            error!("Could not read the operational points: {e}");
+            return vec![];
+        }
+    };
+
+    let mut stop_area_nodes = HashSet::new();
+    let mut operational_points = vec![];
+    for rel in objs.values().filter_map(|obj| obj.relation()) {
+        if !rel.tags.contains("public_transport", "stop_area") {
+            continue;
+        }
+        stop_area_nodes.extend(rel.refs.iter().filter_map(|r| r.member.node()));
+        let mut parts: Vec<_> = rel
+            .refs
+            .iter()
+            .filter(|r| r.role == "stop") // We ignore other members of the relation
+            .flat_map(|r| match r.member {
+                osmpbfreader::OsmId::Node(id) => Some(id),
+                _ => {
+                    warn!("OpenStreetMap relation ({}) has a member ({:?}) with role `stop` that isn’t a node", rel.id.0, r.member);
+                    None
+                },
+            })
+            .flat_map(|node| {
+                nodes_to_tracks
+                    .track_and_position(node)
+                    .map(|(track, position)| OperationalPointPart { track, position, extensions: Default::default() })
+            })
+            .collect();
+        if let Some(op_mapping) = mapping.operational_points.as_ref().filter(|m| m.platforms) {
+            for platform in rel.refs.iter().filter(|r| r.role == "platform") {
+                let coordinates = member_coordinates(&objs, platform.member);
+                for part in platform_parts(&coordinates, rail_edges, op_mapping.platform_distance) {
+                    // The stops already give a part on their track
+                    if parts.iter().all(|p| p.track != part.track) {
+                        parts.push(part);
+                    }
+                }
+            }
+        }
+        // Parts can be empty when the stop_area references stops that are not railway (e.g. bus station)
+        if !parts.is_empty() {
+            operational_points.push(OperationalPoint {
+                id: rel.id.0.to_string().into(),
+                parts,
+                extensions: OperationalPointExtensions {
+                    identifier: identifier(&rel.tags),
+                    sncf: None,
+                },
+            });
+        }
+    }
+
+    if railway_stops {
+        let stops = objs
+            .values()
+            .filter_map(|obj| obj.node())
+            .filter(|node| railway_stop(&node.tags) && !stop_area_nodes.contains(&node.id));
+        for node in stops {
+            if let Some((track, position)) = nodes_to_tracks.track_and_position(node.id) {
+                operational_points.push(OperationalPoint {
+                    id: format!("stop-{}", node.id.0).into(),
+                    parts: vec![OperationalPointPart {
+                        track,
+                        position,
+                        extensions: Default::default(),
+                    }],
                     extensions: OperationalPointExtensions {
-                        identifier: identifier(&rel.tags),
+                        identifier: identifier(&node.tags),
                         sncf: None,
                     },
-                })
+                });
             }
-        })
-        .collect()
+        }
+    }
+    operational_points
 }
 
 fn identifier(tags: &osmpbfreader::Tags) -> Option<OperationalPointIdentifierExtension> {
@@ -500,6 +597,7 @@ fn identifier(tags: &osmpbfreader::Tags) -> Option<OperationalPointIdentifierExt
 
 #[cfg(test)]
 mod tests {
+    use editoast_schemas::rolling_stock::LoadingGaugeType;
     use osm4routing::Coord;
     use rstest::rstest;
 
@@ -562,7 +660,7 @@ mod tests {
             ..Default::default()
         };
 
-        let electrification = electrifications(&edge).unwrap();
+        let electrification = electrifications(&edge, &TagMapping::default()).unwrap();
 
         assert_eq!(electrification.voltage, expected.into());
     }
@@ -574,8 +672,45 @@ mod tests {
             ..Default::default()
         };
 
-        let electrification = electrifications(&edge);
+        let electrification = electrifications(&edge, &TagMapping::default());
 
         assert!(electrification.is_none());
     }
+
+    #[test]
+    fn test_mapped_voltage_and_loading_gauges() {
+        let mapping: TagMapping = serde_yaml::from_str(
+            r#"
+            loading_gauges:
+              - tags: { loading_gauge: GB }
+                category: GB
+              - tags: { gauge: "1435" }
+                category: G1
+            electrifications:
+              - tags: { electrified: contact_line, frequency: "50" }
+                voltage: 25000V
+            "#,
+        )
+        .unwrap();
+        let edge = Edge {
+            id: "1".into(),
+            tags: HashMap::from([
+                ("electrified".into(), "contact_line".into()),
+                ("frequency".into(), "50".into()),
+                ("voltage".into(), "1500".into()),
+                ("loading_gauge".into(), "GB".into()),
+                ("gauge".into(), "1435".into()),
+            ]),
+            ..Default::default()
+        };
+
+        let electrification = electrifications(&edge, &mapping).unwrap();
+        let categories: Vec<_> = loading_gauge_limits(&edge, &mapping)
+            .into_iter()
+            .map(|limit| limit.category)
+            .collect();
+
+        assert_eq!(electrification.voltage, "25000V".into());
+        assert_eq!(categories, vec![LoadingGaugeType::GB, LoadingGaugeType::G1]);
+    }
 }
@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use editoast_schemas::infra::BufferStop;
+use editoast_schemas::infra::Detector;
+use editoast_schemas::infra::Electrification;
+use editoast_schemas::infra::OperationalPoint;
+use editoast_schemas::infra::OperationalPointExtensions;
+use editoast_schemas::infra::OperationalPointIdentifierExtension;
+use editoast_schemas::infra::OperationalPointPart;
+use editoast_schemas::infra::RailJson;
+use editoast_schemas::infra::Signal;
+use editoast_schemas::infra::Speed;
+use editoast_schemas::infra::SpeedSection;
+use editoast_schemas::infra::Switch;
+use roxmltree::Node;
+
+use crate::report::ConversionReport;
+use crate::topology::Topology;
+use crate::utils::*;
+
+/// railML switch types having the same layout as a RailJSON point switch
+const POINT_SWITCH_TYPES: [&str; 3] = [
+    "ordinarySwitch",
+    "insideCurvedSwitch",
+    "outsideCurvedSwitch",
+];
+
+/// Converts the `functionalInfrastructure` of a railML infrastructure
+///
+/// Net relations which are not branches of a switch become links.
+pub fn convert(
+    functional_infrastructure: Option<Node>,
+    topology: &Topology,
+    electrification_systems: &HashMap<&str, f64>,
+    railjson: &mut RailJson,
+    report: &mut ConversionReport,
+) {
+    let mut switch_relations = HashSet::new();
+    for collection in functional_infrastructure
+        .iter()
+        .flat_map(|node| node.children())
+        .filter(|node| node.is_element())
+    {
+        for element in collection.children().filter(|node| node.is_element()) {
+            match (collection.tag_name().name(), element.tag_name().name()) {
+                ("bufferStops", "bufferStop") => railjson
+                    .buffer_stops
+                    .extend(buffer_stop(element, topology, report)),
+                ("signalsIS", "signalIS") => {
+                    railjson.signals.extend(signal(element, topology, report))
+                }
+                ("trainDetectionElements", "trainDetectionElement") => railjson
+                    .detectors
+                    .extend(detector(element, topology, report)),
+                ("switchesIS", "switchIS") => {
+                    for branch in ["leftBranch", "rightBranch"] {
+                        switch_relations.extend(
+                            children(element, branch).filter_map(|b| b.attribute("netRelationRef")),
+                        );
+                    }
+                    railjson.switches.extend(switch(element, topology, report))
+                }
+                ("speeds", "speedSection") => railjson
+                    .speed_sections
+                    .extend(speed_section(element, topology, report)),
+                ("operationalPoints", "operationalPoint") => railjson
+                    .operational_points
+                    .extend(operational_point(element, topology, report)),
+                ("electrificationSections", "electrificationSection") => {
+                    railjson.electrifications.extend(electrification(
+                        element,
+                        topology,
+                        electrification_systems,
+                        report,
+                    ))
+                }
+                _ => report.unmapped(element, "unsupported element"),
+            }
+        }
+    }
+
+    railjson.switches.extend(
+        topology
+            .relations
+            .iter()
+            .filter(|relation| {
+                relation.navigable && !switch_relations.contains(relation.id.as_str())
+            })
+            .map(|relation| Switch {
+                id: relation.id.as_str().into(),
+                switch_type: "link".into(),
+                ports: [
+                    ("A".into(), relation.a.clone()),
+                    ("B".into(), relation.b.clone()),
+                ]
+                .into(),
+                group_change_delay: 0.,
+                ..Default::default()
+            }),
+    );
+}
+
+/// The id of a railML element, reporting the element if it has none
+fn element_id<'a>(element: Node<'a, '_>, report: &mut ConversionReport) -> Option<&'a str> {
+    let id = element.attribute("id");
+    if id.is_none() {
+        report.unmapped(element, "missing id");
+    }
+    id
+}
+
+fn buffer_stop(
+    element: Node,
+    topology: &Topology,
+    report: &mut ConversionReport,
+) -> Option<BufferStop> {
+    let id = element_id(element, report)?;
+    let location = topology.spot_location(element, report)?;
+    Some(BufferStop {
+        id: id.into(),
+        track: location.track,
+        position: location.position,
+        ..Default::default()
+    })
+}
+
+fn signal(element: Node, topology: &Topology, report: &mut ConversionReport) -> Option<Signal> {
+    let id = element_id(element, report)?;
+    let location = topology.spot_location(element, report)?;
+    let Some(direction) = location.direction() else {
+        report.unmapped(
+            element,
+            "applicationDirection must be 'normal' or 'reverse'",
+        );
+        return None;
+    };
+    Some(Signal {
+        id: id.into(),
+        track: location.track,
+        position: location.position,
+        direction,
+        ..Default::default()
+    })
+}
+
+fn detector(element: Node, topology: &Topology, report: &mut ConversionReport) -> Option<Detector> {
+    let id = element_id(element, report)?;
+    let location = topology.spot_location(element, report)?;
+    Some(Detector {
+        id: id.into(),
+        track: location.track,
+        position: location.position,
+        ..Default::default()
+    })
+}
+
+/// Converts a railML switch to a point switch
+///
+/// The switch is located at the end of the netElement leading to its branches. The branch
+/// given by `continueCourse` becomes `B1`.
+fn switch(element: Node, topology: &Topology, report: &mut ConversionReport) -> Option<Switch> {
+    let id = element_id(element, report)?;
+    let switch_type = element.attribute("type").unwrap_or("ordinarySwitch");
+    if !POINT_SWITCH_TYPES.contains(&switch_type) {
+        report.unmapped(element, format!("unsupported switch type '{switch_type}'"));
+        return None;
+    }
+    let location = topology.spot_location(element, report)?;
+    let Some(start) = location.endpoint() else {
+        report.unmapped(element, "not located at the end of a netElement");
+        return None;
+    };
+
+    let mut branches = vec![];
+    for branch in ["leftBranch", "rightBranch"] {
+        let Some(relation) = child(element, branch).and_then(|b| b.attribute("netRelationRef"))
+        else {
+            report.unmapped(element, format!("missing {branch}"));
+            return None;
+        };
+        let Some(end) = topology
+            .relation(relation)
+            .and_then(|relation| relation.other_end(&start))
+        else {
+            report.unmapped(
+                element,
+                format!("netRelation '{relation}' does not start at the switch"),
+            );
+            return None;
+        };
+        branches.push(end.clone());
+    }
+    if element.attribute("continueCourse") == Some("right") {
+        branches.reverse();
+    }
+    let [b1, b2]: [_; 2] = branches.try_into().unwrap();
+
+    Some(Switch {
+        id: id.into(),
+        switch_type: "point_switch".into(),
+        ports: [("A".into(), start), ("B1".into(), b1), ("B2".into(), b2)].into(),
+        group_change_delay: 4.,
+        ..Default::default()
+    })
+}
+
+fn speed_section(
+    element: Node,
+    topology: &Topology,
+    report: &mut ConversionReport,
+) -> Option<SpeedSection> {
+    let id = element_id(element, report)?;
+    let Some(max_speed) = f64_attribute(element, "maxSpeed").filter(|speed| *speed > 0.) else {
+        report.unmapped(element, "no valid maxSpeed");
+        return None;
+    };
+    let track_ranges = topology.track_ranges(element, report);
+    if track_ranges.is_empty() {
+        return None;
+    }
+    Some(SpeedSection {
+        id: id.into(),
+        // railML speeds are in km/h
+        speed_limit: Some(Speed(max_speed / 3.6)),
+        track_ranges,
+        ..Default::default()
+    })
+}
+
+fn operational_point(
+    element: Node,
+    topology: &Topology,
+    report: &mut ConversionReport,
+) -> Option<OperationalPoint> {
+    let id = element_id(element, report)?;
+    let parts: Vec<_> = topology
+        .spot_locations(element, report)
+        .into_iter()
+        .map(|location| OperationalPointPart {
+            track: location.track,
+            position: location.position,
+            ..Default::default()
+        })
+        .collect();
+    if parts.is_empty() {
+        return None;
+    }
+    let uic = children(element, "designator")
+        .filter(|designator| {
+            designator
+                .attribute("register")
+                .is_some_and(|register| register.to_uppercase().contains("UIC"))
+        })
+        .find_map(|designator| designator.attribute("entry")?.trim().parse().ok())
+        .unwrap_or_default();
+    Some(OperationalPoint {
+        id: id.into(),
+        parts,
+        extensions: OperationalPointExtensions {
+            identifier: name(element).map(|name| OperationalPointIdentifierExtension {
+                name: name.into(),
+                uic,
+            }),
+            ..Default::default()
+        },
+    })
+}
+
+fn electrification(
+    element: Node,
+    topology: &Topology,
+    electrification_systems: &HashMap<&str, f64>,
+    report: &mut ConversionReport,
+) -> Option<Electrification> {
+    let id = element_id(element, report)?;
+    let system = child(element, "electrificationSystemRef").and_then(|s| s.attribute("ref"));
+    let Some(voltage) = system.and_then(|system| electrification_systems.get(system)) else {
+        report.unmapped(element, "no electrification system with a voltage");
+        return None;
+    };
+    let track_ranges = topology.track_ranges(element, report);
+    if track_ranges.is_empty() {
+        return None;
+    }
+    Some(Electrification {
+        id: id.into(),
+        voltage: format!("{voltage}V").into(),
+        track_ranges,
+    })
+}
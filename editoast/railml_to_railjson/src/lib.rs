@@ -0,0 +1,11 @@
+mod functional_infrastructure;
+mod railml_to_railjson;
+mod report;
+mod topology;
+mod utils;
+
+pub use railml_to_railjson::parse_railml;
+pub use railml_to_railjson::railml_to_railjson;
+pub use railml_to_railjson::RailMlError;
+pub use report::ConversionReport;
+pub use report::UnmappedElement;
@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::PathBuf;
+
+use editoast_schemas::infra::RailJson;
+use roxmltree::Document;
+use roxmltree::Node;
+use thiserror::Error;
+use tracing::info;
+
+use crate::functional_infrastructure;
+use crate::report::ConversionReport;
+use crate::topology::Topology;
+use crate::utils::*;
+
+#[derive(Debug, Error)]
+pub enum RailMlError {
+    #[error("invalid XML: {0}")]
+    Xml(#[from] roxmltree::Error),
+    #[error("the root element must be 'railML', found '{0}'")]
+    NotRailMl(String),
+    #[error("unsupported railML version '{0}', expected 3.x")]
+    UnsupportedVersion(String),
+    #[error("no infrastructure topology found")]
+    MissingTopology,
+    #[error("unsupported coordinate reference system '{0}', only WGS 84 (EPSG:4326) is supported")]
+    UnsupportedCrs(String),
+}
+
+/// Identifiers of WGS 84, the only coordinate reference system supported
+const WGS84_CRS: [&str; 4] = [
+    "EPSG:4326",
+    "urn:ogc:def:crs:EPSG::4326",
+    "http://www.opengis.net/def/crs/EPSG/0/4326",
+    "WGS84",
+];
+
+/// Run the railml-to-railjson subcommand
+/// Converts a railML 3 infrastructure file to railjson
+pub fn railml_to_railjson(
+    railml_in: PathBuf,
+    railjson_out: PathBuf,
+) -> Result<ConversionReport, Box<dyn Error + Send + Sync>> {
+    info!(
+        "🗺️ Converting {} to {}",
+        railml_in.display(),
+        railjson_out.display()
+    );
+    let railml = std::fs::read_to_string(railml_in)?;
+    let (railjson, report) = parse_railml(&railml)?;
+    let file = std::fs::File::create(railjson_out)?;
+    serde_json::to_writer(file, &railjson)?;
+    Ok(report)
+}
+
+/// Converts the infrastructure of a railML 3 document
+///
+/// Only the micro level of the topology is converted. Routes are not generated.
+pub fn parse_railml(railml: &str) -> Result<(RailJson, ConversionReport), RailMlError> {
+    let document = Document::parse(railml)?;
+    let root = document.root_element();
+    if root.tag_name().name() != "railML" {
+        return Err(RailMlError::NotRailMl(root.tag_name().name().to_owned()));
+    }
+    if let Some(version) = root.attribute("version") {
+        if !version.starts_with("3.") {
+            return Err(RailMlError::UnsupportedVersion(version.to_owned()));
+        }
+    }
+
+    let infrastructure = child(root, "infrastructure").ok_or(RailMlError::MissingTopology)?;
+    let topology = child(infrastructure, "topology").ok_or(RailMlError::MissingTopology)?;
+    check_coordinate_systems(root, topology)?;
+    let electrification_systems: HashMap<_, _> = root
+        .descendants()
+        .filter(|node| node.tag_name().name() == "electrificationSystem")
+        .filter_map(|system| Some((system.attribute("id")?, f64_attribute(system, "voltage")?)))
+        .collect();
+
+    let mut report = ConversionReport::default();
+    let topology = Topology::parse(topology, &mut report);
+    let mut railjson = RailJson {
+        track_sections: topology.track_sections.clone(),
+        ..Default::default()
+    };
+    functional_infrastructure::convert(
+        child(infrastructure, "functionalInfrastructure"),
+        &topology,
+        &electrification_systems,
+        &mut railjson,
+        &mut report,
+    );
+    info!(
+        "🗺️ Converted {} track sections, {} elements could not be mapped",
+        railjson.track_sections.len(),
+        report.unmapped_elements.len()
+    );
+    Ok((railjson, report))
+}
+
+/// Ensures the geometric coordinates of the topology are expressed in WGS 84
+///
+/// Coordinates whose positioning system is not declared or has no `crsDefinition` are assumed to be.
+fn check_coordinate_systems(root: Node, topology: Node) -> Result<(), RailMlError> {
+    let systems: HashMap<_, _> = root
+        .descendants()
+        .filter(|node| node.tag_name().name() == "geometricPositioningSystem")
+        .filter_map(|system| Some((system.attribute("id")?, system.attribute("crsDefinition")?)))
+        .collect();
+    for coordinate in topology
+        .descendants()
+        .filter(|node| node.tag_name().name() == "geometricCoordinate")
+    {
+        let Some(crs) = coordinate
+            .attribute("positioningSystemRef")
+            .and_then(|system| systems.get(system))
+        else {
+            continue;
+        };
+        if !WGS84_CRS
+            .iter()
+            .any(|wgs84| wgs84.eq_ignore_ascii_case(crs.trim()))
+        {
+            return Err(RailMlError::UnsupportedCrs(crs.to_string()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use editoast_schemas::infra::ApplicableDirections;
+    use editoast_schemas::infra::Direction;
+    use editoast_schemas::infra::Endpoint;
+    use editoast_schemas::infra::TrackEndpoint;
+
+    use super::*;
+    use crate::report::UnmappedElement;
+
+    fn parse_simple() -> (RailJson, ConversionReport) {
+        parse_railml(include_str!("tests/simple.railml")).unwrap()
+    }
+
+    #[test]
+    fn convert_railml_to_railjson() {
+        let output = tempfile::NamedTempFile::new().unwrap();
+        let report =
+            railml_to_railjson("src/tests/simple.railml".into(), output.path().into()).unwrap();
+
+        let data = std::fs::read_to_string(output.path()).unwrap();
+        let railjson: RailJson = serde_json::from_str(&data).unwrap();
+        assert_eq!(4, railjson.track_sections.len());
+        assert!(!report.unmapped_elements.is_empty());
+    }
+
+    #[test]
+    fn parse_track_sections() {
+        let (railjson, _) = parse_simple();
+        let track = &railjson.track_sections[0];
+        assert_eq!("ne_1", track.id.as_str());
+        assert_eq!(1000., track.length);
+        // The length is computed from the geometry when missing
+        let track = &railjson.track_sections[3];
+        assert_eq!("ne_4", track.id.as_str());
+        assert!((track.length - 744.).abs() < 1.);
+    }
+
+    #[test]
+    fn parse_switches() {
+        let (railjson, _) = parse_simple();
+        assert_eq!(2, railjson.switches.len());
+
+        let switch = &railjson.switches[0];
+        assert_eq!("point_switch", switch.switch_type.as_str());
+        assert_eq!(
+            TrackEndpoint::new("ne_1", Endpoint::End),
+            switch.ports[&"A".into()]
+        );
+        assert_eq!(
+            TrackEndpoint::new("ne_3", Endpoint::Begin),
+            switch.ports[&"B1".into()]
+        );
+        assert_eq!(
+            TrackEndpoint::new("ne_2", Endpoint::Begin),
+            switch.ports[&"B2".into()]
+        );
+
+        let link = &railjson.switches[1];
+        assert_eq!("link", link.switch_type.as_str());
+        assert_eq!("nr_2_4", link.id.as_str());
+    }
+
+    #[test]
+    fn parse_located_elements() {
+        let (railjson, _) = parse_simple();
+        assert_eq!(3, railjson.buffer_stops.len());
+        assert_eq!(1, railjson.detectors.len());
+        assert_eq!(1, railjson.signals.len());
+        let signal = &railjson.signals[0];
+        assert_eq!(500., signal.position);
+        assert_eq!(Direction::StartToStop, signal.direction);
+    }
+
+    #[test]
+    fn parse_speed_sections() {
+        let (railjson, _) = parse_simple();
+        assert_eq!(1, railjson.speed_sections.len());
+        let speed_section = &railjson.speed_sections[0];
+        assert!((speed_section.speed_limit.unwrap().0 - 100. / 3.6).abs() < 1e-9);
+        assert_eq!(2, speed_section.track_ranges.len());
+        let range = &speed_section.track_ranges[1];
+        assert_eq!(
+            ("ne_2", 0., 400.),
+            (range.track.as_str(), range.begin, range.end)
+        );
+        assert_eq!(
+            ApplicableDirections::StopToStart,
+            range.applicable_directions
+        );
+    }
+
+    #[test]
+    fn parse_operational_points_and_electrifications() {
+        let (railjson, _) = parse_simple();
+        assert_eq!(1, railjson.operational_points.len());
+        let identifier = railjson.operational_points[0]
+            .extensions
+            .identifier
+            .as_ref()
+            .unwrap();
+        assert_eq!("Atlantis", identifier.name.as_str());
+        assert_eq!(8700001, identifier.uic);
+        assert_eq!(1, railjson.electrifications.len());
+        assert_eq!("25000V", railjson.electrifications[0].voltage.as_str());
+    }
+
+    #[test]
+    fn report_unmapped_elements() {
+        let (_, report) = parse_simple();
+        assert_eq!(
+            report.unmapped_elements,
+            vec![
+                UnmappedElement {
+                    element: "trainDetectionElement".to_owned(),
+                    id: None,
+                    reason: "missing id".to_owned(),
+                },
+                UnmappedElement {
+                    element: "signalIS".to_owned(),
+                    id: Some("sig_both".to_owned()),
+                    reason: "applicationDirection must be 'normal' or 'reverse'".to_owned(),
+                },
+                UnmappedElement {
+                    element: "operationalPoint".to_owned(),
+                    id: Some("op_far".to_owned()),
+                    reason: "unknown netElement 'ne_unknown'".to_owned(),
+                },
+                UnmappedElement {
+                    element: "levelCrossingIS".to_owned(),
+                    id: Some("lc_1".to_owned()),
+                    reason: "unsupported element".to_owned(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reject_other_documents() {
+        assert!(matches!(
+            parse_railml("<osm/>"),
+            Err(RailMlError::NotRailMl(_))
+        ));
+        assert!(matches!(
+            parse_railml(r#"<railML version="2.4"/>"#),
+            Err(RailMlError::UnsupportedVersion(_))
+        ));
+        assert!(matches!(
+            parse_railml(r#"<railML version="3.2"/>"#),
+            Err(RailMlError::MissingTopology)
+        ));
+    }
+
+    #[test]
+    fn reject_other_coordinate_systems() {
+        let railml = include_str!("tests/simple.railml").replace("EPSG:4326", "EPSG:2154");
+        assert!(matches!(
+            parse_railml(&railml),
+            Err(RailMlError::UnsupportedCrs(crs)) if crs == "EPSG:2154"
+        ));
+    }
+}
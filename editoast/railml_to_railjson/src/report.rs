@@ -0,0 +1,29 @@
+use roxmltree::Node;
+use serde::Serialize;
+
+/// Lists the railML elements that could not be converted to RailJSON
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct ConversionReport {
+    pub unmapped_elements: Vec<UnmappedElement>,
+}
+
+/// A railML element left out of the converted infrastructure
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UnmappedElement {
+    /// The name of the railML element, e.g. `levelCrossingIS`
+    pub element: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Why the element was not mapped
+    pub reason: String,
+}
+
+impl ConversionReport {
+    pub fn unmapped<T: AsRef<str>>(&mut self, node: Node, reason: T) {
+        self.unmapped_elements.push(UnmappedElement {
+            element: node.tag_name().name().to_owned(),
+            id: node.attribute("id").map(str::to_owned),
+            reason: reason.as_ref().to_owned(),
+        });
+    }
+}
@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use editoast_schemas::infra::ApplicableDirections;
+use editoast_schemas::infra::ApplicableDirectionsTrackRange;
+use editoast_schemas::infra::Direction;
+use editoast_schemas::infra::Endpoint;
+use editoast_schemas::infra::TrackEndpoint;
+use editoast_schemas::infra::TrackSection;
+use editoast_schemas::primitives::Identifier;
+use geos::geojson::Geometry;
+use geos::geojson::Value::LineString;
+use roxmltree::Node;
+
+use crate::report::ConversionReport;
+use crate::utils::*;
+
+/// Tolerance on intrinsic coordinates to consider a location is at the end of a netElement
+const ENDPOINT_TOLERANCE: f64 = 1e-6;
+
+/// A connection between the ends of two netElements
+#[derive(Debug, Clone)]
+pub struct NetRelation {
+    pub id: String,
+    pub a: TrackEndpoint,
+    pub b: TrackEndpoint,
+    /// Whether trains can go from one netElement to the other
+    pub navigable: bool,
+}
+
+impl NetRelation {
+    /// Given one end of the relation, returns the other one
+    pub fn other_end(&self, endpoint: &TrackEndpoint) -> Option<&TrackEndpoint> {
+        if &self.a == endpoint {
+            Some(&self.b)
+        } else if &self.b == endpoint {
+            Some(&self.a)
+        } else {
+            None
+        }
+    }
+}
+
+/// A location on a track section
+#[derive(Debug, Clone)]
+pub struct SpotLocation<'a> {
+    pub track: Identifier,
+    pub position: f64,
+    /// The intrinsic coordinate on the netElement, between 0 and 1
+    pub intrinsic_coord: f64,
+    pub application_direction: Option<&'a str>,
+}
+
+impl SpotLocation<'_> {
+    /// The end of the track section the location is at, if any
+    pub fn endpoint(&self) -> Option<TrackEndpoint> {
+        if self.intrinsic_coord <= ENDPOINT_TOLERANCE {
+            Some(TrackEndpoint::new(&self.track, Endpoint::Begin))
+        } else if self.intrinsic_coord >= 1. - ENDPOINT_TOLERANCE {
+            Some(TrackEndpoint::new(&self.track, Endpoint::End))
+        } else {
+            None
+        }
+    }
+
+    pub fn direction(&self) -> Option<Direction> {
+        match self.application_direction {
+            Some("normal") => Some(Direction::StartToStop),
+            Some("reverse") => Some(Direction::StopToStart),
+            _ => None,
+        }
+    }
+}
+
+/// The micro level topology of a railML infrastructure
+///
+/// Each netElement of the micro level becomes a track section, its intrinsic coordinates
+/// are used to locate the functional infrastructure on the track sections.
+#[derive(Debug, Default)]
+pub struct Topology {
+    pub track_sections: Vec<TrackSection>,
+    pub relations: Vec<NetRelation>,
+    lengths: HashMap<String, f64>,
+    /// netElements of the meso and macro levels, which are aggregations of micro ones
+    aggregated_elements: HashSet<String>,
+}
+
+impl Topology {
+    pub fn parse(topology: Node, report: &mut ConversionReport) -> Self {
+        let mut result = Self::default();
+        for net_elements in children(topology, "netElements") {
+            for net_element in children(net_elements, "netElement") {
+                result.parse_net_element(net_element, report);
+            }
+        }
+        for net_relations in children(topology, "netRelations") {
+            for net_relation in children(net_relations, "netRelation") {
+                result.parse_net_relation(net_relation, report);
+            }
+        }
+        result
+    }
+
+    fn parse_net_element(&mut self, net_element: Node, report: &mut ConversionReport) {
+        let Some(id) = net_element.attribute("id") else {
+            report.unmapped(net_element, "missing id");
+            return;
+        };
+        if child(net_element, "elementCollectionUnordered").is_some()
+            || child(net_element, "elementCollectionOrdered").is_some()
+        {
+            self.aggregated_elements.insert(id.to_owned());
+            return;
+        }
+
+        let mut coordinates: Vec<(f64, Vec<f64>)> =
+            children(net_element, "associatedPositioningSystem")
+                .flat_map(|system| children(system, "intrinsicCoordinate"))
+                .filter_map(|coordinate| {
+                    let intrinsic_coord = f64_attribute(coordinate, "intrinsicCoord")?;
+                    let geometric = child(coordinate, "geometricCoordinate")?;
+                    let x = f64_attribute(geometric, "x")?;
+                    let y = f64_attribute(geometric, "y")?;
+                    Some((intrinsic_coord, vec![x, y]))
+                })
+                .collect();
+        coordinates.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        let coordinates: Vec<_> = coordinates.into_iter().map(|(_, point)| point).collect();
+        if coordinates.len() < 2 {
+            report.unmapped(net_element, "less than two geometric coordinates");
+            return;
+        }
+
+        let length =
+            f64_attribute(net_element, "length").unwrap_or_else(|| geo_length(&coordinates));
+        if length <= 0. {
+            report.unmapped(net_element, "null length");
+            return;
+        }
+        self.lengths.insert(id.to_owned(), length);
+        self.track_sections.push(TrackSection {
+            id: id.into(),
+            length,
+            geo: Geometry::new(LineString(coordinates)),
+            ..Default::default()
+        });
+    }
+
+    fn parse_net_relation(&mut self, net_relation: Node, report: &mut ConversionReport) {
+        let Some(id) = net_relation.attribute("id") else {
+            report.unmapped(net_relation, "missing id");
+            return;
+        };
+        let element_a = child(net_relation, "elementA").and_then(|e| e.attribute("ref"));
+        let element_b = child(net_relation, "elementB").and_then(|e| e.attribute("ref"));
+        let (Some(element_a), Some(element_b)) = (element_a, element_b) else {
+            report.unmapped(net_relation, "missing elementA or elementB");
+            return;
+        };
+        if self.aggregated_elements.contains(element_a)
+            || self.aggregated_elements.contains(element_b)
+        {
+            return;
+        }
+        for element in [element_a, element_b] {
+            if !self.lengths.contains_key(element) {
+                report.unmapped(net_relation, format!("unknown netElement '{element}'"));
+                return;
+            }
+        }
+        let endpoint = |position: &str| match net_relation.attribute(position) {
+            Some("0") => Some(Endpoint::Begin),
+            Some("1") => Some(Endpoint::End),
+            _ => None,
+        };
+        let (Some(position_a), Some(position_b)) =
+            (endpoint("positionOnA"), endpoint("positionOnB"))
+        else {
+            report.unmapped(net_relation, "positionOnA and positionOnB must be 0 or 1");
+            return;
+        };
+        self.relations.push(NetRelation {
+            id: id.to_owned(),
+            a: TrackEndpoint::new(element_a, position_a),
+            b: TrackEndpoint::new(element_b, position_b),
+            navigable: !matches!(net_relation.attribute("navigability"), Some("None")),
+        });
+    }
+
+    pub fn relation(&self, id: &str) -> Option<&NetRelation> {
+        self.relations.iter().find(|relation| relation.id == id)
+    }
+
+    /// Locates the `spotLocation` of an element
+    ///
+    /// Unlocated elements are added to the report.
+    pub fn spot_location<'a>(
+        &self,
+        element: Node<'a, '_>,
+        report: &mut ConversionReport,
+    ) -> Option<SpotLocation<'a>> {
+        let Some(location) = child(element, "spotLocation") else {
+            report.unmapped(element, "no spotLocation");
+            return None;
+        };
+        self.locate(element, location, report)
+    }
+
+    /// Locates all the `spotLocation` of an element
+    pub fn spot_locations<'a>(
+        &self,
+        element: Node<'a, '_>,
+        report: &mut ConversionReport,
+    ) -> Vec<SpotLocation<'a>> {
+        if child(element, "spotLocation").is_none() {
+            report.unmapped(element, "no spotLocation");
+            return vec![];
+        }
+        children(element, "spotLocation")
+            .filter_map(|location| self.locate(element, location, report))
+            .collect()
+    }
+
+    fn locate<'a>(
+        &self,
+        element: Node<'a, '_>,
+        location: Node<'a, '_>,
+        report: &mut ConversionReport,
+    ) -> Option<SpotLocation<'a>> {
+        let Some(net_element) = location.attribute("netElementRef") else {
+            report.unmapped(element, "spotLocation without netElementRef");
+            return None;
+        };
+        let Some(length) = self.lengths.get(net_element) else {
+            report.unmapped(element, format!("unknown netElement '{net_element}'"));
+            return None;
+        };
+        let Some(intrinsic_coord) = f64_attribute(location, "intrinsicCoord") else {
+            report.unmapped(element, "spotLocation without intrinsicCoord");
+            return None;
+        };
+        Some(SpotLocation {
+            track: net_element.into(),
+            position: intrinsic_coord.clamp(0., 1.) * length,
+            intrinsic_coord,
+            application_direction: location.attribute("applicationDirection"),
+        })
+    }
+
+    /// Converts the `linearLocation` of an element to track ranges
+    ///
+    /// Unlocated elements are added to the report.
+    pub fn track_ranges(
+        &self,
+        element: Node,
+        report: &mut ConversionReport,
+    ) -> Vec<ApplicableDirectionsTrackRange> {
+        let mut track_ranges = vec![];
+        for location in children(element, "linearLocation") {
+            let direction = match location.attribute("applicationDirection") {
+                Some("normal") => ApplicableDirections::StartToStop,
+                Some("reverse") => ApplicableDirections::StopToStart,
+                _ => ApplicableDirections::Both,
+            };
+            for associated in children(location, "associatedNetElement") {
+                let Some(net_element) = associated.attribute("netElementRef") else {
+                    report.unmapped(element, "associatedNetElement without netElementRef");
+                    return vec![];
+                };
+                let Some(length) = self.lengths.get(net_element) else {
+                    report.unmapped(element, format!("unknown netElement '{net_element}'"));
+                    return vec![];
+                };
+                let begin = f64_attribute(associated, "intrinsicCoordBegin").unwrap_or(0.);
+                let end = f64_attribute(associated, "intrinsicCoordEnd").unwrap_or(1.);
+                let direction = match associated.attribute("keepsOrientation") {
+                    Some("false") => match direction {
+                        ApplicableDirections::StartToStop => ApplicableDirections::StopToStart,
+                        ApplicableDirections::StopToStart => ApplicableDirections::StartToStop,
+                        ApplicableDirections::Both => ApplicableDirections::Both,
+                    },
+                    _ => direction,
+                };
+                track_ranges.push(ApplicableDirectionsTrackRange::new(
+                    net_element,
+                    begin.min(end).clamp(0., 1.) * length,
+                    begin.max(end).clamp(0., 1.) * length,
+                    direction,
+                ));
+            }
+        }
+        if track_ranges.is_empty() {
+            report.unmapped(element, "no linearLocation on the topology");
+        }
+        track_ranges
+    }
+}
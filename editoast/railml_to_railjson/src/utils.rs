@@ -0,0 +1,46 @@
+use roxmltree::Node;
+
+/// Iterates over the child elements of a node having the given (namespace-less) name
+pub fn children<'a, 'input: 'a>(
+    node: Node<'a, 'input>,
+    name: &'static str,
+) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children()
+        .filter(move |child| child.is_element() && child.tag_name().name() == name)
+}
+
+pub fn child<'a, 'input: 'a>(
+    node: Node<'a, 'input>,
+    name: &'static str,
+) -> Option<Node<'a, 'input>> {
+    children(node, name).next()
+}
+
+pub fn f64_attribute(node: Node, name: &str) -> Option<f64> {
+    node.attribute(name)
+        .and_then(|value| value.trim().parse().ok())
+}
+
+/// The value of the `name` attribute of the first `<name>` child of a node
+pub fn name(node: Node) -> Option<String> {
+    children(node, "name")
+        .filter_map(|name| name.attribute("name"))
+        .map(str::trim)
+        .find(|name| !name.is_empty())
+        .map(str::to_owned)
+}
+
+/// The length in meters of a line string of WGS 84 coordinates
+pub fn geo_length(coordinates: &[Vec<f64>]) -> f64 {
+    const EARTH_RADIUS: f64 = 6_371_008.8;
+    coordinates
+        .windows(2)
+        .map(|segment| {
+            let (lon1, lat1) = (segment[0][0].to_radians(), segment[0][1].to_radians());
+            let (lon2, lat2) = (segment[1][0].to_radians(), segment[1][1].to_radians());
+            let a = ((lat2 - lat1) / 2.).sin().powi(2)
+                + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.).sin().powi(2);
+            2. * EARTH_RADIUS * a.sqrt().asin()
+        })
+        .sum()
+}
@@ -5,6 +5,7 @@ mod telemetry_config;
 use std::env;
 use std::path::PathBuf;
 
+use chrono::NaiveDate;
 use clap::Args;
 use clap::Parser;
 use clap::Subcommand;
@@ -54,7 +55,9 @@ pub enum Commands {
     )]
     ElectricalProfiles(ElectricalProfilesCommands),
     ImportRollingStock(ImportRollingStockArgs),
+    ExportRollingStock(ExportRollingStockArgs),
     OsmToRailjson(OsmToRailjsonArgs),
+    RailmlToRailjson(RailmlToRailjsonArgs),
     #[command(about, long_about = "Prints the OpenApi of the service")]
     Openapi,
     #[command(subcommand, about, long_about = "Search engine related commands")]
@@ -63,12 +66,31 @@ pub enum Commands {
     Infra(InfraCommands),
     #[command(subcommand, about, long_about = "Timetables related commands")]
     Timetables(TimetablesCommands),
+    #[command(subcommand, about, long_about = "Users related commands")]
+    Users(UsersCommands),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum UsersCommands {
+    SetAdmin(SetAdminArgs),
+}
+
+#[derive(Args, Debug)]
+#[command(about, long_about = "Give a user every role on every resource")]
+pub struct SetAdminArgs {
+    /// The identity of the user, as forwarded by the gateway
+    pub identity: String,
+    /// Take the admin rights of the user back
+    #[arg(long)]
+    pub revoke: bool,
 }
 
 #[derive(Subcommand, Debug)]
 pub enum TimetablesCommands {
     Import(ImportTimetableArgs),
     Export(ExportTimetableArgs),
+    ExportGtfs(ExportGtfsArgs),
+    ImportFeed(ImportFeedArgs),
 }
 
 #[derive(Args, Debug, Derivative)]
@@ -92,6 +114,49 @@ pub struct ExportTimetableArgs {
     pub path: PathBuf,
 }
 
+#[derive(Args, Debug, Derivative)]
+#[derivative(Default)]
+#[command(
+    about,
+    long_about = "Import the trips of a GTFS feed or a NeTEx document as train schedules"
+)]
+pub struct ImportFeedArgs {
+    /// The timetable id on which attach the trains to
+    #[arg(long)]
+    pub id: Option<i64>,
+    /// The infrastructure id on which the stops are matched
+    #[arg(long)]
+    pub infra_id: i64,
+    /// The date of the trips to import
+    #[arg(long)]
+    pub date: NaiveDate,
+    /// A YAML or JSON file mapping route and vehicle types to rolling stock names
+    #[arg(long)]
+    pub rolling_stock_mapping: Option<PathBuf>,
+    /// The GTFS zip or NeTEx XML file path
+    pub path: PathBuf,
+}
+
+#[derive(Args, Debug, Derivative)]
+#[derivative(Default)]
+#[command(
+    about,
+    long_about = "Export the simulated trains of a given timetable as a GTFS zip"
+)]
+pub struct ExportGtfsArgs {
+    /// The timetable id on which get the train schedules from
+    pub id: i64,
+    /// The infrastructure id on which the trains are simulated
+    pub infra_id: i64,
+    /// The output file path
+    pub path: PathBuf,
+    #[derivative(Default(value = r#""http://localhost:8080".into()"#))]
+    #[clap(long, env = "OSRD_BACKEND_URL", default_value_t = String::from("http://localhost:8080"))]
+    pub backend_url: String,
+    #[clap(long, env = "OSRD_BACKEND_TOKEN", default_value_t = String::from(""))]
+    pub backend_token: String,
+}
+
 #[derive(Subcommand, Debug)]
 pub enum ElectricalProfilesCommands {
     Import(ImportProfileSetArgs),
@@ -112,6 +177,9 @@ pub enum InfraCommands {
     Clear(ClearArgs),
     Generate(GenerateArgs),
     ImportRailjson(ImportRailjsonArgs),
+    Diff(InfraDiffArgs),
+    MigrateRailjson(MigrateRailjsonArgs),
+    ExportGis(ExportGisArgs),
 }
 
 #[derive(Args, Debug, Derivative, Clone)]
@@ -152,6 +220,13 @@ pub struct RunserverArgs {
     pub root_path: String,
     #[clap(long)]
     pub workers: Option<usize>,
+    /// Accept requests which were not authenticated by the gateway, without checking any role
+    #[arg(long, env = "EDITOAST_ALLOW_ANONYMOUS")]
+    pub allow_anonymous: bool,
+    /// The number of background job workers, none are started with 0
+    #[derivative(Default(value = "2"))]
+    #[arg(long, env = "EDITOAST_JOB_WORKERS", default_value_t = 2)]
+    pub job_workers: usize,
 }
 
 #[derive(Args, Debug)]
@@ -183,6 +258,43 @@ pub struct ImportRailjsonArgs {
     pub generate: bool,
 }
 
+#[derive(Args, Debug)]
+#[command(
+    about,
+    long_about = "Upgrade a railjson file from an older format version to the current one"
+)]
+pub struct MigrateRailjsonArgs {
+    /// Railjson file path
+    pub railjson_path: PathBuf,
+    /// File to write the migrated railjson to
+    pub output: PathBuf,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GisFormat {
+    /// One GeoJSON FeatureCollection file per layer
+    Geojson,
+    /// A single GeoPackage file holding all the layers
+    Gpkg,
+}
+
+#[derive(Args, Debug)]
+#[command(
+    about,
+    long_about = "Export the generated layers of an infrastructure to GIS formats"
+)]
+pub struct ExportGisArgs {
+    /// Infrastructure ID
+    pub infra_id: i64,
+    /// Directory to write the GeoJSON files to, or GeoPackage file path
+    pub output: PathBuf,
+    #[arg(long, value_enum, default_value_t = GisFormat::Geojson)]
+    pub format: GisFormat,
+    /// The view of the layers to export
+    #[arg(long, default_value = "geo")]
+    pub view: String,
+}
+
 #[derive(Args, Debug)]
 #[command(about, long_about = "Add a set of electrical profiles")]
 pub struct ImportProfileSetArgs {
@@ -201,6 +313,25 @@ pub struct InfraCloneArgs {
     pub new_name: Option<String>,
 }
 
+#[derive(Args, Debug)]
+#[command(about, long_about = "Compare the objects of two infrastructures")]
+pub struct InfraDiffArgs {
+    /// Base infrastructure ID
+    pub base_id: i64,
+    /// ID of the infrastructure to compare the base infrastructure with
+    #[arg(required_unless_present = "versions")]
+    pub other_id: Option<i64>,
+    /// Compare two versions of the base infrastructure instead, using its edition history
+    #[arg(long, num_args = 2, value_names = ["BASE_VERSION", "OTHER_VERSION"], conflicts_with = "other_id")]
+    pub versions: Option<Vec<String>>,
+    /// Output the edition operations turning the base infrastructure into the other one
+    #[arg(long)]
+    pub operations: bool,
+    /// File to write the diff to, printed on the standard output if omitted
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
 #[derive(Args, Debug)]
 #[command(
     about,
@@ -222,11 +353,37 @@ pub struct ListProfileSetArgs {
     pub quiet: bool,
 }
 
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RollingStockFormat {
+    /// A RailJSON rolling stock file
+    Json,
+    /// A directory holding `characteristics.csv`, `effort_curves.csv` and optionally `energy_sources.csv`
+    Csv,
+    /// A railML 3 document, whose rollingstock vehicles are converted
+    Railml,
+}
+
 #[derive(Args, Debug)]
-#[command(about, long_about = "Import a rolling stock given a json file")]
+#[command(
+    about,
+    long_about = "Import a rolling stock given a json file, a directory of CSV spreadsheets or a railML file"
+)]
 pub struct ImportRollingStockArgs {
     /// Rolling stock file path
     pub rolling_stock_path: Vec<PathBuf>,
+    #[arg(long, value_enum, default_value_t = RollingStockFormat::Json)]
+    pub format: RollingStockFormat,
+}
+
+#[derive(Args, Debug)]
+#[command(about, long_about = "Export a rolling stock given its name")]
+pub struct ExportRollingStockArgs {
+    /// Rolling stock name
+    pub name: String,
+    /// The output file path, or directory for the CSV format
+    pub output: PathBuf,
+    #[arg(long, value_enum, default_value_t = RollingStockFormat::Json)]
+    pub format: RollingStockFormat,
 }
 
 #[derive(Args, Debug)]
@@ -236,6 +393,25 @@ pub struct OsmToRailjsonArgs {
     pub osm_pbf_in: PathBuf,
     /// Output file in Railjson format
     pub railjson_out: PathBuf,
+    /// YAML file configuring the conversion of the OpenStreetMap tags,
+    /// see osm_to_railjson/tag_mapping.example.yml
+    #[arg(long)]
+    pub tag_mapping: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+#[command(
+    about,
+    long_about = "Extracts a railjson from a railML 3 infrastructure"
+)]
+pub struct RailmlToRailjsonArgs {
+    /// Input file in the railML 3 format
+    pub railml_in: PathBuf,
+    /// Output file in Railjson format
+    pub railjson_out: PathBuf,
+    /// File to write the conversion report to, in JSON
+    #[arg(long)]
+    pub report: Option<PathBuf>,
 }
 
 #[derive(Args, Debug)]
@@ -46,10 +46,10 @@ pub struct PropertyValuesF64 {
 pub struct PropertyElectrificationValues {
     /// List of `n` boundaries of the ranges.
     /// A boundary is a distance from the beginning of the path in mm.
-    boundaries: Vec<u64>,
+    pub boundaries: Vec<u64>,
     #[schema(inline)]
     /// List of `n+1` values associated to the ranges
-    values: Vec<PropertyElectrificationValue>,
+    pub values: Vec<PropertyElectrificationValue>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -68,14 +68,14 @@ pub enum PropertyElectrificationValue {
 pub struct OperationalPointOnPath {
     /// Id of the operational point
     #[schema(inline)]
-    id: Identifier,
+    pub id: Identifier,
     /// The part along the path
-    part: OperationalPointPart,
+    pub part: OperationalPointPart,
     /// Extensions associated to the operational point
     #[serde(default)]
-    extensions: OperationalPointExtensions,
+    pub extensions: OperationalPointExtensions,
     /// Distance from the beginning of the path in mm
-    position: u64,
+    pub position: u64,
 }
 
 impl<'a> AsCoreRequest<Json<PathPropertiesResponse>> for PathPropertiesRequest<'a> {
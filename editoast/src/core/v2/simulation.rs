@@ -242,6 +242,8 @@ pub struct SimulationRequest {
     pub options: TrainScheduleOptions,
     pub rolling_stock: PhysicsRollingStock,
     pub electrical_profile_set_id: Option<i64>,
+    /// The version of the electrical profile set, so that core reloads the set once it is edited
+    pub electrical_profile_set_version: Option<i64>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
@@ -81,16 +81,21 @@ pub trait GeneratedData {
     ) -> Result<()>;
 }
 
+/// The number of layers generated for each infra
+pub const LAYER_COUNT: usize = 12;
+
 /// Refresh all the generated data of a given infra
+///
+/// `on_layer` is called with the table name of each layer once it is generated.
 pub async fn refresh_all(
     db_pool: Arc<DbConnectionPool>,
     infra: i64,
     infra_cache: &InfraCache,
+    on_layer: &(dyn Fn(&'static str) + Sync),
 ) -> Result<()> {
     // The other layers depend on track section layer.
     // We must wait until its completion before running the other requests in parallel
-    TrackSectionLayer::refresh_pool(db_pool.clone(), infra, infra_cache).await?;
-    debug!("⚙️ Infra {infra}: track section layer is generated");
+    refresh_layer::<TrackSectionLayer>(db_pool.clone(), infra, infra_cache, on_layer).await?;
     let mut conn = db_pool.get().await?;
     // The analyze step significantly improves the performance when importing and generating together
     // It doesn’t seem to make a different when the generation step is ran separately
@@ -98,21 +103,31 @@ pub async fn refresh_all(
     sql_query("analyze").execute(&mut conn).await?;
     debug!("⚙️ Infra {infra}: database analyzed");
     futures::try_join!(
-        SpeedSectionLayer::refresh_pool(db_pool.clone(), infra, infra_cache),
-        SignalLayer::refresh_pool(db_pool.clone(), infra, infra_cache),
-        SwitchLayer::refresh_pool(db_pool.clone(), infra, infra_cache),
-        BufferStopLayer::refresh_pool(db_pool.clone(), infra, infra_cache),
-        ElectrificationLayer::refresh_pool(db_pool.clone(), infra, infra_cache),
-        DetectorLayer::refresh_pool(db_pool.clone(), infra, infra_cache),
-        OperationalPointLayer::refresh_pool(db_pool.clone(), infra, infra_cache),
-        PSLSignLayer::refresh_pool(db_pool.clone(), infra, infra_cache),
-        NeutralSectionLayer::refresh_pool(db_pool.clone(), infra, infra_cache),
-        NeutralSignLayer::refresh_pool(db_pool.clone(), infra, infra_cache),
+        refresh_layer::<SpeedSectionLayer>(db_pool.clone(), infra, infra_cache, on_layer),
+        refresh_layer::<SignalLayer>(db_pool.clone(), infra, infra_cache, on_layer),
+        refresh_layer::<SwitchLayer>(db_pool.clone(), infra, infra_cache, on_layer),
+        refresh_layer::<BufferStopLayer>(db_pool.clone(), infra, infra_cache, on_layer),
+        refresh_layer::<ElectrificationLayer>(db_pool.clone(), infra, infra_cache, on_layer),
+        refresh_layer::<DetectorLayer>(db_pool.clone(), infra, infra_cache, on_layer),
+        refresh_layer::<OperationalPointLayer>(db_pool.clone(), infra, infra_cache, on_layer),
+        refresh_layer::<PSLSignLayer>(db_pool.clone(), infra, infra_cache, on_layer),
+        refresh_layer::<NeutralSectionLayer>(db_pool.clone(), infra, infra_cache, on_layer),
+        refresh_layer::<NeutralSignLayer>(db_pool.clone(), infra, infra_cache, on_layer),
     )?;
-    debug!("⚙️ Infra {infra}: object layers is generated");
     // The error layer depends on the other layers and must be executed at the end.
-    ErrorLayer::refresh_pool(db_pool.clone(), infra, infra_cache).await?;
-    debug!("⚙️ Infra {infra}: errors layer is generated");
+    refresh_layer::<ErrorLayer>(db_pool.clone(), infra, infra_cache, on_layer).await?;
+    Ok(())
+}
+
+async fn refresh_layer<T: GeneratedData + Send>(
+    db_pool: Arc<DbConnectionPool>,
+    infra: i64,
+    infra_cache: &InfraCache,
+    on_layer: &(dyn Fn(&'static str) + Sync),
+) -> Result<()> {
+    T::refresh_pool(db_pool, infra, infra_cache).await?;
+    debug!("⚙️ Infra {infra}: {} layer is generated", T::table_name());
+    on_layer(T::table_name());
     Ok(())
 }
 
@@ -170,9 +185,11 @@ pub mod tests {
     async fn refresh_all_test() {
         test_infra_transaction(|_conn, infra| {
             async move {
-                assert!(refresh_all(db_pool(), infra.id, &Default::default())
-                    .await
-                    .is_ok());
+                assert!(
+                    refresh_all(db_pool(), infra.id, &Default::default(), &|_| ())
+                        .await
+                        .is_ok()
+                );
             }
             .scope_boxed()
         })
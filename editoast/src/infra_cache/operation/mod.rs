@@ -5,9 +5,18 @@ mod update;
 use std::ops::Deref as _;
 
 pub use create::RailjsonObject;
+use diesel::sql_query;
+use diesel::sql_types::BigInt;
+use diesel::sql_types::Jsonb;
+use diesel::sql_types::Text;
+use diesel::OptionalExtension;
+use diesel::QueryableByName;
+use diesel_async::RunQueryDsl;
 use editoast_derive::EditoastError;
 use serde::Deserialize;
 use serde::Serialize;
+use serde_json::json;
+use serde_json::Value;
 use thiserror::Error;
 pub use update::UpdateOperation;
 use utoipa::ToSchema;
@@ -15,9 +24,11 @@ use utoipa::ToSchema;
 pub use self::delete::DeleteOperation;
 use crate::error::Result;
 use crate::infra_cache::ObjectCache;
+use crate::modelsv2::get_table;
 use crate::modelsv2::DbConnection;
 use editoast_schemas::primitives::OSRDObject as _;
 use editoast_schemas::primitives::ObjectRef;
+use editoast_schemas::primitives::ObjectType;
 
 editoast_common::schemas! { Operation, }
 
@@ -65,6 +76,15 @@ impl CacheOperation {
 }
 
 impl Operation {
+    /// The object targeted by the operation
+    pub fn object_ref(&self) -> ObjectRef {
+        match self {
+            Operation::Create(railjson_object) => railjson_object.get_ref(),
+            Operation::Update(update) => ObjectRef::new(update.obj_type, &update.obj_id),
+            Operation::Delete(deletion) => ObjectRef::from(deletion.clone()),
+        }
+    }
+
     pub async fn apply(
         &self,
         infra_id: i64,
@@ -85,6 +105,80 @@ impl Operation {
             }
         }
     }
+
+    /// Apply the operation and return the operation undoing it
+    ///
+    /// The inverse operation is computed from the state of the object stored in database
+    /// before the operation is applied.
+    pub async fn apply_reversible(
+        &self,
+        infra_id: i64,
+        conn: &mut DbConnection,
+    ) -> Result<(Option<RailjsonObject>, Operation)> {
+        let inverse = match self {
+            Operation::Create(railjson_object) => {
+                Operation::Delete(railjson_object.get_ref().into())
+            }
+            Operation::Update(update) => {
+                let before =
+                    load_object_data(infra_id, update.obj_type, &update.obj_id, conn).await?;
+                let mut after = before.clone();
+                json_patch::patch(&mut after, &update.railjson_patch).map_err(|err| {
+                    OperationError::InvalidPatch {
+                        error: err.to_string(),
+                    }
+                })?;
+                Operation::Update(UpdateOperation {
+                    obj_id: update.obj_id.clone(),
+                    obj_type: update.obj_type,
+                    railjson_patch: json_patch::diff(&after, &before),
+                })
+            }
+            Operation::Delete(deletion) => {
+                let before =
+                    load_object_data(infra_id, deletion.obj_type, &deletion.obj_id, conn).await?;
+                let railjson_object: RailjsonObject = serde_json::from_value(json!({
+                    "railjson": before,
+                    "obj_type": deletion.obj_type.to_string(),
+                }))?;
+                Operation::Create(Box::new(railjson_object))
+            }
+        };
+        let railjson = self.apply(infra_id, conn).await?;
+        Ok((railjson, inverse))
+    }
+}
+
+#[derive(QueryableByName)]
+struct ObjectData {
+    #[diesel(sql_type = Jsonb)]
+    data: Value,
+}
+
+/// Load the raw data of an object as stored in database
+async fn load_object_data(
+    infra_id: i64,
+    obj_type: ObjectType,
+    obj_id: &str,
+    conn: &mut DbConnection,
+) -> Result<Value> {
+    let object: Option<ObjectData> = sql_query(format!(
+        "SELECT data FROM {} WHERE infra_id = $1 AND obj_id = $2",
+        get_table(&obj_type)
+    ))
+    .bind::<BigInt, _>(infra_id)
+    .bind::<Text, _>(obj_id)
+    .get_result(conn)
+    .await
+    .optional()?;
+    match object {
+        Some(object) => Ok(object.data),
+        None => Err(OperationError::ObjectNotFound {
+            obj_id: obj_id.to_string(),
+            infra_id,
+        }
+        .into()),
+    }
 }
 
 #[derive(Debug, Error, EditoastError)]
@@ -0,0 +1,349 @@
+//! Background jobs
+//!
+//! Long-running operations are recorded in the `job` table, which is used as a queue
+//! by the workers started with `runserver`. This way they survive the disconnection of
+//! the client which submitted them, and can be monitored through the `/jobs` endpoints.
+//!
+//! A running job sends heartbeats to signal its worker is alive: the jobs of a worker
+//! which stopped are picked up again by another one.
+
+mod operations;
+
+use std::io;
+use std::io::Read;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use actix_web::rt::time::sleep;
+use chashmap::CHashMap;
+use chrono::Utc;
+use editoast_derive::EditoastError;
+use futures::future::select;
+use futures::future::Either;
+use futures::pin_mut;
+use futures::FutureExt;
+use serde_json::Value as JsonValue;
+use thiserror::Error;
+use tracing::error;
+use tracing::info;
+use tracing::warn;
+
+use crate::core::CoreClient;
+use crate::error::InternalError;
+use crate::error::Result;
+use crate::infra_cache::InfraCache;
+use crate::map::MapLayers;
+use crate::modelsv2::job::Job;
+use crate::modelsv2::job::JobParameters;
+use crate::modelsv2::job::JobStatus;
+use crate::modelsv2::prelude::*;
+use crate::modelsv2::DbConnectionPool;
+use crate::RedisClient;
+
+/// How often running jobs record their progress and check whether they were cancelled
+const HEARTBEAT_PERIOD: Duration = Duration::from_secs(5);
+/// How long a running job can go without heartbeat before being picked up by another worker
+const STALE_AFTER: Duration = Duration::from_secs(60);
+/// How long idle workers wait before looking for new jobs
+const POLL_PERIOD: Duration = Duration::from_secs(2);
+/// The delay before the first retry of a failed job, doubled at each attempt
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Error, EditoastError)]
+#[editoast_error(base_id = "job")]
+pub enum JobError {
+    #[error("Job '{job_id}' could not be found")]
+    #[editoast_error(status = 404)]
+    NotFound { job_id: i64 },
+    #[error("Job '{job_id}' is already finished")]
+    #[editoast_error(status = 400)]
+    AlreadyFinished { job_id: i64 },
+    #[error("The job was cancelled")]
+    #[editoast_error(status = 400)]
+    Cancelled,
+    #[error("Document '{document_key}' could not be found")]
+    #[editoast_error(status = 404)]
+    DocumentNotFound { document_key: i64 },
+    #[error("Document '{document_key}' was uploaded by another user")]
+    #[editoast_error(status = 403)]
+    DocumentNotOwned { document_key: i64 },
+    #[error("Job '{job_id}' was submitted by another user")]
+    #[editoast_error(status = 403)]
+    Forbidden { job_id: i64 },
+    #[error("The job panicked: {message}")]
+    #[editoast_error(status = 500)]
+    Panicked { message: String },
+    #[error("The worker running the job stopped and the job ran out of attempts")]
+    #[editoast_error(status = 500)]
+    Abandoned,
+}
+
+/// The shared state the workers run jobs with
+#[derive(Clone)]
+pub struct JobRunner {
+    pub db_pool: Arc<DbConnectionPool>,
+    pub redis: Arc<RedisClient>,
+    pub core: Arc<CoreClient>,
+    pub infra_caches: Arc<CHashMap<i64, InfraCache>>,
+    pub map_layers: Arc<MapLayers>,
+}
+
+impl JobRunner {
+    /// Starts `count` workers on the current runtime
+    pub fn spawn_workers(&self, count: usize) {
+        for worker in 0..count {
+            let runner = self.clone();
+            actix_web::rt::spawn(async move { runner.work(worker).await });
+        }
+    }
+
+    async fn work(self, worker: usize) {
+        info!("👷 Job worker {worker} started");
+        loop {
+            match self.run_next().await {
+                Ok(true) => continue,
+                Ok(false) => (),
+                Err(err) => error!("Job worker {worker} failed: {err}"),
+            }
+            sleep(POLL_PERIOD).await;
+        }
+    }
+
+    /// Runs the next job of the queue, returns whether there was one
+    async fn run_next(&self) -> Result<bool> {
+        let mut job = {
+            let conn = &mut self.db_pool.get().await?;
+            let abandoned =
+                Job::fail_abandoned(conn, STALE_AFTER, &JobError::Abandoned.into()).await?;
+            if abandoned > 0 {
+                warn!("👷 {abandoned} abandoned job(s) ran out of attempts");
+            }
+            match Job::claim_next(conn, STALE_AFTER).await? {
+                Some(job) => job,
+                None => return Ok(false),
+            }
+        };
+        info!("👷 Running job {} ({})", job.id, job.kind);
+
+        let context = Arc::new(JobContext::new(job.id, job.attempts, self.db_pool.clone()));
+        context
+            .cancelled
+            .store(job.cancel_requested, Ordering::Relaxed);
+        let outcome = if job.cancel_requested {
+            Err(JobError::Cancelled.into())
+        } else {
+            let run = AssertUnwindSafe(self.run(&job.parameters, job.user_id, context.clone()))
+                .catch_unwind();
+            let heartbeats = context.heartbeats();
+            pin_mut!(run);
+            pin_mut!(heartbeats);
+            match select(run, heartbeats).await {
+                Either::Left((Ok(outcome), _)) => outcome,
+                Either::Left((Err(panic), _)) => {
+                    let message = panic
+                        .downcast_ref::<&str>()
+                        .map(|message| message.to_string())
+                        .or_else(|| panic.downcast_ref::<String>().cloned())
+                        .unwrap_or_default();
+                    error!("👷 Job {} panicked: {message}", job.id);
+                    // A panic is a bug, which another attempt would only run into again
+                    job.max_attempts = job.attempts;
+                    Err(JobError::Panicked { message }.into())
+                }
+                Either::Right(_) => unreachable!("heartbeats never stop"),
+            }
+        };
+        self.finish(job, outcome, &context).await?;
+        Ok(true)
+    }
+
+    async fn run(
+        &self,
+        parameters: &JobParameters,
+        user_id: Option<i64>,
+        context: Arc<JobContext>,
+    ) -> Result<JsonValue> {
+        match parameters {
+            JobParameters::InfraRefresh { infra_ids, force } => {
+                operations::infra_refresh(self, &context, infra_ids, *force).await
+            }
+            JobParameters::InfraClone { infra_id, name } => {
+                operations::infra_clone(self, user_id, *infra_id, name.clone()).await
+            }
+            JobParameters::RailjsonImport {
+                name,
+                document_key,
+                generate_data,
+            } => {
+                operations::railjson_import(
+                    self,
+                    context,
+                    user_id,
+                    name.clone(),
+                    *document_key,
+                    *generate_data,
+                )
+                .await
+            }
+            JobParameters::TrainSimulations {
+                infra_id,
+                train_ids,
+            } => operations::train_simulations(self, &context, *infra_id, train_ids).await,
+        }
+    }
+
+    /// Records the outcome of an attempt
+    ///
+    /// Attempts which failed with an internal error are retried, until the job runs out of attempts.
+    /// Nothing is recorded if the job was taken over by another worker in the meantime.
+    async fn finish(
+        &self,
+        job: Job,
+        outcome: Result<JsonValue>,
+        context: &JobContext,
+    ) -> Result<()> {
+        let now = Utc::now();
+        let conn = &mut self.db_pool.get().await?;
+        let mut failure = None;
+        let changeset = match outcome {
+            Ok(result) => {
+                info!("👷 Job {} succeeded", job.id);
+                Job::changeset()
+                    .status(JobStatus::Succeeded)
+                    .progress(1.)
+                    .result(Some(result))
+                    .error(None)
+                    .finished_at(Some(now))
+            }
+            Err(_) if context.is_cancelled() => {
+                info!("👷 Job {} cancelled", job.id);
+                Job::changeset()
+                    .status(JobStatus::Cancelled)
+                    .finished_at(Some(now))
+            }
+            Err(error) => {
+                warn!("👷 Job {} failed: {}", job.id, error.message);
+                failure = Some(format!(
+                    "Attempt {} failed: {}",
+                    job.attempts, error.message
+                ));
+                let changeset = Job::changeset().error(Some(error.clone()));
+                if error.status.is_server_error() && job.attempts < job.max_attempts {
+                    let delay = RETRY_DELAY * 2u32.pow(job.attempts as u32 - 1);
+                    changeset
+                        .status(JobStatus::Pending)
+                        .scheduled_at(now + chrono::Duration::from_std(delay).unwrap_or_default())
+                } else {
+                    changeset.status(JobStatus::Failed).finished_at(Some(now))
+                }
+            }
+        };
+        if !Job::finish_attempt(conn, job.id, job.attempts, changeset).await? {
+            warn!(
+                "👷 Attempt {} of job {} was taken over by another worker",
+                job.attempts, job.id
+            );
+            return Ok(());
+        }
+        if let Some(failure) = failure {
+            Job::log(conn, job.id, failure).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Lets a running job report its progress and check whether it was cancelled
+pub struct JobContext {
+    job_id: i64,
+    /// The attempt of the job run by this worker
+    attempt: i32,
+    db_pool: Arc<DbConnectionPool>,
+    /// The bits of the progress, an `f64`
+    progress: AtomicU64,
+    cancelled: AtomicBool,
+}
+
+impl JobContext {
+    fn new(job_id: i64, attempt: i32, db_pool: Arc<DbConnectionPool>) -> Self {
+        Self {
+            job_id,
+            attempt,
+            db_pool,
+            progress: AtomicU64::new(0f64.to_bits()),
+            cancelled: AtomicBool::new(false),
+        }
+    }
+
+    /// Sets the progress of the job, between 0 and 1, which is saved with the next heartbeat
+    pub fn set_progress(&self, progress: f64) {
+        self.progress
+            .store(progress.clamp(0., 1.).to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Fails with [JobError::Cancelled] if the cancellation of the job was requested
+    ///
+    /// Jobs call it between their steps, so that they stop in a consistent state.
+    pub fn check_cancelled(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(JobError::Cancelled.into());
+        }
+        Ok(())
+    }
+
+    /// Appends a message to the logs of the job
+    pub async fn log(&self, message: impl Into<String>) -> Result<()> {
+        let conn = &mut self.db_pool.get().await?;
+        Job::log(conn, self.job_id, message.into()).await
+    }
+
+    async fn heartbeats(&self) {
+        loop {
+            sleep(HEARTBEAT_PERIOD).await;
+            let progress = f64::from_bits(self.progress.load(Ordering::Relaxed));
+            let cancel_requested = match self.db_pool.get().await {
+                Ok(mut conn) => {
+                    Job::heartbeat(&mut conn, self.job_id, self.attempt, progress).await
+                }
+                Err(err) => Err(InternalError::from(err)),
+            };
+            match cancel_requested {
+                Ok(Some(cancel_requested)) => {
+                    self.cancelled.store(cancel_requested, Ordering::Relaxed)
+                }
+                Ok(None) => {
+                    // Another worker took the job over, this attempt has to stop
+                    warn!("Job {} was taken over by another worker", self.job_id);
+                    self.cancelled.store(true, Ordering::Relaxed);
+                }
+                Err(err) => warn!("Heartbeat of job {} failed: {err}", self.job_id),
+            }
+        }
+    }
+}
+
+/// Reads a buffer while reporting the progress of a job, and stops when it is cancelled
+struct JobReader {
+    data: io::Cursor<Vec<u8>>,
+    context: Arc<JobContext>,
+}
+
+impl Read for JobReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.context.is_cancelled() {
+            return Err(io::Error::new(io::ErrorKind::Other, "job cancelled"));
+        }
+        let len = self.data.read(buf)?;
+        let total = self.data.get_ref().len().max(1);
+        self.context
+            .set_progress(self.data.position() as f64 / total as f64);
+        Ok(len)
+    }
+}
@@ -0,0 +1,185 @@
+//! The operations which can run as background jobs
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+
+use serde_json::json;
+use serde_json::Value as JsonValue;
+
+use super::JobContext;
+use super::JobError;
+use super::JobReader;
+use super::JobRunner;
+use crate::error::Result;
+use crate::infra_cache::InfraCache;
+use crate::map;
+use crate::modelsv2::prelude::*;
+use crate::modelsv2::resource_grant::ResourceType;
+use crate::modelsv2::train_schedule::TrainSchedule;
+use crate::modelsv2::Document;
+use crate::modelsv2::Infra;
+use crate::modelsv2::User;
+use crate::views::authz::Authorizer;
+use crate::views::infra::InfraApiError;
+use crate::views::v2::train_schedule::train_simulation_batch;
+use crate::views::v2::train_schedule::SimulationSummaryResult;
+use crate::views::v2::train_schedule::TrainScheduleError;
+
+/// The number of trains simulated between two progress reports
+const SIMULATION_CHUNK_SIZE: usize = 20;
+
+/// The authorizer of the user who submitted a job
+async fn submitter(runner: &JobRunner, user_id: Option<i64>) -> Result<Authorizer> {
+    let user = match user_id {
+        Some(user_id) => {
+            let conn = &mut runner.db_pool.get().await?;
+            User::retrieve(conn, user_id).await?
+        }
+        None => None,
+    };
+    Ok(Authorizer::new(user))
+}
+
+pub(super) async fn infra_refresh(
+    runner: &JobRunner,
+    context: &JobContext,
+    infra_ids: &[i64],
+    force: bool,
+) -> Result<JsonValue> {
+    let conn = &mut runner.db_pool.get().await?;
+    let infras = if infra_ids.is_empty() {
+        Infra::all(conn).await
+    } else {
+        let mut infras = Vec::with_capacity(infra_ids.len());
+        for &infra_id in infra_ids {
+            infras.push(
+                Infra::retrieve_or_fail(conn, infra_id, || InfraApiError::NotFound { infra_id })
+                    .await?,
+            );
+        }
+        infras
+    };
+
+    let infra_count = infras.len();
+    let mut infra_refreshed = vec![];
+    for (index, mut infra) in infras.into_iter().enumerate() {
+        context.check_cancelled()?;
+        let infra_cache = InfraCache::get_or_load(conn, &runner.infra_caches, &infra).await?;
+        if infra
+            .refresh(runner.db_pool.clone(), force, &infra_cache)
+            .await?
+        {
+            context
+                .log(format!("Infra '{}' refreshed", infra.id))
+                .await?;
+            infra_refreshed.push(infra.id);
+        }
+        context.set_progress((index + 1) as f64 / infra_count as f64);
+    }
+
+    let mut redis = runner.redis.get_connection().await?;
+    let layers = runner.map_layers.layers.keys().cloned().collect();
+    for infra_id in infra_refreshed.iter() {
+        map::invalidate_all(&mut redis, &layers, *infra_id).await?;
+    }
+    Ok(json!({ "infra_refreshed": infra_refreshed }))
+}
+
+pub(super) async fn infra_clone(
+    runner: &JobRunner,
+    user_id: Option<i64>,
+    infra_id: i64,
+    name: String,
+) -> Result<JsonValue> {
+    let authorizer = submitter(runner, user_id).await?;
+    let conn = &mut runner.db_pool.get().await?;
+    let infra =
+        Infra::retrieve_or_fail(conn, infra_id, || InfraApiError::NotFound { infra_id }).await?;
+    let cloned_infra = infra.clone(runner.db_pool.clone(), name).await?;
+    authorizer
+        .grant_owner(conn, ResourceType::Infra, cloned_infra.id)
+        .await?;
+    Ok(json!({ "infra_id": cloned_infra.id }))
+}
+
+pub(super) async fn railjson_import(
+    runner: &JobRunner,
+    context: Arc<JobContext>,
+    user_id: Option<i64>,
+    name: String,
+    document_key: i64,
+    generate_data: bool,
+) -> Result<JsonValue> {
+    let authorizer = submitter(runner, user_id).await?;
+    let conn = &mut runner.db_pool.get().await?;
+    let document = Document::retrieve_or_fail(conn, document_key, || JobError::DocumentNotFound {
+        document_key,
+    })
+    .await?;
+    let reader = JobReader {
+        data: io::Cursor::new(document.data),
+        context: context.clone(),
+    };
+    let (mut infra, migration) = Infra::changeset()
+        .name(name)
+        .owner(authorizer.owner_uuid())
+        .last_railjson_version()
+        .persist_stream(reader, runner.db_pool.clone(), |_| ())
+        .await?;
+    let infra_id = infra.id;
+    context
+        .log(format!("Railjson imported as infra '{infra_id}'"))
+        .await?;
+
+    authorizer
+        .grant_owner(conn, ResourceType::Infra, infra_id)
+        .await?;
+    infra
+        .bump_version(conn)
+        .await
+        .map_err(|_| InfraApiError::NotFound { infra_id })?;
+    if generate_data {
+        let infra_cache = InfraCache::get_or_load(conn, &runner.infra_caches, &infra).await?;
+        infra
+            .refresh(runner.db_pool.clone(), true, &infra_cache)
+            .await?;
+    }
+    Ok(json!({ "infra_id": infra_id, "migration": migration }))
+}
+
+pub(super) async fn train_simulations(
+    runner: &JobRunner,
+    context: &JobContext,
+    infra_id: i64,
+    train_ids: &[i64],
+) -> Result<JsonValue> {
+    let conn = &mut runner.db_pool.get().await?;
+    let infra =
+        Infra::retrieve_or_fail(conn, infra_id, || InfraApiError::NotFound { infra_id }).await?;
+    let trains: Vec<TrainSchedule> =
+        TrainSchedule::retrieve_batch_or_fail(conn, train_ids.to_vec(), |missing| {
+            TrainScheduleError::BatchTrainScheduleNotFound {
+                number: missing.len(),
+            }
+        })
+        .await?;
+
+    let mut summaries = HashMap::new();
+    for chunk in trains.chunks(SIMULATION_CHUNK_SIZE) {
+        context.check_cancelled()?;
+        let simulations = train_simulation_batch(
+            runner.db_pool.clone(),
+            runner.redis.clone(),
+            runner.core.clone(),
+            chunk,
+            &infra,
+        )
+        .await?;
+        for (train, simulation) in chunk.iter().zip(simulations) {
+            summaries.insert(train.id, SimulationSummaryResult::from(simulation));
+        }
+        context.set_progress(summaries.len() as f64 / trains.len() as f64);
+    }
+    Ok(serde_json::to_value(summaries)?)
+}
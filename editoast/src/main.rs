@@ -7,18 +7,22 @@ mod error;
 mod fixtures;
 mod generated_data;
 mod infra_cache;
+mod jobs;
 mod map;
 mod models;
 mod modelsv2;
 mod redis_utils;
+mod rolling_stock_formats;
 mod tables;
 mod views;
 
 use crate::core::CoreClient;
 use crate::error::InternalError;
+use crate::modelsv2::infra_edition_history::InfraEditionHistory;
 use crate::modelsv2::DbConnectionPool;
 use crate::modelsv2::DbConnectionPoolV2;
 use crate::modelsv2::Infra;
+use crate::modelsv2::User;
 use crate::views::OpenApiRoot;
 use actix_cors::Cors;
 use actix_web::dev::{Service, ServiceRequest};
@@ -30,11 +34,16 @@ use clap::Parser;
 use client::PostgresConfig;
 use client::{
     ClearArgs, Client, Color, Commands, DeleteProfileSetArgs, ElectricalProfilesCommands,
-    ExportTimetableArgs, GenerateArgs, ImportProfileSetArgs, ImportRailjsonArgs,
-    ImportRollingStockArgs, ImportTimetableArgs, InfraCloneArgs, InfraCommands, ListProfileSetArgs,
-    MakeMigrationArgs, RedisConfig, RefreshArgs, RunserverArgs, SearchCommands, TimetablesCommands,
+    ExportGisArgs, ExportGtfsArgs, ExportRollingStockArgs, ExportTimetableArgs, GenerateArgs,
+    GisFormat, ImportFeedArgs, ImportProfileSetArgs, ImportRailjsonArgs, ImportRollingStockArgs,
+    ImportTimetableArgs, InfraCloneArgs, InfraCommands, InfraDiffArgs, ListProfileSetArgs,
+    MakeMigrationArgs, MigrateRailjsonArgs, RailmlToRailjsonArgs, RedisConfig, RefreshArgs,
+    RollingStockFormat, RunserverArgs, SearchCommands, SetAdminArgs, TimetablesCommands,
+    UsersCommands,
 };
+use editoast_schemas::infra::migrate_railjson;
 use editoast_schemas::infra::ElectricalProfileSetData;
+use editoast_schemas::infra::RailJsonMigrationReport;
 use editoast_schemas::rolling_stock::RollingStock;
 use editoast_schemas::train_schedule::TrainScheduleBase;
 use modelsv2::{
@@ -44,6 +53,8 @@ use modelsv2::{
 use modelsv2::{Changeset, RollingStockModel};
 use opentelemetry_datadog::DatadogPropagator;
 use opentelemetry_sdk::propagation::TraceContextPropagator;
+use views::v2::timetable::gtfs::timetable_gtfs_feed;
+use views::v2::timetable::import::{import_feed_trains, RollingStockMapping};
 use views::v2::train_schedule::{TrainScheduleForm, TrainScheduleResult};
 
 use crate::modelsv2::DbConnection;
@@ -51,8 +62,9 @@ use colored::*;
 use diesel::sql_query;
 use diesel_async::RunQueryDsl;
 use diesel_json::Json as DieselJson;
-use editoast_schemas::infra::RailJson;
 use infra_cache::InfraCache;
+use jobs::JobRunner;
+use map::gis::{feature_collection, load_gis_features, GeoPackage};
 use map::MapLayers;
 use modelsv2::electrical_profiles::ElectricalProfileSet;
 use modelsv2::prelude::*;
@@ -60,10 +72,12 @@ use opentelemetry::KeyValue;
 use opentelemetry_otlp::WithExportConfig as _;
 use opentelemetry_sdk::Resource;
 pub use redis_utils::{RedisClient, RedisConnection};
+use rolling_stock_formats::ConversionReport;
 use sentry::ClientInitGuard;
 use std::error::Error;
 use std::fs::File;
-use std::io::{BufReader, IsTerminal};
+use std::io::{BufReader, IsTerminal, Write};
+use std::path::Path;
 use std::process::exit;
 use std::sync::Arc;
 use std::{env, fs};
@@ -71,6 +85,7 @@ use thiserror::Error;
 use tracing::{error, info, warn};
 use tracing_subscriber::{layer::SubscriberExt as _, util::SubscriberInitExt as _, Layer as _};
 use validator::ValidationErrorsKind;
+use views::authz::AuthorizationConfig;
 use views::infra::InfraApiError;
 use views::search::{SearchConfig, SearchConfigFinder, SearchConfigStore};
 
@@ -190,9 +205,15 @@ async fn run() -> Result<(), Box<dyn Error + Send + Sync>> {
     match client.command {
         Commands::Runserver(args) => runserver(args, pg_config, redis_config).await,
         Commands::ImportRollingStock(args) => import_rolling_stock(args, db_pool.pool_v1()).await,
+        Commands::ExportRollingStock(args) => export_rolling_stock(args, db_pool.pool_v1()).await,
         Commands::OsmToRailjson(args) => {
-            osm_to_railjson::osm_to_railjson(args.osm_pbf_in, args.railjson_out)
+            let mapping = match args.tag_mapping {
+                Some(path) => osm_to_railjson::TagMapping::from_file(&path)?,
+                None => Default::default(),
+            };
+            osm_to_railjson::osm_to_railjson(args.osm_pbf_in, args.railjson_out, &mapping)
         }
+        Commands::RailmlToRailjson(args) => railml_to_railjson(args),
         Commands::Openapi => {
             generate_openapi();
             Ok(())
@@ -223,14 +244,41 @@ async fn run() -> Result<(), Box<dyn Error + Send + Sync>> {
                 generate_infra(args, db_pool.pool_v1(), redis_config).await
             }
             InfraCommands::ImportRailjson(args) => import_railjson(args, db_pool.pool_v1()).await,
+            InfraCommands::Diff(args) => diff_infras(args, db_pool.pool_v1()).await,
+            InfraCommands::MigrateRailjson(args) => migrate_railjson_file(args),
+            InfraCommands::ExportGis(args) => export_gis(args, db_pool.pool_v1()).await,
         },
         Commands::Timetables(subcommand) => match subcommand {
             TimetablesCommands::Import(args) => trains_import(args, db_pool.pool_v1()).await,
             TimetablesCommands::Export(args) => trains_export(args, db_pool.pool_v1()).await,
+            TimetablesCommands::ExportGtfs(args) => {
+                trains_export_gtfs(args, db_pool.pool_v1(), redis_config).await
+            }
+            TimetablesCommands::ImportFeed(args) => {
+                trains_import_feed(args, db_pool.pool_v1()).await
+            }
+        },
+        Commands::Users(subcommand) => match subcommand {
+            UsersCommands::SetAdmin(args) => set_user_admin(args, db_pool.pool_v1()).await,
         },
     }
 }
 
+async fn set_user_admin(
+    args: SetAdminArgs,
+    db_pool: Arc<DbConnectionPool>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let conn = &mut db_pool.get().await?;
+    let mut user = User::resolve(conn, &args.identity).await?;
+    user.patch().is_admin(!args.revoke).apply(conn).await?;
+    if user.is_admin {
+        println!("✅ User {} is now an admin", user.identity.bold());
+    } else {
+        println!("✅ User {} is no longer an admin", user.identity.bold());
+    }
+    Ok(())
+}
+
 async fn trains_export(
     args: ExportTimetableArgs,
     db_pool: Arc<DbConnectionPool>,
@@ -265,6 +313,46 @@ async fn trains_export(
     Ok(())
 }
 
+async fn trains_export_gtfs(
+    args: ExportGtfsArgs,
+    db_pool: Arc<DbConnectionPool>,
+    redis_config: RedisConfig,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let conn = &mut db_pool.get().await?;
+    let Some(timetable) = TimetableWithTrains::retrieve(conn, args.id).await? else {
+        let error = CliError::new(1, format!("❌ Timetable not found, id: {0}", args.id));
+        return Err(Box::new(error));
+    };
+    let Some(infra) = Infra::retrieve(conn, args.infra_id).await? else {
+        let error = CliError::new(
+            1,
+            format!("❌ Infrastructure not found, ID: {}", args.infra_id),
+        );
+        return Err(Box::new(error));
+    };
+
+    let redis = Arc::new(RedisClient::new(redis_config)?);
+    let core_client = Arc::new(CoreClient::new_direct(
+        args.backend_url.parse()?,
+        args.backend_token,
+    ));
+    let feed = timetable_gtfs_feed(db_pool, redis, core_client, timetable, &infra).await?;
+    for train_id in &feed.skipped_trains {
+        println!("⚠️  Train schedule {train_id} could not be simulated and was skipped");
+    }
+
+    let file = File::create(&args.path)?;
+    feed.write(file)?;
+
+    println!(
+        "✅ {} trains exported to {}",
+        feed.trip_count(),
+        args.path.to_string_lossy()
+    );
+
+    Ok(())
+}
+
 async fn trains_import(
     args: ImportTimetableArgs,
     db_pool: Arc<DbConnectionPool>,
@@ -318,6 +406,81 @@ async fn trains_import(
     Ok(())
 }
 
+async fn trains_import_feed(
+    args: ImportFeedArgs,
+    db_pool: Arc<DbConnectionPool>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let feed = match fs::read(&args.path) {
+        Ok(feed) => feed,
+        Err(e) => {
+            let error = CliError::new(
+                1,
+                format!("❌ Could not open file {:?} ({:?})", args.path, e),
+            );
+            return Err(Box::new(error));
+        }
+    };
+    let mapping: RollingStockMapping = match &args.rolling_stock_mapping {
+        Some(path) => serde_yaml::from_reader(BufReader::new(File::open(path)?))?,
+        None => Default::default(),
+    };
+
+    let conn = &mut db_pool.get().await?;
+    if Infra::retrieve(conn, args.infra_id).await?.is_none() {
+        let error = CliError::new(
+            1,
+            format!("❌ Infrastructure not found, ID: {}", args.infra_id),
+        );
+        return Err(Box::new(error));
+    }
+    let timetable = match args.id {
+        Some(timetable) => match Timetable::retrieve(conn, timetable).await? {
+            Some(timetable) => timetable,
+            None => {
+                let error = CliError::new(1, format!("❌ Timetable not found, id: {0}", timetable));
+                return Err(Box::new(error));
+            }
+        },
+        None => {
+            let changeset = Timetable::changeset();
+            changeset.create(conn).await?
+        }
+    };
+
+    let (train_schedules, reports) =
+        import_feed_trains(conn, args.infra_id, args.date, &mapping, &feed).await?;
+    for report in &reports {
+        for stop in &report.unmatched_stops {
+            println!(
+                "⚠️  Trip {}: stop {} ({}) not found in the infrastructure",
+                report.trip_id, stop.stop_id, stop.stop_name
+            );
+        }
+        if let Some(error) = &report.error {
+            println!("❌ Trip {} not imported: {:?}", report.trip_id, error);
+        }
+    }
+    let changesets: Vec<TrainScheduleChangeset> = train_schedules
+        .into_iter()
+        .map(|train_schedule| {
+            TrainScheduleForm {
+                timetable_id: Some(timetable.id),
+                train_schedule,
+            }
+            .into()
+        })
+        .collect();
+    let inserted: Vec<_> = TrainSchedule::create_batch(conn, changesets).await?;
+
+    println!(
+        "✅ {} train schedules created for timetable with id {}",
+        inserted.len(),
+        timetable.id
+    );
+
+    Ok(())
+}
+
 fn init_sentry(args: &RunserverArgs) -> Option<ClientInitGuard> {
     match (args.sentry_dsn.clone(), args.sentry_env.clone()) {
         (Some(sentry_dsn), Some(sentry_env)) => Some(sentry::init((
@@ -405,6 +568,19 @@ async fn runserver(
     let _guard = init_sentry(&args);
     let is_sentry_initialized = _guard.is_some();
 
+    // Start the background job workers
+    JobRunner {
+        db_pool: db_pool_v1.clone().into_inner(),
+        redis: Arc::new(redis.clone()),
+        core: Arc::new(CoreClient::new_direct(
+            args.backend_url.parse().expect("invalid backend_url value"),
+            args.backend_token.clone(),
+        )),
+        infra_caches: infra_caches.clone().into_inner(),
+        map_layers: Arc::new(MapLayers::parse()),
+    }
+    .spawn_workers(args.job_workers);
+
     let server = HttpServer::new(move || {
         // Build CORS
         let cors = {
@@ -451,6 +627,9 @@ async fn runserver(
             .app_data(Data::new(MapLayers::parse()))
             .app_data(Data::new(args.map_layers_config.clone()))
             .app_data(Data::new(core_client))
+            .app_data(Data::new(AuthorizationConfig {
+                allow_anonymous: args.allow_anonymous,
+            }))
             .service(scope(&args.root_path).service(views::routes()))
     });
 
@@ -560,10 +739,12 @@ async fn import_rolling_stock(
     args: ImportRollingStockArgs,
     db_pool: Arc<DbConnectionPool>,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
-    for rolling_stock_path in args.rolling_stock_path {
-        let rolling_stock_file = File::open(rolling_stock_path)?;
-        let rolling_stock_form: RollingStock =
-            serde_json::from_reader(BufReader::new(rolling_stock_file))?;
+    let rolling_stock_forms = args
+        .rolling_stock_path
+        .iter()
+        .map(|rolling_stock_path| read_rolling_stocks(rolling_stock_path, args.format))
+        .collect::<Result<Vec<_>, _>>()?;
+    for rolling_stock_form in rolling_stock_forms.into_iter().flatten() {
         let rolling_stock: Changeset<RollingStockModel> = rolling_stock_form.into();
         match rolling_stock.validate_imported_rolling_stock() {
             Ok(()) => {
@@ -606,6 +787,81 @@ async fn import_rolling_stock(
     Ok(())
 }
 
+fn print_conversion_report(report: &ConversionReport) {
+    for issue in &report.issues {
+        println!("⚠️  {issue}");
+    }
+}
+
+/// Reads the rolling stocks of a file, printing the issues found in the CSV and railML formats
+fn read_rolling_stocks(
+    rolling_stock_path: &Path,
+    format: RollingStockFormat,
+) -> Result<Vec<RollingStock>, Box<dyn Error + Send + Sync>> {
+    let rolling_stocks = match format {
+        RollingStockFormat::Json => {
+            let rolling_stock_file = File::open(rolling_stock_path)?;
+            vec![serde_json::from_reader(BufReader::new(rolling_stock_file))?]
+        }
+        RollingStockFormat::Csv => match rolling_stock_formats::csv::read_dir(rolling_stock_path) {
+            Ok(rolling_stock) => vec![rolling_stock],
+            Err(report) => {
+                print_conversion_report(&report);
+                vec![]
+            }
+        },
+        RollingStockFormat::Railml => {
+            let railml = fs::read_to_string(rolling_stock_path)?;
+            let (rolling_stocks, report) = rolling_stock_formats::railml::read(&railml);
+            print_conversion_report(&report);
+            rolling_stocks
+        }
+    };
+    if rolling_stocks.is_empty() {
+        let error = format!(
+            "❌ No rolling stock could be read from {}",
+            rolling_stock_path.to_string_lossy()
+        );
+        return Err(Box::new(CliError::new(2, error)));
+    }
+    Ok(rolling_stocks)
+}
+
+async fn export_rolling_stock(
+    args: ExportRollingStockArgs,
+    db_pool: Arc<DbConnectionPool>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let conn = &mut db_pool.get().await?;
+    let rolling_stock = RollingStockModel::retrieve(conn, args.name.clone())
+        .await?
+        .ok_or_else(|| {
+            CliError::new(
+                1,
+                format!("❌ Rolling stock not found, name: {}", args.name),
+            )
+        })?;
+    let rolling_stock: RollingStock = rolling_stock.into();
+    match args.format {
+        RollingStockFormat::Json => {
+            serde_json::to_writer_pretty(File::create(&args.output)?, &rolling_stock)?
+        }
+        RollingStockFormat::Csv => {
+            rolling_stock_formats::csv::write_dir(&rolling_stock, &args.output)?
+        }
+        RollingStockFormat::Railml => {
+            let (railml, report) = rolling_stock_formats::railml::write(&[rolling_stock]);
+            print_conversion_report(&report);
+            fs::write(&args.output, railml)?;
+        }
+    }
+    println!(
+        "✅ Rolling stock {} exported to {}",
+        args.name.bold(),
+        args.output.to_string_lossy()
+    );
+    Ok(())
+}
+
 async fn clone_infra(
     infra_args: InfraCloneArgs,
     db_pool: Arc<DbConnectionPool>,
@@ -633,6 +889,157 @@ async fn clone_infra(
     Ok(())
 }
 
+async fn diff_infras(
+    args: InfraDiffArgs,
+    db_pool: Arc<DbConnectionPool>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let conn = &mut db_pool.get().await?;
+    let base = Infra::retrieve(conn, args.base_id).await?.ok_or_else(|| {
+        CliError::new(
+            1,
+            format!("❌ Infrastructure not found, ID: {}", args.base_id),
+        )
+    })?;
+    let diff = match (args.other_id, args.versions) {
+        (Some(other_id), _) => {
+            let other = Infra::retrieve(conn, other_id).await?.ok_or_else(|| {
+                CliError::new(1, format!("❌ Infrastructure not found, ID: {other_id}"))
+            })?;
+            base.diff(conn, &other).await?
+        }
+        (None, Some(versions)) => {
+            let mut points = Vec::with_capacity(2);
+            for version in versions {
+                let point = InfraEditionHistory::history_point(conn, base.id, version.clone())
+                    .await?
+                    .ok_or_else(|| {
+                        CliError::new(
+                            1,
+                            format!("❌ Version {version} not found in the edition history"),
+                        )
+                    })?;
+                points.push(point);
+            }
+            InfraEditionHistory::diff_history(conn, base.id, points[0], points[1]).await?
+        }
+        (None, None) => unreachable!("clap requires either an infrastructure or versions"),
+    };
+    let diff = if args.operations {
+        serde_json::to_value(diff.into_operations())?
+    } else {
+        serde_json::to_value(diff)?
+    };
+
+    match args.output {
+        Some(path) => {
+            let file = File::create(&path)?;
+            serde_json::to_writer_pretty(file, &diff)?;
+            println!("✅ Diff written to {}", path.to_string_lossy());
+        }
+        None => println!("{}", serde_json::to_string_pretty(&diff)?),
+    }
+    Ok(())
+}
+
+async fn export_gis(
+    args: ExportGisArgs,
+    db_pool: Arc<DbConnectionPool>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let conn = &mut db_pool.get().await?;
+    let infra = Infra::retrieve(conn, args.infra_id).await?.ok_or_else(|| {
+        CliError::new(
+            1,
+            format!("❌ Infrastructure not found, ID: {}", args.infra_id),
+        )
+    })?;
+    let map_layers = MapLayers::parse();
+    let mut layers: Vec<_> = map_layers
+        .layers
+        .iter()
+        .filter_map(|(name, layer)| Some((name, layer, layer.views.get(&args.view)?)))
+        .collect();
+    if layers.is_empty() {
+        let error = format!("❌ No layer has a view named '{}'", args.view);
+        return Err(Box::new(CliError::new(1, error)));
+    }
+    layers.sort_by_key(|(name, _, _)| *name);
+
+    let mut geopackage = match args.format {
+        GisFormat::Geojson => {
+            fs::create_dir_all(&args.output)?;
+            None
+        }
+        GisFormat::Gpkg => Some(GeoPackage::create(&args.output)?),
+    };
+    for (name, layer, view) in layers {
+        let features = load_gis_features(conn, infra.id, &layer.table_name, view).await?;
+        println!("🗺️  Layer {}: {} features", name.bold(), features.len());
+        match geopackage.as_mut() {
+            Some(geopackage) => geopackage.add_layer(name, &features)?,
+            None => {
+                let file = File::create(args.output.join(format!("{name}.geojson")))?;
+                serde_json::to_writer(file, &feature_collection(name, features))?;
+            }
+        }
+    }
+    println!(
+        "✅ Infra {}[{}] exported to {}",
+        infra.name.bold(),
+        infra.id,
+        args.output.to_string_lossy()
+    );
+    Ok(())
+}
+
+fn railml_to_railjson(args: RailmlToRailjsonArgs) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let report = railml_to_railjson::railml_to_railjson(args.railml_in, args.railjson_out)?;
+    for unmapped in &report.unmapped_elements {
+        println!(
+            "⚠️  {} '{}' not mapped: {}",
+            unmapped.element,
+            unmapped.id.as_deref().unwrap_or_default(),
+            unmapped.reason
+        );
+    }
+    if let Some(path) = args.report {
+        serde_json::to_writer_pretty(File::create(&path)?, &report)?;
+        println!("✅ Conversion report written to {}", path.to_string_lossy());
+    }
+    Ok(())
+}
+
+fn migrate_railjson_file(args: MigrateRailjsonArgs) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let railjson_file = File::open(&args.railjson_path).map_err(|_| {
+        CliError::new(
+            1,
+            format!(
+                "❌ Railjson file not found, Path: {}",
+                args.railjson_path.to_string_lossy()
+            ),
+        )
+    })?;
+    let mut railjson: serde_json::Value = serde_json::from_reader(BufReader::new(railjson_file))?;
+    let migration =
+        migrate_railjson(&mut railjson).map_err(|error| CliError::new(1, format!("❌ {error}")))?;
+    serde_json::to_writer(File::create(&args.output)?, &railjson)?;
+    print_migration_report(&migration);
+    println!("✅ Railjson written to {}", args.output.to_string_lossy());
+    Ok(())
+}
+
+fn print_migration_report(migration: &RailJsonMigrationReport) {
+    println!(
+        "🔁 Railjson migrated from version {} to {}",
+        migration.from_version, migration.to_version
+    );
+    for change in &migration.lossy_changes {
+        println!(
+            "⚠️  [{}] {} '{}': {}",
+            change.version, change.collection, change.obj_id, change.description
+        );
+    }
+}
+
 async fn import_railjson(
     args: ImportRailjsonArgs,
     db_pool: Arc<DbConnectionPool>,
@@ -656,10 +1063,23 @@ async fn import_railjson(
     let infra = Infra::changeset()
         .name(args.infra_name)
         .last_railjson_version();
-    let railjson: RailJson = serde_json::from_reader(BufReader::new(railjson_file))?;
 
     println!("🍞 Importing infra {infra_name}");
-    let mut infra = infra.persist(railjson, db_pool.clone()).await?;
+    let show_progress = std::io::stdout().is_terminal();
+    let (mut infra, migration) = infra
+        .persist_stream(railjson_file, db_pool.clone(), |imported| {
+            if show_progress {
+                print!("\r🍞 {} objects imported", imported.values().sum::<usize>());
+                let _ = std::io::stdout().flush();
+            }
+        })
+        .await?;
+    if show_progress {
+        println!();
+    }
+    if let Some(migration) = migration {
+        print_migration_report(&migration);
+    }
 
     let mut conn = db_pool.get().await?;
     infra
@@ -915,12 +1335,13 @@ mod tests {
     use diesel::sql_query;
     use diesel::sql_types::Text;
     use diesel_async::RunQueryDsl;
+    use editoast_schemas::infra::RailJson;
+    use editoast_schemas::infra::RAILJSON_VERSION;
     use modelsv2::DeleteStatic;
     use rand::distributions::Alphanumeric;
     use rand::{thread_rng, Rng};
     use rstest::rstest;
     use serde::Serialize;
-    use std::io::Write;
     use std::sync::Arc;
     use tempfile::NamedTempFile;
 
@@ -968,6 +1389,7 @@ mod tests {
         // GIVEN
         let args = ImportRollingStockArgs {
             rolling_stock_path: vec!["non/existing/railjson/file/location".into()],
+            format: RollingStockFormat::Json,
         };
 
         // WHEN
@@ -990,6 +1412,7 @@ mod tests {
         let file = generate_temp_file(&non_electric_rs);
         let args = ImportRollingStockArgs {
             rolling_stock_path: vec![file.path().into()],
+            format: RollingStockFormat::Json,
         };
 
         // WHEN
@@ -1020,6 +1443,7 @@ mod tests {
         let file = generate_temp_file(&non_electric_rs);
         let args = ImportRollingStockArgs {
             rolling_stock_path: vec![file.path().into()],
+            format: RollingStockFormat::Json,
         };
 
         // WHEN
@@ -1054,6 +1478,7 @@ mod tests {
         let file = generate_temp_file(&electric_rs);
         let args = ImportRollingStockArgs {
             rolling_stock_path: vec![file.path().into()],
+            format: RollingStockFormat::Json,
         };
 
         // WHEN
@@ -1081,6 +1506,7 @@ mod tests {
         let file = generate_temp_file(&electric_rolling_stock);
         let args = ImportRollingStockArgs {
             rolling_stock_path: vec![file.path().into()],
+            format: RollingStockFormat::Json,
         };
 
         // WHEN
@@ -1168,6 +1594,34 @@ mod tests {
             .unwrap();
     }
 
+    #[rstest]
+    fn migrate_railjson_file_ok() {
+        // GIVEN
+        let railjson = serde_json::json!({
+            "version": "3.4.11",
+            "track_sections": [{"id": "track", "sch": {}}],
+        });
+        let file = generate_temp_file(&railjson);
+        let output = NamedTempFile::new().unwrap();
+        let args = MigrateRailjsonArgs {
+            railjson_path: file.path().into(),
+            output: output.path().into(),
+        };
+
+        // WHEN
+        let result = migrate_railjson_file(args);
+
+        // THEN
+        assert!(result.is_ok());
+        let migrated: serde_json::Value =
+            serde_json::from_reader(File::open(output.path()).unwrap()).unwrap();
+        assert_eq!(migrated["version"], RAILJSON_VERSION);
+        assert_eq!(
+            migrated["track_sections"][0],
+            serde_json::json!({"id": "track"})
+        );
+    }
+
     fn generate_temp_file<T: Serialize>(object: &T) -> NamedTempFile {
         let mut tmp_file = NamedTempFile::new().unwrap();
         write!(tmp_file, "{}", serde_json::to_string(object).unwrap()).unwrap();
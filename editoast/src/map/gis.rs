@@ -0,0 +1,187 @@
+mod geopackage;
+
+use diesel::sql_query;
+use diesel::sql_types::BigInt;
+use diesel::sql_types::Binary;
+use diesel::sql_types::Jsonb;
+use diesel::sql_types::Nullable;
+use diesel::sql_types::Text;
+use diesel_async::RunQueryDsl;
+use serde_json::json;
+use serde_json::Map;
+use serde_json::Value as JsonValue;
+
+pub use self::geopackage::GeoPackage;
+use super::View;
+use crate::error::Result;
+use crate::modelsv2::DbConnection;
+
+/// A feature of a map layer, ready to be exported to GIS formats
+#[derive(Debug, Clone, PartialEq)]
+pub struct GisFeature {
+    /// The geometry in WGS 84, as GeoJSON
+    pub geometry: Option<JsonValue>,
+    /// The geometry in WGS 84, as WKB
+    pub wkb: Option<Vec<u8>>,
+    /// The data of the object, flattened like the tags of the MVT tiles
+    pub properties: Map<String, JsonValue>,
+}
+
+#[derive(QueryableByName)]
+struct GisFeatureRow {
+    #[diesel(sql_type = Nullable<Text>)]
+    geo_json: Option<String>,
+    #[diesel(sql_type = Nullable<Binary>)]
+    wkb: Option<Vec<u8>>,
+    #[diesel(sql_type = Jsonb)]
+    data: JsonValue,
+}
+
+/// Builds the query returning all the features of a layer view for an infra
+fn get_gis_sql_query(table_name: &str, view: &View) -> String {
+    format!(
+        "
+        SELECT
+            ST_AsGeoJSON(ST_Transform(layer.{on_field}, 4326)) AS geo_json,
+            ST_AsBinary(ST_Transform(layer.{on_field}, 4326)) AS wkb,
+            {data_expr} {exclude_fields} AS data
+        FROM {table_name} layer
+        {joins}
+        WHERE layer.infra_id = $1 {where_condition}
+        ORDER BY layer.id
+        ",
+        on_field = view.on_field,
+        data_expr = view.data_expr,
+        exclude_fields = &view
+            .exclude_fields
+            .iter()
+            .map(|field| format!("- '{field}'"))
+            .collect::<Vec<_>>()
+            .join(" "),
+        joins = view.joins.join(" "),
+        where_condition = &view
+            .where_expr
+            .iter()
+            .map(|field| format!("AND ({field})"))
+            .collect::<Vec<_>>()
+            .join(" "),
+    )
+}
+
+/// Loads all the features of a layer view for an infra
+pub async fn load_gis_features(
+    conn: &mut DbConnection,
+    infra_id: i64,
+    table_name: &str,
+    view: &View,
+) -> Result<Vec<GisFeature>> {
+    let rows = sql_query(get_gis_sql_query(table_name, view))
+        .bind::<BigInt, _>(infra_id)
+        .load::<GisFeatureRow>(conn)
+        .await?;
+    rows.into_iter()
+        .map(|row| {
+            Ok(GisFeature {
+                geometry: row
+                    .geo_json
+                    .map(|geo_json| serde_json::from_str(&geo_json))
+                    .transpose()?,
+                wkb: row.wkb,
+                properties: flatten_properties(row.data),
+            })
+        })
+        .collect()
+}
+
+/// Flattens the data of an object into a single level of properties
+///
+/// Nested keys are joined with `_` and arrays are serialized as JSON strings,
+/// just like the tags of the MVT tiles.
+pub fn flatten_properties(data: JsonValue) -> Map<String, JsonValue> {
+    fn flatten(value: JsonValue, name: String, properties: &mut Map<String, JsonValue>) {
+        match value {
+            JsonValue::Object(values) => {
+                for (key, value) in values {
+                    let key = if name.is_empty() {
+                        key
+                    } else {
+                        format!("{name}_{key}")
+                    };
+                    flatten(value, key, properties);
+                }
+            }
+            JsonValue::Array(values) => {
+                properties.insert(name, JsonValue::String(json!(values).to_string()));
+            }
+            JsonValue::Null => (),
+            value => {
+                properties.insert(name, value);
+            }
+        }
+    }
+
+    let mut properties = Map::new();
+    flatten(data, String::new(), &mut properties);
+    properties
+}
+
+/// Builds a GeoJSON FeatureCollection out of the features of a layer
+pub fn feature_collection(name: &str, features: Vec<GisFeature>) -> JsonValue {
+    let features: Vec<_> = features
+        .into_iter()
+        .map(|feature| {
+            json!({
+                "type": "Feature",
+                "geometry": feature.geometry,
+                "properties": feature.properties,
+            })
+        })
+        .collect();
+    json!({
+        "type": "FeatureCollection",
+        "name": name,
+        "features": features,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::*;
+    use crate::map::MapLayers;
+
+    #[test]
+    fn properties_are_flattened() {
+        let data = json!({
+            "id": "signal",
+            "position": 42.5,
+            "extensions": { "sncf": { "label": "S1", "kp": null } },
+            "logical_signals": [{ "signaling_system": "BAL" }],
+        });
+
+        let properties = flatten_properties(data);
+
+        assert_eq!(
+            JsonValue::Object(properties),
+            json!({
+                "id": "signal",
+                "position": 42.5,
+                "extensions_sncf_label": "S1",
+                "logical_signals": r#"[{"signaling_system":"BAL"}]"#,
+            })
+        );
+    }
+
+    #[test]
+    fn gis_query_uses_the_layer_view() {
+        let map_layers = MapLayers::parse();
+        let layer = &map_layers.layers["psl"];
+        let query = get_gis_sql_query(&layer.table_name, &layer.views["geo"]);
+
+        assert!(query.contains("FROM infra_layer_speed_section layer"));
+        assert!(query.contains("inner join infra_object_speed_section speed_section"));
+        assert!(query.contains("AND (speed_section.data @? '$.extensions.psl_sncf.z')"));
+    }
+}
@@ -0,0 +1,286 @@
+use std::path::Path;
+
+use rusqlite::params;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::Connection;
+use rusqlite::DatabaseName;
+use serde_json::Value as JsonValue;
+
+use super::GisFeature;
+
+/// The SRS of the exported geometries
+const SRS_ID: i32 = 4326;
+
+const WGS84_DEFINITION: &str = r#"GEOGCS["WGS 84",DATUM["WGS_1984",SPHEROID["WGS 84",6378137,298.257223563,AUTHORITY["EPSG","7030"]],AUTHORITY["EPSG","6326"]],PRIMEM["Greenwich",0,AUTHORITY["EPSG","8901"]],UNIT["degree",0.0174532925199433,AUTHORITY["EPSG","9122"]],AUTHORITY["EPSG","4326"]]"#;
+
+/// The tables every GeoPackage must contain
+const SCHEMA: &str = r#"
+    PRAGMA application_id = 1196444487;
+    PRAGMA user_version = 10200;
+    CREATE TABLE gpkg_spatial_ref_sys (
+        srs_name TEXT NOT NULL,
+        srs_id INTEGER NOT NULL PRIMARY KEY,
+        organization TEXT NOT NULL,
+        organization_coordsys_id INTEGER NOT NULL,
+        definition TEXT NOT NULL,
+        description TEXT
+    );
+    CREATE TABLE gpkg_contents (
+        table_name TEXT NOT NULL PRIMARY KEY,
+        data_type TEXT NOT NULL,
+        identifier TEXT UNIQUE,
+        description TEXT DEFAULT '',
+        last_change DATETIME NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ','now')),
+        min_x DOUBLE,
+        min_y DOUBLE,
+        max_x DOUBLE,
+        max_y DOUBLE,
+        srs_id INTEGER REFERENCES gpkg_spatial_ref_sys(srs_id)
+    );
+    CREATE TABLE gpkg_geometry_columns (
+        table_name TEXT NOT NULL REFERENCES gpkg_contents(table_name),
+        column_name TEXT NOT NULL,
+        geometry_type_name TEXT NOT NULL,
+        srs_id INTEGER NOT NULL REFERENCES gpkg_spatial_ref_sys(srs_id),
+        z TINYINT NOT NULL,
+        m TINYINT NOT NULL,
+        PRIMARY KEY (table_name, column_name)
+    );
+    INSERT INTO gpkg_spatial_ref_sys VALUES
+        ('Undefined cartesian SRS', -1, 'NONE', -1, 'undefined', NULL),
+        ('Undefined geographic SRS', 0, 'NONE', 0, 'undefined', NULL);
+"#;
+
+/// A GeoPackage file holding one feature table per exported layer
+///
+/// See <https://www.geopackage.org/spec/>.
+pub struct GeoPackage {
+    connection: Connection,
+}
+
+impl GeoPackage {
+    /// Creates a new GeoPackage at the given path, which must not exist yet
+    pub fn create(path: &Path) -> rusqlite::Result<Self> {
+        Self::init(Connection::open(path)?)
+    }
+
+    /// Creates a new GeoPackage in memory, see [GeoPackage::into_bytes]
+    pub fn create_in_memory() -> rusqlite::Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(connection: Connection) -> rusqlite::Result<Self> {
+        connection.execute_batch(SCHEMA)?;
+        connection.execute(
+            "INSERT INTO gpkg_spatial_ref_sys VALUES ('WGS 84 geodetic', ?1, 'EPSG', ?1, ?2, NULL)",
+            params![SRS_ID, WGS84_DEFINITION],
+        )?;
+        Ok(Self { connection })
+    }
+
+    /// Writes the features of a layer in a new feature table
+    ///
+    /// Columns are created for all the properties of the features.
+    pub fn add_layer(&mut self, name: &str, features: &[GisFeature]) -> rusqlite::Result<()> {
+        let mut columns: Vec<(&str, &str)> = vec![];
+        for feature in features {
+            for (key, value) in &feature.properties {
+                let column_type = column_type(value);
+                match columns.iter_mut().find(|(column, _)| column == key) {
+                    Some((_, existing_type)) if *existing_type != column_type => {
+                        *existing_type = merge_column_types(existing_type, column_type)
+                    }
+                    Some(_) => (),
+                    None => columns.push((key, column_type)),
+                }
+            }
+        }
+
+        let transaction = self.connection.transaction()?;
+        let mut column_definitions = vec![
+            "fid INTEGER PRIMARY KEY AUTOINCREMENT".to_owned(),
+            "geom GEOMETRY".to_owned(),
+        ];
+        column_definitions.extend(
+            columns
+                .iter()
+                .map(|(column, column_type)| format!("{} {column_type}", quote(column))),
+        );
+        transaction.execute(
+            &format!(
+                "CREATE TABLE {} ({})",
+                quote(name),
+                column_definitions.join(", ")
+            ),
+            [],
+        )?;
+        transaction.execute(
+            "INSERT INTO gpkg_contents (table_name, data_type, identifier, srs_id) VALUES (?1, 'features', ?1, ?2)",
+            params![name, SRS_ID],
+        )?;
+        transaction.execute(
+            "INSERT INTO gpkg_geometry_columns VALUES (?1, 'geom', 'GEOMETRY', ?2, 0, 0)",
+            params![name, SRS_ID],
+        )?;
+
+        let mut column_names = vec!["geom".to_owned()];
+        column_names.extend(columns.iter().map(|(column, _)| quote(column)));
+        let placeholders: Vec<_> = (1..=column_names.len()).map(|i| format!("?{i}")).collect();
+        let mut insert = transaction.prepare(&format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            quote(name),
+            column_names.join(", "),
+            placeholders.join(", ")
+        ))?;
+        for feature in features {
+            let mut values = vec![feature
+                .wkb
+                .as_ref()
+                .map(|wkb| SqlValue::Blob(geometry_blob(wkb)))
+                .unwrap_or(SqlValue::Null)];
+            values.extend(columns.iter().map(|(column, _)| {
+                feature
+                    .properties
+                    .get(*column)
+                    .map(sql_value)
+                    .unwrap_or(SqlValue::Null)
+            }));
+            insert.execute(rusqlite::params_from_iter(values))?;
+        }
+        drop(insert);
+        transaction.commit()
+    }
+
+    /// Returns the content of the GeoPackage file
+    pub fn into_bytes(self) -> rusqlite::Result<Vec<u8>> {
+        let data = self.connection.serialize(DatabaseName::Main)?;
+        Ok(data.to_vec())
+    }
+}
+
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+fn column_type(value: &JsonValue) -> &'static str {
+    match value {
+        JsonValue::Bool(_) => "BOOLEAN",
+        JsonValue::Number(number) if number.is_i64() => "INTEGER",
+        JsonValue::Number(_) => "REAL",
+        _ => "TEXT",
+    }
+}
+
+/// The type of a column holding values of both types
+fn merge_column_types(a: &'static str, b: &'static str) -> &'static str {
+    match (a, b) {
+        ("INTEGER", "REAL") | ("REAL", "INTEGER") => "REAL",
+        _ => "TEXT",
+    }
+}
+
+fn sql_value(value: &JsonValue) -> SqlValue {
+    match value {
+        JsonValue::Null => SqlValue::Null,
+        JsonValue::Bool(value) => SqlValue::Integer(*value as i64),
+        JsonValue::Number(number) => match number.as_i64() {
+            Some(number) => SqlValue::Integer(number),
+            None => SqlValue::Real(number.as_f64().unwrap_or_default()),
+        },
+        JsonValue::String(value) => SqlValue::Text(value.clone()),
+        value => SqlValue::Text(value.to_string()),
+    }
+}
+
+/// Wraps a WKB geometry into a GeoPackage geometry blob
+///
+/// The header holds the magic number, the version, the flags (little endian, no envelope)
+/// and the SRS id.
+fn geometry_blob(wkb: &[u8]) -> Vec<u8> {
+    let mut blob = Vec::with_capacity(8 + wkb.len());
+    blob.extend_from_slice(b"GP");
+    blob.push(0);
+    blob.push(0b0000_0001);
+    blob.extend_from_slice(&SRS_ID.to_le_bytes());
+    blob.extend_from_slice(wkb);
+    blob
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::map::gis::flatten_properties;
+
+    /// WKB of `POINT(1 2)`
+    const POINT_WKB: [u8; 21] = [
+        1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 240, 63, 0, 0, 0, 0, 0, 0, 0, 64,
+    ];
+
+    type SignalRow = (Option<Vec<u8>>, String, f64, Option<String>);
+
+    #[test]
+    fn layers_are_written_as_feature_tables() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("infra.gpkg");
+        let features = vec![
+            GisFeature {
+                geometry: None,
+                wkb: Some(POINT_WKB.to_vec()),
+                properties: flatten_properties(json!({ "id": "a", "position": 1 })),
+            },
+            GisFeature {
+                geometry: None,
+                wkb: None,
+                properties: flatten_properties(
+                    json!({ "id": "b", "position": 2.5, "extensions": { "label": "B" } }),
+                ),
+            },
+        ];
+
+        let mut geopackage = GeoPackage::create(&path).unwrap();
+        geopackage.add_layer("signals", &features).unwrap();
+        drop(geopackage);
+
+        let connection = Connection::open(&path).unwrap();
+        let application_id: i64 = connection
+            .query_row("PRAGMA application_id", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(application_id, 0x47504B47);
+        let contents: String = connection
+            .query_row("SELECT table_name FROM gpkg_contents", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(contents, "signals");
+        let rows: Vec<SignalRow> = connection
+            .prepare("SELECT geom, id, position, extensions_label FROM signals ORDER BY fid")
+            .unwrap()
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(rows.len(), 2);
+        let geom = rows[0].0.as_ref().unwrap();
+        assert_eq!(&geom[..4], b"GP\x00\x01");
+        assert_eq!(&geom[8..], &POINT_WKB);
+        assert_eq!((rows[0].1.as_str(), rows[0].2), ("a", 1.));
+        assert_eq!(rows[1].0, None);
+        assert_eq!(rows[1].3.as_deref(), Some("B"));
+    }
+
+    #[test]
+    fn in_memory_geopackage_is_serialized() {
+        let mut geopackage = GeoPackage::create_in_memory().unwrap();
+        geopackage.add_layer("empty", &[]).unwrap();
+
+        let bytes = geopackage.into_bytes().unwrap();
+
+        assert!(bytes.starts_with(b"SQLite format 3\0"));
+        // The application id is stored big endian at offset 68
+        assert_eq!(&bytes[68..72], b"GPKG");
+    }
+}
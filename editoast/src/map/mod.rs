@@ -1,3 +1,4 @@
+pub mod gis;
 mod layer_cache;
 mod layers;
 
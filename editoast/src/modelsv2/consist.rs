@@ -0,0 +1,445 @@
+//! Multiple-unit consists: rolling stocks coupled together and running as a single train
+//!
+//! A consist is not a rolling stock of its own: its physics are derived from its units
+//! whenever it is used, so that editing a unit also updates the consists it belongs to.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use editoast_derive::EditoastError;
+use editoast_derive::ModelV2;
+use editoast_schemas::rolling_stock::ConditionalEffortCurve;
+use editoast_schemas::rolling_stock::EffortCurve;
+use editoast_schemas::rolling_stock::EffortCurveConditions;
+use editoast_schemas::rolling_stock::EffortCurves;
+use editoast_schemas::rolling_stock::Gamma;
+use editoast_schemas::rolling_stock::ModeEffortCurves;
+use editoast_schemas::rolling_stock::RollingResistance;
+use editoast_schemas::rolling_stock::RollingStockSupportedSignalingSystems;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+use utoipa::ToSchema;
+
+use crate::error::Result;
+use crate::modelsv2::prelude::*;
+use crate::modelsv2::DbConnection;
+use crate::modelsv2::RollingStockModel;
+
+editoast_common::schemas! {
+    Consist,
+    ConsistUnit,
+}
+
+#[derive(Debug, Error, EditoastError)]
+#[editoast_error(base_id = "consist")]
+pub enum ConsistError {
+    #[error("Consist '{consist_id}' could not be found")]
+    #[editoast_error(status = 404)]
+    NotFound { consist_id: i64 },
+    #[error("Name '{name}' already used by a rolling stock or a consist")]
+    #[editoast_error(status = 400)]
+    NameAlreadyUsed { name: String },
+    #[error("A consist must have at least one unit")]
+    #[editoast_error(status = 400)]
+    NoUnit,
+    #[error("The unit count of rolling stock '{rolling_stock_id}' must be at least 1")]
+    #[editoast_error(status = 400)]
+    NullUnitCount { rolling_stock_id: i64 },
+    #[error("Rolling stocks {rolling_stock_ids:?} could not be found")]
+    #[editoast_error(status = 404)]
+    RollingStocksNotFound { rolling_stock_ids: Vec<i64> },
+    #[error("The units of consist '{name}' have no traction mode in common")]
+    #[editoast_error(status = 400)]
+    NoCommonMode { name: String },
+    #[error("The units of consist '{name}' have different gamma types")]
+    #[editoast_error(status = 400)]
+    IncompatibleGammas { name: String },
+    #[error("The units of consist '{name}' have different rolling resistance types")]
+    #[editoast_error(status = 400)]
+    IncompatibleRollingResistances { name: String },
+    #[error("The units of consist '{name}' have different loading gauges")]
+    #[editoast_error(status = 400)]
+    IncompatibleLoadingGauges { name: String },
+}
+
+/// Rolling stocks of the same kind coupled in a consist
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ConsistUnit {
+    pub rolling_stock_id: i64,
+    /// How many of these rolling stocks are coupled
+    #[schema(minimum = 1)]
+    pub count: u32,
+}
+
+/// Rolling stocks coupled together, the first unit leading the train
+#[derive(Debug, Clone, Serialize, Deserialize, ModelV2, ToSchema)]
+#[model(table = crate::tables::consist)]
+pub struct Consist {
+    pub id: i64,
+    #[model(identifier)]
+    pub name: String,
+    #[model(json)]
+    pub units: Vec<ConsistUnit>,
+}
+
+impl Consist {
+    /// Derives the rolling stock the consist behaves as from its units
+    pub async fn rolling_stock(&self, conn: &mut DbConnection) -> Result<RollingStockModel> {
+        derive_consist_rolling_stock(conn, &self.name, &self.units).await
+    }
+
+    /// Filters the consists with the given rolling stock among their units
+    pub fn with_unit(rolling_stock_id: i64) -> FilterSetting<Consist> {
+        use crate::tables::consist::dsl;
+        use diesel::PgJsonbExpressionMethods;
+        FilterSetting::new(
+            dsl::units.contains(serde_json::json!([{ "rolling_stock_id": rolling_stock_id }])),
+        )
+    }
+}
+
+impl RollingStockModel {
+    /// Retrieves a rolling stock by name, or derives it from the consist of that name
+    ///
+    /// This is how the `rolling_stock_name` of a train schedule is resolved: rolling stocks
+    /// take precedence over consists. A consist with a unit which no longer exists is resolved
+    /// as `None`, just like an unknown rolling stock.
+    pub async fn retrieve_or_derive(
+        conn: &mut DbConnection,
+        name: String,
+    ) -> Result<Option<RollingStockModel>> {
+        if let Some(rolling_stock) = RollingStockModel::retrieve(conn, name.clone()).await? {
+            return Ok(Some(rolling_stock));
+        }
+        let Some(consist) = Consist::retrieve(conn, name).await? else {
+            return Ok(None);
+        };
+        let (rolling_stocks, missing): (HashMap<i64, RollingStockModel>, HashSet<i64>) =
+            RollingStockModel::retrieve_batch_with_key(
+                conn,
+                consist.units.iter().map(|unit| unit.rolling_stock_id),
+            )
+            .await?;
+        if !missing.is_empty() {
+            return Ok(None);
+        }
+        let units: Vec<_> = consist
+            .units
+            .iter()
+            .map(|unit| (&rolling_stocks[&unit.rolling_stock_id], unit.count))
+            .collect();
+        Ok(Some(couple(&consist.name, &units)?))
+    }
+}
+
+/// Retrieves the rolling stocks of some units and derives the rolling stock they form once coupled
+pub async fn derive_consist_rolling_stock(
+    conn: &mut DbConnection,
+    name: &str,
+    units: &[ConsistUnit],
+) -> Result<RollingStockModel> {
+    let rolling_stocks: HashMap<i64, RollingStockModel> =
+        RollingStockModel::retrieve_batch_with_key_or_fail(
+            conn,
+            units.iter().map(|unit| unit.rolling_stock_id),
+            |missing| {
+                let mut rolling_stock_ids: Vec<_> = missing.into_iter().collect();
+                rolling_stock_ids.sort();
+                ConsistError::RollingStocksNotFound { rolling_stock_ids }
+            },
+        )
+        .await?;
+    let units: Vec<_> = units
+        .iter()
+        .map(|unit| (&rolling_stocks[&unit.rolling_stock_id], unit.count))
+        .collect();
+    Ok(couple(name, &units)?)
+}
+
+/// Derives the physics of rolling stocks coupled together
+///
+/// The masses, lengths, tractive efforts, rolling resistances and energy sources add up,
+/// while the most restrictive speed, acceleration and deceleration of the units apply.
+/// The consist can only run on the traction modes supported by all its units.
+/// As it isn't stored, the derived rolling stock borrows the id of its lead unit.
+pub fn couple(
+    name: &str,
+    units: &[(&RollingStockModel, u32)],
+) -> std::result::Result<RollingStockModel, ConsistError> {
+    let Some(&(lead, _)) = units.first() else {
+        return Err(ConsistError::NoUnit);
+    };
+    if let Some((rolling_stock, _)) = units.iter().find(|(_, count)| *count == 0) {
+        return Err(ConsistError::NullUnitCount {
+            rolling_stock_id: rolling_stock.id,
+        });
+    }
+    let name = name.to_owned();
+    let rolling_stocks = || units.iter().map(|(rolling_stock, _)| *rolling_stock);
+    let sum = |value: fn(&RollingStockModel) -> f64| {
+        units
+            .iter()
+            .map(|(rolling_stock, count)| value(rolling_stock) * *count as f64)
+            .sum::<f64>()
+    };
+    let min = |value: fn(&RollingStockModel) -> f64| {
+        rolling_stocks().map(value).fold(f64::INFINITY, f64::min)
+    };
+    let max_option = |value: fn(&RollingStockModel) -> Option<f64>| {
+        rolling_stocks().filter_map(value).reduce(f64::max)
+    };
+
+    if rolling_stocks().any(|rs| rs.gamma.gamma_type != lead.gamma.gamma_type) {
+        return Err(ConsistError::IncompatibleGammas { name });
+    }
+    if rolling_stocks().any(|rs| {
+        rs.rolling_resistance.rolling_resistance_type
+            != lead.rolling_resistance.rolling_resistance_type
+    }) {
+        return Err(ConsistError::IncompatibleRollingResistances { name });
+    }
+    if rolling_stocks().any(|rs| rs.loading_gauge != lead.loading_gauge) {
+        return Err(ConsistError::IncompatibleLoadingGauges { name });
+    }
+    let Some(effort_curves) = couple_effort_curves(units) else {
+        return Err(ConsistError::NoCommonMode { name });
+    };
+
+    let mass = sum(|rs| rs.mass);
+    let supported_signaling_systems = lead
+        .supported_signaling_systems
+        .0
+        .iter()
+        .filter(|system| {
+            rolling_stocks().all(|rs| rs.supported_signaling_systems.0.contains(system))
+        })
+        .cloned()
+        .collect();
+    let energy_sources = units
+        .iter()
+        .flat_map(|(rolling_stock, count)| {
+            (0..*count).flat_map(|_| rolling_stock.energy_sources.iter())
+        })
+        .cloned()
+        .collect();
+
+    Ok(RollingStockModel {
+        id: lead.id,
+        railjson_version: lead.railjson_version.clone(),
+        effort_curves,
+        metadata: None,
+        length: sum(|rs| rs.length),
+        max_speed: min(|rs| rs.max_speed),
+        startup_time: rolling_stocks()
+            .map(|rs| rs.startup_time)
+            .fold(0., f64::max),
+        startup_acceleration: min(|rs| rs.startup_acceleration),
+        comfort_acceleration: min(|rs| rs.comfort_acceleration),
+        gamma: Gamma {
+            gamma_type: lead.gamma.gamma_type.clone(),
+            value: min(|rs| rs.gamma.value),
+        },
+        // The rotating masses are weighted by the mass of their unit
+        inertia_coefficient: sum(|rs| rs.inertia_coefficient * rs.mass) / mass,
+        base_power_class: lead.base_power_class.clone(),
+        mass,
+        rolling_resistance: RollingResistance {
+            rolling_resistance_type: lead.rolling_resistance.rolling_resistance_type.clone(),
+            A: sum(|rs| rs.rolling_resistance.A),
+            B: sum(|rs| rs.rolling_resistance.B),
+            C: sum(|rs| rs.rolling_resistance.C),
+        },
+        loading_gauge: lead.loading_gauge,
+        power_restrictions: lead.power_restrictions.clone(),
+        energy_sources,
+        locked: true,
+        electrical_power_startup_time: max_option(|rs| rs.electrical_power_startup_time),
+        raise_pantograph_time: max_option(|rs| rs.raise_pantograph_time),
+        version: 0,
+        name,
+        supported_signaling_systems: RollingStockSupportedSignalingSystems(
+            supported_signaling_systems,
+        ),
+    })
+}
+
+/// Sums the effort curves of the traction modes supported by all units
+///
+/// A unit lacking the conditional curve of a mode contributes its default curve.
+/// Returns `None` if the units have no mode in common.
+fn couple_effort_curves(units: &[(&RollingStockModel, u32)]) -> Option<EffortCurves> {
+    let lead = &units.first()?.0.effort_curves;
+    let mut effort_curves = EffortCurves::default();
+    for (mode, lead_mode) in &lead.modes {
+        let Some(unit_modes) = units
+            .iter()
+            .map(|(rolling_stock, count)| {
+                let unit_mode = rolling_stock.effort_curves.modes.get(mode)?;
+                Some((unit_mode, *count as f64))
+            })
+            .collect::<Option<Vec<_>>>()
+        else {
+            continue;
+        };
+
+        let mut conditions: Vec<&EffortCurveConditions> = vec![];
+        for (unit_mode, _) in &unit_modes {
+            for curve in &unit_mode.curves {
+                if !conditions.contains(&&curve.cond) {
+                    conditions.push(&curve.cond);
+                }
+            }
+        }
+        let curves = conditions
+            .into_iter()
+            .map(|cond| ConditionalEffortCurve {
+                cond: cond.clone(),
+                curve: sum_curves(unit_modes.iter().map(|(unit_mode, count)| {
+                    let curve = unit_mode
+                        .curves
+                        .iter()
+                        .find(|curve| curve.cond == *cond)
+                        .map_or(&unit_mode.default_curve, |curve| &curve.curve);
+                    (curve, *count)
+                })),
+            })
+            .collect();
+        let default_curve = sum_curves(
+            unit_modes
+                .iter()
+                .map(|(unit_mode, count)| (&unit_mode.default_curve, *count)),
+        );
+        effort_curves.modes.insert(
+            mode.clone(),
+            ModeEffortCurves {
+                curves,
+                default_curve,
+                is_electric: lead_mode.is_electric,
+            },
+        );
+    }
+
+    effort_curves.default_mode = if effort_curves.modes.contains_key(&lead.default_mode) {
+        lead.default_mode.clone()
+    } else {
+        effort_curves.modes.keys().next()?.clone()
+    };
+    Some(effort_curves)
+}
+
+/// Sums weighted effort curves, sampling each of them at the speeds of all the others
+fn sum_curves<'a>(curves: impl Iterator<Item = (&'a EffortCurve, f64)>) -> EffortCurve {
+    let curves: Vec<_> = curves.collect();
+    let mut speeds: Vec<f64> = curves
+        .iter()
+        .flat_map(|(curve, _)| curve.speeds())
+        .copied()
+        .collect();
+    speeds.sort_by(f64::total_cmp);
+    speeds.dedup();
+    let max_efforts = speeds
+        .iter()
+        .map(|&speed| {
+            curves
+                .iter()
+                .map(|(curve, weight)| effort_at(curve, speed) * weight)
+                .sum()
+        })
+        .collect();
+    EffortCurve::new(speeds, max_efforts)
+        .expect("the union of the speeds of valid effort curves is a valid curve")
+}
+
+/// Linearly interpolates an effort curve, which is constant beyond its bounds
+fn effort_at(curve: &EffortCurve, speed: f64) -> f64 {
+    let (speeds, efforts) = (curve.speeds(), curve.max_efforts());
+    match speeds.iter().position(|&s| s >= speed) {
+        None => efforts[efforts.len() - 1],
+        Some(index) if index == 0 || speeds[index] == speed => efforts[index],
+        Some(index) => {
+            let (s0, s1) = (speeds[index - 1], speeds[index]);
+            let (e0, e1) = (efforts[index - 1], efforts[index]);
+            e0 + (e1 - e0) * (speed - s0) / (s1 - s0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rolling_stock(id: i64) -> RollingStockModel {
+        let mut rolling_stock: serde_json::Value =
+            serde_json::from_str(include_str!("../tests/example_rolling_stock_1.json")).unwrap();
+        rolling_stock["id"] = id.into();
+        rolling_stock["version"] = 0.into();
+        serde_json::from_value(rolling_stock).unwrap()
+    }
+
+    #[test]
+    fn couple_sums_physics() {
+        let unit = rolling_stock(1);
+        let consist = couple("double", &[(&unit, 2)]).unwrap();
+
+        assert_eq!(consist.name, "double");
+        assert_eq!(consist.id, unit.id);
+        assert_eq!(consist.mass, 2. * unit.mass);
+        assert_eq!(consist.length, 2. * unit.length);
+        assert_eq!(consist.max_speed, unit.max_speed);
+        assert!((consist.inertia_coefficient - unit.inertia_coefficient).abs() < 1e-12);
+        assert_eq!(consist.rolling_resistance.A, 2. * unit.rolling_resistance.A);
+        assert_eq!(
+            consist.effort_curves.default_mode,
+            unit.effort_curves.default_mode
+        );
+        for (mode, curves) in &consist.effort_curves.modes {
+            let unit_curve = &unit.effort_curves.modes[mode].default_curve;
+            assert_eq!(curves.default_curve.speeds(), unit_curve.speeds());
+            let doubled: Vec<_> = unit_curve.max_efforts().iter().map(|e| 2. * e).collect();
+            assert_eq!(curves.default_curve.max_efforts(), doubled);
+        }
+    }
+
+    #[test]
+    fn couple_keeps_common_modes() {
+        let lead = rolling_stock(1);
+        let mut other = rolling_stock(2);
+        other.max_speed = lead.max_speed / 2.;
+        let mode = other.effort_curves.default_mode.clone();
+        other.effort_curves.modes.retain(|name, _| *name == mode);
+
+        let consist = couple("mixed", &[(&lead, 1), (&other, 1)]).unwrap();
+
+        assert_eq!(consist.max_speed, other.max_speed);
+        assert_eq!(
+            consist.effort_curves.modes.keys().collect::<Vec<_>>(),
+            vec![&mode]
+        );
+
+        other.effort_curves.modes.clear();
+        assert!(matches!(
+            couple("mixed", &[(&lead, 1), (&other, 1)]),
+            Err(ConsistError::NoCommonMode { .. })
+        ));
+    }
+
+    #[test]
+    fn couple_rejects_invalid_units() {
+        let unit = rolling_stock(1);
+        assert!(matches!(couple("empty", &[]), Err(ConsistError::NoUnit)));
+        assert!(matches!(
+            couple("null", &[(&unit, 0)]),
+            Err(ConsistError::NullUnitCount {
+                rolling_stock_id: 1
+            })
+        ));
+        let mut other = rolling_stock(2);
+        other.gamma.gamma_type = "MAX".to_owned();
+        assert!(matches!(
+            couple("gamma", &[(&unit, 1), (&other, 1)]),
+            Err(ConsistError::IncompatibleGammas { .. })
+        ));
+    }
+}
@@ -10,4 +10,6 @@ pub struct Document {
     pub id: i64,
     pub content_type: String,
     pub data: Vec<u8>,
+    /// The user who uploaded the document, if any
+    pub user_id: Option<i64>,
 }
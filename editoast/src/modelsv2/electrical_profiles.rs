@@ -1,3 +1,4 @@
+use diesel::OptionalExtension;
 use diesel_async::RunQueryDsl;
 use editoast_derive::ModelV2;
 use serde::Deserialize;
@@ -7,6 +8,7 @@ use utoipa::ToSchema;
 use crate::diesel::QueryDsl;
 use crate::error::Result;
 use crate::modelsv2::DbConnection;
+use crate::modelsv2::Retrieve;
 use crate::tables::electrical_profile_set;
 use editoast_schemas::infra::ElectricalProfileSetData;
 
@@ -18,9 +20,39 @@ pub struct ElectricalProfileSet {
     pub name: String,
     #[model(json)]
     pub data: ElectricalProfileSetData,
+    /// Bumped whenever the electrical profiles of the set are edited
+    pub version: i64,
 }
 
 impl ElectricalProfileSet {
+    /// Retrieves a set and locks it until the end of the transaction, to edit it
+    pub async fn retrieve_for_update(conn: &mut DbConnection, id: i64) -> Result<Option<Self>> {
+        use crate::tables::electrical_profile_set::dsl;
+        let locked = dsl::electrical_profile_set
+            .find(id)
+            .select(dsl::id)
+            .for_update()
+            .first::<i64>(conn)
+            .await
+            .optional()?;
+        match locked {
+            Some(id) => Self::retrieve(conn, id).await,
+            None => Ok(None),
+        }
+    }
+
+    /// The version of a set, without loading its electrical profiles
+    pub async fn retrieve_version(conn: &mut DbConnection, id: i64) -> Result<Option<i64>> {
+        use crate::tables::electrical_profile_set::dsl;
+        let version = dsl::electrical_profile_set
+            .find(id)
+            .select(dsl::version)
+            .first(conn)
+            .await
+            .optional()?;
+        Ok(version)
+    }
+
     pub async fn list_light(conn: &mut DbConnection) -> Result<Vec<LightElectricalProfileSet>> {
         use crate::tables::electrical_profile_set::dsl::*;
         let result = electrical_profile_set.select((id, name)).load(conn).await?;
@@ -1,3 +1,4 @@
+mod diff;
 mod object_queryable;
 mod railjson_data;
 mod route_from_waypoint_result;
@@ -5,6 +6,7 @@ mod speed_limit_tags;
 mod splited_track_section_with_data;
 mod voltage;
 
+use std::io::Read;
 use std::pin::Pin;
 
 use async_trait::async_trait;
@@ -13,6 +15,7 @@ use chrono::Utc;
 use derivative::Derivative;
 use diesel::sql_query;
 use diesel::sql_types::BigInt;
+use diesel::ExpressionMethods;
 use diesel::QueryDsl;
 use diesel_async::RunQueryDsl;
 use editoast_derive::ModelV2;
@@ -30,24 +33,28 @@ use crate::error::Result;
 use crate::generated_data;
 use crate::infra_cache::InfraCache;
 use crate::models::List;
-use crate::models::NoParams;
 use crate::modelsv2::get_geometry_layer_table;
 use crate::modelsv2::get_table;
 use crate::modelsv2::prelude::*;
 use crate::modelsv2::railjson::persist_railjson;
+use crate::modelsv2::railjson::persist_railjson_stream;
+use crate::modelsv2::railjson::RailJsonImportProgress;
 use crate::modelsv2::Create;
 use crate::modelsv2::DbConnection;
 use crate::modelsv2::DbConnectionPool;
 use crate::tables::infra::dsl;
 use crate::views::pagination::Paginate;
 use crate::views::pagination::PaginatedResponse;
+pub use diff::InfraDiff;
 use editoast_schemas::infra::RailJson;
+use editoast_schemas::infra::RailJsonMigrationReport;
 use editoast_schemas::infra::RAILJSON_VERSION;
 use editoast_schemas::primitives::ObjectType;
 pub use object_queryable::ObjectQueryable;
 
 editoast_common::schemas! {
     Infra,
+    diff::schemas(),
 }
 
 /// The default version of a newly created infrastructure
@@ -94,6 +101,35 @@ impl InfraChangeset {
         Ok(infra)
     }
 
+    /// Creates the infra and streams the objects of a RailJSON document into it
+    ///
+    /// See [persist_railjson_stream]. The infra is deleted if the import fails.
+    /// Returns the report of the migration of the document if it was in an older version.
+    pub async fn persist_stream<R, F>(
+        self,
+        reader: R,
+        db_pool: Arc<DbConnectionPool>,
+        progress: F,
+    ) -> Result<(Infra, Option<RailJsonMigrationReport>)>
+    where
+        R: Read + Send + 'static,
+        F: FnMut(&RailJsonImportProgress),
+    {
+        let conn = &mut db_pool.get().await?;
+        let infra = self.create(conn).await?;
+        debug!("🛤  Begin streaming railjson objects");
+        let migration = match persist_railjson_stream(db_pool, infra.id, reader, progress).await {
+            Ok(migration) => migration,
+            Err(e) => {
+                error!("Could not import infrastructure {}. Rolling back", infra.id);
+                infra.delete(conn).await?;
+                return Err(e);
+            }
+        };
+        debug!("🛤  Import finished successfully");
+        Ok((infra, migration))
+    }
+
     #[must_use = "builder methods are intended to be chained"]
     pub fn last_railjson_version(self) -> Self {
         self.railjson_version(RAILJSON_VERSION.to_owned())
@@ -111,6 +147,15 @@ impl Infra {
             .collect()
     }
 
+    /// Lock the infra row until the end of the current transaction and reload its fields
+    ///
+    /// Concurrent transactions locking the same infra wait for the lock to be released.
+    pub async fn lock_for_update(&mut self, conn: &mut DbConnection) -> Result<()> {
+        let row = dsl::infra.find(self.id).for_update().first(conn).await?;
+        *self = Self::from_row(row);
+        Ok(())
+    }
+
     pub async fn bump_version(&mut self, conn: &mut DbConnection) -> Result<()> {
         let new_version = self
             .version
@@ -196,6 +241,18 @@ impl Infra {
         db_pool: Arc<DbConnectionPool>,
         force: bool,
         infra_cache: &InfraCache,
+    ) -> Result<bool> {
+        self.refresh_with_progress(db_pool, force, infra_cache, &|_| ())
+            .await
+    }
+
+    /// Same as [Infra::refresh], calling `on_layer` with the table name of each layer once generated
+    pub async fn refresh_with_progress(
+        &mut self,
+        db_pool: Arc<DbConnectionPool>,
+        force: bool,
+        infra_cache: &InfraCache,
+        on_layer: &(dyn Fn(&'static str) + Sync),
     ) -> Result<bool> {
         // Check if refresh is needed
         if !force
@@ -207,7 +264,7 @@ impl Infra {
 
         // TODO: lock self for update
 
-        generated_data::refresh_all(db_pool.clone(), self.id, infra_cache).await?;
+        generated_data::refresh_all(db_pool.clone(), self.id, infra_cache, on_layer).await?;
 
         // Update generated infra version
         let mut conn = db_pool.get().await?;
@@ -227,13 +284,14 @@ impl Infra {
     }
 }
 
+/// Lists the infras, except the given hidden ones
 #[async_trait]
-impl List<NoParams> for Infra {
+impl List<Vec<i64>> for Infra {
     async fn list_conn(
         conn: &mut DbConnection,
         page: i64,
         page_size: i64,
-        _: NoParams,
+        hidden_ids: Vec<i64>,
     ) -> Result<PaginatedResponse<Self>> {
         let PaginatedResponse {
             count,
@@ -241,6 +299,7 @@ impl List<NoParams> for Infra {
             next,
             results,
         } = dsl::infra
+            .filter(dsl::id.ne_all(hidden_ids))
             .distinct()
             .paginate(page, page_size)
             .load_and_count::<Row<Self>>(conn)
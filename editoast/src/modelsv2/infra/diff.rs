@@ -0,0 +1,254 @@
+use diesel::sql_query;
+use diesel::sql_types::BigInt;
+use diesel::sql_types::Jsonb;
+use diesel::sql_types::Nullable;
+use diesel::sql_types::Text;
+use diesel::QueryableByName;
+use diesel_async::RunQueryDsl;
+use editoast_schemas::primitives::ObjectRef;
+use editoast_schemas::primitives::ObjectType;
+use json_patch::Patch;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::json;
+use serde_json::Value as JsonValue;
+use strum::IntoEnumIterator;
+use utoipa::ToSchema;
+
+use super::Infra;
+use crate::error::Result;
+use crate::infra_cache::operation::DeleteOperation;
+use crate::infra_cache::operation::Operation;
+use crate::infra_cache::operation::RailjsonObject;
+use crate::infra_cache::operation::UpdateOperation;
+use crate::modelsv2::get_table;
+use crate::modelsv2::DbConnection;
+
+editoast_common::schemas! {
+    InfraDiff,
+    ModifiedObject,
+}
+
+/// The differences between a base infra and another one
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct InfraDiff {
+    /// Objects only present in the other infra
+    #[schema(value_type = Vec<RailjsonObject>)]
+    pub created: Vec<RailjsonObject>,
+    /// Objects only present in the base infra
+    pub deleted: Vec<ObjectRef>,
+    /// Objects present in both infras but with different data
+    pub modified: Vec<ModifiedObject>,
+}
+
+/// An object whose data differs between two infras
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+pub struct ModifiedObject {
+    pub obj_ref: ObjectRef,
+    /// The JSON patch turning the object of the base infra into the one of the other infra
+    #[schema(value_type = Vec<Object>)]
+    pub changes: Patch,
+}
+
+#[derive(QueryableByName)]
+struct ObjectPair {
+    #[diesel(sql_type = Text)]
+    obj_id: String,
+    #[diesel(sql_type = Nullable<Jsonb>)]
+    base: Option<JsonValue>,
+    #[diesel(sql_type = Nullable<Jsonb>)]
+    other: Option<JsonValue>,
+}
+
+impl InfraDiff {
+    /// Whether both infras hold exactly the same objects
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty() && self.deleted.is_empty() && self.modified.is_empty()
+    }
+
+    /// Record the difference between the data of an object in the base infra and in the other one
+    ///
+    /// `None` stands for an object missing from an infra. Nothing is recorded if both sides are equal.
+    pub fn push(
+        &mut self,
+        obj_ref: ObjectRef,
+        base: Option<JsonValue>,
+        other: Option<JsonValue>,
+    ) -> Result<()> {
+        match (base, other) {
+            (None, Some(other)) => {
+                let railjson = serde_json::from_value(json!({
+                    "obj_type": obj_ref.obj_type,
+                    "railjson": other,
+                }))?;
+                self.created.push(railjson);
+            }
+            (Some(_), None) => self.deleted.push(obj_ref),
+            (Some(base), Some(other)) if base != other => self.modified.push(ModifiedObject {
+                obj_ref,
+                changes: json_patch::diff(&base, &other),
+            }),
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Convert the diff to the edition operations turning the base infra into the other one
+    ///
+    /// Creations come first, then updates, then deletions in the reverse order of the diff.
+    pub fn into_operations(self) -> Vec<Operation> {
+        let created = self
+            .created
+            .into_iter()
+            .map(|railjson| Operation::Create(Box::new(railjson)));
+        let modified = self.modified.into_iter().map(|modified| {
+            Operation::Update(UpdateOperation {
+                obj_id: modified.obj_ref.obj_id,
+                obj_type: modified.obj_ref.obj_type,
+                railjson_patch: modified.changes,
+            })
+        });
+        let deleted = self
+            .deleted
+            .into_iter()
+            .rev()
+            .map(|obj_ref| Operation::Delete(DeleteOperation::from(obj_ref)));
+        created.chain(modified).chain(deleted).collect()
+    }
+}
+
+impl Infra {
+    /// Compare the objects of this infra with the ones of another infra
+    ///
+    /// Objects are matched by type and id.
+    pub async fn diff(&self, conn: &mut DbConnection, other: &Infra) -> Result<InfraDiff> {
+        let mut diff = InfraDiff::default();
+        for obj_type in ObjectType::iter() {
+            let query = format!(
+                "SELECT COALESCE(base.obj_id, other.obj_id) AS obj_id, base.data AS base, other.data AS other
+                FROM (SELECT obj_id, data FROM {table} WHERE infra_id = $1) AS base
+                FULL OUTER JOIN (SELECT obj_id, data FROM {table} WHERE infra_id = $2) AS other
+                    ON base.obj_id = other.obj_id
+                WHERE base.data IS DISTINCT FROM other.data
+                ORDER BY obj_id",
+                table = get_table(&obj_type)
+            );
+            let pairs = sql_query(query)
+                .bind::<BigInt, _>(self.id)
+                .bind::<BigInt, _>(other.id)
+                .load::<ObjectPair>(conn)
+                .await?;
+
+            for ObjectPair {
+                obj_id,
+                base,
+                other,
+            } in pairs
+            {
+                diff.push(ObjectRef::new(obj_type, obj_id), base, other)?;
+            }
+        }
+        Ok(diff)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use json_patch::PatchOperation;
+    use json_patch::ReplaceOperation;
+    use rstest::rstest;
+
+    use super::*;
+    use crate::fixtures::tests::db_pool;
+    use crate::fixtures::tests::small_infra;
+    use crate::infra_cache::operation::create::tests::create_signal;
+    use crate::modelsv2::prelude::*;
+    use editoast_schemas::infra::Signal;
+
+    #[rstest]
+    fn into_operations_orders_creations_updates_and_deletions() {
+        let diff = InfraDiff {
+            created: vec![RailjsonObject::Signal {
+                railjson: Signal {
+                    id: "new_signal".into(),
+                    ..Default::default()
+                },
+            }],
+            deleted: vec![
+                ObjectRef::new(ObjectType::TrackSection, "track"),
+                ObjectRef::new(ObjectType::Signal, "signal"),
+            ],
+            modified: vec![ModifiedObject {
+                obj_ref: ObjectRef::new(ObjectType::Detector, "detector"),
+                changes: Patch(vec![PatchOperation::Replace(ReplaceOperation {
+                    path: "/position".parse().unwrap(),
+                    value: json!(42.),
+                })]),
+            }],
+        };
+
+        let operations = diff.into_operations();
+
+        assert_eq!(operations.len(), 4);
+        assert!(matches!(operations[0], Operation::Create(_)));
+        assert!(matches!(operations[1], Operation::Update(_)));
+        assert_eq!(
+            operations[2],
+            Operation::Delete(DeleteOperation {
+                obj_id: "signal".into(),
+                obj_type: ObjectType::Signal,
+            })
+        );
+        assert_eq!(
+            operations[3],
+            Operation::Delete(DeleteOperation {
+                obj_id: "track".into(),
+                obj_type: ObjectType::TrackSection,
+            })
+        );
+    }
+
+    #[rstest]
+    async fn diff_of_a_clone_is_empty() {
+        let db_pool = db_pool();
+        let small_infra = small_infra(db_pool.clone()).await;
+        let conn = &mut db_pool.get().await.unwrap();
+        let clone = small_infra
+            .model
+            .clone(db_pool.clone(), "clone".to_owned())
+            .await
+            .unwrap();
+
+        let diff = small_infra.model.diff(conn, &clone).await.unwrap();
+
+        assert!(diff.is_empty());
+        Infra::delete_static(conn, clone.id).await.unwrap();
+    }
+
+    #[rstest]
+    async fn diff_operations_make_infras_identical() {
+        let db_pool = db_pool();
+        let small_infra = small_infra(db_pool.clone()).await;
+        let conn = &mut db_pool.get().await.unwrap();
+        let clone = small_infra
+            .model
+            .clone(db_pool.clone(), "clone".to_owned())
+            .await
+            .unwrap();
+        let signal = create_signal(conn, clone.id, Default::default()).await;
+
+        let diff = small_infra.model.diff(conn, &clone).await.unwrap();
+        assert_eq!(diff.created, vec![signal]);
+
+        for operation in diff.into_operations() {
+            operation.apply(small_infra.id(), conn).await.unwrap();
+        }
+        assert!(small_infra
+            .model
+            .diff(conn, &clone)
+            .await
+            .unwrap()
+            .is_empty());
+        Infra::delete_static(conn, clone.id).await.unwrap();
+    }
+}
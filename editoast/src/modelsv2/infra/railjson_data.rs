@@ -1,5 +1,6 @@
 use diesel::sql_query;
 use diesel::sql_types::BigInt;
+use diesel::sql_types::Nullable;
 use diesel::sql_types::Text;
 use diesel_async::RunQueryDsl;
 use editoast_schemas::primitives::ObjectType;
@@ -11,20 +12,33 @@ use crate::modelsv2::DbConnection;
 
 #[derive(QueryableByName, Default)]
 pub struct RailJsonData {
+    #[diesel(sql_type = Text)]
+    pub obj_id: String,
     #[diesel(sql_type = Text)]
     pub railjson: String,
 }
 
 impl Infra {
-    pub async fn get_railjson(
+    /// Returns at most `limit` objects of the given type ordered by id, starting after the object `after`
+    ///
+    /// Used to export an infra page by page without loading all its objects at once.
+    pub async fn get_railjson_page(
         conn: &mut DbConnection,
         infra_id: i64,
         object_type: &ObjectType,
+        after: Option<String>,
+        limit: i64,
     ) -> Result<Vec<RailJsonData>> {
         let table_name = get_table(object_type);
-        let query = format!("SELECT (x.data)::text AS railjson FROM {table_name} x WHERE x.infra_id = $1 ORDER BY x.obj_id");
+        let query = format!(
+            "SELECT x.obj_id, (x.data)::text AS railjson FROM {table_name} x
+            WHERE x.infra_id = $1 AND ($2::text IS NULL OR x.obj_id > $2)
+            ORDER BY x.obj_id LIMIT $3"
+        );
         let railjson_data = sql_query(query)
             .bind::<BigInt, _>(infra_id)
+            .bind::<Nullable<Text>, _>(after)
+            .bind::<BigInt, _>(limit)
             .load::<RailJsonData>(conn)
             .await?;
         Ok(railjson_data)
@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use chrono::NaiveDateTime;
+use diesel::sql_query;
+use diesel::sql_types::Array;
+use diesel::sql_types::BigInt;
+use diesel::sql_types::Jsonb;
+use diesel::sql_types::Text;
+use diesel::QueryableByName;
+use diesel_async::RunQueryDsl;
+use editoast_derive::EditoastError;
+use editoast_derive::ModelV2;
+use editoast_schemas::primitives::ObjectRef;
+use itertools::Itertools;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use strum::FromRepr;
+use thiserror::Error;
+use utoipa::ToSchema;
+
+use crate::error::Result;
+use crate::infra_cache::operation::Operation;
+use crate::modelsv2::get_table;
+use crate::modelsv2::infra::InfraDiff;
+use crate::modelsv2::prelude::*;
+use crate::modelsv2::DbConnection;
+
+editoast_common::schemas! {
+    InfraEditionHistory,
+    InfraEditionKind,
+}
+
+#[derive(Debug, Error, EditoastError)]
+#[editoast_error(base_id = "infra_edition_history")]
+pub enum InfraEditionHistoryError {
+    #[error("The edition history of infra '{infra_id}' does not match object '{obj_id}'")]
+    #[editoast_error(status = 500)]
+    InconsistentHistory { infra_id: i64, obj_id: String },
+}
+
+/// How an edition batch was produced
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, FromRepr, ToSchema, PartialEq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum InfraEditionKind {
+    /// Operations sent by a client
+    #[default]
+    Edit,
+    /// Inverse operations of a previous batch
+    Revert,
+    /// Operations of a previously reverted batch applied again
+    Redo,
+}
+
+/// A batch of operations applied to an infrastructure
+///
+/// Each successful edition of an infra is recorded along with the operations
+/// that would undo it, so that it can be reverted later on.
+#[derive(Debug, Default, Clone, ModelV2, Serialize, Deserialize, ToSchema, PartialEq)]
+#[model(table = crate::tables::infra_edition_history)]
+pub struct InfraEditionHistory {
+    pub id: i64,
+    pub infra_id: i64,
+    /// The version of the infra before the edition
+    pub previous_version: String,
+    /// The version of the infra resulting from the edition
+    pub version: String,
+    /// The user who applied the edition, if known
+    pub author: Option<String>,
+    pub created_at: NaiveDateTime,
+    #[model(to_enum)]
+    pub kind: InfraEditionKind,
+    /// The batch reverted or redone by this one
+    pub target_id: Option<i64>,
+    #[model(json)]
+    pub operations: Vec<Operation>,
+    /// The operations undoing this batch, in the order they must be applied
+    #[model(json)]
+    pub inverse_operations: Vec<Operation>,
+}
+
+impl InfraEditionHistory {
+    /// Retrieve the edition of an infra which resulted in the given version
+    pub async fn retrieve_version(
+        conn: &mut DbConnection,
+        infra_id: i64,
+        version: String,
+    ) -> Result<Option<Self>> {
+        let settings = SelectionSettings::new()
+            .filter(move || Self::INFRA_ID.eq(infra_id))
+            .filter(move || Self::VERSION.eq(version.clone()))
+            .limit(1);
+        Ok(Self::list(conn, settings).await?.pop())
+    }
+
+    /// Find the point of the edition history of an infra at which it had the given version
+    ///
+    /// A point of the history is the id of the last edition applied to the infra.
+    /// The version before the first recorded edition is also found.
+    pub async fn history_point(
+        conn: &mut DbConnection,
+        infra_id: i64,
+        version: String,
+    ) -> Result<Option<i64>> {
+        if let Some(edition) = Self::retrieve_version(conn, infra_id, version.clone()).await? {
+            return Ok(Some(edition.id));
+        }
+        let settings = SelectionSettings::new()
+            .filter(move || Self::INFRA_ID.eq(infra_id))
+            .filter(move || Self::PREVIOUS_VERSION.eq(version.clone()))
+            .order_by(|| Self::ID.asc())
+            .limit(1);
+        Ok(Self::list(conn, settings)
+            .await?
+            .pop()
+            .map(|edition| edition.id - 1))
+    }
+
+    /// Compare the objects of an infra between two points of its edition history
+    ///
+    /// The objects are rebuilt from their current state by applying the inverse operations
+    /// of the editions recorded since, most recent first.
+    pub async fn diff_history(
+        conn: &mut DbConnection,
+        infra_id: i64,
+        base_point: i64,
+        other_point: i64,
+    ) -> Result<InfraDiff> {
+        let (first, last) = (base_point.min(other_point), base_point.max(other_point));
+        let settings = SelectionSettings::new()
+            .filter(move || Self::INFRA_ID.eq(infra_id))
+            .order_by(|| Self::ID.desc());
+        let editions = Self::list(conn, settings)
+            .await?
+            .into_iter()
+            .take_while(|edition| edition.id > first)
+            .collect_vec();
+
+        let mut seen = HashSet::new();
+        let obj_refs = editions
+            .iter()
+            .rev()
+            .take_while(|edition| edition.id <= last)
+            .flat_map(|edition| edition.operations.iter().map(Operation::object_ref))
+            .filter(|obj_ref| seen.insert(obj_ref.clone()))
+            .collect_vec();
+
+        let mut objects = load_objects(conn, infra_id, &obj_refs).await?;
+        let mut last_objects = None;
+        for edition in &editions {
+            if edition.id <= last && last_objects.is_none() {
+                last_objects = Some(objects.clone());
+            }
+            for operation in &edition.inverse_operations {
+                undo_on_objects(infra_id, &mut objects, &seen, operation)?;
+            }
+        }
+        let mut last_objects = last_objects.unwrap_or_else(|| objects.clone());
+
+        let mut diff = InfraDiff::default();
+        for obj_ref in obj_refs {
+            let first_object = objects.remove(&obj_ref);
+            let last_object = last_objects.remove(&obj_ref);
+            let (base, other) = if base_point <= other_point {
+                (first_object, last_object)
+            } else {
+                (last_object, first_object)
+            };
+            diff.push(obj_ref, base, other)?;
+        }
+        Ok(diff)
+    }
+}
+
+#[derive(QueryableByName)]
+struct ObjectData {
+    #[diesel(sql_type = Text)]
+    obj_id: String,
+    #[diesel(sql_type = Jsonb)]
+    data: JsonValue,
+}
+
+/// Load the current data of some objects of an infra, missing objects being left out
+async fn load_objects(
+    conn: &mut DbConnection,
+    infra_id: i64,
+    obj_refs: &[ObjectRef],
+) -> Result<HashMap<ObjectRef, JsonValue>> {
+    let mut objects = HashMap::new();
+    for (obj_type, obj_refs) in obj_refs
+        .iter()
+        .into_group_map_by(|obj_ref| obj_ref.obj_type)
+    {
+        let obj_ids = obj_refs
+            .into_iter()
+            .map(|obj_ref| obj_ref.obj_id.clone())
+            .collect_vec();
+        let rows: Vec<ObjectData> = sql_query(format!(
+            "SELECT obj_id, data FROM {} WHERE infra_id = $1 AND obj_id = ANY($2)",
+            get_table(&obj_type)
+        ))
+        .bind::<BigInt, _>(infra_id)
+        .bind::<Array<Text>, _>(obj_ids)
+        .load(conn)
+        .await?;
+        objects.extend(
+            rows.into_iter()
+                .map(|row| (ObjectRef::new(obj_type, row.obj_id), row.data)),
+        );
+    }
+    Ok(objects)
+}
+
+/// Apply an inverse operation to the tracked objects, ignoring the other ones
+fn undo_on_objects(
+    infra_id: i64,
+    objects: &mut HashMap<ObjectRef, JsonValue>,
+    tracked: &HashSet<ObjectRef>,
+    operation: &Operation,
+) -> Result<()> {
+    let obj_ref = operation.object_ref();
+    if !tracked.contains(&obj_ref) {
+        return Ok(());
+    }
+    match operation {
+        Operation::Create(railjson_object) => {
+            objects.insert(obj_ref, railjson_object.get_data());
+        }
+        Operation::Update(update) => {
+            let Some(data) = objects.get_mut(&obj_ref) else {
+                return Err(InfraEditionHistoryError::InconsistentHistory {
+                    infra_id,
+                    obj_id: obj_ref.obj_id,
+                }
+                .into());
+            };
+            json_patch::patch(data, &update.railjson_patch)?;
+        }
+        Operation::Delete(_) => {
+            objects.remove(&obj_ref);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,333 @@
+use std::time::Duration;
+
+use chrono::DateTime;
+use chrono::Utc;
+use diesel::sql_query;
+use diesel::sql_types::BigInt;
+use diesel::sql_types::Double;
+use diesel::sql_types::Jsonb;
+use diesel::sql_types::SmallInt;
+use diesel::ExpressionMethods;
+use diesel::OptionalExtension;
+use diesel::QueryDsl;
+use diesel_async::RunQueryDsl;
+use editoast_derive::ModelV2;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use strum::Display;
+use strum::FromRepr;
+use utoipa::ToSchema;
+
+use crate::error::InternalError;
+use crate::error::Result;
+use crate::modelsv2::prelude::*;
+use crate::modelsv2::DbConnection;
+use crate::tables::job::dsl;
+
+editoast_common::schemas! {
+    Job,
+    JobKind,
+    JobLog,
+    JobParameters,
+    JobStatus,
+}
+
+/// The lifecycle of a background job
+#[derive(
+    Debug, Default, Clone, Copy, Serialize, Deserialize, FromRepr, Display, ToSchema, PartialEq, Eq,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum JobStatus {
+    /// Waiting for a worker, either for the first time or for a retry
+    #[default]
+    Pending,
+    /// Executed by a worker
+    Running,
+    Succeeded,
+    /// Failed on its last attempt
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, Self::Succeeded | Self::Failed | Self::Cancelled)
+    }
+}
+
+/// The operations which can run as background jobs
+#[derive(
+    Debug, Default, Clone, Copy, Serialize, Deserialize, FromRepr, Display, ToSchema, PartialEq, Eq,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum JobKind {
+    #[default]
+    InfraRefresh,
+    InfraClone,
+    RailjsonImport,
+    TrainSimulations,
+}
+
+/// The operation run by a job, along with its parameters
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum JobParameters {
+    /// Generate the layers of some infras, or of all of them if none is given
+    InfraRefresh {
+        #[serde(default)]
+        infra_ids: Vec<i64>,
+        /// Generate the layers even if they are up to date
+        #[serde(default)]
+        force: bool,
+    },
+    /// Duplicate an infra
+    InfraClone {
+        infra_id: i64,
+        /// The name of the new infra
+        name: String,
+    },
+    /// Import an infra from a railjson uploaded with `POST /documents`
+    RailjsonImport {
+        /// The name of the new infra
+        name: String,
+        document_key: i64,
+        /// Generate the layers of the infra once imported
+        #[serde(default)]
+        generate_data: bool,
+    },
+    /// Simulate v2 train schedules on an infra, which also fills the simulation cache
+    TrainSimulations { infra_id: i64, train_ids: Vec<i64> },
+}
+
+impl Default for JobParameters {
+    fn default() -> Self {
+        Self::InfraRefresh {
+            infra_ids: vec![],
+            force: false,
+        }
+    }
+}
+
+impl JobParameters {
+    pub fn kind(&self) -> JobKind {
+        match self {
+            Self::InfraRefresh { .. } => JobKind::InfraRefresh,
+            Self::InfraClone { .. } => JobKind::InfraClone,
+            Self::RailjsonImport { .. } => JobKind::RailjsonImport,
+            Self::TrainSimulations { .. } => JobKind::TrainSimulations,
+        }
+    }
+}
+
+/// A message logged by a job
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq)]
+pub struct JobLog {
+    pub time: DateTime<Utc>,
+    pub message: String,
+}
+
+/// An operation run in the background by the workers of editoast
+///
+/// Failed jobs are retried until they reach their maximum number of attempts,
+/// jobs whose worker stopped sending heartbeats are taken over by another worker.
+#[derive(Debug, Default, Clone, ModelV2, Serialize, Deserialize, ToSchema, PartialEq)]
+#[model(table = crate::tables::job)]
+pub struct Job {
+    pub id: i64,
+    #[model(to_enum)]
+    pub kind: JobKind,
+    #[model(json)]
+    pub parameters: JobParameters,
+    #[model(to_enum)]
+    pub status: JobStatus,
+    /// The progress of the current attempt, between 0 and 1
+    pub progress: f64,
+    #[model(json)]
+    pub logs: Vec<JobLog>,
+    /// The result of the operation, once succeeded
+    #[model(json)]
+    #[schema(value_type = Option<Object>)]
+    pub result: Option<JsonValue>,
+    /// The error of the last failed attempt
+    #[model(json)]
+    pub error: Option<InternalError>,
+    pub attempts: i32,
+    pub max_attempts: i32,
+    /// Whether the cancellation of the running job was requested
+    pub cancel_requested: bool,
+    /// The user who submitted the job, if known
+    pub user_id: Option<i64>,
+    pub created_at: DateTime<Utc>,
+    /// The job won't start before this time
+    pub scheduled_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// The last time the worker running the job signaled it was alive
+    pub heartbeat_at: Option<DateTime<Utc>>,
+}
+
+impl Job {
+    /// Marks the next job to run as running and returns it
+    ///
+    /// Jobs are picked in the order they were scheduled. Running jobs whose last
+    /// heartbeat is older than `stale_after` are considered abandoned and picked again,
+    /// unless they ran out of attempts. Concurrent workers never pick the same job.
+    pub async fn claim_next(
+        conn: &mut DbConnection,
+        stale_after: Duration,
+    ) -> Result<Option<Self>> {
+        let row = sql_query(
+            "UPDATE job
+            SET status = $1, attempts = attempts + 1, progress = 0,
+                started_at = NOW(), heartbeat_at = NOW()
+            WHERE id = (
+                SELECT id FROM job
+                WHERE (status = $2 AND scheduled_at <= NOW())
+                    OR (status = $1 AND heartbeat_at < NOW() - $3 * INTERVAL '1 second'
+                        AND attempts < max_attempts)
+                ORDER BY scheduled_at, id
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING *",
+        )
+        .bind::<SmallInt, _>(JobStatus::Running as i16)
+        .bind::<SmallInt, _>(JobStatus::Pending as i16)
+        .bind::<Double, _>(stale_after.as_secs_f64())
+        .get_result::<Row<Job>>(conn)
+        .await
+        .optional()?;
+        Ok(row.map(Self::from_row))
+    }
+
+    /// Fails the abandoned running jobs which ran out of attempts, returns how many there were
+    pub async fn fail_abandoned(
+        conn: &mut DbConnection,
+        stale_after: Duration,
+        error: &InternalError,
+    ) -> Result<usize> {
+        let count = sql_query(
+            "UPDATE job
+            SET status = $2, error = $3, finished_at = NOW()
+            WHERE status = $1 AND heartbeat_at < NOW() - $4 * INTERVAL '1 second'
+                AND attempts >= max_attempts",
+        )
+        .bind::<SmallInt, _>(JobStatus::Running as i16)
+        .bind::<SmallInt, _>(JobStatus::Failed as i16)
+        .bind::<Jsonb, _>(serde_json::to_value(error)?)
+        .bind::<Double, _>(stale_after.as_secs_f64())
+        .execute(conn)
+        .await?;
+        Ok(count)
+    }
+
+    /// Records that the attempt of a job is still running
+    ///
+    /// Returns whether the cancellation of the job was requested, or `None` if the attempt
+    /// is over, for instance when it was taken over by another worker.
+    pub async fn heartbeat(
+        conn: &mut DbConnection,
+        job_id: i64,
+        attempt: i32,
+        progress: f64,
+    ) -> Result<Option<bool>> {
+        let cancel_requested = diesel::update(
+            dsl::job
+                .find(job_id)
+                .filter(dsl::attempts.eq(attempt))
+                .filter(dsl::status.eq(JobStatus::Running as i16)),
+        )
+        .set((dsl::heartbeat_at.eq(Utc::now()), dsl::progress.eq(progress)))
+        .returning(dsl::cancel_requested)
+        .get_result(conn)
+        .await
+        .optional()?;
+        Ok(cancel_requested)
+    }
+
+    /// Records the outcome of the attempt of a job
+    ///
+    /// Returns whether the attempt was still running, and thus if the outcome was recorded.
+    pub async fn finish_attempt(
+        conn: &mut DbConnection,
+        job_id: i64,
+        attempt: i32,
+        changeset: Changeset<Self>,
+    ) -> Result<bool> {
+        let count = diesel::update(
+            dsl::job
+                .find(job_id)
+                .filter(dsl::attempts.eq(attempt))
+                .filter(dsl::status.eq(JobStatus::Running as i16)),
+        )
+        .set(changeset)
+        .execute(conn)
+        .await?;
+        Ok(count == 1)
+    }
+
+    /// Cancels a pending job, or requests a running job to stop
+    pub async fn request_cancel(conn: &mut DbConnection, job_id: i64) -> Result<Option<Self>> {
+        let row = sql_query(
+            "UPDATE job
+            SET cancel_requested = TRUE,
+                status = CASE WHEN status = $2 THEN $3 ELSE status END,
+                finished_at = CASE WHEN status = $2 THEN NOW() ELSE finished_at END
+            WHERE id = $1
+            RETURNING *",
+        )
+        .bind::<BigInt, _>(job_id)
+        .bind::<SmallInt, _>(JobStatus::Pending as i16)
+        .bind::<SmallInt, _>(JobStatus::Cancelled as i16)
+        .get_result::<Row<Job>>(conn)
+        .await
+        .optional()?;
+        Ok(row.map(Self::from_row))
+    }
+
+    /// Appends a message to the logs of a job
+    pub async fn log(conn: &mut DbConnection, job_id: i64, message: String) -> Result<()> {
+        let log = JobLog {
+            time: Utc::now(),
+            message,
+        };
+        sql_query("UPDATE job SET logs = logs || $2 WHERE id = $1")
+            .bind::<BigInt, _>(job_id)
+            .bind::<Jsonb, _>(serde_json::json!([log]))
+            .execute(conn)
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn deserialize_parameters() {
+        let parameters: JobParameters =
+            serde_json::from_value(json!({ "kind": "infra_refresh" })).unwrap();
+        assert_eq!(parameters, JobParameters::default());
+        assert_eq!(parameters.kind(), JobKind::InfraRefresh);
+
+        let parameters: JobParameters = serde_json::from_value(json!({
+            "kind": "train_simulations",
+            "infra_id": 1,
+            "train_ids": [2, 3],
+        }))
+        .unwrap();
+        assert_eq!(parameters.kind(), JobKind::TrainSimulations);
+
+        assert!(serde_json::from_value::<JobParameters>(json!({
+            "kind": "infra_clone",
+            "infra_id": 1,
+        }))
+        .is_err());
+    }
+}
@@ -2,6 +2,8 @@ use std::collections::HashMap;
 use std::sync::Arc;
 
 use diesel::sql_query;
+use diesel::sql_types::Array;
+use diesel::sql_types::BigInt;
 use diesel::ExpressionMethods;
 use diesel::QueryDsl;
 use diesel::SelectableHelper;
@@ -83,17 +85,20 @@ impl LightRollingStockModel {
         })
     }
 
-    /// List the rolling stocks with their simplified effort curves
+    /// List the rolling stocks with their simplified effort curves, except the hidden ones
     pub async fn list(
         db_pool: Arc<DbConnectionPool>,
         page: i64,
         per_page: i64,
+        hidden_ids: Vec<i64>,
     ) -> Result<PaginatedResponse<LightRollingStockWithLiveriesModel>> {
         let conn = &mut db_pool.get().await?;
-        let light_rolling_stocks = sql_query("SELECT * FROM rolling_stock ORDER BY id")
-            .paginate(page, per_page)
-            .load_and_count::<Row<LightRollingStockModel>>(conn)
-            .await?;
+        let light_rolling_stocks =
+            sql_query("SELECT * FROM rolling_stock WHERE id <> ALL($1) ORDER BY id")
+                .bind::<Array<BigInt>, _>(hidden_ids)
+                .paginate(page, per_page)
+                .load_and_count::<Row<LightRollingStockModel>>(conn)
+                .await?;
 
         let lrs_results: Vec<LightRollingStockModel> = light_rolling_stocks
             .results
@@ -188,7 +193,7 @@ pub mod tests {
         .await;
 
         // WHEN
-        let rolling_stocks = LightRollingStockModel::list(db_pool, 1, 1000)
+        let rolling_stocks = LightRollingStockModel::list(db_pool, 1, 1000, vec![])
             .await
             .unwrap();
 
@@ -1,21 +1,28 @@
+pub mod consist;
 pub mod database;
 pub mod documents;
 pub mod electrical_profiles;
 pub mod infra;
+pub mod infra_edition_history;
 pub mod infra_objects;
+pub mod job;
 pub mod light_rolling_stock;
 // We allow unused until models is moved to a separate crate
 #[allow(unused)]
 pub mod prelude;
 pub mod projects;
 pub mod railjson;
+pub mod resource_grant;
 pub mod rolling_stock_image;
 pub mod rolling_stock_livery;
 pub mod rolling_stock_model;
+pub mod rolling_stock_revision;
 pub mod scenario;
 pub mod study;
 pub mod timetable;
 pub mod train_schedule;
+pub mod train_schedule_cadence;
+pub mod user;
 pub mod work_schedules;
 
 pub use prelude::*;
@@ -34,12 +41,19 @@ pub use rolling_stock_image::RollingStockSeparatedImageModel;
 pub use rolling_stock_model::RollingStockModel;
 pub use scenario::Scenario;
 pub use study::Study;
+pub use user::User;
 
 pub use crate::models::PreferredId;
 
 editoast_common::schemas! {
+    consist::schemas(),
     infra::schemas(),
+    infra_edition_history::schemas(),
+    job::schemas(),
+    resource_grant::schemas(),
     rolling_stock_model::schemas(),
+    rolling_stock_revision::schemas(),
+    user::schemas(),
 }
 
 #[cfg(test)]
@@ -1,8 +1,18 @@
+mod stream;
+
+use std::io::Read;
 use std::sync::Arc;
 
 use editoast_derive::EditoastError;
 use editoast_schemas::infra::RailJson;
+use editoast_schemas::infra::RailJsonMigrationReport;
+use editoast_schemas::infra::RailJsonMigrator;
 use editoast_schemas::infra::RAILJSON_VERSION;
+use editoast_schemas::primitives::ObjectType;
+use enum_map::EnumMap;
+use tokio::sync::mpsc;
+use tracing::debug;
+use tracing::warn;
 
 use crate::error::InternalError;
 use crate::error::Result;
@@ -10,12 +20,26 @@ use crate::modelsv2::infra_objects::*;
 use crate::modelsv2::prelude::*;
 use crate::modelsv2::DbConnection;
 use crate::modelsv2::DbConnectionPool;
+pub use stream::railjson_field;
+pub use stream::read_railjson;
+pub use stream::RailJsonPart;
+
+/// Maximum number of objects inserted at once when streaming a RailJSON document
+const RAILJSON_CHUNK_SIZE: usize = 1000;
+
+/// Number of chunks read ahead of the insertions when streaming a RailJSON document
+const RAILJSON_CHUNKS_BUFFER: usize = 4;
+
+/// Number of objects of each type imported so far
+pub type RailJsonImportProgress = EnumMap<ObjectType, usize>;
 
 #[derive(Debug, thiserror::Error, EditoastError)]
 #[editoast_error(base_id = "railjson")]
 pub enum RailJsonError {
     #[error("Unsupported railjson version '{actual}'. Should be {expected}.")]
     UnsupportedVersion { actual: String, expected: String },
+    #[error("Invalid railjson: {message}")]
+    InvalidRailJson { message: String },
 }
 
 /// Inserts the content of a RailJson object into the database
@@ -79,6 +103,137 @@ pub async fn persist_railjson(
     .map(|_| ())
 }
 
+/// Streams the content of a RailJSON document into the database
+///
+/// The document is read on a blocking thread and its objects are inserted by chunks
+/// as soon as they are parsed, so that the memory used does not depend on the size of
+/// the document. `progress` is called after each inserted chunk.
+///
+/// Documents of an older RailJSON version are migrated while being read,
+/// the report of the migration is returned.
+///
+/// #### `/!\ ATTENTION /!\` On failure this function does NOT rollback the insertions!
+pub async fn persist_railjson_stream<R, F>(
+    db_pool: Arc<DbConnectionPool>,
+    infra_id: i64,
+    reader: R,
+    progress: F,
+) -> Result<Option<RailJsonMigrationReport>>
+where
+    R: Read + Send + 'static,
+    F: FnMut(&RailJsonImportProgress),
+{
+    let (sender, receiver) = mpsc::channel(RAILJSON_CHUNKS_BUFFER);
+    let reading = tokio::task::spawn_blocking(move || {
+        read_railjson(reader, RAILJSON_CHUNK_SIZE, &mut |part| {
+            sender.blocking_send(part).is_ok()
+        })
+    });
+    // Dropping the receiver on failure interrupts the reading
+    let insertion = insert_railjson_parts(db_pool, infra_id, receiver, progress).await;
+    let reading = reading.await;
+
+    let migration = insertion?;
+    if let Err(error) = reading? {
+        return Err(RailJsonError::InvalidRailJson {
+            message: error.to_string(),
+        }
+        .into());
+    }
+    Ok(migration)
+}
+
+/// Inserts the parts of a RailJSON document until its reading is over
+///
+/// Returns the report of the migration of the document, if it was in an older version.
+async fn insert_railjson_parts<F: FnMut(&RailJsonImportProgress)>(
+    db_pool: Arc<DbConnectionPool>,
+    infra_id: i64,
+    mut receiver: mpsc::Receiver<RailJsonPart>,
+    mut progress: F,
+) -> Result<Option<RailJsonMigrationReport>> {
+    let conn = &mut db_pool.get().await?;
+    macro_rules! persist {
+        ($model:ident, $objects:expr) => {{
+            let objects = $objects;
+            let count = objects.len();
+            let _ = $model::create_batch::<_, Vec<_>>(
+                conn,
+                $model::from_infra_schemas(infra_id, objects),
+            )
+            .await?;
+            count
+        }};
+    }
+
+    let mut imported = RailJsonImportProgress::default();
+    let mut migration = None;
+    while let Some(part) = receiver.recv().await {
+        let (obj_type, count) = match part {
+            RailJsonPart::Version(actual) => {
+                // Older versions are migrated by the reader
+                if RailJsonMigrator::new(&actual).is_err() {
+                    return Err(RailJsonError::UnsupportedVersion {
+                        actual,
+                        expected: RAILJSON_VERSION.to_string(),
+                    }
+                    .into());
+                }
+                continue;
+            }
+            RailJsonPart::MigrationReport(report) => {
+                for change in &report.lossy_changes {
+                    warn!(
+                        "Migration to railjson {} of '{}' in {}: {}",
+                        change.version, change.obj_id, change.collection, change.description
+                    );
+                }
+                migration = Some(report);
+                continue;
+            }
+            RailJsonPart::TrackSections(objects) => (
+                ObjectType::TrackSection,
+                persist!(TrackSectionModel, objects),
+            ),
+            RailJsonPart::BufferStops(objects) => {
+                (ObjectType::BufferStop, persist!(BufferStopModel, objects))
+            }
+            RailJsonPart::Electrifications(objects) => (
+                ObjectType::Electrification,
+                persist!(ElectrificationModel, objects),
+            ),
+            RailJsonPart::Detectors(objects) => {
+                (ObjectType::Detector, persist!(DetectorModel, objects))
+            }
+            RailJsonPart::OperationalPoints(objects) => (
+                ObjectType::OperationalPoint,
+                persist!(OperationalPointModel, objects),
+            ),
+            RailJsonPart::Routes(objects) => (ObjectType::Route, persist!(RouteModel, objects)),
+            RailJsonPart::Signals(objects) => (ObjectType::Signal, persist!(SignalModel, objects)),
+            RailJsonPart::Switches(objects) => (ObjectType::Switch, persist!(SwitchModel, objects)),
+            RailJsonPart::SpeedSections(objects) => (
+                ObjectType::SpeedSection,
+                persist!(SpeedSectionModel, objects),
+            ),
+            RailJsonPart::SwitchTypes(objects) => {
+                (ObjectType::SwitchType, persist!(SwitchTypeModel, objects))
+            }
+            RailJsonPart::NeutralSections(objects) => (
+                ObjectType::NeutralSection,
+                persist!(NeutralSectionModel, objects),
+            ),
+        };
+        imported[obj_type] += count;
+        debug!(
+            "🛤  Imported {} objects of type {obj_type}",
+            imported[obj_type]
+        );
+        progress(&imported);
+    }
+    Ok(migration)
+}
+
 pub async fn find_all_schemas<T, C>(conn: &mut DbConnection, infra_id: i64) -> Result<C>
 where
     T: ModelBackedSchema,
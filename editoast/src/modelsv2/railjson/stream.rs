@@ -0,0 +1,417 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::BufReader;
+use std::io::Read;
+use std::marker::PhantomData;
+
+use editoast_schemas::infra::BufferStop;
+use editoast_schemas::infra::Detector;
+use editoast_schemas::infra::Electrification;
+use editoast_schemas::infra::NeutralSection;
+use editoast_schemas::infra::OperationalPoint;
+use editoast_schemas::infra::RailJsonMigrationReport;
+use editoast_schemas::infra::RailJsonMigrator;
+use editoast_schemas::infra::Route;
+use editoast_schemas::infra::Signal;
+use editoast_schemas::infra::SpeedSection;
+use editoast_schemas::infra::Switch;
+use editoast_schemas::infra::SwitchType;
+use editoast_schemas::infra::TrackSection;
+use editoast_schemas::primitives::ObjectType;
+use serde::de;
+use serde::de::DeserializeOwned;
+use serde::de::DeserializeSeed;
+use serde::de::MapAccess;
+use serde::de::SeqAccess;
+use serde::de::Visitor;
+use serde::Deserializer;
+use serde_json::Value as JsonValue;
+
+/// A part of a RailJSON document, handed out while the document is being read
+#[derive(Debug)]
+pub enum RailJsonPart {
+    /// The version of the RailJSON format of the document
+    Version(String),
+    /// The report of the migration of the document to the current version, once it was entirely read
+    MigrationReport(RailJsonMigrationReport),
+    TrackSections(Vec<TrackSection>),
+    BufferStops(Vec<BufferStop>),
+    Electrifications(Vec<Electrification>),
+    Detectors(Vec<Detector>),
+    OperationalPoints(Vec<OperationalPoint>),
+    Routes(Vec<Route>),
+    Signals(Vec<Signal>),
+    Switches(Vec<Switch>),
+    SpeedSections(Vec<SpeedSection>),
+    SwitchTypes(Vec<SwitchType>),
+    NeutralSections(Vec<NeutralSection>),
+}
+
+/// The RailJSON fields holding the objects of each type
+const OBJECT_FIELDS: [(&str, ObjectType); 11] = [
+    ("track_sections", ObjectType::TrackSection),
+    ("buffer_stops", ObjectType::BufferStop),
+    ("electrifications", ObjectType::Electrification),
+    ("detectors", ObjectType::Detector),
+    ("operational_points", ObjectType::OperationalPoint),
+    ("routes", ObjectType::Route),
+    ("signals", ObjectType::Signal),
+    ("switches", ObjectType::Switch),
+    ("speed_sections", ObjectType::SpeedSection),
+    ("extended_switch_types", ObjectType::SwitchType),
+    ("neutral_sections", ObjectType::NeutralSection),
+];
+
+const FIELDS: &[&str] = &[
+    "version",
+    "track_sections",
+    "buffer_stops",
+    "electrifications",
+    "detectors",
+    "operational_points",
+    "routes",
+    "signals",
+    "switches",
+    "speed_sections",
+    "extended_switch_types",
+    "neutral_sections",
+];
+
+/// Returns the name of the RailJSON field holding the objects of the given type
+pub fn railjson_field(object_type: ObjectType) -> &'static str {
+    OBJECT_FIELDS
+        .iter()
+        .find(|(_, obj_type)| *obj_type == object_type)
+        .map(|(field, _)| *field)
+        .expect("all object types have a railjson field")
+}
+
+/// Reads a RailJSON document and hands out its objects by chunks of at most `chunk_size` objects
+///
+/// Only the chunk being filled is kept in memory: `sink` is called as soon as a chunk is full,
+/// in the order of the document. It can return `false` to stop the reading, in which case an
+/// error is returned.
+///
+/// Documents of an older RailJSON version are migrated on the fly, which requires their
+/// version to come before their objects.
+pub fn read_railjson<R: Read>(
+    reader: R,
+    chunk_size: usize,
+    sink: &mut dyn FnMut(RailJsonPart) -> bool,
+) -> serde_json::Result<()> {
+    assert!(chunk_size > 0, "chunk size must be strictly positive");
+    let mut deserializer = serde_json::Deserializer::from_reader(BufReader::new(reader));
+    deserializer.deserialize_map(DocumentVisitor { chunk_size, sink })?;
+    deserializer.end()
+}
+
+fn emit<E: de::Error>(
+    sink: &mut dyn FnMut(RailJsonPart) -> bool,
+    part: RailJsonPart,
+) -> Result<(), E> {
+    if sink(part) {
+        Ok(())
+    } else {
+        Err(E::custom("the reading of the railjson was interrupted"))
+    }
+}
+
+struct DocumentVisitor<'a> {
+    chunk_size: usize,
+    sink: &'a mut dyn FnMut(RailJsonPart) -> bool,
+}
+
+impl<'de, 'a> Visitor<'de> for DocumentVisitor<'a> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a railjson document")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        macro_rules! objects {
+            ($part:ident) => {
+                map.next_value_seed(ChunkedObjects {
+                    chunk_size: self.chunk_size,
+                    sink: &mut *self.sink,
+                    wrap: RailJsonPart::$part,
+                    phantom: PhantomData,
+                })?
+            };
+        }
+
+        let mut has_version = false;
+        let mut has_objects = false;
+        let mut migrator = None;
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "version" {
+                let version: String = map.next_value()?;
+                emit(self.sink, RailJsonPart::Version(version.clone()))?;
+                let version_migrator =
+                    RailJsonMigrator::new(&version).map_err(de::Error::custom)?;
+                if !version_migrator.is_noop() {
+                    if has_objects {
+                        return Err(de::Error::custom(
+                            "the version of a railjson document must come before its objects to migrate it",
+                        ));
+                    }
+                    migrator = Some(version_migrator);
+                }
+                has_version = true;
+                continue;
+            }
+            has_objects = true;
+
+            if let Some(migrator) = migrator.as_mut() {
+                map.next_value_seed(MigratedObjects {
+                    collection: &key,
+                    chunk_size: self.chunk_size,
+                    sink: &mut *self.sink,
+                    migrator,
+                })?;
+                continue;
+            }
+            match key.as_str() {
+                "track_sections" => objects!(TrackSections),
+                "buffer_stops" => objects!(BufferStops),
+                "electrifications" => objects!(Electrifications),
+                "detectors" => objects!(Detectors),
+                "operational_points" => objects!(OperationalPoints),
+                "routes" => objects!(Routes),
+                "signals" => objects!(Signals),
+                "switches" => objects!(Switches),
+                "speed_sections" => objects!(SpeedSections),
+                "extended_switch_types" => objects!(SwitchTypes),
+                "neutral_sections" => objects!(NeutralSections),
+                field => return Err(de::Error::unknown_field(field, FIELDS)),
+            }
+        }
+        if !has_version {
+            return Err(de::Error::missing_field("version"));
+        }
+        if let Some(migrator) = migrator {
+            emit(
+                self.sink,
+                RailJsonPart::MigrationReport(migrator.into_report()),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Converts migrated objects of a collection of the current version into a part
+fn migrated_part<E: de::Error>(
+    collection: &str,
+    objects: Vec<JsonValue>,
+) -> Result<RailJsonPart, E> {
+    macro_rules! part {
+        ($part:ident) => {
+            RailJsonPart::$part(
+                serde_json::from_value(JsonValue::Array(objects)).map_err(E::custom)?,
+            )
+        };
+    }
+
+    Ok(match collection {
+        "track_sections" => part!(TrackSections),
+        "buffer_stops" => part!(BufferStops),
+        "electrifications" => part!(Electrifications),
+        "detectors" => part!(Detectors),
+        "operational_points" => part!(OperationalPoints),
+        "routes" => part!(Routes),
+        "signals" => part!(Signals),
+        "switches" => part!(Switches),
+        "speed_sections" => part!(SpeedSections),
+        "extended_switch_types" => part!(SwitchTypes),
+        "neutral_sections" => part!(NeutralSections),
+        field => return Err(E::unknown_field(field, FIELDS)),
+    })
+}
+
+/// Deserializes an array of objects of an older RailJSON version, migrating them one at a time
+///
+/// Migrated objects may belong to other collections than the original one, so they are
+/// grouped by collection before being handed out by chunks.
+struct MigratedObjects<'a> {
+    collection: &'a str,
+    chunk_size: usize,
+    sink: &'a mut dyn FnMut(RailJsonPart) -> bool,
+    migrator: &'a mut RailJsonMigrator,
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for MigratedObjects<'a> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for MigratedObjects<'a> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array of railjson objects")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let mut chunks: HashMap<String, Vec<JsonValue>> = HashMap::new();
+        while let Some(mut object) = seq.next_element::<JsonValue>()? {
+            let Some(collection) = self.migrator.migrate_object(self.collection, &mut object)
+            else {
+                continue;
+            };
+            let chunk = chunks.entry(collection.clone()).or_default();
+            chunk.push(object);
+            if chunk.len() == self.chunk_size {
+                emit(
+                    self.sink,
+                    migrated_part(&collection, std::mem::take(chunk))?,
+                )?;
+            }
+        }
+        for (collection, chunk) in chunks {
+            if !chunk.is_empty() {
+                emit(self.sink, migrated_part(&collection, chunk)?)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Deserializes an array of objects, handing them out by chunks instead of collecting them
+struct ChunkedObjects<'a, T> {
+    chunk_size: usize,
+    sink: &'a mut dyn FnMut(RailJsonPart) -> bool,
+    wrap: fn(Vec<T>) -> RailJsonPart,
+    phantom: PhantomData<T>,
+}
+
+impl<'de, 'a, T: DeserializeOwned> DeserializeSeed<'de> for ChunkedObjects<'a, T> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, 'a, T: DeserializeOwned> Visitor<'de> for ChunkedObjects<'a, T> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array of railjson objects")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let mut chunk = Vec::with_capacity(self.chunk_size);
+        while let Some(object) = seq.next_element::<T>()? {
+            chunk.push(object);
+            if chunk.len() == self.chunk_size {
+                let full_chunk = std::mem::replace(&mut chunk, Vec::with_capacity(self.chunk_size));
+                emit(self.sink, (self.wrap)(full_chunk))?;
+            }
+        }
+        if !chunk.is_empty() {
+            emit(self.sink, (self.wrap)(chunk))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use editoast_schemas::infra::RailJson;
+    use editoast_schemas::infra::RAILJSON_VERSION;
+    use rstest::rstest;
+    use strum::IntoEnumIterator;
+
+    use super::*;
+
+    fn railjson() -> String {
+        let railjson = RailJson {
+            track_sections: (0..5).map(|_| Default::default()).collect(),
+            signals: (0..2).map(|_| Default::default()).collect(),
+            ..Default::default()
+        };
+        serde_json::to_string(&railjson).unwrap()
+    }
+
+    #[rstest]
+    fn objects_are_read_by_chunks() {
+        let mut parts = vec![];
+        read_railjson(railjson().as_bytes(), 2, &mut |part| {
+            parts.push(part);
+            true
+        })
+        .unwrap();
+
+        assert!(matches!(&parts[0], RailJsonPart::Version(version) if version == RAILJSON_VERSION));
+        let track_chunks: Vec<_> = parts
+            .iter()
+            .filter_map(|part| match part {
+                RailJsonPart::TrackSections(tracks) => Some(tracks.len()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(track_chunks, vec![2, 2, 1]);
+        assert!(parts
+            .iter()
+            .any(|part| matches!(part, RailJsonPart::Signals(signals) if signals.len() == 2)));
+    }
+
+    #[rstest]
+    fn older_objects_are_migrated() {
+        let railjson = r#"{
+            "version": "3.4.5",
+            "switch_types": [
+                {"id": "point_switch", "ports": [], "groups": {}},
+                {"id": "custom", "ports": ["A"], "groups": {"static": []}}
+            ]
+        }"#;
+        let mut parts = vec![];
+        read_railjson(railjson.as_bytes(), 10, &mut |part| {
+            parts.push(part);
+            true
+        })
+        .unwrap();
+
+        assert_eq!(parts.len(), 3);
+        assert!(matches!(&parts[0], RailJsonPart::Version(version) if version == "3.4.5"));
+        assert!(
+            matches!(&parts[1], RailJsonPart::SwitchTypes(types) if types.len() == 1 && types[0].id.as_str() == "custom")
+        );
+        assert!(
+            matches!(&parts[2], RailJsonPart::MigrationReport(report) if report.to_version == RAILJSON_VERSION)
+        );
+    }
+
+    #[rstest]
+    fn reading_stops_when_the_sink_refuses_a_part() {
+        let mut count = 0;
+        let result = read_railjson(railjson().as_bytes(), 1, &mut |_| {
+            count += 1;
+            count < 3
+        });
+
+        assert!(result.is_err());
+        assert_eq!(count, 3);
+    }
+
+    #[rstest]
+    #[case::unknown_field(r#"{"version": "3.4.12", "tracks": []}"#)]
+    #[case::missing_version(r#"{"track_sections": []}"#)]
+    #[case::invalid_object(r#"{"version": "3.4.12", "signals": [42]}"#)]
+    #[case::trailing_data(r#"{"version": "3.4.12"} []"#)]
+    #[case::unknown_version(r#"{"version": "1.0.0"}"#)]
+    #[case::version_after_objects_to_migrate(r#"{"signals": [], "version": "3.4.11"}"#)]
+    fn invalid_railjson_is_rejected(#[case] railjson: &str) {
+        assert!(read_railjson(railjson.as_bytes(), 10, &mut |_| true).is_err());
+    }
+
+    #[rstest]
+    fn all_object_types_have_a_field() {
+        for object_type in ObjectType::iter() {
+            assert!(FIELDS.contains(&railjson_field(object_type)));
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use diesel::sql_query;
+use diesel::sql_types::BigInt;
+use diesel::sql_types::SmallInt;
+use diesel::sql_types::Text;
+use diesel::ExpressionMethods;
+use diesel::QueryDsl;
+use diesel_async::RunQueryDsl;
+use editoast_derive::ModelV2;
+use serde::Deserialize;
+use serde::Serialize;
+use strum::Display;
+use strum::FromRepr;
+use utoipa::ToSchema;
+
+use crate::error::Result;
+use crate::modelsv2::prelude::*;
+use crate::modelsv2::DbConnection;
+use crate::tables::resource_grant::dsl;
+
+editoast_common::schemas! {
+    ResourceType,
+    Role,
+    UserGrant,
+}
+
+/// The kinds of resources on which roles can be granted
+#[derive(
+    Debug, Clone, Copy, Serialize, Deserialize, FromRepr, Display, ToSchema, PartialEq, Eq, Hash,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum ResourceType {
+    Project,
+    Study,
+    Scenario,
+    Infra,
+    RollingStock,
+}
+
+/// The role of a user on a resource, each role includes the permissions of the previous ones
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Serialize,
+    Deserialize,
+    FromRepr,
+    Display,
+    ToSchema,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum Role {
+    /// Can read the resource
+    Viewer,
+    /// Can modify the resource and create its children
+    Editor,
+    /// Can delete the resource and manage its grants
+    Owner,
+}
+
+/// Identifies a resource on which roles can be granted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Resource {
+    pub resource_type: ResourceType,
+    pub id: i64,
+}
+
+impl Resource {
+    pub fn new(resource_type: ResourceType, id: i64) -> Self {
+        Self { resource_type, id }
+    }
+}
+
+impl ResourceType {
+    /// A query listing each resource of the type along with itself and its parents,
+    /// as `(id, resource_type, resource_id)` rows
+    fn with_parents_query(&self) -> &'static str {
+        match self {
+            ResourceType::Project => {
+                "SELECT id, 0::smallint AS resource_type, id AS resource_id FROM project"
+            }
+            ResourceType::Study => {
+                "SELECT id, 1::smallint AS resource_type, id AS resource_id FROM study
+                UNION ALL SELECT id, 0::smallint, project_id FROM study"
+            }
+            ResourceType::Scenario => {
+                "SELECT id, 2::smallint AS resource_type, id AS resource_id FROM scenario
+                UNION ALL SELECT id, 1::smallint, study_id FROM scenario
+                UNION ALL SELECT scenario.id, 0::smallint, study.project_id
+                    FROM scenario INNER JOIN study ON study.id = scenario.study_id"
+            }
+            ResourceType::Infra => {
+                "SELECT id, 3::smallint AS resource_type, id AS resource_id FROM infra"
+            }
+            ResourceType::RollingStock => {
+                "SELECT id, 4::smallint AS resource_type, id AS resource_id FROM rolling_stock"
+            }
+        }
+    }
+}
+
+/// A role granted to a user on a resource
+#[derive(Debug, Clone, ModelV2, Serialize, Deserialize, PartialEq)]
+#[model(table = crate::tables::resource_grant)]
+pub struct ResourceGrant {
+    pub id: i64,
+    pub user_id: i64,
+    #[model(to_enum)]
+    pub resource_type: ResourceType,
+    pub resource_id: i64,
+    #[model(to_enum)]
+    pub role: Role,
+}
+
+/// A role granted on a resource, along with the identity of its user
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq)]
+pub struct UserGrant {
+    pub user_id: i64,
+    pub identity: String,
+    pub role: Role,
+}
+
+#[derive(QueryableByName)]
+struct ResourceId {
+    #[diesel(sql_type = BigInt)]
+    id: i64,
+}
+
+#[derive(QueryableByName)]
+struct UserGrantRow {
+    #[diesel(sql_type = BigInt)]
+    user_id: i64,
+    #[diesel(sql_type = Text)]
+    identity: String,
+    #[diesel(sql_type = SmallInt)]
+    role: i16,
+}
+
+impl From<UserGrantRow> for UserGrant {
+    fn from(row: UserGrantRow) -> Self {
+        Self {
+            user_id: row.user_id,
+            identity: row.identity,
+            role: Role::from_repr(row.role as usize).expect("Invalid variant repr"),
+        }
+    }
+}
+
+impl ResourceGrant {
+    /// The grants of a user on any of the given resources
+    pub async fn list_for_user(
+        conn: &mut DbConnection,
+        user_id: i64,
+        resources: &[Resource],
+    ) -> Result<Vec<Self>> {
+        let rows = dsl::resource_grant
+            .filter(dsl::user_id.eq(user_id))
+            .filter(dsl::resource_id.eq_any(resources.iter().map(|resource| resource.id)))
+            .load::<Row<Self>>(conn)
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(Self::from_row)
+            .filter(|grant| resources.contains(&grant.resource()))
+            .collect())
+    }
+
+    /// Whether any role was granted on one of the given resources
+    pub async fn any_granted(conn: &mut DbConnection, resources: &[Resource]) -> Result<bool> {
+        let granted: Vec<(i16, i64)> = dsl::resource_grant
+            .select((dsl::resource_type, dsl::resource_id))
+            .filter(dsl::resource_id.eq_any(resources.iter().map(|resource| resource.id)))
+            .load(conn)
+            .await?;
+        Ok(resources
+            .iter()
+            .any(|resource| granted.contains(&(resource.resource_type as i16, resource.id))))
+    }
+
+    /// The resources of a type which the user cannot see
+    ///
+    /// These are the resources on which roles were granted, directly or through a parent,
+    /// but none to the user.
+    pub async fn list_hidden(
+        conn: &mut DbConnection,
+        user_id: i64,
+        resource_type: ResourceType,
+    ) -> Result<Vec<i64>> {
+        let hidden = sql_query(format!(
+            "WITH resource AS ({})
+            SELECT resource.id FROM resource
+                INNER JOIN resource_grant ON resource_grant.resource_type = resource.resource_type
+                    AND resource_grant.resource_id = resource.resource_id
+            GROUP BY resource.id
+            HAVING NOT bool_or(resource_grant.user_id = $1)",
+            resource_type.with_parents_query()
+        ))
+        .bind::<BigInt, _>(user_id)
+        .load::<ResourceId>(conn)
+        .await?;
+        Ok(hidden.into_iter().map(|resource| resource.id).collect())
+    }
+
+    pub fn resource(&self) -> Resource {
+        Resource::new(self.resource_type, self.resource_id)
+    }
+
+    /// The users having a role on a resource
+    pub async fn list_for_resource(
+        conn: &mut DbConnection,
+        resource: Resource,
+    ) -> Result<Vec<UserGrant>> {
+        let grants = sql_query(
+            "SELECT resource_grant.user_id, osrd_user.identity, resource_grant.role
+            FROM resource_grant
+                INNER JOIN osrd_user ON osrd_user.id = resource_grant.user_id
+            WHERE resource_grant.resource_type = $1 AND resource_grant.resource_id = $2
+            ORDER BY osrd_user.identity",
+        )
+        .bind::<SmallInt, _>(resource.resource_type as i16)
+        .bind::<BigInt, _>(resource.id)
+        .load::<UserGrantRow>(conn)
+        .await?;
+        Ok(grants.into_iter().map(UserGrant::from).collect())
+    }
+
+    /// Grants a role to a user on a resource, replacing any previous role
+    pub async fn grant(
+        conn: &mut DbConnection,
+        user_id: i64,
+        resource: Resource,
+        role: Role,
+    ) -> Result<Self> {
+        let row = diesel::insert_into(dsl::resource_grant)
+            .values((
+                dsl::user_id.eq(user_id),
+                dsl::resource_type.eq(resource.resource_type as i16),
+                dsl::resource_id.eq(resource.id),
+                dsl::role.eq(role as i16),
+            ))
+            .on_conflict((dsl::user_id, dsl::resource_type, dsl::resource_id))
+            .do_update()
+            .set(dsl::role.eq(role as i16))
+            .get_result::<Row<Self>>(conn)
+            .await?;
+        Ok(Self::from_row(row))
+    }
+
+    /// Revokes the role of a user on a resource, returns whether there was one
+    pub async fn revoke(conn: &mut DbConnection, user_id: i64, resource: Resource) -> Result<bool> {
+        let deleted = diesel::delete(
+            dsl::resource_grant
+                .filter(dsl::user_id.eq(user_id))
+                .filter(dsl::resource_type.eq(resource.resource_type as i16))
+                .filter(dsl::resource_id.eq(resource.id)),
+        )
+        .execute(conn)
+        .await?;
+        Ok(deleted > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roles_are_ordered() {
+        assert!(Role::Viewer < Role::Editor);
+        assert!(Role::Editor < Role::Owner);
+        assert_eq!(Role::from_repr(Role::Owner as usize), Some(Role::Owner));
+    }
+
+    #[test]
+    fn serialize_resource_types() {
+        assert_eq!(ResourceType::RollingStock.to_string(), "rolling_stock");
+        let resource_type: ResourceType = serde_json::from_str("\"rolling_stock\"").unwrap();
+        assert_eq!(resource_type, ResourceType::RollingStock);
+    }
+}
@@ -0,0 +1,191 @@
+use chrono::NaiveDateTime;
+use editoast_derive::ModelV2;
+use editoast_schemas::rolling_stock::RollingStock as RjsRollingStock;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use utoipa::ToSchema;
+
+use crate::error::Result;
+use crate::modelsv2::prelude::*;
+use crate::modelsv2::DbConnection;
+use crate::modelsv2::RollingStockModel;
+
+editoast_common::schemas! {
+    RollingStockRevision,
+    RollingStockFieldDiff,
+}
+
+/// An immutable snapshot of a version of a rolling stock
+///
+/// Each version of a rolling stock is archived when it is created or replaced,
+/// so that simulations can be reproduced with the physics they were run with.
+#[derive(Debug, Clone, ModelV2, Serialize, Deserialize, ToSchema, PartialEq)]
+#[model(table = crate::tables::rolling_stock_revision)]
+pub struct RollingStockRevision {
+    pub id: i64,
+    pub rolling_stock_id: i64,
+    /// The version of the rolling stock archived by this revision
+    pub version: i64,
+    pub created_at: NaiveDateTime,
+    /// The definition of the rolling stock at this version
+    #[model(json)]
+    pub rolling_stock: RjsRollingStock,
+}
+
+/// A field of a rolling stock whose value differs between two revisions
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema, PartialEq)]
+pub struct RollingStockFieldDiff {
+    /// The name of the field in the railjson of the rolling stock
+    pub field: String,
+    /// The value of the field in the base revision, null if absent
+    #[schema(value_type = Object)]
+    pub from: JsonValue,
+    /// The value of the field in the other revision, null if absent
+    #[schema(value_type = Object)]
+    pub to: JsonValue,
+}
+
+impl RollingStockRevision {
+    /// Retrieve the revision archiving the given version of a rolling stock
+    pub async fn retrieve_version(
+        conn: &mut DbConnection,
+        rolling_stock_id: i64,
+        version: i64,
+    ) -> Result<Option<Self>> {
+        let settings = SelectionSettings::new()
+            .filter(move || Self::ROLLING_STOCK_ID.eq(rolling_stock_id))
+            .filter(move || Self::VERSION.eq(version))
+            .limit(1);
+        Ok(Self::list(conn, settings).await?.pop())
+    }
+
+    /// List the fields of the rolling stock which differ in the other revision, sorted by name
+    pub fn diff(&self, other: &Self) -> Vec<RollingStockFieldDiff> {
+        let into_fields =
+            |rolling_stock: &RjsRollingStock| match serde_json::to_value(rolling_stock)
+                .expect("a rolling stock should serialize to JSON")
+            {
+                JsonValue::Object(fields) => fields,
+                _ => unreachable!("a rolling stock serializes to a JSON object"),
+            };
+        let base = into_fields(&self.rolling_stock);
+        let mut other = into_fields(&other.rolling_stock);
+
+        let mut diffs: Vec<_> = base
+            .into_iter()
+            .filter_map(|(field, from)| {
+                let to = other.remove(&field).unwrap_or_default();
+                (from != to).then_some(RollingStockFieldDiff { field, from, to })
+            })
+            .collect();
+        diffs.extend(other.into_iter().map(|(field, to)| RollingStockFieldDiff {
+            field,
+            from: JsonValue::Null,
+            to,
+        }));
+        diffs.sort_by(|a, b| a.field.cmp(&b.field));
+        diffs
+    }
+}
+
+impl RollingStockModel {
+    /// Archive the current version of the rolling stock, if it isn't already
+    pub async fn archive(&self, conn: &mut DbConnection) -> Result<()> {
+        use crate::tables::rolling_stock_revision::dsl;
+        use diesel_async::RunQueryDsl;
+
+        let revision = RollingStockRevision::changeset()
+            .rolling_stock_id(self.id)
+            .version(self.version)
+            .rolling_stock(self.clone().into());
+        diesel::insert_into(dsl::rolling_stock_revision)
+            .values(revision)
+            .on_conflict((dsl::rolling_stock_id, dsl::version))
+            .do_nothing()
+            .execute(conn)
+            .await?;
+        Ok(())
+    }
+}
+
+/// The rolling stock as it was at the archived version
+///
+/// Revisions are immutable, hence the rolling stock is locked.
+impl From<RollingStockRevision> for RollingStockModel {
+    fn from(revision: RollingStockRevision) -> Self {
+        let rolling_stock = revision.rolling_stock;
+        RollingStockModel {
+            id: revision.rolling_stock_id,
+            railjson_version: rolling_stock.railjson_version,
+            name: rolling_stock.name,
+            effort_curves: rolling_stock.effort_curves,
+            metadata: rolling_stock.metadata,
+            length: rolling_stock.length,
+            max_speed: rolling_stock.max_speed,
+            startup_time: rolling_stock.startup_time,
+            startup_acceleration: rolling_stock.startup_acceleration,
+            comfort_acceleration: rolling_stock.comfort_acceleration,
+            gamma: rolling_stock.gamma,
+            inertia_coefficient: rolling_stock.inertia_coefficient,
+            base_power_class: rolling_stock.base_power_class,
+            mass: rolling_stock.mass,
+            rolling_resistance: rolling_stock.rolling_resistance,
+            loading_gauge: rolling_stock.loading_gauge,
+            power_restrictions: rolling_stock.power_restrictions,
+            energy_sources: rolling_stock.energy_sources,
+            locked: true,
+            electrical_power_startup_time: rolling_stock.electrical_power_startup_time,
+            raise_pantograph_time: rolling_stock.raise_pantograph_time,
+            version: revision.version,
+            supported_signaling_systems: rolling_stock.supported_signaling_systems,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn revision(version: i64, rolling_stock: RjsRollingStock) -> RollingStockRevision {
+        RollingStockRevision {
+            id: version,
+            rolling_stock_id: 1,
+            version,
+            created_at: NaiveDateTime::default(),
+            rolling_stock,
+        }
+    }
+
+    #[test]
+    fn diff_lists_modified_fields() {
+        let rolling_stock: RjsRollingStock =
+            serde_json::from_str(include_str!("../tests/example_rolling_stock_1.json")).unwrap();
+        let mut modified = rolling_stock.clone();
+        modified.max_speed += 10.;
+        modified.base_power_class = None;
+        let base = revision(0, rolling_stock.clone());
+        let other = revision(1, modified);
+
+        let diff = base.diff(&other);
+
+        let fields: Vec<_> = diff.iter().map(|diff| diff.field.as_str()).collect();
+        assert_eq!(fields, ["base_power_class", "max_speed"]);
+        assert_eq!(diff[0].to, JsonValue::Null);
+        assert_eq!(diff[1].from, json!(rolling_stock.max_speed));
+        assert!(base.diff(&base).is_empty());
+    }
+
+    #[test]
+    fn revision_into_locked_rolling_stock() {
+        let rolling_stock: RjsRollingStock =
+            serde_json::from_str(include_str!("../tests/example_rolling_stock_1.json")).unwrap();
+        let model: RollingStockModel = revision(3, rolling_stock.clone()).into();
+        assert_eq!(model.id, 1);
+        assert_eq!(model.version, 3);
+        assert!(model.locked);
+        assert_eq!(RjsRollingStock::from(model), rolling_stock);
+    }
+}
@@ -1,16 +1,20 @@
+use std::collections::HashMap;
+
 use async_trait::async_trait;
 use diesel::sql_query;
 use diesel::sql_types::Array;
 use diesel::sql_types::BigInt;
 use diesel::sql_types::Nullable;
+use diesel::ExpressionMethods;
+use diesel::QueryDsl;
 use editoast_derive::ModelV2;
 
-use crate::diesel::query_dsl::methods::DistinctDsl;
 use crate::error::Result;
 use crate::models::List;
-use crate::models::NoParams;
+use crate::modelsv2::prelude::*;
+use crate::modelsv2::train_schedule::TrainSchedule;
+use crate::modelsv2::train_schedule_cadence::TrainScheduleCadence;
 use crate::modelsv2::DbConnection;
-use crate::modelsv2::Retrieve;
 use crate::modelsv2::Row;
 use crate::tables::timetable_v2::dsl;
 use crate::views::pagination::Paginate;
@@ -23,15 +27,25 @@ pub struct Timetable {
     pub electrical_profile_set_id: Option<i64>,
 }
 
+/// Lists the timetables, except the ones used by a scenario of the given hidden studies
 #[async_trait]
-impl List<NoParams> for Timetable {
+impl List<Vec<i64>> for Timetable {
     async fn list_conn(
         conn: &mut DbConnection,
         page: i64,
         page_size: i64,
-        _: NoParams,
+        hidden_study_ids: Vec<i64>,
     ) -> Result<PaginatedResponse<Self>> {
+        use crate::tables::scenario_v2;
+        use diesel::dsl::exists;
+        use diesel::dsl::not;
+
         let timetable_rows = dsl::timetable_v2
+            .filter(not(exists(
+                scenario_v2::table
+                    .filter(scenario_v2::timetable_id.eq(dsl::id))
+                    .filter(scenario_v2::study_id.eq_any(hidden_study_ids)),
+            )))
             .distinct()
             .paginate(page, page_size)
             .load_and_count::<Row<Timetable>>(conn)
@@ -41,6 +55,45 @@ impl List<NoParams> for Timetable {
     }
 }
 
+impl Timetable {
+    /// Copies the timetable along with its cadences and trains
+    ///
+    /// Returns the copy and its trains, in the order of the original ones.
+    pub async fn duplicate(&self, conn: &mut DbConnection) -> Result<(Self, Vec<TrainSchedule>)> {
+        let copy = Timetable::changeset()
+            .electrical_profile_set_id(self.electrical_profile_set_id)
+            .create(conn)
+            .await?;
+
+        let mut cadence_ids = HashMap::new();
+        for cadence in TrainScheduleCadence::list_for_timetable(conn, self.id).await? {
+            let cadence_id = cadence.id;
+            let cadence_copy = Changeset::<TrainScheduleCadence>::from(cadence)
+                .timetable_id(copy.id)
+                .create(conn)
+                .await?;
+            cadence_ids.insert(cadence_id, cadence_copy.id);
+        }
+
+        let timetable_id = self.id;
+        let trains = <TrainSchedule as crate::modelsv2::prelude::List>::list(
+            conn,
+            SelectionSettings::new()
+                .filter(move || TrainSchedule::TIMETABLE_ID.eq(timetable_id))
+                .order_by(|| TrainSchedule::ID.asc()),
+        )
+        .await?;
+        let changesets = trains.into_iter().map(|train| {
+            let cadence_id = train.cadence_id.map(|cadence_id| cadence_ids[&cadence_id]);
+            Changeset::<TrainSchedule>::from(train)
+                .timetable_id(copy.id)
+                .cadence_id(cadence_id)
+        });
+        let trains: Vec<_> = TrainSchedule::create_batch(conn, changesets).await?;
+        Ok((copy, trains))
+    }
+}
+
 /// Should be used to retrieve a timetable with its trains
 #[derive(Debug, Clone, QueryableByName)]
 pub struct TimetableWithTrains {
@@ -9,6 +9,12 @@ use editoast_schemas::train_schedule::PowerRestrictionItem;
 use editoast_schemas::train_schedule::ScheduleItem;
 use editoast_schemas::train_schedule::TrainScheduleOptions;
 
+use crate::error::Result;
+use crate::modelsv2::prelude::*;
+use crate::modelsv2::rolling_stock_revision::RollingStockRevision;
+use crate::modelsv2::DbConnection;
+use crate::modelsv2::RollingStockModel;
+
 #[derive(Debug, Default, Clone, ModelV2)]
 #[model(table = crate::tables::train_schedule_v2)]
 pub struct TrainSchedule {
@@ -34,4 +40,30 @@ pub struct TrainSchedule {
     pub power_restrictions: Vec<PowerRestrictionItem>,
     #[model(json)]
     pub options: TrainScheduleOptions,
+    /// The cadence which generated the train, if any
+    pub cadence_id: Option<i64>,
+    /// The rolling stock revision the train is pinned to, if any
+    pub rolling_stock_revision_id: Option<i64>,
+    /// The departure of the cadence the train was generated for, if any
+    ///
+    /// It remains when the train is edited individually, so that the cadence doesn't
+    /// generate this departure again.
+    pub cadence_departure: Option<DateTime<Utc>>,
+}
+
+impl TrainSchedule {
+    /// Retrieve the rolling stock of the train, as archived by its pinned revision if any
+    ///
+    /// Trains whose revision was deleted along with its rolling stock fall back to the name lookup.
+    pub async fn rolling_stock(
+        &self,
+        conn: &mut DbConnection,
+    ) -> Result<Option<RollingStockModel>> {
+        if let Some(revision_id) = self.rolling_stock_revision_id {
+            if let Some(revision) = RollingStockRevision::retrieve(conn, revision_id).await? {
+                return Ok(Some(revision.into()));
+            }
+        }
+        RollingStockModel::retrieve_or_derive(conn, self.rolling_stock_name.clone()).await
+    }
 }
@@ -0,0 +1,47 @@
+use chrono::DateTime;
+use chrono::Utc;
+use editoast_derive::ModelV2;
+use editoast_schemas::train_schedule::TrainScheduleBase;
+
+use crate::error::Result;
+use crate::modelsv2::prelude::*;
+use crate::modelsv2::train_schedule::TrainSchedule;
+use crate::modelsv2::DbConnection;
+
+/// A template train schedule repeated at a fixed period within a time window
+#[derive(Debug, Clone, ModelV2)]
+#[model(table = crate::tables::train_schedule_cadence)]
+pub struct TrainScheduleCadence {
+    pub id: i64,
+    pub timetable_id: i64,
+    #[model(json)]
+    pub template: TrainScheduleBase,
+    /// The time between two departures, in milliseconds
+    pub period: i64,
+    pub window_start: DateTime<Utc>,
+    pub window_end: DateTime<Utc>,
+    pub name_pattern: String,
+}
+
+impl TrainScheduleCadence {
+    /// The cadences of a timetable
+    pub async fn list_for_timetable(
+        conn: &mut DbConnection,
+        timetable_id: i64,
+    ) -> Result<Vec<Self>> {
+        let settings = SelectionSettings::new()
+            .filter(move || Self::TIMETABLE_ID.eq(timetable_id))
+            .order_by(|| Self::ID.asc());
+        Self::list(conn, settings).await
+    }
+
+    /// The trains generated by the cadence, sorted by start time
+    pub async fn trains(&self, conn: &mut DbConnection) -> Result<Vec<TrainSchedule>> {
+        let cadence_id = self.id;
+        let settings = SelectionSettings::new()
+            .filter(move || TrainSchedule::CADENCE_ID.eq(Some(cadence_id)))
+            .order_by(|| TrainSchedule::START_TIME.asc())
+            .order_by(|| TrainSchedule::ID.asc());
+        TrainSchedule::list(conn, settings).await
+    }
+}
@@ -0,0 +1,71 @@
+use chrono::NaiveDateTime;
+use diesel::sql_query;
+use diesel::sql_types::Text;
+use diesel_async::RunQueryDsl;
+use editoast_derive::ModelV2;
+use serde::Deserialize;
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::error::Result;
+use crate::modelsv2::prelude::*;
+use crate::modelsv2::DbConnection;
+
+editoast_common::schemas! {
+    User,
+}
+
+/// A user authenticated by the gateway
+///
+/// Users are created the first time a request carrying their identity is received.
+#[derive(Debug, Clone, ModelV2, Serialize, Deserialize, ToSchema, PartialEq)]
+#[model(table = crate::tables::osrd_user)]
+pub struct User {
+    pub id: i64,
+    /// The identity forwarded by the gateway, formatted as `<provider>/<user id>`
+    #[model(identifier)]
+    pub identity: String,
+    /// Identifies the user as the owner of other objects, such as infras
+    pub uuid: Uuid,
+    pub created_at: NaiveDateTime,
+    /// Admins have every role on every resource
+    pub is_admin: bool,
+}
+
+impl User {
+    /// Retrieve the user with the given identity, creating it if it doesn't exist yet
+    pub async fn resolve(conn: &mut DbConnection, identity: &str) -> Result<Self> {
+        // The no-op update makes RETURNING yield the existing row on conflict
+        let row = sql_query(
+            "INSERT INTO osrd_user (identity) VALUES ($1)
+            ON CONFLICT (identity) DO UPDATE SET identity = EXCLUDED.identity
+            RETURNING *",
+        )
+        .bind::<Text, _>(identity)
+        .get_result::<Row<User>>(conn)
+        .await?;
+        Ok(Self::from_row(row))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::fixtures::tests::db_pool;
+
+    #[rstest]
+    async fn resolve_creates_users_once() {
+        let pool = db_pool();
+        let conn = &mut pool.get().await.unwrap();
+        let identity = format!("test/{}", Uuid::new_v4());
+
+        let user = User::resolve(conn, &identity).await.unwrap();
+        let same_user = User::resolve(conn, &identity).await.unwrap();
+        assert_eq!(user, same_user);
+
+        assert!(User::delete_static(conn, user.id).await.unwrap());
+    }
+}
@@ -7,7 +7,9 @@ use serde::Deserialize;
 use serde::Serialize;
 use utoipa::ToSchema;
 
-#[derive(Debug, Clone, ModelV2)]
+use crate::modelsv2::prelude::*;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ModelV2, ToSchema)]
 #[model(table = crate::tables::work_schedule_group)]
 pub struct WorkScheduleGroup {
     pub id: i64,
@@ -23,7 +25,7 @@ pub enum WorkScheduleType {
     Track,
 }
 
-#[derive(Debug, Default, Clone, ModelV2)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ModelV2, ToSchema)]
 #[model(table = crate::tables::work_schedule)]
 pub struct WorkSchedule {
     pub id: i64,
@@ -33,6 +35,30 @@ pub struct WorkSchedule {
     pub track_ranges: Vec<TrackRange>,
     pub obj_id: String,
     #[model(to_enum)]
+    #[schema(inline)]
     pub work_schedule_type: WorkScheduleType,
     pub work_schedule_group_id: i64,
 }
+
+impl WorkSchedule {
+    /// Filters the work schedules that are still active after the given date
+    pub fn ends_after(date_time: NaiveDateTime) -> FilterSetting<WorkSchedule> {
+        use crate::tables::work_schedule::dsl;
+        use diesel::ExpressionMethods;
+        FilterSetting::new(dsl::end_date_time.gt(date_time))
+    }
+
+    /// Filters the work schedules that have started before the given date
+    pub fn starts_before(date_time: NaiveDateTime) -> FilterSetting<WorkSchedule> {
+        use crate::tables::work_schedule::dsl;
+        use diesel::ExpressionMethods;
+        FilterSetting::new(dsl::start_date_time.lt(date_time))
+    }
+
+    /// Filters the work schedules with at least one track range on the given track section
+    pub fn on_track_section(track: String) -> FilterSetting<WorkSchedule> {
+        use crate::tables::work_schedule::dsl;
+        use diesel::PgJsonbExpressionMethods;
+        FilterSetting::new(dsl::track_ranges.contains(serde_json::json!([{ "track": track }])))
+    }
+}
@@ -0,0 +1,821 @@
+//! Rolling stocks as a directory of CSV spreadsheets
+//!
+//! All values use the RailJSON units (m, m/s, m/s², kg, N, W, J and s):
+//! - `characteristics.csv` has `field,value` rows, one per scalar field of the rolling stock.
+//!   The gamma and rolling resistance are split into `gamma_type`, `gamma_value`,
+//!   `rolling_resistance_type` and `rolling_resistance_a`/`_b`/`_c`. Signaling systems are
+//!   separated by `;` and power restrictions are written `code=power_class;...`.
+//! - `effort_curves.csv` has one row per point of the tractive effort curves. Points sharing a
+//!   mode and conditions form a curve, and the points of a mode without condition form its default curve.
+//! - `energy_sources.csv`, if present, has one row per point of the power curves of the energy sources.
+//!   The points of a source share its `source` label and the values of its scalar columns.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+
+use csv::Reader;
+use csv::Writer;
+use editoast_schemas::rolling_stock::ConditionalEffortCurve;
+use editoast_schemas::rolling_stock::EffortCurve;
+use editoast_schemas::rolling_stock::EffortCurveConditions;
+use editoast_schemas::rolling_stock::EffortCurves;
+use editoast_schemas::rolling_stock::EnergySource;
+use editoast_schemas::rolling_stock::EnergyStorage;
+use editoast_schemas::rolling_stock::Gamma;
+use editoast_schemas::rolling_stock::LoadingGaugeType;
+use editoast_schemas::rolling_stock::ModeEffortCurves;
+use editoast_schemas::rolling_stock::RefillLaw;
+use editoast_schemas::rolling_stock::RollingResistance;
+use editoast_schemas::rolling_stock::RollingStock;
+use editoast_schemas::rolling_stock::RollingStockComfortType;
+use editoast_schemas::rolling_stock::RollingStockSupportedSignalingSystems;
+use editoast_schemas::rolling_stock::SpeedDependantPower;
+use editoast_schemas::rolling_stock::ROLLING_STOCK_RAILJSON_VERSION;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::ConversionReport;
+
+pub const CHARACTERISTICS_FILE: &str = "characteristics.csv";
+pub const EFFORT_CURVES_FILE: &str = "effort_curves.csv";
+pub const ENERGY_SOURCES_FILE: &str = "energy_sources.csv";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Characteristic {
+    field: String,
+    value: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct EffortCurvePoint {
+    mode: String,
+    is_electric: bool,
+    comfort: Option<RollingStockComfortType>,
+    electrical_profile_level: Option<String>,
+    power_restriction_code: Option<String>,
+    speed: f64,
+    max_effort: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct EnergySourcePoint {
+    source: String,
+    /// `Electrification`, `PowerPack` or `Battery`
+    energy_source_type: String,
+    efficiency: f64,
+    capacity: Option<f64>,
+    soc: Option<f64>,
+    soc_min: Option<f64>,
+    soc_max: Option<f64>,
+    refill_tau: Option<f64>,
+    refill_soc_ref: Option<f64>,
+    speed: f64,
+    max_input_power: f64,
+    max_output_power: f64,
+}
+
+/// Reads a rolling stock from a directory holding its spreadsheets
+pub fn read_dir(dir: &Path) -> Result<RollingStock, ConversionReport> {
+    let open = |name: &str| File::open(dir.join(name));
+    let mut report = ConversionReport::default();
+    let characteristics = open(CHARACTERISTICS_FILE)
+        .map_err(|error| report.invalid(CHARACTERISTICS_FILE, error))
+        .ok();
+    let effort_curves = open(EFFORT_CURVES_FILE)
+        .map_err(|error| report.invalid(EFFORT_CURVES_FILE, error))
+        .ok();
+    let energy_sources = match open(ENERGY_SOURCES_FILE) {
+        Ok(file) => Some(file),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+        Err(error) => {
+            report.invalid(ENERGY_SOURCES_FILE, error);
+            None
+        }
+    };
+    match (characteristics, effort_curves) {
+        (Some(characteristics), Some(effort_curves)) if report.is_empty() => {
+            read(characteristics, effort_curves, energy_sources)
+        }
+        _ => Err(report),
+    }
+}
+
+/// Reads a rolling stock from its spreadsheets
+pub fn read(
+    characteristics: impl Read,
+    effort_curves: impl Read,
+    energy_sources: Option<impl Read>,
+) -> Result<RollingStock, ConversionReport> {
+    let mut report = ConversionReport::default();
+    let characteristics: Vec<Characteristic> =
+        read_rows(characteristics, CHARACTERISTICS_FILE, &mut report);
+    let effort_curve_points: Vec<EffortCurvePoint> =
+        read_rows(effort_curves, EFFORT_CURVES_FILE, &mut report);
+    let energy_source_points: Vec<EnergySourcePoint> = energy_sources
+        .map(|energy_sources| read_rows(energy_sources, ENERGY_SOURCES_FILE, &mut report))
+        .unwrap_or_default();
+
+    let mut fields = Fields::new(characteristics, &mut report);
+    let default_mode = fields.required("default_mode");
+    let rolling_stock = RollingStock {
+        name: fields.required("name").unwrap_or_default(),
+        base_power_class: fields.optional("base_power_class"),
+        length: fields.required_number("length"),
+        max_speed: fields.required_number("max_speed"),
+        startup_time: fields.required_number("startup_time"),
+        startup_acceleration: fields.required_number("startup_acceleration"),
+        comfort_acceleration: fields.required_number("comfort_acceleration"),
+        gamma: Gamma {
+            gamma_type: fields.required("gamma_type").unwrap_or_default(),
+            value: fields.required_number("gamma_value"),
+        },
+        inertia_coefficient: fields.required_number("inertia_coefficient"),
+        mass: fields.required_number("mass"),
+        rolling_resistance: RollingResistance {
+            rolling_resistance_type: fields
+                .required("rolling_resistance_type")
+                .unwrap_or_default(),
+            A: fields.required_number("rolling_resistance_a"),
+            B: fields.required_number("rolling_resistance_b"),
+            C: fields.required_number("rolling_resistance_c"),
+        },
+        loading_gauge: fields.loading_gauge(),
+        power_restrictions: fields.power_restrictions(),
+        electrical_power_startup_time: fields.optional_number("electrical_power_startup_time"),
+        raise_pantograph_time: fields.optional_number("raise_pantograph_time"),
+        supported_signaling_systems: RollingStockSupportedSignalingSystems(
+            fields
+                .optional("supported_signaling_systems")
+                .map(|systems| split_list(&systems).map(str::to_owned).collect())
+                .unwrap_or_default(),
+        ),
+        effort_curves: EffortCurves::default(),
+        energy_sources: vec![],
+        railjson_version: ROLLING_STOCK_RAILJSON_VERSION.to_owned(),
+        metadata: None,
+    };
+    fields.check_unused();
+
+    let effort_curves = build_effort_curves(effort_curve_points, default_mode, &mut report);
+    let energy_sources = build_energy_sources(energy_source_points, &mut report);
+    if !report.is_empty() {
+        return Err(report);
+    }
+    Ok(RollingStock {
+        effort_curves,
+        energy_sources,
+        ..rolling_stock
+    })
+}
+
+fn read_rows<T: DeserializeOwned>(
+    reader: impl Read,
+    file: &str,
+    report: &mut ConversionReport,
+) -> Vec<T> {
+    let mut rows = vec![];
+    for row in Reader::from_reader(reader).deserialize() {
+        match row {
+            Ok(row) => rows.push(row),
+            Err(error) => {
+                let location = match error.position() {
+                    Some(position) => format!("{file}:{}", position.line()),
+                    None => file.to_owned(),
+                };
+                report.invalid(location, error);
+            }
+        }
+    }
+    rows
+}
+
+fn split_list(list: &str) -> impl Iterator<Item = &str> {
+    list.split(';')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+}
+
+/// The rows of `characteristics.csv`, reporting the missing and invalid fields as they are read
+struct Fields<'a> {
+    values: HashMap<String, String>,
+    report: &'a mut ConversionReport,
+}
+
+impl<'a> Fields<'a> {
+    fn new(characteristics: Vec<Characteristic>, report: &'a mut ConversionReport) -> Self {
+        let mut values = HashMap::new();
+        for Characteristic { field, value } in characteristics {
+            if values.contains_key(&field) {
+                report.invalid(CHARACTERISTICS_FILE, format!("'{field}' is set twice"));
+            }
+            values.insert(field, value.trim().to_owned());
+        }
+        Self { values, report }
+    }
+
+    fn optional(&mut self, field: &str) -> Option<String> {
+        self.values.remove(field).filter(|value| !value.is_empty())
+    }
+
+    fn required(&mut self, field: &str) -> Option<String> {
+        let value = self.optional(field);
+        if value.is_none() {
+            self.report.missing(CHARACTERISTICS_FILE, field);
+        }
+        value
+    }
+
+    fn optional_number(&mut self, field: &str) -> Option<f64> {
+        let value = self.optional(field)?;
+        match value.parse() {
+            Ok(value) => Some(value),
+            Err(_) => {
+                self.report.invalid(
+                    CHARACTERISTICS_FILE,
+                    format!("'{field}' should be a number, found '{value}'"),
+                );
+                None
+            }
+        }
+    }
+
+    fn required_number(&mut self, field: &str) -> f64 {
+        if self.values.get(field).map_or(true, String::is_empty) {
+            self.report.missing(CHARACTERISTICS_FILE, field);
+        }
+        self.optional_number(field).unwrap_or_default()
+    }
+
+    fn loading_gauge(&mut self) -> LoadingGaugeType {
+        let Some(value) = self.required("loading_gauge") else {
+            return LoadingGaugeType::G1;
+        };
+        serde_json::from_value(serde_json::Value::String(value.clone())).unwrap_or_else(|_| {
+            self.report.invalid(
+                CHARACTERISTICS_FILE,
+                format!("unknown loading gauge '{value}'"),
+            );
+            LoadingGaugeType::G1
+        })
+    }
+
+    fn power_restrictions(&mut self) -> HashMap<String, String> {
+        let Some(value) = self.optional("power_restrictions") else {
+            return HashMap::new();
+        };
+        let mut power_restrictions = HashMap::new();
+        for restriction in split_list(&value) {
+            match restriction.split_once('=') {
+                Some((code, power_class)) => {
+                    power_restrictions
+                        .insert(code.trim().to_owned(), power_class.trim().to_owned());
+                }
+                None => self.report.invalid(
+                    CHARACTERISTICS_FILE,
+                    format!(
+                        "power restriction '{restriction}' should be written 'code=power_class'"
+                    ),
+                ),
+            }
+        }
+        power_restrictions
+    }
+
+    fn check_unused(self) {
+        let mut unused: Vec<_> = self.values.into_keys().collect();
+        unused.sort();
+        for field in unused {
+            self.report
+                .invalid(CHARACTERISTICS_FILE, format!("unknown field '{field}'"));
+        }
+    }
+}
+
+#[derive(Default)]
+struct ModePoints {
+    is_electric: bool,
+    /// The points of each curve, the default curve having no condition
+    curves: Vec<(EffortCurveConditions, Vec<f64>, Vec<f64>)>,
+}
+
+const NO_CONDITION: EffortCurveConditions = EffortCurveConditions {
+    comfort: None,
+    electrical_profile_level: None,
+    power_restriction_code: None,
+};
+
+fn build_effort_curves(
+    points: Vec<EffortCurvePoint>,
+    default_mode: Option<String>,
+    report: &mut ConversionReport,
+) -> EffortCurves {
+    let mut modes: BTreeMap<String, ModePoints> = BTreeMap::new();
+    for point in points {
+        let mode = modes
+            .entry(point.mode.clone())
+            .or_insert_with(|| ModePoints {
+                is_electric: point.is_electric,
+                curves: vec![],
+            });
+        if mode.is_electric != point.is_electric {
+            report.invalid(
+                EFFORT_CURVES_FILE,
+                format!("mode '{}' is both electric and not electric", point.mode),
+            );
+        }
+        let cond = EffortCurveConditions {
+            comfort: point.comfort,
+            electrical_profile_level: point.electrical_profile_level,
+            power_restriction_code: point.power_restriction_code,
+        };
+        match mode.curves.iter_mut().find(|(c, _, _)| *c == cond) {
+            Some((_, speeds, max_efforts)) => {
+                speeds.push(point.speed);
+                max_efforts.push(point.max_effort);
+            }
+            None => mode
+                .curves
+                .push((cond, vec![point.speed], vec![point.max_effort])),
+        }
+    }
+
+    let mut effort_curves = EffortCurves::default();
+    for (name, mode) in modes {
+        let mut default_curve = None;
+        let mut curves = vec![];
+        for (cond, speeds, max_efforts) in mode.curves {
+            let curve = match EffortCurve::new(speeds, max_efforts) {
+                Ok(curve) => curve,
+                Err(error) => {
+                    report.invalid(
+                        EFFORT_CURVES_FILE,
+                        format!("mode '{name}' {}: {error}", describe_conditions(&cond)),
+                    );
+                    continue;
+                }
+            };
+            if cond == NO_CONDITION {
+                default_curve = Some(curve);
+            } else {
+                curves.push(ConditionalEffortCurve { cond, curve });
+            }
+        }
+        let Some(default_curve) = default_curve else {
+            report.invalid(
+                EFFORT_CURVES_FILE,
+                format!("mode '{name}' has no default curve, whose points have no condition"),
+            );
+            continue;
+        };
+        effort_curves.modes.insert(
+            name,
+            ModeEffortCurves {
+                curves,
+                default_curve,
+                is_electric: mode.is_electric,
+            },
+        );
+    }
+
+    match default_mode {
+        Some(default_mode) if !effort_curves.modes.contains_key(&default_mode) => {
+            report.invalid(
+                CHARACTERISTICS_FILE,
+                format!("default mode '{default_mode}' has no effort curve"),
+            );
+        }
+        Some(default_mode) => effort_curves.default_mode = default_mode,
+        None => (),
+    }
+    effort_curves
+}
+
+fn describe_conditions(cond: &EffortCurveConditions) -> String {
+    if *cond == NO_CONDITION {
+        return "default curve".to_owned();
+    }
+    let conditions: Vec<_> = [
+        cond.comfort
+            .as_ref()
+            .map(|comfort| format!("comfort {comfort}")),
+        cond.electrical_profile_level
+            .as_ref()
+            .map(|level| format!("electrical profile {level}")),
+        cond.power_restriction_code
+            .as_ref()
+            .map(|code| format!("power restriction {code}")),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    format!("curve ({})", conditions.join(", "))
+}
+
+fn build_energy_sources(
+    points: Vec<EnergySourcePoint>,
+    report: &mut ConversionReport,
+) -> Vec<EnergySource> {
+    let power = |speed, power| SpeedDependantPower {
+        speeds: vec![speed],
+        powers: vec![power],
+    };
+    let mut sources: Vec<(EnergySourcePoint, SpeedDependantPower, SpeedDependantPower)> = vec![];
+    for point in points {
+        let Some((first, input, output)) = sources
+            .iter_mut()
+            .find(|(first, _, _)| first.source == point.source)
+        else {
+            let input = power(point.speed, point.max_input_power);
+            let output = power(point.speed, point.max_output_power);
+            sources.push((point, input, output));
+            continue;
+        };
+        let scalars = |point: &EnergySourcePoint| {
+            (
+                point.energy_source_type.clone(),
+                point.efficiency,
+                point.capacity,
+                point.soc,
+                point.soc_min,
+                point.soc_max,
+                point.refill_tau,
+                point.refill_soc_ref,
+            )
+        };
+        if scalars(first) != scalars(&point) {
+            report.invalid(
+                ENERGY_SOURCES_FILE,
+                format!(
+                    "the points of source '{}' have different characteristics",
+                    point.source
+                ),
+            );
+        }
+        input.speeds.push(point.speed);
+        input.powers.push(point.max_input_power);
+        output.speeds.push(point.speed);
+        output.powers.push(point.max_output_power);
+    }
+
+    let mut energy_sources = vec![];
+    for (source, max_input_power, max_output_power) in sources {
+        let location = format!("{ENERGY_SOURCES_FILE}: source '{}'", source.source);
+        let efficiency = source.efficiency;
+        let energy_source = match source.energy_source_type.as_str() {
+            "Electrification" => EnergySource::Electrification {
+                max_input_power,
+                max_output_power,
+                efficiency,
+            },
+            "PowerPack" | "Battery" => {
+                let Some(energy_storage) = energy_storage(&source, &location, report) else {
+                    continue;
+                };
+                if source.energy_source_type == "PowerPack" {
+                    EnergySource::PowerPack {
+                        max_input_power,
+                        max_output_power,
+                        energy_storage,
+                        efficiency,
+                    }
+                } else {
+                    EnergySource::Battery {
+                        max_input_power,
+                        max_output_power,
+                        energy_storage,
+                        efficiency,
+                    }
+                }
+            }
+            energy_source_type => {
+                report.invalid(
+                    location,
+                    format!("unknown energy source type '{energy_source_type}'"),
+                );
+                continue;
+            }
+        };
+        energy_sources.push(energy_source);
+    }
+    energy_sources
+}
+
+fn energy_storage(
+    source: &EnergySourcePoint,
+    location: &str,
+    report: &mut ConversionReport,
+) -> Option<EnergyStorage> {
+    let mut required = |field: &str, value: Option<f64>| {
+        if value.is_none() {
+            report.missing(location, field);
+        }
+        value
+    };
+    let capacity = required("capacity", source.capacity);
+    let soc = required("soc", source.soc);
+    let soc_min = required("soc_min", source.soc_min);
+    let soc_max = required("soc_max", source.soc_max);
+    let refill_law = match (source.refill_tau, source.refill_soc_ref) {
+        (Some(tau), Some(soc_ref)) => Some(RefillLaw { tau, soc_ref }),
+        (None, None) => None,
+        _ => {
+            report.invalid(
+                location,
+                "'refill_tau' and 'refill_soc_ref' should be both set or both empty",
+            );
+            None
+        }
+    };
+    Some(EnergyStorage {
+        capacity: capacity?,
+        soc: soc?,
+        soc_min: soc_min?,
+        soc_max: soc_max?,
+        refill_law,
+    })
+}
+
+/// Writes the spreadsheets of a rolling stock to a directory, which is created if needed
+pub fn write_dir(rolling_stock: &RollingStock, dir: &Path) -> csv::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let energy_sources = if rolling_stock.energy_sources.is_empty() {
+        None
+    } else {
+        Some(File::create(dir.join(ENERGY_SOURCES_FILE))?)
+    };
+    write(
+        rolling_stock,
+        File::create(dir.join(CHARACTERISTICS_FILE))?,
+        File::create(dir.join(EFFORT_CURVES_FILE))?,
+        energy_sources,
+    )
+}
+
+/// Writes the spreadsheets of a rolling stock, the energy sources being written only if a writer is given
+pub fn write(
+    rolling_stock: &RollingStock,
+    characteristics: impl Write,
+    effort_curves: impl Write,
+    energy_sources: Option<impl Write>,
+) -> csv::Result<()> {
+    let mut power_restrictions: Vec<_> = rolling_stock
+        .power_restrictions
+        .iter()
+        .map(|(code, power_class)| format!("{code}={power_class}"))
+        .collect();
+    power_restrictions.sort();
+    let number = |value: f64| value.to_string();
+    let loading_gauge = serde_json::to_value(rolling_stock.loading_gauge)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_owned))
+        .unwrap_or_default();
+    let fields = [
+        ("name", rolling_stock.name.clone()),
+        (
+            "base_power_class",
+            rolling_stock.base_power_class.clone().unwrap_or_default(),
+        ),
+        ("length", number(rolling_stock.length)),
+        ("max_speed", number(rolling_stock.max_speed)),
+        ("startup_time", number(rolling_stock.startup_time)),
+        (
+            "startup_acceleration",
+            number(rolling_stock.startup_acceleration),
+        ),
+        (
+            "comfort_acceleration",
+            number(rolling_stock.comfort_acceleration),
+        ),
+        ("gamma_type", rolling_stock.gamma.gamma_type.clone()),
+        ("gamma_value", number(rolling_stock.gamma.value)),
+        (
+            "inertia_coefficient",
+            number(rolling_stock.inertia_coefficient),
+        ),
+        ("mass", number(rolling_stock.mass)),
+        (
+            "rolling_resistance_type",
+            rolling_stock
+                .rolling_resistance
+                .rolling_resistance_type
+                .clone(),
+        ),
+        (
+            "rolling_resistance_a",
+            number(rolling_stock.rolling_resistance.A),
+        ),
+        (
+            "rolling_resistance_b",
+            number(rolling_stock.rolling_resistance.B),
+        ),
+        (
+            "rolling_resistance_c",
+            number(rolling_stock.rolling_resistance.C),
+        ),
+        ("loading_gauge", loading_gauge),
+        ("power_restrictions", power_restrictions.join(";")),
+        (
+            "electrical_power_startup_time",
+            rolling_stock
+                .electrical_power_startup_time
+                .map(number)
+                .unwrap_or_default(),
+        ),
+        (
+            "raise_pantograph_time",
+            rolling_stock
+                .raise_pantograph_time
+                .map(number)
+                .unwrap_or_default(),
+        ),
+        (
+            "supported_signaling_systems",
+            rolling_stock.supported_signaling_systems.0.join(";"),
+        ),
+        (
+            "default_mode",
+            rolling_stock.effort_curves.default_mode.clone(),
+        ),
+    ];
+    let mut writer = Writer::from_writer(characteristics);
+    for (field, value) in fields {
+        writer.serialize(Characteristic {
+            field: field.to_owned(),
+            value,
+        })?;
+    }
+    writer.flush()?;
+
+    let mut writer = Writer::from_writer(effort_curves);
+    for (name, mode) in &rolling_stock.effort_curves.modes {
+        let curves = std::iter::once((&NO_CONDITION, &mode.default_curve))
+            .chain(mode.curves.iter().map(|curve| (&curve.cond, &curve.curve)));
+        for (cond, curve) in curves {
+            for (&speed, &max_effort) in curve.speeds().iter().zip(curve.max_efforts()) {
+                writer.serialize(EffortCurvePoint {
+                    mode: name.clone(),
+                    is_electric: mode.is_electric,
+                    comfort: cond.comfort.clone(),
+                    electrical_profile_level: cond.electrical_profile_level.clone(),
+                    power_restriction_code: cond.power_restriction_code.clone(),
+                    speed,
+                    max_effort,
+                })?;
+            }
+        }
+    }
+    writer.flush()?;
+
+    let Some(energy_sources) = energy_sources else {
+        return Ok(());
+    };
+    let mut writer = Writer::from_writer(energy_sources);
+    for (index, energy_source) in rolling_stock.energy_sources.iter().enumerate() {
+        let (energy_source_type, max_input_power, max_output_power, energy_storage, efficiency) =
+            match energy_source {
+                EnergySource::Electrification {
+                    max_input_power,
+                    max_output_power,
+                    efficiency,
+                } => (
+                    "Electrification",
+                    max_input_power,
+                    max_output_power,
+                    None,
+                    efficiency,
+                ),
+                EnergySource::PowerPack {
+                    max_input_power,
+                    max_output_power,
+                    energy_storage,
+                    efficiency,
+                } => (
+                    "PowerPack",
+                    max_input_power,
+                    max_output_power,
+                    Some(energy_storage),
+                    efficiency,
+                ),
+                EnergySource::Battery {
+                    max_input_power,
+                    max_output_power,
+                    energy_storage,
+                    efficiency,
+                } => (
+                    "Battery",
+                    max_input_power,
+                    max_output_power,
+                    Some(energy_storage),
+                    efficiency,
+                ),
+            };
+        // The input and output powers share their speeds once written, so both are sampled on every speed
+        let mut speeds: Vec<f64> = max_input_power
+            .speeds
+            .iter()
+            .chain(&max_output_power.speeds)
+            .copied()
+            .collect();
+        speeds.sort_by(f64::total_cmp);
+        speeds.dedup();
+        let refill_law = energy_storage.and_then(|storage| storage.refill_law.as_ref());
+        for speed in speeds {
+            writer.serialize(EnergySourcePoint {
+                source: (index + 1).to_string(),
+                energy_source_type: energy_source_type.to_owned(),
+                efficiency: *efficiency,
+                capacity: energy_storage.map(|storage| storage.capacity),
+                soc: energy_storage.map(|storage| storage.soc),
+                soc_min: energy_storage.map(|storage| storage.soc_min),
+                soc_max: energy_storage.map(|storage| storage.soc_max),
+                refill_tau: refill_law.map(|law| law.tau),
+                refill_soc_ref: refill_law.map(|law| law.soc_ref),
+                speed,
+                max_input_power: power_at(max_input_power, speed),
+                max_output_power: power_at(max_output_power, speed),
+            })?;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Linearly interpolates a power curve, which is constant beyond its bounds
+fn power_at(power: &SpeedDependantPower, speed: f64) -> f64 {
+    let points: Vec<_> = power.speeds.iter().zip(&power.powers).collect();
+    match points.iter().position(|(&s, _)| s >= speed) {
+        None => points.last().map(|(_, &p)| p).unwrap_or_default(),
+        Some(0) => *points[0].1,
+        Some(index) => {
+            let (&s0, &p0) = points[index - 1];
+            let (&s1, &p1) = points[index];
+            p0 + (p1 - p0) * (speed - s0) / (s1 - s0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::tests::get_fast_rolling_stock_schema;
+
+    fn round_trip(rolling_stock: &RollingStock) -> Result<RollingStock, ConversionReport> {
+        let (mut characteristics, mut effort_curves, mut energy_sources) = (vec![], vec![], vec![]);
+        write(
+            rolling_stock,
+            &mut characteristics,
+            &mut effort_curves,
+            Some(&mut energy_sources),
+        )
+        .unwrap();
+        read(
+            &characteristics[..],
+            &effort_curves[..],
+            Some(&energy_sources[..]),
+        )
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let mut rolling_stock = get_fast_rolling_stock_schema("csv_rolling_stock");
+        rolling_stock.metadata = None;
+        rolling_stock.energy_sources = serde_json::from_str::<RollingStock>(include_str!(
+            "../tests/example_rolling_stock_2_energy_sources.json"
+        ))
+        .unwrap()
+        .energy_sources;
+        assert!(!rolling_stock.energy_sources.is_empty());
+
+        assert_eq!(round_trip(&rolling_stock), Ok(rolling_stock));
+    }
+
+    #[test]
+    fn csv_missing_fields() {
+        let characteristics = "field,value\nname,test\nmass,heavy\ncolor,blue\n";
+        let effort_curves = "mode,is_electric,comfort,electrical_profile_level,power_restriction_code,speed,max_effort\n\
+            thermal,false,,,,0,100\n\
+            thermal,false,,,,1,90\n\
+            thermal,false,AC,,,1,90\n";
+        let report = read(
+            characteristics.as_bytes(),
+            effort_curves.as_bytes(),
+            None::<&[u8]>,
+        )
+        .unwrap_err();
+        let messages: Vec<_> = report.issues.iter().map(ToString::to_string).collect();
+        assert!(messages.contains(&"characteristics.csv: 'length' is missing".to_owned()));
+        assert!(messages
+            .contains(&"characteristics.csv: 'mass' should be a number, found 'heavy'".to_owned()));
+        assert!(messages.contains(&"characteristics.csv: unknown field 'color'".to_owned()));
+        assert!(messages.contains(
+            &"effort_curves.csv: mode 'thermal' curve (comfort AC): effort curve should have at least 2 points."
+                .to_owned()
+        ));
+    }
+}
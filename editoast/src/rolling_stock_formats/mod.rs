@@ -0,0 +1,49 @@
+//! Rolling stock exchange formats
+//!
+//! Rolling stock data usually comes from manufacturers, either as spreadsheets of
+//! tractive effort curves or as railML rollingstock documents. Both are converted from and to
+//! RailJSON [RollingStock](editoast_schemas::rolling_stock::RollingStock)s.
+
+pub mod csv;
+pub mod railml;
+
+use std::fmt;
+
+use serde::Serialize;
+
+/// The missing or inconsistent fields found while reading a rolling stock,
+/// or the data left out while writing it
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct ConversionReport {
+    pub issues: Vec<ConversionIssue>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConversionIssue {
+    /// Where the issue was found, e.g. `effort_curves.csv:12` or `vehicle 'vh_1'`
+    pub location: String,
+    pub message: String,
+}
+
+impl ConversionReport {
+    pub fn is_empty(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    fn missing(&mut self, location: impl ToString, field: &str) {
+        self.invalid(location, format!("'{field}' is missing"));
+    }
+
+    fn invalid(&mut self, location: impl ToString, message: impl ToString) {
+        self.issues.push(ConversionIssue {
+            location: location.to_string(),
+            message: message.to_string(),
+        });
+    }
+}
+
+impl fmt::Display for ConversionIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
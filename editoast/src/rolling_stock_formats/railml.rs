@@ -0,0 +1,754 @@
+//! Rolling stocks as railML 3 `vehicle`s
+//!
+//! Only the following parts of a `vehicle` are read and written, with the railML units
+//! (km/h, t, kN, kW and kWh):
+//! - `@speed`, `@length`, `@bruttoWeight`, `@rotatingMassFactor` and its `name`
+//! - `brakes/@meanDeceleration` (or `@maxDeceleration`) giving the gamma
+//! - `trainResistance/daviesFormulaFactors`, whose factors are in kN, kN/(km/h) and kN/(km/h)²
+//! - the `engines/engine/tractionMode`s, electric modes being named after the voltage
+//!   of their `electrificationSystem` unless they have an `osrd:modeName`, and their
+//!   `tractiveEffort` value table
+//! - the `engines/engine/energyStorage`s, as battery or power pack energy sources with constant powers
+//!
+//! The data railML has no place for (startup time and acceleration, comfort acceleration,
+//! loading gauge, signaling systems, power classes and pantograph times) is held by an
+//! `osrd:characteristics` extension element.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use editoast_schemas::rolling_stock::EffortCurve;
+use editoast_schemas::rolling_stock::EffortCurves;
+use editoast_schemas::rolling_stock::EnergySource;
+use editoast_schemas::rolling_stock::EnergyStorage;
+use editoast_schemas::rolling_stock::Gamma;
+use editoast_schemas::rolling_stock::LoadingGaugeType;
+use editoast_schemas::rolling_stock::ModeEffortCurves;
+use editoast_schemas::rolling_stock::RollingResistance;
+use editoast_schemas::rolling_stock::RollingStock;
+use editoast_schemas::rolling_stock::RollingStockSupportedSignalingSystems;
+use editoast_schemas::rolling_stock::SpeedDependantPower;
+use editoast_schemas::rolling_stock::ROLLING_STOCK_RAILJSON_VERSION;
+use roxmltree::Document;
+use roxmltree::Node;
+
+use super::ConversionReport;
+
+const RAILML_NAMESPACE: &str = "https://www.railml.org/schemas/3.2";
+const OSRD_NAMESPACE: &str = "https://osrd.fr/railml/rollingstock";
+
+const KMH: f64 = 1. / 3.6;
+const TONNE: f64 = 1000.;
+const KILO: f64 = 1000.;
+const KWH: f64 = 3.6e6;
+
+/// The mode of the non electric traction modes
+const THERMAL_MODE: &str = "thermal";
+
+fn children<'a, 'input: 'a>(
+    node: Node<'a, 'input>,
+    name: &'static str,
+) -> impl Iterator<Item = Node<'a, 'input>> {
+    node.children()
+        .filter(move |child| child.is_element() && child.tag_name().name() == name)
+}
+
+fn child<'a, 'input: 'a>(node: Node<'a, 'input>, name: &'static str) -> Option<Node<'a, 'input>> {
+    children(node, name).next()
+}
+
+/// Reads an attribute, whatever its namespace
+fn attribute<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attributes()
+        .find(|attribute| attribute.name() == name)
+        .map(|attribute| attribute.value().trim())
+        .filter(|value| !value.is_empty())
+}
+
+/// Reads the vehicles of a railML document
+///
+/// The vehicles having missing or invalid fields are listed in the report and left out.
+pub fn read(railml: &str) -> (Vec<RollingStock>, ConversionReport) {
+    let mut report = ConversionReport::default();
+    let document = match Document::parse(railml) {
+        Ok(document) => document,
+        Err(error) => {
+            report.invalid("document", format!("invalid XML: {error}"));
+            return (vec![], report);
+        }
+    };
+    let root = document.root_element();
+    if root.tag_name().name() != "railML" {
+        report.invalid(
+            "document",
+            format!(
+                "the root element must be 'railML', found '{}'",
+                root.tag_name().name()
+            ),
+        );
+        return (vec![], report);
+    }
+    if let Some(version) = root.attribute("version") {
+        if !version.starts_with("3.") {
+            report.invalid(
+                "document",
+                format!("unsupported railML version '{version}', expected 3.x"),
+            );
+            return (vec![], report);
+        }
+    }
+
+    let vehicles: Vec<_> = child(root, "rollingstock")
+        .and_then(|rollingstock| child(rollingstock, "vehicles"))
+        .map(|vehicles| children(vehicles, "vehicle").collect())
+        .unwrap_or_default();
+    if vehicles.is_empty() {
+        report.invalid("document", "no 'rollingstock/vehicles/vehicle' found");
+    }
+    let rolling_stocks = vehicles
+        .into_iter()
+        .filter_map(|vehicle| {
+            let mut vehicle_report = ConversionReport::default();
+            let rolling_stock = read_vehicle(vehicle, &mut vehicle_report);
+            let valid = vehicle_report.is_empty();
+            report.issues.extend(vehicle_report.issues);
+            valid.then_some(rolling_stock)
+        })
+        .collect();
+    (rolling_stocks, report)
+}
+
+/// Reads the attributes of an element, reporting the missing and invalid ones
+struct Attributes<'a, 'r> {
+    node: Option<Node<'a, 'a>>,
+    element: &'static str,
+    location: &'r str,
+    report: &'r mut ConversionReport,
+}
+
+impl<'a, 'r> Attributes<'a, 'r> {
+    fn optional(&mut self, name: &str) -> Option<String> {
+        attribute(self.node?, name).map(str::to_owned)
+    }
+
+    fn required(&mut self, name: &str) -> String {
+        let value = self.optional(name);
+        if value.is_none() {
+            self.report
+                .missing(self.location, &format!("{}@{name}", self.element));
+        }
+        value.unwrap_or_default()
+    }
+
+    fn optional_number(&mut self, name: &str) -> Option<f64> {
+        let value = self.optional(name)?;
+        match value.parse() {
+            Ok(value) => Some(value),
+            Err(_) => {
+                self.report.invalid(
+                    self.location,
+                    format!(
+                        "'{}@{name}' should be a number, found '{value}'",
+                        self.element
+                    ),
+                );
+                None
+            }
+        }
+    }
+
+    fn required_number(&mut self, name: &str) -> f64 {
+        if self.optional(name).is_none() {
+            self.report
+                .missing(self.location, &format!("{}@{name}", self.element));
+        }
+        self.optional_number(name).unwrap_or_default()
+    }
+}
+
+fn read_vehicle(vehicle: Node, report: &mut ConversionReport) -> RollingStock {
+    let location = format!("vehicle '{}'", vehicle.attribute("id").unwrap_or_default());
+    let location = location.as_str();
+
+    let name = children(vehicle, "name")
+        .filter_map(|name| attribute(name, "name"))
+        .next()
+        .map(str::to_owned);
+    if name.is_none() {
+        report.missing(location, "name");
+    }
+
+    let mut attributes = Attributes {
+        node: Some(vehicle),
+        element: "vehicle",
+        location,
+        report,
+    };
+    let max_speed = attributes.required_number("speed") * KMH;
+    let length = attributes.required_number("length");
+    let mass = attributes.required_number("bruttoWeight") * TONNE;
+    let inertia_coefficient = attributes.required_number("rotatingMassFactor");
+
+    let gamma = read_gamma(vehicle, location, report);
+    let rolling_resistance = read_rolling_resistance(vehicle, location, report);
+
+    let mut characteristics = Attributes {
+        node: child(vehicle, "characteristics"),
+        element: "osrd:characteristics",
+        location,
+        report,
+    };
+    let startup_time = characteristics.required_number("startupTime");
+    let startup_acceleration = characteristics.required_number("startupAcceleration");
+    let comfort_acceleration = characteristics.required_number("comfortAcceleration");
+    let loading_gauge = characteristics.required("loadingGauge");
+    let loading_gauge = (!loading_gauge.is_empty()).then_some(loading_gauge);
+    let base_power_class = characteristics.optional("basePowerClass");
+    let electrical_power_startup_time =
+        characteristics.optional_number("electricalPowerStartupTime");
+    let raise_pantograph_time = characteristics.optional_number("raisePantographTime");
+    let supported_signaling_systems = characteristics
+        .optional("supportedSignalingSystems")
+        .map(|systems| systems.split_whitespace().map(str::to_owned).collect())
+        .unwrap_or_default();
+    let power_restrictions: HashMap<_, _> = characteristics
+        .optional("powerRestrictions")
+        .map(|restrictions| {
+            restrictions
+                .split_whitespace()
+                .filter_map(|restriction| restriction.split_once('='))
+                .map(|(code, power_class)| (code.to_owned(), power_class.to_owned()))
+                .collect()
+        })
+        .unwrap_or_default();
+    let loading_gauge = loading_gauge
+        .and_then(|loading_gauge| {
+            let parsed = serde_json::from_value(serde_json::Value::String(loading_gauge.clone()));
+            if parsed.is_err() {
+                report.invalid(location, format!("unknown loading gauge '{loading_gauge}'"));
+            }
+            parsed.ok()
+        })
+        .unwrap_or(LoadingGaugeType::G1);
+
+    let engines: Vec<_> = child(vehicle, "engines")
+        .map(|engines| children(engines, "engine").collect())
+        .unwrap_or_default();
+    let effort_curves = read_effort_curves(&engines, location, report);
+    let energy_sources = engines
+        .iter()
+        .flat_map(|engine| children(*engine, "energyStorage"))
+        .filter_map(|storage| read_energy_storage(storage, location, report))
+        .collect();
+
+    RollingStock {
+        name: name.unwrap_or_default(),
+        effort_curves,
+        base_power_class,
+        length,
+        max_speed,
+        startup_time,
+        startup_acceleration,
+        comfort_acceleration,
+        gamma,
+        inertia_coefficient,
+        mass,
+        rolling_resistance,
+        loading_gauge,
+        power_restrictions,
+        energy_sources,
+        electrical_power_startup_time,
+        raise_pantograph_time,
+        supported_signaling_systems: RollingStockSupportedSignalingSystems(
+            supported_signaling_systems,
+        ),
+        railjson_version: ROLLING_STOCK_RAILJSON_VERSION.to_owned(),
+        metadata: None,
+    }
+}
+
+fn read_gamma(vehicle: Node, location: &str, report: &mut ConversionReport) -> Gamma {
+    let mut brakes = Attributes {
+        node: child(vehicle, "brakes"),
+        element: "brakes",
+        location,
+        report,
+    };
+    if let Some(value) = brakes.optional_number("meanDeceleration") {
+        return Gamma {
+            gamma_type: "CONST".to_owned(),
+            value,
+        };
+    }
+    if let Some(value) = brakes.optional_number("maxDeceleration") {
+        return Gamma {
+            gamma_type: "MAX".to_owned(),
+            value,
+        };
+    }
+    report.missing(location, "brakes@meanDeceleration");
+    Gamma::default()
+}
+
+fn read_rolling_resistance(
+    vehicle: Node,
+    location: &str,
+    report: &mut ConversionReport,
+) -> RollingResistance {
+    let mut factors = Attributes {
+        node: child(vehicle, "trainResistance")
+            .and_then(|resistance| child(resistance, "daviesFormulaFactors")),
+        element: "trainResistance/daviesFormulaFactors",
+        location,
+        report,
+    };
+    RollingResistance {
+        rolling_resistance_type: "davis".to_owned(),
+        A: factors.required_number("constantFactor") * KILO,
+        B: factors.required_number("speedDependentFactor") * KILO / KMH,
+        C: factors.required_number("squareSpeedDependentFactor") * KILO / (KMH * KMH),
+    }
+}
+
+fn read_effort_curves(
+    engines: &[Node],
+    location: &str,
+    report: &mut ConversionReport,
+) -> EffortCurves {
+    let mut effort_curves = EffortCurves::default();
+    for engine in engines {
+        for traction_mode in children(*engine, "tractionMode") {
+            let is_electric = attribute(traction_mode, "mode") == Some("electric");
+            let voltage = child(traction_mode, "electrificationSystem")
+                .and_then(|system| attribute(system, "voltage"));
+            let mode = match (attribute(traction_mode, "modeName"), voltage) {
+                (Some(mode), _) => mode.to_owned(),
+                (None, Some(voltage)) if is_electric => format!("{voltage}V"),
+                (None, _) if is_electric => {
+                    report.missing(location, "tractionMode/electrificationSystem@voltage");
+                    continue;
+                }
+                (None, _) => THERMAL_MODE.to_owned(),
+            };
+
+            let Some(value_table) = child(traction_mode, "tractiveEffort")
+                .and_then(|effort| child(effort, "valueTable"))
+            else {
+                report.missing(
+                    location,
+                    &format!("tractiveEffort/valueTable of traction mode '{mode}'"),
+                );
+                continue;
+            };
+            let mut speeds = vec![];
+            let mut max_efforts = vec![];
+            for line in children(value_table, "valueLine") {
+                let effort = child(line, "value").and_then(|value| attribute(value, "yValue"));
+                match (attribute(line, "xValue"), effort) {
+                    (Some(speed), Some(effort)) => match (speed.parse::<f64>(), effort.parse::<f64>()) {
+                        (Ok(speed), Ok(effort)) => {
+                            speeds.push(speed * KMH);
+                            max_efforts.push(effort * KILO);
+                        }
+                        _ => report.invalid(
+                            location,
+                            format!("invalid tractive effort point ({speed}, {effort}) of mode '{mode}'"),
+                        ),
+                    },
+                    _ => report.invalid(
+                        location,
+                        format!("incomplete tractive effort point in mode '{mode}'"),
+                    ),
+                }
+            }
+            let default_curve = match EffortCurve::new(speeds, max_efforts) {
+                Ok(curve) => curve,
+                Err(error) => {
+                    report.invalid(location, format!("mode '{mode}': {error}"));
+                    continue;
+                }
+            };
+
+            if attribute(traction_mode, "isPrimaryMode") == Some("true") {
+                effort_curves.default_mode.clone_from(&mode);
+            }
+            let duplicate = effort_curves.modes.insert(
+                mode.clone(),
+                ModeEffortCurves {
+                    curves: vec![],
+                    default_curve,
+                    is_electric,
+                },
+            );
+            if duplicate.is_some() {
+                report.invalid(location, format!("mode '{mode}' is defined twice"));
+            }
+        }
+    }
+
+    if effort_curves.modes.is_empty() {
+        report.missing(location, "engines/engine/tractionMode");
+    } else if effort_curves.default_mode.is_empty() {
+        match effort_curves.modes.keys().next() {
+            Some(mode) if effort_curves.modes.len() == 1 => {
+                effort_curves.default_mode.clone_from(mode)
+            }
+            _ => report.invalid(
+                location,
+                "no traction mode is the primary one, 'isPrimaryMode' is missing",
+            ),
+        }
+    }
+    effort_curves
+}
+
+fn read_energy_storage(
+    storage: Node,
+    location: &str,
+    report: &mut ConversionReport,
+) -> Option<EnergySource> {
+    let mut attributes = Attributes {
+        node: Some(storage),
+        element: "energyStorage",
+        location,
+        report,
+    };
+    let storage_type = attributes.required("type");
+    let energy_storage = EnergyStorage {
+        capacity: attributes.required_number("capacity") * KWH,
+        soc: attributes.required_number("socInitial"),
+        soc_min: attributes.required_number("socMin"),
+        soc_max: attributes.required_number("socMax"),
+        refill_law: None,
+    };
+    let efficiency = attributes.required_number("efficiency");
+    let constant_power = |power: f64| SpeedDependantPower {
+        speeds: vec![0.],
+        powers: vec![power * KILO],
+    };
+    let max_input_power = constant_power(attributes.required_number("maxChargingPower"));
+    let max_output_power = constant_power(attributes.required_number("maxDischargingPower"));
+    match storage_type.as_str() {
+        "battery" => Some(EnergySource::Battery {
+            max_input_power,
+            max_output_power,
+            energy_storage,
+            efficiency,
+        }),
+        "powerPack" => Some(EnergySource::PowerPack {
+            max_input_power,
+            max_output_power,
+            energy_storage,
+            efficiency,
+        }),
+        "" => None,
+        storage_type => {
+            report.invalid(
+                location,
+                format!("unknown energy storage type '{storage_type}'"),
+            );
+            None
+        }
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Writes rolling stocks as the vehicles of a railML document
+///
+/// The data railML can't hold (conditional effort curves, speed dependent powers and
+/// electrification energy sources) is listed in the report.
+pub fn write(rolling_stocks: &[RollingStock]) -> (String, ConversionReport) {
+    let mut report = ConversionReport::default();
+    let mut xml = String::new();
+    let _ = writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    let _ = writeln!(
+        xml,
+        r#"<railML xmlns="{RAILML_NAMESPACE}" xmlns:osrd="{OSRD_NAMESPACE}" version="3.2">"#
+    );
+    xml.push_str("  <rollingstock>\n    <vehicles>\n");
+    for (index, rolling_stock) in rolling_stocks.iter().enumerate() {
+        write_vehicle(
+            &mut xml,
+            &format!("vh_{}", index + 1),
+            rolling_stock,
+            &mut report,
+        );
+    }
+    xml.push_str("    </vehicles>\n  </rollingstock>\n</railML>\n");
+    (xml, report)
+}
+
+fn write_vehicle(
+    xml: &mut String,
+    id: &str,
+    rolling_stock: &RollingStock,
+    report: &mut ConversionReport,
+) {
+    let location = format!("vehicle '{id}'");
+    let _ = writeln!(
+        xml,
+        r#"      <vehicle id="{id}" speed="{}" length="{}" bruttoWeight="{}" rotatingMassFactor="{}">"#,
+        rolling_stock.max_speed / KMH,
+        rolling_stock.length,
+        rolling_stock.mass / TONNE,
+        rolling_stock.inertia_coefficient,
+    );
+    let _ = writeln!(
+        xml,
+        r#"        <name name="{}" language="en"/>"#,
+        escape(&rolling_stock.name)
+    );
+
+    let mut characteristics = vec![
+        ("startupTime", rolling_stock.startup_time.to_string()),
+        (
+            "startupAcceleration",
+            rolling_stock.startup_acceleration.to_string(),
+        ),
+        (
+            "comfortAcceleration",
+            rolling_stock.comfort_acceleration.to_string(),
+        ),
+    ];
+    if let Ok(serde_json::Value::String(loading_gauge)) =
+        serde_json::to_value(rolling_stock.loading_gauge)
+    {
+        characteristics.push(("loadingGauge", loading_gauge));
+    }
+    if let Some(base_power_class) = &rolling_stock.base_power_class {
+        characteristics.push(("basePowerClass", base_power_class.clone()));
+    }
+    if let Some(time) = rolling_stock.electrical_power_startup_time {
+        characteristics.push(("electricalPowerStartupTime", time.to_string()));
+    }
+    if let Some(time) = rolling_stock.raise_pantograph_time {
+        characteristics.push(("raisePantographTime", time.to_string()));
+    }
+    if !rolling_stock.supported_signaling_systems.0.is_empty() {
+        characteristics.push((
+            "supportedSignalingSystems",
+            rolling_stock.supported_signaling_systems.0.join(" "),
+        ));
+    }
+    if !rolling_stock.power_restrictions.is_empty() {
+        let mut restrictions: Vec<_> = rolling_stock
+            .power_restrictions
+            .iter()
+            .map(|(code, power_class)| format!("{code}={power_class}"))
+            .collect();
+        restrictions.sort();
+        characteristics.push(("powerRestrictions", restrictions.join(" ")));
+    }
+    xml.push_str("        <osrd:characteristics");
+    for (name, value) in characteristics {
+        let _ = write!(xml, r#" {name}="{}""#, escape(&value));
+    }
+    xml.push_str("/>\n");
+
+    let deceleration = match rolling_stock.gamma.gamma_type.as_str() {
+        "MAX" => "maxDeceleration",
+        _ => "meanDeceleration",
+    };
+    let _ = writeln!(
+        xml,
+        r#"        <brakes {deceleration}="{}"/>"#,
+        rolling_stock.gamma.value
+    );
+    let resistance = &rolling_stock.rolling_resistance;
+    let _ = writeln!(
+        xml,
+        r#"        <trainResistance>
+          <daviesFormulaFactors constantFactor="{}" speedDependentFactor="{}" squareSpeedDependentFactor="{}"/>
+        </trainResistance>"#,
+        resistance.A / KILO,
+        resistance.B * KMH / KILO,
+        resistance.C * KMH * KMH / KILO,
+    );
+
+    xml.push_str("        <engines>\n          <engine>\n");
+    let effort_curves = &rolling_stock.effort_curves;
+    for (mode, curves) in &effort_curves.modes {
+        if !curves.curves.is_empty() {
+            report.invalid(
+                &location,
+                format!(
+                    "the {} conditional effort curves of mode '{mode}' are left out",
+                    curves.curves.len()
+                ),
+            );
+        }
+        let _ = writeln!(
+            xml,
+            r#"            <tractionMode mode="{}" isPrimaryMode="{}" osrd:modeName="{}">"#,
+            if curves.is_electric {
+                "electric"
+            } else {
+                "diesel"
+            },
+            *mode == effort_curves.default_mode,
+            escape(mode),
+        );
+        if let Some(voltage) = mode.strip_suffix('V').filter(|_| curves.is_electric) {
+            let _ = writeln!(
+                xml,
+                r#"              <electrificationSystem voltage="{}"/>"#,
+                escape(voltage)
+            );
+        }
+        xml.push_str("              <tractiveEffort>\n");
+        xml.push_str(r#"                <valueTable xValueName="speed" xValueUnit="km/h" yValueName="tractiveEffort" yValueUnit="kN">"#);
+        xml.push('\n');
+        let curve = &curves.default_curve;
+        for (speed, effort) in curve.speeds().iter().zip(curve.max_efforts()) {
+            let _ = writeln!(
+                xml,
+                r#"                  <valueLine xValue="{}"><value yValue="{}"/></valueLine>"#,
+                speed / KMH,
+                effort / KILO
+            );
+        }
+        xml.push_str("                </valueTable>\n              </tractiveEffort>\n");
+        xml.push_str("            </tractionMode>\n");
+    }
+
+    for energy_source in &rolling_stock.energy_sources {
+        let (storage_type, max_input_power, max_output_power, storage, efficiency) =
+            match energy_source {
+                EnergySource::Electrification { .. } => {
+                    report.invalid(&location, "electrification energy sources are left out");
+                    continue;
+                }
+                EnergySource::PowerPack {
+                    max_input_power,
+                    max_output_power,
+                    energy_storage,
+                    efficiency,
+                } => (
+                    "powerPack",
+                    max_input_power,
+                    max_output_power,
+                    energy_storage,
+                    efficiency,
+                ),
+                EnergySource::Battery {
+                    max_input_power,
+                    max_output_power,
+                    energy_storage,
+                    efficiency,
+                } => (
+                    "battery",
+                    max_input_power,
+                    max_output_power,
+                    energy_storage,
+                    efficiency,
+                ),
+            };
+        if max_input_power.powers.len() > 1 || max_output_power.powers.len() > 1 {
+            report.invalid(
+                &location,
+                format!("the powers of the {storage_type} are written as their maximum"),
+            );
+        }
+        if storage.refill_law.is_some() {
+            report.invalid(
+                &location,
+                format!("the refill law of the {storage_type} is left out"),
+            );
+        }
+        let max =
+            |power: &SpeedDependantPower| power.powers.iter().copied().fold(0., f64::max) / KILO;
+        let _ = writeln!(
+            xml,
+            r#"            <energyStorage type="{storage_type}" capacity="{}" socInitial="{}" socMin="{}" socMax="{}" efficiency="{efficiency}" maxChargingPower="{}" maxDischargingPower="{}"/>"#,
+            storage.capacity / KWH,
+            storage.soc,
+            storage.soc_min,
+            storage.soc_max,
+            max(max_input_power),
+            max(max_output_power),
+        );
+    }
+    xml.push_str("          </engine>\n        </engines>\n      </vehicle>\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::tests::get_fast_rolling_stock_schema;
+
+    #[test]
+    fn railml_round_trip() {
+        let mut rolling_stock = get_fast_rolling_stock_schema("railml_rolling_stock");
+        rolling_stock.metadata = None;
+
+        let (railml, report) = write(&[rolling_stock.clone()]);
+        assert!(report.is_empty(), "{report:?}");
+        let (rolling_stocks, report) = read(&railml);
+        assert!(report.is_empty(), "{report:?}");
+        assert_eq!(rolling_stocks.len(), 1);
+        let read_rolling_stock = &rolling_stocks[0];
+
+        // Unit conversions round the floats
+        let close = |a: f64, b: f64| (a - b).abs() <= 1e-9 * a.abs().max(1.);
+        assert!(close(read_rolling_stock.max_speed, rolling_stock.max_speed));
+        assert!(close(read_rolling_stock.mass, rolling_stock.mass));
+        assert!(close(
+            read_rolling_stock.rolling_resistance.C,
+            rolling_stock.rolling_resistance.C
+        ));
+        assert_eq!(
+            read_rolling_stock.effort_curves.default_mode,
+            rolling_stock.effort_curves.default_mode
+        );
+        assert_eq!(
+            read_rolling_stock
+                .effort_curves
+                .modes
+                .keys()
+                .collect::<Vec<_>>(),
+            rolling_stock.effort_curves.modes.keys().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            read_rolling_stock.loading_gauge,
+            rolling_stock.loading_gauge
+        );
+        assert_eq!(
+            read_rolling_stock.power_restrictions,
+            rolling_stock.power_restrictions
+        );
+    }
+
+    #[test]
+    fn railml_missing_fields() {
+        let railml = r#"<railML version="3.2"><rollingstock><vehicles>
+            <vehicle id="vh_1" speed="160" length="fifty">
+              <name name="BB 22200"/>
+              <engines><engine>
+                <tractionMode mode="electric" isPrimaryMode="true">
+                  <tractiveEffort><valueTable>
+                    <valueLine xValue="0"><value yValue="300"/></valueLine>
+                    <valueLine xValue="160"><value yValue="100"/></valueLine>
+                  </valueTable></tractiveEffort>
+                </tractionMode>
+              </engine></engines>
+            </vehicle>
+        </vehicles></rollingstock></railML>"#;
+        let (rolling_stocks, report) = read(railml);
+        assert!(rolling_stocks.is_empty());
+        let messages: Vec<_> = report.issues.iter().map(ToString::to_string).collect();
+        assert!(messages.contains(
+            &"vehicle 'vh_1': 'vehicle@length' should be a number, found 'fifty'".to_owned()
+        ));
+        assert!(messages.contains(&"vehicle 'vh_1': 'vehicle@bruttoWeight' is missing".to_owned()));
+        assert!(messages.contains(
+            &"vehicle 'vh_1': 'tractionMode/electrificationSystem@voltage' is missing".to_owned()
+        ));
+    }
+}
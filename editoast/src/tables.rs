@@ -1,5 +1,17 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use postgis_diesel::sql_types::*;
+
+    consist (id) {
+        id -> Int8,
+        #[max_length = 255]
+        name -> Varchar,
+        units -> Jsonb,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use postgis_diesel::sql_types::*;
@@ -9,6 +21,7 @@ diesel::table! {
         #[max_length = 255]
         content_type -> Varchar,
         data -> Bytea,
+        user_id -> Nullable<Int8>,
     }
 }
 
@@ -21,6 +34,7 @@ diesel::table! {
         #[max_length = 128]
         name -> Varchar,
         data -> Jsonb,
+        version -> Int8,
     }
 }
 
@@ -45,6 +59,27 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use postgis_diesel::sql_types::*;
+
+    infra_edition_history (id) {
+        id -> Int8,
+        infra_id -> Int8,
+        #[max_length = 40]
+        previous_version -> Varchar,
+        #[max_length = 40]
+        version -> Varchar,
+        #[max_length = 255]
+        author -> Nullable<Varchar>,
+        created_at -> Timestamptz,
+        kind -> Int2,
+        target_id -> Nullable<Int8>,
+        operations -> Jsonb,
+        inverse_operations -> Jsonb,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use postgis_diesel::sql_types::*;
@@ -355,6 +390,45 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use postgis_diesel::sql_types::*;
+
+    job (id) {
+        id -> Int8,
+        kind -> Int2,
+        parameters -> Jsonb,
+        status -> Int2,
+        progress -> Float8,
+        logs -> Jsonb,
+        result -> Jsonb,
+        error -> Jsonb,
+        attempts -> Int4,
+        max_attempts -> Int4,
+        cancel_requested -> Bool,
+        user_id -> Nullable<Int8>,
+        created_at -> Timestamptz,
+        scheduled_at -> Timestamptz,
+        started_at -> Nullable<Timestamptz>,
+        finished_at -> Nullable<Timestamptz>,
+        heartbeat_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use postgis_diesel::sql_types::*;
+
+    osrd_user (id) {
+        id -> Int8,
+        #[max_length = 255]
+        identity -> Varchar,
+        uuid -> Uuid,
+        created_at -> Timestamptz,
+        is_admin -> Bool,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use postgis_diesel::sql_types::*;
@@ -394,6 +468,19 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use postgis_diesel::sql_types::*;
+
+    resource_grant (id) {
+        id -> Int8,
+        user_id -> Int8,
+        resource_type -> Int2,
+        resource_id -> Int8,
+        role -> Int2,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use postgis_diesel::sql_types::*;
@@ -441,6 +528,19 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use postgis_diesel::sql_types::*;
+
+    rolling_stock_revision (id) {
+        id -> Int8,
+        rolling_stock_id -> Int8,
+        version -> Int8,
+        created_at -> Timestamptz,
+        rolling_stock -> Jsonb,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use postgis_diesel::sql_types::*;
@@ -507,6 +607,7 @@ diesel::table! {
         ci -> Nullable<Int4>,
         ch -> Nullable<Text>,
         name -> Nullable<Text>,
+        geographic -> Nullable<Geometry>,
     }
 }
 
@@ -522,6 +623,21 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use postgis_diesel::sql_types::*;
+
+    search_rolling_stock (id) {
+        id -> Int8,
+        name -> Nullable<Text>,
+        traction_modes -> Nullable<Array<Nullable<Text>>>,
+        max_speed -> Nullable<Float8>,
+        #[max_length = 16]
+        loading_gauge -> Nullable<Varchar>,
+        locked -> Nullable<Bool>,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use postgis_diesel::sql_types::*;
@@ -551,6 +667,22 @@ diesel::table! {
         line_code -> Nullable<Int4>,
         sprite_signaling_system -> Nullable<Text>,
         sprite -> Nullable<Text>,
+        geographic -> Nullable<Geometry>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use postgis_diesel::sql_types::*;
+
+    search_speed_section (id) {
+        id -> Int8,
+        #[max_length = 255]
+        obj_id -> Nullable<Varchar>,
+        infra_id -> Nullable<Int4>,
+        speed_limit -> Nullable<Float8>,
+        line_name -> Nullable<Text>,
+        line_code -> Nullable<Int4>,
     }
 }
 
@@ -567,6 +699,22 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use postgis_diesel::sql_types::*;
+
+    search_switch (id) {
+        id -> Int8,
+        #[max_length = 255]
+        obj_id -> Nullable<Varchar>,
+        infra_id -> Nullable<Int4>,
+        #[max_length = 255]
+        switch_type -> Nullable<Varchar>,
+        line_name -> Nullable<Text>,
+        line_code -> Nullable<Int4>,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use postgis_diesel::sql_types::*;
@@ -580,6 +728,19 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use postgis_diesel::sql_types::*;
+
+    search_train_schedule_v2 (id) {
+        id -> Int8,
+        timetable_id -> Nullable<Int8>,
+        train_name -> Nullable<Text>,
+        labels -> Nullable<Text>,
+        rolling_stock_name -> Nullable<Text>,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use postgis_diesel::sql_types::*;
@@ -673,6 +834,22 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use postgis_diesel::sql_types::*;
+
+    train_schedule_cadence (id) {
+        id -> Int8,
+        timetable_id -> Int8,
+        template -> Jsonb,
+        period -> Int8,
+        window_start -> Timestamptz,
+        window_end -> Timestamptz,
+        #[max_length = 128]
+        name_pattern -> Varchar,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use postgis_diesel::sql_types::*;
@@ -696,6 +873,9 @@ diesel::table! {
         speed_limit_tag -> Nullable<Varchar>,
         power_restrictions -> Jsonb,
         options -> Jsonb,
+        cadence_id -> Nullable<Int8>,
+        rolling_stock_revision_id -> Nullable<Int8>,
+        cadence_departure -> Nullable<Timestamptz>,
     }
 }
 
@@ -727,6 +907,7 @@ diesel::table! {
     }
 }
 
+diesel::joinable!(infra_edition_history -> infra (infra_id));
 diesel::joinable!(infra_layer_buffer_stop -> infra (infra_id));
 diesel::joinable!(infra_layer_detector -> infra (infra_id));
 diesel::joinable!(infra_layer_electrification -> infra (infra_id));
@@ -750,10 +931,14 @@ diesel::joinable!(infra_object_signal -> infra (infra_id));
 diesel::joinable!(infra_object_speed_section -> infra (infra_id));
 diesel::joinable!(infra_object_switch -> infra (infra_id));
 diesel::joinable!(infra_object_track_section -> infra (infra_id));
+diesel::joinable!(document -> osrd_user (user_id));
+diesel::joinable!(job -> osrd_user (user_id));
 diesel::joinable!(pathfinding -> infra (infra_id));
 diesel::joinable!(project -> document (image_id));
+diesel::joinable!(resource_grant -> osrd_user (user_id));
 diesel::joinable!(rolling_stock_livery -> document (compound_image_id));
 diesel::joinable!(rolling_stock_livery -> rolling_stock (rolling_stock_id));
+diesel::joinable!(rolling_stock_revision -> rolling_stock (rolling_stock_id));
 diesel::joinable!(rolling_stock_separate_image -> document (image_id));
 diesel::joinable!(rolling_stock_separate_image -> rolling_stock_livery (livery_id));
 diesel::joinable!(scenario -> electrical_profile_set (electrical_profile_set_id));
@@ -765,22 +950,31 @@ diesel::joinable!(scenario_v2 -> study (study_id));
 diesel::joinable!(scenario_v2 -> timetable_v2 (timetable_id));
 diesel::joinable!(search_operational_point -> infra_object_operational_point (id));
 diesel::joinable!(search_project -> project (id));
+diesel::joinable!(search_rolling_stock -> rolling_stock (id));
 diesel::joinable!(search_scenario -> scenario (id));
 diesel::joinable!(search_signal -> infra_object_signal (id));
+diesel::joinable!(search_speed_section -> infra_object_speed_section (id));
 diesel::joinable!(search_study -> study (id));
+diesel::joinable!(search_switch -> infra_object_switch (id));
+diesel::joinable!(search_train_schedule_v2 -> train_schedule_v2 (id));
 diesel::joinable!(simulation_output -> train_schedule (train_schedule_id));
 diesel::joinable!(study -> project (project_id));
 diesel::joinable!(timetable_v2 -> electrical_profile_set (electrical_profile_set_id));
 diesel::joinable!(train_schedule -> pathfinding (path_id));
 diesel::joinable!(train_schedule -> rolling_stock (rolling_stock_id));
 diesel::joinable!(train_schedule -> timetable (timetable_id));
+diesel::joinable!(train_schedule_cadence -> timetable_v2 (timetable_id));
+diesel::joinable!(train_schedule_v2 -> rolling_stock_revision (rolling_stock_revision_id));
 diesel::joinable!(train_schedule_v2 -> timetable_v2 (timetable_id));
+diesel::joinable!(train_schedule_v2 -> train_schedule_cadence (cadence_id));
 diesel::joinable!(work_schedule -> work_schedule_group (work_schedule_group_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    consist,
     document,
     electrical_profile_set,
     infra,
+    infra_edition_history,
     infra_layer_buffer_stop,
     infra_layer_detector,
     infra_layer_electrification,
@@ -804,24 +998,33 @@ diesel::allow_tables_to_appear_in_same_query!(
     infra_object_speed_section,
     infra_object_switch,
     infra_object_track_section,
+    job,
+    osrd_user,
     pathfinding,
     project,
+    resource_grant,
     rolling_stock,
     rolling_stock_livery,
+    rolling_stock_revision,
     rolling_stock_separate_image,
     scenario,
     scenario_v2,
     search_operational_point,
     search_project,
+    search_rolling_stock,
     search_scenario,
     search_signal,
+    search_speed_section,
     search_study,
+    search_switch,
     search_track,
+    search_train_schedule_v2,
     simulation_output,
     study,
     timetable,
     timetable_v2,
     train_schedule,
+    train_schedule_cadence,
     train_schedule_v2,
     work_schedule,
     work_schedule_group,
@@ -0,0 +1,620 @@
+//! Identifies the users forwarded by the gateway and checks their roles on resources
+//!
+//! A role granted on a project also applies to its studies and scenarios, and a role
+//! granted on a study applies to its scenarios. Resources on which no role was ever
+//! granted, such as the ones created before users were introduced, can be viewed and
+//! edited by everyone, but only admins can grant their first roles or delete them.
+//! Creators are made owners of the resources they create.
+
+use std::future::ready;
+use std::future::Ready;
+
+use actix_web::delete;
+use actix_web::dev::Payload;
+use actix_web::get;
+use actix_web::put;
+use actix_web::web::Data;
+use actix_web::web::Json;
+use actix_web::web::Path;
+use actix_web::FromRequest;
+use actix_web::HttpRequest;
+use actix_web::HttpResponse;
+use diesel::ExpressionMethods;
+use diesel::OptionalExtension;
+use diesel::QueryDsl;
+use diesel_async::RunQueryDsl;
+use editoast_derive::EditoastError;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+use utoipa::IntoParams;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::error::InternalError;
+use crate::error::Result;
+use crate::modelsv2::resource_grant::Resource;
+use crate::modelsv2::resource_grant::ResourceGrant;
+use crate::modelsv2::resource_grant::ResourceType;
+use crate::modelsv2::resource_grant::Role;
+use crate::modelsv2::resource_grant::UserGrant;
+use crate::modelsv2::DbConnection;
+use crate::modelsv2::DbConnectionPool;
+use crate::modelsv2::User;
+
+crate::routes! {
+    "/users/me" => {
+        current_user,
+    },
+    "/grants/{resource_type}/{resource_id}" => {
+        list_grants,
+        put_grant,
+        "/{user_id}" => {
+            delete_grant,
+        },
+    },
+}
+
+editoast_common::schemas! {
+    GrantForm,
+}
+
+/// The header set by the gateway with the identity of the authenticated user
+pub const REMOTE_USER_HEADER: &str = "x-remote-user";
+
+#[derive(Debug, Error, EditoastError)]
+#[editoast_error(base_id = "authz")]
+pub enum AuthorizationError {
+    #[error("The request was not authenticated by the gateway")]
+    #[editoast_error(status = 401)]
+    Unauthenticated,
+    #[error("The role '{required_role}' is required on {resource_type} '{resource_id}'")]
+    #[editoast_error(status = 403)]
+    Forbidden {
+        resource_type: ResourceType,
+        resource_id: i64,
+        required_role: Role,
+    },
+    #[error("User '{user_id}' has no role on {resource_type} '{resource_id}'")]
+    #[editoast_error(status = 404)]
+    GrantNotFound {
+        user_id: i64,
+        resource_type: ResourceType,
+        resource_id: i64,
+    },
+    #[error("{resource_type} '{resource_id}' must keep at least one owner")]
+    #[editoast_error(status = 400)]
+    LastOwner {
+        resource_type: ResourceType,
+        resource_id: i64,
+    },
+}
+
+/// How requests which were not authenticated by the gateway are handled
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AuthorizationConfig {
+    /// Accept requests without identity, and skip all role checks for them
+    pub allow_anonymous: bool,
+}
+
+/// The identity of the user sending a request
+///
+/// Extracting it fails if the request has no identity and anonymous requests are
+/// not allowed by the [AuthorizationConfig] of the app.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Authentication {
+    User(String),
+    Anonymous,
+}
+
+impl FromRequest for Authentication {
+    type Error = InternalError;
+    type Future = Ready<Result<Self>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let identity = req
+            .headers()
+            .get(REMOTE_USER_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .filter(|identity| !identity.is_empty());
+        let allow_anonymous = req
+            .app_data::<Data<AuthorizationConfig>>()
+            .map(|config| config.allow_anonymous)
+            .unwrap_or_default();
+        ready(match identity {
+            Some(identity) => Ok(Self::User(identity.to_owned())),
+            None if allow_anonymous => Ok(Self::Anonymous),
+            None => Err(AuthorizationError::Unauthenticated.into()),
+        })
+    }
+}
+
+impl Authentication {
+    /// Resolves the user record of the identity
+    pub async fn authorizer(&self, conn: &mut DbConnection) -> Result<Authorizer> {
+        let user = match self {
+            Self::User(identity) => Some(User::resolve(conn, identity).await?),
+            Self::Anonymous => None,
+        };
+        Ok(Authorizer::new(user))
+    }
+}
+
+/// Checks the roles of the user of a request
+#[derive(Debug, Clone)]
+pub struct Authorizer {
+    /// `None` for anonymous requests, which are allowed everything
+    user: Option<User>,
+}
+
+impl Authorizer {
+    /// The authorizer of a known user, such as the submitter of a background job
+    pub fn new(user: Option<User>) -> Self {
+        Self { user }
+    }
+
+    pub fn user(&self) -> Option<&User> {
+        self.user.as_ref()
+    }
+
+    /// Whether the user is allowed everything, which is also the case of anonymous requests
+    pub fn is_admin(&self) -> bool {
+        match &self.user {
+            Some(user) => user.is_admin,
+            None => true,
+        }
+    }
+
+    /// The identifier of the user, used as the owner of new infras
+    pub fn owner_uuid(&self) -> Uuid {
+        self.user.as_ref().map(|user| user.uuid).unwrap_or_default()
+    }
+
+    /// Fails with [AuthorizationError::Forbidden] unless the user has at least the
+    /// `required_role` on the resource or one of its parents
+    ///
+    /// Resources on which no role was granted only require to be an admin to own them.
+    pub async fn check(
+        &self,
+        conn: &mut DbConnection,
+        resource_type: ResourceType,
+        resource_id: i64,
+        required_role: Role,
+    ) -> Result<()> {
+        let Some(user) = self.user.as_ref().filter(|user| !user.is_admin) else {
+            return Ok(());
+        };
+        let resources = with_parents(conn, Resource::new(resource_type, resource_id)).await?;
+        let role = ResourceGrant::list_for_user(conn, user.id, &resources)
+            .await?
+            .into_iter()
+            .map(|grant| grant.role)
+            .max();
+        if role.is_some_and(|role| role >= required_role)
+            || (required_role < Role::Owner
+                && !ResourceGrant::any_granted(conn, &resources).await?)
+        {
+            return Ok(());
+        }
+        Err(AuthorizationError::Forbidden {
+            resource_type,
+            resource_id,
+            required_role,
+        }
+        .into())
+    }
+
+    /// Checks the role of the user on the scenarios using a timetable
+    ///
+    /// The scenarios of the v2 timetables are protected by the roles on their study.
+    /// Timetables which are not used by any scenario are left open.
+    pub async fn check_timetable(
+        &self,
+        conn: &mut DbConnection,
+        timetable_id: i64,
+        required_role: Role,
+    ) -> Result<()> {
+        use crate::tables::scenario_v2;
+
+        if self.is_admin() {
+            return Ok(());
+        }
+        let study_ids = scenario_v2::table
+            .filter(scenario_v2::timetable_id.eq(timetable_id))
+            .select(scenario_v2::study_id)
+            .load::<i64>(conn)
+            .await?;
+        for study_id in study_ids {
+            self.check(conn, ResourceType::Study, study_id, required_role)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Checks the role of the user on the timetables of some train schedules
+    pub async fn check_train_schedules(
+        &self,
+        conn: &mut DbConnection,
+        train_ids: &[i64],
+        required_role: Role,
+    ) -> Result<()> {
+        use crate::tables::train_schedule_v2;
+
+        if self.is_admin() {
+            return Ok(());
+        }
+        let timetable_ids = train_schedule_v2::table
+            .filter(train_schedule_v2::id.eq_any(train_ids))
+            .select(train_schedule_v2::timetable_id)
+            .distinct()
+            .load::<i64>(conn)
+            .await?;
+        for timetable_id in timetable_ids {
+            self.check_timetable(conn, timetable_id, required_role)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// The resources of a type which must be left out of the lists shown to the user
+    pub async fn hidden(
+        &self,
+        conn: &mut DbConnection,
+        resource_type: ResourceType,
+    ) -> Result<Vec<i64>> {
+        match self.user.as_ref().filter(|user| !user.is_admin) {
+            Some(user) => ResourceGrant::list_hidden(conn, user.id, resource_type).await,
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Makes the user the owner of a resource it just created
+    pub async fn grant_owner(
+        &self,
+        conn: &mut DbConnection,
+        resource_type: ResourceType,
+        resource_id: i64,
+    ) -> Result<()> {
+        if let Some(user) = &self.user {
+            let resource = Resource::new(resource_type, resource_id);
+            ResourceGrant::grant(conn, user.id, resource, Role::Owner).await?;
+        }
+        Ok(())
+    }
+}
+
+/// The resource followed by the resources whose roles also apply to it
+async fn with_parents(conn: &mut DbConnection, resource: Resource) -> Result<Vec<Resource>> {
+    use crate::tables::scenario;
+    use crate::tables::study;
+
+    let mut resources = vec![resource];
+    if resource.resource_type == ResourceType::Scenario {
+        let study_id = scenario::table
+            .find(resource.id)
+            .select(scenario::study_id)
+            .first::<i64>(conn)
+            .await
+            .optional()?;
+        resources.extend(study_id.map(|id| Resource::new(ResourceType::Study, id)));
+    }
+    let last = resources.last().copied().unwrap();
+    if last.resource_type == ResourceType::Study {
+        let project_id = study::table
+            .find(last.id)
+            .select(study::project_id)
+            .first::<i64>(conn)
+            .await
+            .optional()?;
+        resources.extend(project_id.map(|id| Resource::new(ResourceType::Project, id)));
+    }
+    Ok(resources)
+}
+
+/// Returns the user sending the request
+#[utoipa::path(
+    tag = "authz",
+    responses(
+        (status = 200, body = User, description = "The current user"),
+        (status = 401, description = "The request was not authenticated"),
+    )
+)]
+#[get("")]
+async fn current_user(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+) -> Result<Json<User>> {
+    let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    let user = authorizer
+        .user()
+        .cloned()
+        .ok_or(AuthorizationError::Unauthenticated)?;
+    Ok(Json(user))
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+struct ResourceParams {
+    resource_type: ResourceType,
+    resource_id: i64,
+}
+
+impl From<ResourceParams> for Resource {
+    fn from(params: ResourceParams) -> Self {
+        Resource::new(params.resource_type, params.resource_id)
+    }
+}
+
+/// Lists the roles granted on a resource
+#[utoipa::path(
+    tag = "authz",
+    params(ResourceParams),
+    responses(
+        (status = 200, body = Vec<UserGrant>, description = "The roles granted on the resource"),
+        (status = 403, description = "The user is not a viewer of the resource"),
+    )
+)]
+#[get("")]
+async fn list_grants(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    params: Path<ResourceParams>,
+) -> Result<Json<Vec<UserGrant>>> {
+    let ResourceParams {
+        resource_type,
+        resource_id,
+    } = params.into_inner();
+    let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check(conn, resource_type, resource_id, Role::Viewer)
+        .await?;
+    let resource = Resource::new(resource_type, resource_id);
+    Ok(Json(
+        ResourceGrant::list_for_resource(conn, resource).await?,
+    ))
+}
+
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
+struct GrantForm {
+    /// The identity of the user, as forwarded by the gateway
+    identity: String,
+    role: Role,
+}
+
+/// Grants a role on a resource to a user, replacing its previous role
+#[utoipa::path(
+    tag = "authz",
+    params(ResourceParams),
+    request_body = GrantForm,
+    responses(
+        (status = 200, body = UserGrant, description = "The granted role"),
+        (status = 400, description = "The last owner of the resource cannot be demoted"),
+        (status = 403, description = "The user is not an owner of the resource"),
+    )
+)]
+#[put("")]
+async fn put_grant(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    params: Path<ResourceParams>,
+    Json(GrantForm { identity, role }): Json<GrantForm>,
+) -> Result<Json<UserGrant>> {
+    let resource: Resource = params.into_inner().into();
+    let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check(conn, resource.resource_type, resource.id, Role::Owner)
+        .await?;
+    let user = User::resolve(conn, &identity).await?;
+    if role != Role::Owner {
+        check_other_owners(conn, resource, user.id).await?;
+    }
+    ResourceGrant::grant(conn, user.id, resource, role).await?;
+    Ok(Json(UserGrant {
+        user_id: user.id,
+        identity: user.identity,
+        role,
+    }))
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, IntoParams)]
+#[into_params(parameter_in = Path)]
+struct UserGrantParams {
+    resource_type: ResourceType,
+    resource_id: i64,
+    user_id: i64,
+}
+
+/// Revokes the role of a user on a resource
+#[utoipa::path(
+    tag = "authz",
+    params(UserGrantParams),
+    responses(
+        (status = 204, description = "The role was revoked"),
+        (status = 400, description = "The last owner of the resource cannot be removed"),
+        (status = 403, description = "The user is not an owner of the resource"),
+        (status = 404, description = "The user has no role on the resource"),
+    )
+)]
+#[delete("")]
+async fn delete_grant(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    params: Path<UserGrantParams>,
+) -> Result<HttpResponse> {
+    let UserGrantParams {
+        resource_type,
+        resource_id,
+        user_id,
+    } = params.into_inner();
+    let resource = Resource::new(resource_type, resource_id);
+    let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check(conn, resource_type, resource_id, Role::Owner)
+        .await?;
+    check_other_owners(conn, resource, user_id).await?;
+    if !ResourceGrant::revoke(conn, user_id, resource).await? {
+        return Err(AuthorizationError::GrantNotFound {
+            user_id,
+            resource_type,
+            resource_id,
+        }
+        .into());
+    }
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Fails if the user is the only owner of a resource
+async fn check_other_owners(
+    conn: &mut DbConnection,
+    resource: Resource,
+    user_id: i64,
+) -> Result<()> {
+    let grants = ResourceGrant::list_for_resource(conn, resource).await?;
+    let is_owner = |grant: &UserGrant| grant.role == Role::Owner;
+    let user_is_owner = grants
+        .iter()
+        .any(|grant| grant.user_id == user_id && is_owner(grant));
+    let owners = grants.iter().filter(|grant| is_owner(grant)).count();
+    if user_is_owner && owners == 1 {
+        return Err(AuthorizationError::LastOwner {
+            resource_type: resource.resource_type,
+            resource_id: resource.id,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_http::StatusCode;
+    use actix_web::test as actix_test;
+    use actix_web::test::call_service;
+    use actix_web::test::read_body_json;
+    use actix_web::test::TestRequest;
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::*;
+    use crate::fixtures::tests::db_pool;
+    use crate::fixtures::tests::project;
+    use crate::fixtures::tests::TestFixture;
+    use crate::modelsv2::prelude::*;
+    use crate::modelsv2::Project;
+    use crate::views::tests::create_test_service;
+
+    async fn authenticate(req: TestRequest) -> Result<Authentication> {
+        Authentication::extract(&req.to_http_request()).await
+    }
+
+    #[actix_test]
+    async fn authenticate_forwarded_identity() {
+        let req = TestRequest::default().insert_header((REMOTE_USER_HEADER, "provider/jdoe"));
+        assert_eq!(
+            authenticate(req).await.unwrap(),
+            Authentication::User("provider/jdoe".to_owned())
+        );
+    }
+
+    #[actix_test]
+    async fn reject_anonymous_requests() {
+        let error = authenticate(TestRequest::default()).await.unwrap_err();
+        assert_eq!(error.get_type(), "editoast:authz:Unauthenticated");
+        assert_eq!(error.get_status(), 401);
+    }
+
+    #[actix_test]
+    async fn allow_anonymous_requests() {
+        let config = AuthorizationConfig {
+            allow_anonymous: true,
+        };
+        let req = TestRequest::default().app_data(Data::new(config));
+        assert_eq!(authenticate(req).await.unwrap(), Authentication::Anonymous);
+    }
+
+    #[rstest]
+    async fn project_roles(
+        db_pool: std::sync::Arc<DbConnectionPool>,
+        #[future] project: TestFixture<Project>,
+    ) {
+        let app = create_test_service().await;
+        let project = project.await;
+        let owner = format!("test/{}", Uuid::new_v4());
+        let viewer = format!("test/{}", Uuid::new_v4());
+        let project_uri = format!("/projects/{}", project.id());
+        let grants_uri = format!("/grants/project/{}", project.id());
+
+        // Nobody has a role on the project yet, so only an admin can grant the first one
+        let claim_project = |identity: &str| {
+            TestRequest::put()
+                .uri(&grants_uri)
+                .insert_header((REMOTE_USER_HEADER, identity))
+                .set_json(json!({ "identity": owner, "role": "owner" }))
+                .to_request()
+        };
+        let response = call_service(&app, claim_project(&owner)).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let admin = format!("test/{}", Uuid::new_v4());
+        let conn = &mut db_pool.get().await.unwrap();
+        let mut admin_user = User::resolve(conn, &admin).await.unwrap();
+        admin_user.patch().is_admin(true).apply(conn).await.unwrap();
+        let response = call_service(&app, claim_project(&admin)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let get_project = |identity: &str| {
+            TestRequest::get()
+                .uri(&project_uri)
+                .insert_header((REMOTE_USER_HEADER, identity))
+                .to_request()
+        };
+        let response = call_service(&app, get_project(&viewer)).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let list_projects = |identity: &str| {
+            TestRequest::get()
+                .uri("/projects?page_size=100")
+                .insert_header((REMOTE_USER_HEADER, identity))
+                .to_request()
+        };
+        let projects: serde_json::Value =
+            read_body_json(call_service(&app, list_projects(&viewer)).await).await;
+        assert!(projects["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|listed| listed["id"] != project.id()));
+
+        let req = TestRequest::put()
+            .uri(&grants_uri)
+            .insert_header((REMOTE_USER_HEADER, owner.as_str()))
+            .set_json(json!({ "identity": viewer, "role": "viewer" }))
+            .to_request();
+        let grant: UserGrant = read_body_json(call_service(&app, req).await).await;
+        assert_eq!(grant.role, Role::Viewer);
+        let response = call_service(&app, get_project(&viewer)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let req = TestRequest::delete()
+            .uri(&project_uri)
+            .insert_header((REMOTE_USER_HEADER, viewer.as_str()))
+            .to_request();
+        assert_eq!(
+            call_service(&app, req).await.status(),
+            StatusCode::FORBIDDEN
+        );
+
+        // The only owner cannot leave the project
+        let owner_id = User::resolve(conn, &owner).await.unwrap().id;
+        let req = TestRequest::delete()
+            .uri(&format!("{grants_uri}/{owner_id}"))
+            .insert_header((REMOTE_USER_HEADER, owner.as_str()))
+            .to_request();
+        assert_eq!(
+            call_service(&app, req).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+}
@@ -0,0 +1,368 @@
+use std::ops::DerefMut as _;
+
+use actix_web::delete;
+use actix_web::get;
+use actix_web::post;
+use actix_web::put;
+use actix_web::web::Data;
+use actix_web::web::Json;
+use actix_web::web::Path;
+use actix_web::web::Query;
+use actix_web::HttpResponse;
+use editoast_schemas::rolling_stock::RollingStock;
+use serde::Deserialize;
+use serde::Serialize;
+use utoipa::IntoParams;
+use utoipa::ToSchema;
+
+use crate::error::InternalError;
+use crate::error::Result;
+use crate::modelsv2::consist::derive_consist_rolling_stock;
+use crate::modelsv2::consist::Consist;
+use crate::modelsv2::consist::ConsistError;
+use crate::modelsv2::consist::ConsistUnit;
+use crate::modelsv2::prelude::*;
+use crate::modelsv2::DbConnection;
+use crate::modelsv2::DbConnectionPool;
+use crate::modelsv2::RollingStockModel;
+use crate::views::pagination::PaginatedList as _;
+use crate::views::pagination::PaginationQueryParam;
+use crate::views::pagination::PaginationStats;
+
+crate::routes! {
+    "/consist" => {
+        create,
+        list,
+        "/{consist_id}" => {
+            get,
+            update,
+            delete,
+            "/rolling_stock" => {
+                get_rolling_stock,
+            },
+        },
+    }
+}
+
+editoast_common::schemas! {
+    ConsistForm,
+}
+
+fn map_diesel_error(e: InternalError, name: impl AsRef<str>) -> InternalError {
+    if e.message
+        .contains(r#"duplicate key value violates unique constraint "consist_name_key""#)
+    {
+        ConsistError::NameAlreadyUsed {
+            name: name.as_ref().to_string(),
+        }
+        .into()
+    } else {
+        e
+    }
+}
+
+/// Creation and update form of a consist
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+struct ConsistForm {
+    /// The name by which train schedules refer to the consist, which can't be the name of a rolling stock
+    name: String,
+    /// The coupled rolling stocks, the first one leading the train
+    units: Vec<ConsistUnit>,
+}
+
+impl ConsistForm {
+    /// Checks the name is free and the units can be coupled
+    async fn validate(&self, conn: &mut DbConnection) -> Result<()> {
+        if RollingStockModel::exists(conn, self.name.clone()).await? {
+            return Err(ConsistError::NameAlreadyUsed {
+                name: self.name.clone(),
+            }
+            .into());
+        }
+        derive_consist_rolling_stock(conn, &self.name, &self.units).await?;
+        Ok(())
+    }
+}
+
+/// Create a consist of existing rolling stocks
+#[utoipa::path(
+    tag = "consist",
+    request_body = ConsistForm,
+    responses(
+        (status = 200, body = Consist, description = "The created consist"),
+    )
+)]
+#[post("")]
+async fn create(db_pool: Data<DbConnectionPool>, data: Json<ConsistForm>) -> Result<Json<Consist>> {
+    let conn = &mut db_pool.get().await?;
+    let form = data.into_inner();
+    form.validate(conn).await?;
+    let consist = Consist::changeset()
+        .name(form.name.clone())
+        .units(form.units)
+        .create(conn)
+        .await
+        .map_err(|e| map_diesel_error(e, form.name))?;
+    Ok(Json(consist))
+}
+
+#[derive(Serialize, ToSchema)]
+struct ConsistListResponse {
+    results: Vec<Consist>,
+    #[serde(flatten)]
+    stats: PaginationStats,
+}
+
+/// Returns a paginated list of consists
+#[utoipa::path(
+    tag = "consist",
+    params(PaginationQueryParam),
+    responses(
+        (status = 200, body = inline(ConsistListResponse), description = "The list of consists"),
+    )
+)]
+#[get("")]
+async fn list(
+    db_pool: Data<DbConnectionPool>,
+    Query(pagination_params): Query<PaginationQueryParam>,
+) -> Result<Json<ConsistListResponse>> {
+    let settings = pagination_params
+        .validate(1000)?
+        .warn_page_size(100)
+        .into_selection_settings()
+        .order_by(|| Consist::NAME.asc());
+    let (results, stats) =
+        Consist::list_paginated(db_pool.get().await?.deref_mut(), settings).await?;
+    Ok(Json(ConsistListResponse { results, stats }))
+}
+
+#[derive(IntoParams, Deserialize)]
+struct ConsistIdParam {
+    /// The id of a consist
+    consist_id: i64,
+}
+
+/// Retrieve a consist
+#[utoipa::path(
+    tag = "consist",
+    params(ConsistIdParam),
+    responses(
+        (status = 200, body = Consist, description = "The requested consist"),
+        (status = 404, body = InternalError, description = "The requested consist was not found"),
+    )
+)]
+#[get("")]
+async fn get(db_pool: Data<DbConnectionPool>, path: Path<ConsistIdParam>) -> Result<Json<Consist>> {
+    let consist_id = path.consist_id;
+    let conn = &mut db_pool.get().await?;
+    let consist =
+        Consist::retrieve_or_fail(conn, consist_id, || ConsistError::NotFound { consist_id })
+            .await?;
+    Ok(Json(consist))
+}
+
+/// Update the name and the units of a consist
+#[utoipa::path(
+    tag = "consist",
+    params(ConsistIdParam),
+    request_body = ConsistForm,
+    responses(
+        (status = 200, body = Consist, description = "The updated consist"),
+        (status = 404, body = InternalError, description = "The requested consist was not found"),
+    )
+)]
+#[put("")]
+async fn update(
+    db_pool: Data<DbConnectionPool>,
+    path: Path<ConsistIdParam>,
+    data: Json<ConsistForm>,
+) -> Result<Json<Consist>> {
+    let consist_id = path.consist_id;
+    let conn = &mut db_pool.get().await?;
+    let form = data.into_inner();
+    let mut consist =
+        Consist::retrieve_or_fail(conn, consist_id, || ConsistError::NotFound { consist_id })
+            .await?;
+    form.validate(conn).await?;
+    consist
+        .patch()
+        .name(form.name.clone())
+        .units(form.units)
+        .apply(conn)
+        .await
+        .map_err(|e| map_diesel_error(e, form.name))?;
+    Ok(Json(consist))
+}
+
+/// Delete a consist, leaving its rolling stocks untouched
+#[utoipa::path(
+    tag = "consist",
+    params(ConsistIdParam),
+    responses(
+        (status = 204, description = "The consist was deleted successfully"),
+        (status = 404, body = InternalError, description = "The requested consist was not found"),
+    )
+)]
+#[delete("")]
+async fn delete(
+    db_pool: Data<DbConnectionPool>,
+    path: Path<ConsistIdParam>,
+) -> Result<HttpResponse> {
+    let consist_id = path.consist_id;
+    let conn = &mut db_pool.get().await?;
+    Consist::delete_static_or_fail(conn, consist_id, || ConsistError::NotFound { consist_id })
+        .await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Derive the physics of a consist from its units
+///
+/// The consist behaves as this rolling stock when a train schedule or an STDCM request refers to it.
+#[utoipa::path(
+    tag = "consist",
+    params(ConsistIdParam),
+    responses(
+        (status = 200, body = RollingStock, description = "The rolling stock the consist behaves as"),
+        (status = 404, body = InternalError, description = "The requested consist or one of its rolling stocks was not found"),
+    )
+)]
+#[get("")]
+async fn get_rolling_stock(
+    db_pool: Data<DbConnectionPool>,
+    path: Path<ConsistIdParam>,
+) -> Result<Json<RollingStock>> {
+    let consist_id = path.consist_id;
+    let conn = &mut db_pool.get().await?;
+    let consist =
+        Consist::retrieve_or_fail(conn, consist_id, || ConsistError::NotFound { consist_id })
+            .await?;
+    let rolling_stock = consist.rolling_stock(conn).await?;
+    Ok(Json(rolling_stock.into()))
+}
+
+#[cfg(test)]
+pub mod test {
+    use actix_web::http::StatusCode;
+    use actix_web::test::call_service;
+    use actix_web::test::read_body_json;
+    use actix_web::test::TestRequest;
+    use rstest::rstest;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::assert_response_error_type_match;
+    use crate::fixtures::tests::db_pool;
+    use crate::fixtures::tests::named_fast_rolling_stock;
+    use crate::fixtures::tests::TestFixture;
+    use crate::views::tests::create_test_service;
+
+    #[rstest]
+    async fn consist_create_and_derive(db_pool: Arc<DbConnectionPool>) {
+        // GIVEN
+        let app = create_test_service().await;
+        let unit =
+            named_fast_rolling_stock("consist_create_and_derive_unit", db_pool.clone()).await;
+        let req = TestRequest::post()
+            .uri("/consist")
+            .set_json(json!({
+                "name": "consist_create_and_derive",
+                "units": [{ "rolling_stock_id": unit.id(), "count": 3 }]
+            }))
+            .to_request();
+
+        // WHEN
+        let response = call_service(&app, req).await;
+
+        // THEN
+        assert_eq!(response.status(), StatusCode::OK);
+        let consist: Consist = read_body_json(response).await;
+        let consist = TestFixture::new(consist, db_pool.clone());
+        let req = TestRequest::get()
+            .uri(format!("/consist/{}/rolling_stock", consist.id()).as_str())
+            .to_request();
+        let rolling_stock: RollingStock = read_body_json(call_service(&app, req).await).await;
+        assert_eq!(rolling_stock.name, "consist_create_and_derive");
+        assert_eq!(rolling_stock.mass, 3. * unit.model.mass);
+
+        let mut conn = db_pool.get().await.unwrap();
+        let derived =
+            RollingStockModel::retrieve_or_derive(&mut conn, "consist_create_and_derive".into())
+                .await
+                .unwrap()
+                .expect("the consist should be found by name");
+        assert_eq!(derived.length, 3. * unit.model.length);
+
+        let delete_unit = |force: bool| {
+            TestRequest::delete()
+                .uri(format!("/rolling_stock/{}?force={force}", unit.id()).as_str())
+                .to_request()
+        };
+        let response = call_service(&app, delete_unit(false)).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let error: serde_json::Value = read_body_json(response).await;
+        assert_eq!(
+            error["context"]["consists"],
+            json!(["consist_create_and_derive"])
+        );
+
+        // Once a unit is gone, the consist resolves like an unknown rolling stock
+        let response = call_service(&app, delete_unit(true)).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let derived =
+            RollingStockModel::retrieve_or_derive(&mut conn, "consist_create_and_derive".into())
+                .await
+                .unwrap();
+        assert!(derived.is_none());
+    }
+
+    #[rstest]
+    async fn consist_create_name_of_rolling_stock(db_pool: Arc<DbConnectionPool>) {
+        // GIVEN
+        let app = create_test_service().await;
+        let unit = named_fast_rolling_stock("consist_create_name_of_rolling_stock", db_pool).await;
+        let req = TestRequest::post()
+            .uri("/consist")
+            .set_json(json!({
+                "name": unit.model.name,
+                "units": [{ "rolling_stock_id": unit.id(), "count": 2 }]
+            }))
+            .to_request();
+
+        // WHEN
+        let response = call_service(&app, req).await;
+
+        // THEN
+        assert_response_error_type_match!(
+            response,
+            ConsistError::NameAlreadyUsed {
+                name: unit.model.name.clone()
+            }
+        );
+    }
+
+    #[rstest]
+    async fn consist_create_unknown_rolling_stock() {
+        // GIVEN
+        let app = create_test_service().await;
+        let req = TestRequest::post()
+            .uri("/consist")
+            .set_json(json!({
+                "name": "consist_create_unknown_rolling_stock",
+                "units": [{ "rolling_stock_id": -1, "count": 2 }]
+            }))
+            .to_request();
+
+        // WHEN
+        let response = call_service(&app, req).await;
+
+        // THEN
+        assert_response_error_type_match!(
+            response,
+            ConsistError::RollingStocksNotFound {
+                rolling_stock_ids: vec![-1]
+            }
+        );
+    }
+}
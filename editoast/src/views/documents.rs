@@ -16,6 +16,7 @@ use utoipa::ToSchema;
 use crate::error::Result;
 use crate::modelsv2::DbConnectionPoolV2;
 use crate::modelsv2::*;
+use crate::views::authz::Authentication;
 
 crate::routes! {
     "/documents" => {
@@ -80,6 +81,7 @@ struct NewDocumentResponse {
 #[post("")]
 async fn post(
     db_pool: Data<DbConnectionPoolV2>,
+    authentication: Authentication,
     content_type: Header<ContentType>,
     bytes: Bytes,
 ) -> Result<HttpResponse> {
@@ -88,9 +90,11 @@ async fn post(
 
     // Create document
     let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
     let doc = Document::changeset()
         .content_type(content_type.to_owned())
         .data(bytes.to_vec())
+        .user_id(authorizer.user().map(|user| user.id))
         .create(conn)
         .await?;
 
@@ -1,26 +1,39 @@
+mod generation;
+
 use std::collections::HashMap;
+use std::collections::HashSet;
 
 use actix_web::delete;
 use actix_web::get;
 use actix_web::post;
+use actix_web::put;
 use actix_web::web::Data;
 use actix_web::web::Json;
 use actix_web::web::Path;
 use actix_web::web::Query;
 use actix_web::HttpResponse;
+use chashmap::CHashMap;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::AsyncConnection;
 use editoast_derive::EditoastError;
 use serde::Deserialize;
 use thiserror::Error;
 use utoipa::IntoParams;
 
+use crate::error::InternalError;
 use crate::error::Result;
+use crate::infra_cache::InfraCache;
 use crate::modelsv2::electrical_profiles::ElectricalProfileSet;
 use crate::modelsv2::electrical_profiles::LightElectricalProfileSet;
 use crate::modelsv2::Create;
+use crate::modelsv2::DbConnection;
 use crate::modelsv2::DbConnectionPool;
 use crate::modelsv2::DeleteStatic;
+use crate::modelsv2::Infra;
 use crate::modelsv2::Model;
 use crate::modelsv2::Retrieve;
+use crate::modelsv2::Update;
+use editoast_schemas::infra::ElectricalProfile;
 use editoast_schemas::infra::ElectricalProfileSetData;
 use editoast_schemas::infra::LevelValues;
 
@@ -28,11 +41,21 @@ crate::routes! {
     "/electrical_profile_set" => {
         post_electrical_profile,
         list,
+        generation::routes(),
         "/{electrical_profile_set_id}" => {
             get,
+            put,
             delete,
+            "/levels" => {
+                add_level,
+                "/{level_index}" => {
+                    update_level,
+                    delete_level,
+                },
+            },
             "/level_order" => {
-                get_level_order
+                get_level_order,
+                update_level_order,
             }
         }
     }
@@ -41,6 +64,7 @@ crate::routes! {
 editoast_common::schemas! {
     LightElectricalProfileSet,
     ElectricalProfileSet,
+    generation::schemas(),
 }
 
 #[derive(IntoParams)]
@@ -49,6 +73,19 @@ pub struct ElectricalProfileSetId {
     electrical_profile_set_id: i64,
 }
 
+#[derive(Deserialize, IntoParams)]
+struct ElectricalProfileLevelParam {
+    electrical_profile_set_id: i64,
+    /// The index of the electrical profile in the levels of the set
+    level_index: usize,
+}
+
+#[derive(Deserialize, IntoParams)]
+struct ElectricalProfileValidationArgs {
+    /// When given, the track ranges are checked against the track sections of this infra
+    infra_id: Option<i64>,
+}
+
 /// Retrieve the list of ids and names of electrical profile sets available
 #[utoipa::path(
     tag = "electrical_profiles",
@@ -170,6 +207,348 @@ async fn post_electrical_profile(
     Ok(Json(ep_set.create(conn).await?))
 }
 
+#[derive(Deserialize, IntoParams)]
+struct ElectricalProfileSetEditArgs {
+    /// The new name of the set, left unchanged if not given
+    name: Option<String>,
+    /// When given, the track ranges are checked against the track sections of this infra
+    infra_id: Option<i64>,
+}
+
+/// Replace the content of an electrical profile set
+#[utoipa::path(
+    tag = "electrical_profiles",
+    params(ElectricalProfileSetId, ElectricalProfileSetEditArgs),
+    request_body = ElectricalProfileSetData,
+    responses(
+        (status = 200, body = ElectricalProfileSet, description = "The updated electrical profile set"),
+        (status = 400, body = InternalError, description = "The electrical profiles are invalid"),
+        (status = 404, body = InternalError, description = "The electrical profile set or the infra was not found"),
+    )
+)]
+#[put("")]
+async fn put(
+    db_pool: Data<DbConnectionPool>,
+    infra_caches: Data<CHashMap<i64, InfraCache>>,
+    electrical_profile_set: Path<i64>,
+    Query(args): Query<ElectricalProfileSetEditArgs>,
+    Json(data): Json<ElectricalProfileSetData>,
+) -> Result<Json<ElectricalProfileSet>> {
+    let electrical_profile_set_id = electrical_profile_set.into_inner();
+    let mut conn = db_pool.get().await?;
+    let ep_set = edit_set(
+        &mut conn,
+        &infra_caches,
+        electrical_profile_set_id,
+        args.infra_id,
+        args.name,
+        |current| {
+            *current = data;
+            Ok(())
+        },
+    )
+    .await?;
+    Ok(Json(ep_set))
+}
+
+/// Add an electrical profile to the levels of a set
+#[utoipa::path(
+    tag = "electrical_profiles",
+    params(ElectricalProfileSetId, ElectricalProfileValidationArgs),
+    request_body = ElectricalProfile,
+    responses(
+        (status = 200, body = ElectricalProfileSet, description = "The updated electrical profile set"),
+        (status = 400, body = InternalError, description = "The electrical profile is invalid or overlaps another one"),
+        (status = 404, body = InternalError, description = "The electrical profile set or the infra was not found"),
+    )
+)]
+#[post("")]
+async fn add_level(
+    db_pool: Data<DbConnectionPool>,
+    infra_caches: Data<CHashMap<i64, InfraCache>>,
+    electrical_profile_set: Path<i64>,
+    Query(args): Query<ElectricalProfileValidationArgs>,
+    Json(profile): Json<ElectricalProfile>,
+) -> Result<Json<ElectricalProfileSet>> {
+    let electrical_profile_set_id = electrical_profile_set.into_inner();
+    let mut conn = db_pool.get().await?;
+    let ep_set = edit_set(
+        &mut conn,
+        &infra_caches,
+        electrical_profile_set_id,
+        args.infra_id,
+        None,
+        |data| {
+            data.levels.push(profile);
+            Ok(())
+        },
+    )
+    .await?;
+    Ok(Json(ep_set))
+}
+
+/// Replace an electrical profile of a set
+#[utoipa::path(
+    tag = "electrical_profiles",
+    params(ElectricalProfileLevelParam, ElectricalProfileValidationArgs),
+    request_body = ElectricalProfile,
+    responses(
+        (status = 200, body = ElectricalProfileSet, description = "The updated electrical profile set"),
+        (status = 400, body = InternalError, description = "The electrical profile is invalid or overlaps another one"),
+        (status = 404, body = InternalError, description = "The electrical profile set, the level or the infra was not found"),
+    )
+)]
+#[put("")]
+async fn update_level(
+    db_pool: Data<DbConnectionPool>,
+    infra_caches: Data<CHashMap<i64, InfraCache>>,
+    path: Path<ElectricalProfileLevelParam>,
+    Query(args): Query<ElectricalProfileValidationArgs>,
+    Json(profile): Json<ElectricalProfile>,
+) -> Result<Json<ElectricalProfileSet>> {
+    let ElectricalProfileLevelParam {
+        electrical_profile_set_id,
+        level_index,
+    } = path.into_inner();
+    let mut conn = db_pool.get().await?;
+    let ep_set = edit_set(
+        &mut conn,
+        &infra_caches,
+        electrical_profile_set_id,
+        args.infra_id,
+        None,
+        |data| {
+            let level =
+                data.levels
+                    .get_mut(level_index)
+                    .ok_or(ElectricalProfilesError::LevelNotFound {
+                        electrical_profile_set_id,
+                        level_index,
+                    })?;
+            *level = profile;
+            Ok(())
+        },
+    )
+    .await?;
+    Ok(Json(ep_set))
+}
+
+/// Remove an electrical profile from a set
+#[utoipa::path(
+    tag = "electrical_profiles",
+    params(ElectricalProfileLevelParam),
+    responses(
+        (status = 200, body = ElectricalProfileSet, description = "The updated electrical profile set"),
+        (status = 404, body = InternalError, description = "The electrical profile set or the level was not found"),
+    )
+)]
+#[delete("")]
+async fn delete_level(
+    db_pool: Data<DbConnectionPool>,
+    infra_caches: Data<CHashMap<i64, InfraCache>>,
+    path: Path<ElectricalProfileLevelParam>,
+) -> Result<Json<ElectricalProfileSet>> {
+    let ElectricalProfileLevelParam {
+        electrical_profile_set_id,
+        level_index,
+    } = path.into_inner();
+    let mut conn = db_pool.get().await?;
+    let ep_set = edit_set(
+        &mut conn,
+        &infra_caches,
+        electrical_profile_set_id,
+        None,
+        None,
+        |data| {
+            if level_index >= data.levels.len() {
+                return Err(ElectricalProfilesError::LevelNotFound {
+                    electrical_profile_set_id,
+                    level_index,
+                }
+                .into());
+            }
+            data.levels.remove(level_index);
+            Ok(())
+        },
+    )
+    .await?;
+    Ok(Json(ep_set))
+}
+
+/// Replace the electrical profile value order of a set
+#[utoipa::path(
+    tag = "electrical_profiles",
+    params(ElectricalProfileSetId),
+    request_body(
+        content = HashMap<String, LevelValues>,
+        description = "A dictionary mapping electrification modes to a list of electrical profiles ordered by decreasing strength",
+        example = json!({
+            "1500V": ["A", "B", "C"],
+            "25000V": ["25000V", "22500V", "20000V"]
+        })
+    ),
+    responses(
+        (status = 200, body = HashMap<String, LevelValues>, description = "The updated level order"),
+        (status = 400, body = InternalError, description = "A value appears twice in the order of an electrification mode"),
+        (status = 404, body = InternalError, description = "The requested electrical profile set was not found"),
+    )
+)]
+#[put("")]
+async fn update_level_order(
+    db_pool: Data<DbConnectionPool>,
+    infra_caches: Data<CHashMap<i64, InfraCache>>,
+    electrical_profile_set: Path<i64>,
+    Json(level_order): Json<HashMap<String, LevelValues>>,
+) -> Result<Json<HashMap<String, LevelValues>>> {
+    let electrical_profile_set_id = electrical_profile_set.into_inner();
+    let mut conn = db_pool.get().await?;
+    let ep_set = edit_set(
+        &mut conn,
+        &infra_caches,
+        electrical_profile_set_id,
+        None,
+        None,
+        |data| {
+            data.level_order = level_order;
+            Ok(())
+        },
+    )
+    .await?;
+    Ok(Json(ep_set.data.level_order))
+}
+
+/// Edits the electrical profiles of a set, and renames it if a name is given
+///
+/// The set stays locked until the edit is saved, so that concurrent edits don't overwrite
+/// each other. Its version is bumped, which invalidates the simulations run with it.
+async fn edit_set<F>(
+    conn: &mut DbConnection,
+    infra_caches: &CHashMap<i64, InfraCache>,
+    electrical_profile_set_id: i64,
+    infra_id: Option<i64>,
+    name: Option<String>,
+    edit: F,
+) -> Result<ElectricalProfileSet>
+where
+    F: FnOnce(&mut ElectricalProfileSetData) -> Result<()> + Send,
+{
+    conn.transaction::<_, InternalError, _>(|conn| {
+        async move {
+            let mut ep_set =
+                ElectricalProfileSet::retrieve_for_update(conn, electrical_profile_set_id)
+                    .await?
+                    .ok_or(ElectricalProfilesError::NotFound {
+                        electrical_profile_set_id,
+                    })?;
+            edit(&mut ep_set.data)?;
+            validate_with_infra(conn, infra_caches, infra_id, &ep_set.data).await?;
+            ElectricalProfileSet::changeset()
+                .flat_name(name)
+                .data(ep_set.data)
+                .version(ep_set.version + 1)
+                .update_or_fail(conn, electrical_profile_set_id, || {
+                    ElectricalProfilesError::NotFound {
+                        electrical_profile_set_id,
+                    }
+                })
+                .await
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+/// Validate the electrical profiles of a set, against the track sections of an infra if given
+async fn validate_with_infra(
+    conn: &mut DbConnection,
+    infra_caches: &CHashMap<i64, InfraCache>,
+    infra_id: Option<i64>,
+    data: &ElectricalProfileSetData,
+) -> Result<()> {
+    let Some(infra_id) = infra_id else {
+        return validate_profile_set(data, None);
+    };
+    let infra = Infra::retrieve_or_fail(conn, infra_id, || {
+        ElectricalProfilesError::InfraNotFound { infra_id }
+    })
+    .await?;
+    let infra_cache = InfraCache::get_or_load(conn, infra_caches, &infra).await?;
+    validate_profile_set(data, Some(&*infra_cache))
+}
+
+/// Check that the electrical profiles of a set are consistent
+///
+/// Track ranges must be non-empty and, if an infra is given, lie on one of its track sections.
+/// Two profiles of the same power class may only overlap if they have the same value,
+/// and a value may only appear once in the order of an electrification mode.
+fn validate_profile_set(
+    data: &ElectricalProfileSetData,
+    infra_cache: Option<&InfraCache>,
+) -> Result<()> {
+    let mut ranges_by_track: HashMap<(&str, &str), Vec<(f64, f64, &str)>> = HashMap::new();
+    for profile in &data.levels {
+        for range in &profile.track_ranges {
+            let track = range.track.as_str();
+            let length = match infra_cache {
+                Some(infra_cache) => {
+                    let Ok(track_section) = infra_cache.get_track_section(track) else {
+                        return Err(ElectricalProfilesError::TrackSectionNotFound {
+                            track: track.to_string(),
+                        }
+                        .into());
+                    };
+                    track_section.length
+                }
+                None => f64::INFINITY,
+            };
+            if range.begin < 0. || range.begin >= range.end || range.end > length {
+                return Err(ElectricalProfilesError::InvalidTrackRange {
+                    track: track.to_string(),
+                    begin: range.begin,
+                    end: range.end,
+                }
+                .into());
+            }
+            ranges_by_track
+                .entry((profile.power_class.as_str(), track))
+                .or_default()
+                .push((range.begin, range.end, profile.value.as_str()));
+        }
+    }
+
+    for ((power_class, track), mut ranges) in ranges_by_track {
+        ranges.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for (i, (_, end, value)) in ranges.iter().enumerate() {
+            let overlapping = ranges[i + 1..]
+                .iter()
+                .take_while(|(other_begin, _, _)| other_begin < end);
+            for (_, _, other_value) in overlapping {
+                if value != other_value {
+                    return Err(ElectricalProfilesError::OverlappingProfiles {
+                        power_class: power_class.to_string(),
+                        track: track.to_string(),
+                        value: value.to_string(),
+                        other_value: other_value.to_string(),
+                    }
+                    .into());
+                }
+            }
+        }
+    }
+
+    for (electrification, values) in &data.level_order {
+        let mut seen = HashSet::new();
+        if let Some(value) = values.0.iter().find(|value| !seen.insert(*value)) {
+            return Err(ElectricalProfilesError::DuplicateLevelValue {
+                electrification: electrification.clone(),
+                value: value.clone(),
+            }
+            .into());
+        }
+    }
+    Ok(())
+}
+
 #[derive(Debug, Error, EditoastError)]
 #[editoast_error(base_id = "electrical_profiles")]
 pub enum ElectricalProfilesError {
@@ -177,6 +556,41 @@ pub enum ElectricalProfilesError {
     #[error("Electrical Profile Set '{electrical_profile_set_id}', could not be found")]
     #[editoast_error(status = 404)]
     NotFound { electrical_profile_set_id: i64 },
+    #[error("Electrical profile set '{electrical_profile_set_id}' has no level at index '{level_index}'")]
+    #[editoast_error(status = 404)]
+    LevelNotFound {
+        electrical_profile_set_id: i64,
+        level_index: usize,
+    },
+    #[error("Infra '{infra_id}', could not be found")]
+    #[editoast_error(status = 404)]
+    InfraNotFound { infra_id: i64 },
+    #[error("Track section '{track}' does not exist in the infra")]
+    #[editoast_error(status = 400)]
+    TrackSectionNotFound { track: String },
+    #[error("Invalid range [{begin}, {end}] on track section '{track}'")]
+    #[editoast_error(status = 400)]
+    InvalidTrackRange { track: String, begin: f64, end: f64 },
+    #[error("Electrical profiles '{value}' and '{other_value}' of power class '{power_class}' overlap on track section '{track}'")]
+    #[editoast_error(status = 400)]
+    OverlappingProfiles {
+        power_class: String,
+        track: String,
+        value: String,
+        other_value: String,
+    },
+    #[error("Electrical profile '{value}' appears twice in the order of '{electrification}'")]
+    #[editoast_error(status = 400)]
+    DuplicateLevelValue {
+        electrification: String,
+        value: String,
+    },
+    #[error("Feeding point at position '{position}' of track section '{track}' is invalid")]
+    #[editoast_error(status = 400)]
+    InvalidFeedingPoint { track: String, position: f64 },
+    #[error("Invalid profile steps for electrification '{voltage}'")]
+    #[editoast_error(status = 400)]
+    InvalidProfileSteps { voltage: String },
 }
 
 #[cfg(test)]
@@ -190,12 +604,13 @@ mod tests {
     use std::sync::Arc;
 
     use super::*;
+    use crate::assert_response_error_type_match;
     use crate::fixtures::tests::db_pool;
     use crate::fixtures::tests::dummy_electrical_profile_set;
     use crate::fixtures::tests::electrical_profile_set;
     use crate::fixtures::tests::TestFixture;
+    use crate::infra_cache::tests::create_small_infra_cache;
     use crate::views::tests::create_test_service;
-    use editoast_schemas::infra::ElectricalProfile;
     use editoast_schemas::infra::TrackRange;
 
     #[rstest]
@@ -331,4 +746,74 @@ mod tests {
         };
         assert_eq!(created_ep_set.model.name.clone(), "elec");
     }
+
+    #[rstest]
+    async fn add_level_rejects_overlapping_profiles(
+        #[future] dummy_electrical_profile_set: TestFixture<ElectricalProfileSet>,
+    ) {
+        let profile_set = dummy_electrical_profile_set.await;
+        let electrical_profile_set_id = profile_set.id();
+        let app = create_test_service().await;
+        let profile = |value: &str, power_class: &str| ElectricalProfile {
+            value: value.to_string(),
+            power_class: power_class.to_string(),
+            track_ranges: vec![TrackRange::new("InvalidRef", 50., 150.)],
+        };
+
+        let req = TestRequest::post()
+            .uri(&format!(
+                "/electrical_profile_set/{electrical_profile_set_id}/levels"
+            ))
+            .set_json(profile("B", "1"))
+            .to_request();
+        let response = call_service(&app, req).await;
+        assert_response_error_type_match!(
+            response,
+            ElectricalProfilesError::OverlappingProfiles {
+                power_class: "1".to_string(),
+                track: "InvalidRef".to_string(),
+                value: "A".to_string(),
+                other_value: "B".to_string(),
+            }
+        );
+
+        let req = TestRequest::post()
+            .uri(&format!(
+                "/electrical_profile_set/{electrical_profile_set_id}/levels"
+            ))
+            .set_json(profile("B", "2"))
+            .to_request();
+        let response = call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let updated: ElectricalProfileSet = read_body_json(response).await;
+        assert_eq!(updated.data.levels.len(), 2);
+        // The rejected edit left the version untouched
+        assert_eq!(updated.version, profile_set.model.version + 1);
+    }
+
+    #[test]
+    fn validate_profile_set_against_infra() {
+        let infra_cache = create_small_infra_cache();
+        let data = |track: &str, begin: f64, end: f64| ElectricalProfileSetData {
+            levels: vec![ElectricalProfile {
+                value: "A".to_string(),
+                power_class: "1".to_string(),
+                track_ranges: vec![TrackRange::new(track, begin, end)],
+            }],
+            level_order: HashMap::from([("1500V".to_string(), LevelValues(vec!["A".to_string()]))]),
+        };
+
+        assert!(validate_profile_set(&data("A", 0., 500.), Some(&infra_cache)).is_ok());
+        assert!(validate_profile_set(&data("A", 0., 600.), None).is_ok());
+        assert!(validate_profile_set(&data("A", 0., 600.), Some(&infra_cache)).is_err());
+        assert!(validate_profile_set(&data("Z", 0., 100.), Some(&infra_cache)).is_err());
+        assert!(validate_profile_set(&data("A", 100., 100.), None).is_err());
+
+        let mut duplicated_value = data("A", 0., 500.);
+        duplicated_value.level_order = HashMap::from([(
+            "1500V".to_string(),
+            LevelValues(vec!["A".to_string(), "A".to_string()]),
+        )]);
+        assert!(validate_profile_set(&duplicated_value, None).is_err());
+    }
 }
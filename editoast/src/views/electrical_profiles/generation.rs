@@ -0,0 +1,497 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::collections::HashMap;
+
+use actix_web::post;
+use actix_web::web::Data;
+use actix_web::web::Json;
+use chashmap::CHashMap;
+use editoast_common::rangemap_utils::Float;
+use editoast_schemas::infra::ElectricalProfile;
+use editoast_schemas::infra::ElectricalProfileSetData;
+use editoast_schemas::infra::Endpoint;
+use editoast_schemas::infra::LevelValues;
+use editoast_schemas::infra::TrackEndpoint;
+use editoast_schemas::infra::TrackRange;
+use itertools::Itertools;
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use super::ElectricalProfilesError;
+use crate::error::Result;
+use crate::infra_cache::InfraCache;
+use crate::modelsv2::electrical_profiles::ElectricalProfileSet;
+use crate::modelsv2::Create;
+use crate::modelsv2::DbConnectionPool;
+use crate::modelsv2::Infra;
+use crate::modelsv2::Model;
+use crate::modelsv2::Retrieve;
+
+crate::routes! {
+    "/generate" => {
+        generate,
+    },
+}
+
+editoast_common::schemas! {
+    ElectricalProfileSetGenerationForm,
+    FeedingPoint,
+    ProfileStep,
+}
+
+/// The substations of an infra and how their power weakens along the catenaries
+#[derive(Debug, Deserialize, ToSchema)]
+struct ElectricalProfileSetGenerationForm {
+    /// The name of the generated electrical profile set
+    name: String,
+    infra_id: i64,
+    /// The points where substations feed the catenaries
+    feeding_points: Vec<FeedingPoint>,
+    /// For each electrification voltage, the electrical profiles from the strongest to the weakest
+    #[schema(example = json!({
+        "25000V": [
+            { "value": "25000V", "max_distance": 10000.0 },
+            { "value": "22500V", "max_distance": 20000.0 },
+            { "value": "20000V" }
+        ]
+    }))]
+    profiles: HashMap<String, Vec<ProfileStep>>,
+    /// The power classes of the rolling stocks the profiles are generated for
+    power_classes: Vec<String>,
+}
+
+/// A point where a substation feeds the catenaries of an electrification voltage
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+struct FeedingPoint {
+    track: String,
+    /// The offset of the feeding point on the track section in meters
+    position: f64,
+    #[schema(example = "25000V")]
+    voltage: String,
+}
+
+/// An electrical profile applying up to a distance from the closest feeding point
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+struct ProfileStep {
+    #[schema(example = "25000V")]
+    value: String,
+    /// The distance in meters up to which the profile applies, unbounded if not given
+    max_distance: Option<f64>,
+}
+
+/// Generate an electrical profile set from the electrifications of an infra
+///
+/// Along each electrified track range, the electrical profile weakens with the distance
+/// to the closest feeding point of the same voltage, measured along the track sections.
+#[utoipa::path(
+    tag = "electrical_profiles",
+    request_body = ElectricalProfileSetGenerationForm,
+    responses(
+        (status = 200, body = ElectricalProfileSet, description = "The generated electrical profile set"),
+        (status = 400, body = InternalError, description = "The feeding points or the profile steps are invalid"),
+        (status = 404, body = InternalError, description = "The infra was not found"),
+    )
+)]
+#[post("")]
+async fn generate(
+    db_pool: Data<DbConnectionPool>,
+    infra_caches: Data<CHashMap<i64, InfraCache>>,
+    Json(form): Json<ElectricalProfileSetGenerationForm>,
+) -> Result<Json<ElectricalProfileSet>> {
+    let conn = &mut db_pool.get().await?;
+    let infra_id = form.infra_id;
+    let infra = Infra::retrieve_or_fail(conn, infra_id, || {
+        ElectricalProfilesError::InfraNotFound { infra_id }
+    })
+    .await?;
+    let data = {
+        let infra_cache = InfraCache::get_or_load(conn, &infra_caches, &infra).await?;
+        generate_profiles(
+            &infra_cache,
+            &form.feeding_points,
+            &form.profiles,
+            &form.power_classes,
+        )?
+    };
+    let ep_set = ElectricalProfileSet::changeset()
+        .name(form.name)
+        .data(data)
+        .create(conn)
+        .await?;
+    Ok(Json(ep_set))
+}
+
+/// Build the electrical profiles of the electrifications of an infra
+///
+/// Each voltage with profile steps gets one electrical profile per step and power class.
+/// Electrified ranges out of reach of the feeding points of their voltage get the last step,
+/// unless it is bounded.
+fn generate_profiles(
+    infra_cache: &InfraCache,
+    feeding_points: &[FeedingPoint],
+    profiles: &HashMap<String, Vec<ProfileStep>>,
+    power_classes: &[String],
+) -> Result<ElectricalProfileSetData> {
+    for feeding_point in feeding_points {
+        let length = infra_cache
+            .get_track_section(&feeding_point.track)
+            .map(|track_section| track_section.length);
+        if !matches!(length, Ok(length) if (0.0..=length).contains(&feeding_point.position)) {
+            return Err(ElectricalProfilesError::InvalidFeedingPoint {
+                track: feeding_point.track.clone(),
+                position: feeding_point.position,
+            }
+            .into());
+        }
+    }
+    for (voltage, steps) in profiles {
+        check_steps(voltage, steps)?;
+    }
+
+    let links = track_links(infra_cache);
+    let mut levels = vec![];
+    let mut level_order = HashMap::new();
+    for (voltage, steps) in profiles.iter().sorted_by_key(|(voltage, _)| *voltage) {
+        let voltage_feeding_points: Vec<_> = feeding_points
+            .iter()
+            .filter(|feeding_point| &feeding_point.voltage == voltage)
+            .collect();
+        let distances = endpoint_distances(infra_cache, &links, &voltage_feeding_points);
+
+        let mut ranges_by_step = vec![vec![]; steps.len()];
+        let electrifications = infra_cache
+            .electrifications()
+            .values()
+            .map(|electrification| electrification.unwrap_electrification())
+            .filter(|electrification| &electrification.voltage.0 == voltage);
+        for electrification in electrifications {
+            for range in &electrification.track_ranges {
+                let track = range.track.as_str();
+                let Ok(track_section) = infra_cache.get_track_section(track) else {
+                    continue;
+                };
+                let distance_from = |endpoint| {
+                    distances
+                        .get(&TrackEndpoint::new(track, endpoint))
+                        .copied()
+                        .unwrap_or(f64::INFINITY)
+                };
+                let track_distance = TrackDistance {
+                    length: track_section.length,
+                    begin: distance_from(Endpoint::Begin),
+                    end: distance_from(Endpoint::End),
+                    feeding_points: voltage_feeding_points
+                        .iter()
+                        .filter(|feeding_point| feeding_point.track == track)
+                        .map(|feeding_point| feeding_point.position)
+                        .collect(),
+                };
+                for (begin, end, step) in track_distance.split(range.begin, range.end, steps) {
+                    ranges_by_step[step].push(TrackRange::new(track, begin, end));
+                }
+            }
+        }
+
+        for (step, track_ranges) in steps.iter().zip(ranges_by_step) {
+            if track_ranges.is_empty() {
+                continue;
+            }
+            levels.extend(power_classes.iter().map(|power_class| ElectricalProfile {
+                value: step.value.clone(),
+                power_class: power_class.clone(),
+                track_ranges: track_ranges.clone(),
+            }));
+        }
+        let values = steps.iter().map(|step| step.value.clone()).collect();
+        level_order.insert(voltage.clone(), LevelValues(values));
+    }
+    Ok(ElectricalProfileSetData {
+        levels,
+        level_order,
+    })
+}
+
+/// Check that the steps are given by strictly increasing distance, with distinct values,
+/// and that only the last one is unbounded
+fn check_steps(voltage: &str, steps: &[ProfileStep]) -> Result<()> {
+    let distances: Vec<_> = steps.iter().filter_map(|step| step.max_distance).collect();
+    let is_valid = !steps.is_empty()
+        && steps[..steps.len() - 1]
+            .iter()
+            .all(|step| step.max_distance.is_some())
+        && distances
+            .iter()
+            .all(|distance| distance.is_finite() && *distance >= 0.)
+        && distances.iter().tuple_windows().all(|(a, b)| a < b)
+        && steps.iter().map(|step| &step.value).all_unique();
+    if !is_valid {
+        return Err(ElectricalProfilesError::InvalidProfileSteps {
+            voltage: voltage.to_string(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// The track endpoints connected to each track endpoint by a switch
+fn track_links(infra_cache: &InfraCache) -> HashMap<TrackEndpoint, Vec<TrackEndpoint>> {
+    let mut links: HashMap<_, Vec<_>> = HashMap::new();
+    for switch in infra_cache.switches().values() {
+        let switch = switch.unwrap_switch();
+        let Some(switch_type) = infra_cache.switch_types().get(&switch.switch_type) else {
+            continue;
+        };
+        for connection in switch_type.unwrap_switch_type().groups.values().flatten() {
+            let Some(src) = switch.ports.get::<String>(&connection.src) else {
+                continue;
+            };
+            let Some(dst) = switch.ports.get::<String>(&connection.dst) else {
+                continue;
+            };
+            links.entry(src.clone()).or_default().push(dst.clone());
+            links.entry(dst.clone()).or_default().push(src.clone());
+        }
+    }
+    links
+}
+
+/// The distance from each reachable track endpoint to the closest feeding point,
+/// along the track sections
+fn endpoint_distances(
+    infra_cache: &InfraCache,
+    links: &HashMap<TrackEndpoint, Vec<TrackEndpoint>>,
+    feeding_points: &[&FeedingPoint],
+) -> HashMap<TrackEndpoint, f64> {
+    let mut candidates = vec![];
+    for feeding_point in feeding_points {
+        let Ok(track_section) = infra_cache.get_track_section(&feeding_point.track) else {
+            continue;
+        };
+        let track = &feeding_point.track;
+        candidates.push((
+            TrackEndpoint::new(track, Endpoint::Begin),
+            feeding_point.position,
+        ));
+        candidates.push((
+            TrackEndpoint::new(track, Endpoint::End),
+            track_section.length - feeding_point.position,
+        ));
+    }
+    let mut queue: BinaryHeap<_> = candidates
+        .iter()
+        .enumerate()
+        .map(|(index, (_, distance))| Reverse((Float::from(*distance), index)))
+        .collect();
+
+    let mut distances = HashMap::new();
+    while let Some(Reverse((_, index))) = queue.pop() {
+        let (endpoint, distance) = candidates[index].clone();
+        if distances.contains_key(&endpoint) {
+            continue;
+        }
+        let Ok(track_section) = infra_cache.get_track_section(&endpoint.track) else {
+            continue;
+        };
+        let other_endpoint = match endpoint.endpoint {
+            Endpoint::Begin => Endpoint::End,
+            Endpoint::End => Endpoint::Begin,
+        };
+        let mut neighbours = vec![(
+            TrackEndpoint::new(&endpoint.track, other_endpoint),
+            distance + track_section.length,
+        )];
+        if let Some(linked) = links.get(&endpoint) {
+            neighbours.extend(linked.iter().map(|linked| (linked.clone(), distance)));
+        }
+        distances.insert(endpoint, distance);
+        for (neighbour, distance) in neighbours {
+            if !distances.contains_key(&neighbour) {
+                queue.push(Reverse((Float::from(distance), candidates.len())));
+                candidates.push((neighbour, distance));
+            }
+        }
+    }
+    distances
+}
+
+/// The distance to the closest feeding point along a track section
+struct TrackDistance {
+    length: f64,
+    /// The distance from the beginning of the track section to the closest feeding point
+    begin: f64,
+    /// The distance from the end of the track section to the closest feeding point
+    end: f64,
+    /// The positions of the feeding points located on the track section
+    feeding_points: Vec<f64>,
+}
+
+impl TrackDistance {
+    fn at(&self, position: f64) -> f64 {
+        self.feeding_points
+            .iter()
+            .map(|feeding_point| (position - feeding_point).abs())
+            .fold(
+                f64::min(self.begin + position, self.end + self.length - position),
+                f64::min,
+            )
+    }
+
+    /// The positions at which the distance may cross the given threshold
+    fn crossings(&self, threshold: f64) -> Vec<f64> {
+        let mut crossings = vec![threshold - self.begin, self.length - threshold + self.end];
+        for feeding_point in &self.feeding_points {
+            crossings.push(feeding_point - threshold);
+            crossings.push(feeding_point + threshold);
+        }
+        crossings
+    }
+
+    /// Split a range of the track section by the index of the step applying to each part
+    fn split(&self, begin: f64, end: f64, steps: &[ProfileStep]) -> Vec<(f64, f64, usize)> {
+        let mut bounds: Vec<_> = steps
+            .iter()
+            .filter_map(|step| step.max_distance)
+            .flat_map(|max_distance| self.crossings(max_distance))
+            .filter(|position| begin < *position && *position < end)
+            .chain([begin, end])
+            .collect();
+        bounds.sort_by(f64::total_cmp);
+        bounds.dedup();
+
+        let mut parts: Vec<(f64, f64, usize)> = vec![];
+        for (from, to) in bounds.into_iter().tuple_windows() {
+            let distance = self.at((from + to) / 2.);
+            let Some(step) = steps
+                .iter()
+                .position(|step| step.max_distance.unwrap_or(f64::INFINITY) >= distance)
+            else {
+                continue;
+            };
+            match parts.last_mut() {
+                Some(last) if last.1 == from && last.2 == step => last.1 = to,
+                _ => parts.push((from, to, step)),
+            }
+        }
+        parts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::infra_cache::tests::create_electrification_cache;
+    use crate::infra_cache::tests::create_small_infra_cache;
+
+    fn steps(steps: &[(&str, Option<f64>)]) -> HashMap<String, Vec<ProfileStep>> {
+        let steps = steps
+            .iter()
+            .map(|(value, max_distance)| ProfileStep {
+                value: value.to_string(),
+                max_distance: *max_distance,
+            })
+            .collect();
+        HashMap::from([("1500V".to_string(), steps)])
+    }
+
+    fn feeding_point(track: &str, position: f64) -> FeedingPoint {
+        FeedingPoint {
+            track: track.to_string(),
+            position,
+            voltage: "1500V".to_string(),
+        }
+    }
+
+    #[test]
+    fn profiles_weaken_with_distance() {
+        let mut infra_cache = create_small_infra_cache();
+        let electrification = create_electrification_cache(
+            "E",
+            vec![("A", 0., 500.), ("B", 0., 500.), ("C", 0., 500.)],
+        );
+        infra_cache.add(electrification).unwrap();
+        let power_classes = ["1".to_string()];
+
+        let data = generate_profiles(
+            &infra_cache,
+            &[feeding_point("A", 100.)],
+            &steps(&[("A", Some(600.)), ("B", Some(1000.)), ("C", None)]),
+            &power_classes,
+        )
+        .unwrap();
+
+        let ranges: HashMap<_, _> = data
+            .levels
+            .into_iter()
+            .map(|profile| {
+                let mut ranges = profile.track_ranges;
+                ranges.sort_by(|a, b| a.track.as_str().cmp(b.track.as_str()));
+                (profile.value, ranges)
+            })
+            .collect();
+        assert_eq!(
+            ranges["A"],
+            vec![
+                TrackRange::new("A", 0., 500.),
+                TrackRange::new("B", 0., 200.)
+            ]
+        );
+        assert_eq!(
+            ranges["B"],
+            vec![
+                TrackRange::new("B", 200., 500.),
+                TrackRange::new("C", 0., 100.)
+            ]
+        );
+        assert_eq!(ranges["C"], vec![TrackRange::new("C", 100., 500.)]);
+        assert_eq!(
+            data.level_order["1500V"],
+            LevelValues(vec!["A".into(), "B".into(), "C".into()])
+        );
+    }
+
+    #[test]
+    fn unreachable_ranges_get_the_last_step() {
+        let mut infra_cache = create_small_infra_cache();
+        let electrification = create_electrification_cache("E", vec![("A", 0., 500.)]);
+        infra_cache.add(electrification).unwrap();
+
+        let data = generate_profiles(
+            &infra_cache,
+            &[],
+            &steps(&[("A", Some(600.)), ("B", None)]),
+            &["1".to_string()],
+        )
+        .unwrap();
+
+        assert_eq!(data.levels.len(), 1);
+        assert_eq!(data.levels[0].value, "B");
+        assert_eq!(
+            data.levels[0].track_ranges,
+            vec![TrackRange::new("A", 0., 500.)]
+        );
+    }
+
+    #[test]
+    fn invalid_feeding_point_and_steps() {
+        let infra_cache = create_small_infra_cache();
+        let valid_steps = steps(&[("A", Some(600.)), ("B", None)]);
+
+        let outside_track =
+            generate_profiles(&infra_cache, &[feeding_point("A", 600.)], &valid_steps, &[]);
+        let unknown_track =
+            generate_profiles(&infra_cache, &[feeding_point("Z", 0.)], &valid_steps, &[]);
+        let unordered_steps = generate_profiles(
+            &infra_cache,
+            &[],
+            &steps(&[("A", Some(600.)), ("B", Some(300.))]),
+            &[],
+        );
+        let unbounded_first_step =
+            generate_profiles(&infra_cache, &[], &steps(&[("A", None), ("B", None)]), &[]);
+
+        assert!(outside_track.is_err());
+        assert!(unknown_track.is_err());
+        assert!(unordered_steps.is_err());
+        assert!(unbounded_first_step.is_err());
+    }
+}
@@ -0,0 +1,171 @@
+use actix_web::get;
+use actix_web::web::Data;
+use actix_web::web::Json;
+use actix_web::web::Path;
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use super::InfraApiError;
+use crate::error::Result;
+use crate::infra_cache::operation::Operation;
+use crate::modelsv2::infra::InfraDiff;
+use crate::modelsv2::prelude::*;
+use crate::modelsv2::resource_grant::ResourceType;
+use crate::modelsv2::resource_grant::Role;
+use crate::modelsv2::DbConnection;
+use crate::modelsv2::DbConnectionPool;
+use crate::modelsv2::Infra;
+use crate::views::authz::Authentication;
+use crate::views::authz::Authorizer;
+
+crate::routes! {
+    "/diff/{other_infra_id}" => {
+        infra_diff,
+        infra_diff_operations,
+    },
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+struct InfraDiffParams {
+    /// The base infra ID
+    infra_id: i64,
+    /// The ID of the infra to compare the base infra with
+    other_infra_id: i64,
+}
+
+async fn compute_diff(
+    conn: &mut DbConnection,
+    authorizer: &Authorizer,
+    params: InfraDiffParams,
+) -> Result<InfraDiff> {
+    let InfraDiffParams {
+        infra_id,
+        other_infra_id,
+    } = params;
+    for infra_id in [infra_id, other_infra_id] {
+        authorizer
+            .check(conn, ResourceType::Infra, infra_id, Role::Viewer)
+            .await?;
+    }
+    let infra =
+        Infra::retrieve_or_fail(conn, infra_id, || InfraApiError::NotFound { infra_id }).await?;
+    let other_infra = Infra::retrieve_or_fail(conn, other_infra_id, || InfraApiError::NotFound {
+        infra_id: other_infra_id,
+    })
+    .await?;
+    infra.diff(conn, &other_infra).await
+}
+
+/// Compare the objects of two infras
+///
+/// Objects are matched by type and id. Modified objects come with the JSON patch
+/// turning the object of the base infra into the one of the other infra.
+#[utoipa::path(
+    tag = "infra",
+    params(InfraDiffParams),
+    responses(
+        (status = 200, body = InfraDiff, description = "The objects created, deleted and modified in the other infra"),
+        (status = 404, description = "One of the infras was not found"),
+    )
+)]
+#[get("")]
+async fn infra_diff(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    params: Path<InfraDiffParams>,
+) -> Result<Json<InfraDiff>> {
+    let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    Ok(Json(
+        compute_diff(conn, &authorizer, params.into_inner()).await?,
+    ))
+}
+
+/// Compare the objects of two infras and return the edition operations turning the base infra into the other one
+///
+/// The operations can be sent as is to the edition endpoint of the base infra.
+#[utoipa::path(
+    tag = "infra",
+    params(InfraDiffParams),
+    responses(
+        (status = 200, body = Vec<Operation>, description = "The operations to apply to the base infra"),
+        (status = 404, description = "One of the infras was not found"),
+    )
+)]
+#[get("/operations")]
+async fn infra_diff_operations(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    params: Path<InfraDiffParams>,
+) -> Result<Json<Vec<Operation>>> {
+    let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    let diff = compute_diff(conn, &authorizer, params.into_inner()).await?;
+    Ok(Json(diff.into_operations()))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use actix_web::test::call_and_read_body_json;
+    use actix_web::test::call_service;
+    use actix_web::test::TestRequest;
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::*;
+    use crate::fixtures::tests::db_pool;
+    use crate::fixtures::tests::empty_infra;
+    use crate::fixtures::tests::small_infra;
+    use crate::views::tests::create_test_service;
+
+    #[rstest]
+    async fn diff_operations_can_be_replayed() {
+        let app = create_test_service().await;
+        let db_pool = db_pool();
+        let empty_infra = empty_infra(db_pool.clone()).await;
+        let small_infra = small_infra(db_pool).await;
+
+        let req = TestRequest::get()
+            .uri(
+                format!(
+                    "/infra/{}/diff/{}/operations",
+                    empty_infra.id(),
+                    small_infra.id()
+                )
+                .as_str(),
+            )
+            .to_request();
+        let operations: Vec<Operation> = call_and_read_body_json(&app, req).await;
+        assert!(!operations.is_empty());
+        assert!(operations
+            .iter()
+            .all(|operation| matches!(operation, Operation::Create(_))));
+
+        let req = TestRequest::post()
+            .uri(format!("/infra/{}", empty_infra.id()).as_str())
+            .set_json(json!(operations))
+            .to_request();
+        assert_eq!(call_service(&app, req).await.status(), StatusCode::OK);
+
+        let req = TestRequest::get()
+            .uri(format!("/infra/{}/diff/{}", empty_infra.id(), small_infra.id()).as_str())
+            .to_request();
+        let diff: InfraDiff = call_and_read_body_json(&app, req).await;
+        assert!(diff.is_empty());
+    }
+
+    #[rstest]
+    async fn diff_with_unknown_infra_is_not_found() {
+        let app = create_test_service().await;
+        let empty_infra = empty_infra(db_pool()).await;
+
+        let req = TestRequest::get()
+            .uri(format!("/infra/{}/diff/{}", empty_infra.id(), -1).as_str())
+            .to_request();
+        assert_eq!(
+            call_service(&app, req).await.status(),
+            StatusCode::NOT_FOUND
+        );
+    }
+}
@@ -1,7 +1,14 @@
+use actix_web::http::header::EntityTag;
+use actix_web::http::header::IfMatch;
+use actix_web::http::header::ETAG;
 use actix_web::post;
 use actix_web::web::Data;
 use actix_web::web::Json;
 use actix_web::web::Path;
+use actix_web::FromRequest as _;
+use actix_web::HttpMessage as _;
+use actix_web::HttpRequest;
+use actix_web::HttpResponse;
 use chashmap::CHashMap;
 use editoast_derive::EditoastError;
 use editoast_schemas::infra::ApplicableDirectionsTrackRange;
@@ -36,10 +43,17 @@ use crate::infra_cache::InfraCache;
 use crate::infra_cache::ObjectCache;
 use crate::map;
 use crate::map::MapLayers;
+use crate::modelsv2::infra_edition_history::InfraEditionHistory;
+use crate::modelsv2::infra_edition_history::InfraEditionHistoryChangeset;
+use crate::modelsv2::infra_edition_history::InfraEditionKind;
 use crate::modelsv2::prelude::*;
+use crate::modelsv2::resource_grant::ResourceType;
+use crate::modelsv2::resource_grant::Role;
 use crate::modelsv2::DbConnection;
 use crate::modelsv2::DbConnectionPool;
 use crate::modelsv2::Infra;
+use crate::views::authz::Authentication;
+use crate::views::infra::history::request_author;
 use crate::views::infra::InfraApiError;
 use crate::views::infra::InfraIdParam;
 use crate::RedisClient;
@@ -48,26 +62,54 @@ crate::routes! {
     split_track_section,
 }
 
+/// Extract the infra versions expected by the client from the `If-Match` header
+///
+/// Returns `None` if the header is missing or matches any version (`*`).
+fn expected_versions(req: &HttpRequest) -> Option<Vec<String>> {
+    match req.get_header::<IfMatch>()? {
+        IfMatch::Any => None,
+        IfMatch::Items(tags) => Some(tags.iter().map(|tag| tag.tag().to_owned()).collect()),
+    }
+}
+
 /// CRUD for edit an infrastructure. Takes a batch of operations.
+///
+/// If an `If-Match` header is provided, the batch is rejected unless the infra is at one of the given versions.
+/// The version of the infra after the edition is returned in the `ETag` header.
 #[post("")]
 pub async fn edit<'a>(
+    req: HttpRequest,
     infra: Path<i64>,
     operations: Json<Vec<Operation>>,
     db_pool: Data<DbConnectionPool>,
     infra_caches: Data<CHashMap<i64, InfraCache>>,
     redis_client: Data<RedisClient>,
     map_layers: Data<MapLayers>,
-) -> Result<Json<Vec<RailjsonObject>>> {
+) -> Result<HttpResponse> {
     let infra_id = infra.into_inner();
 
+    let authentication = Authentication::extract(&req).await?;
     let mut conn = db_pool.get().await?;
-    // TODO: lock for update
+    let authorizer = authentication.authorizer(&mut conn).await?;
+    authorizer
+        .check(&mut conn, ResourceType::Infra, infra_id, Role::Editor)
+        .await?;
     let mut infra =
         Infra::retrieve_or_fail(&mut conn, infra_id, || InfraApiError::NotFound { infra_id })
             .await?;
     let mut infra_cache = InfraCache::get_or_load_mut(&mut conn, &infra_caches, &infra).await?;
-    let operation_results =
-        apply_edit(&mut conn, &mut infra, &operations, &mut infra_cache).await?;
+    let history = InfraEditionHistory::changeset()
+        .kind(InfraEditionKind::Edit)
+        .author(request_author(&req));
+    let operation_results = apply_edit(
+        &mut conn,
+        &mut infra,
+        &operations,
+        &mut infra_cache,
+        expected_versions(&req).as_deref(),
+        history,
+    )
+    .await?;
 
     let mut conn = redis_client.get_connection().await?;
     map::invalidate_all(
@@ -77,7 +119,9 @@ pub async fn edit<'a>(
     )
     .await?;
 
-    Ok(Json(operation_results))
+    Ok(HttpResponse::Ok()
+        .insert_header((ETAG, EntityTag::new_strong(infra.version)))
+        .json(operation_results))
 }
 
 #[utoipa::path(
@@ -85,18 +129,20 @@ pub async fn edit<'a>(
     params(InfraIdParam),
     request_body = TrackOffset,
     responses(
-        (status = 200, body = inline(Vec<String>), description = "ID of the trackSections created")
+        (status = 200, body = inline(Vec<String>), description = "ID of the trackSections created"),
+        (status = 412, description = "The infra version does not match the `If-Match` header"),
     ),
 )]
 #[post("/split_track_section")]
 pub async fn split_track_section<'a>(
+    req: HttpRequest,
     infra: Path<i64>,
     payload: Json<TrackOffset>,
     db_pool: Data<DbConnectionPool>,
     infra_caches: Data<CHashMap<i64, InfraCache>>,
     redis_client: Data<RedisClient>,
     map_layers: Data<MapLayers>,
-) -> Result<Json<Vec<String>>> {
+) -> Result<HttpResponse> {
     let payload = payload.into_inner();
     let infra_id = infra.into_inner();
     info!(
@@ -104,7 +150,12 @@ pub async fn split_track_section<'a>(
         offset = payload.offset,
         "Splitting track section"
     );
+    let authentication = Authentication::extract(&req).await?;
     let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check(conn, ResourceType::Infra, infra_id, Role::Editor)
+        .await?;
 
     // Check the infra
     let mut infra =
@@ -289,7 +340,18 @@ pub async fn split_track_section<'a>(
     }));
 
     // Apply operations
-    apply_edit(conn, &mut infra, &operations, &mut infra_cache).await?;
+    let history = InfraEditionHistory::changeset()
+        .kind(InfraEditionKind::Edit)
+        .author(request_author(&req));
+    apply_edit(
+        conn,
+        &mut infra,
+        &operations,
+        &mut infra_cache,
+        expected_versions(&req).as_deref(),
+        history,
+    )
+    .await?;
     let mut conn = redis_client.get_connection().await?;
     map::invalidate_all(
         &mut conn,
@@ -299,13 +361,15 @@ pub async fn split_track_section<'a>(
     .await?;
 
     // Return the result
-    Ok(Json(
-        [
-            left_tracksection_id.to_string(),
-            right_tracksection_id.to_string(),
-        ]
-        .to_vec(),
-    ))
+    Ok(HttpResponse::Ok()
+        .insert_header((ETAG, EntityTag::new_strong(infra.version)))
+        .json(
+            [
+                left_tracksection_id.to_string(),
+                right_tracksection_id.to_string(),
+            ]
+            .to_vec(),
+        ))
 }
 
 /// Function used while splitting a track section.
@@ -792,27 +856,56 @@ fn get_splitted_patch_operations_for_ranges(
     patch_operations
 }
 
-async fn apply_edit(
+/// Apply a batch of operations to an infra and record it in the infra edition history
+///
+/// `history` may carry the metadata of the edition (author, kind, target). The
+/// remaining fields are filled once the operations are applied.
+///
+/// The infra row is locked for the duration of the transaction, so that concurrent
+/// editions are serialized. If `expected_versions` is given, the edition fails unless
+/// the infra is still at one of these versions once locked.
+pub(super) async fn apply_edit(
     connection: &mut DbConnection,
     infra: &mut Infra,
     operations: &[Operation],
     infra_cache: &mut InfraCache,
+    expected_versions: Option<&[String]>,
+    history: InfraEditionHistoryChangeset,
 ) -> Result<Vec<RailjsonObject>> {
     let infra_id = infra.id;
-    // Check if the infra is locked
-    if infra.locked {
-        return Err(EditionError::InfraIsLocked { infra_id }.into());
-    }
 
     // Apply modifications in one transaction
     connection
         .build_transaction()
         .run(|conn| {
             Box::pin(async {
+                // Wait for concurrent editions and reload the infra
+                infra.lock_for_update(conn).await?;
+
+                // Check if the infra is locked
+                if infra.locked {
+                    return Err(EditionError::InfraIsLocked { infra_id }.into());
+                }
+
+                // Check that the infra didn't change since the client last fetched it
+                if let Some(expected_versions) = expected_versions {
+                    if !expected_versions.contains(&infra.version) {
+                        return Err(EditionError::VersionMismatch {
+                            infra_id,
+                            expected_version: expected_versions.join(", "),
+                            version: infra.version.clone(),
+                        }
+                        .into());
+                    }
+                }
+
                 let mut railjsons = vec![];
                 let mut cache_operations = vec![];
+                let mut inverse_operations = vec![];
                 for operation in operations {
-                    let railjson = operation.apply(infra_id, conn).await?;
+                    let (railjson, inverse_operation) =
+                        operation.apply_reversible(infra_id, conn).await?;
+                    inverse_operations.push(inverse_operation);
                     match (operation, railjson) {
                         (Operation::Create(_), Some(railjson)) => {
                             railjsons.push(railjson.clone());
@@ -833,7 +926,19 @@ async fn apply_edit(
                 }
 
                 // Bump version
+                let previous_version = infra.version.clone();
                 infra.bump_version(conn).await?;
+
+                // Record the edition, the last operation being the first to undo
+                inverse_operations.reverse();
+                history
+                    .infra_id(infra_id)
+                    .previous_version(previous_version)
+                    .version(infra.version.clone())
+                    .operations(operations.to_vec())
+                    .inverse_operations(inverse_operations)
+                    .create(conn)
+                    .await?;
                 // Apply operations to infra cache
                 infra_cache.apply_operations(&cache_operations)?;
 
@@ -857,6 +962,16 @@ enum EditionError {
     #[error("Infra {infra_id} is locked")]
     InfraIsLocked { infra_id: i64 },
 
+    #[error(
+        "Infra {infra_id} is at version {version} but version {expected_version} was expected"
+    )]
+    #[editoast_error(status = 412)]
+    VersionMismatch {
+        infra_id: i64,
+        expected_version: String,
+        version: String,
+    },
+
     #[error("Invalid split offset for track section '{tracksection_id}' in infra '{infra_id}'. Expected a value between 0 and {tracksection_length} meters")]
     #[editoast_error(status = 400)]
     SplitTrackSectionBadOffset {
@@ -1018,10 +1133,16 @@ pub mod tests {
             }),
         ]
         .to_vec();
-        let result: Vec<RailjsonObject> =
-            apply_edit(conn, &mut small_infra.model, &operations, &mut infra_cache)
-                .await
-                .unwrap();
+        let result: Vec<RailjsonObject> = apply_edit(
+            conn,
+            &mut small_infra.model,
+            &operations,
+            &mut infra_cache,
+            None,
+            InfraEditionHistory::changeset().kind(InfraEditionKind::Edit),
+        )
+        .await
+        .unwrap();
 
         // Check that the updated track has the new length
         assert_eq!(1234.0, result[0].get_data()["length"]);
@@ -1064,7 +1185,15 @@ pub mod tests {
             }),
         ]
         .to_vec();
-        let result = apply_edit(conn, &mut small_infra.model, &operations, &mut infra_cache).await;
+        let result = apply_edit(
+            conn,
+            &mut small_infra.model,
+            &operations,
+            &mut infra_cache,
+            None,
+            InfraEditionHistory::changeset().kind(InfraEditionKind::Edit),
+        )
+        .await;
 
         // Check that we have an error
         assert!(result.is_err());
@@ -1077,4 +1206,67 @@ pub mod tests {
         let res: Vec<JsonValue> = call_and_read_body_json(&app, req).await;
         assert_eq!(2000.0, res[0]["railjson"]["length"])
     }
+
+    #[rstest]
+    #[case::no_header(None, None)]
+    #[case::any(Some("*"), None)]
+    #[case::strong_tag(Some("\"3\""), Some(vec!["3"]))]
+    #[case::weak_tag(Some("W/\"3\""), Some(vec!["3"]))]
+    #[case::several_tags(Some("\"3\", \"4\""), Some(vec!["3", "4"]))]
+    fn expected_versions_from_if_match_header(
+        #[case] if_match: Option<&str>,
+        #[case] expected: Option<Vec<&str>>,
+    ) {
+        let mut req = TestRequest::default();
+        if let Some(if_match) = if_match {
+            req = req.insert_header(("If-Match", if_match));
+        }
+        let expected = expected.map(|tags| tags.into_iter().map(String::from).collect::<Vec<_>>());
+        assert_eq!(expected_versions(&req.to_http_request()), expected);
+    }
+
+    #[rstest]
+    async fn edit_with_outdated_version_is_rejected() {
+        let pg_db_pool = db_pool();
+        let small_infra = small_infra(pg_db_pool.clone()).await;
+        let app = create_test_service().await;
+        let operation = Operation::Update(UpdateOperation {
+            obj_type: ObjectType::TrackSection,
+            obj_id: "TA0".to_string(),
+            railjson_patch: Patch(vec![PatchOperation::Replace(ReplaceOperation {
+                path: "/length".parse().unwrap(),
+                value: json!(1234),
+            })]),
+        });
+        let version = small_infra.model.version.clone();
+
+        // A first edition at the current version succeeds
+        let req = TestRequest::post()
+            .uri(format!("/infra/{}", small_infra.id()).as_str())
+            .insert_header(("If-Match", format!("\"{version}\"")))
+            .set_json(json!([operation]))
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        let new_version = res.headers().get(ETAG).unwrap().to_str().unwrap();
+        assert_ne!(new_version, format!("\"{version}\""));
+
+        // A second edition based on the same version is rejected
+        let req = TestRequest::post()
+            .uri(format!("/infra/{}", small_infra.id()).as_str())
+            .insert_header(("If-Match", format!("\"{version}\"")))
+            .set_json(json!([operation]))
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+
+        // An edition listing the current version among others succeeds
+        let req = TestRequest::post()
+            .uri(format!("/infra/{}", small_infra.id()).as_str())
+            .insert_header(("If-Match", format!("\"{version}\", {new_version}")))
+            .set_json(json!([operation]))
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
 }
@@ -0,0 +1,548 @@
+use actix_web::get;
+use actix_web::post;
+use actix_web::web::Data;
+use actix_web::web::Json;
+use actix_web::web::Path;
+use actix_web::web::Query;
+use actix_web::FromRequest as _;
+use actix_web::HttpRequest;
+use chashmap::CHashMap;
+use editoast_derive::EditoastError;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+use utoipa::IntoParams;
+use utoipa::ToSchema;
+
+use super::edition::apply_edit;
+use super::InfraApiError;
+use super::InfraIdParam;
+use crate::error::Result;
+use crate::infra_cache::InfraCache;
+use crate::map;
+use crate::map::MapLayers;
+use crate::modelsv2::infra::InfraDiff;
+use crate::modelsv2::infra_edition_history::InfraEditionHistory;
+use crate::modelsv2::infra_edition_history::InfraEditionKind;
+use crate::modelsv2::prelude::*;
+use crate::modelsv2::resource_grant::ResourceType;
+use crate::modelsv2::resource_grant::Role;
+use crate::modelsv2::DbConnectionPool;
+use crate::modelsv2::Infra;
+use crate::views::authz::Authentication;
+use crate::views::authz::REMOTE_USER_HEADER;
+use crate::views::pagination::PaginatedList as _;
+use crate::views::pagination::PaginationQueryParam;
+use crate::views::pagination::PaginationStats;
+use crate::RedisClient;
+
+crate::routes! {
+    "/history" => {
+        list,
+        "/{version}" => {
+            get,
+            revert,
+            redo,
+            edition_diff,
+            version_diff,
+        },
+    },
+}
+
+/// Extract the author of a request from the headers set by the gateway
+pub(super) fn request_author(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(REMOTE_USER_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(|author| author.to_owned())
+}
+
+#[derive(Debug, Error, EditoastError)]
+#[editoast_error(base_id = "infra:history")]
+enum HistoryError {
+    #[error("No edition of infra '{infra_id}' resulted in version '{version}'")]
+    #[editoast_error(status = 404)]
+    NotFound { infra_id: i64, version: String },
+    #[error("The edition of infra '{infra_id}' resulting in version '{version}' was not recorded")]
+    #[editoast_error(status = 500)]
+    EditionNotRecorded { infra_id: i64, version: String },
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+struct InfraVersionParam {
+    /// An existing infra ID
+    infra_id: i64,
+    /// The infra version resulting from the edition
+    version: String,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+struct InfraVersionsParam {
+    /// An existing infra ID
+    infra_id: i64,
+    /// The base version of the infra
+    version: String,
+    /// The version of the infra to compare the base version with
+    other_version: String,
+}
+
+#[derive(Serialize, ToSchema)]
+struct InfraEditionHistoryListResponse {
+    results: Vec<InfraEditionHistory>,
+    #[serde(flatten)]
+    stats: PaginationStats,
+}
+
+/// Returns the paginated edition history of an infra, most recent first
+#[utoipa::path(
+    tag = "infra",
+    params(InfraIdParam, PaginationQueryParam),
+    responses(
+        (status = 200, body = inline(InfraEditionHistoryListResponse), description = "The edition history of the infra"),
+        (status = 404, description = "The infra was not found"),
+    )
+)]
+#[get("")]
+async fn list(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    infra: Path<InfraIdParam>,
+    Query(pagination_params): Query<PaginationQueryParam>,
+) -> Result<Json<InfraEditionHistoryListResponse>> {
+    let infra_id = infra.infra_id;
+    let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check(conn, ResourceType::Infra, infra_id, Role::Viewer)
+        .await?;
+    if !Infra::exists(conn, infra_id).await? {
+        return Err(InfraApiError::NotFound { infra_id }.into());
+    }
+
+    let settings = pagination_params
+        .validate(1000)?
+        .warn_page_size(100)
+        .into_selection_settings()
+        .filter(move || InfraEditionHistory::INFRA_ID.eq(infra_id))
+        .order_by(|| InfraEditionHistory::ID.desc());
+    let (results, stats) = InfraEditionHistory::list_paginated(conn, settings).await?;
+    Ok(Json(InfraEditionHistoryListResponse { results, stats }))
+}
+
+/// Returns the edition of an infra which resulted in the given version
+///
+/// The `operations` of the edition describe the changes from `previous_version`
+/// to `version` and its `inverse_operations` the changes the other way around.
+#[utoipa::path(
+    tag = "infra",
+    params(InfraVersionParam),
+    responses(
+        (status = 200, body = InfraEditionHistory, description = "The edition"),
+        (status = 404, description = "The infra or the edition was not found"),
+    )
+)]
+#[get("")]
+async fn get(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    path: Path<InfraVersionParam>,
+) -> Result<Json<InfraEditionHistory>> {
+    let InfraVersionParam { infra_id, version } = path.into_inner();
+    let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check(conn, ResourceType::Infra, infra_id, Role::Viewer)
+        .await?;
+    let edition = InfraEditionHistory::retrieve_version(conn, infra_id, version.clone())
+        .await?
+        .ok_or(HistoryError::NotFound { infra_id, version })?;
+    Ok(Json(edition))
+}
+
+/// Reverts an edition of an infra by applying its inverse operations
+///
+/// The revert is recorded as a new edition in the history of the infra.
+#[utoipa::path(
+    tag = "infra",
+    params(InfraVersionParam),
+    responses(
+        (status = 200, body = InfraEditionHistory, description = "The edition created by the revert"),
+        (status = 404, description = "The infra or the edition was not found"),
+    )
+)]
+#[post("/revert")]
+async fn revert(
+    req: HttpRequest,
+    path: Path<InfraVersionParam>,
+    db_pool: Data<DbConnectionPool>,
+    infra_caches: Data<CHashMap<i64, InfraCache>>,
+    redis_client: Data<RedisClient>,
+    map_layers: Data<MapLayers>,
+) -> Result<Json<InfraEditionHistory>> {
+    replay(
+        req,
+        path.into_inner(),
+        InfraEditionKind::Revert,
+        db_pool,
+        infra_caches,
+        redis_client,
+        map_layers,
+    )
+    .await
+}
+
+/// Applies again the operations of an edition of an infra, usually after it was reverted
+///
+/// The redo is recorded as a new edition in the history of the infra.
+#[utoipa::path(
+    tag = "infra",
+    params(InfraVersionParam),
+    responses(
+        (status = 200, body = InfraEditionHistory, description = "The edition created by the redo"),
+        (status = 404, description = "The infra or the edition was not found"),
+    )
+)]
+#[post("/redo")]
+async fn redo(
+    req: HttpRequest,
+    path: Path<InfraVersionParam>,
+    db_pool: Data<DbConnectionPool>,
+    infra_caches: Data<CHashMap<i64, InfraCache>>,
+    redis_client: Data<RedisClient>,
+    map_layers: Data<MapLayers>,
+) -> Result<Json<InfraEditionHistory>> {
+    replay(
+        req,
+        path.into_inner(),
+        InfraEditionKind::Redo,
+        db_pool,
+        infra_caches,
+        redis_client,
+        map_layers,
+    )
+    .await
+}
+
+/// Apply the operations (or inverse operations) of a recorded edition as a new edition
+async fn replay(
+    req: HttpRequest,
+    InfraVersionParam { infra_id, version }: InfraVersionParam,
+    kind: InfraEditionKind,
+    db_pool: Data<DbConnectionPool>,
+    infra_caches: Data<CHashMap<i64, InfraCache>>,
+    redis_client: Data<RedisClient>,
+    map_layers: Data<MapLayers>,
+) -> Result<Json<InfraEditionHistory>> {
+    let authentication = Authentication::extract(&req).await?;
+    let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check(conn, ResourceType::Infra, infra_id, Role::Editor)
+        .await?;
+    let mut infra =
+        Infra::retrieve_or_fail(conn, infra_id, || InfraApiError::NotFound { infra_id }).await?;
+    let target = InfraEditionHistory::retrieve_version(conn, infra_id, version.clone())
+        .await?
+        .ok_or(HistoryError::NotFound { infra_id, version })?;
+    let operations = match kind {
+        InfraEditionKind::Revert => &target.inverse_operations,
+        _ => &target.operations,
+    };
+
+    let mut infra_cache = InfraCache::get_or_load_mut(conn, &infra_caches, &infra).await?;
+    let history = InfraEditionHistory::changeset()
+        .kind(kind)
+        .target_id(Some(target.id))
+        .author(request_author(&req));
+    apply_edit(
+        conn,
+        &mut infra,
+        operations,
+        &mut infra_cache,
+        None,
+        history,
+    )
+    .await?;
+
+    let mut redis_conn = redis_client.get_connection().await?;
+    map::invalidate_all(
+        &mut redis_conn,
+        &map_layers.layers.keys().cloned().collect(),
+        infra_id,
+    )
+    .await?;
+
+    let edition = InfraEditionHistory::retrieve_version(conn, infra_id, infra.version.clone())
+        .await?
+        .ok_or(HistoryError::EditionNotRecorded {
+            infra_id,
+            version: infra.version,
+        })?;
+    Ok(Json(edition))
+}
+
+/// Compare the objects touched by an edition of an infra before and after it was applied
+///
+/// Modified objects come with the JSON patch turning the object before the edition
+/// into the object after it.
+#[utoipa::path(
+    tag = "infra",
+    params(InfraVersionParam),
+    responses(
+        (status = 200, body = InfraDiff, description = "The objects created, deleted and modified by the edition"),
+        (status = 404, description = "The infra or the edition was not found"),
+    )
+)]
+#[get("/diff")]
+async fn edition_diff(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    path: Path<InfraVersionParam>,
+) -> Result<Json<InfraDiff>> {
+    let InfraVersionParam { infra_id, version } = path.into_inner();
+    let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check(conn, ResourceType::Infra, infra_id, Role::Viewer)
+        .await?;
+    let edition = InfraEditionHistory::retrieve_version(conn, infra_id, version.clone())
+        .await?
+        .ok_or(HistoryError::NotFound { infra_id, version })?;
+    let diff =
+        InfraEditionHistory::diff_history(conn, infra_id, edition.id - 1, edition.id).await?;
+    Ok(Json(diff))
+}
+
+/// Compare the objects of an infra between two of its versions
+///
+/// The versions are located in the edition history of the infra. Modified objects come
+/// with the JSON patch turning the object in the base version into the one in the other version.
+#[utoipa::path(
+    tag = "infra",
+    params(InfraVersionsParam),
+    responses(
+        (status = 200, body = InfraDiff, description = "The objects created, deleted and modified in the other version"),
+        (status = 404, description = "The infra or one of the versions was not found in the history"),
+    )
+)]
+#[get("/diff/{other_version}")]
+async fn version_diff(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    path: Path<InfraVersionsParam>,
+) -> Result<Json<InfraDiff>> {
+    let InfraVersionsParam {
+        infra_id,
+        version,
+        other_version,
+    } = path.into_inner();
+    let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check(conn, ResourceType::Infra, infra_id, Role::Viewer)
+        .await?;
+    let mut points = Vec::with_capacity(2);
+    for version in [version, other_version] {
+        let point = InfraEditionHistory::history_point(conn, infra_id, version.clone())
+            .await?
+            .ok_or(HistoryError::NotFound { infra_id, version })?;
+        points.push(point);
+    }
+    let diff = InfraEditionHistory::diff_history(conn, infra_id, points[0], points[1]).await?;
+    Ok(Json(diff))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    use actix_web::test::call_and_read_body_json;
+    use actix_web::test::call_service;
+    use actix_web::test::TestRequest;
+    use editoast_schemas::primitives::ObjectType;
+    use json_patch::Patch;
+    use json_patch::PatchOperation;
+    use json_patch::ReplaceOperation;
+    use rstest::rstest;
+    use serde_json::json;
+    use serde_json::Value as JsonValue;
+
+    use super::*;
+    use crate::fixtures::tests::db_pool;
+    use crate::fixtures::tests::small_infra;
+    use crate::infra_cache::operation::Operation;
+    use crate::infra_cache::operation::UpdateOperation;
+    use crate::views::tests::create_test_service;
+
+    fn track_length_update(length: f64) -> Operation {
+        Operation::Update(UpdateOperation {
+            obj_type: ObjectType::TrackSection,
+            obj_id: "TA0".to_string(),
+            railjson_patch: Patch(vec![PatchOperation::Replace(ReplaceOperation {
+                path: "/length".parse().unwrap(),
+                value: json!(length),
+            })]),
+        })
+    }
+
+    fn get_track_request(infra_id: i64) -> actix_http::Request {
+        TestRequest::post()
+            .uri(format!("/infra/{infra_id}/objects/TrackSection").as_str())
+            .set_json(json!(["TA0"]))
+            .to_request()
+    }
+
+    #[rstest]
+    async fn edition_is_recorded_and_reverted() {
+        let app = create_test_service().await;
+        let small_infra = small_infra(db_pool()).await;
+        let infra_id = small_infra.id();
+
+        let req = TestRequest::post()
+            .uri(format!("/infra/{infra_id}").as_str())
+            .insert_header((REMOTE_USER_HEADER, "jdoe"))
+            .set_json(json!([track_length_update(1234.)]))
+            .to_request();
+        assert_eq!(call_service(&app, req).await.status(), StatusCode::OK);
+
+        let req = TestRequest::get()
+            .uri(format!("/infra/{infra_id}/history").as_str())
+            .to_request();
+        let history: JsonValue = call_and_read_body_json(&app, req).await;
+        assert_eq!(history["count"], 1);
+        let edition: InfraEditionHistory =
+            serde_json::from_value(history["results"][0].clone()).unwrap();
+        assert_eq!(edition.author.as_deref(), Some("jdoe"));
+        assert_eq!(edition.kind, InfraEditionKind::Edit);
+        assert_eq!(edition.operations, vec![track_length_update(1234.)]);
+        assert_eq!(edition.inverse_operations, vec![track_length_update(2000.)]);
+        let tracks: Vec<JsonValue> =
+            call_and_read_body_json(&app, get_track_request(infra_id)).await;
+        assert_eq!(tracks[0]["railjson"]["length"], 1234.);
+
+        let req = TestRequest::post()
+            .uri(format!("/infra/{infra_id}/history/{}/revert", edition.version).as_str())
+            .to_request();
+        let reverted: InfraEditionHistory = call_and_read_body_json(&app, req).await;
+        assert_eq!(reverted.kind, InfraEditionKind::Revert);
+        assert_eq!(reverted.target_id, Some(edition.id));
+        assert_eq!(reverted.previous_version, edition.version);
+        let tracks: Vec<JsonValue> =
+            call_and_read_body_json(&app, get_track_request(infra_id)).await;
+        assert_eq!(tracks[0]["railjson"]["length"], 2000.);
+
+        let req = TestRequest::post()
+            .uri(format!("/infra/{infra_id}/history/{}/redo", edition.version).as_str())
+            .to_request();
+        let redone: InfraEditionHistory = call_and_read_body_json(&app, req).await;
+        assert_eq!(redone.kind, InfraEditionKind::Redo);
+        let tracks: Vec<JsonValue> =
+            call_and_read_body_json(&app, get_track_request(infra_id)).await;
+        assert_eq!(tracks[0]["railjson"]["length"], 1234.);
+    }
+
+    #[rstest]
+    async fn edition_diff_compares_objects_before_and_after() {
+        let app = create_test_service().await;
+        let small_infra = small_infra(db_pool()).await;
+        let infra_id = small_infra.id();
+
+        let mut versions = vec![];
+        for length in [1234., 1500.] {
+            let req = TestRequest::post()
+                .uri(format!("/infra/{infra_id}").as_str())
+                .set_json(json!([track_length_update(length)]))
+                .to_request();
+            assert_eq!(call_service(&app, req).await.status(), StatusCode::OK);
+            let req = TestRequest::get()
+                .uri(format!("/infra/{infra_id}/history").as_str())
+                .to_request();
+            let history: JsonValue = call_and_read_body_json(&app, req).await;
+            versions.push(
+                history["results"][0]["version"]
+                    .as_str()
+                    .unwrap()
+                    .to_owned(),
+            );
+        }
+
+        let req = TestRequest::get()
+            .uri(format!("/infra/{infra_id}/history/{}/diff", versions[0]).as_str())
+            .to_request();
+        let diff: InfraDiff = call_and_read_body_json(&app, req).await;
+        assert!(diff.created.is_empty());
+        assert!(diff.deleted.is_empty());
+        assert_eq!(diff.modified.len(), 1);
+        assert_eq!(diff.modified[0].obj_ref.obj_id, "TA0");
+        assert_eq!(
+            diff.modified[0].changes,
+            Patch(vec![PatchOperation::Replace(ReplaceOperation {
+                path: "/length".parse().unwrap(),
+                value: json!(1234.),
+            })])
+        );
+    }
+
+    #[rstest]
+    async fn version_diff_spans_several_editions() {
+        let app = create_test_service().await;
+        let small_infra = small_infra(db_pool()).await;
+        let infra_id = small_infra.id();
+        let initial_version = small_infra.model.version.clone();
+
+        for length in [1234., 1500.] {
+            let req = TestRequest::post()
+                .uri(format!("/infra/{infra_id}").as_str())
+                .set_json(json!([track_length_update(length)]))
+                .to_request();
+            assert_eq!(call_service(&app, req).await.status(), StatusCode::OK);
+        }
+        let req = TestRequest::get()
+            .uri(format!("/infra/{infra_id}/history").as_str())
+            .to_request();
+        let history: JsonValue = call_and_read_body_json(&app, req).await;
+        let last_version = history["results"][0]["version"].as_str().unwrap();
+
+        let req = TestRequest::get()
+            .uri(
+                format!("/infra/{infra_id}/history/{initial_version}/diff/{last_version}").as_str(),
+            )
+            .to_request();
+        let diff: InfraDiff = call_and_read_body_json(&app, req).await;
+        assert_eq!(diff.modified.len(), 1);
+        assert_eq!(
+            diff.modified[0].changes,
+            Patch(vec![PatchOperation::Replace(ReplaceOperation {
+                path: "/length".parse().unwrap(),
+                value: json!(1500.),
+            })])
+        );
+
+        let req = TestRequest::get()
+            .uri(
+                format!("/infra/{infra_id}/history/{last_version}/diff/{initial_version}").as_str(),
+            )
+            .to_request();
+        let diff: InfraDiff = call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            diff.modified[0].changes,
+            Patch(vec![PatchOperation::Replace(ReplaceOperation {
+                path: "/length".parse().unwrap(),
+                value: json!(2000.),
+            })])
+        );
+    }
+
+    #[rstest]
+    async fn unknown_version_is_not_found() {
+        let app = create_test_service().await;
+        let small_infra = small_infra(db_pool()).await;
+
+        let req = TestRequest::get()
+            .uri(format!("/infra/{}/history/42", small_infra.id()).as_str())
+            .to_request();
+        assert_eq!(
+            call_service(&app, req).await.status(),
+            StatusCode::NOT_FOUND
+        );
+    }
+}
@@ -1,7 +1,9 @@
 mod attached;
 mod auto_fixes;
+mod diff;
 mod edition;
 mod errors;
+mod history;
 mod lines;
 mod objects;
 mod pathfinding;
@@ -23,9 +25,12 @@ use actix_web::HttpResponse;
 use actix_web::Responder;
 use chashmap::CHashMap;
 use editoast_derive::EditoastError;
+use futures::channel::mpsc;
 use serde::Deserialize;
 use serde::Serialize;
 use std::collections::HashMap;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use thiserror::Error;
 use utoipa::IntoParams;
@@ -40,17 +45,22 @@ use crate::core::infra_state::InfraStateResponse;
 use crate::core::AsCoreRequest;
 use crate::core::CoreClient;
 use crate::error::Result;
+use crate::generated_data;
 use crate::infra_cache::InfraCache;
 use crate::infra_cache::ObjectCache;
 use crate::map;
 use crate::map::MapLayers;
 use crate::models::List as ModelList;
-use crate::models::NoParams;
 use crate::modelsv2::prelude::*;
+use crate::modelsv2::resource_grant::ResourceType;
+use crate::modelsv2::resource_grant::Role;
 use crate::modelsv2::DbConnectionPool;
 use crate::modelsv2::Infra;
+use crate::views::authz::Authentication;
 use crate::views::pagination::PaginatedResponse;
 use crate::views::pagination::PaginationQueryParam;
+use crate::views::sse;
+use crate::views::sse::Event;
 use crate::RedisClient;
 use editoast_schemas::infra::SwitchType;
 
@@ -64,6 +74,8 @@ crate::routes! {
                 pathfinding::routes(),
                 attached::routes(),
                 edition::routes(),
+                history::routes(),
+                diff::routes(),
             ),
             get,
             load,
@@ -94,6 +106,7 @@ pub fn infra_routes() -> impl HttpServiceFactory {
             list,
             create,
             refresh,
+            refresh_stream,
             cache_status,
             get_all_voltages,
             railjson::railjson_routes(),
@@ -169,13 +182,87 @@ async fn refresh(
     infra_caches: Data<CHashMap<i64, InfraCache>>,
     map_layers: Data<MapLayers>,
 ) -> Result<Json<RefreshResponse>> {
-    let mut conn = db_pool.get().await?;
-    // Use a transaction to give scope to infra list lock
-    let RefreshQueryParams {
+    let infra_refreshed = refresh_infras(
+        db_pool.into_inner(),
+        redis_client.into_inner(),
+        infra_caches.into_inner(),
+        map_layers.into_inner(),
+        query_params,
+        &|_| (),
+    )
+    .await?;
+    Ok(Json(RefreshResponse { infra_refreshed }))
+}
+
+#[derive(Debug, Serialize)]
+struct LayerProgress {
+    infra_id: i64,
+    layer: &'static str,
+    /// The number of layers of the infra generated so far
+    generated: usize,
+    total: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct InfraRefreshed {
+    infra_id: i64,
+    /// False if the generated data were already up to date
+    refreshed: bool,
+}
+
+/// Refresh infra generated data, streaming the progress as server-sent events
+///
+/// Events:
+/// - `layer`: a layer of an infra was generated
+/// - `infra`: an infra was processed
+/// - `error`: the refresh failed, no event follows
+/// - `end`: all the infras were processed, with the ids of the refreshed ones
+///
+/// The refresh goes on if the client disconnects.
+#[post("/refresh/stream")]
+async fn refresh_stream(
+    db_pool: Data<DbConnectionPool>,
+    redis_client: Data<RedisClient>,
+    Query(query_params): Query<RefreshQueryParams>,
+    infra_caches: Data<CHashMap<i64, InfraCache>>,
+    map_layers: Data<MapLayers>,
+) -> HttpResponse {
+    let (sender, receiver) = mpsc::unbounded();
+    actix_web::rt::spawn(async move {
+        let send = |event| {
+            // The client may be gone, which doesn't stop the refresh
+            let _ = sender.unbounded_send(event);
+        };
+        let outcome = refresh_infras(
+            db_pool.into_inner(),
+            redis_client.into_inner(),
+            infra_caches.into_inner(),
+            map_layers.into_inner(),
+            query_params,
+            &send,
+        )
+        .await;
+        send(match outcome {
+            Ok(infra_refreshed) => Event::new("end", &RefreshResponse { infra_refreshed }),
+            Err(error) => Event::new("error", &error),
+        });
+    });
+    sse::stream_events(receiver)
+}
+
+/// Refreshes the generated data of infras, returns the ids of the refreshed ones
+async fn refresh_infras(
+    db_pool: Arc<DbConnectionPool>,
+    redis_client: Arc<RedisClient>,
+    infra_caches: Arc<CHashMap<i64, InfraCache>>,
+    map_layers: Arc<MapLayers>,
+    RefreshQueryParams {
         force,
         infras: List(infras),
-    } = query_params;
-
+    }: RefreshQueryParams,
+    on_event: &(dyn Fn(Event) + Sync),
+) -> Result<Vec<i64>> {
+    let mut conn = db_pool.get().await?;
     let infras_list = if infras.is_empty() {
         // Retrieve all available infra
         Infra::all(&mut conn).await
@@ -188,14 +275,35 @@ async fn refresh(
     };
 
     // Refresh each infras
-    let db_pool = db_pool.into_inner();
     let mut infra_refreshed = vec![];
-
     for mut infra in infras_list {
+        let infra_id = infra.id;
         let infra_cache = InfraCache::get_or_load(&mut conn, &infra_caches, &infra).await?;
-        if infra.refresh(db_pool.clone(), force, &infra_cache).await? {
-            infra_refreshed.push(infra.id);
+        let generated = AtomicUsize::new(0);
+        let on_layer = |layer| {
+            on_event(Event::new(
+                "layer",
+                &LayerProgress {
+                    infra_id,
+                    layer,
+                    generated: generated.fetch_add(1, Ordering::Relaxed) + 1,
+                    total: generated_data::LAYER_COUNT,
+                },
+            ))
+        };
+        let refreshed = infra
+            .refresh_with_progress(db_pool.clone(), force, &infra_cache, &on_layer)
+            .await?;
+        if refreshed {
+            infra_refreshed.push(infra_id);
         }
+        on_event(Event::new(
+            "infra",
+            &InfraRefreshed {
+                infra_id,
+                refreshed,
+            },
+        ));
     }
 
     let mut conn = redis_client.get_connection().await?;
@@ -207,8 +315,7 @@ async fn refresh(
         )
         .await?;
     }
-
-    Ok(Json(RefreshResponse { infra_refreshed }))
+    Ok(infra_refreshed)
 }
 
 /// Return a list of infras
@@ -216,6 +323,7 @@ async fn refresh(
 async fn list(
     db_pool: Data<DbConnectionPool>,
     core: Data<CoreClient>,
+    authentication: Authentication,
     pagination_params: Query<PaginationQueryParam>,
 ) -> Result<Json<PaginatedResponse<InfraWithState>>> {
     let (page, per_page) = pagination_params
@@ -223,7 +331,12 @@ async fn list(
         .warn_page_size(100)
         .unpack();
     let db_pool = db_pool.into_inner();
-    let infras = Infra::list(db_pool.clone(), page, per_page, NoParams).await?;
+    let infras = {
+        let conn = &mut db_pool.get().await?;
+        let authorizer = authentication.authorizer(conn).await?;
+        let hidden = authorizer.hidden(conn, ResourceType::Infra).await?;
+        Infra::list_conn(conn, page, per_page, hidden).await?
+    };
     let infra_state = call_core_infra_state(None, db_pool, core).await?;
     let infras_with_state: Vec<InfraWithState> = infras
         .results
@@ -289,11 +402,16 @@ struct InfraIdParam {
 #[get("")]
 async fn get(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     infra: Path<InfraIdParam>,
     core: Data<CoreClient>,
 ) -> Result<Json<InfraWithState>> {
     let infra_id = infra.infra_id;
     let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check(conn, ResourceType::Infra, infra_id, Role::Viewer)
+        .await?;
     let infra =
         Infra::retrieve_or_fail(conn, infra_id, || InfraApiError::NotFound { infra_id }).await?;
     let infra_state = call_core_infra_state(Some(infra_id), db_pool.into_inner(), core).await?;
@@ -306,10 +424,18 @@ async fn get(
 
 /// Create an infra
 #[post("")]
-async fn create(db_pool: Data<DbConnectionPool>, data: Json<InfraForm>) -> Result<impl Responder> {
+async fn create(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    data: Json<InfraForm>,
+) -> Result<impl Responder> {
     let infra: Changeset<Infra> = data.into_inner().into();
     let conn = &mut db_pool.get().await?;
-    let infra = infra.create(conn).await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    let infra = infra.owner(authorizer.owner_uuid()).create(conn).await?;
+    authorizer
+        .grant_owner(conn, ResourceType::Infra, infra.id)
+        .await?;
     Ok(HttpResponse::Created().json(infra))
 }
 
@@ -333,14 +459,22 @@ struct CloneQuery {
 async fn clone(
     params: Path<InfraIdParam>,
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     Query(CloneQuery { name }): Query<CloneQuery>,
 ) -> Result<Json<i64>> {
     let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check(conn, ResourceType::Infra, params.infra_id, Role::Viewer)
+        .await?;
     let infra = Infra::retrieve_or_fail(conn, params.infra_id, || InfraApiError::NotFound {
         infra_id: params.infra_id,
     })
     .await?;
     let cloned_infra = infra.clone(db_pool.into_inner(), name).await?;
+    authorizer
+        .grant_owner(conn, ResourceType::Infra, cloned_infra.id)
+        .await?;
     Ok(Json(cloned_infra.id))
 }
 
@@ -365,10 +499,15 @@ async fn clone(
 async fn delete(
     infra: Path<InfraIdParam>,
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     infra_caches: Data<CHashMap<i64, InfraCache>>,
 ) -> Result<HttpResponse> {
     let infra_id = infra.infra_id;
     let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check(conn, ResourceType::Infra, infra_id, Role::Owner)
+        .await?;
     if Infra::delete_static(conn, infra_id).await? {
         infra_caches.remove(&infra_id);
         Ok(HttpResponse::NoContent().finish())
@@ -402,11 +541,16 @@ impl From<InfraPatchForm> for Changeset<Infra> {
 #[put("")]
 async fn put(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     infra: Path<i64>,
     Json(patch): Json<InfraPatchForm>,
 ) -> Result<Json<Infra>> {
     let infra_cs: Changeset<Infra> = patch.into();
     let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check(conn, ResourceType::Infra, *infra, Role::Editor)
+        .await?;
     let infra = infra_cs
         .update_or_fail(conn, *infra, || InfraApiError::NotFound {
             infra_id: *infra,
@@ -539,7 +683,18 @@ async fn set_locked(infra_id: i64, locked: bool, db_pool: Arc<DbConnectionPool>)
     )
 )]
 #[post("/lock")]
-async fn lock(infra: Path<InfraIdParam>, db_pool: Data<DbConnectionPool>) -> Result<HttpResponse> {
+async fn lock(
+    infra: Path<InfraIdParam>,
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+) -> Result<HttpResponse> {
+    {
+        let conn = &mut db_pool.get().await?;
+        let authorizer = authentication.authorizer(conn).await?;
+        authorizer
+            .check(conn, ResourceType::Infra, infra.infra_id, Role::Editor)
+            .await?;
+    }
     set_locked(infra.infra_id, true, db_pool.into_inner()).await?;
     Ok(HttpResponse::NoContent().finish())
 }
@@ -557,7 +712,15 @@ async fn lock(infra: Path<InfraIdParam>, db_pool: Data<DbConnectionPool>) -> Res
 async fn unlock(
     infra: Path<InfraIdParam>,
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
 ) -> Result<HttpResponse> {
+    {
+        let conn = &mut db_pool.get().await?;
+        let authorizer = authentication.authorizer(conn).await?;
+        authorizer
+            .check(conn, ResourceType::Infra, infra.infra_id, Role::Editor)
+            .await?;
+    }
     set_locked(infra.infra_id, false, db_pool.into_inner()).await?;
     Ok(HttpResponse::NoContent().finish())
 }
@@ -714,7 +877,7 @@ pub mod tests {
         let conn = &mut db_pool.get().await.unwrap();
         let infra_cache = InfraCache::load(conn, &small_infra).await.unwrap();
 
-        generated_data::refresh_all(db_pool.clone(), small_infra_id, &infra_cache)
+        generated_data::refresh_all(db_pool.clone(), small_infra_id, &infra_cache, &|_| ())
             .await
             .unwrap();
 
@@ -903,6 +1066,38 @@ pub mod tests {
         assert!(refreshed_infras.infra_refreshed.contains(&empty_infra.id()));
     }
 
+    #[rstest] // Slow test
+    async fn infra_refresh_stream(#[future] empty_infra: TestFixture<Infra>) {
+        let empty_infra = empty_infra.await;
+        let app = create_test_service().await;
+
+        let req = TestRequest::post()
+            .uri(
+                format!(
+                    "/infra/refresh/stream?infras={}&force=true",
+                    empty_infra.id()
+                )
+                .as_str(),
+            )
+            .to_request();
+        let response = call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = actix_test::read_body(response).await;
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert_eq!(
+            body.matches("event: layer\n").count(),
+            generated_data::LAYER_COUNT
+        );
+        assert!(body.contains(&format!(
+            "event: infra\ndata: {{\"infra_id\":{},\"refreshed\":true}}",
+            empty_infra.id()
+        )));
+        assert!(body.ends_with(&format!(
+            "event: end\ndata: {{\"infra_refreshed\":[{}]}}\n\n",
+            empty_infra.id()
+        )));
+    }
+
     #[rstest]
     async fn infra_get_speed_limit_tags(#[future] empty_infra: TestFixture<Infra>) {
         let empty_infra = empty_infra.await;
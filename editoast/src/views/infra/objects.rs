@@ -11,8 +11,11 @@ use thiserror::Error;
 
 use crate::error::Result;
 use crate::modelsv2::infra::ObjectQueryable;
+use crate::modelsv2::resource_grant::ResourceType;
+use crate::modelsv2::resource_grant::Role;
 use crate::modelsv2::DbConnectionPool;
 use crate::modelsv2::Infra;
+use crate::views::authz::Authentication;
 use crate::views::infra::InfraApiError;
 use crate::Retrieve;
 use editoast_schemas::primitives::ObjectType;
@@ -42,6 +45,7 @@ async fn get_objects(
     path_params: Path<(i64, ObjectType)>,
     obj_ids: Json<Vec<String>>,
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
 ) -> Result<Json<Vec<ObjectQueryable>>> {
     let (infra_id, obj_type) = path_params.into_inner();
     if !has_unique_ids(&obj_ids) {
@@ -49,6 +53,10 @@ async fn get_objects(
     }
 
     let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check(conn, ResourceType::Infra, infra_id, Role::Viewer)
+        .await?;
     let infra =
         Infra::retrieve_or_fail(conn, infra_id, || InfraApiError::NotFound { infra_id }).await?;
     let obj_ids = obj_ids.into_inner();
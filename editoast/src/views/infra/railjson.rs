@@ -1,32 +1,44 @@
+use std::io;
+use std::io::Read;
+use std::sync::Arc;
+
 use actix_web::dev::HttpServiceFactory;
 use actix_web::get;
 use actix_web::http::header::ContentType;
 use actix_web::post;
 use actix_web::services;
+use actix_web::web::Bytes;
 use actix_web::web::Data;
 use actix_web::web::Json;
 use actix_web::web::Path;
+use actix_web::web::Payload;
 use actix_web::web::Query;
 use actix_web::HttpResponse;
 use actix_web::Responder;
 use chashmap::CHashMap;
-use editoast_derive::EditoastError;
-use editoast_schemas::infra::RailJson;
-use editoast_schemas::infra::RAILJSON_VERSION;
-use enum_map::EnumMap;
-use futures::future::try_join_all;
+use editoast_schemas::infra::RailJsonMigrationReport;
+use futures::stream;
+use futures::StreamExt;
 use serde::Deserialize;
 use serde::Serialize;
 use strum::IntoEnumIterator;
-use thiserror::Error;
+use tokio::sync::mpsc;
+use tracing::debug;
+use tracing::error;
 use utoipa::IntoParams;
 use utoipa::ToSchema;
 
+use crate::error::InternalError;
 use crate::error::Result;
 use crate::infra_cache::InfraCache;
 use crate::modelsv2::prelude::*;
+use crate::modelsv2::railjson::railjson_field;
+use crate::modelsv2::railjson::RailJsonImportProgress;
+use crate::modelsv2::resource_grant::ResourceType;
+use crate::modelsv2::resource_grant::Role;
 use crate::modelsv2::DbConnectionPool;
 use crate::modelsv2::Infra;
+use crate::views::authz::Authentication;
 use crate::views::infra::InfraApiError;
 use crate::views::infra::InfraIdParam;
 use editoast_schemas::primitives::ObjectType;
@@ -41,14 +53,15 @@ crate::routes! {
     post_railjson,
 }
 
-#[derive(Debug, Error, EditoastError)]
-#[editoast_error(base_id = "infra:railjson")]
-enum ListErrorsRailjson {
-    #[error("Wrong Railjson version provided")]
-    WrongRailjsonVersionProvided,
-}
+/// Number of objects fetched at once when exporting an infra
+const EXPORT_PAGE_SIZE: i64 = 1000;
+
+/// Number of payload chunks buffered ahead of the reading of an imported railjson
+const IMPORT_PAYLOAD_BUFFER: usize = 16;
 
 /// Serialize an infra
+///
+/// The document is streamed: objects are fetched and sent page by page.
 #[utoipa::path(
     tag = "infra",
     params(InfraIdParam),
@@ -61,71 +74,102 @@ enum ListErrorsRailjson {
 async fn get_railjson(
     infra: Path<InfraIdParam>,
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
 ) -> Result<impl Responder> {
     let infra_id = infra.infra_id;
     let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check(conn, ResourceType::Infra, infra_id, Role::Viewer)
+        .await?;
     let infra_meta =
         Infra::retrieve_or_fail(conn, infra_id, || InfraApiError::NotFound { infra_id }).await?;
 
-    let futures: Vec<_> = ObjectType::iter()
-        .map(|object_type| (object_type, db_pool.get()))
-        .map(|(object_type, conn_future)| async move {
-            let conn = &mut conn_future.await?;
-            let railjson_data = Infra::get_railjson(conn, infra_id, &object_type).await?;
-            let result: Result<_> = Ok((object_type, railjson_data));
-            result
-        })
-        .collect();
-
-    // TODO: we could map the objects in the async loop above, so we can start processing some objects
-    // even if we didn’t get everything back yet
-    let res: EnumMap<_, _> = try_join_all(futures)
-        .await?
-        .into_iter()
-        .map(|(obj_type, objects)| {
-            let obj_list = objects
-                .into_iter()
-                .map(|obj| obj.railjson)
-                .collect::<Vec<_>>()
-                .join(",");
-            (obj_type, format!("[{obj_list}]"))
-        })
-        .collect();
-
-    // Here we avoid the deserialization of the whole RailJson object
-    let railjson = format!(
-        r#"{{
-            "version": "{version}",
-            "track_sections": {track_sections},
-            "signals": {signals},
-            "speed_sections": {speed_sections},
-            "detectors": {detectors},
-            "switches": {switches},
-            "extended_switch_types": {switch_types},
-            "buffer_stops": {buffer_stops},
-            "routes": {routes},
-            "operational_points": {operational_points},
-            "electrifications": {electrifications},
-            "neutral_sections": {neutral_sections}
-        }}"#,
-        version = infra_meta.railjson_version,
-        track_sections = res[ObjectType::TrackSection],
-        signals = res[ObjectType::Signal],
-        speed_sections = res[ObjectType::SpeedSection],
-        detectors = res[ObjectType::Detector],
-        switches = res[ObjectType::Switch],
-        switch_types = res[ObjectType::SwitchType],
-        buffer_stops = res[ObjectType::BufferStop],
-        routes = res[ObjectType::Route],
-        operational_points = res[ObjectType::OperationalPoint],
-        electrifications = res[ObjectType::Electrification],
-        neutral_sections = res[ObjectType::NeutralSection]
-    );
+    let export = RailJsonExport {
+        db_pool: db_pool.into_inner(),
+        infra_id,
+        header: Some(format!(r#"{{"version":"{}""#, infra_meta.railjson_version)),
+        remaining: ObjectType::iter().rev().collect(),
+        after: None,
+        done: false,
+    };
+    let body = stream::try_unfold(export, |mut export| async move {
+        let chunk = export.next_chunk().await.map_err(|error| {
+            error!(
+                "Could not export infrastructure {}: {error}",
+                export.infra_id
+            );
+            error
+        })?;
+        Ok::<_, InternalError>(chunk.map(|chunk| (chunk, export)))
+    });
 
     Ok(HttpResponse::Ok()
         .content_type(ContentType::json())
         .append_header(("x-infra-version", infra_meta.version))
-        .body(railjson))
+        .streaming(body))
+}
+
+/// The state of the export of an infra as a streamed railjson document
+struct RailJsonExport {
+    db_pool: Arc<DbConnectionPool>,
+    infra_id: i64,
+    /// The beginning of the document, until it is sent
+    header: Option<String>,
+    /// The object types left to export, the current one last
+    remaining: Vec<ObjectType>,
+    /// The id of the last object sent for the current object type
+    after: Option<String>,
+    done: bool,
+}
+
+impl RailJsonExport {
+    /// Returns the next chunk of the document, or `None` once it was entirely sent
+    async fn next_chunk(&mut self) -> Result<Option<Bytes>> {
+        if let Some(header) = self.header.take() {
+            return Ok(Some(header.into()));
+        }
+        let Some(&object_type) = self.remaining.last() else {
+            if self.done {
+                return Ok(None);
+            }
+            self.done = true;
+            return Ok(Some(Bytes::from_static(b"}")));
+        };
+
+        let first_page = self.after.is_none();
+        let conn = &mut self.db_pool.get().await?;
+        let page = Infra::get_railjson_page(
+            conn,
+            self.infra_id,
+            &object_type,
+            self.after.take(),
+            EXPORT_PAGE_SIZE,
+        )
+        .await?;
+
+        let mut chunk = if first_page {
+            format!(r#","{}":["#, railjson_field(object_type))
+        } else if !page.is_empty() {
+            ",".to_owned()
+        } else {
+            String::new()
+        };
+        chunk.push_str(
+            &page
+                .iter()
+                .map(|object| object.railjson.as_str())
+                .collect::<Vec<_>>()
+                .join(","),
+        );
+        if page.len() < EXPORT_PAGE_SIZE as usize {
+            chunk.push(']');
+            self.remaining.pop();
+        } else {
+            self.after = page.last().map(|object| object.obj_id.clone());
+        }
+        Ok(Some(chunk.into()))
+    }
 }
 
 /// Represents the query parameters for a `POST /infra/railjson` request
@@ -138,12 +182,18 @@ struct PostRailjsonQueryParams {
     generate_data: bool,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 struct PostRailjsonResponse {
     pub infra: i64,
+    /// The report of the migration of the railjson, if it was in an older version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub migration: Option<RailJsonMigrationReport>,
 }
 
 /// Import an infra from railjson
+///
+/// The payload is read and its objects inserted as they are received, by chunks.
+/// A railjson in an older version is migrated to the current one while being imported.
 #[utoipa::path(
     tag = "infra",
     params(PostRailjsonQueryParams),
@@ -156,24 +206,50 @@ struct PostRailjsonResponse {
 #[post("/railjson")]
 async fn post_railjson(
     params: Query<PostRailjsonQueryParams>,
-    railjson: Json<RailJson>,
+    mut payload: Payload,
     db_pool: Data<DbConnectionPool>,
     infra_caches: Data<CHashMap<i64, InfraCache>>,
+    authentication: Authentication,
 ) -> Result<Json<PostRailjsonResponse>> {
-    if railjson.version != RAILJSON_VERSION {
-        return Err(ListErrorsRailjson::WrongRailjsonVersionProvided.into());
-    }
-    let railjson = railjson.into_inner();
+    let authorizer = {
+        let conn = &mut db_pool.get().await?;
+        authentication.authorizer(conn).await?
+    };
+    let (sender, receiver) = mpsc::channel(IMPORT_PAYLOAD_BUFFER);
+    let forward_payload = async move {
+        while let Some(chunk) = payload.next().await {
+            let chunk = chunk.map_err(|error| io::Error::new(io::ErrorKind::Other, error));
+            if sender.send(chunk).await.is_err() {
+                // The reading stopped, the rest of the payload is useless
+                break;
+            }
+        }
+    };
 
     let db_pool = db_pool.into_inner();
-    let mut infra = Infra::changeset()
+    let infra_name = params.name.clone();
+    let import = Infra::changeset()
         .name(params.name.clone())
+        .owner(authorizer.owner_uuid())
         .last_railjson_version()
-        .persist(railjson, db_pool.clone())
-        .await?;
+        .persist_stream(
+            PayloadReader::new(receiver),
+            db_pool.clone(),
+            |imported: &RailJsonImportProgress| {
+                debug!(
+                    "🛤  Infra '{infra_name}': {} objects imported",
+                    imported.values().sum::<usize>()
+                )
+            },
+        );
+    let (_, imported) = futures::join!(forward_payload, import);
+    let (mut infra, migration) = imported?;
     let infra_id = infra.id;
 
     let mut conn = db_pool.get().await?;
+    authorizer
+        .grant_owner(&mut conn, ResourceType::Infra, infra_id)
+        .await?;
     infra
         .bump_version(&mut conn)
         .await
@@ -183,7 +259,39 @@ async fn post_railjson(
         infra.refresh(db_pool, true, &infra_cache).await?;
     }
 
-    Ok(Json(PostRailjsonResponse { infra: infra.id }))
+    Ok(Json(PostRailjsonResponse {
+        infra: infra.id,
+        migration,
+    }))
+}
+
+/// A blocking reader over the chunks of a request payload forwarded through a channel
+struct PayloadReader {
+    receiver: mpsc::Receiver<io::Result<Bytes>>,
+    current: Bytes,
+}
+
+impl PayloadReader {
+    fn new(receiver: mpsc::Receiver<io::Result<Bytes>>) -> Self {
+        Self {
+            receiver,
+            current: Bytes::new(),
+        }
+    }
+}
+
+impl Read for PayloadReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.current.is_empty() {
+            match self.receiver.blocking_recv() {
+                Some(chunk) => self.current = chunk?,
+                None => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.current.len());
+        buf[..len].copy_from_slice(&self.current.split_to(len));
+        Ok(len)
+    }
 }
 
 #[cfg(test)]
@@ -201,7 +309,9 @@ mod tests {
     use crate::fixtures::tests::TestFixture;
     use crate::views::infra::tests::create_object_request;
     use crate::views::tests::create_test_service;
+    use editoast_schemas::infra::RailJson;
     use editoast_schemas::infra::SwitchType;
+    use editoast_schemas::infra::RAILJSON_VERSION;
 
     #[rstest]
     #[serial_test::serial]
@@ -253,4 +363,21 @@ mod tests {
         let conn = &mut db_pool.get().await.unwrap();
         assert!(Infra::delete_static(conn, res.infra).await.unwrap());
     }
+
+    #[rstest]
+    async fn test_post_railjson_unsupported_version() {
+        let app = create_test_service().await;
+        let railjson = RailJson {
+            version: "0".to_owned(),
+            track_sections: (0..10).map(|_| Default::default()).collect(),
+            ..Default::default()
+        };
+
+        let req = actix_test::TestRequest::post()
+            .uri("/infra/railjson?name=post_railjson_unsupported_version_test")
+            .set_json(&railjson)
+            .to_request();
+        let response = call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
 }
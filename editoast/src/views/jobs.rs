@@ -0,0 +1,360 @@
+use actix_web::get;
+use actix_web::post;
+use actix_web::web::Data;
+use actix_web::web::Json;
+use actix_web::web::Path;
+use actix_web::web::Query;
+use actix_web::HttpResponse;
+use serde::Deserialize;
+use serde::Serialize;
+use utoipa::IntoParams;
+use utoipa::ToSchema;
+
+use crate::error::Result;
+use crate::jobs::JobError;
+use crate::modelsv2::job::Job;
+use crate::modelsv2::job::JobKind;
+use crate::modelsv2::job::JobParameters;
+use crate::modelsv2::job::JobStatus;
+use crate::modelsv2::prelude::*;
+use crate::modelsv2::resource_grant::ResourceType;
+use crate::modelsv2::resource_grant::Role;
+use crate::modelsv2::DbConnectionPool;
+use crate::modelsv2::Document;
+use crate::modelsv2::Infra;
+use crate::views::authz::Authentication;
+use crate::views::authz::Authorizer;
+use crate::views::pagination::PaginatedList;
+use crate::views::pagination::PaginationQueryParam;
+use crate::views::pagination::PaginationStats;
+
+crate::routes! {
+    "/jobs" => {
+        submit,
+        list,
+        "/{job_id}" => {
+            get,
+            cancel,
+        },
+    },
+}
+
+editoast_common::schemas! {
+    JobForm,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+struct JobIdParam {
+    /// A job ID
+    job_id: i64,
+}
+
+/// A job to run in the background
+#[derive(Debug, Deserialize, ToSchema)]
+struct JobForm {
+    parameters: JobParameters,
+    /// How many times the job is attempted when it fails with an internal error
+    #[serde(default = "default_max_attempts")]
+    #[schema(default = 3, minimum = 1, maximum = 10)]
+    max_attempts: u8,
+}
+
+fn default_max_attempts() -> u8 {
+    3
+}
+
+/// Submit a job, which is run by the first available worker
+#[utoipa::path(
+    tag = "jobs",
+    request_body = JobForm,
+    responses(
+        (status = 201, body = Job, description = "The submitted job"),
+    )
+)]
+#[post("")]
+async fn submit(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    data: Json<JobForm>,
+) -> Result<HttpResponse> {
+    let JobForm {
+        parameters,
+        max_attempts,
+    } = data.into_inner();
+    let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    match &parameters {
+        JobParameters::InfraClone { infra_id, .. } => {
+            authorizer
+                .check(conn, ResourceType::Infra, *infra_id, Role::Viewer)
+                .await?;
+        }
+        JobParameters::RailjsonImport { document_key, .. } => {
+            let document_key = *document_key;
+            let document = Document::retrieve_or_fail(conn, document_key, || {
+                JobError::DocumentNotFound { document_key }
+            })
+            .await?;
+            let uploader = authorizer.user().map(|user| user.id);
+            if !authorizer.is_admin() && document.user_id != uploader {
+                return Err(JobError::DocumentNotOwned { document_key }.into());
+            }
+        }
+        JobParameters::InfraRefresh { infra_ids, .. } => {
+            // No infra means all of them, which only matters for the users who aren't admins
+            let infra_ids = if infra_ids.is_empty() && !authorizer.is_admin() {
+                Infra::all(conn)
+                    .await
+                    .into_iter()
+                    .map(|infra| infra.id)
+                    .collect()
+            } else {
+                infra_ids.clone()
+            };
+            for infra_id in infra_ids {
+                authorizer
+                    .check(conn, ResourceType::Infra, infra_id, Role::Editor)
+                    .await?;
+            }
+        }
+        JobParameters::TrainSimulations {
+            infra_id,
+            train_ids,
+        } => {
+            authorizer
+                .check_train_schedules(conn, train_ids, Role::Viewer)
+                .await?;
+            authorizer
+                .check(conn, ResourceType::Infra, *infra_id, Role::Viewer)
+                .await?;
+        }
+    }
+
+    let job = Job::changeset()
+        .kind(parameters.kind())
+        .parameters(parameters)
+        .max_attempts(max_attempts.clamp(1, 10) as i32)
+        .user_id(authorizer.user().map(|user| user.id))
+        .create(conn)
+        .await?;
+    Ok(HttpResponse::Created().json(job))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct JobFilterParams {
+    /// Only return the jobs with this status
+    status: Option<JobStatus>,
+    /// Only return the jobs of this kind
+    kind: Option<JobKind>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct JobListResponse {
+    results: Vec<Job>,
+    #[serde(flatten)]
+    stats: PaginationStats,
+}
+
+/// Returns the paginated jobs, most recent first
+///
+/// Users who aren't admins only see the jobs they submitted.
+#[utoipa::path(
+    tag = "jobs",
+    params(PaginationQueryParam, JobFilterParams),
+    responses(
+        (status = 200, body = inline(JobListResponse), description = "The jobs"),
+    )
+)]
+#[get("")]
+async fn list(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    Query(pagination_params): Query<PaginationQueryParam>,
+    Query(JobFilterParams { status, kind }): Query<JobFilterParams>,
+) -> Result<Json<JobListResponse>> {
+    let mut settings = pagination_params
+        .validate(1000)?
+        .warn_page_size(100)
+        .into_selection_settings()
+        .order_by(|| Job::ID.desc());
+    if let Some(status) = status {
+        settings = settings.filter(move || Job::STATUS.eq(status));
+    }
+    if let Some(kind) = kind {
+        settings = settings.filter(move || Job::KIND.eq(kind));
+    }
+    let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    if let Some(user) = authorizer.user().filter(|user| !user.is_admin) {
+        let user_id = user.id;
+        settings = settings.filter(move || Job::USER_ID.eq(Some(user_id)));
+    }
+    let (results, stats) = Job::list_paginated(conn, settings).await?;
+    Ok(Json(JobListResponse { results, stats }))
+}
+
+/// Returns a job, with its progress, logs and outcome
+#[utoipa::path(
+    tag = "jobs",
+    params(JobIdParam),
+    responses(
+        (status = 200, body = Job, description = "The job"),
+        (status = 403, description = "The job was submitted by another user"),
+        (status = 404, description = "The job was not found"),
+    )
+)]
+#[get("")]
+async fn get(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    path: Path<JobIdParam>,
+) -> Result<Json<Job>> {
+    let job_id = path.job_id;
+    let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    let job = Job::retrieve_or_fail(conn, job_id, || JobError::NotFound { job_id }).await?;
+    check_submitter(&authorizer, &job)?;
+    Ok(Json(job))
+}
+
+/// Only the user who submitted a job and the admins may follow it
+fn check_submitter(authorizer: &Authorizer, job: &Job) -> Result<()> {
+    let user_id = authorizer.user().map(|user| user.id);
+    if authorizer.is_admin() || job.user_id == user_id {
+        Ok(())
+    } else {
+        Err(JobError::Forbidden { job_id: job.id }.into())
+    }
+}
+
+/// Cancel a job
+///
+/// Pending jobs are cancelled right away. Running jobs stop at their next step,
+/// their status becomes `cancelled` once their worker noticed the cancellation.
+#[utoipa::path(
+    tag = "jobs",
+    params(JobIdParam),
+    responses(
+        (status = 200, body = Job, description = "The job"),
+        (status = 400, description = "The job was already finished"),
+        (status = 403, description = "The job was submitted by another user"),
+        (status = 404, description = "The job was not found"),
+    )
+)]
+#[post("/cancel")]
+async fn cancel(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    path: Path<JobIdParam>,
+) -> Result<Json<Job>> {
+    let job_id = path.job_id;
+    let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    let job = Job::retrieve_or_fail(conn, job_id, || JobError::NotFound { job_id }).await?;
+    check_submitter(&authorizer, &job)?;
+    if job.status.is_finished() {
+        return Err(JobError::AlreadyFinished { job_id }.into());
+    }
+    let job = Job::request_cancel(conn, job_id)
+        .await?
+        .ok_or(JobError::NotFound { job_id })?;
+    Ok(Json(job))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use actix_web::http::StatusCode;
+    use actix_web::test::call_and_read_body_json;
+    use actix_web::test::call_service;
+    use actix_web::test::read_body_json;
+    use actix_web::test::TestRequest;
+    use rstest::rstest;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::fixtures::tests::db_pool;
+    use crate::modelsv2::User;
+    use crate::views::authz::REMOTE_USER_HEADER;
+    use crate::views::tests::create_test_service;
+
+    #[rstest]
+    async fn cancel_pending_job(db_pool: Arc<DbConnectionPool>) {
+        let service = create_test_service().await;
+        let request = TestRequest::post()
+            .uri("/jobs")
+            .set_json(json!({ "parameters": { "kind": "infra_refresh", "infra_ids": [] } }))
+            .to_request();
+        let job: Job = call_and_read_body_json(&service, request).await;
+        assert_eq!(job.kind, JobKind::InfraRefresh);
+        assert_eq!(job.max_attempts, 3);
+
+        let request = TestRequest::post()
+            .uri(&format!("/jobs/{}/cancel", job.id))
+            .to_request();
+        let cancelled: Job = call_and_read_body_json(&service, request).await;
+        assert_eq!(cancelled.status, JobStatus::Cancelled);
+        assert!(cancelled.finished_at.is_some());
+
+        let request = TestRequest::post()
+            .uri(&format!("/jobs/{}/cancel", job.id))
+            .to_request();
+        let response = call_service(&service, request).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let conn = &mut db_pool.get().await.unwrap();
+        Job::delete_static(conn, job.id).await.unwrap();
+    }
+
+    #[rstest]
+    async fn only_the_submitter_follows_a_job(db_pool: Arc<DbConnectionPool>) {
+        let service = create_test_service().await;
+        let submitter = format!("test/{}", Uuid::new_v4());
+        let other = format!("test/{}", Uuid::new_v4());
+        let conn = &mut db_pool.get().await.unwrap();
+        let submitter_id = User::resolve(conn, &submitter).await.unwrap().id;
+        // A finished job, so that no worker picks it up
+        let job = Job::changeset()
+            .kind(JobKind::InfraRefresh)
+            .parameters(JobParameters::default())
+            .status(JobStatus::Cancelled)
+            .user_id(Some(submitter_id))
+            .create(conn)
+            .await
+            .unwrap();
+
+        let get_job = |identity: &str| {
+            TestRequest::get()
+                .uri(&format!("/jobs/{}", job.id))
+                .insert_header((REMOTE_USER_HEADER, identity))
+                .to_request()
+        };
+        let response = call_service(&service, get_job(&submitter)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = call_service(&service, get_job(&other)).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let request = TestRequest::get()
+            .uri("/jobs?page_size=1000")
+            .insert_header((REMOTE_USER_HEADER, other.as_str()))
+            .to_request();
+        let jobs: serde_json::Value = read_body_json(call_service(&service, request).await).await;
+        assert!(jobs["results"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .all(|listed| listed["id"] != job.id));
+
+        let request = TestRequest::post()
+            .uri(&format!("/jobs/{}/cancel", job.id))
+            .insert_header((REMOTE_USER_HEADER, other.as_str()))
+            .to_request();
+        let response = call_service(&service, request).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        Job::delete_static(conn, job.id).await.unwrap();
+    }
+}
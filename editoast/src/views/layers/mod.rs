@@ -27,10 +27,19 @@ use crate::client::MapLayersConfig;
 use crate::error::Result;
 use crate::map::get_cache_tile_key;
 use crate::map::get_view_cache_prefix;
+use crate::map::gis::feature_collection;
+use crate::map::gis::load_gis_features;
+use crate::map::gis::GeoPackage;
 use crate::map::Layer;
 use crate::map::MapLayers;
 use crate::map::Tile;
+use crate::modelsv2::prelude::*;
+use crate::modelsv2::resource_grant::ResourceType;
+use crate::modelsv2::resource_grant::Role;
 use crate::modelsv2::DbConnectionPool;
+use crate::modelsv2::Infra;
+use crate::views::authz::Authentication;
+use crate::views::infra::InfraApiError;
 use crate::RedisClient;
 
 crate::routes! {
@@ -38,6 +47,12 @@ crate::routes! {
         "/layer/{layer_slug}/mvt/{view_slug}" => {
             layer_view,
         },
+        "/layer/{layer_slug}/geojson/{view_slug}" => {
+            layer_geojson,
+        },
+        "/gpkg/{view_slug}" => {
+            layers_geopackage,
+        },
         "/tile/{layer_slug}/{view_slug}/{z}/{x}/{y}" => {
             cache_and_get_mvt_tile,
         },
@@ -57,6 +72,8 @@ enum LayersError {
         view_name: String,
         expected_names: Vec<String>,
     },
+    #[error("No layer has a view named '{}'", .view_name)]
+    NoLayerWithView { view_name: String },
 }
 
 impl LayersError {
@@ -156,6 +173,112 @@ async fn layer_view(
     }))
 }
 
+/// Returns all the features of a layer view as a GeoJSON FeatureCollection
+///
+/// The properties of the features are the flattened data of the objects.
+#[utoipa::path(
+    tag = "layers",
+    params(InfraQueryParam, LayerViewParams),
+    responses(
+        (status = 200, body = serde_json::Value, description = "A GeoJSON FeatureCollection"),
+    )
+)]
+#[get("")]
+async fn layer_geojson(
+    path: Path<(String, String)>,
+    params: Query<InfraQueryParam>,
+    map_layers: Data<MapLayers>,
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+) -> Result<Json<serde_json::Value>> {
+    let (layer_slug, view_slug) = path.into_inner();
+    let infra_id = params.infra;
+    let layer = match map_layers.layers.get(&layer_slug) {
+        Some(layer) => layer,
+        None => return Err(LayersError::new_layer_not_found(layer_slug, &map_layers).into()),
+    };
+    let view = match layer.views.get(&view_slug) {
+        Some(view) => view,
+        None => return Err(LayersError::new_view_not_found(view_slug, layer).into()),
+    };
+
+    let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check(conn, ResourceType::Infra, infra_id, Role::Viewer)
+        .await?;
+    Infra::retrieve_or_fail(conn, infra_id, || InfraApiError::NotFound { infra_id }).await?;
+    let features = load_gis_features(conn, infra_id, &layer.table_name, view).await?;
+    Ok(Json(feature_collection(&layer_slug, features)))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[allow(unused)]
+struct GeoPackageParams {
+    view_slug: String,
+}
+
+/// Returns a GeoPackage file holding one table per layer having the requested view
+#[utoipa::path(
+    tag = "layers",
+    params(InfraQueryParam, GeoPackageParams),
+    responses(
+        (status = 200, body = Vec<u8>, description = "The GeoPackage file"),
+    )
+)]
+#[get("")]
+async fn layers_geopackage(
+    path: Path<String>,
+    params: Query<InfraQueryParam>,
+    map_layers: Data<MapLayers>,
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+) -> Result<HttpResponse> {
+    let view_slug = path.into_inner();
+    let infra_id = params.infra;
+    let mut layers: Vec<_> = map_layers
+        .layers
+        .iter()
+        .filter_map(|(name, layer)| Some((name, layer, layer.views.get(&view_slug)?)))
+        .collect();
+    if layers.is_empty() {
+        return Err(LayersError::NoLayerWithView {
+            view_name: view_slug,
+        }
+        .into());
+    }
+    layers.sort_by_key(|(name, _, _)| *name);
+
+    let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check(conn, ResourceType::Infra, infra_id, Role::Viewer)
+        .await?;
+    Infra::retrieve_or_fail(conn, infra_id, || InfraApiError::NotFound { infra_id }).await?;
+    let mut layer_features = Vec::with_capacity(layers.len());
+    for (name, layer, view) in layers {
+        let features = load_gis_features(conn, infra_id, &layer.table_name, view).await?;
+        layer_features.push((name.clone(), features));
+    }
+
+    let geopackage = tokio::task::spawn_blocking(move || -> Result<Vec<u8>> {
+        let mut geopackage = GeoPackage::create_in_memory()?;
+        for (name, features) in layer_features {
+            geopackage.add_layer(&name, &features)?;
+        }
+        Ok(geopackage.into_bytes()?)
+    })
+    .await??;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/geopackage+sqlite3")
+        .insert_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"infra_{infra_id}.gpkg\""),
+        ))
+        .body(geopackage))
+}
+
 #[derive(Deserialize, IntoParams)]
 #[allow(unused)]
 struct TileParams {
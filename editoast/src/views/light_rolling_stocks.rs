@@ -6,9 +6,12 @@ use actix_web::web::Query;
 
 use crate::decl_paginated_response;
 use crate::error::Result;
+use crate::modelsv2::resource_grant::ResourceType;
+use crate::modelsv2::resource_grant::Role;
 use crate::modelsv2::DbConnectionPool;
 use crate::modelsv2::LightRollingStockModel;
 use crate::modelsv2::Retrieve;
+use crate::views::authz::Authentication;
 use crate::views::pagination::PaginatedResponse;
 use crate::views::pagination::PaginationQueryParam;
 use crate::views::rolling_stocks::RollingStockError;
@@ -51,10 +54,16 @@ editoast_common::schemas! {
 #[get("")]
 async fn list(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     page_settings: Query<PaginationQueryParam>,
 ) -> Result<Json<PaginatedResponse<LightRollingStockWithLiveries>>> {
     let (page, per_page) = page_settings.validate(1000)?.warn_page_size(100).unpack();
-    let result = LightRollingStockModel::list(db_pool.into_inner(), page, per_page).await?;
+    let hidden = {
+        let conn = &mut db_pool.get().await?;
+        let authorizer = authentication.authorizer(conn).await?;
+        authorizer.hidden(conn, ResourceType::RollingStock).await?
+    };
+    let result = LightRollingStockModel::list(db_pool.into_inner(), page, per_page, hidden).await?;
 
     let results: Vec<LightRollingStockWithLiveries> =
         result.results.into_iter().map(|l| l.into()).collect();
@@ -80,10 +89,20 @@ async fn list(
 #[get("")]
 async fn get(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     rolling_stock_id: Path<i64>,
 ) -> Result<Json<LightRollingStockWithLiveries>> {
     let rolling_stock_id = rolling_stock_id.into_inner();
     let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check(
+            conn,
+            ResourceType::RollingStock,
+            rolling_stock_id,
+            Role::Viewer,
+        )
+        .await?;
     let rolling_stock = LightRollingStockModel::retrieve_or_fail(conn, rolling_stock_id, || {
         RollingStockError::KeyNotFound {
             rolling_stock_key: RollingStockKey::Id(rolling_stock_id),
@@ -108,6 +127,7 @@ async fn get(
 #[get("")]
 async fn get_by_name(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     rolling_stock_name: Path<String>,
 ) -> Result<Json<LightRollingStockWithLiveries>> {
     let rolling_stock_name = rolling_stock_name.into_inner();
@@ -119,6 +139,15 @@ async fn get_by_name(
             }
         })
         .await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check(
+            conn,
+            ResourceType::RollingStock,
+            rolling_stock.id,
+            Role::Viewer,
+        )
+        .await?;
     let rollig_stock_with_liveries: LightRollingStockWithLiveries = rolling_stock
         .with_liveries(db_pool.into_inner())
         .await?
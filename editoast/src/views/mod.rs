@@ -1,6 +1,9 @@
+pub mod authz;
+mod consists;
 mod documents;
 pub mod electrical_profiles;
 pub mod infra;
+mod jobs;
 mod layers;
 pub mod light_rolling_stocks;
 pub mod openapi;
@@ -14,6 +17,7 @@ pub mod scenario;
 pub mod search;
 mod single_simulation;
 pub mod sprites;
+mod sse;
 pub mod stdcm;
 pub mod study;
 pub mod timetable;
@@ -63,7 +67,7 @@ use crate::RedisClient;
 fn routes_v2() -> Routes<impl HttpServiceFactory> {
     crate::routes! {
         (health, version, core_version),
-        (rolling_stocks::routes(), light_rolling_stocks::routes()),
+        (rolling_stocks::routes(), light_rolling_stocks::routes(), consists::routes()),
         (pathfinding::routes(), stdcm::routes(), train_schedule::routes()),
         (projects::routes(),timetable::routes(), work_schedules::routes()),
         documents::routes(),
@@ -73,7 +77,7 @@ fn routes_v2() -> Routes<impl HttpServiceFactory> {
         layers::routes(),
         infra::routes(),
         single_simulation::routes(),
-        v2::routes()
+        (v2::routes(), authz::routes(), jobs::routes())
     }
     routes()
 }
@@ -84,6 +88,8 @@ pub fn routes() -> impl HttpServiceFactory {
 
 editoast_common::schemas! {
     error::schemas(),
+    authz::schemas(),
+    consists::schemas(),
     models::schemas(),
     modelsv2::schemas(),
     core::schemas(),
@@ -100,6 +106,7 @@ editoast_common::schemas! {
     light_rolling_stocks::schemas(),
     electrical_profiles::schemas(),
     infra::schemas(),
+    jobs::schemas(),
     single_simulation::schemas(),
     v2::schemas(),
     work_schedules::schemas(),
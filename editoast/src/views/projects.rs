@@ -18,12 +18,16 @@ use thiserror::Error;
 use utoipa::IntoParams;
 use utoipa::ToSchema;
 
+mod archive;
+
 use super::operational_studies::OperationalStudiesOrderingParam;
 use super::pagination::PaginatedList;
 use super::pagination::PaginationStats;
 use super::study;
 use crate::error::Result;
 use crate::modelsv2::projects::Tags;
+use crate::modelsv2::resource_grant::ResourceType;
+use crate::modelsv2::resource_grant::Role;
 use crate::modelsv2::Changeset;
 use crate::modelsv2::Create;
 use crate::modelsv2::DbConnection;
@@ -32,12 +36,14 @@ use crate::modelsv2::Document;
 use crate::modelsv2::Model;
 use crate::modelsv2::Project;
 use crate::modelsv2::Retrieve;
+use crate::views::authz::Authentication;
 use crate::views::pagination::PaginationQueryParam;
 
 crate::routes! {
     "/projects" => {
         create,
         list,
+        archive::routes(),
         "/{project_id}" => {
             get,
             delete,
@@ -149,6 +155,7 @@ impl ProjectWithStudyCount {
 #[post("")]
 async fn create(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     data: Json<ProjectCreateForm>,
 ) -> Result<Json<ProjectWithStudyCount>> {
     let project_create_form = data.into_inner();
@@ -157,7 +164,11 @@ async fn create(
     }
     let project: Changeset<Project> = project_create_form.into();
     let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
     let project = project.create(conn).await?;
+    authorizer
+        .grant_owner(conn, ResourceType::Project, project.id)
+        .await?;
     let project_with_studies = ProjectWithStudyCount::try_fetch(conn, project).await?;
 
     Ok(Json(project_with_studies))
@@ -182,17 +193,21 @@ struct ProjectWithStudyCountList {
 #[get("")]
 async fn list(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     pagination_params: Query<PaginationQueryParam>,
     Query(ordering_params): Query<OperationalStudiesOrderingParam>,
 ) -> Result<Json<ProjectWithStudyCountList>> {
+    let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    let hidden = authorizer.hidden(conn, ResourceType::Project).await?;
     let ordering = ordering_params.ordering;
     let settings = pagination_params
         .validate(1000)?
         .warn_page_size(100)
         .into_selection_settings()
+        .filter(move || Project::ID.ne_all(hidden.clone()))
         .order_by(move || ordering.as_project_ordering());
-    let (projects, stats) =
-        Project::list_paginated(db_pool.get().await?.deref_mut(), settings).await?;
+    let (projects, stats) = Project::list_paginated(conn, settings).await?;
     let results = projects
         .into_iter()
         .zip(std::iter::repeat(&db_pool).map(|p| p.get()))
@@ -223,10 +238,15 @@ pub struct ProjectIdParam {
 #[get("")]
 async fn get(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     project: Path<i64>,
 ) -> Result<Json<ProjectWithStudyCount>> {
     let project_id = project.into_inner();
     let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check(conn, ResourceType::Project, project_id, Role::Viewer)
+        .await?;
     let project =
         Project::retrieve_or_fail(conn, project_id, || ProjectError::NotFound { project_id })
             .await?;
@@ -243,9 +263,17 @@ async fn get(
     )
 )]
 #[delete("")]
-async fn delete(project: Path<i64>, db_pool: Data<DbConnectionPool>) -> Result<HttpResponse> {
+async fn delete(
+    project: Path<i64>,
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+) -> Result<HttpResponse> {
     let project_id = project.into_inner();
     let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check(conn, ResourceType::Project, project_id, Role::Owner)
+        .await?;
     if Project::delete_and_prune_document(conn, project_id).await? {
         Ok(HttpResponse::NoContent().finish())
     } else {
@@ -302,14 +330,19 @@ async fn patch(
     data: Json<ProjectPatchForm>,
     project_id: Path<i64>,
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
 ) -> Result<Json<ProjectWithStudyCount>> {
     let data = data.into_inner();
     let project_id = project_id.into_inner();
+    let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check(conn, ResourceType::Project, project_id, Role::Editor)
+        .await?;
     if let Some(image) = data.image {
         check_image_content(db_pool.clone(), image).await?;
     }
     let project_changeset: Changeset<Project> = data.into();
-    let conn = &mut db_pool.get().await?;
     let project = Project::update_and_prune_document(conn, project_changeset, project_id).await?;
     Ok(Json(ProjectWithStudyCount::try_fetch(conn, project).await?))
 }
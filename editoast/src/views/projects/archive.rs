@@ -0,0 +1,868 @@
+//! Portable project archives
+//!
+//! An archive is a zip file holding a `project.json` manifest and the project image, if any.
+//! Database ids are not exported: rolling stocks, electrical profile sets and infras are
+//! referenced by name, and resolved by name when the archive is imported.
+//! Rolling stock revisions are not exported either, so imported trains are never pinned
+//! to a revision and run with the current version of their rolling stock.
+
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::io::Read;
+use std::io::Seek;
+use std::io::Write;
+
+use actix_web::get;
+use actix_web::http::header;
+use actix_web::post;
+use actix_web::web::Bytes;
+use actix_web::web::Data;
+use actix_web::web::Path;
+use actix_web::HttpResponse;
+use chrono::DateTime;
+use chrono::NaiveDate;
+use chrono::Utc;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::AsyncConnection;
+use editoast_derive::EditoastError;
+use editoast_schemas::infra::ElectricalProfileSetData;
+use editoast_schemas::rolling_stock::RollingStock;
+use editoast_schemas::train_schedule::TrainScheduleBase;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+use zip::write::FileOptions;
+use zip::CompressionMethod;
+use zip::ZipArchive;
+use zip::ZipWriter;
+
+use super::ProjectError;
+use super::ProjectWithStudyCount;
+use crate::error::InternalError;
+use crate::error::Result;
+use crate::modelsv2::prelude::*;
+use crate::modelsv2::resource_grant::ResourceType;
+use crate::modelsv2::resource_grant::Role;
+use crate::modelsv2::timetable::Timetable;
+use crate::modelsv2::train_schedule::TrainSchedule;
+use crate::modelsv2::train_schedule::TrainScheduleChangeset;
+use crate::modelsv2::train_schedule_cadence::TrainScheduleCadence;
+use crate::modelsv2::DbConnection;
+use crate::modelsv2::DbConnectionPool;
+use crate::modelsv2::Document;
+use crate::modelsv2::ElectricalProfileSet;
+use crate::modelsv2::Infra;
+use crate::modelsv2::Project;
+use crate::modelsv2::RollingStockModel;
+use crate::modelsv2::Scenario;
+use crate::modelsv2::Study;
+use crate::modelsv2::Tags;
+use crate::views::authz::Authentication;
+use crate::views::authz::Authorizer;
+use crate::views::v2::scenario::ScenarioError;
+use crate::views::v2::train_schedule::TrainScheduleForm;
+use crate::views::v2::train_schedule::TrainScheduleResult;
+
+/// Bumped whenever the manifest changes in a way older editoast versions can't read
+const ARCHIVE_VERSION: u32 = 1;
+const MANIFEST_FILE: &str = "project.json";
+const IMAGE_FILE: &str = "image";
+
+crate::routes! {
+    import,
+    export,
+}
+
+#[derive(Debug, Error, EditoastError)]
+#[editoast_error(base_id = "project_archive")]
+pub enum ProjectArchiveError {
+    #[error("Invalid project archive: {error}")]
+    #[editoast_error(status = 400)]
+    InvalidArchive { error: String },
+    #[error("Unsupported project archive version '{version}', expected '{expected}'")]
+    #[editoast_error(status = 400)]
+    UnsupportedVersion { version: u32, expected: u32 },
+    #[error("No infra named '{infra_name}' could be found")]
+    #[editoast_error(status = 404)]
+    InfraNotFound { infra_name: String },
+}
+
+fn invalid(error: impl ToString) -> ProjectArchiveError {
+    ProjectArchiveError::InvalidArchive {
+        error: error.to_string(),
+    }
+}
+
+/// The content of the `project.json` manifest
+#[derive(Debug, Serialize, Deserialize)]
+struct ProjectArchive {
+    version: u32,
+    project: ProjectRecord,
+    studies: Vec<StudyRecord>,
+    /// The rolling stocks used by the trains
+    rolling_stocks: Vec<RollingStock>,
+    /// The electrical profile sets used by the timetables
+    electrical_profile_sets: Vec<ElectricalProfileSetRecord>,
+}
+
+/// Only read first, to reject archives of unknown versions with a meaningful error
+#[derive(Debug, Deserialize)]
+struct ArchiveHeader {
+    version: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ProjectRecord {
+    name: String,
+    objectives: Option<String>,
+    description: Option<String>,
+    funders: Option<String>,
+    budget: Option<i32>,
+    tags: Tags,
+    /// The content type of the image file, if the project has one
+    image_content_type: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StudyRecord {
+    name: String,
+    description: Option<String>,
+    business_code: Option<String>,
+    service_code: Option<String>,
+    start_date: Option<NaiveDate>,
+    expected_end_date: Option<NaiveDate>,
+    actual_end_date: Option<NaiveDate>,
+    budget: Option<i32>,
+    tags: Tags,
+    state: String,
+    study_type: Option<String>,
+    scenarios: Vec<ScenarioRecord>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ScenarioRecord {
+    name: String,
+    description: String,
+    tags: Tags,
+    infra_name: String,
+    /// The name of the electrical profile set of the timetable
+    electrical_profile_set: Option<String>,
+    cadences: Vec<CadenceRecord>,
+    trains: Vec<TrainRecord>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CadenceRecord {
+    template: TrainScheduleBase,
+    period: i64,
+    window_start: DateTime<Utc>,
+    window_end: DateTime<Utc>,
+    name_pattern: String,
+}
+
+/// A train of a scenario, which is unpinned from its rolling stock revision if it was pinned
+#[derive(Debug, Serialize, Deserialize)]
+struct TrainRecord {
+    #[serde(flatten)]
+    train_schedule: TrainScheduleBase,
+    /// The index of the cadence which generated the train in the scenario cadences
+    cadence: Option<usize>,
+    /// The departure of the cadence the train was generated for
+    #[serde(default)]
+    cadence_departure: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ElectricalProfileSetRecord {
+    name: String,
+    data: ElectricalProfileSetData,
+}
+
+impl ProjectArchive {
+    /// Gathers the content of a project, along with its image
+    async fn collect(conn: &mut DbConnection, project: Project) -> Result<(Self, Option<Vec<u8>>)> {
+        let image = match project.image {
+            Some(document_key) => Some(
+                Document::retrieve_or_fail(conn, document_key, || ProjectError::ImageNotFound {
+                    document_key,
+                })
+                .await?,
+            ),
+            None => None,
+        };
+
+        let project_id = project.id;
+        let studies = Study::list(
+            conn,
+            SelectionSettings::new()
+                .filter(move || Study::PROJECT_ID.eq(project_id))
+                .order_by(|| Study::ID.asc()),
+        )
+        .await?;
+
+        let mut rolling_stock_names = BTreeSet::new();
+        let mut electrical_profile_set_ids = BTreeSet::new();
+        let mut study_records = vec![];
+        for study in studies {
+            let study_id = study.id;
+            let scenarios = Scenario::list(
+                conn,
+                SelectionSettings::new()
+                    .filter(move || Scenario::STUDY_ID.eq(study_id))
+                    .order_by(|| Scenario::ID.asc()),
+            )
+            .await?;
+            let mut scenario_records = vec![];
+            for scenario in scenarios {
+                let timetable_id = scenario.timetable_id;
+                let timetable = Timetable::retrieve_or_fail(conn, timetable_id, || {
+                    ScenarioError::TimetableNotFound { timetable_id }
+                })
+                .await?;
+                electrical_profile_set_ids.extend(timetable.electrical_profile_set_id);
+                let record = ScenarioRecord::collect(conn, scenario, &timetable).await?;
+                rolling_stock_names.extend(
+                    record
+                        .trains
+                        .iter()
+                        .map(|train| train.train_schedule.rolling_stock_name.clone()),
+                );
+                rolling_stock_names.extend(
+                    record
+                        .cadences
+                        .iter()
+                        .map(|cadence| cadence.template.rolling_stock_name.clone()),
+                );
+                scenario_records.push(record);
+            }
+            study_records.push(StudyRecord {
+                name: study.name,
+                description: study.description,
+                business_code: study.business_code,
+                service_code: study.service_code,
+                start_date: study.start_date,
+                expected_end_date: study.expected_end_date,
+                actual_end_date: study.actual_end_date,
+                budget: study.budget,
+                tags: study.tags,
+                state: study.state,
+                study_type: study.study_type,
+                scenarios: scenario_records,
+            });
+        }
+
+        // Trains referencing an unknown rolling stock are still exported, as they are in OSRD
+        let mut rolling_stocks = vec![];
+        for name in rolling_stock_names {
+            if let Some(rolling_stock) = RollingStockModel::retrieve(conn, name).await? {
+                rolling_stocks.push(rolling_stock.into());
+            }
+        }
+
+        let mut electrical_profile_sets = vec![];
+        for id in electrical_profile_set_ids {
+            if let Some(set) = ElectricalProfileSet::retrieve(conn, id).await? {
+                electrical_profile_sets.push(ElectricalProfileSetRecord {
+                    name: set.name,
+                    data: set.data,
+                });
+            }
+        }
+        // Scenarios reference the sets by name, so the first one wins when two share a name
+        let mut names = BTreeSet::new();
+        electrical_profile_sets.retain(|set| names.insert(set.name.clone()));
+
+        let archive = ProjectArchive {
+            version: ARCHIVE_VERSION,
+            project: ProjectRecord {
+                name: project.name,
+                objectives: project.objectives,
+                description: project.description,
+                funders: project.funders,
+                budget: project.budget,
+                tags: project.tags,
+                image_content_type: image.as_ref().map(|image| image.content_type.clone()),
+            },
+            studies: study_records,
+            rolling_stocks,
+            electrical_profile_sets,
+        };
+        Ok((archive, image.map(|image| image.data)))
+    }
+
+    /// Writes the archive as a zip file
+    fn write<W: Write + Seek>(&self, writer: W, image: Option<&[u8]>) -> Result<()> {
+        let mut zip = ZipWriter::new(writer);
+        let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        zip.start_file(MANIFEST_FILE, options)?;
+        serde_json::to_writer(&mut zip, self)?;
+        if let Some(image) = image {
+            zip.start_file(IMAGE_FILE, options)?;
+            zip.write_all(image)?;
+        }
+        zip.finish()?;
+        Ok(())
+    }
+
+    /// Reads a zip file, returning the archive and the project image
+    fn read(data: &[u8]) -> Result<(Self, Option<Vec<u8>>)> {
+        let mut zip = ZipArchive::new(Cursor::new(data)).map_err(invalid)?;
+
+        let mut manifest = String::new();
+        zip.by_name(MANIFEST_FILE)
+            .map_err(invalid)?
+            .read_to_string(&mut manifest)
+            .map_err(invalid)?;
+        let header: ArchiveHeader = serde_json::from_str(&manifest).map_err(invalid)?;
+        if header.version != ARCHIVE_VERSION {
+            return Err(ProjectArchiveError::UnsupportedVersion {
+                version: header.version,
+                expected: ARCHIVE_VERSION,
+            }
+            .into());
+        }
+        let archive: ProjectArchive = serde_json::from_str(&manifest).map_err(invalid)?;
+
+        let image = match archive.project.image_content_type {
+            Some(_) => {
+                let mut image = vec![];
+                zip.by_name(IMAGE_FILE)
+                    .map_err(invalid)?
+                    .read_to_end(&mut image)
+                    .map_err(invalid)?;
+                Some(image)
+            }
+            None => None,
+        };
+        Ok((archive, image))
+    }
+
+    /// Creates the project and its content, owned by the importer
+    ///
+    /// Rolling stocks are reused when one with the same name exists, and created otherwise.
+    /// Electrical profile sets are only reused when their data is the same, a renamed copy
+    /// is created otherwise. Scenarios run on the latest infra with the recorded name.
+    async fn restore(
+        self,
+        conn: &mut DbConnection,
+        authorizer: &Authorizer,
+        image: Option<Vec<u8>>,
+    ) -> Result<Project> {
+        for rolling_stock in self.rolling_stocks {
+            if !RollingStockModel::exists(conn, rolling_stock.name.clone()).await? {
+                let rolling_stock = Changeset::<RollingStockModel>::from(rolling_stock);
+                rolling_stock.validate_imported_rolling_stock()?;
+                let rolling_stock = rolling_stock.locked(false).version(0).create(conn).await?;
+                authorizer
+                    .grant_owner(conn, ResourceType::RollingStock, rolling_stock.id)
+                    .await?;
+            }
+        }
+
+        let mut electrical_profile_set_ids = HashMap::new();
+        for set in self.electrical_profile_sets {
+            let name = set.name.clone();
+            let existing = ElectricalProfileSet::list(
+                conn,
+                SelectionSettings::new()
+                    .filter(move || ElectricalProfileSet::NAME.eq(name.clone()))
+                    .order_by(|| ElectricalProfileSet::ID.asc()),
+            )
+            .await?;
+            let id = match existing
+                .into_iter()
+                .find(|existing| existing.data == set.data)
+            {
+                Some(existing) => existing.id,
+                None => {
+                    let name = unused_electrical_profile_set_name(conn, &set.name).await?;
+                    ElectricalProfileSet::changeset()
+                        .name(name)
+                        .data(set.data)
+                        .create(conn)
+                        .await?
+                        .id
+                }
+            };
+            electrical_profile_set_ids.insert(set.name, id);
+        }
+
+        let ProjectRecord {
+            name,
+            objectives,
+            description,
+            funders,
+            budget,
+            tags,
+            image_content_type,
+        } = self.project;
+        let image = match (image_content_type, image) {
+            (Some(content_type), Some(data)) => Some(
+                Document::changeset()
+                    .content_type(content_type)
+                    .data(data)
+                    .user_id(authorizer.user().map(|user| user.id))
+                    .create(conn)
+                    .await?
+                    .id,
+            ),
+            _ => None,
+        };
+        let now = Utc::now().naive_utc();
+        let project = Project::changeset()
+            .name(name)
+            .objectives(objectives)
+            .description(description)
+            .funders(funders)
+            .budget(budget)
+            .tags(tags)
+            .image(image)
+            .creation_date(now)
+            .last_modification(now)
+            .create(conn)
+            .await?;
+        authorizer
+            .grant_owner(conn, ResourceType::Project, project.id)
+            .await?;
+
+        let mut infra_ids = HashMap::new();
+        for study in self.studies {
+            let created_study = Study::changeset()
+                .name(study.name)
+                .description(study.description)
+                .business_code(study.business_code)
+                .service_code(study.service_code)
+                .creation_date(now)
+                .last_modification(now)
+                .start_date(study.start_date)
+                .expected_end_date(study.expected_end_date)
+                .actual_end_date(study.actual_end_date)
+                .budget(study.budget)
+                .tags(study.tags)
+                .state(study.state)
+                .study_type(study.study_type)
+                .project_id(project.id)
+                .create(conn)
+                .await?;
+
+            for scenario in study.scenarios {
+                let infra_id = match infra_ids.get(&scenario.infra_name) {
+                    Some(&infra_id) => infra_id,
+                    None => {
+                        let infra_id = find_infra(conn, &scenario.infra_name).await?;
+                        infra_ids.insert(scenario.infra_name.clone(), infra_id);
+                        infra_id
+                    }
+                };
+                let electrical_profile_set_id = scenario
+                    .electrical_profile_set
+                    .as_ref()
+                    .and_then(|name| electrical_profile_set_ids.get(name).copied());
+                let timetable = scenario
+                    .restore_timetable(conn, electrical_profile_set_id)
+                    .await?;
+                Scenario::changeset()
+                    .name(scenario.name)
+                    .description(scenario.description)
+                    .creation_date(now)
+                    .last_modification(now)
+                    .infra_id(infra_id)
+                    .timetable_id(timetable.id)
+                    .tags(scenario.tags)
+                    .study_id(created_study.id)
+                    .create(conn)
+                    .await?;
+            }
+        }
+        Ok(project)
+    }
+}
+
+impl ScenarioRecord {
+    async fn collect(
+        conn: &mut DbConnection,
+        scenario: Scenario,
+        timetable: &Timetable,
+    ) -> Result<Self> {
+        let infra_name = scenario.infra_name(conn).await?;
+        let electrical_profile_set = match timetable.electrical_profile_set_id {
+            Some(id) => ElectricalProfileSet::retrieve(conn, id)
+                .await?
+                .map(|set| set.name),
+            None => None,
+        };
+
+        let cadences = TrainScheduleCadence::list_for_timetable(conn, timetable.id).await?;
+        let cadence_indexes: HashMap<_, _> = cadences
+            .iter()
+            .enumerate()
+            .map(|(index, cadence)| (cadence.id, index))
+            .collect();
+        let timetable_id = timetable.id;
+        let trains = TrainSchedule::list(
+            conn,
+            SelectionSettings::new()
+                .filter(move || TrainSchedule::TIMETABLE_ID.eq(timetable_id))
+                .order_by(|| TrainSchedule::ID.asc()),
+        )
+        .await?;
+        let trains = trains
+            .into_iter()
+            .map(|train| {
+                let cadence = train.cadence_id.map(|id| cadence_indexes[&id]);
+                let cadence_departure = train.cadence_departure;
+                TrainRecord {
+                    train_schedule: TrainScheduleResult::from(train).train_schedule,
+                    cadence,
+                    cadence_departure,
+                }
+            })
+            .collect();
+        let cadences = cadences
+            .into_iter()
+            .map(|cadence| CadenceRecord {
+                template: cadence.template,
+                period: cadence.period,
+                window_start: cadence.window_start,
+                window_end: cadence.window_end,
+                name_pattern: cadence.name_pattern,
+            })
+            .collect();
+
+        Ok(Self {
+            name: scenario.name,
+            description: scenario.description,
+            tags: scenario.tags,
+            infra_name,
+            electrical_profile_set,
+            cadences,
+            trains,
+        })
+    }
+
+    /// Creates a timetable holding the cadences and the trains of the scenario
+    async fn restore_timetable(
+        &self,
+        conn: &mut DbConnection,
+        electrical_profile_set_id: Option<i64>,
+    ) -> Result<Timetable> {
+        let timetable = Timetable::changeset()
+            .electrical_profile_set_id(electrical_profile_set_id)
+            .create(conn)
+            .await?;
+
+        let mut cadence_ids = vec![];
+        for cadence in &self.cadences {
+            let cadence = TrainScheduleCadence::changeset()
+                .timetable_id(timetable.id)
+                .template(cadence.template.clone())
+                .period(cadence.period)
+                .window_start(cadence.window_start)
+                .window_end(cadence.window_end)
+                .name_pattern(cadence.name_pattern.clone())
+                .create(conn)
+                .await?;
+            cadence_ids.push(cadence.id);
+        }
+
+        let mut changesets = vec![];
+        for train in &self.trains {
+            let cadence_id = match train.cadence {
+                Some(index) => Some(
+                    *cadence_ids
+                        .get(index)
+                        .ok_or_else(|| invalid(format!("unknown cadence '{index}'")))?,
+                ),
+                None => None,
+            };
+            let changeset: TrainScheduleChangeset = TrainScheduleForm {
+                timetable_id: Some(timetable.id),
+                train_schedule: train.train_schedule.clone(),
+            }
+            .into();
+            changesets.push(
+                changeset
+                    .cadence_id(cadence_id)
+                    .cadence_departure(train.cadence_departure),
+            );
+        }
+        let _: Vec<_> = TrainSchedule::create_batch(conn, changesets).await?;
+        Ok(timetable)
+    }
+}
+
+/// The given name, followed by the first number which makes it unused by electrical profile sets
+async fn unused_electrical_profile_set_name(conn: &mut DbConnection, name: &str) -> Result<String> {
+    let mut candidate = name.to_owned();
+    for index in 2.. {
+        let taken = candidate.clone();
+        let count = ElectricalProfileSet::count(
+            conn,
+            SelectionSettings::new().filter(move || ElectricalProfileSet::NAME.eq(taken.clone())),
+        )
+        .await?;
+        if count == 0 {
+            break;
+        }
+        candidate = format!("{name} ({index})");
+    }
+    Ok(candidate)
+}
+
+/// The id of the latest infra with the given name
+async fn find_infra(conn: &mut DbConnection, infra_name: &str) -> Result<i64> {
+    let name = infra_name.to_owned();
+    let infras = Infra::list(
+        conn,
+        SelectionSettings::new()
+            .filter(move || Infra::NAME.eq(name.clone()))
+            .order_by(|| Infra::ID.desc())
+            .limit(1),
+    )
+    .await?;
+    infras
+        .into_iter()
+        .next()
+        .map(|infra| infra.id)
+        .ok_or_else(|| {
+            ProjectArchiveError::InfraNotFound {
+                infra_name: infra_name.to_owned(),
+            }
+            .into()
+        })
+}
+
+/// Export a project with its studies, scenarios and timetables as a portable archive
+///
+/// Only the scenarios of the v2 timetables are exported.
+/// Rolling stock revisions are local to an instance: trains pinned to a revision are exported unpinned.
+#[utoipa::path(
+    tag = "projects",
+    params(super::ProjectIdParam),
+    responses(
+        (status = 200, description = "The project archive", content_type = "application/zip"),
+        (status = 404, body = InternalError, description = "The requested project was not found"),
+    )
+)]
+#[get("/{project_id}/export")]
+async fn export(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    project: Path<i64>,
+) -> Result<HttpResponse> {
+    let project_id = project.into_inner();
+    let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check(conn, ResourceType::Project, project_id, Role::Viewer)
+        .await?;
+    let project =
+        Project::retrieve_or_fail(conn, project_id, || ProjectError::NotFound { project_id })
+            .await?;
+
+    let (archive, image) = ProjectArchive::collect(conn, project).await?;
+    let mut zip = Cursor::new(Vec::new());
+    archive.write(&mut zip, image.as_deref())?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/zip")
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"project_{project_id}.zip\""),
+        ))
+        .body(zip.into_inner()))
+}
+
+/// Import a project archive as a new project
+///
+/// Missing rolling stocks and electrical profile sets are created from the archive. The importer
+/// owns the project and the created rolling stocks. An electrical profile set whose name is taken by different data is imported
+/// under a new name.
+/// The infras of the scenarios must exist in this instance, and are looked up by name.
+#[utoipa::path(
+    tag = "projects",
+    request_body(content = [u8], content_type = "application/zip", description = "The project archive"),
+    responses(
+        (status = 201, body = ProjectWithStudies, description = "The imported project"),
+        (status = 400, body = InternalError, description = "The archive is invalid"),
+        (status = 404, body = InternalError, description = "The infra of a scenario was not found"),
+    )
+)]
+#[post("/import")]
+async fn import(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    data: Bytes,
+) -> Result<HttpResponse> {
+    let (archive, image) = ProjectArchive::read(&data)?;
+
+    let mut tx = db_pool.get().await?;
+    let authorizer = authentication.authorizer(&mut tx).await?;
+    let project = tx
+        .transaction::<_, InternalError, _>(|conn| {
+            async move { archive.restore(conn, &authorizer, image).await }.scope_boxed()
+        })
+        .await?;
+
+    let project_with_studies = ProjectWithStudyCount::try_fetch(&mut tx, project).await?;
+    Ok(HttpResponse::Created().json(project_with_studies))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rstest::rstest;
+
+    use super::*;
+    use crate::fixtures::tests::db_pool;
+    use crate::fixtures::tests::make_simple_train_schedule_v2;
+    use crate::fixtures::tests::scenario_v2_fixture_set;
+    use crate::fixtures::tests::ScenarioV2FixtureSet;
+
+    fn archive() -> ProjectArchive {
+        ProjectArchive {
+            version: ARCHIVE_VERSION,
+            project: ProjectRecord {
+                name: "project".into(),
+                objectives: Some("objectives".into()),
+                description: None,
+                funders: None,
+                budget: Some(42),
+                tags: Tags::new(vec!["tag".into()]),
+                image_content_type: Some("image/png".into()),
+            },
+            studies: vec![StudyRecord {
+                name: "study".into(),
+                description: None,
+                business_code: None,
+                service_code: None,
+                start_date: None,
+                expected_end_date: None,
+                actual_end_date: None,
+                budget: None,
+                tags: Tags::default(),
+                state: "started".into(),
+                study_type: None,
+                scenarios: vec![],
+            }],
+            rolling_stocks: vec![],
+            electrical_profile_sets: vec![],
+        }
+    }
+
+    #[test]
+    fn archive_round_trip() {
+        let mut zip = Cursor::new(Vec::new());
+        archive().write(&mut zip, Some(b"png")).unwrap();
+
+        let (archive, image) = ProjectArchive::read(&zip.into_inner()).unwrap();
+        assert_eq!(archive.project.name, "project");
+        assert_eq!(archive.project.budget, Some(42));
+        assert_eq!(archive.studies.len(), 1);
+        assert_eq!(archive.studies[0].state, "started");
+        assert_eq!(image.as_deref(), Some(&b"png"[..]));
+    }
+
+    #[test]
+    fn unsupported_archive_version() {
+        let mut archive = archive();
+        archive.version = ARCHIVE_VERSION + 1;
+        let mut zip = Cursor::new(Vec::new());
+        archive.write(&mut zip, Some(b"png")).unwrap();
+
+        let error = ProjectArchive::read(&zip.into_inner()).unwrap_err();
+        assert_eq!(
+            error.error_type,
+            "editoast:project_archive:UnsupportedVersion"
+        );
+    }
+
+    #[rstest]
+    async fn export_import_remaps_ids(
+        db_pool: Arc<DbConnectionPool>,
+        #[future] scenario_v2_fixture_set: ScenarioV2FixtureSet,
+    ) {
+        let fixtures = scenario_v2_fixture_set.await;
+        let timetable_id = fixtures.timetable.id();
+        let conn = &mut db_pool.get().await.unwrap();
+        let train = make_simple_train_schedule_v2(timetable_id, db_pool.clone()).await;
+        let template = TrainScheduleResult::from(train.model.clone()).train_schedule;
+        let cadence = TrainScheduleCadence::changeset()
+            .timetable_id(timetable_id)
+            .template(template.clone())
+            .period(3_600_000)
+            .window_start(template.start_time)
+            .window_end(template.start_time)
+            .name_pattern("cadence {n}".into())
+            .create(conn)
+            .await
+            .unwrap();
+        let cadence_train: TrainScheduleChangeset = TrainScheduleForm {
+            timetable_id: Some(timetable_id),
+            train_schedule: template,
+        }
+        .into();
+        let cadence_train = cadence_train
+            .cadence_id(Some(cadence.id))
+            .create(conn)
+            .await
+            .unwrap();
+
+        let (archive, image) = ProjectArchive::collect(conn, fixtures.project.model.clone())
+            .await
+            .unwrap();
+        let project = archive
+            .restore(conn, &Authorizer::new(None), image)
+            .await
+            .unwrap();
+
+        assert_ne!(project.id, fixtures.project.id());
+        let project_id = project.id;
+        let studies = Study::list(
+            conn,
+            SelectionSettings::new().filter(move || Study::PROJECT_ID.eq(project_id)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(studies.len(), 1);
+        assert_ne!(studies[0].id, fixtures.study.id());
+        let study_id = studies[0].id;
+        let scenarios = Scenario::list(
+            conn,
+            SelectionSettings::new().filter(move || Scenario::STUDY_ID.eq(study_id)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(scenarios.len(), 1);
+        assert_ne!(scenarios[0].id, fixtures.scenario.id());
+        let imported_timetable_id = scenarios[0].timetable_id;
+        assert_ne!(imported_timetable_id, timetable_id);
+
+        let cadences = TrainScheduleCadence::list_for_timetable(conn, imported_timetable_id)
+            .await
+            .unwrap();
+        assert_eq!(cadences.len(), 1);
+        assert_ne!(cadences[0].id, cadence.id);
+        let trains = TrainSchedule::list(
+            conn,
+            SelectionSettings::new()
+                .filter(move || TrainSchedule::TIMETABLE_ID.eq(imported_timetable_id)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(trains.len(), 2);
+        assert!(trains
+            .iter()
+            .all(|imported| imported.id != train.id() && imported.id != cadence_train.id));
+        let mut cadence_ids: Vec<_> = trains.iter().map(|train| train.cadence_id).collect();
+        cadence_ids.sort();
+        assert_eq!(cadence_ids, vec![None, Some(cadences[0].id)]);
+
+        Project::delete_static(conn, project.id).await.unwrap();
+        Timetable::delete_static(conn, imported_timetable_id)
+            .await
+            .unwrap();
+    }
+}
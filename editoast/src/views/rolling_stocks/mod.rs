@@ -1,4 +1,5 @@
 pub mod light_rolling_stock;
+mod revisions;
 pub mod rolling_stock_form;
 
 use std::io::BufReader;
@@ -18,6 +19,8 @@ use actix_web::web::Json;
 use actix_web::web::Path;
 use actix_web::web::Query;
 use actix_web::HttpResponse;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::AsyncConnection;
 use editoast_derive::EditoastError;
 use editoast_schemas::rolling_stock::RollingStockLivery;
 use editoast_schemas::rolling_stock::RollingStockLiveryMetadata;
@@ -37,13 +40,18 @@ use validator::Validate;
 
 use crate::error::InternalError;
 use crate::error::Result;
+use crate::modelsv2::consist::Consist;
 use crate::modelsv2::prelude::*;
+use crate::modelsv2::resource_grant::ResourceType;
+use crate::modelsv2::resource_grant::Role;
 use crate::modelsv2::rolling_stock_livery::RollingStockLiveryModel;
 use crate::modelsv2::rolling_stock_model::TrainScheduleScenarioStudyProject;
+use crate::modelsv2::DbConnection;
 use crate::modelsv2::DbConnectionPool;
 use crate::modelsv2::Document;
 use crate::modelsv2::RollingStockModel;
 use crate::modelsv2::RollingStockSeparatedImageModel;
+use crate::views::authz::Authentication;
 
 crate::routes! {
     "/rolling_stock" => {
@@ -55,6 +63,7 @@ crate::routes! {
             get_by_name,
         },
         "/{rolling_stock_id}" => {
+            revisions::routes(),
             get,
             update,
             delete,
@@ -117,6 +126,8 @@ pub enum RollingStockError {
     RollingStockIsUsed {
         rolling_stock_id: i64,
         usage: Vec<TrainScheduleScenarioStudyProject>,
+        /// The names of the consists the rolling stock is a unit of
+        consists: Vec<String>,
     },
     #[error("Base power class is an empty string")]
     #[editoast_error(status = 400)]
@@ -158,9 +169,22 @@ pub struct RollingStockNameParam {
 #[get("")]
 async fn get(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     path: Path<i64>,
 ) -> Result<Json<RollingStockWithLiveries>> {
     let rolling_stock_id = path.into_inner();
+    {
+        let conn = &mut db_pool.get().await?;
+        let authorizer = authentication.authorizer(conn).await?;
+        authorizer
+            .check(
+                conn,
+                ResourceType::RollingStock,
+                rolling_stock_id,
+                Role::Viewer,
+            )
+            .await?;
+    }
 
     let rolling_stock =
         retrieve_existing_rolling_stock(&db_pool, RollingStockKey::Id(rolling_stock_id)).await?;
@@ -179,12 +203,25 @@ async fn get(
 #[get("")]
 async fn get_by_name(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     path: Path<String>,
 ) -> Result<Json<RollingStockWithLiveries>> {
     let rolling_stock_name = path.into_inner();
     let rolling_stock =
         retrieve_existing_rolling_stock(&db_pool, RollingStockKey::Name(rolling_stock_name))
             .await?;
+    {
+        let conn = &mut db_pool.get().await?;
+        let authorizer = authentication.authorizer(conn).await?;
+        authorizer
+            .check(
+                conn,
+                ResourceType::RollingStock,
+                rolling_stock.id,
+                Role::Viewer,
+            )
+            .await?;
+    }
     let rolling_stock_with_liveries = rolling_stock.with_liveries(db_pool.into_inner()).await?;
     Ok(Json(rolling_stock_with_liveries))
 }
@@ -224,12 +261,15 @@ struct PostRollingStockQueryParams {
 #[post("")]
 async fn create(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     Json(rolling_stock_form): Json<RollingStockForm>,
     query_params: Query<PostRollingStockQueryParams>,
 ) -> Result<Json<RollingStockModel>> {
     rolling_stock_form.validate()?;
     let mut db_conn = db_pool.get().await?;
+    let authorizer = authentication.authorizer(&mut db_conn).await?;
     let rolling_stock_name = rolling_stock_form.name.clone();
+    assert_name_unused_by_consists(&mut db_conn, &rolling_stock_name).await?;
     let rolling_stock_changeset: Changeset<RollingStockModel> = rolling_stock_form.into();
 
     let rolling_stock = rolling_stock_changeset
@@ -238,6 +278,10 @@ async fn create(
         .create(&mut db_conn)
         .await
         .map_err(|e| map_diesel_error(e, rolling_stock_name))?;
+    authorizer
+        .grant_owner(&mut db_conn, ResourceType::RollingStock, rolling_stock.id)
+        .await?;
+    rolling_stock.archive(&mut db_conn).await?;
 
     Ok(Json(rolling_stock))
 }
@@ -253,6 +297,7 @@ async fn create(
 #[patch("")]
 async fn update(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     path: Path<i64>,
     Json(rolling_stock_form): Json<RollingStockForm>,
 ) -> Result<Json<RollingStockWithLiveries>> {
@@ -260,32 +305,54 @@ async fn update(
     let mut conn = db_pool.get().await?;
 
     let rolling_stock_id = path.into_inner();
+    let authorizer = authentication.authorizer(&mut conn).await?;
+    authorizer
+        .check(
+            &mut conn,
+            ResourceType::RollingStock,
+            rolling_stock_id,
+            Role::Editor,
+        )
+        .await?;
     let name = rolling_stock_form.name.clone();
 
-    let previous_rolling_stock =
-        RollingStockModel::retrieve_or_fail(&mut conn, rolling_stock_id, || {
-            RollingStockError::KeyNotFound {
-                rolling_stock_key: RollingStockKey::Id(rolling_stock_id),
+    // The revisions are only archived along with the update they belong to
+    let new_rolling_stock = conn
+        .transaction::<_, InternalError, _>(|conn| {
+            async move {
+                let previous_rolling_stock =
+                    RollingStockModel::retrieve_or_fail(conn, rolling_stock_id, || {
+                        RollingStockError::KeyNotFound {
+                            rolling_stock_key: RollingStockKey::Id(rolling_stock_id),
+                        }
+                    })
+                    .await?;
+                assert_rolling_stock_unlocked(&previous_rolling_stock)?;
+                assert_name_unused_by_consists(conn, &name).await?;
+                previous_rolling_stock.archive(conn).await?;
+
+                let mut new_rolling_stock =
+                    Into::<Changeset<RollingStockModel>>::into(rolling_stock_form)
+                        .update(conn, rolling_stock_id)
+                        .await
+                        .map_err(|e| map_diesel_error(e, name.clone()))?
+                        .ok_or(RollingStockError::KeyNotFound {
+                            rolling_stock_key: RollingStockKey::Id(rolling_stock_id),
+                        })?;
+
+                if new_rolling_stock != previous_rolling_stock {
+                    new_rolling_stock.version += 1;
+                    new_rolling_stock
+                        .save(conn)
+                        .await
+                        .map_err(|err| map_diesel_error(err, name))?;
+                    new_rolling_stock.archive(conn).await?;
+                }
+                Ok(new_rolling_stock)
             }
+            .scope_boxed()
         })
         .await?;
-    assert_rolling_stock_unlocked(&previous_rolling_stock)?;
-
-    let mut new_rolling_stock = Into::<Changeset<RollingStockModel>>::into(rolling_stock_form)
-        .update(&mut conn, rolling_stock_id)
-        .await
-        .map_err(|e| map_diesel_error(e, name.clone()))?
-        .ok_or(RollingStockError::KeyNotFound {
-            rolling_stock_key: RollingStockKey::Id(rolling_stock_id),
-        })?;
-
-    if new_rolling_stock != previous_rolling_stock {
-        new_rolling_stock.version += 1;
-        new_rolling_stock
-            .save(&mut conn)
-            .await
-            .map_err(|err| map_diesel_error(err, name))?;
-    }
 
     Ok(Json(
         new_rolling_stock
@@ -294,6 +361,17 @@ async fn update(
     ))
 }
 
+/// Train schedules refer to rolling stocks and consists by name, which must be unambiguous
+async fn assert_name_unused_by_consists(conn: &mut DbConnection, name: &str) -> Result<()> {
+    if Consist::exists(conn, name.to_owned()).await? {
+        return Err(RollingStockError::NameAlreadyUsed {
+            name: name.to_owned(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
 #[derive(Deserialize, IntoParams, ToSchema)]
 struct DeleteRollingStockQueryParams {
     /// force the deletion even if it’s used
@@ -314,10 +392,23 @@ struct DeleteRollingStockQueryParams {
 #[delete("")]
 async fn delete(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     path: Path<i64>,
     params: Query<DeleteRollingStockQueryParams>,
 ) -> Result<HttpResponse> {
     let rolling_stock_id = path.into_inner();
+    {
+        let conn = &mut db_pool.get().await?;
+        let authorizer = authentication.authorizer(conn).await?;
+        authorizer
+            .check(
+                conn,
+                ResourceType::RollingStock,
+                rolling_stock_id,
+                Role::Owner,
+            )
+            .await?;
+    }
     assert_rolling_stock_unlocked(
         &retrieve_existing_rolling_stock(&db_pool, RollingStockKey::Id(rolling_stock_id)).await?,
     )?;
@@ -327,12 +418,26 @@ async fn delete(
     }
 
     let trains = get_rolling_stock_usage(db_pool.clone(), rolling_stock_id).await?;
-    if trains.is_empty() {
+    let consists: Vec<_> = {
+        let conn = &mut db_pool.get().await?;
+        Consist::list(
+            conn,
+            SelectionSettings::new()
+                .filter(move || Consist::with_unit(rolling_stock_id))
+                .order_by(|| Consist::NAME.asc()),
+        )
+        .await?
+        .into_iter()
+        .map(|consist| consist.name)
+        .collect()
+    };
+    if trains.is_empty() && consists.is_empty() {
         return delete_rolling_stock(db_pool.clone(), rolling_stock_id).await;
     }
     Err(RollingStockError::RollingStockIsUsed {
         rolling_stock_id,
         usage: trains,
+        consists,
     }
     .into())
 }
@@ -369,12 +474,22 @@ struct RollingStockLockedUpdateForm {
 #[patch("")]
 async fn update_locked(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     rolling_stock_id: Path<i64>,
     data: Json<RollingStockLockedUpdateForm>,
 ) -> Result<HttpResponse> {
     let mut db_conn = db_pool.get().await?;
     let rolling_stock_locked_update_form = data.into_inner();
     let rolling_stock_id = rolling_stock_id.into_inner();
+    let authorizer = authentication.authorizer(&mut db_conn).await?;
+    authorizer
+        .check(
+            &mut db_conn,
+            ResourceType::RollingStock,
+            rolling_stock_id,
+            Role::Owner,
+        )
+        .await?;
 
     // FIXME: check that the rolling stock exists (the Option<RollingSrtockModel> is ignored here)
     RollingStockModel::changeset()
@@ -421,12 +536,22 @@ async fn get_rolling_stock_usage(
 #[post("")]
 async fn create_livery(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     rolling_stock_id: Path<i64>,
     MultipartForm(form): MultipartForm<RollingStockLiveryCreateForm>,
 ) -> Result<Json<RollingStockLivery>> {
     let mut conn = db_pool.get().await?;
 
     let rolling_stock_id = rolling_stock_id.into_inner();
+    let authorizer = authentication.authorizer(&mut conn).await?;
+    authorizer
+        .check(
+            &mut conn,
+            ResourceType::RollingStock,
+            rolling_stock_id,
+            Role::Editor,
+        )
+        .await?;
 
     let formatted_images = format_images(form.images)?;
 
@@ -1039,7 +1164,8 @@ pub mod tests {
             response,
             RollingStockError::RollingStockIsUsed {
                 rolling_stock_id,
-                usage: expected_usage
+                usage: expected_usage,
+                consists: vec![]
             }
         )
     }
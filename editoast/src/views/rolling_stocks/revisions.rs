@@ -0,0 +1,406 @@
+use actix_web::get;
+use actix_web::post;
+use actix_web::web::Data;
+use actix_web::web::Json;
+use actix_web::web::Path;
+use actix_web::web::Query;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::AsyncConnection;
+use editoast_derive::EditoastError;
+use editoast_schemas::rolling_stock::RollingStock;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+use utoipa::IntoParams;
+use utoipa::ToSchema;
+
+use super::assert_name_unused_by_consists;
+use super::assert_rolling_stock_unlocked;
+use super::map_diesel_error;
+use super::RollingStockError;
+use super::RollingStockKey;
+use super::RollingStockWithLiveries;
+use crate::error::InternalError;
+use crate::error::Result;
+use crate::modelsv2::prelude::*;
+use crate::modelsv2::resource_grant::ResourceType;
+use crate::modelsv2::resource_grant::Role;
+use crate::modelsv2::rolling_stock_revision::RollingStockFieldDiff;
+use crate::modelsv2::rolling_stock_revision::RollingStockRevision;
+use crate::modelsv2::DbConnection;
+use crate::modelsv2::DbConnectionPool;
+use crate::modelsv2::RollingStockModel;
+use crate::views::authz::Authentication;
+use crate::views::pagination::PaginatedList as _;
+use crate::views::pagination::PaginationQueryParam;
+use crate::views::pagination::PaginationStats;
+
+crate::routes! {
+    "/revisions" => {
+        list,
+        "/{version}" => {
+            get,
+            restore,
+            "/diff/{other_version}" => {
+                diff,
+            },
+        },
+    },
+}
+
+#[derive(Debug, Error, EditoastError)]
+#[editoast_error(base_id = "rollingstocks:revisions")]
+enum RevisionError {
+    #[error("Rolling stock '{rolling_stock_id}' has no revision at version '{version}'")]
+    #[editoast_error(status = 404)]
+    NotFound { rolling_stock_id: i64, version: i64 },
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+struct RollingStockRevisionIdParam {
+    /// An existing rolling stock ID
+    rolling_stock_id: i64,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+struct RollingStockVersionParam {
+    /// An existing rolling stock ID
+    rolling_stock_id: i64,
+    /// The version of the rolling stock archived by the revision
+    version: i64,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+struct RollingStockVersionsParam {
+    /// An existing rolling stock ID
+    rolling_stock_id: i64,
+    /// The version of the base revision
+    version: i64,
+    /// The version of the revision to compare the base revision with
+    other_version: i64,
+}
+
+async fn check_role(
+    conn: &mut DbConnection,
+    authentication: Authentication,
+    rolling_stock_id: i64,
+    role: Role,
+) -> Result<()> {
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check(conn, ResourceType::RollingStock, rolling_stock_id, role)
+        .await
+}
+
+async fn retrieve_revision(
+    conn: &mut DbConnection,
+    rolling_stock_id: i64,
+    version: i64,
+) -> Result<RollingStockRevision> {
+    let revision = RollingStockRevision::retrieve_version(conn, rolling_stock_id, version)
+        .await?
+        .ok_or(RevisionError::NotFound {
+            rolling_stock_id,
+            version,
+        })?;
+    Ok(revision)
+}
+
+#[derive(Serialize, ToSchema)]
+struct RollingStockRevisionListResponse {
+    results: Vec<RollingStockRevision>,
+    #[serde(flatten)]
+    stats: PaginationStats,
+}
+
+/// Returns the paginated revisions of a rolling stock, most recent first
+#[utoipa::path(
+    tag = "rolling_stock",
+    params(RollingStockRevisionIdParam, PaginationQueryParam),
+    responses(
+        (status = 200, body = inline(RollingStockRevisionListResponse), description = "The revisions of the rolling stock"),
+        (status = 404, description = "The rolling stock was not found"),
+    )
+)]
+#[get("")]
+async fn list(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    path: Path<RollingStockRevisionIdParam>,
+    Query(pagination_params): Query<PaginationQueryParam>,
+) -> Result<Json<RollingStockRevisionListResponse>> {
+    let rolling_stock_id = path.rolling_stock_id;
+    let conn = &mut db_pool.get().await?;
+    check_role(conn, authentication, rolling_stock_id, Role::Viewer).await?;
+    if !RollingStockModel::exists(conn, rolling_stock_id).await? {
+        return Err(RollingStockError::KeyNotFound {
+            rolling_stock_key: RollingStockKey::Id(rolling_stock_id),
+        }
+        .into());
+    }
+
+    let settings = pagination_params
+        .validate(1000)?
+        .warn_page_size(100)
+        .into_selection_settings()
+        .filter(move || RollingStockRevision::ROLLING_STOCK_ID.eq(rolling_stock_id))
+        .order_by(|| RollingStockRevision::VERSION.desc());
+    let (results, stats) = RollingStockRevision::list_paginated(conn, settings).await?;
+    Ok(Json(RollingStockRevisionListResponse { results, stats }))
+}
+
+/// Returns the revision archiving a version of a rolling stock
+#[utoipa::path(
+    tag = "rolling_stock",
+    params(RollingStockVersionParam),
+    responses(
+        (status = 200, body = RollingStockRevision, description = "The revision"),
+        (status = 404, description = "The rolling stock or the revision was not found"),
+    )
+)]
+#[get("")]
+async fn get(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    path: Path<RollingStockVersionParam>,
+) -> Result<Json<RollingStockRevision>> {
+    let RollingStockVersionParam {
+        rolling_stock_id,
+        version,
+    } = path.into_inner();
+    let conn = &mut db_pool.get().await?;
+    check_role(conn, authentication, rolling_stock_id, Role::Viewer).await?;
+    let revision = retrieve_revision(conn, rolling_stock_id, version).await?;
+    Ok(Json(revision))
+}
+
+/// Compare two revisions of a rolling stock
+///
+/// Lists the railjson fields whose value differs between the base revision and the other one.
+#[utoipa::path(
+    tag = "rolling_stock",
+    params(RollingStockVersionsParam),
+    responses(
+        (status = 200, body = Vec<RollingStockFieldDiff>, description = "The modified fields, sorted by name"),
+        (status = 404, description = "The rolling stock or one of the revisions was not found"),
+    )
+)]
+#[get("")]
+async fn diff(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    path: Path<RollingStockVersionsParam>,
+) -> Result<Json<Vec<RollingStockFieldDiff>>> {
+    let RollingStockVersionsParam {
+        rolling_stock_id,
+        version,
+        other_version,
+    } = path.into_inner();
+    let conn = &mut db_pool.get().await?;
+    check_role(conn, authentication, rolling_stock_id, Role::Viewer).await?;
+    let revision = retrieve_revision(conn, rolling_stock_id, version).await?;
+    let other_revision = retrieve_revision(conn, rolling_stock_id, other_version).await?;
+    Ok(Json(revision.diff(&other_revision)))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct RestoreQueryParams {
+    /// Also restore the name the rolling stock had at this version
+    #[serde(default)]
+    restore_name: bool,
+}
+
+/// Restore a revision of a rolling stock
+///
+/// The archived definition replaces the current one as a new version of the rolling stock,
+/// unless they are already identical. The rolling stock keeps its current name, unless
+/// `restore_name` is set.
+#[utoipa::path(
+    tag = "rolling_stock",
+    params(RollingStockVersionParam, RestoreQueryParams),
+    responses(
+        (status = 200, body = RollingStockWithLiveries, description = "The restored rolling stock"),
+        (status = 400, description = "The rolling stock is locked"),
+        (status = 404, description = "The rolling stock or the revision was not found"),
+    )
+)]
+#[post("/restore")]
+async fn restore(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    path: Path<RollingStockVersionParam>,
+    Query(RestoreQueryParams { restore_name }): Query<RestoreQueryParams>,
+) -> Result<Json<RollingStockWithLiveries>> {
+    let RollingStockVersionParam {
+        rolling_stock_id,
+        version,
+    } = path.into_inner();
+    let mut conn = db_pool.get().await?;
+    check_role(&mut conn, authentication, rolling_stock_id, Role::Editor).await?;
+    let rolling_stock = conn
+        .transaction::<_, InternalError, _>(|conn| {
+            async move {
+                let mut rolling_stock =
+                    RollingStockModel::retrieve_or_fail(conn, rolling_stock_id, || {
+                        RollingStockError::KeyNotFound {
+                            rolling_stock_key: RollingStockKey::Id(rolling_stock_id),
+                        }
+                    })
+                    .await?;
+                assert_rolling_stock_unlocked(&rolling_stock)?;
+                let mut archived = retrieve_revision(conn, rolling_stock_id, version)
+                    .await?
+                    .rolling_stock;
+                if restore_name {
+                    assert_name_unused_by_consists(conn, &archived.name).await?;
+                } else {
+                    archived.name = rolling_stock.name.clone();
+                }
+
+                if RollingStock::from(rolling_stock.clone()) != archived {
+                    rolling_stock.archive(conn).await?;
+                    let name = archived.name.clone();
+                    let next_version = rolling_stock.version + 1;
+                    rolling_stock = Changeset::<RollingStockModel>::from(archived)
+                        .version(next_version)
+                        .update(conn, rolling_stock_id)
+                        .await
+                        .map_err(|e| map_diesel_error(e, name))?
+                        .ok_or(RollingStockError::KeyNotFound {
+                            rolling_stock_key: RollingStockKey::Id(rolling_stock_id),
+                        })?;
+                    rolling_stock.archive(conn).await?;
+                }
+                Ok(rolling_stock)
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    Ok(Json(
+        rolling_stock.with_liveries(db_pool.into_inner()).await?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::call_and_read_body_json;
+    use actix_web::test::call_service;
+    use actix_web::test::TestRequest;
+    use rstest::rstest;
+    use serde_json::json;
+    use serde_json::Value as JsonValue;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::assert_response_error_type_match;
+    use crate::fixtures::tests::db_pool;
+    use crate::fixtures::tests::named_fast_rolling_stock;
+    use crate::views::rolling_stocks::rolling_stock_form::RollingStockForm;
+    use crate::views::tests::create_test_service;
+
+    #[rstest]
+    async fn update_is_archived_and_restored(db_pool: Arc<DbConnectionPool>) {
+        // GIVEN
+        let app = create_test_service().await;
+        let name = "fast_rolling_stock_update_is_archived_and_restored";
+        let rolling_stock = named_fast_rolling_stock(name, db_pool.clone()).await;
+        let rolling_stock_id = rolling_stock.id();
+        let mut form: RollingStockForm = rolling_stock.model.clone().into();
+        form.max_speed += 10.;
+        let req = TestRequest::patch()
+            .uri(format!("/rolling_stock/{rolling_stock_id}").as_str())
+            .set_json(&form)
+            .to_request();
+        call_service(&app, req).await;
+
+        // WHEN
+        let req = TestRequest::get()
+            .uri(format!("/rolling_stock/{rolling_stock_id}/revisions").as_str())
+            .to_request();
+        let revisions: JsonValue = call_and_read_body_json(&app, req).await;
+        let req = TestRequest::get()
+            .uri(format!("/rolling_stock/{rolling_stock_id}/revisions/0/diff/1").as_str())
+            .to_request();
+        let diff: Vec<RollingStockFieldDiff> = call_and_read_body_json(&app, req).await;
+        let req = TestRequest::post()
+            .uri(format!("/rolling_stock/{rolling_stock_id}/revisions/0/restore").as_str())
+            .to_request();
+        let restored: JsonValue = call_and_read_body_json(&app, req).await;
+
+        // THEN
+        assert_eq!(revisions["count"], 2);
+        assert_eq!(revisions["results"][0]["version"], 1);
+        assert_eq!(
+            diff,
+            vec![RollingStockFieldDiff {
+                field: "max_speed".to_string(),
+                from: json!(rolling_stock.model.max_speed),
+                to: json!(form.max_speed),
+            }]
+        );
+        assert_eq!(restored["version"], 2);
+        assert_eq!(restored["max_speed"], json!(rolling_stock.model.max_speed));
+        let mut conn = db_pool.get().await.unwrap();
+        let revision = RollingStockRevision::retrieve_version(&mut conn, rolling_stock_id, 2)
+            .await
+            .unwrap()
+            .expect("the restored version should be archived");
+        assert_eq!(
+            revision.rolling_stock.max_speed,
+            rolling_stock.model.max_speed
+        );
+    }
+
+    #[rstest]
+    async fn restore_keeps_the_current_name(db_pool: Arc<DbConnectionPool>) {
+        let app = create_test_service().await;
+        let name = "fast_rolling_stock_restore_keeps_the_current_name";
+        let rolling_stock = named_fast_rolling_stock(name, db_pool).await;
+        let rolling_stock_id = rolling_stock.id();
+        let mut form: RollingStockForm = rolling_stock.model.clone().into();
+        form.name = format!("{name}_renamed");
+        form.max_speed += 10.;
+        let req = TestRequest::patch()
+            .uri(format!("/rolling_stock/{rolling_stock_id}").as_str())
+            .set_json(&form)
+            .to_request();
+        call_service(&app, req).await;
+
+        let restore = |query: &str| {
+            TestRequest::post()
+                .uri(
+                    format!("/rolling_stock/{rolling_stock_id}/revisions/0/restore{query}")
+                        .as_str(),
+                )
+                .to_request()
+        };
+        let restored: JsonValue = call_and_read_body_json(&app, restore("")).await;
+        assert_eq!(restored["name"], json!(form.name));
+        assert_eq!(restored["max_speed"], json!(rolling_stock.model.max_speed));
+        let restored: JsonValue =
+            call_and_read_body_json(&app, restore("?restore_name=true")).await;
+        assert_eq!(restored["name"], json!(name));
+    }
+
+    #[rstest]
+    async fn unknown_revision_is_not_found(db_pool: Arc<DbConnectionPool>) {
+        let app = create_test_service().await;
+        let rolling_stock =
+            named_fast_rolling_stock("fast_rolling_stock_unknown_revision_is_not_found", db_pool)
+                .await;
+        let rolling_stock_id = rolling_stock.id();
+        let req = TestRequest::get()
+            .uri(format!("/rolling_stock/{rolling_stock_id}/revisions/42").as_str())
+            .to_request();
+        let response = call_service(&app, req).await;
+        assert_response_error_type_match!(
+            response,
+            RevisionError::NotFound {
+                rolling_stock_id,
+                version: 42,
+            }
+        );
+    }
+}
@@ -33,10 +33,13 @@ use crate::models::ScenarioWithCountTrains;
 use crate::models::ScenarioWithDetails;
 use crate::models::Timetable;
 use crate::models::Update;
+use crate::modelsv2::resource_grant::ResourceType;
+use crate::modelsv2::resource_grant::Role;
 use crate::modelsv2::DbConnection;
 use crate::modelsv2::DbConnectionPool;
 use crate::modelsv2::Project;
 use crate::modelsv2::Study;
+use crate::views::authz::Authentication;
 use crate::views::pagination::PaginatedResponse;
 use crate::views::pagination::PaginationQueryParam;
 use crate::views::projects::ProjectError;
@@ -177,12 +180,17 @@ pub async fn check_project_study_conn(
 #[post("")]
 async fn create(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     data: Json<ScenarioCreateForm>,
     path: Path<(i64, i64)>,
 ) -> Result<Json<ScenarioResponse>> {
     let (project_id, study_id) = path.into_inner();
 
     let mut conn = db_pool.get().await?;
+    let authorizer = authentication.authorizer(&mut conn).await?;
+    authorizer
+        .check(&mut conn, ResourceType::Study, study_id, Role::Editor)
+        .await?;
     // Check if the project and the study exist
     let (mut project, mut study) =
         check_project_study_conn(&mut conn, project_id, study_id).await?;
@@ -238,9 +246,17 @@ pub struct ScenarioIdParam {
 async fn delete(
     path: Path<(i64, i64, i64)>,
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
 ) -> Result<HttpResponse> {
     let (project_id, study_id, scenario_id) = path.into_inner();
 
+    {
+        let conn = &mut db_pool.get().await?;
+        let authorizer = authentication.authorizer(conn).await?;
+        authorizer
+            .check(conn, ResourceType::Scenario, scenario_id, Role::Owner)
+            .await?;
+    }
     let db_pool = db_pool.into_inner();
     // Check if the project and the study exist
     let (mut project, mut study) = check_project_study(db_pool.clone(), project_id, study_id)
@@ -297,10 +313,15 @@ async fn patch(
     data: Json<ScenarioPatchForm>,
     path: Path<(i64, i64, i64)>,
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
 ) -> Result<Json<ScenarioResponse>> {
     let (project_id, study_id, scenario_id) = path.into_inner();
 
     let mut conn = db_pool.get().await?;
+    let authorizer = authentication.authorizer(&mut conn).await?;
+    authorizer
+        .check(&mut conn, ResourceType::Scenario, scenario_id, Role::Editor)
+        .await?;
 
     let (project, study, scenario) = conn
         .transaction::<_, InternalError, _>(|conn| {
@@ -341,9 +362,17 @@ async fn patch(
 #[get("")]
 async fn get(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     path: Path<(i64, i64, i64)>,
 ) -> Result<Json<ScenarioResponse>> {
     let (project_id, study_id, scenario_id) = path.into_inner();
+    {
+        let conn = &mut db_pool.get().await?;
+        let authorizer = authentication.authorizer(conn).await?;
+        authorizer
+            .check(conn, ResourceType::Scenario, scenario_id, Role::Viewer)
+            .await?;
+    }
     let db_pool = db_pool.into_inner();
     let (project, study) = check_project_study(db_pool.clone(), project_id, study_id).await?;
 
@@ -15,7 +15,7 @@ use crate::error::Result;
 ///
 /// Functions of the [QueryContext] consume [TypedAst]s  as argument(s) and
 /// produce another [TypedAst]. See [QueryContext::def_function].
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum TypedAst {
     Null,
     Boolean(bool),
@@ -103,6 +103,8 @@ pub enum ProcessingError {
     #[error("expected value of type {expected}, but got ersatz '{value}'")]
     #[editoast_error(no_context)]
     UnexpectedErsatz { value: String, expected: TypeSpec },
+    #[error("invalid GeoJSON geometry: {message}")]
+    InvalidGeoJson { message: String },
 }
 
 pub type QueryFunctionFn = Rc<dyn Fn(Vec<TypedAst>) -> Result<TypedAst>>;
@@ -191,6 +191,23 @@
 //! The SQL request is now complete and ready to be executed in Postgres.
 //! The resulting table of the request will then be converted to a JSON array of
 //! mappings that constitutes the payload of the HTTP response.
+//!
+//! # Ordering
+//!
+//! The payload may also contain an `order_by` expression, evaluated the same way
+//! as the query but expected to produce a number or a string. The results are
+//! sorted by that value in ascending order. For instance, to find the signals
+//! around a point of the map, from the closest to the farthest:
+//!
+//! ```yaml
+//! {
+//!     "object": "signal",
+//!     "query": ["and",
+//!                 ["=", ["infra_id"], 2],
+//!                 ["distance_lt", ["geographic"], 2.35, 48.85, 500]],
+//!     "order_by": ["nearest", ["geographic"], 2.35, 48.85]
+//! }
+//! ```
 
 // TODO: the documentation of this file needs to be updated (no more search.yml)
 
@@ -252,6 +269,8 @@ enum SearchError {
     ObjectType { object_type: String },
     #[error("query has type '{query_type}' but Boolean is expected")]
     QueryAst { query_type: String },
+    #[error("order_by has type '{query_type}' but Integer, Float or String is expected")]
+    OrderByAst { query_type: String },
 }
 
 impl SearchConfig {
@@ -304,6 +323,13 @@ pub struct SearchPayload {
     /// The query to run
     #[schema(value_type = SearchQuery)]
     query: JsonValue,
+    /// An expression to sort the results by, in ascending order
+    ///
+    /// For instance `["nearest", ["geographic"], 2.35, 48.85]` sorts the results
+    /// from the closest to the farthest from a point.
+    #[schema(value_type = Option<SearchQuery>)]
+    #[serde(default)]
+    order_by: Option<JsonValue>,
     /// Whether to return the SQL query instead of executing it
     #[serde(default)]
     dry: bool,
@@ -311,6 +337,7 @@ pub struct SearchPayload {
 
 fn create_sql_query(
     query: JsonValue,
+    order_by: Option<JsonValue>,
     search_config: &SearchConfig,
     limit: i64,
     offset: i64,
@@ -330,17 +357,48 @@ fn create_sql_query(
     let result_columns = search_config.result_columns();
     let mut bindings = Default::default();
     let constraints = where_expression.to_sql(&mut bindings);
-    let sql_code = format!(
-        "WITH _RESULT AS (
-            SELECT {result_columns}
-            FROM {table}
-            {joins}
-            WHERE {constraints}
-            LIMIT {limit} OFFSET {offset}
+    let sql_code = if let Some(order_by) = order_by {
+        let ast = SearchAst::build_ast(order_by)?;
+        let order_by_type = context.typecheck_search_query(&ast)?;
+        let orderable = TypeSpec::or(
+            TypeSpec::or(AstType::Integer, AstType::Float),
+            AstType::String,
+        );
+        if !orderable.is_supertype_spec(&order_by_type) {
+            return Err(SearchError::OrderByAst {
+                query_type: order_by_type.to_string(),
+            }
+            .into());
+        }
+        let order_expression = context.search_ast_to_sql(&ast)?.to_sql(&mut bindings);
+        // The ordering key is computed alongside the result columns so that the
+        // outer query, which cannot see the search table, can sort by it too
+        format!(
+            "WITH _RESULT AS (
+                SELECT {result_columns}, ({order_expression}) AS \"_order\"
+                FROM {table}
+                {joins}
+                WHERE {constraints}
+                ORDER BY \"_order\"
+                LIMIT {limit} OFFSET {offset}
+            )
+            SELECT to_jsonb(_RESULT) - '_order' AS result
+            FROM _RESULT
+            ORDER BY \"_order\""
         )
-        SELECT to_jsonb(_RESULT) AS result
-        FROM _RESULT"
-    );
+    } else {
+        format!(
+            "WITH _RESULT AS (
+                SELECT {result_columns}
+                FROM {table}
+                {joins}
+                WHERE {constraints}
+                LIMIT {limit} OFFSET {offset}
+            )
+            SELECT to_jsonb(_RESULT) AS result
+            FROM _RESULT"
+        )
+    };
     let mut sql_query = sql_query(sql_code).into_boxed();
     for string in bindings {
         sql_query = sql_query.bind::<Text, _>(string.to_owned());
@@ -361,6 +419,7 @@ struct SearchDBResult {
 ///     {
 ///         "object": string,
 ///         "query": query,
+///         "order_by": query, # optional
 ///         "dry": boolean, # default: false
 ///     }
 ///
@@ -368,6 +427,8 @@ struct SearchDBResult {
 /// - `object` can be any search object declared in `search.yml`
 /// - `query` is a JSON document which can be deserialized into a [SearchAst].
 ///   Check out examples below.
+/// - `order_by` is an optional expression of the same language evaluating to a
+///   number or a string, by which the results are sorted in ascending order.
 ///
 /// # Response
 ///
@@ -393,6 +454,8 @@ struct SearchDBResult {
 ///   `["or", ["search", ["name"], "Paris"], ["search", ["name"], "Lyon"]]`
 /// * All railway stations with "Paris" in their name but not PNO :
 ///   `["and", ["search", ["name"], "Paris"], ["not", ["=", ["trigram"], "pno"]]]`
+/// * The signals drawn inside a rectangle of the map:
+///   `["within_bbox", ["geographic"], 2.2, 48.8, 2.4, 48.9]`
 ///
 /// See [SearchAst] for a more detailed view of the query language.
 #[utoipa::path(
@@ -410,13 +473,18 @@ pub async fn search(
     db_pool: Data<DbConnectionPool>,
 ) -> Result<impl Responder> {
     let (page, per_page) = query_params.validate(1000)?.warn_page_size(100).unpack();
-    let Json(SearchPayload { object, query, dry }) = payload;
+    let Json(SearchPayload {
+        object,
+        query,
+        order_by,
+        dry,
+    }) = payload;
     let search_config =
         SearchConfigFinder::find(&object).ok_or_else(|| SearchError::ObjectType {
             object_type: object.to_owned(),
         })?;
     let offset = (page - 1) * per_page;
-    let sql = create_sql_query(query, &search_config, per_page, offset)?;
+    let sql = create_sql_query(query, order_by, &search_config, per_page, offset)?;
 
     if dry {
         let query = diesel::debug_query::<Pg, _>(&sql).to_string();
@@ -428,3 +496,137 @@ pub async fn search(
     let results: Vec<_> = objects.into_iter().map(|r| r.result).collect();
     Ok(HttpResponse::Ok().json(results))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::Utc;
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::*;
+    use crate::fixtures::tests::db_pool;
+    use crate::fixtures::tests::TestFixture;
+    use crate::modelsv2::Model;
+    use crate::modelsv2::Project;
+    use crate::modelsv2::Tags;
+
+    fn sql_of(object: &str, query: JsonValue, order_by: Option<JsonValue>) -> Result<String> {
+        let search_config = SearchConfigFinder::find(object).expect("unknown search object");
+        let sql = create_sql_query(query, order_by, &search_config, 10, 0)?;
+        Ok(diesel::debug_query::<Pg, _>(&sql).to_string())
+    }
+
+    #[test]
+    fn order_by_sorts_on_a_hidden_column() {
+        let sql = sql_of("project", json!(["=", ["id"], 1]), Some(json!(["name"]))).unwrap();
+        assert!(sql.contains("AS \"_order\""));
+        assert!(sql.contains("to_jsonb(_RESULT) - '_order' AS result"));
+        assert!(sql.contains("ORDER BY \"_order\""));
+
+        let sql = sql_of("project", json!(["=", ["id"], 1]), None).unwrap();
+        assert!(!sql.contains("_order"));
+    }
+
+    #[test]
+    fn order_by_must_be_orderable() {
+        let error = sql_of(
+            "project",
+            json!(["=", ["id"], 1]),
+            Some(json!(["=", ["id"], 1])),
+        )
+        .unwrap_err();
+        assert_eq!(error.error_type, "editoast:search:OrderByAst");
+    }
+
+    #[test]
+    fn train_schedules_are_searched_within_a_timetable() {
+        let sql = sql_of(
+            "trainschedule",
+            json!([
+                "and",
+                ["=", ["timetable_id"], 3],
+                ["search", ["train_name"], "tgv"]
+            ]),
+            None,
+        )
+        .unwrap();
+        assert!(sql.contains("FROM search_train_schedule_v2"));
+        assert!(sql.contains("(\"search_train_schedule_v2\".\"timetable_id\") = (3)"));
+        assert!(sql.contains("\"search_train_schedule_v2\".\"train_name\""));
+    }
+
+    #[rstest]
+    #[case::switch("switch", "search_switch")]
+    #[case::speed_section("speedsection", "search_speed_section")]
+    fn infra_objects_are_searched_by_line_name(#[case] object: &str, #[case] table: &str) {
+        let sql = sql_of(
+            object,
+            json!([
+                "and",
+                ["=", ["infra_id"], 2],
+                ["search", ["line_name"], "Paris"]
+            ]),
+            None,
+        )
+        .unwrap();
+        assert!(sql.contains(&format!("FROM {table}")));
+        assert!(sql.contains(&format!("(\"{table}\".\"infra_id\") = (2)")));
+        assert!(sql.contains(&format!(
+            "(\"{table}\".\"line_name\") ILIKE (osrd_to_ilike_search(($1)))"
+        )));
+        assert!(sql.contains("\"Paris\""));
+    }
+
+    #[test]
+    fn rolling_stocks_are_searched_by_traction_modes() {
+        let sql = sql_of(
+            "rollingstock",
+            json!(["contains", ["list", "1500V"], ["traction_modes"]]),
+            None,
+        )
+        .unwrap();
+        assert!(sql.contains("FROM search_rolling_stock"));
+        assert!(
+            sql.contains("(ARRAY[($1)]::TEXT[]) <@ (\"search_rolling_stock\".\"traction_modes\")")
+        );
+        assert!(sql.contains("\"1500V\""));
+
+        let error = sql_of(
+            "rollingstock",
+            json!(["contains", "1500V", ["traction_modes"]]),
+            None,
+        )
+        .unwrap_err();
+        assert!(error.error_type.starts_with("editoast:search:"));
+    }
+
+    #[rstest]
+    async fn order_by_results_have_no_order_column(db_pool: Arc<DbConnectionPool>) {
+        let project = |name: &str| {
+            Project::changeset()
+                .name(name.to_owned())
+                .creation_date(Utc::now().naive_utc())
+                .last_modification(Utc::now().naive_utc())
+                .tags(Tags::default())
+        };
+        let second = TestFixture::create(project("search_order_by_b"), db_pool.clone()).await;
+        let first = TestFixture::create(project("search_order_by_a"), db_pool.clone()).await;
+        let search_config = SearchConfigFinder::find("project").unwrap();
+        let sql = create_sql_query(
+            json!(["or", ["=", ["id"], second.id()], ["=", ["id"], first.id()]]),
+            Some(json!(["name"])),
+            &search_config,
+            10,
+            0,
+        )
+        .unwrap();
+
+        let mut conn = db_pool.get().await.unwrap();
+        let results: Vec<SearchDBResult> = sql.load(&mut conn).await.unwrap();
+        let ids: Vec<_> = results.iter().map(|row| row.result["id"].clone()).collect();
+        assert_eq!(ids, vec![json!(first.id()), json!(second.id())]);
+        assert!(results.iter().all(|row| row.result.get("_order").is_none()));
+    }
+}
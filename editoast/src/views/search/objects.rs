@@ -1,10 +1,16 @@
+use std::collections::HashMap;
+
+use chrono::DateTime;
 use chrono::NaiveDateTime;
+use chrono::Utc;
 use editoast_derive::Search;
 use editoast_derive::SearchConfigStore;
 use serde_derive::Serialize;
 use utoipa::ToSchema;
 
+use editoast_common::geometry::GeoJsonMultiLineString;
 use editoast_common::geometry::GeoJsonPoint;
+use editoast_schemas::rolling_stock::LoadingGaugeType;
 
 // NOTE: every structure deriving `Search` here might have to `#[allow(unused)]`
 // because while the name and type information of the fields are read by the macro,
@@ -39,7 +45,44 @@ pub(super) struct SearchResultItemTrack {
 #[search(
     name = "operationalpoint",
     table = "search_operational_point",
-    migration(src_table = "infra_object_operational_point"),
+    migration(
+        src_table = "infra_object_operational_point",
+        // The layer of an operational point is generated after the point is inserted or
+        // updated, so its geographic column is refreshed along with the layer
+        append_sql(
+            up = "
+CREATE OR REPLACE FUNCTION search_operational_point__layer_trig_fun()
+    RETURNS TRIGGER
+    LANGUAGE plpgsql
+AS $$
+BEGIN
+    UPDATE search_operational_point
+        SET geographic = (SELECT ST_Transform(ST_Collect(lay.geographic), 4326) FROM infra_layer_operational_point AS lay WHERE lay.infra_id = changed.infra_id AND lay.obj_id = changed.obj_id)
+        FROM (SELECT DISTINCT infra_id, obj_id FROM changed_layers) AS changed
+        WHERE search_operational_point.infra_id = changed.infra_id
+            AND search_operational_point.obj_id = changed.obj_id;
+    RETURN NULL;
+END;
+$$;
+CREATE OR REPLACE TRIGGER search_operational_point__layer_ins_trig
+AFTER INSERT ON infra_layer_operational_point
+REFERENCING NEW TABLE AS changed_layers
+FOR EACH STATEMENT EXECUTE FUNCTION search_operational_point__layer_trig_fun();
+CREATE OR REPLACE TRIGGER search_operational_point__layer_upd_trig
+AFTER UPDATE ON infra_layer_operational_point
+REFERENCING NEW TABLE AS changed_layers
+FOR EACH STATEMENT EXECUTE FUNCTION search_operational_point__layer_trig_fun();
+CREATE OR REPLACE TRIGGER search_operational_point__layer_del_trig
+AFTER DELETE ON infra_layer_operational_point
+REFERENCING OLD TABLE AS changed_layers
+FOR EACH STATEMENT EXECUTE FUNCTION search_operational_point__layer_trig_fun();",
+            down = "
+DROP TRIGGER IF EXISTS search_operational_point__layer_ins_trig ON infra_layer_operational_point;
+DROP TRIGGER IF EXISTS search_operational_point__layer_upd_trig ON infra_layer_operational_point;
+DROP TRIGGER IF EXISTS search_operational_point__layer_del_trig ON infra_layer_operational_point;
+DROP FUNCTION IF EXISTS search_operational_point__layer_trig_fun;",
+        ),
+    ),
     joins = "
         INNER JOIN infra_object_operational_point AS OP ON OP.id = search_operational_point.id
         INNER JOIN (SELECT DISTINCT ON (infra_id, obj_id) * FROM infra_layer_operational_point)
@@ -79,6 +122,11 @@ pub(super) struct SearchResultItemTrack {
         data_type = "text",
         sql = "infra_object_operational_point.data->'extensions'->'identifier'->>'name'",
         textual_search,
+    ),
+    column(
+        name = "geographic",
+        data_type = "geometry(MultiPoint, 4326)",
+        sql = "(SELECT ST_Transform(ST_Collect(lay.geographic), 4326) FROM infra_layer_operational_point AS lay WHERE lay.infra_id = infra_object_operational_point.infra_id AND lay.obj_id = infra_object_operational_point.obj_id)",
     )
 )]
 #[allow(unused)]
@@ -124,6 +172,38 @@ pub(super) struct SearchResultItemOperationalPointTrackSections {
             INNER JOIN infra_layer_signal AS layer
             ON layer.infra_id = infra_object_signal.infra_id
                 AND layer.obj_id = infra_object_signal.obj_id",
+        // The layer of a signal is generated after the signal is inserted or updated,
+        // so the columns read from it are refreshed along with the layer
+        append_sql(
+            up = "
+CREATE OR REPLACE FUNCTION search_signal__layer_trig_fun()
+    RETURNS TRIGGER
+    LANGUAGE plpgsql
+AS $$
+BEGIN
+    UPDATE search_signal
+        SET sprite_signaling_system = changed.signaling_system,
+        sprite = changed.sprite,
+        geographic = ST_Transform(changed.geographic, 4326)
+        FROM changed_layers AS changed
+        WHERE search_signal.infra_id = changed.infra_id
+            AND search_signal.obj_id = changed.obj_id;
+    RETURN NULL;
+END;
+$$;
+CREATE OR REPLACE TRIGGER search_signal__layer_ins_trig
+AFTER INSERT ON infra_layer_signal
+REFERENCING NEW TABLE AS changed_layers
+FOR EACH STATEMENT EXECUTE FUNCTION search_signal__layer_trig_fun();
+CREATE OR REPLACE TRIGGER search_signal__layer_upd_trig
+AFTER UPDATE ON infra_layer_signal
+REFERENCING NEW TABLE AS changed_layers
+FOR EACH STATEMENT EXECUTE FUNCTION search_signal__layer_trig_fun();",
+            down = "
+DROP TRIGGER IF EXISTS search_signal__layer_ins_trig ON infra_layer_signal;
+DROP TRIGGER IF EXISTS search_signal__layer_upd_trig ON infra_layer_signal;
+DROP FUNCTION IF EXISTS search_signal__layer_trig_fun;",
+        ),
     ),
     column(
         name = "label",
@@ -168,6 +248,11 @@ pub(super) struct SearchResultItemOperationalPointTrackSections {
         sql = "layer.signaling_system"
     ),
     column(name = "sprite", data_type = "TEXT", sql = "layer.sprite"),
+    column(
+        name = "geographic",
+        data_type = "geometry(Point, 4326)",
+        sql = "ST_Transform(layer.geographic, 4326)"
+    ),
     joins = "
         INNER JOIN infra_object_signal AS sig ON sig.id = search_signal.id
         INNER JOIN infra_object_track_section AS track_section ON track_section.obj_id = sig.data->>'track' AND track_section.infra_id = sig.infra_id
@@ -309,6 +394,270 @@ pub(super) struct SearchResultItemScenario {
     tags: Vec<String>,
 }
 
+#[derive(Search, Serialize, ToSchema)]
+#[search(
+    name = "switch",
+    table = "search_switch",
+    migration(
+        src_table = "infra_object_switch",
+        query_joins = "
+            LEFT JOIN infra_object_track_section AS track_section
+            ON track_section.infra_id = infra_object_switch.infra_id
+                AND track_section.obj_id = (
+                    SELECT port.value->>'track'
+                    FROM jsonb_each(infra_object_switch.data->'ports') AS port
+                    ORDER BY port.key
+                    LIMIT 1
+                )",
+    ),
+    column(
+        name = "obj_id",
+        data_type = "VARCHAR(255)",
+        sql = "infra_object_switch.obj_id"
+    ),
+    column(
+        name = "infra_id",
+        data_type = "integer",
+        sql = "infra_object_switch.infra_id"
+    ),
+    column(
+        name = "switch_type",
+        data_type = "VARCHAR(255)",
+        sql = "infra_object_switch.data->>'switch_type'"
+    ),
+    column(
+        name = "line_name",
+        data_type = "text",
+        sql = "track_section.data->'extensions'->'sncf'->>'line_name'",
+        textual_search
+    ),
+    column(
+        name = "line_code",
+        data_type = "integer",
+        sql = "(track_section.data->'extensions'->'sncf'->>'line_code')::integer"
+    ),
+    joins = "
+        INNER JOIN infra_object_switch AS sw ON sw.id = search_switch.id
+        LEFT JOIN infra_object_track_section AS track_section ON track_section.infra_id = sw.infra_id
+            AND track_section.obj_id = (SELECT port.value->>'track' FROM jsonb_each(sw.data->'ports') AS port ORDER BY port.key LIMIT 1)
+        LEFT JOIN infra_layer_switch AS lay ON lay.infra_id = sw.infra_id AND lay.obj_id = sw.obj_id"
+)]
+#[allow(unused)]
+/// A search result item for a query with `object = "switch"`
+///
+/// The line of a switch is the one of the track section connected to its first port.
+pub(super) struct SearchResultItemSwitch {
+    #[search(sql = "sw.infra_id")]
+    infra_id: i64,
+    #[search(sql = "sw.obj_id")]
+    obj_id: String,
+    #[search(sql = "sw.data->>'switch_type'")]
+    switch_type: String,
+    #[search(sql = "track_section.data->'extensions'->'sncf'->>'line_name'")]
+    #[schema(required)]
+    line_name: Option<String>,
+    #[search(sql = "search_switch.line_code")]
+    #[schema(required)]
+    line_code: Option<i64>,
+    #[search(sql = "ST_AsGeoJSON(ST_Transform(lay.geographic, 4326))::json")]
+    #[schema(required)]
+    geographic: Option<GeoJsonPoint>,
+}
+
+#[derive(Search, Serialize, ToSchema)]
+#[search(
+    name = "speedsection",
+    table = "search_speed_section",
+    migration(
+        src_table = "infra_object_speed_section",
+        query_joins = "
+            LEFT JOIN infra_object_track_section AS track_section
+            ON track_section.infra_id = infra_object_speed_section.infra_id
+                AND track_section.obj_id = infra_object_speed_section.data->'track_ranges'->0->>'track'",
+    ),
+    column(
+        name = "obj_id",
+        data_type = "VARCHAR(255)",
+        sql = "infra_object_speed_section.obj_id"
+    ),
+    column(
+        name = "infra_id",
+        data_type = "integer",
+        sql = "infra_object_speed_section.infra_id"
+    ),
+    column(
+        name = "speed_limit",
+        data_type = "double precision",
+        sql = "(infra_object_speed_section.data->>'speed_limit')::double precision"
+    ),
+    column(
+        name = "line_name",
+        data_type = "text",
+        sql = "track_section.data->'extensions'->'sncf'->>'line_name'",
+        textual_search
+    ),
+    column(
+        name = "line_code",
+        data_type = "integer",
+        sql = "(track_section.data->'extensions'->'sncf'->>'line_code')::integer"
+    ),
+    joins = "
+        INNER JOIN infra_object_speed_section AS speed ON speed.id = search_speed_section.id
+        LEFT JOIN infra_object_track_section AS track_section ON track_section.infra_id = speed.infra_id
+            AND track_section.obj_id = speed.data->'track_ranges'->0->>'track'
+        LEFT JOIN infra_layer_speed_section AS lay ON lay.infra_id = speed.infra_id AND lay.obj_id = speed.obj_id"
+)]
+#[allow(unused)]
+/// A search result item for a query with `object = "speedsection"`
+///
+/// The line of a speed section is the one of its first track range.
+pub(super) struct SearchResultItemSpeedSection {
+    #[search(sql = "speed.infra_id")]
+    infra_id: i64,
+    #[search(sql = "speed.obj_id")]
+    obj_id: String,
+    #[search(sql = "search_speed_section.speed_limit")]
+    #[schema(required)]
+    speed_limit: Option<f64>,
+    #[search(sql = "speed.data->'speed_limit_by_tag'")]
+    speed_limit_by_tag: HashMap<String, f64>,
+    #[search(sql = "track_section.data->'extensions'->'sncf'->>'line_name'")]
+    #[schema(required)]
+    line_name: Option<String>,
+    #[search(sql = "search_speed_section.line_code")]
+    #[schema(required)]
+    line_code: Option<i64>,
+    #[search(sql = "ST_AsGeoJSON(ST_Transform(lay.geographic, 4326))::json")]
+    #[schema(required)]
+    geographic: Option<GeoJsonMultiLineString>,
+}
+
+#[derive(Search, Serialize, ToSchema)]
+#[search(
+    name = "rollingstock",
+    table = "search_rolling_stock",
+    migration(src_table = "rolling_stock"),
+    joins = "INNER JOIN rolling_stock ON rolling_stock.id = search_rolling_stock.id",
+    column(
+        name = "name",
+        data_type = "text",
+        sql = "rolling_stock.name",
+        textual_search
+    ),
+    column(
+        name = "traction_modes",
+        data_type = "TEXT[]",
+        sql = "ARRAY(SELECT jsonb_object_keys(rolling_stock.effort_curves->'modes'))"
+    ),
+    column(
+        name = "max_speed",
+        data_type = "double precision",
+        sql = "rolling_stock.max_speed"
+    ),
+    column(
+        name = "loading_gauge",
+        data_type = "VARCHAR(16)",
+        sql = "(ARRAY['G1', 'G2', 'GA', 'GB', 'GB1', 'GC', 'FR3.3', 'FR3.3/GB/G2', 'GLOTT'])[rolling_stock.loading_gauge + 1]"
+    ),
+    column(name = "locked", data_type = "boolean", sql = "rolling_stock.locked")
+)]
+#[allow(unused)]
+/// A search result item for a query with `object = "rollingstock"`
+///
+/// The loading gauge of a rolling stock is stored as the index of its [LoadingGaugeType]
+/// variant, hence the name lookup of the `loading_gauge` search column.
+pub(super) struct SearchResultItemRollingStock {
+    #[search(sql = "rolling_stock.id")]
+    id: i64,
+    #[search(sql = "rolling_stock.name")]
+    name: String,
+    #[search(sql = "search_rolling_stock.traction_modes")]
+    traction_modes: Vec<String>,
+    #[search(sql = "rolling_stock.max_speed")]
+    max_speed: f64,
+    #[search(sql = "search_rolling_stock.loading_gauge")]
+    loading_gauge: LoadingGaugeType,
+    #[search(sql = "rolling_stock.locked")]
+    locked: bool,
+}
+
+#[derive(Search, Serialize, ToSchema)]
+#[search(
+    name = "trainschedule",
+    table = "search_train_schedule_v2",
+    migration(src_table = "train_schedule_v2"),
+    joins = "INNER JOIN train_schedule_v2 AS train ON train.id = search_train_schedule_v2.id",
+    column(
+        name = "timetable_id",
+        data_type = "bigint",
+        sql = "train_schedule_v2.timetable_id"
+    ),
+    column(
+        name = "train_name",
+        data_type = "text",
+        sql = "train_schedule_v2.train_name",
+        textual_search
+    ),
+    column(
+        name = "labels",
+        data_type = "TEXT",
+        sql = "osrd_prepare_for_search_tags(train_schedule_v2.labels)"
+    ),
+    column(
+        name = "rolling_stock_name",
+        data_type = "text",
+        sql = "train_schedule_v2.rolling_stock_name",
+        textual_search
+    )
+)]
+#[allow(unused)]
+/// A search result item for a query with `object = "trainschedule"`
+pub(super) struct SearchResultItemTrainSchedule {
+    #[search(sql = "train.id")]
+    id: i64,
+    #[search(sql = "train.timetable_id")]
+    timetable_id: i64,
+    #[search(sql = "train.train_name")]
+    train_name: String,
+    #[search(sql = "train.labels")]
+    labels: Vec<String>,
+    #[search(sql = "train.rolling_stock_name")]
+    rolling_stock_name: String,
+    #[search(sql = "train.start_time")]
+    start_time: DateTime<Utc>,
+}
+
 /// See [crate::views::search::SearchConfigStore::find]
 #[derive(SearchConfigStore)]
 pub struct SearchConfigFinder;
+
+#[cfg(test)]
+mod tests {
+    use editoast_schemas::rolling_stock::LoadingGaugeType;
+
+    use super::SearchConfigFinder;
+    use crate::views::search::SearchConfigStore;
+
+    #[test]
+    fn loading_gauge_names_match_their_repr() {
+        let search_config = SearchConfigFinder::find("rollingstock").unwrap();
+        let loading_gauge = search_config
+            .criterias
+            .iter()
+            .find(|criteria| criteria.name == "loading_gauge")
+            .and_then(|criteria| criteria.migration.as_ref())
+            .unwrap();
+        let (_, names) = loading_gauge.sql.split_once("ARRAY[").unwrap();
+        let (names, _) = names.split_once(']').unwrap();
+        let names: Vec<_> = names
+            .split(',')
+            .map(|name| name.trim().trim_matches('\'').to_owned())
+            .collect();
+
+        for (index, name) in names.iter().enumerate() {
+            let loading_gauge = LoadingGaugeType::from_repr(index).unwrap();
+            assert_eq!(serde_json::to_value(loading_gauge).unwrap(), *name);
+        }
+        assert!(LoadingGaugeType::from_repr(names.len()).is_none());
+    }
+}
@@ -2,6 +2,8 @@
 
 use std::rc::Rc;
 
+use geos::Geom;
+
 use super::context::ProcessingError;
 use super::context::QueryContext;
 use super::context::TypedAst;
@@ -101,6 +103,17 @@ impl QueryContext {
 /// - to_string : (string | null) -> string
 /// - list : variadic string -> string list
 /// - contains : string list -> string list -> bool
+///
+/// Geospatial functions, where `number` is `int | float` and coordinates are
+/// WGS84 longitudes and latitudes:
+///
+/// - within_bbox : geometry -> number -> number -> number -> number -> bool
+///   (lon_min, lat_min, lon_max, lat_max)
+/// - distance_lt : geometry -> number -> number -> number -> bool
+///   (lon, lat, distance in meters)
+/// - intersects : geometry -> string -> bool (GeoJSON geometry)
+/// - nearest : geometry -> number -> number -> float (lon, lat), to sort results
+///   from the closest to the farthest
 pub fn create_processing_context() -> QueryContext {
     let mut context = QueryContext::default();
     context.def_function_1::<dsl::Nullable<dsl::Ersatz<dsl::Boolean>>, dsl::Sql<dsl::Boolean>>(
@@ -207,9 +220,142 @@ pub fn create_processing_context() -> QueryContext {
         "contains",
         Rc::new(|sub, array| Ok(SqlQuery::infix("<@", sub, array))),
     );
+    let number = || TypeSpec::or(AstType::Integer, AstType::Float);
+    context.def_function(
+        "within_bbox",
+        AstType::Geometry >> number() >> number() >> number() >> number() >> AstType::Boolean,
+        Rc::new(|args| {
+            let mut args = args.into_iter();
+            let geometry = args.next().unwrap();
+            let envelope = SqlQuery::call(
+                "ST_MakeEnvelope",
+                args.map(SqlQuery::from)
+                    .chain([TypedAst::Integer(SRID_WGS84).into()])
+                    .collect(),
+            );
+            Ok(SqlQuery::call("ST_Within", vec![geometry.into(), envelope])
+                .into_typed_ast(AstType::Boolean.into()))
+        }),
+    );
+    context.def_function(
+        "distance_lt",
+        AstType::Geometry >> number() >> number() >> number() >> AstType::Boolean,
+        Rc::new(|args| {
+            let [geometry, lon, lat, distance] = <[TypedAst; 4]>::try_from(args).unwrap();
+            let point = wgs84_point(lon, lat.clone());
+            // The exact distance is computed on geographies, which can't use the index of the
+            // geometry column: candidates are first found within a box containing the distance
+            let lat_margin = SqlQuery::infix(
+                "/",
+                distance.clone(),
+                TypedAst::Float(MIN_METERS_PER_DEGREE),
+            );
+            let max_lat = SqlQuery::call(
+                "LEAST",
+                vec![
+                    SqlQuery::infix("+", SqlQuery::call("abs", vec![lat]), lat_margin.clone()),
+                    TypedAst::Float(MAX_LATITUDE).into(),
+                ],
+            );
+            let lon_margin = SqlQuery::infix(
+                "/",
+                distance.clone().into(),
+                SqlQuery::infix(
+                    "*",
+                    TypedAst::Float(MIN_METERS_PER_DEGREE).into(),
+                    SqlQuery::call("cos", vec![SqlQuery::call("radians", vec![max_lat])]),
+                ),
+            );
+            let candidates = SqlQuery::infix(
+                "&&",
+                geometry.clone().into(),
+                SqlQuery::call("ST_Expand", vec![point.clone(), lon_margin, lat_margin]),
+            );
+            let within = SqlQuery::call(
+                "ST_DWithin",
+                vec![
+                    SqlQuery::cast(geometry, "geography"),
+                    SqlQuery::cast(point, "geography"),
+                    distance.into(),
+                ],
+            );
+            Ok(SqlQuery::infix("AND", candidates, within).into_typed_ast(AstType::Boolean.into()))
+        }),
+    );
+    context.def_function(
+        "intersects",
+        AstType::Geometry >> AstType::String >> AstType::Boolean,
+        Rc::new(|args| {
+            let [geometry, geojson] = <[TypedAst; 2]>::try_from(args).unwrap();
+            validate_geojson(&geojson)?;
+            let other = SqlQuery::call(
+                "ST_SetSRID",
+                vec![
+                    SqlQuery::call("ST_GeomFromGeoJSON", vec![geojson]),
+                    TypedAst::Integer(SRID_WGS84).into(),
+                ],
+            );
+            Ok(
+                SqlQuery::call("ST_Intersects", vec![geometry.into(), other])
+                    .into_typed_ast(AstType::Boolean.into()),
+            )
+        }),
+    );
+    context.def_function(
+        "nearest",
+        AstType::Geometry >> number() >> number() >> AstType::Float,
+        Rc::new(|args| {
+            let [geometry, lon, lat] = <[TypedAst; 3]>::try_from(args).unwrap();
+            Ok(
+                SqlQuery::infix("<->", geometry.into(), wgs84_point(lon, lat))
+                    .into_typed_ast(AstType::Float.into()),
+            )
+        }),
+    );
     context
 }
 
+/// The SRID of the WGS84 coordinates used by the geospatial functions
+const SRID_WGS84: i64 = 4326;
+
+/// A lower bound of the length of a degree of latitude, or of longitude at the equator, in meters
+const MIN_METERS_PER_DEGREE: f64 = 110_000.;
+
+/// The latitude up to which the longitude margin of `distance_lt` is computed
+const MAX_LATITUDE: f64 = 89.;
+
+/// Checks that a GeoJSON argument is a literal and valid geometry
+///
+/// PostGIS would otherwise fail to read it when the query runs.
+fn validate_geojson(geojson: &TypedAst) -> Result<()> {
+    let invalid = |message: String| ProcessingError::InvalidGeoJson { message };
+    let TypedAst::String(geojson) = geojson else {
+        return Err(invalid("the geometry must be given as a literal string".to_owned()).into());
+    };
+    let geometry = geojson
+        .parse::<geos::geojson::Geometry>()
+        .map_err(|error| invalid(error.to_string()))?;
+    let geometry =
+        geos::Geometry::try_from(geometry).map_err(|error| invalid(error.to_string()))?;
+    if !geometry.is_valid() {
+        let reason = geometry
+            .is_valid_reason()
+            .unwrap_or_else(|_| "the geometry is not valid".to_owned());
+        return Err(invalid(reason).into());
+    }
+    Ok(())
+}
+
+fn wgs84_point(lon: TypedAst, lat: TypedAst) -> SqlQuery {
+    SqlQuery::call(
+        "ST_SetSRID",
+        vec![
+            SqlQuery::call("ST_MakePoint", vec![lon, lat]),
+            TypedAst::Integer(SRID_WGS84).into(),
+        ],
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use std::rc::Rc;
@@ -227,6 +373,8 @@ mod tests {
             .insert("trigram".into(), AstType::String.into());
         env.columns_type
             .insert("infra_id".into(), AstType::Integer.into());
+        env.columns_type
+            .insert("geographic".into(), AstType::Geometry.into());
         // + : int -> int -> int
         env.def_function_2::<dsl::Integer, dsl::Integer, dsl::Integer>(
             "+",
@@ -463,4 +611,67 @@ mod tests {
         assert!(try_eval(json!(["like", "test"])).is_err());
         assert!(try_eval(json!(["like", "test", null, null])).is_err());
     }
+
+    #[test]
+    fn test_geospatial_functions() {
+        let to_sql = |query: Value| {
+            let TypedAst::Sql(sql, _) = eval(query) else {
+                panic!("expected an SQL expression");
+            };
+            let mut bindings = vec![];
+            (sql.to_sql(&mut bindings), bindings)
+        };
+        assert_eq!(
+            to_sql(json!(["within_bbox", ["geographic"], 2, 48, 2.5, 49])).0,
+            r#"ST_Within(("geographic"), (ST_MakeEnvelope((2), (48), (2.5), (49), (4326))))"#
+        );
+        let point = "ST_SetSRID((ST_MakePoint((2.35), (48.85))), (4326))";
+        let lat_margin = "(500) / (110000.0)";
+        let lon_margin = format!(
+            "(500) / ((110000.0) * (cos((radians((LEAST(((abs((48.85))) + ({lat_margin})), (89.0))))))))"
+        );
+        assert_eq!(
+            to_sql(json!(["distance_lt", ["geographic"], 2.35, 48.85, 500])).0,
+            format!(
+                r#"(("geographic") && (ST_Expand(({point}), ({lon_margin}), ({lat_margin})))) AND (ST_DWithin((("geographic")::geography), (({point})::geography), (500)))"#
+            )
+        );
+        let geojson = r#"{"type":"Point","coordinates":[2.35,48.85]}"#;
+        assert_eq!(
+            to_sql(json!(["intersects", ["geographic"], geojson])),
+            (
+                r#"ST_Intersects(("geographic"), (ST_SetSRID((ST_GeomFromGeoJSON(($1))), (4326))))"#
+                    .to_owned(),
+                vec![geojson.to_owned()]
+            )
+        );
+        assert_eq!(
+            to_sql(json!(["nearest", ["geographic"], 2.35, 48.85])).0,
+            r#"("geographic") <-> (ST_SetSRID((ST_MakePoint((2.35), (48.85))), (4326)))"#
+        );
+    }
+
+    #[rstest::rstest]
+    #[case::not_json("POINT (2.35 48.85)")]
+    #[case::not_a_geometry(r#"{"type":"Point"}"#)]
+    #[case::feature(
+        r#"{"type":"Feature","properties":{},"geometry":{"type":"Point","coordinates":[0,0]}}"#
+    )]
+    #[case::self_intersecting(
+        r#"{"type":"Polygon","coordinates":[[[0,0],[1,1],[1,0],[0,1],[0,0]]]}"#
+    )]
+    fn test_intersects_invalid_geojson(#[case] geojson: &str) {
+        let error = try_eval(json!(["intersects", ["geographic"], geojson])).unwrap_err();
+        assert_eq!(error.error_type, "editoast:search:InvalidGeoJson");
+    }
+
+    #[test]
+    fn test_geospatial_typecheck_error() {
+        assert!(try_eval(json!(["within_bbox", ["geographic"], 2, 48, 2.5])).is_err());
+        assert!(try_eval(json!(["distance_lt", ["name"], 2.35, 48.85, 500])).is_err());
+        assert!(try_eval(json!(["intersects", ["geographic"], 12])).is_err());
+        assert!(try_eval(json!(["intersects", ["geographic"], ["name"]])).is_err());
+        assert!(try_eval(json!(["nearest", 2.35, 2.35, 48.85])).is_err());
+        assert!(try_eval(json!(["=", ["geographic"], ["geographic"]])).is_err());
+    }
 }
@@ -43,6 +43,7 @@ pub struct CriteriaMigration {
 pub enum Index {
     Default,
     GinTrgm,
+    Gist,
 }
 
 pub enum SearchType {
@@ -239,6 +240,9 @@ impl Index {
                     "CREATE INDEX \"{name}\" ON \"{table}\" USING gin (\"{column}\" gin_trgm_ops);"
                 )
             }
+            Index::Gist => {
+                format!("CREATE INDEX \"{name}\" ON \"{table}\" USING gist (\"{column}\");")
+            }
         }
     }
 }
@@ -48,6 +48,9 @@ pub enum AstType {
     Integer,
     Float,
     String,
+    /// Only available through columns, see the geospatial functions of
+    /// [super::process::create_processing_context()]
+    Geometry,
 }
 
 /// Allows combining [AstType]s in order to express more complex types
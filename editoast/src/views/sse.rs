@@ -0,0 +1,63 @@
+//! Server-sent events
+//!
+//! Long operations stream their progress as a `text/event-stream` response, which
+//! browsers consume with an `EventSource` and which goes through the gateway unbuffered.
+
+use std::convert::Infallible;
+use std::fmt;
+
+use actix_web::http::header;
+use actix_web::web::Bytes;
+use actix_web::HttpResponse;
+use futures::Stream;
+use futures::StreamExt;
+use serde::Serialize;
+
+/// A named event whose data is a JSON document
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    name: &'static str,
+    data: String,
+}
+
+impl Event {
+    pub fn new(name: &'static str, data: &impl Serialize) -> Self {
+        Self {
+            name,
+            data: serde_json::to_string(data).expect("event data should serialize to JSON"),
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Serialized JSON never contains newlines, so the data always fits in a single line
+        write!(f, "event: {}\ndata: {}\n\n", self.name, self.data)
+    }
+}
+
+/// Builds a response sending the events as soon as they are produced
+pub fn stream_events(events: impl Stream<Item = Event> + 'static) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        // Prevents the reverse proxies from buffering the stream
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(events.map(|event| Ok::<_, Infallible>(Bytes::from(event.to_string()))))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn format_event() {
+        let event = Event::new("summary", &json!({ "train_id": 1, "name": "a\nb" }));
+        assert_eq!(
+            event.to_string(),
+            "event: summary\ndata: {\"name\":\"a\\nb\",\"train_id\":1}\n\n"
+        );
+    }
+}
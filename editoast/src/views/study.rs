@@ -27,11 +27,14 @@ use super::scenario;
 use crate::error::InternalError;
 use crate::error::Result;
 use crate::modelsv2::prelude::*;
+use crate::modelsv2::resource_grant::ResourceType;
+use crate::modelsv2::resource_grant::Role;
 use crate::modelsv2::DbConnection;
 use crate::modelsv2::DbConnectionPool;
 use crate::modelsv2::Project;
 use crate::modelsv2::Study;
 use crate::modelsv2::Tags;
+use crate::views::authz::Authentication;
 use crate::views::pagination::PaginatedList as _;
 use crate::views::pagination::PaginationQueryParam;
 use crate::views::projects::ProjectError;
@@ -141,12 +144,17 @@ impl StudyCreateForm {
 #[post("")]
 async fn create(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     data: Json<StudyCreateForm>,
     project: Path<i64>,
 ) -> Result<Json<StudyResponse>> {
     let project_id = project.into_inner();
 
     let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check(conn, ResourceType::Project, project_id, Role::Editor)
+        .await?;
 
     let (study, project) = conn
         .transaction::<_, InternalError, _>(|conn| {
@@ -200,10 +208,18 @@ pub struct StudyIdParam {
     )
 )]
 #[delete("")]
-async fn delete(path: Path<(i64, i64)>, db_pool: Data<DbConnectionPool>) -> Result<HttpResponse> {
+async fn delete(
+    path: Path<(i64, i64)>,
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+) -> Result<HttpResponse> {
     let (project_id, study_id) = path.into_inner();
-    // Check if project exists
     let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check(conn, ResourceType::Study, study_id, Role::Owner)
+        .await?;
+    // Check if project exists
     use crate::modelsv2::Retrieve;
     let mut project =
         Project::retrieve_or_fail(conn, project_id, || ProjectError::NotFound { project_id })
@@ -230,12 +246,17 @@ async fn delete(path: Path<(i64, i64)>, db_pool: Data<DbConnectionPool>) -> Resu
 #[get("")]
 async fn get(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     path: Path<(i64, i64)>,
 ) -> Result<Json<StudyResponse>> {
     let (project_id, study_id) = path.into_inner();
 
-    // Check if project exists
     let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check(conn, ResourceType::Study, study_id, Role::Viewer)
+        .await?;
+    // Check if project exists
     use crate::modelsv2::Retrieve;
     let project =
         Project::retrieve_or_fail(conn, project_id, || ProjectError::NotFound { project_id })
@@ -309,9 +330,14 @@ async fn patch(
     data: Json<StudyPatchForm>,
     path: Path<(i64, i64)>,
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
 ) -> Result<Json<StudyResponse>> {
     let (project_id, study_id) = path.into_inner();
     let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check(conn, ResourceType::Study, study_id, Role::Editor)
+        .await?;
     let (study_scenarios, project) = conn
         .transaction::<_, InternalError, _>(|conn| {
             async {
@@ -379,25 +405,29 @@ struct StudyListResponse {
 #[get("")]
 async fn list(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     project: Path<i64>,
     Query(pagination_params): Query<PaginationQueryParam>,
     Query(ordering_params): Query<OperationalStudiesOrderingParam>,
 ) -> Result<Json<StudyListResponse>> {
     let ordering = ordering_params.ordering;
     let project_id = project.into_inner();
-    if !Project::exists(db_pool.get().await?.deref_mut(), project_id).await? {
+    let conn = &mut db_pool.get().await?;
+    if !Project::exists(conn, project_id).await? {
         return Err(ProjectError::NotFound { project_id }.into());
     }
+    let authorizer = authentication.authorizer(conn).await?;
+    let hidden = authorizer.hidden(conn, ResourceType::Study).await?;
 
     let settings = pagination_params
         .validate(1000)?
         .warn_page_size(100)
         .into_selection_settings()
         .filter(move || Study::PROJECT_ID.eq(project_id))
+        .filter(move || Study::ID.ne_all(hidden.clone()))
         .order_by(move || ordering.as_study_ordering());
 
-    let (studies, stats) =
-        Study::list_paginated(db_pool.get().await?.deref_mut(), settings).await?;
+    let (studies, stats) = Study::list_paginated(conn, settings).await?;
     let results = studies
         .into_iter()
         .zip(std::iter::repeat(&db_pool).map(|p| p.get()))
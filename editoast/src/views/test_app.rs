@@ -22,6 +22,7 @@ use crate::{
     infra_cache::InfraCache,
     map::MapLayers,
     modelsv2::{database::connection_pool::create_connection_pool, DbConnectionPoolV2},
+    views::authz::AuthorizationConfig,
     RedisClient,
 };
 
@@ -100,7 +101,10 @@ impl TestAppBuilder {
             .app_data(Data::new(CHashMap::<i64, InfraCache>::default()))
             .app_data(Data::new(MapLayers::parse()))
             .app_data(Data::new(MapLayersConfig::default()))
-            .app_data(core_client);
+            .app_data(core_client)
+            .app_data(Data::new(AuthorizationConfig {
+                allow_anonymous: true,
+            }));
 
         let ref_db_pool = if self.db_pool_v1 {
             let config = PostgresConfig::default();
@@ -3,6 +3,7 @@ pub mod projection;
 mod properties;
 
 pub use pathfinding::pathfinding_from_train;
+pub use pathfinding::pathfinding_from_train_batch;
 
 use editoast_derive::EditoastError;
 use thiserror::Error;
@@ -29,7 +29,6 @@ use crate::modelsv2::OperationalPointModel;
 use crate::modelsv2::Retrieve;
 use crate::modelsv2::RetrieveBatch;
 use crate::modelsv2::RetrieveBatchUnchecked;
-use crate::modelsv2::RollingStockModel;
 use crate::modelsv2::TrackSectionModel;
 use crate::redis_utils::RedisClient;
 use crate::redis_utils::RedisConnection;
@@ -169,8 +168,7 @@ pub async fn pathfinding_from_train(
 ) -> Result<PathfindingResult> {
     // Retrieve rolling stock
     let rolling_stock_name = train_schedule.rolling_stock_name.clone();
-    let Some(rolling_stock) = RollingStockModel::retrieve(conn, rolling_stock_name.clone()).await?
-    else {
+    let Some(rolling_stock) = train_schedule.rolling_stock(conn).await? else {
         return Ok(PathfindingResult::RollingStockNotFound { rolling_stock_name });
     };
 
@@ -190,6 +188,30 @@ pub async fn pathfinding_from_train(
     pathfinding_blocks(conn, redis, core, infra, &path_input).await
 }
 
+/// Compute the paths of a batch of train schedules
+///
+/// Note: The order of the returned paths is the same as the order of the train schedules.
+pub async fn pathfinding_from_train_batch(
+    db_pool: Arc<DbConnectionPool>,
+    redis_client: Arc<RedisClient>,
+    core: Arc<CoreClient>,
+    infra: &Infra,
+    train_schedules: &[TrainSchedule],
+) -> Result<Vec<PathfindingResult>> {
+    let pending_pathfindings = train_schedules.iter().map(|train_schedule| {
+        let db_pool = db_pool.clone();
+        let redis_client = redis_client.clone();
+        let core = core.clone();
+        async move {
+            let conn = &mut db_pool.get().await?;
+            let mut redis_conn = redis_client.get_connection().await?;
+            pathfinding_from_train(conn, &mut redis_conn, core, infra, train_schedule.clone()).await
+        }
+    });
+    let pathfindings = futures::future::join_all(pending_pathfindings).await;
+    pathfindings.into_iter().collect()
+}
+
 /// Generates a unique hash based on the pathfinding entries.
 /// We need to recalculate the path if:
 ///   - The path entry is different
@@ -355,7 +377,7 @@ pub async fn extract_location_from_path_items(
     Ok(Ok(result))
 }
 
-async fn check_tracks_from_path_items(
+pub async fn check_tracks_from_path_items(
     conn: &mut DbConnection,
     infra_id: i64,
     track_offsets: &[Vec<TrackOffset>],
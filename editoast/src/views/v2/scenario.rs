@@ -18,20 +18,26 @@ use thiserror::Error;
 use utoipa::IntoParams;
 use utoipa::ToSchema;
 
+use crate::core::v2::pathfinding::PathfindingResult;
 use crate::decl_paginated_response;
 use crate::error::InternalError;
 use crate::error::Result;
 use crate::models::train_schedule::LightTrainSchedule;
 use crate::models::List;
 use crate::modelsv2::prelude::*;
+use crate::modelsv2::resource_grant::ResourceType;
+use crate::modelsv2::resource_grant::Role;
 use crate::modelsv2::scenario::Scenario;
 use crate::modelsv2::scenario::ScenarioWithDetails;
 use crate::modelsv2::timetable::Timetable;
+use crate::modelsv2::train_schedule::TrainSchedule;
+use crate::modelsv2::DbConnection;
 use crate::modelsv2::DbConnectionPool;
 use crate::modelsv2::Infra;
 use crate::modelsv2::Project;
 use crate::modelsv2::Study;
 use crate::modelsv2::Tags;
+use crate::views::authz::Authentication;
 use crate::views::operational_studies::OperationalStudiesOrderingParam;
 use crate::views::pagination::PaginatedResponse;
 use crate::views::pagination::PaginationQueryParam;
@@ -40,6 +46,8 @@ use crate::views::scenario::check_project_study;
 use crate::views::scenario::check_project_study_conn;
 use crate::views::scenario::ScenarioIdParam;
 use crate::views::study::StudyIdParam;
+use crate::views::v2::path::pathfinding::check_tracks_from_path_items;
+use crate::views::v2::path::pathfinding::extract_location_from_path_items;
 
 crate::routes! {
     "/v2/projects/{project_id}/studies/{study_id}/scenarios" => {
@@ -49,6 +57,7 @@ crate::routes! {
             get,
             delete,
             patch,
+            duplicate,
         }
     }
 }
@@ -59,6 +68,9 @@ editoast_common::schemas! {
     ScenarioWithDetails,
     ScenarioResponse,
     ScenarioCreateForm,
+    ScenarioDuplicateForm,
+    ScenarioDuplicateResponse,
+    UnresolvedTrain,
     PaginatedResponseOfScenarioWithDetails,
     LightTrainSchedule, // TODO: remove from here once train schedule is migrated
 }
@@ -114,6 +126,10 @@ pub enum ScenarioError {
     #[error("Infra '{infra_id}', could not be found")]
     #[editoast_error(status = 404)]
     InfraNotFound { infra_id: i64 },
+
+    #[error("Study '{study_id}', could not be found")]
+    #[editoast_error(status = 404)]
+    StudyNotFound { study_id: i64 },
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
@@ -156,6 +172,7 @@ impl ScenarioResponse {
 #[post("")]
 async fn create(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     data: Json<ScenarioCreateForm>,
     path: Path<(i64, i64)>,
 ) -> Result<Json<ScenarioResponse>> {
@@ -165,6 +182,10 @@ async fn create(
     let scenario: Changeset<Scenario> = data.into_inner().into();
 
     let mut tx = db_pool.get().await?;
+    let authorizer = authentication.authorizer(&mut tx).await?;
+    authorizer
+        .check(&mut tx, ResourceType::Study, study_id, Role::Editor)
+        .await?;
 
     let scenarios_response = tx
         .transaction::<_, InternalError, _>(|conn| {
@@ -221,6 +242,7 @@ async fn create(
 async fn delete(
     path: Path<ScenarioPathParam>,
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
 ) -> Result<HttpResponse> {
     let ScenarioPathParam {
         project_id,
@@ -228,6 +250,10 @@ async fn delete(
         scenario_id,
     } = path.into_inner();
     let mut tx = db_pool.get().await?;
+    let authorizer = authentication.authorizer(&mut tx).await?;
+    authorizer
+        .check(&mut tx, ResourceType::Study, study_id, Role::Owner)
+        .await?;
 
     let db_pool = db_pool.into_inner();
     tx.transaction::<_, InternalError, _>(|conn| {
@@ -294,6 +320,7 @@ async fn patch(
     data: Json<ScenarioPatchForm>,
     path: Path<ScenarioPathParam>,
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
 ) -> Result<Json<ScenarioResponse>> {
     let ScenarioPathParam {
         project_id,
@@ -302,6 +329,10 @@ async fn patch(
     } = path.into_inner();
 
     let mut tx = db_pool.get().await?;
+    let authorizer = authentication.authorizer(&mut tx).await?;
+    authorizer
+        .check(&mut tx, ResourceType::Study, study_id, Role::Editor)
+        .await?;
 
     let scenarios_response = tx
         .transaction::<_, InternalError, _>(|conn| {
@@ -358,6 +389,7 @@ async fn patch(
 #[get("")]
 async fn get(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     path: Path<ScenarioPathParam>,
 ) -> Result<Json<ScenarioResponse>> {
     use crate::modelsv2::Retrieve;
@@ -369,8 +401,12 @@ async fn get(
     } = path.into_inner();
 
     let db_pool = db_pool.into_inner();
-    let (project, study) = check_project_study(db_pool.clone(), project_id, study_id).await?;
     let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check(conn, ResourceType::Study, study_id, Role::Viewer)
+        .await?;
+    let (project, study) = check_project_study(db_pool.clone(), project_id, study_id).await?;
     // Return the scenarios
     let scenario = Scenario::retrieve_or_fail(conn, scenario_id, || ScenarioError::NotFound {
         scenario_id,
@@ -387,6 +423,172 @@ async fn get(
     Ok(Json(scenarios_response))
 }
 
+/// This structure is used by the duplicate endpoint to copy a scenario
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+#[schema(as = ScenarioDuplicateFormV2)]
+struct ScenarioDuplicateForm {
+    /// The name of the copy, defaults to the name of the scenario followed by "(copy)"
+    pub name: Option<String>,
+    /// The study to create the copy in, defaults to the study of the scenario
+    pub study_id: Option<i64>,
+    /// The infra of the copy, defaults to the infra of the scenario
+    pub infra_id: Option<i64>,
+}
+
+/// A copied train whose path items can't be located on the infra of the copy
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+struct UnresolvedTrain {
+    /// The id of the copied train
+    pub train_id: i64,
+    pub train_name: String,
+    /// Either `invalid_path_item` with the first path item which can't be located,
+    /// or `not_found_in_tracks` if it is located on missing track sections
+    pub reason: PathfindingResult,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[schema(as = ScenarioDuplicateResponseV2)]
+struct ScenarioDuplicateResponse {
+    #[serde(flatten)]
+    #[schema(value_type = ScenarioResponseV2)]
+    pub scenario: ScenarioResponse,
+    pub unresolved_trains: Vec<UnresolvedTrain>,
+}
+
+/// Copy a scenario with its timetable and trains
+///
+/// The copy can be created in another study and use another infra. Every train is copied,
+/// the ones whose path items don't resolve on the infra of the copy are reported.
+#[utoipa::path(
+    tag = "scenariosv2",
+    params(ProjectIdParam, StudyIdParam, ScenarioIdParam),
+    request_body = ScenarioDuplicateFormV2,
+    responses(
+        (status = 201, body = ScenarioDuplicateResponseV2, description = "The copy of the scenario"),
+        (status = 404, body = InternalError, description = "The scenario, the target study or the target infra was not found"),
+    )
+)]
+#[post("/duplicate")]
+async fn duplicate(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    path: Path<ScenarioPathParam>,
+    data: Json<ScenarioDuplicateForm>,
+) -> Result<HttpResponse> {
+    let ScenarioPathParam {
+        project_id,
+        study_id,
+        scenario_id,
+    } = path.into_inner();
+    let ScenarioDuplicateForm {
+        name,
+        study_id: target_study_id,
+        infra_id,
+    } = data.into_inner();
+    let target_study_id = target_study_id.unwrap_or(study_id);
+
+    let mut tx = db_pool.get().await?;
+    let authorizer = authentication.authorizer(&mut tx).await?;
+    authorizer
+        .check(&mut tx, ResourceType::Study, study_id, Role::Viewer)
+        .await?;
+    authorizer
+        .check(&mut tx, ResourceType::Study, target_study_id, Role::Editor)
+        .await?;
+
+    let response = tx
+        .transaction::<_, InternalError, _>(|conn| {
+            async move {
+                check_project_study_conn(conn, project_id, study_id).await?;
+                let scenario = Scenario::retrieve_or_fail(conn, scenario_id, || {
+                    ScenarioError::NotFound { scenario_id }
+                })
+                .await?;
+                if scenario.study_id != study_id {
+                    return Err(ScenarioError::NotFound { scenario_id }.into());
+                }
+                let target_study = Study::retrieve_or_fail(conn, target_study_id, || {
+                    ScenarioError::StudyNotFound {
+                        study_id: target_study_id,
+                    }
+                })
+                .await?;
+                let (mut project, study) =
+                    check_project_study_conn(conn, target_study.project_id, target_study_id)
+                        .await?;
+                let infra_id = infra_id.unwrap_or(scenario.infra_id);
+                if !Infra::exists(conn, infra_id).await? {
+                    return Err(ScenarioError::InfraNotFound { infra_id }.into());
+                }
+
+                let timetable_id = scenario.timetable_id;
+                let timetable = Timetable::retrieve_or_fail(conn, timetable_id, || {
+                    ScenarioError::TimetableNotFound { timetable_id }
+                })
+                .await?;
+                let (timetable, trains) = timetable.duplicate(conn).await?;
+                let mut unresolved_trains = vec![];
+                for train in trains {
+                    if let Some(reason) = unresolved_path(conn, infra_id, &train).await? {
+                        unresolved_trains.push(UnresolvedTrain {
+                            train_id: train.id,
+                            train_name: train.train_name,
+                            reason,
+                        });
+                    }
+                }
+
+                let now = Utc::now().naive_utc();
+                let copy = Scenario::changeset()
+                    .name(name.unwrap_or_else(|| format!("{} (copy)", scenario.name)))
+                    .description(scenario.description)
+                    .creation_date(now)
+                    .last_modification(now)
+                    .infra_id(infra_id)
+                    .timetable_id(timetable.id)
+                    .tags(scenario.tags)
+                    .study_id(target_study_id)
+                    .create(conn)
+                    .await?;
+
+                study.clone().update_last_modified(conn).await?;
+                project.update_last_modified(conn).await?;
+
+                let scenario_with_details = ScenarioWithDetails::from_scenario(copy, conn).await?;
+                Ok(ScenarioDuplicateResponse {
+                    scenario: ScenarioResponse::new(scenario_with_details, project, study),
+                    unresolved_trains,
+                })
+            }
+            .scope_boxed()
+        })
+        .await?;
+
+    Ok(HttpResponse::Created().json(response))
+}
+
+/// Checks whether the path items of a train can be located on an infra
+///
+/// Returns the reason why they can't, if so.
+async fn unresolved_path(
+    conn: &mut DbConnection,
+    infra_id: i64,
+    train: &TrainSchedule,
+) -> Result<Option<PathfindingResult>> {
+    let locations = train
+        .path
+        .iter()
+        .map(|item| item.location.clone())
+        .collect::<Vec<_>>();
+    let track_offsets = match extract_location_from_path_items(conn, infra_id, &locations).await? {
+        Ok(track_offsets) => track_offsets,
+        Err(error) => return Ok(Some(error.into())),
+    };
+    Ok(check_tracks_from_path_items(conn, infra_id, &track_offsets)
+        .await?
+        .err())
+}
+
 type ScenarioWithDetailsV2 = ScenarioWithDetails;
 decl_paginated_response!(
     PaginatedResponseOfScenarioWithDetails,
@@ -440,8 +642,10 @@ mod tests {
     use crate::fixtures::tests::ScenarioV2FixtureSet;
     use crate::fixtures::tests::TestFixture;
     use crate::modelsv2::timetable::Timetable as TimetableV2;
+    use crate::modelsv2::train_schedule::TrainScheduleChangeset;
     use crate::modelsv2::Infra;
     use crate::views::tests::create_test_service;
+    use crate::views::v2::train_schedule::TrainScheduleForm;
 
     pub fn scenario_url(project_id: i64, study_id: i64, scenario_id: Option<i64>) -> String {
         format!(
@@ -587,6 +791,62 @@ mod tests {
         assert_eq!(response.scenario.tags, study_tags);
     }
 
+    #[rstest]
+    async fn duplicate_scenario(
+        #[future] scenario_v2_fixture_set: ScenarioV2FixtureSet,
+        db_pool: Arc<DbConnectionPool>,
+    ) {
+        let service = create_test_service().await;
+        let fixtures = scenario_v2_fixture_set.await;
+        let conn = &mut db_pool.get().await.unwrap();
+        // The train runs on the small infra, the path items don't resolve on the fixture infra
+        let train: TrainScheduleForm = TrainScheduleForm {
+            timetable_id: Some(fixtures.timetable.id()),
+            train_schedule: serde_json::from_str(include_str!(
+                "../../tests/train_schedules/simple.json"
+            ))
+            .unwrap(),
+        };
+        let train = TrainScheduleChangeset::from(train)
+            .create(conn)
+            .await
+            .unwrap();
+
+        let url = scenario_url(
+            fixtures.project.id(),
+            fixtures.study.id(),
+            Some(fixtures.scenario.id()),
+        );
+        let request = TestRequest::post()
+            .uri(&format!("{url}/duplicate"))
+            .set_json(json!({}))
+            .to_request();
+        let response: ScenarioDuplicateResponse = call_and_read_body_json(&service, request).await;
+
+        let copy = response.scenario.scenario;
+        assert_eq!(
+            copy.name,
+            format!("{} (copy)", fixtures.scenario.model.name)
+        );
+        assert_eq!(copy.study_id, fixtures.study.id());
+        assert_eq!(copy.infra_id, fixtures.infra.id());
+        assert_ne!(copy.timetable_id, fixtures.timetable.id());
+        assert_eq!(response.scenario.trains_count, 1);
+        assert_eq!(response.unresolved_trains.len(), 1);
+        let unresolved = &response.unresolved_trains[0];
+        assert_ne!(unresolved.train_id, train.id);
+        assert_eq!(unresolved.train_name, train.train_name);
+        assert!(matches!(
+            unresolved.reason,
+            PathfindingResult::InvalidPathItem { index: 0, .. }
+        ));
+
+        Scenario::delete_static(conn, copy.id).await.unwrap();
+        TimetableV2::delete_static(conn, copy.timetable_id)
+            .await
+            .unwrap();
+    }
+
     #[rstest]
     async fn patch_scenario_with_unavailable_infra(
         #[future] scenario_v2_fixture_set: ScenarioV2FixtureSet,
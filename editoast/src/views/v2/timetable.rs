@@ -1,4 +1,9 @@
+mod cadence;
+pub mod gtfs;
+pub mod import;
+mod report;
 pub mod stdcm;
+mod work_schedule_conflicts;
 
 use std::collections::HashMap;
 
@@ -29,7 +34,8 @@ use crate::core::AsCoreRequest;
 use crate::decl_paginated_response;
 use crate::error::Result;
 use crate::models::List;
-use crate::models::NoParams;
+use crate::modelsv2::resource_grant::ResourceType;
+use crate::modelsv2::resource_grant::Role;
 use crate::modelsv2::timetable::Timetable;
 use crate::modelsv2::timetable::TimetableWithTrains;
 use crate::modelsv2::train_schedule::TrainSchedule;
@@ -41,6 +47,7 @@ use crate::modelsv2::Infra;
 use crate::modelsv2::Model;
 use crate::modelsv2::Retrieve;
 use crate::modelsv2::Update;
+use crate::views::authz::Authentication;
 use crate::views::pagination::PaginatedResponse;
 use crate::views::pagination::PaginationQueryParam;
 use crate::views::v2::train_schedule::train_simulation_batch;
@@ -60,7 +67,12 @@ crate::routes! {
             put,
             conflicts,
             train_schedule,
+            cadence::routes(),
+            gtfs::routes(),
+            import::routes(),
+            report::routes(),
             stdcm::routes(),
+            work_schedule_conflicts::routes(),
         }
     },
 }
@@ -70,7 +82,11 @@ editoast_common::schemas! {
     TimetableForm,
     TimetableResult,
     TimetableDetailedResult,
+    cadence::schemas(),
+    import::schemas(),
+    report::schemas(),
     stdcm::schemas(),
+    work_schedule_conflicts::schemas(),
 }
 
 #[derive(Debug, Error, EditoastError)]
@@ -82,6 +98,9 @@ enum TimetableError {
     #[error("Infra '{infra_id}', could not be found")]
     #[editoast_error(status = 404)]
     InfraNotFound { infra_id: i64 },
+    #[error("The simulation report of train '{train_id}' is empty")]
+    #[editoast_error(status = 500)]
+    EmptySimulationReport { train_id: i64 },
 }
 
 /// Creation form for a Timetable
@@ -147,12 +166,17 @@ struct TimetableIdParam {
 #[get("")]
 async fn get(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     timetable_id: Path<TimetableIdParam>,
 ) -> Result<Json<TimetableDetailedResult>> {
     let timetable_id = timetable_id.id;
     // Return the timetable
 
     let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check_timetable(conn, timetable_id, Role::Viewer)
+        .await?;
     let timetable = TimetableWithTrains::retrieve_or_fail(conn, timetable_id, || {
         TimetableError::NotFound { timetable_id }
     })
@@ -174,6 +198,7 @@ decl_paginated_response!(PaginatedResponseOfTimetable, TimetableResult);
 #[get("")]
 async fn list(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     pagination_params: Query<PaginationQueryParam>,
 ) -> Result<Json<PaginatedResponse<TimetableResult>>> {
     let (page, per_page) = pagination_params
@@ -181,7 +206,9 @@ async fn list(
         .warn_page_size(100)
         .unpack();
     let conn = &mut db_pool.get().await?;
-    let timetable = Timetable::list_conn(conn, page, per_page, NoParams).await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    let hidden_study_ids = authorizer.hidden(conn, ResourceType::Study).await?;
+    let timetable = Timetable::list_conn(conn, page, per_page, hidden_study_ids).await?;
     Ok(Json(timetable.into()))
 }
 
@@ -220,11 +247,16 @@ async fn post(
 #[put("")]
 async fn put(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     timetable_id: Path<TimetableIdParam>,
     data: Json<TimetableForm>,
 ) -> Result<Json<TimetableDetailedResult>> {
     let timetable_id = timetable_id.id;
     let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check_timetable(conn, timetable_id, Role::Editor)
+        .await?;
 
     let elec_profile_set = data.into_inner().electrical_profile_set_id;
     let changeset = Timetable::changeset().electrical_profile_set_id(elec_profile_set);
@@ -253,10 +285,15 @@ async fn put(
 #[delete("")]
 async fn delete(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     timetable_id: Path<TimetableIdParam>,
 ) -> Result<HttpResponse> {
     let timetable_id = timetable_id.id;
     let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check_timetable(conn, timetable_id, Role::Editor)
+        .await?;
     Timetable::delete_static_or_fail(conn, timetable_id, || TimetableError::NotFound {
         timetable_id,
     })
@@ -276,6 +313,7 @@ async fn delete(
 #[post("train_schedule")]
 async fn train_schedule(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     timetable_id: Path<TimetableIdParam>,
     data: Json<Vec<TrainScheduleBase>>,
 ) -> Result<Json<Vec<TrainScheduleResult>>> {
@@ -284,6 +322,10 @@ async fn train_schedule(
     let conn = &mut db_pool.get().await?;
 
     let timetable_id = timetable_id.id;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check_timetable(conn, timetable_id, Role::Editor)
+        .await?;
     TimetableWithTrains::retrieve_or_fail(conn, timetable_id, || TimetableError::NotFound {
         timetable_id,
     })
@@ -322,6 +364,7 @@ pub async fn conflicts(
     db_pool: Data<DbConnectionPool>,
     redis_client: Data<RedisClient>,
     core_client: Data<CoreClient>,
+    authentication: Authentication,
     timetable_id: Path<TimetableIdParam>,
     query: Query<InfraIdQueryParam>,
 ) -> Result<Json<Vec<Conflict>>> {
@@ -331,6 +374,13 @@ pub async fn conflicts(
     let core_client = core_client.into_inner();
     let timetable_id = timetable_id.into_inner().id;
     let infra_id = query.into_inner().infra_id;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check_timetable(conn, timetable_id, Role::Viewer)
+        .await?;
+    authorizer
+        .check(conn, ResourceType::Infra, infra_id, Role::Viewer)
+        .await?;
 
     // 1. Retrieve Timetable / Infra / Trains / Simultion
     let timetable = TimetableWithTrains::retrieve_or_fail(conn, timetable_id, || {
@@ -0,0 +1,719 @@
+//! Cadenced trains: a template train schedule repeated at a fixed period
+//!
+//! A cadence remembers its template, so that editing it updates every train it generated.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use actix_web::delete;
+use actix_web::get;
+use actix_web::post;
+use actix_web::put;
+use actix_web::web::Data;
+use actix_web::web::Json;
+use actix_web::web::Path;
+use actix_web::HttpResponse;
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Utc;
+use diesel_async::scoped_futures::ScopedFutureExt;
+use diesel_async::AsyncConnection;
+use editoast_derive::EditoastError;
+use editoast_schemas::primitives::PositiveDuration;
+use editoast_schemas::train_schedule::TrainScheduleBase;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+use utoipa::IntoParams;
+use utoipa::ToSchema;
+
+use super::TimetableIdParam;
+use crate::error::InternalError;
+use crate::error::Result;
+use crate::modelsv2::prelude::*;
+use crate::modelsv2::resource_grant::Role;
+use crate::modelsv2::timetable::Timetable;
+use crate::modelsv2::train_schedule::TrainSchedule;
+use crate::modelsv2::train_schedule::TrainScheduleChangeset;
+use crate::modelsv2::train_schedule_cadence::TrainScheduleCadence;
+use crate::modelsv2::train_schedule_cadence::TrainScheduleCadenceChangeset;
+use crate::modelsv2::DbConnection;
+use crate::modelsv2::DbConnectionPool;
+use crate::views::authz::Authentication;
+use crate::views::v2::train_schedule::TrainScheduleForm;
+use crate::views::v2::train_schedule::TrainScheduleResult;
+
+crate::routes! {
+    "/cadence" => {
+        create,
+        list,
+        "/{cadence_id}" => {
+            get,
+            update,
+            delete,
+        },
+    },
+}
+
+editoast_common::schemas! {
+    CadenceForm,
+    CadenceResult,
+}
+
+/// The maximum number of trains a cadence can generate
+const MAX_CADENCE_TRAINS: usize = 1000;
+
+/// The maximum length of the name pattern of a cadence
+const MAX_NAME_PATTERN_LENGTH: usize = 128;
+
+#[derive(Debug, Error, EditoastError)]
+#[editoast_error(base_id = "train_schedule_cadence")]
+pub enum CadenceError {
+    #[error("Cadence '{cadence_id}' could not be found")]
+    #[editoast_error(status = 404)]
+    NotFound { cadence_id: i64 },
+    #[error("Timetable '{timetable_id}' could not be found")]
+    #[editoast_error(status = 404)]
+    TimetableNotFound { timetable_id: i64 },
+    #[error("The period of a cadence can't be zero")]
+    #[editoast_error(status = 400)]
+    NullPeriod,
+    #[error("The time window of a cadence can't end before it starts")]
+    #[editoast_error(status = 400)]
+    InvalidWindow,
+    #[error("A cadence can't generate more than {max} trains")]
+    #[editoast_error(status = 400)]
+    TooManyTrains { max: usize },
+    #[error("The name pattern of a cadence can't be longer than {max_length} characters")]
+    #[editoast_error(status = 400)]
+    NamePatternTooLong { max_length: usize },
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[allow(unused)]
+struct CadenceIdParam {
+    /// A cadence ID
+    cadence_id: i64,
+}
+
+/// A template train schedule repeated at a fixed period
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CadenceForm {
+    /// The train schedule to repeat, its start time sets the phase of the departures
+    pub template: TrainScheduleBase,
+    /// The time between two departures
+    #[schema(value_type = String, example = "PT30M")]
+    pub period: PositiveDuration,
+    /// The first possible departure time
+    pub window_start: DateTime<Utc>,
+    /// The last possible departure time
+    pub window_end: DateTime<Utc>,
+    /// The name of the generated trains
+    ///
+    /// `{name}` is replaced by the name of the template, `{n}` by the rank of the departure
+    /// (starting at 1) and `{time}` by its time (HH:MM, UTC).
+    #[schema(example = "{name} {time}", max_length = 128)]
+    pub name_pattern: String,
+}
+
+impl CadenceForm {
+    fn validate(&self) -> Result<()> {
+        if self.name_pattern.chars().count() > MAX_NAME_PATTERN_LENGTH {
+            return Err(CadenceError::NamePatternTooLong {
+                max_length: MAX_NAME_PATTERN_LENGTH,
+            }
+            .into());
+        }
+        departures(
+            self.template.start_time,
+            *self.period,
+            self.window_start,
+            self.window_end,
+        )?;
+        Ok(())
+    }
+}
+
+impl From<CadenceForm> for TrainScheduleCadenceChangeset {
+    fn from(form: CadenceForm) -> Self {
+        TrainScheduleCadence::changeset()
+            .template(form.template)
+            .period(form.period.num_milliseconds())
+            .window_start(form.window_start)
+            .window_end(form.window_end)
+            .name_pattern(form.name_pattern)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CadenceResult {
+    pub id: i64,
+    pub timetable_id: i64,
+    #[serde(flatten)]
+    pub cadence: CadenceForm,
+    /// The trains of the cadence, sorted by start time
+    pub train_ids: Vec<i64>,
+    /// The trains of the cadence which were edited individually
+    ///
+    /// Updates of the cadence leave them as they are.
+    pub edited_train_ids: Vec<i64>,
+}
+
+impl CadenceResult {
+    fn new(cadence: TrainScheduleCadence, trains: Vec<TrainSchedule>) -> Result<Self> {
+        let train_ids = trains.iter().map(|train| train.id).collect();
+        let (_, edited) = split_edited_trains(&cadence, trains)?;
+        Ok(Self {
+            id: cadence.id,
+            timetable_id: cadence.timetable_id,
+            cadence: CadenceForm {
+                template: cadence.template,
+                period: Duration::milliseconds(cadence.period)
+                    .try_into()
+                    .expect("periods are positive"),
+                window_start: cadence.window_start,
+                window_end: cadence.window_end,
+                name_pattern: cadence.name_pattern,
+            },
+            train_ids,
+            edited_train_ids: edited.into_iter().map(|train| train.id).collect(),
+        })
+    }
+
+    async fn load(conn: &mut DbConnection, cadence: TrainScheduleCadence) -> Result<Self> {
+        let trains = cadence.trains(conn).await?;
+        Self::new(cadence, trains)
+    }
+}
+
+/// The departures of a cadence, in chronological order
+///
+/// These are the times within the window which are a whole number of periods away from the anchor.
+fn departures(
+    anchor: DateTime<Utc>,
+    period: Duration,
+    window_start: DateTime<Utc>,
+    window_end: DateTime<Utc>,
+) -> Result<Vec<DateTime<Utc>>> {
+    if period <= Duration::zero() {
+        return Err(CadenceError::NullPeriod.into());
+    }
+    if window_end < window_start {
+        return Err(CadenceError::InvalidWindow.into());
+    }
+    let period_ms = period.num_milliseconds();
+    let offset = (window_start - anchor).num_milliseconds();
+    let first_rank = offset.div_euclid(period_ms) + (offset.rem_euclid(period_ms) != 0) as i64;
+    let first = anchor + Duration::milliseconds(first_rank * period_ms);
+    if first > window_end {
+        return Ok(vec![]);
+    }
+    let count = (window_end - first).num_milliseconds() / period_ms + 1;
+    if count as usize > MAX_CADENCE_TRAINS {
+        return Err(CadenceError::TooManyTrains {
+            max: MAX_CADENCE_TRAINS,
+        }
+        .into());
+    }
+    Ok((0..count)
+        .map(|rank| first + Duration::milliseconds(rank * period_ms))
+        .collect())
+}
+
+/// The name of the train generated for a departure of a cadence
+fn train_name(pattern: &str, name: &str, rank: usize, departure: DateTime<Utc>) -> String {
+    pattern
+        .replace("{name}", name)
+        .replace("{n}", &rank.to_string())
+        .replace("{time}", &departure.format("%H:%M").to_string())
+}
+
+/// The train schedules of all the departures of a cadence
+fn generate_trains(cadence: &TrainScheduleCadence) -> Result<Vec<TrainScheduleBase>> {
+    let template = &cadence.template;
+    let departures = departures(
+        template.start_time,
+        Duration::milliseconds(cadence.period),
+        cadence.window_start,
+        cadence.window_end,
+    )?;
+    Ok(departures
+        .into_iter()
+        .enumerate()
+        .map(|(index, departure)| TrainScheduleBase {
+            train_name: train_name(
+                &cadence.name_pattern,
+                &template.train_name,
+                index + 1,
+                departure,
+            ),
+            start_time: departure,
+            ..template.clone()
+        })
+        .collect())
+}
+
+fn train_changeset(
+    cadence: &TrainScheduleCadence,
+    train: TrainScheduleBase,
+) -> TrainScheduleChangeset {
+    let departure = train.start_time;
+    TrainScheduleChangeset::from(TrainScheduleForm {
+        timetable_id: Some(cadence.timetable_id),
+        train_schedule: train,
+    })
+    .cadence_id(Some(cadence.id))
+    .cadence_departure(Some(departure))
+}
+
+/// The departure of the cadence a train was generated for
+///
+/// Trains generated before departures were recorded fall back to their start time.
+fn train_departure(train: &TrainSchedule) -> DateTime<Utc> {
+    train.cadence_departure.unwrap_or(train.start_time)
+}
+
+/// Splits the trains of a cadence between the ones it generated and the ones edited individually
+///
+/// A train is edited when it differs from the train the cadence generates for its departure.
+fn split_edited_trains(
+    cadence: &TrainScheduleCadence,
+    trains: Vec<TrainSchedule>,
+) -> Result<(Vec<TrainSchedule>, Vec<TrainSchedule>)> {
+    let mut generated = HashMap::new();
+    for train in generate_trains(cadence)? {
+        generated.insert(train.start_time, serde_json::to_value(train)?);
+    }
+    let mut unchanged = vec![];
+    let mut edited = vec![];
+    for train in trains {
+        let content =
+            serde_json::to_value(TrainScheduleResult::from(train.clone()).train_schedule)?;
+        if generated.get(&train_departure(&train)) == Some(&content) {
+            unchanged.push(train);
+        } else {
+            edited.push(train);
+        }
+    }
+    Ok((unchanged, edited))
+}
+
+/// Brings the trains of a cadence in line with its template
+///
+/// Trains are matched with the departures of the cadence they were generated for, so that
+/// they keep their ids. Trains edited individually since the `previous` version of the cadence
+/// are left untouched, and their departures are not generated again, even if they were moved.
+/// Missing trains are created and the ones without departure are deleted.
+async fn sync_trains(
+    conn: &mut DbConnection,
+    previous: &TrainScheduleCadence,
+    cadence: &TrainScheduleCadence,
+) -> Result<()> {
+    let (unchanged, edited) = split_edited_trains(previous, cadence.trains(conn).await?)?;
+    let edited_departures: HashSet<_> = edited.iter().map(train_departure).collect();
+    let mut existing = HashMap::new();
+    let mut obsolete = vec![];
+    for train in unchanged {
+        if let Some(duplicate) = existing.insert(train_departure(&train), train.id) {
+            obsolete.push(duplicate);
+        }
+    }
+    let mut missing = vec![];
+    for train in generate_trains(cadence)? {
+        if edited_departures.contains(&train.start_time) {
+            continue;
+        }
+        let existing_id = existing.remove(&train.start_time);
+        let changeset = train_changeset(cadence, train);
+        match existing_id {
+            Some(train_id) => {
+                changeset
+                    .update_or_fail(conn, train_id, || CadenceError::NotFound {
+                        cadence_id: cadence.id,
+                    })
+                    .await?;
+            }
+            None => missing.push(changeset),
+        }
+    }
+    let _: Vec<_> = TrainSchedule::create_batch(conn, missing).await?;
+    obsolete.extend(existing.into_values());
+    TrainSchedule::delete_batch(conn, obsolete).await?;
+    Ok(())
+}
+
+async fn retrieve_cadence(
+    conn: &mut DbConnection,
+    timetable_id: i64,
+    cadence_id: i64,
+) -> Result<TrainScheduleCadence> {
+    let cadence = TrainScheduleCadence::retrieve_or_fail(conn, cadence_id, || {
+        CadenceError::NotFound { cadence_id }
+    })
+    .await?;
+    if cadence.timetable_id != timetable_id {
+        return Err(CadenceError::NotFound { cadence_id }.into());
+    }
+    Ok(cadence)
+}
+
+/// Create a cadence and all its trains
+#[utoipa::path(
+    tag = "timetablev2,train_schedulev2",
+    params(TimetableIdParam),
+    request_body = CadenceForm,
+    responses(
+        (status = 200, description = "The cadence and the ids of its trains", body = CadenceResult),
+        (status = 404, description = "Timetable not found"),
+    ),
+)]
+#[post("")]
+async fn create(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    timetable_id: Path<TimetableIdParam>,
+    data: Json<CadenceForm>,
+) -> Result<Json<CadenceResult>> {
+    let timetable_id = timetable_id.id;
+    let form = data.into_inner();
+    form.validate()?;
+    let changeset: TrainScheduleCadenceChangeset = form.into();
+    let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check_timetable(conn, timetable_id, Role::Editor)
+        .await?;
+
+    let cadence = conn
+        .transaction::<_, InternalError, _>(|conn| {
+            async move {
+                if !Timetable::exists(conn, timetable_id).await? {
+                    return Err(CadenceError::TimetableNotFound { timetable_id }.into());
+                }
+                let cadence = changeset.timetable_id(timetable_id).create(conn).await?;
+                sync_trains(conn, &cadence, &cadence).await?;
+                CadenceResult::load(conn, cadence).await
+            }
+            .scope_boxed()
+        })
+        .await?;
+    Ok(Json(cadence))
+}
+
+/// List the cadences of a timetable
+#[utoipa::path(
+    tag = "timetablev2",
+    params(TimetableIdParam),
+    responses(
+        (status = 200, description = "The cadences of the timetable", body = Vec<CadenceResult>),
+        (status = 404, description = "Timetable not found"),
+    ),
+)]
+#[get("")]
+async fn list(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    timetable_id: Path<TimetableIdParam>,
+) -> Result<Json<Vec<CadenceResult>>> {
+    let timetable_id = timetable_id.id;
+    let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check_timetable(conn, timetable_id, Role::Viewer)
+        .await?;
+    if !Timetable::exists(conn, timetable_id).await? {
+        return Err(CadenceError::TimetableNotFound { timetable_id }.into());
+    }
+    let mut results = vec![];
+    for cadence in TrainScheduleCadence::list_for_timetable(conn, timetable_id).await? {
+        results.push(CadenceResult::load(conn, cadence).await?);
+    }
+    Ok(Json(results))
+}
+
+/// Return a cadence with the ids of its trains
+#[utoipa::path(
+    tag = "timetablev2",
+    params(TimetableIdParam, CadenceIdParam),
+    responses(
+        (status = 200, description = "The cadence and the ids of its trains", body = CadenceResult),
+        (status = 404, description = "Cadence not found"),
+    ),
+)]
+#[get("")]
+async fn get(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    path: Path<(i64, i64)>,
+) -> Result<Json<CadenceResult>> {
+    let (timetable_id, cadence_id) = path.into_inner();
+    let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check_timetable(conn, timetable_id, Role::Viewer)
+        .await?;
+    let cadence = retrieve_cadence(conn, timetable_id, cadence_id).await?;
+    Ok(Json(CadenceResult::load(conn, cadence).await?))
+}
+
+/// Update a cadence and propagate the changes to its trains
+///
+/// Trains are regenerated from the template and keep their ids when the departure they were
+/// generated for is still part of the cadence. Trains edited individually are left untouched,
+/// even if they were moved, and their departures are not generated again.
+#[utoipa::path(
+    tag = "timetablev2,train_schedulev2",
+    params(TimetableIdParam, CadenceIdParam),
+    request_body = CadenceForm,
+    responses(
+        (status = 200, description = "The cadence and the ids of its trains", body = CadenceResult),
+        (status = 404, description = "Cadence not found"),
+    ),
+)]
+#[put("")]
+async fn update(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    path: Path<(i64, i64)>,
+    data: Json<CadenceForm>,
+) -> Result<Json<CadenceResult>> {
+    let (timetable_id, cadence_id) = path.into_inner();
+    let form = data.into_inner();
+    form.validate()?;
+    let changeset: TrainScheduleCadenceChangeset = form.into();
+    let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check_timetable(conn, timetable_id, Role::Editor)
+        .await?;
+
+    let cadence = conn
+        .transaction::<_, InternalError, _>(|conn| {
+            async move {
+                let previous = retrieve_cadence(conn, timetable_id, cadence_id).await?;
+                let cadence = changeset
+                    .update_or_fail(conn, cadence_id, || CadenceError::NotFound { cadence_id })
+                    .await?;
+                sync_trains(conn, &previous, &cadence).await?;
+                CadenceResult::load(conn, cadence).await
+            }
+            .scope_boxed()
+        })
+        .await?;
+    Ok(Json(cadence))
+}
+
+/// Delete a cadence and its trains
+#[utoipa::path(
+    tag = "timetablev2,train_schedulev2",
+    params(TimetableIdParam, CadenceIdParam),
+    responses(
+        (status = 204, description = "The cadence and its trains were deleted"),
+        (status = 404, description = "Cadence not found"),
+    ),
+)]
+#[delete("")]
+async fn delete(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    path: Path<(i64, i64)>,
+) -> Result<HttpResponse> {
+    let (timetable_id, cadence_id) = path.into_inner();
+    let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check_timetable(conn, timetable_id, Role::Editor)
+        .await?;
+    // Trains are deleted along with their cadence
+    let cadence = retrieve_cadence(conn, timetable_id, cadence_id).await?;
+    cadence.delete(conn).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use actix_web::http::StatusCode;
+    use actix_web::test::call_and_read_body_json;
+    use actix_web::test::call_service;
+    use actix_web::test::TestRequest;
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::*;
+    use crate::fixtures::tests::db_pool;
+    use crate::fixtures::tests::timetable_v2;
+    use crate::fixtures::tests::TestFixture;
+    use crate::views::tests::create_test_service;
+
+    fn time(time: &str) -> DateTime<Utc> {
+        format!("2024-05-28T{time}Z").parse().unwrap()
+    }
+
+    #[test]
+    fn departures_within_window() {
+        let times = departures(
+            time("08:15:00"),
+            Duration::minutes(30),
+            time("06:00:00"),
+            time("08:45:00"),
+        )
+        .unwrap();
+        assert_eq!(
+            times,
+            ["06:15:00", "06:45:00", "07:15:00", "07:45:00", "08:15:00", "08:45:00"].map(time)
+        );
+
+        let times = departures(
+            time("05:00:00"),
+            Duration::hours(1),
+            time("06:00:00"),
+            time("06:59:59"),
+        )
+        .unwrap();
+        assert_eq!(times, [time("06:00:00")]);
+    }
+
+    #[test]
+    fn invalid_departures() {
+        let anchor = time("08:00:00");
+        assert!(departures(anchor, Duration::zero(), anchor, anchor).is_err());
+        assert!(departures(anchor, Duration::hours(1), anchor, time("07:00:00")).is_err());
+        assert!(departures(anchor, Duration::seconds(1), anchor, time("09:00:00")).is_err());
+    }
+
+    #[test]
+    fn name_trains() {
+        assert_eq!(
+            train_name("{name} #{n} ({time})", "RE", 3, time("07:05:00")),
+            "RE #3 (07:05)"
+        );
+    }
+
+    #[rstest]
+    async fn create_and_update_cadence(
+        #[future] timetable_v2: TestFixture<Timetable>,
+        db_pool: Arc<DbConnectionPool>,
+    ) {
+        let timetable = timetable_v2.await;
+        let service = create_test_service().await;
+        let template: serde_json::Value =
+            serde_json::from_str(include_str!("../../../tests/train_schedules/simple.json"))
+                .unwrap();
+        let url = format!("/v2/timetable/{}/cadence", timetable.id());
+
+        let request = TestRequest::post()
+            .uri(&url)
+            .set_json(json!({
+                "template": template,
+                "period": "PT1H",
+                "window_start": "2023-12-21T08:00:00Z",
+                "window_end": "2023-12-21T10:59:00Z",
+                "name_pattern": "{name}-{n}",
+            }))
+            .to_request();
+        let cadence: CadenceResult = call_and_read_body_json(&service, request).await;
+        assert_eq!(cadence.train_ids.len(), 3);
+
+        let conn = &mut db_pool.get().await.unwrap();
+        let trains: Vec<TrainSchedule> =
+            TrainSchedule::retrieve_batch_unchecked(conn, cadence.train_ids.clone())
+                .await
+                .unwrap();
+        assert!(trains
+            .iter()
+            .all(|train| train.cadence_id == Some(cadence.id)));
+        let mut names = trains
+            .iter()
+            .map(|train| train.train_name.clone())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["ABC3615-1", "ABC3615-2", "ABC3615-3"]);
+
+        // Shrink the window and change the template
+        let mut form = cadence.cadence.clone();
+        form.window_end = "2023-12-21T09:59:00Z".parse().unwrap();
+        form.template.initial_speed = 10.;
+        let request = TestRequest::put()
+            .uri(&format!("{url}/{}", cadence.id))
+            .set_json(form)
+            .to_request();
+        let updated: CadenceResult = call_and_read_body_json(&service, request).await;
+        assert_eq!(updated.train_ids, cadence.train_ids[..2]);
+        let mut trains: Vec<TrainSchedule> =
+            TrainSchedule::retrieve_batch_unchecked(conn, updated.train_ids.clone())
+                .await
+                .unwrap();
+        trains.sort_by_key(|train| train.start_time);
+        assert!(trains.iter().all(|train| train.initial_speed == 10.));
+        assert!(!TrainSchedule::exists(conn, cadence.train_ids[2])
+            .await
+            .unwrap());
+
+        // Edit the second train, then move the window by an hour
+        let mut edited = trains[1].clone();
+        edited.patch().initial_speed(20.).apply(conn).await.unwrap();
+        let mut form = updated.cadence.clone();
+        form.window_start = "2023-12-21T09:00:00Z".parse().unwrap();
+        form.window_end = "2023-12-21T11:59:00Z".parse().unwrap();
+        let request = TestRequest::put()
+            .uri(&format!("{url}/{}", cadence.id))
+            .set_json(form)
+            .to_request();
+        let moved: CadenceResult = call_and_read_body_json(&service, request).await;
+        assert_eq!(moved.train_ids.len(), 3);
+        assert_eq!(moved.train_ids[0], edited.id);
+        assert_eq!(moved.edited_train_ids, [edited.id]);
+        assert!(!TrainSchedule::exists(conn, trains[0].id).await.unwrap());
+        let edited = TrainSchedule::retrieve(conn, edited.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(edited.initial_speed, 20.);
+
+        // Move the edited train, its departure must not be generated again
+        let departure = edited.start_time;
+        let mut edited = edited;
+        edited
+            .patch()
+            .start_time(departure + Duration::minutes(5))
+            .apply(conn)
+            .await
+            .unwrap();
+        let request = TestRequest::put()
+            .uri(&format!("{url}/{}", cadence.id))
+            .set_json(moved.cadence.clone())
+            .to_request();
+        let updated: CadenceResult = call_and_read_body_json(&service, request).await;
+        assert_eq!(updated.train_ids.len(), 3);
+        assert_eq!(updated.edited_train_ids, [edited.id]);
+        let trains: Vec<TrainSchedule> =
+            TrainSchedule::retrieve_batch_unchecked(conn, updated.train_ids)
+                .await
+                .unwrap();
+        assert!(trains.iter().all(|train| train.start_time != departure));
+    }
+
+    #[rstest]
+    async fn name_pattern_too_long(#[future] timetable_v2: TestFixture<Timetable>) {
+        let timetable = timetable_v2.await;
+        let service = create_test_service().await;
+        let template: serde_json::Value =
+            serde_json::from_str(include_str!("../../../tests/train_schedules/simple.json"))
+                .unwrap();
+
+        let request = TestRequest::post()
+            .uri(&format!("/v2/timetable/{}/cadence", timetable.id()))
+            .set_json(json!({
+                "template": template,
+                "period": "PT1H",
+                "window_start": "2023-12-21T08:00:00Z",
+                "window_end": "2023-12-21T10:59:00Z",
+                "name_pattern": "{name}".repeat(30),
+            }))
+            .to_request();
+        let response = call_service(&service, request).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
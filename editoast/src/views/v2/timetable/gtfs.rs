@@ -0,0 +1,709 @@
+//! Export of a timetable to a [GTFS](https://gtfs.org/schedule/reference/) feed
+//!
+//! The feed is built from the simulation of each train of the timetable:
+//! - `stops.txt` lists the operational points along the paths of the trains
+//! - `routes.txt` groups the trains sharing the same labels
+//! - `trips.txt` and `stop_times.txt` give the simulated arrival and departure times at each path item
+//! - `shapes.txt` contains the geometry of the paths
+//!
+//! Times are expressed in UTC, relative to the day on which each train starts.
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::io::Seek;
+use std::io::Write;
+use std::sync::Arc;
+
+use actix_web::get;
+use actix_web::web::Data;
+use actix_web::web::Path;
+use actix_web::web::Query;
+use actix_web::HttpResponse;
+use chrono::NaiveDate;
+use editoast_common::geometry::GeoJsonLineString;
+use editoast_common::geometry::GeoJsonLineStringValue;
+use itertools::Itertools;
+use serde::Serialize;
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+use super::InfraIdQueryParam;
+use super::TimetableError;
+use super::TimetableIdParam;
+use crate::core::v2::path_properties::OperationalPointOnPath;
+use crate::core::v2::path_properties::PathPropertiesRequest;
+use crate::core::v2::pathfinding::PathfindingResult;
+use crate::core::v2::pathfinding::PathfindingResultSuccess;
+use crate::core::v2::simulation::ReportTrain;
+use crate::core::v2::simulation::SimulationResponse;
+use crate::core::AsCoreRequest;
+use crate::error::Result;
+use crate::modelsv2::prelude::*;
+use crate::modelsv2::resource_grant::ResourceType;
+use crate::modelsv2::resource_grant::Role;
+use crate::modelsv2::timetable::TimetableWithTrains;
+use crate::modelsv2::train_schedule::TrainSchedule;
+use crate::modelsv2::DbConnectionPool;
+use crate::modelsv2::Infra;
+use crate::views::authz::Authentication;
+use crate::views::v2::path::pathfinding_from_train;
+use crate::views::v2::train_schedule::train_simulation_batch;
+use crate::CoreClient;
+use crate::RedisClient;
+
+crate::routes! {
+    "/gtfs" => {
+        gtfs,
+    },
+}
+
+/// The mean radius of the earth in meters
+const EARTH_RADIUS: f64 = 6_371_000.;
+
+/// GTFS route type of railway services
+const RAIL_ROUTE_TYPE: u8 = 2;
+
+const AGENCY_ID: &str = "osrd";
+
+/// Export the simulated trains of a timetable as a GTFS zip (invalid trains are ignored)
+#[utoipa::path(
+    tag = "timetablev2",
+    params(TimetableIdParam, InfraIdQueryParam),
+    responses(
+        (status = 200, description = "The GTFS feed of the timetable", content_type = "application/zip"),
+        (status = 404, description = "Timetable or infra not found"),
+    ),
+)]
+#[get("")]
+async fn gtfs(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    redis_client: Data<RedisClient>,
+    core_client: Data<CoreClient>,
+    timetable_id: Path<TimetableIdParam>,
+    query: Query<InfraIdQueryParam>,
+) -> Result<HttpResponse> {
+    let db_pool = db_pool.into_inner();
+    let conn = &mut db_pool.clone().get().await?;
+    let timetable_id = timetable_id.into_inner().id;
+    let infra_id = query.into_inner().infra_id;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check_timetable(conn, timetable_id, Role::Viewer)
+        .await?;
+    authorizer
+        .check(conn, ResourceType::Infra, infra_id, Role::Viewer)
+        .await?;
+
+    let timetable = TimetableWithTrains::retrieve_or_fail(conn, timetable_id, || {
+        TimetableError::NotFound { timetable_id }
+    })
+    .await?;
+    let infra = Infra::retrieve_or_fail(conn, infra_id, || TimetableError::InfraNotFound {
+        infra_id,
+    })
+    .await?;
+
+    let feed = timetable_gtfs_feed(
+        db_pool,
+        redis_client.into_inner(),
+        core_client.into_inner(),
+        timetable,
+        &infra,
+    )
+    .await?;
+    let mut zip = Cursor::new(Vec::new());
+    feed.write(&mut zip)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/zip")
+        .insert_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"timetable_{timetable_id}_gtfs.zip\""),
+        ))
+        .body(zip.into_inner()))
+}
+
+/// Simulates the trains of a timetable on an infra and builds their GTFS feed
+pub async fn timetable_gtfs_feed(
+    db_pool: Arc<DbConnectionPool>,
+    redis_client: Arc<RedisClient>,
+    core_client: Arc<CoreClient>,
+    timetable: TimetableWithTrains,
+    infra: &Infra,
+) -> Result<GtfsFeed> {
+    let conn = &mut db_pool.clone().get().await?;
+    let mut redis_conn = redis_client.get_connection().await?;
+
+    let (trains, _): (Vec<TrainSchedule>, _) =
+        TrainSchedule::retrieve_batch(conn, timetable.train_ids).await?;
+    let simulations = train_simulation_batch(
+        db_pool.clone(),
+        redis_client.clone(),
+        core_client.clone(),
+        &trains,
+        infra,
+    )
+    .await?;
+
+    let mut feed = GtfsFeed::default();
+    for (train, sim) in trains.into_iter().zip(simulations) {
+        let SimulationResponse::Success { final_output, .. } = sim else {
+            feed.skipped_trains.push(train.id);
+            continue;
+        };
+        let PathfindingResult::Success(PathfindingResultSuccess {
+            track_section_ranges,
+            length,
+            path_items_positions,
+            ..
+        }) = pathfinding_from_train(
+            conn,
+            &mut redis_conn,
+            core_client.clone(),
+            infra,
+            train.clone(),
+        )
+        .await?
+        else {
+            feed.skipped_trains.push(train.id);
+            continue;
+        };
+        let properties = PathPropertiesRequest {
+            track_section_ranges: &track_section_ranges,
+            infra: infra.id,
+            expected_version: infra.version.clone(),
+        }
+        .fetch(core_client.as_ref())
+        .await?;
+        let GeoJsonLineString::LineString(GeoJsonLineStringValue(points)) = properties.geometry;
+        let path = TrainPath {
+            length,
+            path_items_positions,
+            geometry: points
+                .into_iter()
+                .map(|point| (point.0[0], point.0[1]))
+                .collect(),
+            operational_points: properties.operational_points,
+        };
+        feed.add_trip(&train, &path, &final_output.report_train);
+    }
+    Ok(feed)
+}
+
+/// The path of a train, as computed by the core
+#[derive(Debug)]
+struct TrainPath {
+    /// Length of the path in mm
+    length: u64,
+    /// Position of each path item of the train in mm
+    path_items_positions: Vec<u64>,
+    /// Longitude and latitude of the points of the path
+    geometry: Vec<(f64, f64)>,
+    operational_points: Vec<OperationalPointOnPath>,
+}
+
+/// The content of a GTFS feed
+#[derive(Debug, Default)]
+pub struct GtfsFeed {
+    stops: BTreeMap<String, Stop>,
+    routes: BTreeMap<String, Route>,
+    trips: Vec<Trip>,
+    stop_times: Vec<StopTime>,
+    shapes: Vec<ShapePoint>,
+    service_dates: BTreeSet<NaiveDate>,
+    /// Trains which couldn't be simulated and are missing from the feed
+    pub skipped_trains: Vec<i64>,
+}
+
+#[derive(Debug, Serialize)]
+struct Agency {
+    agency_id: &'static str,
+    agency_name: &'static str,
+    agency_url: &'static str,
+    agency_timezone: &'static str,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Stop {
+    stop_id: String,
+    stop_name: String,
+    stop_lat: f64,
+    stop_lon: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct Route {
+    route_id: String,
+    agency_id: &'static str,
+    route_short_name: String,
+    route_type: u8,
+}
+
+#[derive(Debug, Serialize)]
+struct Trip {
+    route_id: String,
+    service_id: String,
+    trip_id: String,
+    trip_headsign: String,
+    trip_short_name: String,
+    shape_id: String,
+}
+
+#[derive(Debug, Serialize)]
+struct StopTime {
+    trip_id: String,
+    arrival_time: String,
+    departure_time: String,
+    stop_id: String,
+    stop_sequence: usize,
+    pickup_type: u8,
+    drop_off_type: u8,
+    shape_dist_traveled: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct ShapePoint {
+    shape_id: String,
+    shape_pt_lat: f64,
+    shape_pt_lon: f64,
+    shape_pt_sequence: usize,
+    shape_dist_traveled: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct CalendarDate {
+    service_id: String,
+    date: String,
+    exception_type: u8,
+}
+
+impl GtfsFeed {
+    /// Number of trains in the feed
+    pub fn trip_count(&self) -> usize {
+        self.trips.len()
+    }
+
+    /// Adds a simulated train to the feed
+    fn add_trip(&mut self, train: &TrainSchedule, path: &TrainPath, report: &ReportTrain) {
+        let trip_id = train.id.to_string();
+        let geometry = PathGeometry::new(&path.geometry, path.length as f64 / 1000.);
+
+        // Stops
+        for operational_point in &path.operational_points {
+            self.stops
+                .entry(operational_point.id.0.clone())
+                .or_insert_with(|| {
+                    let (stop_lon, stop_lat) =
+                        geometry.point_at(operational_point.position as f64 / 1000.);
+                    Stop {
+                        stop_id: operational_point.id.0.clone(),
+                        stop_name: operational_point
+                            .extensions
+                            .identifier
+                            .as_ref()
+                            .map(|identifier| identifier.name.0.clone())
+                            .unwrap_or_else(|| operational_point.id.0.clone()),
+                        stop_lat,
+                        stop_lon,
+                    }
+                });
+        }
+
+        // Route
+        let labels = train.labels.iter().flatten().sorted().dedup().join(", ");
+        let route_id = if labels.is_empty() {
+            "unlabeled".to_owned()
+        } else {
+            labels
+        };
+        self.routes
+            .entry(route_id.clone())
+            .or_insert_with(|| Route {
+                route_id: route_id.clone(),
+                agency_id: AGENCY_ID,
+                route_short_name: route_id.clone(),
+                route_type: RAIL_ROUTE_TYPE,
+            });
+
+        // Stop times
+        let service_date = train.start_time.date_naive();
+        let day_start = service_date.and_hms_opt(0, 0, 0).unwrap().and_utc();
+        let start_offset = (train.start_time - day_start).num_milliseconds() as u64;
+        let stop_durations: HashMap<_, _> = train
+            .schedule
+            .iter()
+            .filter_map(|item| Some((&item.at.0, item.stop_for.as_ref()?.num_milliseconds())))
+            .collect();
+        let last_index = train.path.len().saturating_sub(1);
+        let mut headsign = String::new();
+        for (index, (path_item, &position)) in train
+            .path
+            .iter()
+            .zip(&path.path_items_positions)
+            .enumerate()
+        {
+            let stop_id = match path
+                .operational_points
+                .iter()
+                .find(|operational_point| operational_point.position == position)
+            {
+                Some(operational_point) => operational_point.id.0.clone(),
+                None => {
+                    let stop_id = format!("{}:{}", train.id, path_item.id.0);
+                    let (stop_lon, stop_lat) = geometry.point_at(position as f64 / 1000.);
+                    self.stops.insert(
+                        stop_id.clone(),
+                        Stop {
+                            stop_id: stop_id.clone(),
+                            stop_name: path_item.id.0.clone(),
+                            stop_lat,
+                            stop_lon,
+                        },
+                    );
+                    stop_id
+                }
+            };
+            headsign.clone_from(&self.stops[&stop_id].stop_name);
+
+            let stop_duration = stop_durations.get(&path_item.id.0).copied();
+            let (arrival, departure) = passage_times(report, position);
+            let departure = match stop_duration {
+                Some(duration) => departure.max(arrival + duration as u64),
+                None => departure,
+            };
+            // Passengers can only board or alight where the train stops
+            let boarding = if stop_duration.is_some() || index == 0 || index == last_index {
+                0
+            } else {
+                1
+            };
+            self.stop_times.push(StopTime {
+                trip_id: trip_id.clone(),
+                arrival_time: gtfs_time(start_offset + arrival),
+                departure_time: gtfs_time(start_offset + departure),
+                stop_id,
+                stop_sequence: index,
+                pickup_type: boarding,
+                drop_off_type: boarding,
+                shape_dist_traveled: position as f64 / 1000.,
+            });
+        }
+
+        // Shape
+        self.shapes.extend(
+            geometry
+                .points
+                .iter()
+                .zip(&geometry.distances)
+                .enumerate()
+                .map(|(sequence, (&(lon, lat), &distance))| ShapePoint {
+                    shape_id: trip_id.clone(),
+                    shape_pt_lat: lat,
+                    shape_pt_lon: lon,
+                    shape_pt_sequence: sequence,
+                    shape_dist_traveled: distance,
+                }),
+        );
+
+        self.service_dates.insert(service_date);
+        self.trips.push(Trip {
+            route_id,
+            service_id: service_id(service_date),
+            trip_id: trip_id.clone(),
+            trip_headsign: headsign,
+            trip_short_name: train.train_name.clone(),
+            shape_id: trip_id,
+        });
+    }
+
+    /// Writes the feed as a zip archive
+    pub fn write<W: Write + Seek>(&self, writer: W) -> Result<()> {
+        let mut zip = ZipWriter::new(writer);
+        let agency = Agency {
+            agency_id: AGENCY_ID,
+            agency_name: "OSRD",
+            agency_url: "https://osrd.fr",
+            agency_timezone: "UTC",
+        };
+        write_csv(&mut zip, "agency.txt", [agency])?;
+        write_csv(&mut zip, "stops.txt", self.stops.values())?;
+        write_csv(&mut zip, "routes.txt", self.routes.values())?;
+        write_csv(&mut zip, "trips.txt", &self.trips)?;
+        write_csv(&mut zip, "stop_times.txt", &self.stop_times)?;
+        write_csv(&mut zip, "shapes.txt", &self.shapes)?;
+        let calendar_dates = self.service_dates.iter().map(|&date| CalendarDate {
+            service_id: service_id(date),
+            date: date.format("%Y%m%d").to_string(),
+            exception_type: 1,
+        });
+        write_csv(&mut zip, "calendar_dates.txt", calendar_dates)?;
+        zip.finish()?;
+        Ok(())
+    }
+}
+
+fn write_csv<W: Write + Seek, T: Serialize>(
+    zip: &mut ZipWriter<W>,
+    name: &str,
+    records: impl IntoIterator<Item = T>,
+) -> Result<()> {
+    zip.start_file(name, FileOptions::default())?;
+    let mut writer = csv::Writer::from_writer(zip);
+    for record in records {
+        writer.serialize(record)?;
+    }
+    writer.flush().map_err(csv::Error::from)?;
+    Ok(())
+}
+
+/// Trains are grouped in one service per day they start on
+fn service_id(date: NaiveDate) -> String {
+    date.format("%Y%m%d").to_string()
+}
+
+/// Formats a time in ms since the start of the service day, which can exceed 24 hours
+fn gtfs_time(time: u64) -> String {
+    let seconds = (time + 500) / 1000;
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Returns the times in ms at which the train arrives at and leaves a path position
+fn passage_times(report: &ReportTrain, position: u64) -> (u64, u64) {
+    let positions = &report.positions;
+    let times = &report.times;
+    if positions.is_empty() {
+        return (0, 0);
+    }
+    let index = positions.partition_point(|&p| p < position);
+    let arrival = if index == 0 {
+        times[0]
+    } else if index == positions.len() {
+        times[index - 1]
+    } else {
+        let (start_pos, end_pos) = (positions[index - 1], positions[index]);
+        let (start_time, end_time) = (times[index - 1], times[index]);
+        start_time + (position - start_pos) * (end_time - start_time) / (end_pos - start_pos)
+    };
+    // When the train stops, the report contains several times at the same position
+    let last_index = positions.partition_point(|&p| p <= position);
+    let departure = if last_index > index {
+        times[last_index - 1]
+    } else {
+        arrival
+    };
+    (arrival, departure)
+}
+
+/// The geometry of a path, along with the distance of each point from its start
+#[derive(Debug)]
+struct PathGeometry<'a> {
+    points: &'a [(f64, f64)],
+    /// Distances in meters, scaled to match the length of the path
+    distances: Vec<f64>,
+}
+
+impl<'a> PathGeometry<'a> {
+    fn new(points: &'a [(f64, f64)], length: f64) -> Self {
+        let mut distances = Vec::with_capacity(points.len());
+        let mut distance = 0.;
+        distances.extend(points.first().map(|_| 0.));
+        for (start, end) in points.iter().tuple_windows() {
+            distance += haversine_distance(*start, *end);
+            distances.push(distance);
+        }
+        if distance > 0. {
+            let scale = length / distance;
+            distances.iter_mut().for_each(|d| *d *= scale);
+        }
+        Self { points, distances }
+    }
+
+    /// Interpolates the point at a given distance in meters from the start of the path
+    fn point_at(&self, distance: f64) -> (f64, f64) {
+        let index = self.distances.partition_point(|&d| d < distance);
+        if index == 0 {
+            return self.points.first().copied().unwrap_or_default();
+        }
+        if index == self.points.len() {
+            return self.points[index - 1];
+        }
+        let (start, end) = (self.points[index - 1], self.points[index]);
+        let (start_distance, end_distance) = (self.distances[index - 1], self.distances[index]);
+        let ratio = (distance - start_distance) / (end_distance - start_distance);
+        (
+            start.0 + (end.0 - start.0) * ratio,
+            start.1 + (end.1 - start.1) * ratio,
+        )
+    }
+}
+
+/// Distance in meters between two points given as longitude and latitude
+fn haversine_distance((lon1, lat1): (f64, f64), (lon2, lat2): (f64, f64)) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let delta_lat = lat2 - lat1;
+    let delta_lon = (lon2 - lon1).to_radians();
+    let a =
+        (delta_lat / 2.).sin().powi(2) + lat1.cos() * lat2.cos() * (delta_lon / 2.).sin().powi(2);
+    2. * EARTH_RADIUS * a.sqrt().asin()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use serde_json::json;
+    use zip::ZipArchive;
+
+    use super::*;
+
+    fn report(positions: Vec<u64>, times: Vec<u64>) -> ReportTrain {
+        ReportTrain {
+            speeds: vec![0.; positions.len()],
+            positions,
+            times,
+            energy_consumption: 0.,
+        }
+    }
+
+    #[test]
+    fn gtfs_times_can_exceed_a_day() {
+        assert_eq!(gtfs_time(8 * 3_600_000 + 51_499), "08:00:51");
+        assert_eq!(gtfs_time(25 * 3_600_000 + 61_500), "25:01:02");
+    }
+
+    #[test]
+    fn passage_times_at_stops() {
+        // The train stops 2 minutes at 1km
+        let report = report(
+            vec![0, 1_000_000, 1_000_000, 3_000_000],
+            vec![0, 100_000, 220_000, 420_000],
+        );
+        assert_eq!(passage_times(&report, 0), (0, 0));
+        assert_eq!(passage_times(&report, 500_000), (50_000, 50_000));
+        assert_eq!(passage_times(&report, 1_000_000), (100_000, 220_000));
+        assert_eq!(passage_times(&report, 2_000_000), (320_000, 320_000));
+        assert_eq!(passage_times(&report, 3_000_000), (420_000, 420_000));
+    }
+
+    #[test]
+    fn interpolate_path_geometry() {
+        let points = [(0., 0.), (0., 1.), (0., 2.)];
+        // The path is longer than its geometry
+        let geometry = PathGeometry::new(&points, 300_000.);
+        assert_eq!(geometry.distances.len(), 3);
+        assert!((geometry.distances[1] - 150_000.).abs() < 1e-6);
+        let (lon, lat) = geometry.point_at(75_000.);
+        assert_eq!(lon, 0.);
+        assert!((lat - 0.5).abs() < 1e-9);
+        assert_eq!(geometry.point_at(400_000.), (0., 2.));
+    }
+
+    fn read_file(archive: &mut ZipArchive<Cursor<Vec<u8>>>, name: &str) -> String {
+        let mut content = String::new();
+        archive
+            .by_name(name)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        content
+    }
+
+    #[test]
+    fn export_feed() {
+        let train = TrainSchedule {
+            id: 7,
+            train_name: "ABC3615".into(),
+            labels: vec![Some("choo-choo".into()), None],
+            start_time: "2023-12-21T23:30:00Z".parse().unwrap(),
+            path: serde_json::from_value(json!([
+                { "id": "a", "operational_point": "West_station" },
+                { "id": "b", "track": "TA0", "offset": 1000 },
+                { "id": "c", "operational_point": "East_station" },
+            ]))
+            .unwrap(),
+            schedule: serde_json::from_value(json!([
+                { "at": "c", "stop_for": "PT1M" },
+            ]))
+            .unwrap(),
+            ..Default::default()
+        };
+        let path = TrainPath {
+            length: 2_000_000,
+            path_items_positions: vec![0, 1_000_000, 2_000_000],
+            geometry: vec![(0., 0.), (0., 1.)],
+            operational_points: serde_json::from_value(json!([
+                {
+                    "id": "West_station",
+                    "part": { "track": "TA0", "position": 0.0 },
+                    "extensions": { "identifier": { "name": "West", "uic": 1 } },
+                    "position": 0,
+                },
+                {
+                    "id": "East_station",
+                    "part": { "track": "TA1", "position": 1000.0 },
+                    "position": 2_000_000,
+                },
+            ]))
+            .unwrap(),
+        };
+        let mut feed = GtfsFeed::default();
+        feed.add_trip(
+            &train,
+            &path,
+            &report(vec![0, 2_000_000], vec![0, 3_600_000]),
+        );
+        assert_eq!(feed.trip_count(), 1);
+
+        let mut zip = Cursor::new(Vec::new());
+        feed.write(&mut zip).unwrap();
+        zip.set_position(0);
+        let mut archive = ZipArchive::new(zip).unwrap();
+        let mut files: Vec<_> = archive.file_names().collect();
+        files.sort();
+        assert_eq!(
+            files,
+            [
+                "agency.txt",
+                "calendar_dates.txt",
+                "routes.txt",
+                "shapes.txt",
+                "stop_times.txt",
+                "stops.txt",
+                "trips.txt"
+            ]
+        );
+
+        assert_eq!(
+            read_file(&mut archive, "stops.txt"),
+            "stop_id,stop_name,stop_lat,stop_lon\n\
+            7:b,b,0.5,0.0\n\
+            East_station,East_station,1.0,0.0\n\
+            West_station,West,0.0,0.0\n"
+        );
+        assert_eq!(
+            read_file(&mut archive, "trips.txt"),
+            "route_id,service_id,trip_id,trip_headsign,trip_short_name,shape_id\n\
+            choo-choo,20231221,7,East_station,ABC3615,7\n"
+        );
+        // The train runs past midnight and passes through b without stopping
+        assert_eq!(
+            read_file(&mut archive, "stop_times.txt"),
+            "trip_id,arrival_time,departure_time,stop_id,stop_sequence,pickup_type,drop_off_type,shape_dist_traveled\n\
+            7,23:30:00,23:30:00,West_station,0,0,0,0.0\n\
+            7,24:00:00,24:00:00,7:b,1,1,1,1000.0\n\
+            7,24:30:00,24:31:00,East_station,2,0,0,2000.0\n"
+        );
+        assert_eq!(
+            read_file(&mut archive, "calendar_dates.txt"),
+            "service_id,date,exception_type\n20231221,20231221,1\n"
+        );
+    }
+}
@@ -0,0 +1,580 @@
+//! Import of GTFS feeds and NeTEx documents into v2 train schedules
+//!
+//! Each trip of the feed running on a given date becomes a train schedule.
+//! Stops are matched with the operational points of an infra through their UIC code or trigram,
+//! the stops which can't be matched are left out of the path and listed in the import report.
+
+mod gtfs;
+mod netex;
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::io::Read;
+
+use actix_multipart::form::json::Json as MultipartJson;
+use actix_multipart::form::tempfile::TempFile;
+use actix_multipart::form::MultipartForm;
+use actix_web::post;
+use actix_web::web::Data;
+use actix_web::web::Json;
+use actix_web::web::Path;
+use actix_web::web::Query;
+use chrono::DateTime;
+use chrono::NaiveDate;
+use chrono::Utc;
+use editoast_derive::EditoastError;
+use editoast_schemas::primitives::NonBlankString;
+use editoast_schemas::train_schedule::PathItem;
+use editoast_schemas::train_schedule::PathItemLocation;
+use editoast_schemas::train_schedule::ScheduleItem;
+use editoast_schemas::train_schedule::TrainScheduleBase;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+use utoipa::IntoParams;
+use utoipa::ToSchema;
+
+use super::TimetableError;
+use super::TimetableIdParam;
+use crate::error::Result;
+use crate::modelsv2::prelude::*;
+use crate::modelsv2::resource_grant::ResourceType;
+use crate::modelsv2::resource_grant::Role;
+use crate::modelsv2::timetable::Timetable;
+use crate::modelsv2::train_schedule::TrainSchedule;
+use crate::modelsv2::train_schedule::TrainScheduleChangeset;
+use crate::modelsv2::DbConnection;
+use crate::modelsv2::DbConnectionPool;
+use crate::modelsv2::Infra;
+use crate::modelsv2::OperationalPointModel;
+use crate::views::authz::Authentication;
+use crate::views::v2::train_schedule::TrainScheduleForm;
+
+crate::routes! {
+    "/import" => {
+        import_feed,
+    },
+}
+
+editoast_common::schemas! {
+    RollingStockMapping,
+    TripImportReport,
+    UnmatchedStop,
+    TripImportError,
+    FeedImportResult,
+}
+
+#[derive(Debug, Error, EditoastError)]
+#[editoast_error(base_id = "timetable_feed_import")]
+pub enum FeedImportError {
+    #[error("Invalid GTFS feed: {message}")]
+    #[editoast_error(status = 400)]
+    InvalidGtfs { message: String },
+    #[error("Invalid NeTEx document: {message}")]
+    #[editoast_error(status = 400)]
+    InvalidNetex { message: String },
+    #[error("Unknown time zone '{timezone}'")]
+    #[editoast_error(status = 400)]
+    UnknownTimezone { timezone: String },
+}
+
+/// Rolling stock names to use for the imported trips
+///
+/// The vehicle type of a trip takes precedence over its route type.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RollingStockMapping {
+    /// Rolling stock names by GTFS route type
+    #[serde(default)]
+    pub route_types: HashMap<String, String>,
+    /// Rolling stock names by NeTEx vehicle type id
+    #[serde(default)]
+    pub vehicle_types: HashMap<String, String>,
+    /// The rolling stock name of the trips matching no other rule
+    #[serde(default)]
+    pub default: Option<String>,
+}
+
+impl RollingStockMapping {
+    fn rolling_stock_name(&self, trip: &ImportedTrip) -> Option<&String> {
+        trip.vehicle_type
+            .as_ref()
+            .and_then(|vehicle_type| self.vehicle_types.get(vehicle_type))
+            .or_else(|| {
+                trip.route_type
+                    .as_ref()
+                    .and_then(|route_type| self.route_types.get(route_type))
+            })
+            .or(self.default.as_ref())
+    }
+}
+
+/// The outcome of the import of a trip
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TripImportReport {
+    /// The id of the trip in the feed
+    pub trip_id: String,
+    /// The stops which couldn't be matched with an operational point of the infra
+    pub unmatched_stops: Vec<UnmatchedStop>,
+    /// Why the trip wasn't imported, if it wasn't
+    pub error: Option<TripImportError>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct UnmatchedStop {
+    pub stop_id: String,
+    pub stop_name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TripImportError {
+    /// Less than two stops of the trip were matched
+    NotEnoughMatchedStops,
+    /// No rolling stock is mapped to the route or vehicle type of the trip
+    RollingStockNotMapped {
+        route_type: Option<String>,
+        vehicle_type: Option<String>,
+    },
+}
+
+/// A trip read from a feed
+#[derive(Debug, Clone, PartialEq)]
+struct ImportedTrip {
+    id: String,
+    name: String,
+    labels: Vec<String>,
+    route_type: Option<String>,
+    vehicle_type: Option<String>,
+    stops: Vec<ImportedStop>,
+}
+
+/// A stop of a trip read from a feed
+#[derive(Debug, Clone, PartialEq)]
+struct ImportedStop {
+    id: String,
+    name: String,
+    uic: Option<u32>,
+    trigram: Option<String>,
+    arrival: Option<DateTime<Utc>>,
+    departure: Option<DateTime<Utc>>,
+    /// Whether passengers can board or alight
+    is_stop: bool,
+}
+
+/// The UIC code and trigram of a stop, read from a code field or from its id
+fn stop_codes(code: Option<&str>, id: &str) -> (Option<u32>, Option<String>) {
+    let code = code.map(str::trim).filter(|code| !code.is_empty());
+    let trigram = code
+        .filter(|code| code.len() <= 3 && code.chars().all(|c| c.is_ascii_uppercase()))
+        .map(str::to_owned);
+    // Ids such as `StopPoint:OCETrain TER-87686006` end with the UIC code
+    let digits = id.len() - id.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    let uic = code
+        .and_then(|code| code.parse().ok())
+        .or_else(|| (digits >= 7).then(|| id[id.len() - digits..].parse().ok())?);
+    (uic, trigram)
+}
+
+/// The UIC codes and trigrams of the operational points of an infra
+#[derive(Debug, Default)]
+struct KnownOperationalPoints {
+    uics: HashSet<u32>,
+    trigrams: HashSet<String>,
+}
+
+impl KnownOperationalPoints {
+    /// Loads the operational points of an infra referenced by the stops of the trips
+    async fn load(conn: &mut DbConnection, infra_id: i64, trips: &[ImportedTrip]) -> Result<Self> {
+        let stops = || trips.iter().flat_map(|trip| &trip.stops);
+        let uics: Vec<i64> = stops()
+            .filter_map(|stop| stop.uic)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .map(i64::from)
+            .collect();
+        let trigrams: Vec<String> = stops()
+            .filter_map(|stop| stop.trigram.clone())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        let mut operational_points =
+            OperationalPointModel::retrieve_from_uic(conn, infra_id, &uics).await?;
+        operational_points.extend(
+            OperationalPointModel::retrieve_from_trigrams(conn, infra_id, &trigrams).await?,
+        );
+        let mut known = Self::default();
+        for operational_point in operational_points {
+            let extensions = operational_point.schema.extensions;
+            if let Some(identifier) = extensions.identifier {
+                known.uics.extend(u32::try_from(identifier.uic).ok());
+            }
+            if let Some(sncf) = extensions.sncf {
+                known.trigrams.insert(sncf.trigram);
+            }
+        }
+        Ok(known)
+    }
+
+    fn location(&self, stop: &ImportedStop) -> Option<PathItemLocation> {
+        if let Some(uic) = stop.uic.filter(|uic| self.uics.contains(uic)) {
+            return Some(PathItemLocation::OperationalPointUic {
+                uic,
+                secondary_code: None,
+            });
+        }
+        let trigram = stop
+            .trigram
+            .as_ref()
+            .filter(|trigram| self.trigrams.contains(*trigram))?;
+        Some(PathItemLocation::OperationalPointDescription {
+            trigram: NonBlankString(trigram.clone()),
+            secondary_code: None,
+        })
+    }
+}
+
+/// Converts an imported trip to a train schedule, along with its import report
+fn convert_trip(
+    trip: &ImportedTrip,
+    known_operational_points: &KnownOperationalPoints,
+    mapping: &RollingStockMapping,
+) -> (Option<TrainScheduleBase>, TripImportReport) {
+    let mut report = TripImportReport {
+        trip_id: trip.id.clone(),
+        unmatched_stops: vec![],
+        error: None,
+    };
+    let mut stops = vec![];
+    for stop in &trip.stops {
+        match known_operational_points.location(stop) {
+            Some(location) => stops.push((stop, location)),
+            None => report.unmatched_stops.push(UnmatchedStop {
+                stop_id: stop.id.clone(),
+                stop_name: stop.name.clone(),
+            }),
+        }
+    }
+    let Some(start_time) = stops
+        .first()
+        .and_then(|(stop, _)| stop.departure.or(stop.arrival))
+        .filter(|_| stops.len() >= 2)
+    else {
+        report.error = Some(TripImportError::NotEnoughMatchedStops);
+        return (None, report);
+    };
+    let Some(rolling_stock_name) = mapping.rolling_stock_name(trip) else {
+        report.error = Some(TripImportError::RollingStockNotMapped {
+            route_type: trip.route_type.clone(),
+            vehicle_type: trip.vehicle_type.clone(),
+        });
+        return (None, report);
+    };
+
+    let mut path = vec![];
+    let mut schedule = vec![];
+    let mut ids = HashSet::new();
+    let last_index = stops.len() - 1;
+    for (index, (stop, location)) in stops.into_iter().enumerate() {
+        let id = if ids.insert(stop.id.clone()) {
+            stop.id.clone()
+        } else {
+            format!("{}-{index}", stop.id)
+        };
+        // The first stop gives the start time of the train
+        let arrival = stop
+            .arrival
+            .filter(|_| index > 0)
+            .and_then(|arrival| (arrival - start_time).try_into().ok());
+        let stop_for = match (stop.arrival, stop.departure) {
+            _ if !stop.is_stop => None,
+            (Some(arrival), Some(departure)) if index < last_index => {
+                (departure - arrival).try_into().ok()
+            }
+            _ => chrono::Duration::zero().try_into().ok(),
+        };
+        if arrival.is_some() || stop_for.is_some() {
+            schedule.push(ScheduleItem {
+                at: NonBlankString(id.clone()),
+                arrival,
+                stop_for,
+                ..Default::default()
+            });
+        }
+        path.push(PathItem {
+            id: NonBlankString(id),
+            deleted: false,
+            location,
+        });
+    }
+
+    let train_schedule = TrainScheduleBase {
+        train_name: trip.name.clone(),
+        labels: trip.labels.clone(),
+        rolling_stock_name: rolling_stock_name.clone(),
+        start_time,
+        path,
+        schedule,
+        ..Default::default()
+    };
+    (Some(train_schedule), report)
+}
+
+/// Reads the trips of a GTFS feed or a NeTEx document running on a date,
+/// and converts them to train schedules running on an infra
+pub async fn import_feed_trains(
+    conn: &mut DbConnection,
+    infra_id: i64,
+    date: NaiveDate,
+    mapping: &RollingStockMapping,
+    feed: &[u8],
+) -> Result<(Vec<TrainScheduleBase>, Vec<TripImportReport>)> {
+    // GTFS feeds are zip archives
+    let trips = if feed.starts_with(b"PK") {
+        gtfs::parse_gtfs(feed, date)?
+    } else {
+        netex::parse_netex(feed, date)?
+    };
+    let known_operational_points = KnownOperationalPoints::load(conn, infra_id, &trips).await?;
+    let mut train_schedules = vec![];
+    let mut reports = vec![];
+    for trip in &trips {
+        let (train_schedule, report) = convert_trip(trip, &known_operational_points, mapping);
+        train_schedules.extend(train_schedule);
+        reports.push(report);
+    }
+    Ok((train_schedules, reports))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+struct FeedImportParams {
+    /// The infra on which the stops are matched
+    infra_id: i64,
+    /// The date of the trips to import
+    date: NaiveDate,
+}
+
+#[derive(Debug, MultipartForm, ToSchema)]
+struct FeedImportForm {
+    /// A GTFS zip or a NeTEx XML document
+    #[schema(value_type = String, format = Binary)]
+    feed: TempFile,
+    #[schema(value_type = Option<RollingStockMapping>)]
+    rolling_stock_mapping: Option<MultipartJson<RollingStockMapping>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, ToSchema)]
+pub struct FeedImportResult {
+    /// The ids of the created train schedules
+    pub train_ids: Vec<i64>,
+    pub reports: Vec<TripImportReport>,
+}
+
+/// Import the trips of a GTFS feed or a NeTEx document running on a given date
+#[utoipa::path(
+    tag = "timetablev2,train_schedulev2",
+    params(TimetableIdParam, FeedImportParams),
+    request_body(content = FeedImportForm, content_type = "multipart/form-data"),
+    responses(
+        (status = 200, description = "The created train schedules and the import report of each trip", body = FeedImportResult),
+        (status = 404, description = "Timetable or infra not found"),
+    )
+)]
+#[post("")]
+async fn import_feed(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    timetable_id: Path<TimetableIdParam>,
+    query: Query<FeedImportParams>,
+    MultipartForm(form): MultipartForm<FeedImportForm>,
+) -> Result<Json<FeedImportResult>> {
+    let conn = &mut db_pool.get().await?;
+    let timetable_id = timetable_id.into_inner().id;
+    let FeedImportParams { infra_id, date } = query.into_inner();
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check_timetable(conn, timetable_id, Role::Editor)
+        .await?;
+    authorizer
+        .check(conn, ResourceType::Infra, infra_id, Role::Viewer)
+        .await?;
+
+    Timetable::retrieve_or_fail(conn, timetable_id, || TimetableError::NotFound {
+        timetable_id,
+    })
+    .await?;
+    Infra::retrieve_or_fail(conn, infra_id, || TimetableError::InfraNotFound {
+        infra_id,
+    })
+    .await?;
+
+    let mut feed = vec![];
+    form.feed.file.into_file().read_to_end(&mut feed)?;
+    let mapping = form
+        .rolling_stock_mapping
+        .map(|mapping| mapping.into_inner())
+        .unwrap_or_default();
+
+    let (train_schedules, reports) =
+        import_feed_trains(conn, infra_id, date, &mapping, &feed).await?;
+    let changesets: Vec<TrainScheduleChangeset> = train_schedules
+        .into_iter()
+        .map(|train_schedule| {
+            TrainScheduleForm {
+                timetable_id: Some(timetable_id),
+                train_schedule,
+            }
+            .into()
+        })
+        .collect();
+    let train_schedules: Vec<_> = TrainSchedule::create_batch(conn, changesets).await?;
+
+    Ok(Json(FeedImportResult {
+        train_ids: train_schedules.iter().map(|train| train.id).collect(),
+        reports,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stop(id: &str, uic: Option<u32>, times: Option<(&str, &str)>) -> ImportedStop {
+        ImportedStop {
+            id: id.into(),
+            name: id.to_uppercase(),
+            uic,
+            trigram: None,
+            arrival: times.map(|(arrival, _)| arrival.parse().unwrap()),
+            departure: times.map(|(_, departure)| departure.parse().unwrap()),
+            is_stop: true,
+        }
+    }
+
+    fn trip(stops: Vec<ImportedStop>) -> ImportedTrip {
+        ImportedTrip {
+            id: "trip".into(),
+            name: "1234".into(),
+            labels: vec!["TER".into()],
+            route_type: Some("2".into()),
+            vehicle_type: None,
+            stops,
+        }
+    }
+
+    fn known() -> KnownOperationalPoints {
+        KnownOperationalPoints {
+            uics: HashSet::from([1, 3]),
+            trigrams: HashSet::from(["WS".to_owned()]),
+        }
+    }
+
+    fn mapping() -> RollingStockMapping {
+        RollingStockMapping {
+            route_types: HashMap::from([("2".into(), "R2D2".into())]),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn read_stop_codes() {
+        assert_eq!(stop_codes(Some("87686006"), "a"), (Some(87686006), None));
+        assert_eq!(stop_codes(Some("PNO"), "a"), (None, Some("PNO".into())));
+        assert_eq!(
+            stop_codes(None, "StopPoint:OCETrain TER-87686006"),
+            (Some(87686006), None)
+        );
+        assert_eq!(stop_codes(Some(""), "stop_12"), (None, None));
+    }
+
+    #[test]
+    fn convert_trip_to_train_schedule() {
+        let trip = trip(vec![
+            stop(
+                "a",
+                Some(1),
+                Some(("2024-01-01T08:00:00Z", "2024-01-01T08:00:00Z")),
+            ),
+            stop(
+                "b",
+                Some(2),
+                Some(("2024-01-01T08:10:00Z", "2024-01-01T08:12:00Z")),
+            ),
+            stop(
+                "c",
+                Some(3),
+                Some(("2024-01-01T08:30:00Z", "2024-01-01T08:32:00Z")),
+            ),
+            stop(
+                "a",
+                Some(1),
+                Some(("2024-01-01T09:00:00Z", "2024-01-01T09:00:00Z")),
+            ),
+        ]);
+        let (train_schedule, report) = convert_trip(&trip, &known(), &mapping());
+        assert_eq!(
+            report.unmatched_stops,
+            vec![UnmatchedStop {
+                stop_id: "b".into(),
+                stop_name: "B".into()
+            }]
+        );
+        assert_eq!(report.error, None);
+
+        let train_schedule = train_schedule.unwrap();
+        assert_eq!(train_schedule.rolling_stock_name, "R2D2");
+        assert_eq!(
+            train_schedule.start_time,
+            "2024-01-01T08:00:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        let ids: Vec<_> = train_schedule
+            .path
+            .iter()
+            .map(|p| p.id.0.as_str())
+            .collect();
+        assert_eq!(ids, ["a", "c", "a-2"]);
+        // The path is valid once serialized
+        let json = serde_json::to_value(&train_schedule).unwrap();
+        serde_json::from_value::<TrainScheduleBase>(json.clone()).unwrap();
+        assert_eq!(
+            json["schedule"],
+            serde_json::json!([
+                { "at": "a", "arrival": null, "stop_for": "P0D", "on_stop_signal": false, "locked": false },
+                { "at": "c", "arrival": "PT1800S", "stop_for": "PT120S", "on_stop_signal": false, "locked": false },
+                { "at": "a-2", "arrival": "PT3600S", "stop_for": "P0D", "on_stop_signal": false, "locked": false },
+            ])
+        );
+    }
+
+    #[test]
+    fn report_trips_not_imported() {
+        let stops = vec![
+            stop(
+                "a",
+                Some(1),
+                Some(("2024-01-01T08:00:00Z", "2024-01-01T08:00:00Z")),
+            ),
+            stop(
+                "b",
+                Some(2),
+                Some(("2024-01-01T08:10:00Z", "2024-01-01T08:10:00Z")),
+            ),
+        ];
+        let (train_schedule, report) = convert_trip(&trip(stops.clone()), &known(), &mapping());
+        assert!(train_schedule.is_none());
+        assert_eq!(report.error, Some(TripImportError::NotEnoughMatchedStops));
+
+        let mut stops = stops;
+        stops[1].uic = Some(3);
+        let (train_schedule, report) =
+            convert_trip(&trip(stops), &known(), &RollingStockMapping::default());
+        assert!(train_schedule.is_none());
+        assert_eq!(
+            report.error,
+            Some(TripImportError::RollingStockNotMapped {
+                route_type: Some("2".into()),
+                vehicle_type: None,
+            })
+        );
+    }
+}
@@ -0,0 +1,413 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::io::Cursor;
+
+use chrono::DateTime;
+use chrono::Datelike;
+use chrono::Duration;
+use chrono::NaiveDate;
+use chrono::TimeZone;
+use chrono::Utc;
+use chrono::Weekday;
+use chrono_tz::Tz;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use zip::ZipArchive;
+
+use super::stop_codes;
+use super::FeedImportError;
+use super::ImportedStop;
+use super::ImportedTrip;
+
+#[derive(Debug, Deserialize)]
+struct Agency {
+    #[serde(default)]
+    agency_id: Option<String>,
+    agency_timezone: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Route {
+    route_id: String,
+    #[serde(default)]
+    agency_id: Option<String>,
+    #[serde(default)]
+    route_short_name: Option<String>,
+    #[serde(default)]
+    route_long_name: Option<String>,
+    route_type: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Trip {
+    route_id: String,
+    service_id: String,
+    trip_id: String,
+    #[serde(default)]
+    trip_short_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Stop {
+    stop_id: String,
+    #[serde(default)]
+    stop_code: Option<String>,
+    #[serde(default)]
+    stop_name: Option<String>,
+    #[serde(default)]
+    parent_station: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StopTime {
+    trip_id: String,
+    #[serde(default)]
+    arrival_time: Option<String>,
+    #[serde(default)]
+    departure_time: Option<String>,
+    stop_id: String,
+    stop_sequence: u32,
+    #[serde(default)]
+    pickup_type: Option<u8>,
+    #[serde(default)]
+    drop_off_type: Option<u8>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Calendar {
+    service_id: String,
+    monday: u8,
+    tuesday: u8,
+    wednesday: u8,
+    thursday: u8,
+    friday: u8,
+    saturday: u8,
+    sunday: u8,
+    start_date: String,
+    end_date: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CalendarDate {
+    service_id: String,
+    date: String,
+    exception_type: u8,
+}
+
+fn invalid(message: impl ToString) -> FeedImportError {
+    FeedImportError::InvalidGtfs {
+        message: message.to_string(),
+    }
+}
+
+fn read_file<T: DeserializeOwned>(
+    archive: &mut ZipArchive<Cursor<&[u8]>>,
+    name: &str,
+    required: bool,
+) -> Result<Vec<T>, FeedImportError> {
+    let file = match archive.by_name(name) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) if !required => return Ok(vec![]),
+        Err(err) => return Err(invalid(format!("{name}: {err}"))),
+    };
+    csv::Reader::from_reader(file)
+        .deserialize()
+        .collect::<Result<_, _>>()
+        .map_err(|err| invalid(format!("{name}: {err}")))
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate, FeedImportError> {
+    NaiveDate::parse_from_str(date, "%Y%m%d").map_err(|_| invalid(format!("invalid date '{date}'")))
+}
+
+/// Parses a GTFS time, which can exceed 24 hours, as a duration since the start of the day
+fn parse_time(time: &str) -> Result<Duration, FeedImportError> {
+    let parts: Vec<_> = time.trim().split(':').map(str::parse::<i64>).collect();
+    match parts.as_slice() {
+        [Ok(hours), Ok(minutes), Ok(seconds)] => {
+            Ok(Duration::seconds(hours * 3600 + minutes * 60 + seconds))
+        }
+        _ => Err(invalid(format!("invalid time '{time}'"))),
+    }
+}
+
+/// The services running on a date
+fn active_services(
+    calendars: &[Calendar],
+    calendar_dates: &[CalendarDate],
+    date: NaiveDate,
+) -> Result<HashSet<String>, FeedImportError> {
+    let mut services = HashSet::new();
+    for calendar in calendars {
+        let runs_on_weekday = match date.weekday() {
+            Weekday::Mon => calendar.monday,
+            Weekday::Tue => calendar.tuesday,
+            Weekday::Wed => calendar.wednesday,
+            Weekday::Thu => calendar.thursday,
+            Weekday::Fri => calendar.friday,
+            Weekday::Sat => calendar.saturday,
+            Weekday::Sun => calendar.sunday,
+        } == 1;
+        if runs_on_weekday
+            && parse_date(&calendar.start_date)? <= date
+            && date <= parse_date(&calendar.end_date)?
+        {
+            services.insert(calendar.service_id.clone());
+        }
+    }
+    for calendar_date in calendar_dates {
+        if parse_date(&calendar_date.date)? != date {
+            continue;
+        }
+        match calendar_date.exception_type {
+            1 => services.insert(calendar_date.service_id.clone()),
+            _ => services.remove(&calendar_date.service_id),
+        };
+    }
+    Ok(services)
+}
+
+/// Reads the trips of a GTFS feed running on a date
+pub(super) fn parse_gtfs(
+    feed: &[u8],
+    date: NaiveDate,
+) -> Result<Vec<ImportedTrip>, FeedImportError> {
+    let mut archive = ZipArchive::new(Cursor::new(feed)).map_err(invalid)?;
+    let agencies: Vec<Agency> = read_file(&mut archive, "agency.txt", true)?;
+    let routes: Vec<Route> = read_file(&mut archive, "routes.txt", true)?;
+    let trips: Vec<Trip> = read_file(&mut archive, "trips.txt", true)?;
+    let stops: Vec<Stop> = read_file(&mut archive, "stops.txt", true)?;
+    let stop_times: Vec<StopTime> = read_file(&mut archive, "stop_times.txt", true)?;
+    let calendars: Vec<Calendar> = read_file(&mut archive, "calendar.txt", false)?;
+    let calendar_dates: Vec<CalendarDate> = read_file(&mut archive, "calendar_dates.txt", false)?;
+
+    let mut timezones = HashMap::new();
+    for agency in &agencies {
+        let timezone: Tz =
+            agency
+                .agency_timezone
+                .parse()
+                .map_err(|_| FeedImportError::UnknownTimezone {
+                    timezone: agency.agency_timezone.clone(),
+                })?;
+        timezones.insert(agency.agency_id.clone(), timezone);
+    }
+    let default_timezone = timezones.values().next().copied().unwrap_or(Tz::UTC);
+    let routes: HashMap<_, _> = routes
+        .iter()
+        .map(|route| (&route.route_id, route))
+        .collect();
+    let stops: HashMap<_, _> = stops.iter().map(|stop| (&stop.stop_id, stop)).collect();
+    let mut trip_stop_times: HashMap<_, Vec<_>> = HashMap::new();
+    for stop_time in &stop_times {
+        trip_stop_times
+            .entry(&stop_time.trip_id)
+            .or_default()
+            .push(stop_time);
+    }
+    let services = active_services(&calendars, &calendar_dates, date)?;
+
+    let mut imported_trips = vec![];
+    for trip in trips
+        .iter()
+        .filter(|trip| services.contains(&trip.service_id))
+    {
+        let route = routes
+            .get(&trip.route_id)
+            .ok_or_else(|| invalid(format!("unknown route '{}'", trip.route_id)))?;
+        let timezone = timezones
+            .get(&route.agency_id)
+            .copied()
+            .unwrap_or(default_timezone);
+        // GTFS times are relative to noon minus 12h, in the timezone of the agency
+        let day_start = timezone
+            .from_local_datetime(&date.and_hms_opt(12, 0, 0).unwrap())
+            .earliest()
+            .ok_or_else(|| invalid(format!("invalid local date '{date}'")))?
+            .with_timezone(&Utc)
+            - Duration::hours(12);
+        let to_datetime = |time: &Option<String>| -> Result<_, FeedImportError> {
+            match time.as_deref().filter(|time| !time.is_empty()) {
+                Some(time) => Ok(Some(day_start + parse_time(time)?)),
+                None => Ok(None::<DateTime<Utc>>),
+            }
+        };
+
+        let mut trip_stops = trip_stop_times.remove(&trip.trip_id).unwrap_or_default();
+        trip_stops.sort_by_key(|stop_time| stop_time.stop_sequence);
+        let mut imported_stops = vec![];
+        for stop_time in trip_stops {
+            let stop = stops
+                .get(&stop_time.stop_id)
+                .ok_or_else(|| invalid(format!("unknown stop '{}'", stop_time.stop_id)))?;
+            let (mut uic, mut trigram) = stop_codes(stop.stop_code.as_deref(), &stop.stop_id);
+            // Platforms can be identified by their station
+            if let Some(parent) = stop.parent_station.as_ref().and_then(|id| stops.get(id)) {
+                let (parent_uic, parent_trigram) =
+                    stop_codes(parent.stop_code.as_deref(), &parent.stop_id);
+                uic = uic.or(parent_uic);
+                trigram = trigram.or(parent_trigram);
+            }
+            // Trains don't stop where passengers can neither board nor alight
+            let is_stop = stop_time.pickup_type.unwrap_or_default() != 1
+                || stop_time.drop_off_type.unwrap_or_default() != 1;
+            imported_stops.push(ImportedStop {
+                id: stop.stop_id.clone(),
+                name: stop
+                    .stop_name
+                    .clone()
+                    .unwrap_or_else(|| stop.stop_id.clone()),
+                uic,
+                trigram,
+                arrival: to_datetime(&stop_time.arrival_time)?,
+                departure: to_datetime(&stop_time.departure_time)?,
+                is_stop,
+            });
+        }
+
+        let label = route
+            .route_short_name
+            .clone()
+            .or_else(|| route.route_long_name.clone())
+            .filter(|label| !label.is_empty());
+        imported_trips.push(ImportedTrip {
+            id: trip.trip_id.clone(),
+            name: trip
+                .trip_short_name
+                .clone()
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| trip.trip_id.clone()),
+            labels: label.into_iter().collect(),
+            route_type: Some(route.route_type.clone()),
+            vehicle_type: None,
+            stops: imported_stops,
+        });
+    }
+    Ok(imported_trips)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    use super::*;
+
+    fn feed(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in files {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn simple_feed() -> Vec<u8> {
+        feed(&[
+            (
+                "agency.txt",
+                "agency_id,agency_name,agency_url,agency_timezone\n\
+                sncf,SNCF,https://sncf.com,Europe/Paris\n",
+            ),
+            (
+                "routes.txt",
+                "route_id,agency_id,route_short_name,route_long_name,route_type\n\
+                r1,sncf,TER,,2\n",
+            ),
+            (
+                "trips.txt",
+                "route_id,service_id,trip_id,trip_short_name\n\
+                r1,weekdays,t1,861234\n\
+                r1,sundays,t2,861236\n",
+            ),
+            (
+                "stops.txt",
+                "stop_id,stop_code,stop_name,parent_station\n\
+                StopArea:OCE87686006,,Paris Gare de Lyon,\n\
+                StopPoint:OCETrain TER-87686006,,Paris Gare de Lyon,StopArea:OCE87686006\n\
+                MWS_1,MWS,Mid West station,\n",
+            ),
+            (
+                "stop_times.txt",
+                "trip_id,arrival_time,departure_time,stop_id,stop_sequence,pickup_type,drop_off_type\n\
+                t1,24:10:00,24:15:00,MWS_1,2,1,1\n\
+                t1,23:50:00,23:50:00,StopPoint:OCETrain TER-87686006,1,0,0\n\
+                t2,10:00:00,10:00:00,MWS_1,1,0,0\n",
+            ),
+            (
+                "calendar.txt",
+                "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,end_date\n\
+                weekdays,1,1,1,1,1,0,0,20240101,20241231\n\
+                sundays,0,0,0,0,0,0,1,20240101,20241231\n",
+            ),
+            (
+                "calendar_dates.txt",
+                "service_id,date,exception_type\n\
+                weekdays,20240501,2\n",
+            ),
+        ])
+    }
+
+    #[test]
+    fn parse_trips_running_on_date() {
+        // Tuesday
+        let date = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+        let trips = parse_gtfs(&simple_feed(), date).unwrap();
+        assert_eq!(
+            trips,
+            vec![ImportedTrip {
+                id: "t1".into(),
+                name: "861234".into(),
+                labels: vec!["TER".into()],
+                route_type: Some("2".into()),
+                vehicle_type: None,
+                stops: vec![
+                    ImportedStop {
+                        id: "StopPoint:OCETrain TER-87686006".into(),
+                        name: "Paris Gare de Lyon".into(),
+                        uic: Some(87686006),
+                        trigram: None,
+                        // Paris is UTC+1 in winter
+                        arrival: Some("2024-01-02T22:50:00Z".parse().unwrap()),
+                        departure: Some("2024-01-02T22:50:00Z".parse().unwrap()),
+                        is_stop: true,
+                    },
+                    ImportedStop {
+                        id: "MWS_1".into(),
+                        name: "Mid West station".into(),
+                        uic: None,
+                        trigram: Some("MWS".into()),
+                        arrival: Some("2024-01-02T23:10:00Z".parse().unwrap()),
+                        departure: Some("2024-01-02T23:15:00Z".parse().unwrap()),
+                        is_stop: false,
+                    },
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn calendar_exceptions() {
+        let feed = simple_feed();
+        // Removed service
+        let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        assert!(parse_gtfs(&feed, date).unwrap().is_empty());
+        // Sunday
+        let date = NaiveDate::from_ymd_opt(2024, 1, 7).unwrap();
+        let trips = parse_gtfs(&feed, date).unwrap();
+        assert_eq!(trips.len(), 1);
+        assert_eq!(trips[0].id, "t2");
+    }
+
+    #[test]
+    fn missing_files() {
+        let feed = feed(&[("agency.txt", "agency_name\nSNCF\n")]);
+        let date = NaiveDate::from_ymd_opt(2024, 1, 2).unwrap();
+        assert!(matches!(
+            parse_gtfs(&feed, date),
+            Err(FeedImportError::InvalidGtfs { .. })
+        ));
+    }
+}
@@ -0,0 +1,397 @@
+//! Reads the service journeys of a NeTEx document
+//!
+//! Only the objects needed to build the trips are read:
+//! - `ServiceJourney` with their `TimetabledPassingTime`s, `DayTypeRef`, `LineRef` and `VehicleTypeRef`
+//! - `ServiceJourneyPattern` linking the passing times to `ScheduledStopPoint`s
+//! - `PassengerStopAssignment` linking the scheduled stop points to `StopPlace`s
+//! - `DayTypeAssignment` giving the dates of the day types
+//! - `Line` and `Route` giving the labels of the trips
+//!
+//! Stops are identified by a `uic` or `trigram` key of their `keyList`, their `PrivateCode` or their id.
+
+use std::collections::HashMap;
+
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::NaiveDate;
+use chrono::NaiveTime;
+use chrono::TimeZone;
+use chrono::Utc;
+use chrono_tz::Tz;
+use roxmltree::Document;
+use roxmltree::Node;
+
+use super::stop_codes;
+use super::FeedImportError;
+use super::ImportedStop;
+use super::ImportedTrip;
+
+fn invalid(message: impl ToString) -> FeedImportError {
+    FeedImportError::InvalidNetex {
+        message: message.to_string(),
+    }
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
+
+fn child_text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    child(node, name)
+        .and_then(|child| child.text())
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+}
+
+/// The `ref` attribute of a child element
+fn child_ref<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    child(node, name).and_then(|child| child.attribute("ref"))
+}
+
+fn elements<'a, 'input: 'a>(
+    document: &'a Document<'input>,
+    name: &'a str,
+) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    document
+        .descendants()
+        .filter(move |node| node.has_tag_name(name))
+}
+
+/// Elements of a kind, by id
+fn elements_by_id<'a, 'input: 'a>(
+    document: &'a Document<'input>,
+    name: &'a str,
+) -> HashMap<&'a str, Node<'a, 'input>> {
+    elements(document, name)
+        .filter_map(|node| Some((node.attribute("id")?, node)))
+        .collect()
+}
+
+/// Reads the value of a key of a `keyList`, the key is case insensitive
+fn key_value<'a>(node: Node<'a, '_>, key: &str) -> Option<&'a str> {
+    child(node, "keyList")?
+        .children()
+        .filter(|key_value| key_value.has_tag_name("KeyValue"))
+        .find(|key_value| {
+            child_text(*key_value, "Key").is_some_and(|k| k.eq_ignore_ascii_case(key))
+        })
+        .and_then(|key_value| child_text(key_value, "Value"))
+}
+
+/// The UIC code and trigram of a stop point or stop place
+fn node_codes(node: Node) -> (Option<u32>, Option<String>) {
+    let (uic, trigram) = stop_codes(
+        child_text(node, "PrivateCode"),
+        node.attribute("id").unwrap_or_default(),
+    );
+    (
+        key_value(node, "uic")
+            .and_then(|uic| uic.parse().ok())
+            .or(uic),
+        key_value(node, "trigram").map(str::to_owned).or(trigram),
+    )
+}
+
+fn parse_time(time: &str) -> Result<NaiveTime, FeedImportError> {
+    NaiveTime::parse_from_str(time, "%H:%M:%S")
+        .map_err(|_| invalid(format!("invalid time '{time}'")))
+}
+
+/// Reads the service journeys of a NeTEx document running on a date
+pub(super) fn parse_netex(
+    document: &[u8],
+    date: NaiveDate,
+) -> Result<Vec<ImportedTrip>, FeedImportError> {
+    let document = std::str::from_utf8(document).map_err(invalid)?;
+    let document = Document::parse(document).map_err(invalid)?;
+
+    let timezone = match elements(&document, "DefaultLocale")
+        .find_map(|locale| child_text(locale, "TimeZone"))
+    {
+        Some(timezone) => timezone
+            .parse::<Tz>()
+            .map_err(|_| FeedImportError::UnknownTimezone {
+                timezone: timezone.to_owned(),
+            })?,
+        None => Tz::UTC,
+    };
+    let to_datetime =
+        |time: Option<&str>, day_offset: Option<&str>| -> Result<_, FeedImportError> {
+            let Some(time) = time else {
+                return Ok(None::<DateTime<Utc>>);
+            };
+            let day_offset: i64 = day_offset
+                .and_then(|offset| offset.parse().ok())
+                .unwrap_or(0);
+            let local = (date + Duration::days(day_offset)).and_time(parse_time(time)?);
+            let datetime = timezone
+                .from_local_datetime(&local)
+                .earliest()
+                .ok_or_else(|| invalid(format!("invalid local time '{local}'")))?;
+            Ok(Some(datetime.with_timezone(&Utc)))
+        };
+
+    let lines = elements_by_id(&document, "Line");
+    let routes = elements_by_id(&document, "Route");
+    let journey_patterns: HashMap<_, _> = elements_by_id(&document, "ServiceJourneyPattern")
+        .into_iter()
+        .chain(elements_by_id(&document, "JourneyPattern"))
+        .collect();
+    let stop_points_in_patterns = elements_by_id(&document, "StopPointInJourneyPattern");
+    let scheduled_stop_points = elements_by_id(&document, "ScheduledStopPoint");
+    let stop_places = elements_by_id(&document, "StopPlace");
+    let stop_place_assignments: HashMap<_, _> = elements(&document, "PassengerStopAssignment")
+        .filter_map(|assignment| {
+            Some((
+                child_ref(assignment, "ScheduledStopPointRef")?,
+                stop_places.get(child_ref(assignment, "StopPlaceRef")?)?,
+            ))
+        })
+        .collect();
+    let mut day_types_running = HashMap::new();
+    for assignment in elements(&document, "DayTypeAssignment") {
+        let (Some(day_type), Some(assignment_date)) = (
+            child_ref(assignment, "DayTypeRef"),
+            child_text(assignment, "Date"),
+        ) else {
+            continue;
+        };
+        let assignment_date = NaiveDate::parse_from_str(assignment_date, "%Y-%m-%d")
+            .map_err(|_| invalid(format!("invalid date '{assignment_date}'")))?;
+        if assignment_date == date {
+            let is_available = child_text(assignment, "isAvailable") != Some("false");
+            day_types_running.insert(day_type, is_available);
+        }
+    }
+
+    let mut trips = vec![];
+    for journey in elements(&document, "ServiceJourney") {
+        let id = journey
+            .attribute("id")
+            .ok_or_else(|| invalid("service journey without id"))?;
+        let day_types: Vec<_> = child(journey, "dayTypes")
+            .into_iter()
+            .flat_map(|day_types| day_types.children())
+            .filter_map(|day_type| day_type.attribute("ref"))
+            .collect();
+        let runs = day_types
+            .iter()
+            .any(|day_type| day_types_running.get(day_type) == Some(&true));
+        if !day_types.is_empty() && !runs {
+            continue;
+        }
+
+        let journey_pattern = child_ref(journey, "ServiceJourneyPatternRef")
+            .or_else(|| child_ref(journey, "JourneyPatternRef"))
+            .and_then(|pattern| journey_patterns.get(pattern));
+        let line = child_ref(journey, "LineRef")
+            .or_else(|| {
+                let route = routes.get(child_ref(*journey_pattern?, "RouteRef")?)?;
+                child_ref(*route, "LineRef")
+            })
+            .and_then(|line| lines.get(line));
+        let label = line
+            .and_then(|line| child_text(*line, "PublicCode").or_else(|| child_text(*line, "Name")));
+
+        let mut stops = vec![];
+        for passing_time in child(journey, "passingTimes")
+            .into_iter()
+            .flat_map(|passing_times| passing_times.children())
+            .filter(|passing_time| passing_time.has_tag_name("TimetabledPassingTime"))
+        {
+            let stop_point_ref = child_ref(passing_time, "StopPointInJourneyPatternRef")
+                .ok_or_else(|| invalid(format!("passing time of '{id}' without stop point")))?;
+            let stop_point = stop_points_in_patterns
+                .get(stop_point_ref)
+                .ok_or_else(|| invalid(format!("unknown stop point '{stop_point_ref}'")))?;
+            let scheduled_stop_point_ref = child_ref(*stop_point, "ScheduledStopPointRef")
+                .ok_or_else(|| invalid(format!("stop point '{stop_point_ref}' without stop")))?;
+            let scheduled_stop_point = scheduled_stop_points.get(scheduled_stop_point_ref);
+
+            let (mut uic, mut trigram) = scheduled_stop_point
+                .map(|node| node_codes(*node))
+                .unwrap_or_else(|| stop_codes(None, scheduled_stop_point_ref));
+            if let Some(stop_place) = stop_place_assignments.get(scheduled_stop_point_ref) {
+                let (place_uic, place_trigram) = node_codes(**stop_place);
+                uic = place_uic.or(uic);
+                trigram = place_trigram.or(trigram);
+            }
+            let name = scheduled_stop_point
+                .and_then(|node| child_text(*node, "Name"))
+                .or_else(|| {
+                    child_text(
+                        **stop_place_assignments.get(scheduled_stop_point_ref)?,
+                        "Name",
+                    )
+                })
+                .unwrap_or(scheduled_stop_point_ref);
+            let for_boarding = child_text(*stop_point, "ForBoarding") != Some("false");
+            let for_alighting = child_text(*stop_point, "ForAlighting") != Some("false");
+
+            stops.push(ImportedStop {
+                id: scheduled_stop_point_ref.to_owned(),
+                name: name.to_owned(),
+                uic,
+                trigram,
+                arrival: to_datetime(
+                    child_text(passing_time, "ArrivalTime"),
+                    child_text(passing_time, "ArrivalDayOffset"),
+                )?,
+                departure: to_datetime(
+                    child_text(passing_time, "DepartureTime"),
+                    child_text(passing_time, "DepartureDayOffset"),
+                )?,
+                is_stop: for_boarding || for_alighting,
+            });
+        }
+
+        trips.push(ImportedTrip {
+            id: id.to_owned(),
+            name: child_text(journey, "PublicCode")
+                .or_else(|| child_text(journey, "PrivateCode"))
+                .or_else(|| child_text(journey, "Name"))
+                .unwrap_or(id)
+                .to_owned(),
+            labels: label.map(str::to_owned).into_iter().collect(),
+            route_type: None,
+            vehicle_type: child_ref(journey, "VehicleTypeRef").map(str::to_owned),
+            stops,
+        });
+    }
+    Ok(trips)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<PublicationDelivery xmlns="http://www.netex.org.uk/netex" version="1.1">
+  <dataObjects>
+    <CompositeFrame id="frame" version="1">
+      <FrameDefaults>
+        <DefaultLocale><TimeZone>Europe/Paris</TimeZone></DefaultLocale>
+      </FrameDefaults>
+      <frames>
+        <SiteFrame id="site" version="1">
+          <stopPlaces>
+            <StopPlace id="FR:StopPlace:87686006" version="1">
+              <Name>Paris Gare de Lyon</Name>
+            </StopPlace>
+          </stopPlaces>
+        </SiteFrame>
+        <ServiceFrame id="service" version="1">
+          <routes>
+            <Route id="route" version="1"><LineRef ref="line"/></Route>
+          </routes>
+          <lines>
+            <Line id="line" version="1"><Name>Lyon - Paris</Name><PublicCode>TGV</PublicCode></Line>
+          </lines>
+          <scheduledStopPoints>
+            <ScheduledStopPoint id="ssp_paris" version="1"><Name>Paris</Name></ScheduledStopPoint>
+            <ScheduledStopPoint id="ssp_mws" version="1">
+              <Name>Mid West station</Name>
+              <keyList><KeyValue><Key>TRIGRAM</Key><Value>MWS</Value></KeyValue></keyList>
+            </ScheduledStopPoint>
+          </scheduledStopPoints>
+          <stopAssignments>
+            <PassengerStopAssignment id="assignment" version="1" order="1">
+              <ScheduledStopPointRef ref="ssp_paris"/>
+              <StopPlaceRef ref="FR:StopPlace:87686006"/>
+            </PassengerStopAssignment>
+          </stopAssignments>
+          <journeyPatterns>
+            <ServiceJourneyPattern id="pattern" version="1">
+              <RouteRef ref="route"/>
+              <pointsInSequence>
+                <StopPointInJourneyPattern id="sp1" order="1" version="1">
+                  <ScheduledStopPointRef ref="ssp_mws"/>
+                  <ForAlighting>false</ForAlighting>
+                  <ForBoarding>false</ForBoarding>
+                </StopPointInJourneyPattern>
+                <StopPointInJourneyPattern id="sp2" order="2" version="1">
+                  <ScheduledStopPointRef ref="ssp_paris"/>
+                </StopPointInJourneyPattern>
+              </pointsInSequence>
+            </ServiceJourneyPattern>
+          </journeyPatterns>
+        </ServiceFrame>
+        <ServiceCalendarFrame id="calendar" version="1">
+          <dayTypeAssignments>
+            <DayTypeAssignment id="dta1" order="1" version="1">
+              <Date>2024-07-01</Date>
+              <DayTypeRef ref="summer"/>
+            </DayTypeAssignment>
+          </dayTypeAssignments>
+        </ServiceCalendarFrame>
+        <TimetableFrame id="timetable" version="1">
+          <vehicleJourneys>
+            <ServiceJourney id="sj1" version="1">
+              <PrivateCode>6601</PrivateCode>
+              <dayTypes><DayTypeRef ref="summer"/></dayTypes>
+              <ServiceJourneyPatternRef ref="pattern"/>
+              <VehicleTypeRef ref="TGV_2N2"/>
+              <passingTimes>
+                <TimetabledPassingTime version="1">
+                  <StopPointInJourneyPatternRef ref="sp1"/>
+                  <ArrivalTime>23:50:00</ArrivalTime>
+                  <DepartureTime>23:55:00</DepartureTime>
+                </TimetabledPassingTime>
+                <TimetabledPassingTime version="1">
+                  <StopPointInJourneyPatternRef ref="sp2"/>
+                  <ArrivalTime>01:30:00</ArrivalTime>
+                  <ArrivalDayOffset>1</ArrivalDayOffset>
+                </TimetabledPassingTime>
+              </passingTimes>
+            </ServiceJourney>
+          </vehicleJourneys>
+        </TimetableFrame>
+      </frames>
+    </CompositeFrame>
+  </dataObjects>
+</PublicationDelivery>
+"#;
+
+    #[test]
+    fn parse_service_journeys() {
+        let date = NaiveDate::from_ymd_opt(2024, 7, 1).unwrap();
+        let trips = parse_netex(DOCUMENT.as_bytes(), date).unwrap();
+        assert_eq!(
+            trips,
+            vec![ImportedTrip {
+                id: "sj1".into(),
+                name: "6601".into(),
+                labels: vec!["TGV".into()],
+                route_type: None,
+                vehicle_type: Some("TGV_2N2".into()),
+                stops: vec![
+                    ImportedStop {
+                        id: "ssp_mws".into(),
+                        name: "Mid West station".into(),
+                        uic: None,
+                        trigram: Some("MWS".into()),
+                        // Paris is UTC+2 in summer
+                        arrival: Some("2024-07-01T21:50:00Z".parse().unwrap()),
+                        departure: Some("2024-07-01T21:55:00Z".parse().unwrap()),
+                        is_stop: false,
+                    },
+                    ImportedStop {
+                        id: "ssp_paris".into(),
+                        name: "Paris".into(),
+                        uic: Some(87686006),
+                        trigram: None,
+                        arrival: Some("2024-07-01T23:30:00Z".parse().unwrap()),
+                        departure: None,
+                        is_stop: true,
+                    },
+                ],
+            }]
+        );
+    }
+
+    #[test]
+    fn skip_journeys_not_running() {
+        let date = NaiveDate::from_ymd_opt(2024, 7, 2).unwrap();
+        assert!(parse_netex(DOCUMENT.as_bytes(), date).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,626 @@
+//! Key performance indicators of a timetable, computed from the simulation of its trains
+//!
+//! The indicators are aggregated over all the trains, and grouped by rolling stock, label,
+//! hour of departure, class of commercial speed and electrification mode.
+
+use std::collections::hash_map::Entry;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use actix_web::get;
+use actix_web::web::Data;
+use actix_web::web::Path;
+use actix_web::web::Query;
+use actix_web::HttpResponse;
+use chrono::Timelike;
+use serde::Deserialize;
+use serde::Serialize;
+use utoipa::IntoParams;
+use utoipa::ToSchema;
+
+use super::TimetableError;
+use super::TimetableIdParam;
+use crate::core::v2::path_properties::PathPropertiesRequest;
+use crate::core::v2::path_properties::PropertyElectrificationValue;
+use crate::core::v2::path_properties::PropertyElectrificationValues;
+use crate::core::v2::pathfinding::PathfindingResult;
+use crate::core::v2::pathfinding::PathfindingResultSuccess;
+use crate::core::v2::simulation::ReportTrain;
+use crate::core::v2::simulation::SimulationResponse;
+use crate::core::AsCoreRequest;
+use crate::error::Result;
+use crate::modelsv2::prelude::*;
+use crate::modelsv2::resource_grant::ResourceType;
+use crate::modelsv2::resource_grant::Role;
+use crate::modelsv2::timetable::TimetableWithTrains;
+use crate::modelsv2::train_schedule::TrainSchedule;
+use crate::modelsv2::DbConnectionPool;
+use crate::modelsv2::Infra;
+use crate::views::authz::Authentication;
+use crate::views::v2::path::pathfinding_from_train;
+use crate::views::v2::train_schedule::train_simulation_batch;
+use crate::CoreClient;
+use crate::RedisClient;
+
+crate::routes! {
+    "/report" => {
+        report,
+    },
+}
+
+editoast_common::schemas! {
+    TimetableReport,
+    Indicators,
+    ElectrificationIndicators,
+    SpeedStatistics,
+}
+
+/// The width of the classes of the commercial speed distribution, in km/h
+const SPEED_CLASS_WIDTH: f64 = 20.;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+enum ReportFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ReportQueryParams {
+    /// The infra on which the trains are simulated
+    infra_id: i64,
+    /// Export the report as a JSON document or as a CSV table
+    #[serde(default)]
+    #[param(inline)]
+    format: ReportFormat,
+}
+
+/// Compute the key performance indicators of a timetable
+///
+/// The report of a scenario is the report of its timetable on its infra.
+#[utoipa::path(
+    tag = "timetablev2",
+    params(TimetableIdParam, ReportQueryParams),
+    responses(
+        (status = 200, description = "The report of the timetable, as JSON or as CSV", body = TimetableReport),
+        (status = 404, description = "Timetable or infra not found"),
+    ),
+)]
+#[get("")]
+async fn report(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    redis_client: Data<RedisClient>,
+    core_client: Data<CoreClient>,
+    timetable_id: Path<TimetableIdParam>,
+    Query(ReportQueryParams { infra_id, format }): Query<ReportQueryParams>,
+) -> Result<HttpResponse> {
+    let db_pool = db_pool.into_inner();
+    let conn = &mut db_pool.get().await?;
+    let timetable_id = timetable_id.into_inner().id;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check_timetable(conn, timetable_id, Role::Viewer)
+        .await?;
+    authorizer
+        .check(conn, ResourceType::Infra, infra_id, Role::Viewer)
+        .await?;
+
+    let timetable = TimetableWithTrains::retrieve_or_fail(conn, timetable_id, || {
+        TimetableError::NotFound { timetable_id }
+    })
+    .await?;
+    let infra = Infra::retrieve_or_fail(conn, infra_id, || TimetableError::InfraNotFound {
+        infra_id,
+    })
+    .await?;
+
+    let kpi_report = timetable_report(
+        db_pool.clone(),
+        redis_client.into_inner(),
+        core_client.into_inner(),
+        timetable,
+        &infra,
+    )
+    .await?;
+    Ok(match format {
+        ReportFormat::Json => HttpResponse::Ok().json(kpi_report),
+        ReportFormat::Csv => HttpResponse::Ok()
+            .content_type("text/csv")
+            .insert_header((
+                "Content-Disposition",
+                format!("attachment; filename=\"timetable_{timetable_id}_report.csv\""),
+            ))
+            .body(kpi_report.to_csv()?),
+    })
+}
+
+/// Simulates the trains of a timetable on an infra and aggregates their indicators
+async fn timetable_report(
+    db_pool: Arc<DbConnectionPool>,
+    redis_client: Arc<RedisClient>,
+    core_client: Arc<CoreClient>,
+    timetable: TimetableWithTrains,
+    infra: &Infra,
+) -> Result<TimetableReport> {
+    let conn = &mut db_pool.get().await?;
+    let mut redis_conn = redis_client.get_connection().await?;
+
+    let (trains, _): (Vec<TrainSchedule>, _) =
+        TrainSchedule::retrieve_batch(conn, timetable.train_ids).await?;
+    let simulations = train_simulation_batch(
+        db_pool.clone(),
+        redis_client.clone(),
+        core_client.clone(),
+        &trains,
+        infra,
+    )
+    .await?;
+
+    // Trains often share their path, whose properties are only fetched once
+    let mut path_electrifications = HashMap::new();
+    let mut runs = vec![];
+    for (train, simulation) in trains.iter().zip(simulations) {
+        let SimulationResponse::Success { final_output, .. } = simulation else {
+            runs.push((train, None));
+            continue;
+        };
+        let electrifications = match pathfinding_from_train(
+            conn,
+            &mut redis_conn,
+            core_client.clone(),
+            infra,
+            train.clone(),
+        )
+        .await?
+        {
+            PathfindingResult::Success(PathfindingResultSuccess {
+                track_section_ranges,
+                ..
+            }) => match path_electrifications.entry(track_section_ranges) {
+                Entry::Occupied(entry) => Some(&*entry.into_mut()),
+                Entry::Vacant(entry) => {
+                    let properties = PathPropertiesRequest {
+                        track_section_ranges: entry.key(),
+                        infra: infra.id,
+                        expected_version: infra.version.clone(),
+                    }
+                    .fetch(core_client.as_ref())
+                    .await?;
+                    Some(&*entry.insert(properties.electrifications))
+                }
+            },
+            _ => None,
+        };
+        let run = TrainRun::new(train, &final_output.report_train, electrifications);
+        runs.push((train, Some(run)));
+    }
+    Ok(TimetableReport::new(runs))
+}
+
+/// The indicators of a simulated train
+#[derive(Debug, Clone, PartialEq)]
+struct TrainRun {
+    distance_km: f64,
+    run_time_hours: f64,
+    energy_consumption: f64,
+    stops: usize,
+    /// In seconds
+    dwell_time: f64,
+    /// The part of the path covered by each electrification mode
+    electrifications: BTreeMap<String, f64>,
+}
+
+impl TrainRun {
+    fn new(
+        train: &TrainSchedule,
+        simulation: &ReportTrain,
+        electrifications: Option<&PropertyElectrificationValues>,
+    ) -> Self {
+        let length = simulation.positions.last().copied().unwrap_or_default();
+        let stops = train
+            .schedule
+            .iter()
+            .filter_map(|item| item.stop_for.as_ref())
+            .collect::<Vec<_>>();
+        Self {
+            distance_km: length as f64 / 1_000_000.,
+            run_time_hours: simulation.times.last().copied().unwrap_or_default() as f64
+                / 3_600_000.,
+            energy_consumption: simulation.energy_consumption,
+            stops: stops.len(),
+            dwell_time: stops
+                .iter()
+                .map(|stop_for| stop_for.num_milliseconds() as f64 / 1000.)
+                .sum(),
+            electrifications: electrifications
+                .map(|electrifications| electrification_shares(electrifications, length))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// The commercial speed in km/h, if the train runs at all
+    fn commercial_speed(&self) -> Option<f64> {
+        (self.run_time_hours > 0.).then(|| self.distance_km / self.run_time_hours)
+    }
+}
+
+/// Computes the part of a path of `length` mm covered by each electrification mode
+fn electrification_shares(
+    electrifications: &PropertyElectrificationValues,
+    length: u64,
+) -> BTreeMap<String, f64> {
+    let mut shares = BTreeMap::new();
+    if length == 0 {
+        return shares;
+    }
+    let bounds = std::iter::once(0)
+        .chain(electrifications.boundaries.iter().copied())
+        .chain(std::iter::once(length))
+        .map(|bound| bound.min(length))
+        .collect::<Vec<_>>();
+    for (value, range) in electrifications.values.iter().zip(bounds.windows(2)) {
+        let mode = match value {
+            PropertyElectrificationValue::Electrification { voltage } => voltage.clone(),
+            PropertyElectrificationValue::NeutralSection { .. } => "neutral_section".to_owned(),
+            PropertyElectrificationValue::NonElectrified => "non_electrified".to_owned(),
+        };
+        *shares.entry(mode).or_default() += (range[1] - range[0]) as f64 / length as f64;
+    }
+    shares
+}
+
+/// Indicators aggregated over a group of trains
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Indicators {
+    /// The number of trains of the group
+    pub trains: usize,
+    /// The number of trains whose simulation failed, which don't count in the other indicators
+    pub failed_simulations: usize,
+    pub train_km: f64,
+    /// The total run time of the trains, stops included
+    pub train_hours: f64,
+    /// The total energy consumption of the trains, as given by the simulations
+    pub energy_consumption: f64,
+    /// The number of scheduled stops
+    pub stops: usize,
+    /// The total duration of the scheduled stops, in seconds
+    pub dwell_time: f64,
+    /// The train-km divided by the train-hours, in km/h
+    pub commercial_speed: Option<f64>,
+}
+
+impl Indicators {
+    fn add(&mut self, run: Option<&TrainRun>) {
+        self.trains += 1;
+        let Some(run) = run else {
+            self.failed_simulations += 1;
+            return;
+        };
+        self.train_km += run.distance_km;
+        self.train_hours += run.run_time_hours;
+        self.energy_consumption += run.energy_consumption;
+        self.stops += run.stops;
+        self.dwell_time += run.dwell_time;
+        self.commercial_speed = (self.train_hours > 0.).then(|| self.train_km / self.train_hours);
+    }
+}
+
+/// Indicators of the parts of the paths covered by an electrification mode
+///
+/// The energy consumption of a train is split between the electrification modes
+/// in proportion to the distance covered with each of them.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ElectrificationIndicators {
+    /// The number of trains running under this electrification mode
+    pub trains: usize,
+    pub train_km: f64,
+    pub energy_consumption: f64,
+}
+
+/// The distribution of the commercial speeds of the trains, in km/h
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SpeedStatistics {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub median: f64,
+}
+
+impl SpeedStatistics {
+    fn new(mut speeds: Vec<f64>) -> Option<Self> {
+        if speeds.is_empty() {
+            return None;
+        }
+        speeds.sort_by(f64::total_cmp);
+        let count = speeds.len();
+        let median = if count % 2 == 0 {
+            (speeds[count / 2 - 1] + speeds[count / 2]) / 2.
+        } else {
+            speeds[count / 2]
+        };
+        Some(Self {
+            min: speeds[0],
+            max: speeds[count - 1],
+            mean: speeds.iter().sum::<f64>() / count as f64,
+            median,
+        })
+    }
+}
+
+/// The key performance indicators of a timetable
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TimetableReport {
+    pub total: Indicators,
+    pub by_rolling_stock: BTreeMap<String, Indicators>,
+    /// Trains with several labels count in each of them
+    pub by_label: BTreeMap<String, Indicators>,
+    /// Grouped by the hour of day (UTC) the trains depart at
+    pub by_hour: BTreeMap<u32, Indicators>,
+    /// Grouped by classes of 20 km/h, keyed by their lower bound
+    pub by_commercial_speed: BTreeMap<u32, Indicators>,
+    pub by_electrification: BTreeMap<String, ElectrificationIndicators>,
+    /// The distribution of the commercial speeds of the trains, if any could be simulated
+    pub commercial_speed: Option<SpeedStatistics>,
+    pub failed_train_ids: Vec<i64>,
+}
+
+impl TimetableReport {
+    /// Aggregates the runs of trains, which are `None` if their simulation failed
+    fn new<'a>(runs: impl IntoIterator<Item = (&'a TrainSchedule, Option<TrainRun>)>) -> Self {
+        let mut timetable_report = Self::default();
+        let mut speeds = vec![];
+        for (train, run) in runs {
+            let run = run.as_ref();
+            timetable_report.total.add(run);
+            timetable_report
+                .by_rolling_stock
+                .entry(train.rolling_stock_name.clone())
+                .or_default()
+                .add(run);
+            for label in train.labels.iter().flatten() {
+                timetable_report
+                    .by_label
+                    .entry(label.clone())
+                    .or_default()
+                    .add(run);
+            }
+            timetable_report
+                .by_hour
+                .entry(train.start_time.hour())
+                .or_default()
+                .add(run);
+
+            let Some(run) = run else {
+                timetable_report.failed_train_ids.push(train.id);
+                continue;
+            };
+            if let Some(speed) = run.commercial_speed() {
+                let class = (speed / SPEED_CLASS_WIDTH).floor() * SPEED_CLASS_WIDTH;
+                timetable_report
+                    .by_commercial_speed
+                    .entry(class as u32)
+                    .or_default()
+                    .add(Some(run));
+                speeds.push(speed);
+            }
+            for (mode, share) in run.electrifications.iter() {
+                let indicators = timetable_report
+                    .by_electrification
+                    .entry(mode.clone())
+                    .or_default();
+                indicators.trains += 1;
+                indicators.train_km += run.distance_km * share;
+                indicators.energy_consumption += run.energy_consumption * share;
+            }
+        }
+        timetable_report.commercial_speed = SpeedStatistics::new(speeds);
+        timetable_report
+    }
+
+    /// Writes the groups of the report as the rows of a CSV table
+    fn to_csv(&self) -> Result<Vec<u8>> {
+        let mut writer = csv::Writer::from_writer(vec![]);
+        writer.serialize(CsvRow::new("total", String::new(), &self.total))?;
+        for (rolling_stock, indicators) in self.by_rolling_stock.iter() {
+            writer.serialize(CsvRow::new(
+                "rolling_stock",
+                rolling_stock.clone(),
+                indicators,
+            ))?;
+        }
+        for (label, indicators) in self.by_label.iter() {
+            writer.serialize(CsvRow::new("label", label.clone(), indicators))?;
+        }
+        for (hour, indicators) in self.by_hour.iter() {
+            writer.serialize(CsvRow::new("hour", hour.to_string(), indicators))?;
+        }
+        for (class, indicators) in self.by_commercial_speed.iter() {
+            writer.serialize(CsvRow::new(
+                "commercial_speed",
+                format!("{class}-{}", *class + SPEED_CLASS_WIDTH as u32),
+                indicators,
+            ))?;
+        }
+        for (mode, indicators) in self.by_electrification.iter() {
+            writer.serialize(CsvRow {
+                group: "electrification",
+                key: mode.clone(),
+                trains: indicators.trains,
+                train_km: indicators.train_km,
+                energy_consumption: indicators.energy_consumption,
+                ..Default::default()
+            })?;
+        }
+        writer.into_inner().map_err(|err| err.into_error().into())
+    }
+}
+
+/// A group of trains in the CSV export of a report
+///
+/// The electrification groups only have the indicators which can be split along the paths.
+#[derive(Debug, Default, Serialize)]
+struct CsvRow {
+    group: &'static str,
+    key: String,
+    trains: usize,
+    failed_simulations: Option<usize>,
+    train_km: f64,
+    train_hours: Option<f64>,
+    energy_consumption: f64,
+    stops: Option<usize>,
+    dwell_time: Option<f64>,
+    commercial_speed: Option<f64>,
+}
+
+impl CsvRow {
+    fn new(group: &'static str, key: String, indicators: &Indicators) -> Self {
+        Self {
+            group,
+            key,
+            trains: indicators.trains,
+            failed_simulations: Some(indicators.failed_simulations),
+            train_km: indicators.train_km,
+            train_hours: Some(indicators.train_hours),
+            energy_consumption: indicators.energy_consumption,
+            stops: Some(indicators.stops),
+            dwell_time: Some(indicators.dwell_time),
+            commercial_speed: indicators.commercial_speed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn train(
+        id: i64,
+        rolling_stock_name: &str,
+        labels: &[&str],
+        start_time: &str,
+    ) -> TrainSchedule {
+        TrainSchedule {
+            id,
+            rolling_stock_name: rolling_stock_name.to_owned(),
+            labels: labels.iter().map(|label| Some(label.to_string())).collect(),
+            start_time: start_time.parse().unwrap(),
+            ..Default::default()
+        }
+    }
+
+    fn run(distance_km: f64, run_time_hours: f64) -> TrainRun {
+        TrainRun {
+            distance_km,
+            run_time_hours,
+            energy_consumption: distance_km * 10.,
+            stops: 2,
+            dwell_time: 60.,
+            electrifications: BTreeMap::from([
+                ("25000V".to_owned(), 0.75),
+                ("non_electrified".to_owned(), 0.25),
+            ]),
+        }
+    }
+
+    #[test]
+    fn train_run_from_simulation() {
+        let mut train = train(1, "rs", &[], "2024-06-01T08:00:00Z");
+        train.schedule = serde_json::from_str(
+            r#"[{"at": "a", "stop_for": "PT30S"}, {"at": "b"}, {"at": "c", "stop_for": "PT2M"}]"#,
+        )
+        .unwrap();
+        let simulation = ReportTrain {
+            positions: vec![0, 60_000_000, 120_000_000],
+            times: vec![0, 1_800_000, 3_600_000],
+            speeds: vec![0., 40., 0.],
+            energy_consumption: 42.,
+        };
+        let electrifications = PropertyElectrificationValues {
+            boundaries: vec![30_000_000, 90_000_000],
+            values: serde_json::from_str(
+                r#"[
+                    {"type": "electrification", "voltage": "1500V"},
+                    {"type": "non_electrified"},
+                    {"type": "electrification", "voltage": "1500V"}
+                ]"#,
+            )
+            .unwrap(),
+        };
+
+        let run = TrainRun::new(&train, &simulation, Some(&electrifications));
+        assert_eq!(run.distance_km, 120.);
+        assert_eq!(run.run_time_hours, 1.);
+        assert_eq!(run.commercial_speed(), Some(120.));
+        assert_eq!(run.stops, 2);
+        assert_eq!(run.dwell_time, 150.);
+        assert_eq!(
+            run.electrifications,
+            BTreeMap::from([
+                ("1500V".to_owned(), 0.5),
+                ("non_electrified".to_owned(), 0.5)
+            ])
+        );
+    }
+
+    #[test]
+    fn aggregate_runs() {
+        let trains = [
+            train(1, "tgv", &["fast", "south"], "2024-06-01T08:10:00Z"),
+            train(2, "tgv", &["fast"], "2024-06-01T08:40:00Z"),
+            train(3, "ter", &[], "2024-06-01T09:00:00Z"),
+        ];
+        let timetable_report = TimetableReport::new([
+            (&trains[0], Some(run(300., 1.5))),
+            (&trains[1], Some(run(100., 1.))),
+            (&trains[2], None),
+        ]);
+
+        assert_eq!(timetable_report.total.trains, 3);
+        assert_eq!(timetable_report.total.failed_simulations, 1);
+        assert_eq!(timetable_report.total.train_km, 400.);
+        assert_eq!(timetable_report.total.train_hours, 2.5);
+        assert_eq!(timetable_report.total.commercial_speed, Some(160.));
+        assert_eq!(timetable_report.total.stops, 4);
+        assert_eq!(timetable_report.by_rolling_stock["tgv"].trains, 2);
+        assert_eq!(
+            timetable_report.by_rolling_stock["ter"].failed_simulations,
+            1
+        );
+        assert_eq!(timetable_report.by_label["fast"].train_km, 400.);
+        assert_eq!(timetable_report.by_label["south"].train_km, 300.);
+        assert_eq!(timetable_report.by_hour[&8].trains, 2);
+        assert_eq!(timetable_report.by_hour[&9].failed_simulations, 1);
+        assert_eq!(
+            timetable_report
+                .by_commercial_speed
+                .keys()
+                .collect::<Vec<_>>(),
+            [&100, &200]
+        );
+        assert_eq!(timetable_report.by_electrification["25000V"].train_km, 300.);
+        assert_eq!(
+            timetable_report.by_electrification["non_electrified"].energy_consumption,
+            1000.
+        );
+        let speeds = timetable_report.commercial_speed.as_ref().unwrap();
+        assert_eq!((speeds.min, speeds.max, speeds.median), (100., 200., 150.));
+        assert_eq!(timetable_report.failed_train_ids, [3]);
+
+        let csv = String::from_utf8(timetable_report.to_csv().unwrap()).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("group,key,trains,failed_simulations,train_km,train_hours,energy_consumption,stops,dwell_time,commercial_speed")
+        );
+        assert_eq!(
+            lines.next(),
+            Some("total,,3,1,400.0,2.5,4000.0,4,120.0,160.0")
+        );
+        assert!(csv.contains("\ncommercial_speed,100-120,1,0,100.0,1.0,1000.0,2,60.0,100.0\n"));
+        assert!(csv.ends_with("electrification,non_electrified,2,,100.0,,1000.0,,,\n"));
+    }
+}
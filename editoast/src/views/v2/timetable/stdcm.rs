@@ -26,11 +26,15 @@ use crate::core::v2::stdcm::{STDCMPathItem, STDCMWorkSchedule, UndirectedTrackRa
 use crate::core::AsCoreRequest;
 use crate::core::CoreClient;
 use crate::error::Result;
+use crate::modelsv2::consist::Consist;
+use crate::modelsv2::resource_grant::ResourceType;
+use crate::modelsv2::resource_grant::Role;
 use crate::modelsv2::timetable::TimetableWithTrains;
 use crate::modelsv2::train_schedule::TrainSchedule;
 use crate::modelsv2::work_schedules::WorkSchedule;
 use crate::modelsv2::RollingStockModel;
 use crate::modelsv2::{DbConnection, Infra, List};
+use crate::views::authz::Authentication;
 use crate::views::v2::path::pathfinding::extract_location_from_path_items;
 use crate::views::v2::path::pathfinding::TrackOffsetExtractionError;
 use crate::views::v2::train_schedule::train_simulation_batch;
@@ -60,6 +64,11 @@ enum STDCMError {
     TimetableNotFound { timetable_id: i64 },
     #[error("Rolling stock {rolling_stock_id} does not exist")]
     RollingStockNotFound { rolling_stock_id: i64 },
+    #[error("Consist {consist_id} does not exist")]
+    ConsistNotFound { consist_id: i64 },
+    #[error("Exactly one of 'rolling_stock_id' and 'consist_id' must be given")]
+    #[editoast_error(status = 400)]
+    RollingStockOrConsistRequired,
     #[error("Path item {index} is invalid")]
     InvalidPathItem {
         index: usize,
@@ -72,7 +81,10 @@ enum STDCMError {
 pub struct STDCMRequestPayload {
     start_time: DateTime<Utc>,
     steps: Vec<PathfindingItem>,
-    rolling_stock_id: i64,
+    /// The rolling stock of the train, unless it is a consist
+    rolling_stock_id: Option<i64>,
+    /// The consist of the train, instead of a rolling stock
+    consist_id: Option<i64>,
     comfort: Comfort,
     /// By how long we can shift the departure time in milliseconds
     #[serde(default = "default_maximum_departure_delay")]
@@ -139,6 +151,7 @@ struct InfraIdQueryParam {
 #[post("")]
 async fn stdcm(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     redis_client: Data<RedisClient>,
     core_client: Data<CoreClient>,
     id: Path<i64>,
@@ -150,6 +163,13 @@ async fn stdcm(
     let core_client = core_client.into_inner();
     let timetable_id = id.into_inner();
     let infra_id = query.into_inner().infra;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check_timetable(conn, timetable_id, Role::Viewer)
+        .await?;
+    authorizer
+        .check(conn, ResourceType::Infra, infra_id, Role::Viewer)
+        .await?;
     let data = data.into_inner();
 
     // 1. Retrieve Timetable / Infra / Trains / Simulation / Rolling Stock
@@ -172,12 +192,31 @@ async fn stdcm(
     )
     .await?;
 
-    let rolling_stock = RollingStockModel::retrieve_or_fail(conn, data.rolling_stock_id, || {
-        STDCMError::RollingStockNotFound {
-            rolling_stock_id: data.rolling_stock_id,
+    let rolling_stock = match (data.rolling_stock_id, data.consist_id) {
+        (Some(rolling_stock_id), None) => {
+            RollingStockModel::retrieve_or_fail(conn, rolling_stock_id, || {
+                STDCMError::RollingStockNotFound { rolling_stock_id }
+            })
+            .await?
         }
-    })
-    .await?;
+        (None, Some(consist_id)) => {
+            Consist::retrieve_or_fail(conn, consist_id, || STDCMError::ConsistNotFound {
+                consist_id,
+            })
+            .await?
+            .rolling_stock(conn)
+            .await?
+        }
+        _ => return Err(STDCMError::RollingStockOrConsistRequired.into()),
+    };
+    authorizer
+        .check(
+            conn,
+            ResourceType::RollingStock,
+            rolling_stock.id,
+            Role::Viewer,
+        )
+        .await?;
 
     // 2. Build core request
     let mut trains_requirements = HashMap::new();
@@ -0,0 +1,393 @@
+use std::collections::HashMap;
+
+use actix_web::get;
+use actix_web::web::Data;
+use actix_web::web::Json;
+use actix_web::web::Path;
+use actix_web::web::Query;
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::TimeZone;
+use chrono::Utc;
+use editoast_schemas::infra::Direction;
+use editoast_schemas::infra::TrackRange;
+use serde::Deserialize;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::InfraIdQueryParam;
+use super::TimetableError;
+use super::TimetableIdParam;
+use crate::core::v2::pathfinding::PathfindingResult;
+use crate::core::v2::pathfinding::PathfindingResultSuccess;
+use crate::core::v2::pathfinding::TrackRange as PathTrackRange;
+use crate::core::v2::simulation::SimulationResponse;
+use crate::error::Result;
+use crate::modelsv2::prelude::*;
+use crate::modelsv2::resource_grant::ResourceType;
+use crate::modelsv2::resource_grant::Role;
+use crate::modelsv2::timetable::TimetableWithTrains;
+use crate::modelsv2::train_schedule::TrainSchedule;
+use crate::modelsv2::work_schedules::WorkSchedule;
+use crate::modelsv2::DbConnectionPool;
+use crate::modelsv2::Infra;
+use crate::modelsv2::RollingStockModel;
+use crate::views::authz::Authentication;
+use crate::views::v2::path::pathfinding_from_train_batch;
+use crate::views::v2::train_schedule::train_simulation_batch;
+use crate::CoreClient;
+use crate::RedisClient;
+
+crate::routes! {
+    "/work_schedule_conflicts" => {
+        work_schedule_conflicts,
+    },
+}
+
+editoast_common::schemas! {
+    WorkScheduleConflict,
+}
+
+/// A train running through a track range closed by a work schedule
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct WorkScheduleConflict {
+    /// The train running through the work schedule
+    pub train_id: i64,
+    /// The work schedule the train runs through
+    pub work_schedule_id: i64,
+    /// Datetime of the start of the conflict
+    pub start_time: DateTime<Utc>,
+    /// Datetime of the end of the conflict
+    pub end_time: DateTime<Utc>,
+    /// The part of the work schedule track ranges occupied by the train
+    pub track_range: TrackRange,
+}
+
+/// Retrieve the list of trains running through active work schedules (invalid trains are ignored)
+#[utoipa::path(
+    tag = "timetablev2,work_schedules",
+    params(TimetableIdParam, InfraIdQueryParam),
+    responses(
+        (status = 200, description = "List of work schedule conflicts", body = Vec<WorkScheduleConflict>),
+    ),
+)]
+#[get("")]
+async fn work_schedule_conflicts(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    redis_client: Data<RedisClient>,
+    core_client: Data<CoreClient>,
+    timetable_id: Path<TimetableIdParam>,
+    query: Query<InfraIdQueryParam>,
+) -> Result<Json<Vec<WorkScheduleConflict>>> {
+    let db_pool = db_pool.into_inner();
+    let conn = &mut db_pool.clone().get().await?;
+    let redis_client = redis_client.into_inner();
+    let core_client = core_client.into_inner();
+    let timetable_id = timetable_id.into_inner().id;
+    let infra_id = query.into_inner().infra_id;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check_timetable(conn, timetable_id, Role::Viewer)
+        .await?;
+    authorizer
+        .check(conn, ResourceType::Infra, infra_id, Role::Viewer)
+        .await?;
+
+    // 1. Retrieve Timetable / Infra / Trains / Simulations
+    let timetable = TimetableWithTrains::retrieve_or_fail(conn, timetable_id, || {
+        TimetableError::NotFound { timetable_id }
+    })
+    .await?;
+
+    let infra = Infra::retrieve_or_fail(conn, infra_id, || TimetableError::InfraNotFound {
+        infra_id,
+    })
+    .await?;
+
+    let (trains, _): (Vec<TrainSchedule>, _) =
+        TrainSchedule::retrieve_batch(conn, timetable.train_ids).await?;
+
+    let simulations = train_simulation_batch(
+        db_pool.clone(),
+        redis_client.clone(),
+        core_client.clone(),
+        &trains,
+        &infra,
+    )
+    .await?;
+    let pathfindings =
+        pathfinding_from_train_batch(db_pool, redis_client, core_client, &infra, &trains).await?;
+
+    // 2. Build the occupancy of each successfully simulated train
+    let mut train_lengths = HashMap::new();
+    let mut occupancies = Vec::with_capacity(trains.len());
+    for ((train, sim), pathfinding) in trains.into_iter().zip(simulations).zip(pathfindings) {
+        let final_output = match sim {
+            SimulationResponse::Success { final_output, .. } => final_output,
+            _ => continue,
+        };
+        let track_section_ranges = match pathfinding {
+            PathfindingResult::Success(PathfindingResultSuccess {
+                track_section_ranges,
+                ..
+            }) => track_section_ranges,
+            _ => continue,
+        };
+        let length = match train_lengths.get(&train.rolling_stock_name) {
+            Some(length) => *length,
+            None => {
+                let Some(rolling_stock) =
+                    RollingStockModel::retrieve_or_derive(conn, train.rolling_stock_name.clone())
+                        .await?
+                else {
+                    continue;
+                };
+                let length = (rolling_stock.length * 1000.).round() as u64;
+                train_lengths.insert(train.rolling_stock_name.clone(), length);
+                length
+            }
+        };
+        occupancies.push(TrainOccupancy {
+            train_id: train.id,
+            start_time: train.start_time,
+            length,
+            path: track_section_ranges,
+            positions: final_output.report_train.positions,
+            times: final_output.report_train.times,
+        });
+    }
+
+    // 3. Only consider the work schedules active while the trains are running
+    let (Some(first_start), Some(last_end)) = (
+        occupancies.iter().map(|o| o.start_time).min(),
+        occupancies.iter().map(|o| o.end_time()).max(),
+    ) else {
+        return Ok(Json(vec![]));
+    };
+    let settings = SelectionSettings::new()
+        .filter(move || WorkSchedule::ends_after(first_start.naive_utc()))
+        .filter(move || WorkSchedule::starts_before(last_end.naive_utc()));
+    let work_schedules = WorkSchedule::list(conn, settings).await?;
+
+    // 4. Detect the conflicts
+    let mut conflicts = vec![];
+    for occupancy in &occupancies {
+        conflicts.extend(occupancy.work_schedule_conflicts(&work_schedules)?);
+    }
+    Ok(Json(conflicts))
+}
+
+/// The simulated occupancy of the tracks by a train
+#[derive(Debug)]
+struct TrainOccupancy {
+    train_id: i64,
+    start_time: DateTime<Utc>,
+    /// Length of the train in mm
+    length: u64,
+    /// The path of the train
+    path: Vec<PathTrackRange>,
+    /// Positions of the head of the train on its path in mm
+    positions: Vec<u64>,
+    /// Times in ms since `start_time` associated to a position
+    times: Vec<u64>,
+}
+
+impl TrainOccupancy {
+    fn end_time(&self) -> DateTime<Utc> {
+        self.start_time + Duration::milliseconds(*self.times.last().unwrap_or(&0) as i64)
+    }
+
+    /// Returns the datetime at which the head of the train first reaches a path position
+    ///
+    /// Fails if the simulation report of the train is empty.
+    fn datetime_at(&self, position: u64) -> Result<DateTime<Utc>> {
+        let index = self.positions.partition_point(|&p| p < position);
+        let time = if index == 0 {
+            *self
+                .times
+                .first()
+                .ok_or(TimetableError::EmptySimulationReport {
+                    train_id: self.train_id,
+                })?
+        } else if index == self.positions.len() {
+            self.times[index - 1]
+        } else {
+            let (start_pos, end_pos) = (self.positions[index - 1], self.positions[index]);
+            let (start_time, end_time) = (self.times[index - 1], self.times[index]);
+            start_time + (position - start_pos) * (end_time - start_time) / (end_pos - start_pos)
+        };
+        Ok(self.start_time + Duration::milliseconds(time as i64))
+    }
+
+    /// Lists the track ranges of the work schedules occupied by the train while they are active
+    ///
+    /// A path range is occupied from the moment the head of the train enters it
+    /// until the tail of the train leaves it.
+    fn work_schedule_conflicts(
+        &self,
+        work_schedules: &[WorkSchedule],
+    ) -> Result<Vec<WorkScheduleConflict>> {
+        let path_length = self.positions.last().copied().unwrap_or_default();
+        let mut conflicts = vec![];
+        for work_schedule in work_schedules {
+            let work_start = Utc.from_utc_datetime(&work_schedule.start_date_time);
+            let work_end = Utc.from_utc_datetime(&work_schedule.end_date_time);
+            let mut range_position = 0;
+            for path_range in &self.path {
+                let path_range_position = range_position;
+                range_position += path_range.length();
+                for work_range in work_schedule
+                    .track_ranges
+                    .iter()
+                    .filter(|work_range| work_range.track == path_range.track_section)
+                {
+                    let work_begin = (work_range.begin.min(work_range.end) * 1000.).round() as u64;
+                    let work_end_offset =
+                        (work_range.begin.max(work_range.end) * 1000.).round() as u64;
+                    let begin = work_begin.max(path_range.begin);
+                    let end = work_end_offset.min(path_range.end);
+                    if begin >= end {
+                        continue;
+                    }
+                    let (entry, exit) = match path_range.direction {
+                        Direction::StartToStop => (
+                            path_range_position + begin - path_range.begin,
+                            path_range_position + end - path_range.begin,
+                        ),
+                        Direction::StopToStart => (
+                            path_range_position + path_range.end - end,
+                            path_range_position + path_range.end - begin,
+                        ),
+                    };
+                    let start_time = self.datetime_at(entry)?.max(work_start);
+                    let end_time = self
+                        .datetime_at((exit + self.length).min(path_length))?
+                        .min(work_end);
+                    if start_time >= end_time {
+                        continue;
+                    }
+                    conflicts.push(WorkScheduleConflict {
+                        train_id: self.train_id,
+                        work_schedule_id: work_schedule.id,
+                        start_time,
+                        end_time,
+                        track_range: TrackRange::new(
+                            &path_range.track_section,
+                            begin as f64 / 1000.,
+                            end as f64 / 1000.,
+                        ),
+                    });
+                }
+            }
+        }
+        Ok(conflicts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+    use rstest::rstest;
+
+    use super::*;
+
+    fn occupancy() -> TrainOccupancy {
+        // The train runs 1km on A (forward) then 1km on B (backward) at 10m/s
+        TrainOccupancy {
+            train_id: 1,
+            start_time: "2024-01-01T08:00:00Z".parse().unwrap(),
+            length: 100_000,
+            path: vec![
+                PathTrackRange::new("A", 0, 1_000_000, Direction::StartToStop),
+                PathTrackRange::new("B", 0, 1_000_000, Direction::StopToStart),
+            ],
+            positions: vec![0, 2_000_000],
+            times: vec![0, 200_000],
+        }
+    }
+
+    fn work_schedule(start: &str, end: &str, track_ranges: Vec<TrackRange>) -> WorkSchedule {
+        WorkSchedule {
+            id: 42,
+            start_date_time: start.parse::<NaiveDateTime>().unwrap(),
+            end_date_time: end.parse::<NaiveDateTime>().unwrap(),
+            track_ranges,
+            ..Default::default()
+        }
+    }
+
+    #[rstest]
+    #[case::other_track(vec![TrackRange::new("C", 0., 1000.)], "2024-01-01T07:00:00", "2024-01-01T09:00:00")]
+    #[case::before_train(vec![TrackRange::new("A", 0., 1000.)], "2024-01-01T07:00:00", "2024-01-01T08:00:00")]
+    #[case::after_train(vec![TrackRange::new("B", 0., 1000.)], "2024-01-01T08:04:00", "2024-01-01T09:00:00")]
+    fn no_conflict(#[case] track_ranges: Vec<TrackRange>, #[case] start: &str, #[case] end: &str) {
+        let work_schedules = vec![work_schedule(start, end, track_ranges)];
+        assert!(occupancy()
+            .work_schedule_conflicts(&work_schedules)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn conflict_forward_range() {
+        let work_schedules = vec![work_schedule(
+            "2024-01-01T07:00:00",
+            "2024-01-01T09:00:00",
+            vec![TrackRange::new("A", 200., 500.)],
+        )];
+        let conflicts = occupancy()
+            .work_schedule_conflicts(&work_schedules)
+            .unwrap();
+        // The head enters at 200m (20s) and the tail leaves at 500m + 100m (60s)
+        assert_eq!(
+            conflicts,
+            vec![WorkScheduleConflict {
+                train_id: 1,
+                work_schedule_id: 42,
+                start_time: "2024-01-01T08:00:20Z".parse().unwrap(),
+                end_time: "2024-01-01T08:01:00Z".parse().unwrap(),
+                track_range: TrackRange::new("A", 200., 500.),
+            }]
+        );
+    }
+
+    #[test]
+    fn conflict_backward_range_truncated_by_work_window() {
+        let work_schedules = vec![work_schedule(
+            "2024-01-01T08:02:40",
+            "2024-01-01T09:00:00",
+            vec![TrackRange::new("B", 0., 500.)],
+        )];
+        let conflicts = occupancy()
+            .work_schedule_conflicts(&work_schedules)
+            .unwrap();
+        // B is run backward: [0, 500] on B spans path positions [1500m, 2000m],
+        // the head enters at 150s (before the work starts) and the train stops at the end of its path at 200s
+        assert_eq!(
+            conflicts,
+            vec![WorkScheduleConflict {
+                train_id: 1,
+                work_schedule_id: 42,
+                start_time: "2024-01-01T08:02:40Z".parse().unwrap(),
+                end_time: "2024-01-01T08:03:20Z".parse().unwrap(),
+                track_range: TrackRange::new("B", 0., 500.),
+            }]
+        );
+    }
+
+    #[test]
+    fn empty_simulation_report() {
+        let work_schedules = vec![work_schedule(
+            "2024-01-01T07:00:00",
+            "2024-01-01T09:00:00",
+            vec![TrackRange::new("A", 200., 500.)],
+        )];
+        let occupancy = TrainOccupancy {
+            positions: vec![],
+            times: vec![],
+            ..occupancy()
+        };
+        assert!(occupancy.work_schedule_conflicts(&work_schedules).is_err());
+    }
+}
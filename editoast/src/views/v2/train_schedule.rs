@@ -12,6 +12,9 @@ use actix_web::HttpRequest;
 use actix_web::{delete, get, put, HttpResponse};
 use editoast_derive::EditoastError;
 use editoast_schemas::train_schedule::TrainScheduleBase;
+use futures::stream;
+use futures::Stream;
+use futures::StreamExt;
 use itertools::Itertools;
 use serde::Deserialize;
 use serde::Serialize;
@@ -37,27 +40,38 @@ use crate::core::v2::simulation::SimulationScheduleItem;
 use crate::core::v2::simulation::ZoneUpdate;
 use crate::core::AsCoreRequest;
 use crate::core::CoreClient;
+use crate::error::InternalError;
 use crate::error::Result;
 use crate::modelsv2::infra::Infra;
+use crate::modelsv2::resource_grant::ResourceType;
+use crate::modelsv2::resource_grant::Role;
+use crate::modelsv2::rolling_stock_revision::RollingStockRevision;
 use crate::modelsv2::timetable::Timetable;
 use crate::modelsv2::train_schedule::TrainSchedule;
 use crate::modelsv2::train_schedule::TrainScheduleChangeset;
 use crate::modelsv2::DbConnection;
 use crate::modelsv2::DbConnectionPool;
+use crate::modelsv2::ElectricalProfileSet;
 use crate::modelsv2::Model;
 use crate::modelsv2::Retrieve;
 use crate::modelsv2::RetrieveBatch;
+use crate::modelsv2::RollingStockModel;
+use crate::views::authz::Authentication;
+use crate::views::sse;
+use crate::views::sse::Event;
 use crate::views::v2::path::pathfinding_from_train;
 use crate::views::v2::path::PathfindingError;
 use crate::RedisClient;
-use crate::RollingStockModel;
 
 const CACHE_SIMULATION_EXPIRATION: u64 = 604800; // 1 week
+/// The number of simulations computed at the same time by [train_simulation_stream]
+const SIMULATION_STREAM_CONCURRENCY: usize = 32;
 
 crate::routes! {
     "/v2/train_schedule" => {
         delete,
         simulation_summary,
+        simulation_summary_stream,
         get_batch,
         projection::routes(),
         "/{id}" => {
@@ -66,6 +80,9 @@ crate::routes! {
             simulation,
             "/path" => {
                 get_path
+            },
+            "/rolling_stock_revision" => {
+                pin_rolling_stock_revision
             }
         }
     },
@@ -75,6 +92,7 @@ editoast_common::schemas! {
     TrainScheduleBase,
     TrainScheduleForm,
     TrainScheduleResult,
+    RollingStockRevisionPinForm,
     BatchDeletionRequest,
     SimulationSummaryResult,
     InfraIdQueryParam,
@@ -97,6 +115,15 @@ pub enum TrainScheduleError {
     #[error("Invalid query params '{message}'")]
     #[editoast_error(status = 400)]
     InvalidQueryParams { message: String },
+    #[error("Rolling stock revision '{rolling_stock_revision_id}' could not be found")]
+    #[editoast_error(status = 404)]
+    RollingStockRevisionNotFound { rolling_stock_revision_id: i64 },
+    #[error("Rolling stock revision '{rolling_stock_revision_id}' isn't a revision of rolling stock '{rolling_stock_name}'")]
+    #[editoast_error(status = 400)]
+    RollingStockRevisionMismatch {
+        rolling_stock_revision_id: i64,
+        rolling_stock_name: String,
+    },
 }
 
 #[derive(IntoParams, Deserialize)]
@@ -109,6 +136,10 @@ struct TrainScheduleIdParam {
 pub struct TrainScheduleResult {
     id: i64,
     timetable_id: i64,
+    /// The cadence which generated the train, if any
+    cadence_id: Option<i64>,
+    /// The rolling stock revision the train is pinned to, if any
+    rolling_stock_revision_id: Option<i64>,
     #[serde(flatten)]
     pub train_schedule: TrainScheduleBase,
 }
@@ -118,6 +149,8 @@ impl From<TrainSchedule> for TrainScheduleResult {
         Self {
             id: value.id,
             timetable_id: value.timetable_id,
+            cadence_id: value.cadence_id,
+            rolling_stock_revision_id: value.rolling_stock_revision_id,
             train_schedule: TrainScheduleBase {
                 train_name: value.train_name,
                 labels: value.labels.into_iter().flatten().collect(),
@@ -186,10 +219,15 @@ struct BatchDeletionRequest {
 #[get("")]
 async fn get(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     train_schedule_id: Path<TrainScheduleIdParam>,
 ) -> Result<Json<TrainScheduleResult>> {
     let train_schedule_id = train_schedule_id.id;
     let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check_train_schedules(conn, &[train_schedule_id], Role::Viewer)
+        .await?;
 
     let train_schedule = TrainSchedule::retrieve_or_fail(conn, train_schedule_id, || {
         TrainScheduleError::NotFound { train_schedule_id }
@@ -216,11 +254,16 @@ struct GetBatchParams {
 #[get("")]
 async fn get_batch(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     params: QsQuery<GetBatchParams>,
 ) -> Result<Json<Vec<TrainScheduleResult>>> {
     let conn = &mut db_pool.get().await?;
     let query_props = params.into_inner();
     let train_ids = query_props.ids;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check_train_schedules(conn, &train_ids, Role::Viewer)
+        .await?;
     let trains: Vec<TrainSchedule> =
         TrainSchedule::retrieve_batch_or_fail(conn, train_ids, |missing| {
             TrainScheduleError::BatchTrainScheduleNotFound {
@@ -242,12 +285,17 @@ async fn get_batch(
 #[delete("")]
 async fn delete(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     data: Json<BatchDeletionRequest>,
 ) -> Result<HttpResponse> {
     use crate::modelsv2::DeleteBatch;
 
     let conn = &mut db_pool.get().await?;
     let train_ids = data.into_inner().ids;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check_train_schedules(conn, &train_ids.iter().copied().collect_vec(), Role::Editor)
+        .await?;
     TrainSchedule::delete_batch_or_fail(conn, train_ids, |number| {
         TrainScheduleError::BatchTrainScheduleNotFound { number }
     })
@@ -268,6 +316,7 @@ async fn delete(
 #[put("")]
 async fn put(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     train_schedule_id: Path<TrainScheduleIdParam>,
     data: Json<TrainScheduleForm>,
 ) -> Result<Json<TrainScheduleResult>> {
@@ -275,7 +324,28 @@ async fn put(
     let conn = &mut db_pool.get().await?;
 
     let train_id = train_schedule_id.id;
-    let ts_changeset: TrainScheduleChangeset = data.into_inner().into();
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check_train_schedules(conn, &[train_id], Role::Editor)
+        .await?;
+    let train_schedule =
+        TrainSchedule::retrieve_or_fail(conn, train_id, || TrainScheduleError::NotFound {
+            train_schedule_id: train_id,
+        })
+        .await?;
+    let form = data.into_inner();
+    if let Some(timetable_id) = form.timetable_id {
+        authorizer
+            .check_timetable(conn, timetable_id, Role::Editor)
+            .await?;
+    }
+    // A pinned revision only makes sense for the rolling stock it archives
+    let rolling_stock_changed =
+        form.train_schedule.rolling_stock_name != train_schedule.rolling_stock_name;
+    let mut ts_changeset: TrainScheduleChangeset = form.into();
+    if rolling_stock_changed {
+        ts_changeset = ts_changeset.rolling_stock_revision_id(None);
+    }
 
     let ts_result = ts_changeset
         .update_or_fail(conn, train_id, || TrainScheduleError::NotFound {
@@ -286,6 +356,72 @@ async fn put(
     Ok(Json(ts_result.into()))
 }
 
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+struct RollingStockRevisionPinForm {
+    /// A revision of the rolling stock of the train, or null to simulate the train with its latest version
+    rolling_stock_revision_id: Option<i64>,
+}
+
+/// Pin a train schedule to a revision of its rolling stock
+///
+/// The train is then simulated with the rolling stock as archived by the revision,
+/// whatever its later updates. Changing the rolling stock of the train unpins it.
+#[utoipa::path(
+    tag = "train_schedulev2",
+    params(TrainScheduleIdParam),
+    request_body = RollingStockRevisionPinForm,
+    responses(
+        (status = 200, description = "The pinned train schedule", body = TrainScheduleResult),
+        (status = 404, description = "The train schedule or the revision was not found"),
+    )
+)]
+#[put("")]
+async fn pin_rolling_stock_revision(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    train_schedule_id: Path<TrainScheduleIdParam>,
+    data: Json<RollingStockRevisionPinForm>,
+) -> Result<Json<TrainScheduleResult>> {
+    let conn = &mut db_pool.get().await?;
+
+    let train_schedule_id = train_schedule_id.id;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check_train_schedules(conn, &[train_schedule_id], Role::Editor)
+        .await?;
+    let mut train_schedule = TrainSchedule::retrieve_or_fail(conn, train_schedule_id, || {
+        TrainScheduleError::NotFound { train_schedule_id }
+    })
+    .await?;
+    let rolling_stock_revision_id = data.into_inner().rolling_stock_revision_id;
+    if let Some(rolling_stock_revision_id) = rolling_stock_revision_id {
+        let revision =
+            RollingStockRevision::retrieve_or_fail(conn, rolling_stock_revision_id, || {
+                TrainScheduleError::RollingStockRevisionNotFound {
+                    rolling_stock_revision_id,
+                }
+            })
+            .await?;
+        let rolling_stock_name = train_schedule.rolling_stock_name.clone();
+        let rolling_stock = RollingStockModel::retrieve(conn, rolling_stock_name.clone()).await?;
+        if rolling_stock.map(|rolling_stock| rolling_stock.id) != Some(revision.rolling_stock_id) {
+            return Err(TrainScheduleError::RollingStockRevisionMismatch {
+                rolling_stock_revision_id,
+                rolling_stock_name,
+            }
+            .into());
+        }
+    }
+
+    train_schedule
+        .patch()
+        .rolling_stock_revision_id(rolling_stock_revision_id)
+        .apply(conn)
+        .await?;
+    Ok(Json(train_schedule.into()))
+}
+
 /// Retrieve the space, speed and time curve of a given train
 #[utoipa::path(
     tag = "train_schedulev2",
@@ -297,6 +433,7 @@ async fn put(
 #[get("/simulation")]
 pub async fn simulation(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     redis_client: Data<RedisClient>,
     core_client: Data<CoreClient>,
     train_schedule_id: Path<TrainScheduleIdParam>,
@@ -305,6 +442,13 @@ pub async fn simulation(
     let infra_id = query.into_inner().infra_id;
     let train_schedule_id = train_schedule_id.into_inner().id;
     let conn = &mut db_pool.get().await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check_train_schedules(conn, &[train_schedule_id], Role::Viewer)
+        .await?;
+    authorizer
+        .check(conn, ResourceType::Infra, infra_id, Role::Viewer)
+        .await?;
     let db_pool = db_pool.into_inner();
     let redis_client = redis_client.into_inner();
     let core_client = core_client.into_inner();
@@ -401,8 +545,8 @@ async fn build_simulation_request(
     path: SimulationPath,
 ) -> Result<SimulationRequest> {
     // Get rolling stock
-    let rolling_stock_name = train_schedule.rolling_stock_name.clone();
-    let rolling_stock = RollingStockModel::retrieve(conn, rolling_stock_name.clone())
+    let rolling_stock = train_schedule
+        .rolling_stock(conn)
         .await?
         .expect("Rolling stock should exist since the pathfinding succeeded");
     // Get electrical_profile_set_id
@@ -410,6 +554,11 @@ async fn build_simulation_request(
     let timetable = Timetable::retrieve(conn, timetable_id)
         .await?
         .expect("Timetable should exist since it's a foreign key");
+    // Part of the simulation hash, which must change when the set is edited
+    let electrical_profile_set_version = match timetable.electrical_profile_set_id {
+        Some(id) => ElectricalProfileSet::retrieve_version(conn, id).await?,
+        None => None,
+    };
 
     assert_eq!(path_items_position.len(), train_schedule.path.len());
 
@@ -472,6 +621,7 @@ async fn build_simulation_request(
         options: train_schedule.options.clone(),
         rolling_stock: rolling_stock.into(),
         electrical_profile_set_id: timetable.electrical_profile_set_id,
+        electrical_profile_set_version,
     })
 }
 
@@ -496,7 +646,7 @@ struct SimulationBatchParams {
 
 #[derive(Debug, Serialize, ToSchema)]
 #[serde(tag = "status", rename_all = "snake_case")]
-enum SimulationSummaryResult {
+pub enum SimulationSummaryResult {
     /// Minimal information on a simulation's result
     Success {
         /// Length of a path in mm
@@ -516,6 +666,37 @@ enum SimulationSummaryResult {
     RollingStockNotFound { rolling_stock_name: String },
 }
 
+impl From<SimulationResponse> for SimulationSummaryResult {
+    fn from(response: SimulationResponse) -> Self {
+        match response {
+            SimulationResponse::Success { final_output, .. } => {
+                let report = final_output.report_train;
+                Self::Success {
+                    length: *report.positions.last().unwrap(),
+                    time: *report.times.last().unwrap(),
+                    energy_consumption: report.energy_consumption,
+                }
+            }
+            SimulationResponse::PathfindingFailed { pathfinding_result } => {
+                match pathfinding_result {
+                    PathfindingResult::PathfindingFailed { core_error } => {
+                        Self::PathfindingFailed {
+                            error_type: core_error.get_type().into(),
+                        }
+                    }
+                    PathfindingResult::RollingStockNotFound { rolling_stock_name } => {
+                        Self::RollingStockNotFound { rolling_stock_name }
+                    }
+                    _ => Self::PathfindingNotFound,
+                }
+            }
+            SimulationResponse::SimulationFailed { core_error } => Self::SimulationFailed {
+                error_type: core_error.get_type().into(),
+            },
+        }
+    }
+}
+
 /// Associate each train id with its simulation summary response
 /// If the simulation fails, it associates the reason: pathfinding failed or running time failed
 #[utoipa::path(
@@ -531,10 +712,115 @@ enum SimulationSummaryResult {
 #[get("/simulation_summary")]
 pub async fn simulation_summary(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     redis_client: Data<RedisClient>,
     req: HttpRequest,
     core: Data<CoreClient>,
 ) -> Result<Json<HashMap<i64, SimulationSummaryResult>>> {
+    let conn = &mut db_pool.get().await?;
+    let (infra, trains) = retrieve_simulation_batch(conn, &req).await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check_train_schedules(
+            conn,
+            &trains.iter().map(|train| train.id).collect_vec(),
+            Role::Viewer,
+        )
+        .await?;
+    authorizer
+        .check(conn, ResourceType::Infra, infra.id, Role::Viewer)
+        .await?;
+    let db_pool = db_pool.into_inner();
+    let redis_client = redis_client.into_inner();
+    let core = core.into_inner();
+
+    let simulations = train_simulation_batch(db_pool, redis_client, core, &trains, &infra).await?;
+
+    // Transform simulations to simulation summary
+    let simulation_summaries = trains
+        .iter()
+        .zip(simulations)
+        .map(|(train, sim)| (train.id, sim.into()))
+        .collect();
+
+    Ok(Json(simulation_summaries))
+}
+
+#[derive(Debug, Serialize)]
+struct SimulationSummaryEvent {
+    train_id: i64,
+    summary: SimulationSummaryResult,
+}
+
+#[derive(Debug, Serialize)]
+struct SimulationErrorEvent {
+    train_id: i64,
+    error: InternalError,
+}
+
+/// Stream the simulation summary of each train as server-sent events, as soon as it is computed
+///
+/// Events:
+/// - `summary`: the simulation summary of a train
+/// - `error`: the simulation of a train could not be computed
+/// - `end`: all the trains were simulated
+#[utoipa::path(
+    tag = "train_schedulev2",
+    params(
+        ("infra" = i64, Query, description = "The infra id"),
+        ("ids" = Vec<i64>, Query, description = "Ids of train schedule"),
+    ),
+    responses(
+        (status = 200, description = "A `text/event-stream` of the simulation summaries", content_type = "text/event-stream", body = String),
+    ),
+)]
+#[get("/simulation_summary/stream")]
+async fn simulation_summary_stream(
+    db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
+    redis_client: Data<RedisClient>,
+    req: HttpRequest,
+    core: Data<CoreClient>,
+) -> Result<HttpResponse> {
+    let conn = &mut db_pool.get().await?;
+    let (infra, trains) = retrieve_simulation_batch(conn, &req).await?;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check_train_schedules(
+            conn,
+            &trains.iter().map(|train| train.id).collect_vec(),
+            Role::Viewer,
+        )
+        .await?;
+    authorizer
+        .check(conn, ResourceType::Infra, infra.id, Role::Viewer)
+        .await?;
+    let events = train_simulation_stream(
+        db_pool.into_inner(),
+        redis_client.into_inner(),
+        core.into_inner(),
+        trains,
+        infra,
+    )
+    .map(|(train_id, response)| match response {
+        Ok(response) => Event::new(
+            "summary",
+            &SimulationSummaryEvent {
+                train_id,
+                summary: response.into(),
+            },
+        ),
+        Err(error) => Event::new("error", &SimulationErrorEvent { train_id, error }),
+    })
+    .chain(stream::once(async { Event::new("end", &()) }));
+    Ok(sse::stream_events(events))
+}
+
+/// Retrieves the infra and the trains given in the query of a simulation batch request
+async fn retrieve_simulation_batch(
+    conn: &mut DbConnection,
+    req: &HttpRequest,
+) -> Result<(Infra, Vec<TrainSchedule>)> {
     // TODO: Stop using serde_qs and move the query params to the body
     let config = Config::new(5, false);
     let query_props: SimulationBatchParams =
@@ -544,61 +830,17 @@ pub async fn simulation_summary(
             }
         })?;
     let infra_id = query_props.infra;
-    let conn = &mut db_pool.clone().get().await?;
-    let db_pool = db_pool.into_inner();
-    let redis_client = redis_client.into_inner();
-    let core = core.into_inner();
-
     let infra = Infra::retrieve_or_fail(conn, infra_id, || TrainScheduleError::InfraNotFound {
         infra_id,
     })
     .await?;
-    let train_ids = query_props.ids;
-    let trains: Vec<TrainSchedule> =
-        TrainSchedule::retrieve_batch_or_fail(conn, train_ids, |missing| {
-            TrainScheduleError::BatchTrainScheduleNotFound {
-                number: missing.len(),
-            }
-        })
-        .await?;
-
-    let simulations = train_simulation_batch(db_pool, redis_client, core, &trains, &infra).await?;
-
-    // Transform simulations to simulation summary
-    let mut simulation_summaries = HashMap::new();
-    for (train, sim) in trains.iter().zip(simulations) {
-        let simulation_summary_result = match sim {
-            SimulationResponse::Success { final_output, .. } => {
-                let report = final_output.report_train;
-                SimulationSummaryResult::Success {
-                    length: *report.positions.last().unwrap(),
-                    time: *report.times.last().unwrap(),
-                    energy_consumption: report.energy_consumption,
-                }
-            }
-            SimulationResponse::PathfindingFailed { pathfinding_result } => {
-                match pathfinding_result {
-                    PathfindingResult::PathfindingFailed { core_error } => {
-                        SimulationSummaryResult::PathfindingFailed {
-                            error_type: core_error.get_type().into(),
-                        }
-                    }
-                    PathfindingResult::RollingStockNotFound { rolling_stock_name } => {
-                        SimulationSummaryResult::RollingStockNotFound { rolling_stock_name }
-                    }
-                    _ => SimulationSummaryResult::PathfindingNotFound,
-                }
-            }
-            SimulationResponse::SimulationFailed { core_error } => {
-                SimulationSummaryResult::SimulationFailed {
-                    error_type: core_error.get_type().into(),
-                }
-            }
-        };
-        simulation_summaries.insert(train.id, simulation_summary_result);
-    }
-
-    Ok(Json(simulation_summaries))
+    let trains = TrainSchedule::retrieve_batch_or_fail(conn, query_props.ids, |missing| {
+        TrainScheduleError::BatchTrainScheduleNotFound {
+            number: missing.len(),
+        }
+    })
+    .await?;
+    Ok((infra, trains))
 }
 
 /// Compute train simulation in batch given a list of train schedules.
@@ -628,6 +870,33 @@ pub async fn train_simulation_batch(
     simulations.into_iter().collect()
 }
 
+/// Compute train simulations, yielding each of them along with its train id as soon as it completes
+///
+/// At most [SIMULATION_STREAM_CONCURRENCY] simulations are computed at the same time.
+pub fn train_simulation_stream(
+    db_pool: Arc<DbConnectionPool>,
+    redis_client: Arc<RedisClient>,
+    core_client: Arc<CoreClient>,
+    train_schedules: Vec<TrainSchedule>,
+    infra: Infra,
+) -> impl Stream<Item = (i64, Result<SimulationResponse>)> {
+    let infra = Arc::new(infra);
+    stream::iter(train_schedules)
+        .map(move |train_schedule| {
+            let db_pool = db_pool.clone();
+            let redis_client = redis_client.clone();
+            let core_client = core_client.clone();
+            let infra = infra.clone();
+            async move {
+                let response =
+                    train_simulation(db_pool, redis_client, core_client, &train_schedule, &infra)
+                        .await;
+                (train_schedule.id, response)
+            }
+        })
+        .buffer_unordered(SIMULATION_STREAM_CONCURRENCY)
+}
+
 #[derive(Debug, Default, Clone, Serialize, Deserialize, IntoParams, ToSchema)]
 pub struct InfraIdQueryParam {
     infra_id: i64,
@@ -645,6 +914,7 @@ pub struct InfraIdQueryParam {
 #[get("")]
 async fn get_path(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     redis_client: Data<RedisClient>,
     core: Data<CoreClient>,
     train_schedule_id: Path<TrainScheduleIdParam>,
@@ -657,6 +927,13 @@ async fn get_path(
     let inner_query = query.into_inner();
     let infra_id = inner_query.infra_id;
     let train_schedule_id = train_schedule_id.id;
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check_train_schedules(conn, &[train_schedule_id], Role::Viewer)
+        .await?;
+    authorizer
+        .check(conn, ResourceType::Infra, infra_id, Role::Viewer)
+        .await?;
 
     let infra = Infra::retrieve_or_fail(conn, infra_id, || PathfindingError::InfraNotFound {
         infra_id,
@@ -682,6 +959,7 @@ mod tests {
     use std::sync::Arc;
 
     use super::*;
+    use crate::assert_response_error_type_match;
     use crate::fixtures::tests::db_pool;
     use crate::fixtures::tests::make_simple_train_schedule_v2;
     use crate::fixtures::tests::named_fast_rolling_stock;
@@ -832,6 +1110,33 @@ mod tests {
         assert_eq!(response.train_schedule.rolling_stock_name, rs_name)
     }
 
+    #[rstest]
+    async fn train_schedule_pin_unknown_revision(
+        #[future] train_schedule_v2: TrainScheduleV2FixtureSet,
+    ) {
+        let TrainScheduleV2FixtureSet { train_schedule, .. } = train_schedule_v2.await;
+        let service = create_test_service().await;
+        let request = TestRequest::put()
+            .uri(
+                format!(
+                    "/v2/train_schedule/{}/rolling_stock_revision",
+                    train_schedule.id()
+                )
+                .as_str(),
+            )
+            .set_json(json!({ "rolling_stock_revision_id": -1 }))
+            .to_request();
+
+        let response = call_service(&service, request).await;
+
+        assert_response_error_type_match!(
+            response,
+            TrainScheduleError::RollingStockRevisionNotFound {
+                rolling_stock_revision_id: -1
+            }
+        );
+    }
+
     #[rstest]
     #[ignore] // TODO: This test should be rewritten using mocks
     async fn train_schedule_simulation(
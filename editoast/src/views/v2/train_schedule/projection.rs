@@ -31,9 +31,12 @@ use crate::core::AsCoreRequest;
 use crate::core::CoreClient;
 use crate::error::Result;
 use crate::modelsv2::infra::Infra;
+use crate::modelsv2::resource_grant::ResourceType;
+use crate::modelsv2::resource_grant::Role;
 use crate::modelsv2::train_schedule::TrainSchedule;
 use crate::modelsv2::Retrieve;
 use crate::modelsv2::RetrieveBatch;
+use crate::views::authz::Authentication;
 use crate::views::v2::path::pathfinding_from_train;
 use crate::views::v2::path::projection::PathProjection;
 use crate::views::v2::path::projection::TrackLocationFromPath;
@@ -124,6 +127,7 @@ struct CachedProjectPathTrainResult {
 #[post("/project_path")]
 async fn project_path(
     db_pool: Data<DbConnectionPool>,
+    authentication: Authentication,
     redis_client: Data<RedisClient>,
     core_client: Data<CoreClient>,
     params: QsQuery<SimulationBatchParams>,
@@ -143,6 +147,13 @@ async fn project_path(
     let conn = &mut db_pool.clone().get().await?;
     let mut redis_conn = redis_client.get_connection().await?;
     let core = core_client.into_inner();
+    let authorizer = authentication.authorizer(conn).await?;
+    authorizer
+        .check_train_schedules(conn, &train_ids, Role::Viewer)
+        .await?;
+    authorizer
+        .check(conn, ResourceType::Infra, infra_id, Role::Viewer)
+        .await?;
 
     let infra = Infra::retrieve_or_fail(conn, infra_id, || TrainScheduleError::InfraNotFound {
         infra_id,
@@ -283,18 +294,24 @@ async fn project_path(
                 .clone()
         })
         .collect();
-    let (rs, missing): (Vec<_>, _) =
+    let (rs, missing): (Vec<_>, HashSet<_>) =
         RollingStockModel::retrieve_batch(conn, rolling_stock_names).await?;
-    assert!(missing.is_empty(), "Missing rolling stock models");
-    let rolling_stock_length: HashMap<_, _> =
+    let mut rolling_stock_length: HashMap<_, _> =
         rs.into_iter().map(|rs| (rs.name, rs.length)).collect();
+    // The names which aren't rolling stocks are consists
+    for name in missing {
+        if let Some(consist) = RollingStockModel::retrieve_or_derive(conn, name.clone()).await? {
+            rolling_stock_length.insert(name, consist.length);
+        }
+    }
 
     // 4.2 Build the projection response
     for (id, cached) in hit_cache {
         let train = train_map.get(&id).expect("Train not found");
-        let length = rolling_stock_length
-            .get(&train.rolling_stock_name)
-            .expect("Rolling stock length not found");
+        // The rolling stock was deleted since the train was simulated
+        let Some(length) = rolling_stock_length.get(&train.rolling_stock_name) else {
+            continue;
+        };
 
         project_path_result.insert(
             id,
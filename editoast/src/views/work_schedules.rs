@@ -1,6 +1,14 @@
+use std::ops::DerefMut as _;
+
+use actix_web::delete;
+use actix_web::get;
+use actix_web::patch;
 use actix_web::post;
 use actix_web::web::Data;
 use actix_web::web::Json;
+use actix_web::web::Path;
+use actix_web::web::Query;
+use actix_web::HttpResponse;
 use chrono::NaiveDateTime;
 use chrono::Utc;
 use derivative::Derivative;
@@ -10,30 +18,46 @@ use serde::Deserialize;
 use serde::Serialize;
 use std::result::Result as StdResult;
 use thiserror::Error;
+use utoipa::IntoParams;
 use utoipa::ToSchema;
 
 use crate::error::InternalError;
 use crate::error::Result;
+use crate::modelsv2::prelude::*;
 use crate::modelsv2::work_schedules::WorkSchedule;
 use crate::modelsv2::work_schedules::WorkScheduleGroup;
 use crate::modelsv2::work_schedules::WorkScheduleType;
-use crate::modelsv2::Changeset;
-use crate::modelsv2::Create;
-use crate::modelsv2::CreateBatch;
 use crate::modelsv2::DbConnectionPool;
-use crate::modelsv2::Model;
+use crate::views::pagination::PaginatedList as _;
+use crate::views::pagination::PaginationQueryParam;
+use crate::views::pagination::PaginationStats;
 use editoast_schemas::infra::TrackRange;
 
 crate::routes! {
     "/work_schedules" => {
         create,
+        list,
+        "/groups" => {
+            list_groups,
+            "/{work_schedule_group_id}" => {
+                delete_group,
+            },
+        },
+        "/{work_schedule_id}" => {
+            get,
+            patch,
+            delete,
+        },
     }
 }
 
 editoast_common::schemas! {
+    WorkSchedule,
+    WorkScheduleGroup,
     WorkScheduleCreateForm,
     WorkScheduleCreateResponse,
     WorkScheduleItemForm,
+    WorkSchedulePatchForm,
 }
 
 #[derive(Debug, Error, EditoastError)]
@@ -42,6 +66,18 @@ pub enum WorkScheduleError {
     #[error("Name '{name}' already used")]
     #[editoast_error(status = 400)]
     NameAlreadyUsed { name: String },
+    #[error("Work schedule '{work_schedule_id}' could not be found")]
+    #[editoast_error(status = 404)]
+    NotFound { work_schedule_id: i64 },
+    #[error("Work schedule group '{work_schedule_group_id}' could not be found")]
+    #[editoast_error(status = 404)]
+    GroupNotFound { work_schedule_group_id: i64 },
+    #[error("The work schedule start date '{start_date_time}' must be before the end date '{end_date_time}'")]
+    #[editoast_error(status = 400)]
+    StartDateAfterEndDate {
+        start_date_time: NaiveDateTime,
+        end_date_time: NaiveDateTime,
+    },
 }
 
 pub fn map_diesel_error(e: InternalError, name: impl AsRef<str>) -> InternalError {
@@ -167,6 +203,262 @@ async fn create(
     }))
 }
 
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct WorkScheduleFilterParams {
+    /// Only return the work schedules of this group
+    work_schedule_group_id: Option<i64>,
+    /// Only return the work schedules still active after this date
+    start_date_time: Option<NaiveDateTime>,
+    /// Only return the work schedules starting before this date
+    end_date_time: Option<NaiveDateTime>,
+    /// Only return the work schedules of this type
+    #[param(inline)]
+    work_schedule_type: Option<WorkScheduleType>,
+    /// Only return the work schedules impacting this track section
+    track: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+struct WorkScheduleListResponse {
+    results: Vec<WorkSchedule>,
+    #[serde(flatten)]
+    stats: PaginationStats,
+}
+
+/// Returns a paginated list of work schedules
+///
+/// The work schedules can be filtered by group, type, impacted track section and by
+/// a time window: only the work schedules overlapping `[start_date_time, end_date_time]`
+/// are returned.
+#[utoipa::path(
+    tag = "work_schedules",
+    params(PaginationQueryParam, WorkScheduleFilterParams),
+    responses(
+        (status = 200, body = inline(WorkScheduleListResponse), description = "The list of work schedules"),
+    )
+)]
+#[get("")]
+async fn list(
+    db_pool: Data<DbConnectionPool>,
+    Query(pagination_params): Query<PaginationQueryParam>,
+    Query(filter_params): Query<WorkScheduleFilterParams>,
+) -> Result<Json<WorkScheduleListResponse>> {
+    let WorkScheduleFilterParams {
+        work_schedule_group_id,
+        start_date_time,
+        end_date_time,
+        work_schedule_type,
+        track,
+    } = filter_params;
+
+    let mut settings = pagination_params
+        .validate(1000)?
+        .warn_page_size(100)
+        .into_selection_settings()
+        .order_by(|| WorkSchedule::START_DATE_TIME.asc())
+        .order_by(|| WorkSchedule::ID.asc());
+    if let Some(group_id) = work_schedule_group_id {
+        settings = settings.filter(move || WorkSchedule::WORK_SCHEDULE_GROUP_ID.eq(group_id));
+    }
+    if let Some(start_date_time) = start_date_time {
+        settings = settings.filter(move || WorkSchedule::ends_after(start_date_time));
+    }
+    if let Some(end_date_time) = end_date_time {
+        settings = settings.filter(move || WorkSchedule::starts_before(end_date_time));
+    }
+    if let Some(work_schedule_type) = work_schedule_type {
+        settings = settings.filter(move || WorkSchedule::WORK_SCHEDULE_TYPE.eq(work_schedule_type));
+    }
+    if let Some(track) = track {
+        settings = settings.filter(move || WorkSchedule::on_track_section(track.clone()));
+    }
+
+    let (results, stats) =
+        WorkSchedule::list_paginated(db_pool.get().await?.deref_mut(), settings).await?;
+    Ok(Json(WorkScheduleListResponse { results, stats }))
+}
+
+#[derive(IntoParams, Deserialize)]
+struct WorkScheduleIdParam {
+    /// The id of a work schedule
+    work_schedule_id: i64,
+}
+
+/// Retrieve a work schedule
+#[utoipa::path(
+    tag = "work_schedules",
+    params(WorkScheduleIdParam),
+    responses(
+        (status = 200, body = WorkSchedule, description = "The requested work schedule"),
+        (status = 404, body = InternalError, description = "The requested work schedule was not found"),
+    )
+)]
+#[get("")]
+async fn get(
+    db_pool: Data<DbConnectionPool>,
+    path: Path<WorkScheduleIdParam>,
+) -> Result<Json<WorkSchedule>> {
+    let work_schedule_id = path.work_schedule_id;
+    let conn = &mut db_pool.get().await?;
+    let work_schedule = WorkSchedule::retrieve_or_fail(conn, work_schedule_id, || {
+        WorkScheduleError::NotFound { work_schedule_id }
+    })
+    .await?;
+    Ok(Json(work_schedule))
+}
+
+/// Patch form for a work schedule
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+struct WorkSchedulePatchForm {
+    start_date_time: Option<NaiveDateTime>,
+    end_date_time: Option<NaiveDateTime>,
+    track_ranges: Option<Vec<TrackRange>>,
+    obj_id: Option<String>,
+    #[schema(inline)]
+    work_schedule_type: Option<WorkScheduleType>,
+}
+
+/// Update a work schedule
+#[utoipa::path(
+    tag = "work_schedules",
+    params(WorkScheduleIdParam),
+    request_body = WorkSchedulePatchForm,
+    responses(
+        (status = 200, body = WorkSchedule, description = "The updated work schedule"),
+        (status = 404, body = InternalError, description = "The requested work schedule was not found"),
+    )
+)]
+#[patch("")]
+async fn patch(
+    db_pool: Data<DbConnectionPool>,
+    path: Path<WorkScheduleIdParam>,
+    data: Json<WorkSchedulePatchForm>,
+) -> Result<Json<WorkSchedule>> {
+    let work_schedule_id = path.work_schedule_id;
+    let WorkSchedulePatchForm {
+        start_date_time,
+        end_date_time,
+        track_ranges,
+        obj_id,
+        work_schedule_type,
+    } = data.into_inner();
+    let conn = &mut db_pool.get().await?;
+    let mut work_schedule = WorkSchedule::retrieve_or_fail(conn, work_schedule_id, || {
+        WorkScheduleError::NotFound { work_schedule_id }
+    })
+    .await?;
+
+    // The dates must be checked against the stored values as only one of them may be patched
+    let start_date_time = start_date_time.unwrap_or(work_schedule.start_date_time);
+    let end_date_time = end_date_time.unwrap_or(work_schedule.end_date_time);
+    if start_date_time >= end_date_time {
+        return Err(WorkScheduleError::StartDateAfterEndDate {
+            start_date_time,
+            end_date_time,
+        }
+        .into());
+    }
+
+    work_schedule
+        .patch()
+        .start_date_time(start_date_time)
+        .end_date_time(end_date_time)
+        .flat_track_ranges(track_ranges)
+        .flat_obj_id(obj_id)
+        .flat_work_schedule_type(work_schedule_type)
+        .apply(conn)
+        .await?;
+    Ok(Json(work_schedule))
+}
+
+/// Delete a work schedule
+#[utoipa::path(
+    tag = "work_schedules",
+    params(WorkScheduleIdParam),
+    responses(
+        (status = 204, description = "The work schedule was deleted successfully"),
+        (status = 404, body = InternalError, description = "The requested work schedule was not found"),
+    )
+)]
+#[delete("")]
+async fn delete(
+    db_pool: Data<DbConnectionPool>,
+    path: Path<WorkScheduleIdParam>,
+) -> Result<HttpResponse> {
+    let work_schedule_id = path.work_schedule_id;
+    let conn = &mut db_pool.get().await?;
+    WorkSchedule::delete_static_or_fail(conn, work_schedule_id, || WorkScheduleError::NotFound {
+        work_schedule_id,
+    })
+    .await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Serialize, ToSchema)]
+struct WorkScheduleGroupListResponse {
+    results: Vec<WorkScheduleGroup>,
+    #[serde(flatten)]
+    stats: PaginationStats,
+}
+
+/// Returns a paginated list of work schedule groups
+#[utoipa::path(
+    tag = "work_schedules",
+    params(PaginationQueryParam),
+    responses(
+        (status = 200, body = inline(WorkScheduleGroupListResponse), description = "The list of work schedule groups"),
+    )
+)]
+#[get("")]
+async fn list_groups(
+    db_pool: Data<DbConnectionPool>,
+    Query(pagination_params): Query<PaginationQueryParam>,
+) -> Result<Json<WorkScheduleGroupListResponse>> {
+    let settings = pagination_params
+        .validate(1000)?
+        .warn_page_size(100)
+        .into_selection_settings()
+        .order_by(|| WorkScheduleGroup::CREATION_DATE.desc())
+        .order_by(|| WorkScheduleGroup::ID.desc());
+    let (results, stats) =
+        WorkScheduleGroup::list_paginated(db_pool.get().await?.deref_mut(), settings).await?;
+    Ok(Json(WorkScheduleGroupListResponse { results, stats }))
+}
+
+#[derive(IntoParams, Deserialize)]
+struct WorkScheduleGroupIdParam {
+    /// The id of a work schedule group
+    work_schedule_group_id: i64,
+}
+
+/// Delete a work schedule group and all its work schedules
+#[utoipa::path(
+    tag = "work_schedules",
+    params(WorkScheduleGroupIdParam),
+    responses(
+        (status = 204, description = "The work schedule group was deleted successfully"),
+        (status = 404, body = InternalError, description = "The requested work schedule group was not found"),
+    )
+)]
+#[delete("")]
+async fn delete_group(
+    db_pool: Data<DbConnectionPool>,
+    path: Path<WorkScheduleGroupIdParam>,
+) -> Result<HttpResponse> {
+    let work_schedule_group_id = path.work_schedule_group_id;
+    let conn = &mut db_pool.get().await?;
+    // The work schedules of the group are deleted by the `ON DELETE CASCADE` constraint
+    WorkScheduleGroup::delete_static_or_fail(conn, work_schedule_group_id, || {
+        WorkScheduleError::GroupNotFound {
+            work_schedule_group_id,
+        }
+    })
+    .await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
 #[cfg(test)]
 pub mod test {
     use actix_web::http::StatusCode;
@@ -289,4 +581,184 @@ pub mod test {
             }
         );
     }
+
+    async fn create_work_schedule_group(
+        db_pool: Arc<DbConnectionPool>,
+        name: &str,
+    ) -> (TestFixture<WorkScheduleGroup>, Vec<WorkSchedule>) {
+        let conn = &mut db_pool.get().await.unwrap();
+        let group = WorkScheduleGroup::changeset()
+            .name(name.to_string())
+            .creation_date(Utc::now().naive_utc())
+            .create(conn)
+            .await
+            .unwrap();
+        let work_schedules = [
+            (
+                "2024-01-01T08:00:00",
+                "2024-01-01T09:00:00",
+                "TA0",
+                WorkScheduleType::Catenary,
+            ),
+            (
+                "2024-01-01T10:00:00",
+                "2024-01-01T12:00:00",
+                "TA1",
+                WorkScheduleType::Track,
+            ),
+        ]
+        .into_iter()
+        .map(|(start, end, track, work_schedule_type)| {
+            WorkSchedule::changeset()
+                .start_date_time(start.parse().unwrap())
+                .end_date_time(end.parse().unwrap())
+                .track_ranges(vec![TrackRange::new(track, 0., 100.)])
+                .obj_id(format!("{track}_work"))
+                .work_schedule_type(work_schedule_type)
+                .work_schedule_group_id(group.id)
+        });
+        let work_schedules: Vec<_> = WorkSchedule::create_batch(conn, work_schedules)
+            .await
+            .unwrap();
+        (TestFixture::new(group, db_pool.clone()), work_schedules)
+    }
+
+    #[rstest]
+    async fn work_schedule_list_filtered(db_pool: Arc<DbConnectionPool>) {
+        // GIVEN
+        let app = create_test_service().await;
+        let (group, work_schedules) =
+            create_work_schedule_group(db_pool.clone(), "work schedule list filtered").await;
+        let group_id = group.id();
+
+        // WHEN
+        let req = TestRequest::get()
+            .uri(&format!(
+                "/work_schedules?work_schedule_group_id={group_id}&start_date_time=2024-01-01T08:30:00&end_date_time=2024-01-01T20:00:00&track=TA1"
+            ))
+            .to_request();
+        let response = call_service(&app, req).await;
+
+        // THEN
+        assert_eq!(response.status(), StatusCode::OK);
+        let response: serde_json::Value = read_body_json(response).await;
+        let results = response["results"].as_array().unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0]["id"], work_schedules[1].id);
+        assert_eq!(response["count"], 1);
+    }
+
+    #[rstest]
+    async fn work_schedule_get(db_pool: Arc<DbConnectionPool>) {
+        // GIVEN
+        let app = create_test_service().await;
+        let (_group, work_schedules) =
+            create_work_schedule_group(db_pool.clone(), "work schedule get").await;
+
+        // WHEN
+        let req = TestRequest::get()
+            .uri(&format!("/work_schedules/{}", work_schedules[0].id))
+            .to_request();
+        let response = call_service(&app, req).await;
+
+        // THEN
+        assert_eq!(response.status(), StatusCode::OK);
+        let work_schedule: WorkSchedule = read_body_json(response).await;
+        assert_eq!(work_schedule.obj_id, "TA0_work");
+    }
+
+    #[rstest]
+    async fn work_schedule_patch(db_pool: Arc<DbConnectionPool>) {
+        // GIVEN
+        let app = create_test_service().await;
+        let (_group, work_schedules) =
+            create_work_schedule_group(db_pool.clone(), "work schedule patch").await;
+
+        // WHEN
+        let req = TestRequest::patch()
+            .uri(&format!("/work_schedules/{}", work_schedules[0].id))
+            .set_json(json!({
+                "end_date_time": "2024-01-01T11:00:00",
+                "work_schedule_type": "TRACK"
+            }))
+            .to_request();
+        let response = call_service(&app, req).await;
+
+        // THEN
+        assert_eq!(response.status(), StatusCode::OK);
+        let work_schedule: WorkSchedule = read_body_json(response).await;
+        assert_eq!(
+            work_schedule.end_date_time,
+            "2024-01-01T11:00:00".parse::<NaiveDateTime>().unwrap()
+        );
+        assert_eq!(work_schedule.work_schedule_type, WorkScheduleType::Track);
+        assert_eq!(work_schedule.obj_id, "TA0_work");
+    }
+
+    #[rstest]
+    async fn work_schedule_patch_fail_end_date_before_start_date(db_pool: Arc<DbConnectionPool>) {
+        // GIVEN
+        let app = create_test_service().await;
+        let (_group, work_schedules) =
+            create_work_schedule_group(db_pool.clone(), "work schedule patch invalid dates").await;
+
+        // WHEN
+        let req = TestRequest::patch()
+            .uri(&format!("/work_schedules/{}", work_schedules[0].id))
+            .set_json(json!({ "end_date_time": "2024-01-01T07:00:00" }))
+            .to_request();
+        let response = call_service(&app, req).await;
+
+        // THEN
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_response_error_type_match!(
+            response,
+            WorkScheduleError::StartDateAfterEndDate {
+                start_date_time: work_schedules[0].start_date_time,
+                end_date_time: "2024-01-01T07:00:00".parse().unwrap(),
+            }
+        );
+    }
+
+    #[rstest]
+    async fn work_schedule_delete(db_pool: Arc<DbConnectionPool>) {
+        // GIVEN
+        let app = create_test_service().await;
+        let (_group, work_schedules) =
+            create_work_schedule_group(db_pool.clone(), "work schedule delete").await;
+        let work_schedule_id = work_schedules[0].id;
+
+        // WHEN
+        let req = TestRequest::delete()
+            .uri(&format!("/work_schedules/{work_schedule_id}"))
+            .to_request();
+        let response = call_service(&app, req).await;
+
+        // THEN
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let conn = &mut db_pool.get().await.unwrap();
+        assert!(!WorkSchedule::exists(conn, work_schedule_id).await.unwrap());
+    }
+
+    #[rstest]
+    async fn work_schedule_group_delete_cascades(db_pool: Arc<DbConnectionPool>) {
+        // GIVEN
+        let app = create_test_service().await;
+        let (group, work_schedules) =
+            create_work_schedule_group(db_pool.clone(), "work schedule group delete").await;
+
+        // WHEN
+        let req = TestRequest::delete()
+            .uri(&format!("/work_schedules/groups/{}", group.id()))
+            .to_request();
+        let response = call_service(&app, req).await;
+
+        // THEN
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let conn = &mut db_pool.get().await.unwrap();
+        assert!(!WorkScheduleGroup::exists(conn, group.id()).await.unwrap());
+        for work_schedule in work_schedules {
+            assert!(!WorkSchedule::exists(conn, work_schedule.id).await.unwrap());
+        }
+    }
 }